@@ -0,0 +1,28 @@
+#include <stdio.h>
+#include <stdbool.h>
+#include <stdlib.h>
+
+#define OSHO_RUNTIME_ERROR 70
+
+static long osho_div(long a, long b) {
+    return b == -1 ? (long)(0UL - (unsigned long)a) : a / b;
+}
+
+static long osho_mod(long a, long b) {
+    return b == -1 ? 0 : a % b;
+}
+
+static void osho_arith_error(const char *message) {
+    fprintf(stderr, "%s\n", message);
+    exit(OSHO_RUNTIME_ERROR);
+}
+
+int main() {
+long zero = 0;
+printf("%ld\n", (long)1);
+if (zero == 0) osho_arith_error("Division by zero");
+long _t0 = osho_div(7, zero);
+printf("%ld\n", (long)_t0);
+
+return 0;
+}
\ No newline at end of file
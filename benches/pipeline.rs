@@ -0,0 +1,84 @@
+//! Benchmarks of the compiler's phases over large synthetic programs.
+//!
+//! `cargo bench` times lexing, parsing, analysis and C generation of each
+//! program with criterion, which also reports their throughput. Criterion's
+//! own arguments filter the benchmarks and save or compare baselines, as in
+//! `cargo bench --bench pipeline -- data/ --save-baseline before`.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use osho_lang::{Backend, CodeGenerator, Lexer, Parser, SemanticAnalyzer};
+
+/// How many copies of the program's pattern each benchmark has
+const SIZE: usize = 500;
+
+/// Writes the `i`th copy of a program's pattern
+type Pattern = fn(usize) -> String;
+
+/// A function with loops and arithmetic, and a call to it
+fn functions(i: usize) -> String {
+    format!(
+        "fn f{i}(a: int, b: int) -> int {{
+    let mut total = 0
+    for (let mut j = 0; j < a; j++) {{
+        if j % 3 == 0 {{
+            total += j * b
+        }} else {{
+            total -= b - j
+        }}
+    }}
+    while total > 1000 {{
+        total = total / 2
+    }}
+    return total + {i}
+}}
+println(f{i}(10, {i}))
+"
+    )
+}
+
+/// A struct, an array and an interpolated string
+fn data(i: usize) -> String {
+    format!(
+        "struct Point{i} {{ x, y }}
+let p{i} = Point{i} {{ x: {i}, y: {i} * 2 }}
+let xs{i} = [1, 2, 3, p{i}.x]
+let sum{i} = xs{i}[0] + p{i}.y
+let scale{i} = {i}.5 * 2.0
+println(\"point ${{p{i}.x}} ${{sum{i}}} ${{scale{i} > 1.0 && xs{i}[3] != 0}}\")
+"
+    )
+}
+
+fn pipeline(c: &mut Criterion) {
+    let programs: [(&str, Pattern); 2] = [("functions", functions), ("data", data)];
+    for (program, pattern) in programs {
+        let source: String = (0..SIZE).map(pattern).collect();
+        let tokens = Lexer::new(&source).get_tokens().unwrap();
+        let ast = Parser::new(&tokens).parse().unwrap();
+
+        let mut group = c.benchmark_group(program);
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_function("lex", |b| {
+            b.iter(|| Lexer::new(black_box(&source)).get_tokens().unwrap())
+        });
+        group.bench_function("parse", |b| {
+            b.iter(|| Parser::new(black_box(&tokens)).parse().unwrap())
+        });
+        group.bench_function("analyze", |b| {
+            b.iter(|| {
+                SemanticAnalyzer::new()
+                    .analyze_all(black_box(&ast))
+                    .unwrap()
+            })
+        });
+        group.bench_function("generate-c", |b| {
+            b.iter(|| CodeGenerator::new().generate(black_box(&ast)).unwrap())
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, pipeline);
+criterion_main!(benches);
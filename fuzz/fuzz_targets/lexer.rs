@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use osho_lang::Lexer;
+
+fuzz_target!(|source: &str| {
+    let _ = Lexer::try_tokenize(source);
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use osho_lang::{Lexer, Parser};
+
+fuzz_target!(|source: &str| {
+    if let Ok(tokens) = Lexer::try_tokenize(source) {
+        let _ = Parser::try_parse(&tokens);
+    }
+});
@@ -0,0 +1,648 @@
+//! The nodes of a parsed program. The [`Parser`](crate::parser::Parser)
+//! builds an [`Ast`] from them, and every later pass reads or rewrites it.
+
+use std::collections::BTreeSet;
+use std::ops::{Index, IndexMut};
+
+use serde::Serialize;
+
+use crate::lexer::Span;
+use crate::semantic_analyzer::Type;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ASTNode {
+    Program(Vec<NodeId>),
+    /// `let name = value`, or `let mut name = value` for a variable that
+    /// can be assigned to later. `let name: type = value` gives its type.
+    LetDeclaration {
+        name: String,
+        mutable: bool,
+        ty: Option<Type>,
+        value: NodeId,
+        span: Span,
+    },
+    /// `const name = value`, where the value is known at compile time
+    ConstDeclaration {
+        name: String,
+        value: NodeId,
+        span: Span,
+    },
+    /// `struct Name { a, b }`, declaring a struct type and its fields
+    StructDeclaration {
+        name: String,
+        fields: Vec<String>,
+        span: Span,
+    },
+    /// `fn name(a, b) { body }`, or with types `fn name(a: int) -> int`
+    FunctionDeclaration {
+        name: String,
+        params: Vec<Param>,
+        result: Option<Type>,
+        body: NodeId,
+        span: Span,
+    },
+    /// `return value`, or a bare `return` from a function without a value
+    Return {
+        value: Option<NodeId>,
+        span: Span,
+    },
+    /// `name = value`. Its value can be the assignment of another variable,
+    /// as in `a = b = 0`, which gives `a` what was stored in `b`.
+    Assignment {
+        name: String,
+        value: NodeId,
+        span: Span,
+    },
+    /// `name op= value`, such as `x += 1`
+    CompoundAssignment {
+        name: String,
+        op: BinaryOperator,
+        value: NodeId,
+        span: Span,
+    },
+    Increment {
+        name: String,
+        span: Span,
+    },
+    Decrement {
+        name: String,
+        span: Span,
+    },
+    /// `print a, b` or `print(a, b)`, optionally with a format string first
+    /// as in `print "x = {}", x`. `println` ends the output with a newline.
+    Print {
+        format: Option<String>,
+        args: Vec<NodeId>,
+        newline: bool,
+        span: Span,
+    },
+    /// `assert condition, "message"`, which stops the program with a
+    /// runtime error when the condition is falsy. The message is optional.
+    Assert {
+        condition: NodeId,
+        message: Option<String>,
+        span: Span,
+    },
+    /// `input name`, which reads a number from stdin into a variable
+    Input {
+        name: String,
+        span: Span,
+    },
+    /// `array[index] = value`
+    IndexAssignment {
+        array: NodeId,
+        index: NodeId,
+        value: NodeId,
+        span: Span,
+    },
+    /// `object.field = value`
+    FieldAssignment {
+        object: NodeId,
+        field: String,
+        value: NodeId,
+        span: Span,
+    },
+    Block(Vec<NodeId>),
+    If {
+        condition: NodeId,
+        then_branch: NodeId,
+        else_branch: Option<NodeId>,
+        span: Span,
+    },
+    While {
+        condition: NodeId,
+        body: NodeId,
+        span: Span,
+    },
+    /// `do { ... } while condition`, which runs the body before checking the
+    /// condition, so always at least once
+    DoWhile {
+        body: NodeId,
+        condition: NodeId,
+        span: Span,
+    },
+    For {
+        init: Option<NodeId>,
+        condition: Option<NodeId>,
+        step: Option<NodeId>,
+        body: NodeId,
+        span: Span,
+    },
+    /// `for name in range { ... }`, running the body once for each int in
+    /// the range with `name` bound to it
+    ForIn {
+        name: String,
+        range: NodeId,
+        body: NodeId,
+        span: Span,
+    },
+    /// `start..end`, or `start..=end` to include `end`, as iterated by a
+    /// for-in loop. The bounds are evaluated once, before the first iteration.
+    Range {
+        start: NodeId,
+        end: NodeId,
+        inclusive: bool,
+        span: Span,
+    },
+    /// `|x, y: int| body`, a function value whose body is an expression.
+    /// It captures the variables it uses from where it is made.
+    Lambda {
+        params: Vec<Param>,
+        body: NodeId,
+        span: Span,
+    },
+    /// `break`, leaving the innermost loop
+    Break {
+        span: Span,
+    },
+    /// `continue`, skipping to the next iteration of the innermost loop
+    Continue {
+        span: Span,
+    },
+    /// `import "file.osho"`, which the [`modules`](crate::modules) loader
+    /// replaces with the file's declarations
+    Import {
+        path: String,
+        span: Span,
+    },
+    /// `module name { ... }`. Parsing a program replaces modules with their
+    /// declarations, named `name.item`.
+    Module {
+        name: String,
+        items: Vec<ModuleItem>,
+        span: Span,
+    },
+    BinaryOp {
+        left: NodeId,
+        op: BinaryOperator,
+        right: NodeId,
+        span: Span,
+    },
+    UnaryOp {
+        op: UnaryOperator,
+        operand: NodeId,
+        span: Span,
+    },
+    /// `[a, b, c]`
+    ArrayLiteral {
+        elements: Vec<NodeId>,
+        span: Span,
+    },
+    /// `array[index]`
+    Index {
+        array: NodeId,
+        index: NodeId,
+        span: Span,
+    },
+    /// `array[start:end]`, a new array holding the elements from `start` up
+    /// to `end`. Either bound can be left out, meaning the start or the end.
+    Slice {
+        array: NodeId,
+        start: Option<NodeId>,
+        end: Option<NodeId>,
+        span: Span,
+    },
+    /// `name(args)`, a call to a builtin function
+    Call {
+        name: String,
+        args: Vec<NodeId>,
+        span: Span,
+    },
+    /// `{"a": 1, "b": 2}`, a map from string keys to values
+    MapLiteral {
+        entries: Vec<(NodeId, NodeId)>,
+        span: Span,
+    },
+    /// `Name { a: 1, b: 2 }`, a value of a struct type
+    StructLiteral {
+        name: String,
+        fields: Vec<(String, NodeId)>,
+        span: Span,
+    },
+    /// `object.field`
+    FieldAccess {
+        object: NodeId,
+        field: String,
+        span: Span,
+    },
+    /// `condition ? then_value : else_value`, which evaluates only the chosen
+    /// branch
+    Conditional {
+        condition: NodeId,
+        then_value: NodeId,
+        else_value: NodeId,
+        span: Span,
+    },
+    Int(i64),
+    Number(f64),
+    Bool(bool),
+    /// A string literal, `"text"`
+    Str(String),
+    /// A character literal, `'a'`
+    Char(char),
+    /// `"text ${value} text"`, a string with values formatted into it. There
+    /// is one more piece of text than there are values.
+    Interpolation {
+        pieces: Vec<String>,
+        values: Vec<NodeId>,
+        span: Span,
+    },
+    Identifier {
+        name: String,
+        span: Span,
+    },
+}
+
+impl ASTNode {
+    /// Whether the node computes a value rather than being a statement
+    pub fn is_expression(&self) -> bool {
+        matches!(
+            self,
+            ASTNode::Int(_)
+                | ASTNode::Number(_)
+                | ASTNode::Bool(_)
+                | ASTNode::Str(_)
+                | ASTNode::Char(_)
+                | ASTNode::Interpolation { .. }
+                | ASTNode::Identifier { .. }
+                | ASTNode::BinaryOp { .. }
+                | ASTNode::UnaryOp { .. }
+                | ASTNode::ArrayLiteral { .. }
+                | ASTNode::Index { .. }
+                | ASTNode::Slice { .. }
+                | ASTNode::Call { .. }
+                | ASTNode::MapLiteral { .. }
+                | ASTNode::StructLiteral { .. }
+                | ASTNode::FieldAccess { .. }
+                | ASTNode::Conditional { .. }
+                | ASTNode::Lambda { .. }
+        )
+    }
+
+    /// Whether a function body gives back a value, which is when any of its
+    /// `return`s has one
+    pub fn returns_value(&self, ast: &Ast) -> bool {
+        match self {
+            ASTNode::Return { value, .. } => value.is_some(),
+            ASTNode::Block(statements) => {
+                statements.iter().any(|stmt| ast[*stmt].returns_value(ast))
+            }
+            ASTNode::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                ast[*then_branch].returns_value(ast)
+                    || else_branch.is_some_and(|else_branch| ast[else_branch].returns_value(ast))
+            }
+            ASTNode::While { body, .. }
+            | ASTNode::DoWhile { body, .. }
+            | ASTNode::For { body, .. }
+            | ASTNode::ForIn { body, .. } => ast[*body].returns_value(ast),
+            _ => false,
+        }
+    }
+
+    /// Source location of the node: the keyword of a statement, the operator of
+    /// an operation, or the name of a variable. Literals, blocks and programs
+    /// don't track one and get a default span.
+    pub fn span(&self) -> Span {
+        match self {
+            ASTNode::Print { span, .. }
+            | ASTNode::Assert { span, .. }
+            | ASTNode::Input { span, .. }
+            | ASTNode::If { span, .. }
+            | ASTNode::While { span, .. }
+            | ASTNode::DoWhile { span, .. }
+            | ASTNode::For { span, .. }
+            | ASTNode::ForIn { span, .. }
+            | ASTNode::Range { span, .. }
+            | ASTNode::Lambda { span, .. }
+            | ASTNode::Break { span }
+            | ASTNode::Continue { span }
+            | ASTNode::Import { span, .. }
+            | ASTNode::Module { span, .. }
+            | ASTNode::LetDeclaration { span, .. }
+            | ASTNode::ConstDeclaration { span, .. }
+            | ASTNode::StructDeclaration { span, .. }
+            | ASTNode::FunctionDeclaration { span, .. }
+            | ASTNode::Return { span, .. }
+            | ASTNode::Assignment { span, .. }
+            | ASTNode::CompoundAssignment { span, .. }
+            | ASTNode::Increment { span, .. }
+            | ASTNode::Decrement { span, .. }
+            | ASTNode::BinaryOp { span, .. }
+            | ASTNode::UnaryOp { span, .. }
+            | ASTNode::IndexAssignment { span, .. }
+            | ASTNode::ArrayLiteral { span, .. }
+            | ASTNode::Index { span, .. }
+            | ASTNode::Slice { span, .. }
+            | ASTNode::Call { span, .. }
+            | ASTNode::MapLiteral { span, .. }
+            | ASTNode::StructLiteral { span, .. }
+            | ASTNode::FieldAccess { span, .. }
+            | ASTNode::Conditional { span, .. }
+            | ASTNode::Interpolation { span, .. }
+            | ASTNode::FieldAssignment { span, .. }
+            | ASTNode::Identifier { span, .. } => *span,
+            _ => Span::default(),
+        }
+    }
+
+    /// The nodes this one holds, in source order. Every kind of node is
+    /// listed, so a new kind must say what it holds before the
+    /// [`visitor`](crate::visitor) passes can walk it.
+    pub fn children(&self) -> Vec<NodeId> {
+        match self {
+            ASTNode::Program(nodes)
+            | ASTNode::Block(nodes)
+            | ASTNode::Print { args: nodes, .. }
+            | ASTNode::Call { args: nodes, .. }
+            | ASTNode::ArrayLiteral {
+                elements: nodes, ..
+            }
+            | ASTNode::Interpolation { values: nodes, .. } => nodes.clone(),
+            ASTNode::LetDeclaration { value, .. }
+            | ASTNode::ConstDeclaration { value, .. }
+            | ASTNode::Assignment { value, .. }
+            | ASTNode::CompoundAssignment { value, .. }
+            | ASTNode::Assert {
+                condition: value, ..
+            }
+            | ASTNode::FunctionDeclaration { body: value, .. }
+            | ASTNode::Lambda { body: value, .. }
+            | ASTNode::UnaryOp { operand: value, .. }
+            | ASTNode::FieldAccess { object: value, .. } => vec![*value],
+            ASTNode::Return { value, .. } => value.iter().copied().collect(),
+            ASTNode::IndexAssignment {
+                array,
+                index,
+                value,
+                ..
+            } => vec![*array, *index, *value],
+            ASTNode::FieldAssignment { object, value, .. } => vec![*object, *value],
+            ASTNode::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => [*condition, *then_branch]
+                .into_iter()
+                .chain(*else_branch)
+                .collect(),
+            ASTNode::While {
+                condition: first,
+                body: second,
+                ..
+            }
+            | ASTNode::DoWhile {
+                body: first,
+                condition: second,
+                ..
+            }
+            | ASTNode::ForIn {
+                range: first,
+                body: second,
+                ..
+            }
+            | ASTNode::Range {
+                start: first,
+                end: second,
+                ..
+            }
+            | ASTNode::BinaryOp {
+                left: first,
+                right: second,
+                ..
+            }
+            | ASTNode::Index {
+                array: first,
+                index: second,
+                ..
+            } => vec![*first, *second],
+            ASTNode::For {
+                init,
+                condition,
+                step,
+                body,
+                ..
+            } => [*init, *condition, *step, Some(*body)]
+                .into_iter()
+                .flatten()
+                .collect(),
+            ASTNode::Module { items, .. } => items.iter().map(|item| item.declaration).collect(),
+            ASTNode::Slice {
+                array, start, end, ..
+            } => [Some(*array), *start, *end].into_iter().flatten().collect(),
+            ASTNode::MapLiteral { entries, .. } => entries
+                .iter()
+                .flat_map(|(key, value)| [*key, *value])
+                .collect(),
+            ASTNode::StructLiteral { fields, .. } => {
+                fields.iter().map(|(_, value)| *value).collect()
+            }
+            ASTNode::Conditional {
+                condition,
+                then_value,
+                else_value,
+                ..
+            } => vec![*condition, *then_value, *else_value],
+            ASTNode::StructDeclaration { .. }
+            | ASTNode::Increment { .. }
+            | ASTNode::Decrement { .. }
+            | ASTNode::Input { .. }
+            | ASTNode::Break { .. }
+            | ASTNode::Continue { .. }
+            | ASTNode::Import { .. }
+            | ASTNode::Int(_)
+            | ASTNode::Number(_)
+            | ASTNode::Bool(_)
+            | ASTNode::Str(_)
+            | ASTNode::Char(_)
+            | ASTNode::Identifier { .. } => Vec::new(),
+        }
+    }
+}
+
+/// Where a node is in its [`Ast`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct NodeId(u32);
+
+/// The nodes of a program, stored in one list so that nodes refer to their
+/// children by [`NodeId`] instead of owning them. Passes that rewrite the
+/// tree replace nodes in place, and nodes they drop stay in the list unused.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Ast {
+    nodes: Vec<ASTNode>,
+
+    /// The [`ASTNode::Program`] holding the top-level statements
+    root: NodeId,
+
+    /// Qualified names of the `pub` items of the program's modules, which
+    /// are left for the code that imports them to use
+    #[serde(skip)]
+    public: BTreeSet<String>,
+}
+
+impl Ast {
+    /// An AST with an empty program
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![ASTNode::Program(Vec::new())],
+            root: NodeId(0),
+            public: BTreeSet::new(),
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    /// Add a node, which is given the next id
+    pub fn add(&mut self, node: ASTNode) -> NodeId {
+        let id = NodeId(self.nodes.len() as u32);
+        self.nodes.push(node);
+        id
+    }
+
+    /// Put `node` in the place of the node `id`, returning the old one
+    pub fn replace(&mut self, id: NodeId, node: ASTNode) -> ASTNode {
+        std::mem::replace(&mut self[id], node)
+    }
+
+    /// Run `f` on the node `id` taken out of the AST, so that it can change
+    /// the node and its children at the same time
+    pub fn update<T>(&mut self, id: NodeId, f: impl FnOnce(&mut Ast, &mut ASTNode) -> T) -> T {
+        let mut node = self.replace(id, ASTNode::Block(Vec::new()));
+        let result = f(self, &mut node);
+        self[id] = node;
+        result
+    }
+
+    /// Record that the module item `name` is `pub`
+    pub fn mark_public(&mut self, name: String) {
+        self.public.insert(name);
+    }
+
+    /// Whether `name` is a `pub` item of a module
+    pub fn is_public(&self, name: &str) -> bool {
+        self.public.contains(name)
+    }
+
+    /// The top-level statements of the program
+    pub fn statements(&self) -> &[NodeId] {
+        match &self[self.root] {
+            ASTNode::Program(statements) => statements,
+            _ => &[],
+        }
+    }
+}
+
+impl Default for Ast {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Index<NodeId> for Ast {
+    type Output = ASTNode;
+
+    fn index(&self, id: NodeId) -> &ASTNode {
+        &self.nodes[id.0 as usize]
+    }
+}
+
+impl IndexMut<NodeId> for Ast {
+    fn index_mut(&mut self, id: NodeId) -> &mut ASTNode {
+        &mut self.nodes[id.0 as usize]
+    }
+}
+
+/// A declaration in a module, which code outside the module can only use
+/// when it is `pub`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModuleItem {
+    pub public: bool,
+    pub declaration: NodeId,
+}
+
+/// A parameter of a declared function, with its type if it is annotated
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Param {
+    pub name: String,
+    pub ty: Option<Type>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum BinaryOperator {
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+    Power,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or, // Add other operators as needed
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum UnaryOperator {
+    Not,
+    Negate,
+}
+
+impl BinaryOperator {
+    /// Whether the operator compares its operands instead of computing a number
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinaryOperator::Equal
+                | BinaryOperator::NotEqual
+                | BinaryOperator::Less
+                | BinaryOperator::LessEqual
+                | BinaryOperator::Greater
+                | BinaryOperator::GreaterEqual
+        )
+    }
+
+    /// Whether the operator combines truth values with short-circuiting
+    pub fn is_logical(&self) -> bool {
+        matches!(self, BinaryOperator::And | BinaryOperator::Or)
+    }
+
+    /// The operator as written in source
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOperator::Plus => "+",
+            BinaryOperator::Minus => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Modulo => "%",
+            BinaryOperator::Power => "**",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessEqual => "<=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterEqual => ">=",
+            BinaryOperator::And => "&&",
+            BinaryOperator::Or => "||",
+        }
+    }
+}
+
+impl UnaryOperator {
+    /// The operator as written in source
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UnaryOperator::Not => "!",
+            UnaryOperator::Negate => "-",
+        }
+    }
+}
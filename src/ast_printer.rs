@@ -0,0 +1,782 @@
+//! Human-readable renderings of the syntax tree.
+//!
+//! [`to_tree`] draws the AST as an indented tree, and [`to_sexpr`] writes it
+//! as a single-line S-expression, which is handy for comparing in tests.
+//! [`to_source`] writes it back as osho source.
+
+use crate::format;
+use crate::parser::{ASTNode, Ast, NodeId, Param};
+use crate::semantic_analyzer::Type;
+
+/// Render a node and its children as an indented tree:
+///
+/// ```text
+/// program
+/// ├── let x
+/// │   └── 1
+/// └── print
+///     └── +
+///         ├── x
+///         └── 2
+/// ```
+pub fn to_tree(ast: &Ast, node: NodeId) -> String {
+    let mut out = String::new();
+    write_tree(ast, node, None, "", "", &mut out);
+    out
+}
+
+/// Render a node as an S-expression, e.g. `(program (let x 1) (print (+ x 2)))`.
+/// Missing `for` clauses are written as `_`.
+pub fn to_sexpr(ast: &Ast, node: NodeId) -> String {
+    let (label, children) = describe(&ast[node]);
+    // Literals and variables stand alone; statements like `(++ x)` get parens
+    let atom = matches!(
+        ast[node],
+        ASTNode::Int(_)
+            | ASTNode::Number(_)
+            | ASTNode::Bool(_)
+            | ASTNode::Str(_)
+            | ASTNode::Char(_)
+            | ASTNode::Identifier { .. }
+    );
+    if atom {
+        return label;
+    }
+    let mut out = format!("({}", label);
+    for (_, child) in children {
+        out.push(' ');
+        match child {
+            Some(child) => out.push_str(&to_sexpr(ast, child)),
+            None => out.push('_'),
+        }
+    }
+    out.push(')');
+    out
+}
+
+/// A child of a node, with the role it plays in the parent (like `then` or
+/// `body`) when the position alone doesn't make it clear
+type Child = (Option<&'static str>, Option<NodeId>);
+
+/// The label of a node and its children in source order
+fn describe(node: &ASTNode) -> (String, Vec<Child>) {
+    match node {
+        ASTNode::Program(statements) => ("program".to_string(), unlabelled(statements)),
+        ASTNode::Block(statements) => ("block".to_string(), unlabelled(statements)),
+        ASTNode::LetDeclaration {
+            name,
+            mutable,
+            ty,
+            value,
+            ..
+        } => {
+            let keyword = if *mutable { "let mut" } else { "let" };
+            (
+                format!("{} {}{}", keyword, name, annotation(": ", ty)),
+                vec![(None, Some(*value))],
+            )
+        }
+        ASTNode::ConstDeclaration { name, value, .. } => {
+            (format!("const {}", name), vec![(None, Some(*value))])
+        }
+        ASTNode::StructDeclaration { name, fields, .. } => (
+            format!("struct {} {{{}}}", name, fields.join(", ")),
+            Vec::new(),
+        ),
+        ASTNode::FunctionDeclaration {
+            name,
+            params,
+            result,
+            body,
+            ..
+        } => (
+            format!(
+                "fn {}({}){}",
+                name,
+                params_list(params),
+                annotation(" -> ", result)
+            ),
+            vec![(None, Some(*body))],
+        ),
+        ASTNode::Lambda { params, body, .. } => (
+            format!("|{}|", params_list(params)),
+            vec![(None, Some(*body))],
+        ),
+        ASTNode::Return { value, .. } => match value {
+            Some(value) => ("return".to_string(), vec![(None, Some(*value))]),
+            None => ("return".to_string(), Vec::new()),
+        },
+        ASTNode::Assignment { name, value, .. } => {
+            (format!("= {}", name), vec![(None, Some(*value))])
+        }
+        ASTNode::CompoundAssignment {
+            name, op, value, ..
+        } => (
+            format!("{}= {}", op.symbol(), name),
+            vec![(None, Some(*value))],
+        ),
+        ASTNode::Break { .. } => ("break".to_string(), Vec::new()),
+        ASTNode::Continue { .. } => ("continue".to_string(), Vec::new()),
+        ASTNode::Import { path, .. } => (format!("import {:?}", path), Vec::new()),
+        ASTNode::Module { name, items, .. } => (
+            format!("module {}", name),
+            items
+                .iter()
+                .map(|item| (item.public.then_some("pub"), Some(item.declaration)))
+                .collect(),
+        ),
+        ASTNode::Increment { name, .. } => (format!("++ {}", name), Vec::new()),
+        ASTNode::Input { name, .. } => (format!("input {}", name), Vec::new()),
+        ASTNode::Assert {
+            condition, message, ..
+        } => {
+            let label = match message {
+                Some(message) => format!("assert {:?}", message),
+                None => "assert".to_string(),
+            };
+            (label, vec![(None, Some(*condition))])
+        }
+        ASTNode::Decrement { name, .. } => (format!("-- {}", name), Vec::new()),
+        ASTNode::Print {
+            format,
+            args,
+            newline,
+            ..
+        } => {
+            let keyword = if *newline { "println" } else { "print" };
+            let label = match format {
+                Some(format) => format!("{} {:?}", keyword, format),
+                None => keyword.to_string(),
+            };
+            (label, unlabelled(args))
+        }
+        ASTNode::IndexAssignment {
+            array,
+            index,
+            value,
+            ..
+        } => (
+            "[]=".to_string(),
+            vec![
+                (None, Some(*array)),
+                (None, Some(*index)),
+                (None, Some(*value)),
+            ],
+        ),
+        ASTNode::FieldAssignment {
+            object,
+            field,
+            value,
+            ..
+        } => (
+            format!(".{}=", field),
+            vec![(None, Some(*object)), (None, Some(*value))],
+        ),
+        ASTNode::If {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            let mut children = vec![
+                (Some("condition"), Some(*condition)),
+                (Some("then"), Some(*then_branch)),
+            ];
+            if let Some(else_branch) = else_branch {
+                children.push((Some("else"), Some(*else_branch)));
+            }
+            ("if".to_string(), children)
+        }
+        ASTNode::While {
+            condition, body, ..
+        } => (
+            "while".to_string(),
+            vec![
+                (Some("condition"), Some(*condition)),
+                (Some("body"), Some(*body)),
+            ],
+        ),
+        ASTNode::DoWhile {
+            body, condition, ..
+        } => (
+            "do-while".to_string(),
+            vec![
+                (Some("body"), Some(*body)),
+                (Some("condition"), Some(*condition)),
+            ],
+        ),
+        ASTNode::For {
+            init,
+            condition,
+            step,
+            body,
+            ..
+        } => (
+            "for".to_string(),
+            vec![
+                (Some("init"), *init),
+                (Some("condition"), *condition),
+                (Some("step"), *step),
+                (Some("body"), Some(*body)),
+            ],
+        ),
+        ASTNode::ForIn {
+            name, range, body, ..
+        } => (
+            format!("for {} in", name),
+            vec![(Some("range"), Some(*range)), (Some("body"), Some(*body))],
+        ),
+        ASTNode::Range {
+            start,
+            end,
+            inclusive,
+            ..
+        } => (
+            if *inclusive { "..=" } else { ".." }.to_string(),
+            vec![(None, Some(*start)), (None, Some(*end))],
+        ),
+        ASTNode::BinaryOp {
+            left, op, right, ..
+        } => (
+            op.symbol().to_string(),
+            vec![(None, Some(*left)), (None, Some(*right))],
+        ),
+        ASTNode::UnaryOp { op, operand, .. } => {
+            (op.symbol().to_string(), vec![(None, Some(*operand))])
+        }
+        ASTNode::ArrayLiteral { elements, .. } => ("array".to_string(), unlabelled(elements)),
+        ASTNode::Index { array, index, .. } => (
+            "[]".to_string(),
+            vec![(None, Some(*array)), (None, Some(*index))],
+        ),
+        ASTNode::Slice {
+            array, start, end, ..
+        } => (
+            "[:]".to_string(),
+            vec![
+                (None, Some(*array)),
+                (Some("start"), *start),
+                (Some("end"), *end),
+            ],
+        ),
+        ASTNode::Call { name, args, .. } => (format!("call {}", name), unlabelled(args)),
+        ASTNode::MapLiteral { entries, .. } => (
+            "map".to_string(),
+            entries
+                .iter()
+                .flat_map(|(key, value)| [(None, Some(*key)), (None, Some(*value))])
+                .collect(),
+        ),
+        ASTNode::StructLiteral { name, fields, .. } => {
+            let names: Vec<_> = fields.iter().map(|(field, _)| field.as_str()).collect();
+            (
+                format!("{} {{{}}}", name, names.join(", ")),
+                fields
+                    .iter()
+                    .map(|(_, value)| (None, Some(*value)))
+                    .collect(),
+            )
+        }
+        ASTNode::FieldAccess { object, field, .. } => {
+            (format!(".{}", field), vec![(None, Some(*object))])
+        }
+        ASTNode::Conditional {
+            condition,
+            then_value,
+            else_value,
+            ..
+        } => (
+            "?".to_string(),
+            vec![
+                (None, Some(*condition)),
+                (None, Some(*then_value)),
+                (None, Some(*else_value)),
+            ],
+        ),
+        ASTNode::Int(num) => (num.to_string(), Vec::new()),
+        // Debug formatting keeps the '.0' so floats don't look like integers
+        ASTNode::Number(num) => (format!("{:?}", num), Vec::new()),
+        ASTNode::Bool(b) => (b.to_string(), Vec::new()),
+        ASTNode::Str(text) => (format!("{:?}", text), Vec::new()),
+        ASTNode::Char(c) => (format!("{:?}", c), Vec::new()),
+        ASTNode::Interpolation { pieces, values, .. } => (
+            format!("interpolate {:?}", format::unparse(pieces)),
+            unlabelled(values),
+        ),
+        ASTNode::Identifier { name, .. } => (name.clone(), Vec::new()),
+    }
+}
+
+/// Render a node as osho source that parses back to the same tree. Operands
+/// that are operations themselves get parentheses, whatever their
+/// precedence, and nothing else of the original layout is kept.
+pub fn to_source(ast: &Ast, node: NodeId) -> String {
+    match &ast[node] {
+        ASTNode::Program(statements) => statements
+            .iter()
+            .map(|stmt| format!("{}\n", statement(ast, *stmt, 0)))
+            .collect(),
+        _ => statement(ast, node, 0),
+    }
+}
+
+/// A statement, whose lines after the first are indented by `indent` levels
+fn statement(ast: &Ast, node: NodeId, indent: usize) -> String {
+    match &ast[node] {
+        ASTNode::LetDeclaration {
+            name,
+            mutable,
+            ty,
+            value,
+            ..
+        } => format!(
+            "let {}{}{} = {}",
+            if *mutable { "mut " } else { "" },
+            name,
+            annotation(": ", ty),
+            expression(ast, *value)
+        ),
+        ASTNode::ConstDeclaration { name, value, .. } => {
+            format!("const {} = {}", name, expression(ast, *value))
+        }
+        ASTNode::StructDeclaration { name, fields, .. } => {
+            format!("struct {} {{ {} }}", name, fields.join(", "))
+        }
+        ASTNode::FunctionDeclaration {
+            name,
+            params,
+            result,
+            body,
+            ..
+        } => format!(
+            "fn {}({}){} {}",
+            name,
+            params_list(params),
+            annotation(" -> ", result),
+            statement(ast, *body, indent)
+        ),
+        ASTNode::Return { value, .. } => match value {
+            Some(value) => format!("return {}", expression(ast, *value)),
+            None => "return".to_string(),
+        },
+        ASTNode::Assignment { name, value, .. } => {
+            format!("{} = {}", name, expression(ast, *value))
+        }
+        ASTNode::CompoundAssignment {
+            name, op, value, ..
+        } => format!("{} {}= {}", name, op.symbol(), expression(ast, *value)),
+        ASTNode::Increment { name, .. } => format!("{}++", name),
+        ASTNode::Decrement { name, .. } => format!("{}--", name),
+        ASTNode::Print {
+            format,
+            args,
+            newline,
+            ..
+        } => {
+            let mut parts: Vec<_> = format.iter().map(|format| string(format)).collect();
+            for (i, arg) in args.iter().enumerate() {
+                let arg = expression(ast, *arg);
+                // A leading string would be taken for a format string
+                if i == 0 && format.is_none() && arg.starts_with('"') {
+                    parts.push(format!("({})", arg));
+                } else {
+                    parts.push(arg);
+                }
+            }
+            let keyword = if *newline { "println" } else { "print" };
+            format!("{}({})", keyword, parts.join(", "))
+        }
+        ASTNode::Input { name, .. } => format!("input {}", name),
+        ASTNode::Assert {
+            condition, message, ..
+        } => match message {
+            Some(message) => format!(
+                "assert {}, {}",
+                expression(ast, *condition),
+                string(message)
+            ),
+            None => format!("assert {}", expression(ast, *condition)),
+        },
+        ASTNode::IndexAssignment {
+            array,
+            index,
+            value,
+            ..
+        } => format!(
+            "{}[{}] = {}",
+            postfix_operand(ast, *array),
+            expression(ast, *index),
+            expression(ast, *value)
+        ),
+        ASTNode::FieldAssignment {
+            object,
+            field,
+            value,
+            ..
+        } => format!(
+            "{}.{} = {}",
+            postfix_operand(ast, *object),
+            field,
+            expression(ast, *value)
+        ),
+        ASTNode::Block(statements) => {
+            let mut out = String::from("{\n");
+            for stmt in statements {
+                out.push_str(&"    ".repeat(indent + 1));
+                out.push_str(&statement(ast, *stmt, indent + 1));
+                out.push('\n');
+            }
+            out.push_str(&"    ".repeat(indent));
+            out.push('}');
+            out
+        }
+        ASTNode::If {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            let mut out = format!(
+                "if {} {}",
+                condition_expression(ast, *condition),
+                statement(ast, *then_branch, indent)
+            );
+            if let Some(else_branch) = else_branch {
+                out.push_str(" else ");
+                out.push_str(&statement(ast, *else_branch, indent));
+            }
+            out
+        }
+        ASTNode::While {
+            condition, body, ..
+        } => format!(
+            "while {} {}",
+            condition_expression(ast, *condition),
+            statement(ast, *body, indent)
+        ),
+        ASTNode::DoWhile {
+            body, condition, ..
+        } => format!(
+            "do {} while {}",
+            statement(ast, *body, indent),
+            condition_expression(ast, *condition)
+        ),
+        ASTNode::For {
+            init,
+            condition,
+            step,
+            body,
+            ..
+        } => {
+            let clause = |node: &Option<NodeId>| {
+                node.map(|node| statement(ast, node, indent))
+                    .unwrap_or_default()
+            };
+            format!(
+                "for ({}; {}; {}) {}",
+                clause(init),
+                condition
+                    .map(|condition| expression(ast, condition))
+                    .unwrap_or_default(),
+                clause(step),
+                statement(ast, *body, indent)
+            )
+        }
+        ASTNode::ForIn {
+            name, range, body, ..
+        } => format!(
+            "for {} in {} {}",
+            name,
+            expression(ast, *range),
+            statement(ast, *body, indent)
+        ),
+        ASTNode::Break { .. } => "break".to_string(),
+        ASTNode::Continue { .. } => "continue".to_string(),
+        ASTNode::Import { path, .. } => format!("import {}", string(path)),
+        ASTNode::Module { name, items, .. } => {
+            let mut out = format!("module {} {{\n", name);
+            for item in items {
+                out.push_str(&"    ".repeat(indent + 1));
+                if item.public {
+                    out.push_str("pub ");
+                }
+                out.push_str(&statement(ast, item.declaration, indent + 1));
+                out.push('\n');
+            }
+            out.push_str(&"    ".repeat(indent));
+            out.push('}');
+            out
+        }
+        _ => expression(ast, node),
+    }
+}
+
+/// An expression, with parentheses around operands that are operations
+fn expression(ast: &Ast, node: NodeId) -> String {
+    match &ast[node] {
+        ASTNode::Int(num) => num.to_string(),
+        ASTNode::Number(num) => {
+            // Display never uses an exponent, which the lexer doesn't read
+            let text = num.to_string();
+            if text.contains('.') {
+                text
+            } else {
+                format!("{}.0", text)
+            }
+        }
+        ASTNode::Bool(b) => b.to_string(),
+        ASTNode::Str(text) => string(text),
+        ASTNode::Char(c) => char_literal(*c),
+        ASTNode::Interpolation { pieces, values, .. } => {
+            let mut out = format!("\"{}", escape(&pieces[0]));
+            for (value, piece) in values.iter().zip(&pieces[1..]) {
+                out.push_str(&format!(
+                    "${{{}}}{}",
+                    expression(ast, *value),
+                    escape(piece)
+                ));
+            }
+            out.push('"');
+            out
+        }
+        ASTNode::Identifier { name, .. } => name.clone(),
+        ASTNode::BinaryOp {
+            left, op, right, ..
+        } => format!(
+            "{} {} {}",
+            operand(ast, *left),
+            op.symbol(),
+            operand(ast, *right)
+        ),
+        ASTNode::UnaryOp {
+            op, operand: value, ..
+        } => {
+            format!("{}{}", op.symbol(), operand(ast, *value))
+        }
+        ASTNode::Conditional {
+            condition,
+            then_value,
+            else_value,
+            ..
+        } => format!(
+            "{} ? {} : {}",
+            operand(ast, *condition),
+            operand(ast, *then_value),
+            operand(ast, *else_value)
+        ),
+        ASTNode::ArrayLiteral { elements, .. } => format!("[{}]", list(ast, elements)),
+        ASTNode::Index { array, index, .. } => {
+            format!(
+                "{}[{}]",
+                postfix_operand(ast, *array),
+                expression(ast, *index)
+            )
+        }
+        ASTNode::Slice {
+            array, start, end, ..
+        } => {
+            let bound = |bound: &Option<NodeId>| {
+                bound.map_or(String::new(), |bound| expression(ast, bound))
+            };
+            format!(
+                "{}[{}:{}]",
+                postfix_operand(ast, *array),
+                bound(start),
+                bound(end)
+            )
+        }
+        ASTNode::Call { name, args, .. } => format!("{}({})", name, list(ast, args)),
+        ASTNode::MapLiteral { entries, .. } => {
+            let entries: Vec<_> = entries
+                .iter()
+                .map(|(key, value)| {
+                    format!("{}: {}", expression(ast, *key), expression(ast, *value))
+                })
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        ASTNode::StructLiteral { name, fields, .. } => {
+            let fields: Vec<_> = fields
+                .iter()
+                .map(|(field, value)| format!("{}: {}", field, expression(ast, *value)))
+                .collect();
+            if fields.is_empty() {
+                return format!("{} {{}}", name);
+            }
+            format!("{} {{ {} }}", name, fields.join(", "))
+        }
+        ASTNode::FieldAccess { object, field, .. } => {
+            format!("{}.{}", postfix_operand(ast, *object), field)
+        }
+        ASTNode::Lambda { params, body, .. } => {
+            format!("|{}| {}", params_list(params), expression(ast, *body))
+        }
+        ASTNode::Range {
+            start,
+            end,
+            inclusive,
+            ..
+        } => format!(
+            "{}{}{}",
+            condition_expression(ast, *start),
+            if *inclusive { "..=" } else { ".." },
+            condition_expression(ast, *end)
+        ),
+        // Statements like `x++` are also expressions, as in a `for` step
+        _ => statement(ast, node, 0),
+    }
+}
+
+/// An operand of an operator, in parentheses unless it is a single term
+fn operand(ast: &Ast, node: NodeId) -> String {
+    match &ast[node] {
+        // A lambda's body would take in what follows it
+        ASTNode::BinaryOp { .. }
+        | ASTNode::UnaryOp { .. }
+        | ASTNode::Conditional { .. }
+        | ASTNode::Lambda { .. } => format!("({})", expression(ast, node)),
+        // `-2 ** 2` would be `-(2 ** 2)`
+        ASTNode::Int(num) if *num < 0 => format!("({})", num),
+        ASTNode::Number(num) if num.is_sign_negative() => format!("({})", expression(ast, node)),
+        _ => expression(ast, node),
+    }
+}
+
+/// What is indexed or has a field taken, which binds tighter than any
+/// operator
+fn postfix_operand(ast: &Ast, node: NodeId) -> String {
+    match &ast[node] {
+        ASTNode::Identifier { .. }
+        | ASTNode::Index { .. }
+        | ASTNode::Slice { .. }
+        | ASTNode::FieldAccess { .. }
+        | ASTNode::Call { .. }
+        | ASTNode::ArrayLiteral { .. }
+        | ASTNode::MapLiteral { .. }
+        | ASTNode::Str(_)
+        | ASTNode::Char(_)
+        | ASTNode::Interpolation { .. } => expression(ast, node),
+        _ => format!("({})", expression(ast, node)),
+    }
+}
+
+/// The condition of an `if` or `while`, where a `{` would start the body
+/// unless it is in parentheses
+fn condition_expression(ast: &Ast, node: NodeId) -> String {
+    if has_struct_literal(ast, node) {
+        format!("({})", expression(ast, node))
+    } else {
+        expression(ast, node)
+    }
+}
+
+fn has_struct_literal(ast: &Ast, node: NodeId) -> bool {
+    matches!(ast[node], ASTNode::StructLiteral { .. })
+        || describe(&ast[node])
+            .1
+            .into_iter()
+            .any(|(_, child)| child.is_some_and(|child| has_struct_literal(ast, child)))
+}
+
+fn list(ast: &Ast, nodes: &[NodeId]) -> String {
+    nodes
+        .iter()
+        .map(|node| expression(ast, *node))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A string literal
+fn string(text: &str) -> String {
+    format!("\"{}\"", escape(text))
+}
+
+/// A character literal, with the characters that would end it escaped
+fn char_literal(c: char) -> String {
+    match c {
+        '\\' => "'\\\\'".to_string(),
+        '\'' => "'\\''".to_string(),
+        '\n' => "'\\n'".to_string(),
+        '\t' => "'\\t'".to_string(),
+        c => format!("'{}'", c),
+    }
+}
+
+/// Text in a string literal, with the characters that would end it or start
+/// an interpolation escaped
+fn escape(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '$' if chars.peek() == Some(&'{') => out.push_str("\\$"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unlabelled(statements: &[NodeId]) -> Vec<Child> {
+    statements.iter().map(|stmt| (None, Some(*stmt))).collect()
+}
+
+/// Parameters separated by commas, with their annotated types
+fn params_list(params: &[Param]) -> String {
+    let params: Vec<_> = params
+        .iter()
+        .map(|param| format!("{}{}", param.name, annotation(": ", &param.ty)))
+        .collect();
+    params.join(", ")
+}
+
+/// A type annotation after `separator`, or nothing if there isn't one
+fn annotation(separator: &str, ty: &Option<Type>) -> String {
+    match ty {
+        Some(ty) => format!("{}{}", separator, ty),
+        None => String::new(),
+    }
+}
+
+/// Write one line for the node, then its children with box-drawing guides.
+/// `prefix` starts the node's own line; `indent` starts its children's lines.
+fn write_tree(
+    ast: &Ast,
+    node: NodeId,
+    role: Option<&str>,
+    prefix: &str,
+    indent: &str,
+    out: &mut String,
+) {
+    let (label, children) = describe(&ast[node]);
+    out.push_str(prefix);
+    if let Some(role) = role {
+        out.push_str(role);
+        out.push_str(": ");
+    }
+    out.push_str(&label);
+    out.push('\n');
+
+    // Clauses left out of a `for` aren't drawn
+    let children: Vec<_> = children
+        .into_iter()
+        .filter_map(|(role, child)| child.map(|child| (role, child)))
+        .collect();
+    let count = children.len();
+    for (i, (role, child)) in children.into_iter().enumerate() {
+        let last = i + 1 == count;
+        let branch = if last { "└── " } else { "├── " };
+        let guide = if last { "    " } else { "│   " };
+        write_tree(
+            ast,
+            child,
+            role,
+            &format!("{}{}", indent, branch),
+            &format!("{}{}", indent, guide),
+            out,
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! The interface shared by the code generators, and the registry of targets
+//! they can be selected by.
+
+use crate::checks::Checks;
+use crate::code_generator::CodeGenerator;
+use crate::error::CompileError;
+use crate::js_generator::JsGenerator;
+use crate::parser::Ast;
+use crate::wasm_generator::WasmGenerator;
+
+/// Turns a checked program into source code for another language
+pub trait Backend {
+    /// Generate the code for a whole program
+    fn generate(&mut self, ast: &Ast) -> Result<String, CompileError>;
+}
+
+/// A language osho can be compiled to
+pub struct Target {
+    /// Name used to select the target, e.g. with `--target`
+    pub name: &'static str,
+
+    /// Extension of the files the generated code is written to
+    pub extension: &'static str,
+
+    /// Whether the generated code is built into an executable with the C
+    /// compiler, rather than just written out
+    pub native: bool,
+
+    /// Creates a fresh backend for the target
+    new: fn(Checks) -> Box<dyn Backend>,
+}
+
+impl Target {
+    /// A new backend that generates code for this target, making `checks`
+    /// where it supports them
+    pub fn backend(&self, checks: Checks) -> Box<dyn Backend> {
+        (self.new)(checks)
+    }
+}
+
+/// Every target, with the default first
+pub static TARGETS: [Target; 3] = [
+    Target {
+        name: "c",
+        extension: "c",
+        native: true,
+        new: |checks| Box::new(CodeGenerator::new().with_checks(checks)),
+    },
+    Target {
+        name: "wasm",
+        extension: "wat",
+        native: false,
+        new: |_| Box::new(WasmGenerator::new()),
+    },
+    Target {
+        name: "js",
+        extension: "js",
+        native: false,
+        new: |_| Box::new(JsGenerator::new()),
+    },
+];
+
+/// Look up a target by name
+pub fn target(name: &str) -> Result<&'static Target, String> {
+    TARGETS
+        .iter()
+        .find(|target| target.name == name)
+        .ok_or_else(|| {
+            let names: Vec<_> = TARGETS.iter().map(|target| target.name).collect();
+            format!(
+                "Unknown target '{}', expected one of {}",
+                name,
+                names.join(", ")
+            )
+        })
+}
@@ -0,0 +1,810 @@
+//! Functions built into the language, like `len(a)`.
+//!
+//! Each backend implements them itself; this module only knows their names,
+//! checks their arguments and evaluates them for the interpreters.
+
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+
+use crate::interpreter::{self, Value};
+use crate::semantic_analyzer::Type;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    /// `len(array)` or `len(text)`, the number of elements of an array or
+    /// characters of a string
+    Len,
+
+    /// `push(array, value)`, which adds an element to the end of an array
+    Push,
+
+    /// `pop(array)`, which removes the last element of an array and returns it
+    Pop,
+
+    /// `keys(map)`, the keys of a map as a sorted array
+    Keys,
+
+    /// `sqrt(x)`, the square root of a number as a float
+    Sqrt,
+
+    /// `abs(x)`, the absolute value of a number, of the same type
+    Abs,
+
+    /// `floor(x)`, a number rounded down, of the same type
+    Floor,
+
+    /// `ceil(x)`, a number rounded up, of the same type
+    Ceil,
+
+    /// `sin(x)`, the sine of an angle in radians
+    Sin,
+
+    /// `cos(x)`, the cosine of an angle in radians
+    Cos,
+
+    /// `pow(x, y)`, `x` raised to the power `y` as a float
+    Pow,
+
+    /// `min(x, y)`, the smaller of two numbers, an int if both are
+    Min,
+
+    /// `max(x, y)`, the larger of two numbers, an int if both are
+    Max,
+
+    /// `rand()`, a random float from 0 up to but not including 1
+    Rand,
+
+    /// `rand_range(low, high)`, a random int from `low` up to but not
+    /// including `high`
+    RandRange,
+
+    /// `seed(n)`, which restarts the random numbers from seed `n`
+    Seed,
+
+    /// `exit(code)`, which stops the program with an exit code from 0 to 255
+    Exit,
+
+    /// `error(message)`, which stops the program with a runtime error
+    Error,
+
+    /// `args()`, the arguments the program was run with as strings
+    Args,
+
+    /// `argc()`, the number of arguments the program was run with
+    Argc,
+
+    /// `argv(index)`, one of the arguments the program was run with
+    Argv,
+
+    /// `env(name)` or `env(name, default)`, the value of an environment
+    /// variable, or the default, an empty string unless given, if it isn't
+    /// set
+    Env,
+
+    /// `int(x)`, a number or a bool as an int. Floats are truncated toward
+    /// zero and must fit in an int.
+    Int,
+
+    /// `float(x)`, a number or a bool as a float
+    Float,
+
+    /// `str(x)`, a value as the text `print` shows for it
+    Str,
+
+    /// `typeof(x)`, the name of the type of a value, like `"[int]"`. Types
+    /// are known before the program runs, so `x` isn't evaluated.
+    TypeOf,
+
+    /// `parse_num(text)`, the float a string holds, like `"2.5"` or `"-3"`
+    ParseNum,
+
+    /// `substring(text, start, end)`, the characters of a string from
+    /// `start` up to but not including `end`
+    Substring,
+
+    /// `find(text, part)`, the index of the character where `part` first
+    /// appears in a string, or -1 if it doesn't
+    Find,
+
+    /// `split(text, separator)`, the parts of a string between each
+    /// separator
+    Split,
+
+    /// `upper(text)`, a string with its ASCII letters in upper case
+    Upper,
+
+    /// `lower(text)`, a string with its ASCII letters in lower case
+    Lower,
+
+    /// `ord(c)`, the Unicode code point of a character
+    Ord,
+
+    /// `chr(n)`, the character with the Unicode code point `n`
+    Chr,
+
+    /// `read_file(path)`, the contents of a file as a string
+    ReadFile,
+
+    /// `write_file(path, text)`, which replaces the contents of a file,
+    /// creating it if it doesn't exist
+    WriteFile,
+
+    /// `append_file(path, text)`, which adds text to the end of a file,
+    /// creating it if it doesn't exist
+    AppendFile,
+
+    /// `apply(f, args...)`, the result of calling the function `f` with
+    /// the rest of the arguments
+    Apply,
+
+    /// `map(f, array)`, a new array of the results of calling `f` on each
+    /// element
+    Map,
+}
+
+/// The random number generator behind `rand()`, splitmix64. Every backend
+/// runs the same one, so a seed gives the same numbers everywhere.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub(crate) fn seed(&mut self, seed: i64) {
+        self.state = seed as u64;
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// A float from 0 up to but not including 1, from the top 53 bits
+    pub(crate) fn float(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// An int from `low` up to but not including `high`
+    pub(crate) fn range(&mut self, low: i64, high: i64) -> Result<i64, String> {
+        if low >= high {
+            return Err(format!(
+                "rand_range({}, {}) has no numbers to pick from",
+                low, high
+            ));
+        }
+        let span = (high as u64).wrapping_sub(low as u64);
+        Ok((low as u64).wrapping_add(self.next() % span) as i64)
+    }
+}
+
+impl Builtin {
+    /// The builtin called `name`, if there is one
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "len" => Some(Builtin::Len),
+            "push" => Some(Builtin::Push),
+            "pop" => Some(Builtin::Pop),
+            "keys" => Some(Builtin::Keys),
+            "sqrt" => Some(Builtin::Sqrt),
+            "abs" => Some(Builtin::Abs),
+            "floor" => Some(Builtin::Floor),
+            "ceil" => Some(Builtin::Ceil),
+            "sin" => Some(Builtin::Sin),
+            "cos" => Some(Builtin::Cos),
+            "pow" => Some(Builtin::Pow),
+            "min" => Some(Builtin::Min),
+            "max" => Some(Builtin::Max),
+            "rand" => Some(Builtin::Rand),
+            "rand_range" => Some(Builtin::RandRange),
+            "seed" => Some(Builtin::Seed),
+            "exit" => Some(Builtin::Exit),
+            "error" => Some(Builtin::Error),
+            "args" => Some(Builtin::Args),
+            "argc" => Some(Builtin::Argc),
+            "argv" => Some(Builtin::Argv),
+            "env" => Some(Builtin::Env),
+            "int" => Some(Builtin::Int),
+            "float" => Some(Builtin::Float),
+            "str" => Some(Builtin::Str),
+            "typeof" => Some(Builtin::TypeOf),
+            "parse_num" => Some(Builtin::ParseNum),
+            "substring" => Some(Builtin::Substring),
+            "find" => Some(Builtin::Find),
+            "split" => Some(Builtin::Split),
+            "upper" => Some(Builtin::Upper),
+            "lower" => Some(Builtin::Lower),
+            "ord" => Some(Builtin::Ord),
+            "chr" => Some(Builtin::Chr),
+            "read_file" => Some(Builtin::ReadFile),
+            "write_file" => Some(Builtin::WriteFile),
+            "append_file" => Some(Builtin::AppendFile),
+            "apply" => Some(Builtin::Apply),
+            "map" => Some(Builtin::Map),
+            _ => None,
+        }
+    }
+
+    /// The name the builtin is called by
+    pub fn name(&self) -> &'static str {
+        match self {
+            Builtin::Len => "len",
+            Builtin::Push => "push",
+            Builtin::Pop => "pop",
+            Builtin::Keys => "keys",
+            Builtin::Sqrt => "sqrt",
+            Builtin::Abs => "abs",
+            Builtin::Floor => "floor",
+            Builtin::Ceil => "ceil",
+            Builtin::Sin => "sin",
+            Builtin::Cos => "cos",
+            Builtin::Pow => "pow",
+            Builtin::Min => "min",
+            Builtin::Max => "max",
+            Builtin::Rand => "rand",
+            Builtin::RandRange => "rand_range",
+            Builtin::Seed => "seed",
+            Builtin::Exit => "exit",
+            Builtin::Error => "error",
+            Builtin::Args => "args",
+            Builtin::Argc => "argc",
+            Builtin::Argv => "argv",
+            Builtin::Env => "env",
+            Builtin::Int => "int",
+            Builtin::Float => "float",
+            Builtin::Str => "str",
+            Builtin::TypeOf => "typeof",
+            Builtin::ParseNum => "parse_num",
+            Builtin::Substring => "substring",
+            Builtin::Find => "find",
+            Builtin::Split => "split",
+            Builtin::Upper => "upper",
+            Builtin::Lower => "lower",
+            Builtin::Ord => "ord",
+            Builtin::Chr => "chr",
+            Builtin::ReadFile => "read_file",
+            Builtin::WriteFile => "write_file",
+            Builtin::AppendFile => "append_file",
+            Builtin::Apply => "apply",
+            Builtin::Map => "map",
+        }
+    }
+
+    /// Whether a call gives a value, so it can be used in an expression
+    pub fn has_result(&self) -> bool {
+        !matches!(
+            self,
+            Builtin::Push
+                | Builtin::Seed
+                | Builtin::Exit
+                | Builtin::Error
+                | Builtin::WriteFile
+                | Builtin::AppendFile
+        )
+    }
+
+    /// Whether a call changes its first argument, which must then be a
+    /// mutable variable
+    pub fn mutates(&self) -> bool {
+        matches!(self, Builtin::Push | Builtin::Pop)
+    }
+
+    /// Whether a call only computes its result: it can't fail and changes
+    /// nothing, so it can be removed if the result isn't used
+    pub fn is_pure(&self) -> bool {
+        matches!(
+            self,
+            Builtin::Len
+                | Builtin::Keys
+                | Builtin::Args
+                | Builtin::Argc
+                | Builtin::Env
+                | Builtin::Float
+                | Builtin::Str
+                | Builtin::TypeOf
+                | Builtin::Find
+                | Builtin::Upper
+                | Builtin::Lower
+                | Builtin::Ord
+        ) || self.is_math()
+    }
+
+    /// Whether the builtin reads the arguments the program was run with,
+    /// which the interpreters are given
+    pub fn reads_arguments(&self) -> bool {
+        matches!(self, Builtin::Args | Builtin::Argc | Builtin::Argv)
+    }
+
+    /// Whether the builtin reads or writes a file, which can fail
+    pub fn uses_files(&self) -> bool {
+        matches!(
+            self,
+            Builtin::ReadFile | Builtin::WriteFile | Builtin::AppendFile
+        )
+    }
+
+    /// Whether the builtin calls a function it is given, so the backends
+    /// run it themselves
+    pub fn calls_function(&self) -> bool {
+        matches!(self, Builtin::Apply | Builtin::Map)
+    }
+
+    /// Whether this is one of the math functions, which take numbers
+    pub fn is_math(&self) -> bool {
+        matches!(
+            self,
+            Builtin::Sqrt
+                | Builtin::Abs
+                | Builtin::Floor
+                | Builtin::Ceil
+                | Builtin::Sin
+                | Builtin::Cos
+                | Builtin::Pow
+                | Builtin::Min
+                | Builtin::Max
+        )
+    }
+
+    /// Check the types of the arguments of a call, returning the type of
+    /// its result, if it has one
+    pub(crate) fn check(&self, args: &[Type]) -> Result<Option<Type>, String> {
+        match self {
+            Builtin::Len => match self.arguments(args, 1)? {
+                [Type::Array(_) | Type::Str] => Ok(Some(Type::Int)),
+                [other] => Err(format!(
+                    "len() expects an array or a string, found {}",
+                    other
+                )),
+                _ => unreachable!("argument count was checked"),
+            },
+            Builtin::Push => match self.arguments(args, 2)? {
+                [Type::Array(element), value] if element.accepts(value) => Ok(None),
+                [array @ Type::Array(_), value] => {
+                    Err(format!("Cannot push {} onto {}", value, array))
+                }
+                [other, _] => Err(self.expected_array(other)),
+                _ => unreachable!("argument count was checked"),
+            },
+            Builtin::Pop => match self.arguments(args, 1)? {
+                [Type::Array(element)] => Ok(Some((**element).clone())),
+                [other] => Err(self.expected_array(other)),
+                _ => unreachable!("argument count was checked"),
+            },
+            Builtin::Keys => match self.arguments(args, 1)? {
+                [Type::Map(_)] => Ok(Some(Type::Array(Box::new(Type::Str)))),
+                [other] => Err(format!("keys() expects a map, found {}", other)),
+                _ => unreachable!("argument count was checked"),
+            },
+            Builtin::Sqrt | Builtin::Sin | Builtin::Cos => {
+                self.numbers(args, 1)?;
+                Ok(Some(Type::Float))
+            }
+            Builtin::Abs | Builtin::Floor | Builtin::Ceil => {
+                Ok(Some(self.numbers(args, 1)?[0].clone()))
+            }
+            Builtin::Pow => {
+                self.numbers(args, 2)?;
+                Ok(Some(Type::Float))
+            }
+            Builtin::Min | Builtin::Max => match self.numbers(args, 2)? {
+                [Type::Int, Type::Int] => Ok(Some(Type::Int)),
+                _ => Ok(Some(Type::Float)),
+            },
+            Builtin::Rand => {
+                self.arguments(args, 0)?;
+                Ok(Some(Type::Float))
+            }
+            Builtin::RandRange => match self.arguments(args, 2)? {
+                [Type::Int, Type::Int] => Ok(Some(Type::Int)),
+                [Type::Int, other] | [other, _] => Err(self.expected_int(other)),
+                _ => unreachable!("argument count was checked"),
+            },
+            Builtin::Seed | Builtin::Exit => match self.arguments(args, 1)? {
+                [Type::Int] => Ok(None),
+                [other] => Err(self.expected_int(other)),
+                _ => unreachable!("argument count was checked"),
+            },
+            Builtin::Args => {
+                self.arguments(args, 0)?;
+                Ok(Some(Type::Array(Box::new(Type::Str))))
+            }
+            Builtin::Argc => {
+                self.arguments(args, 0)?;
+                Ok(Some(Type::Int))
+            }
+            Builtin::Argv => match self.arguments(args, 1)? {
+                [Type::Int] => Ok(Some(Type::Str)),
+                [other] => Err(self.expected_int(other)),
+                _ => unreachable!("argument count was checked"),
+            },
+            Builtin::Env => match args {
+                [Type::Str] | [Type::Str, Type::Str] => Ok(Some(Type::Str)),
+                [_] | [_, _] => {
+                    let other = args.iter().find(|ty| **ty != Type::Str);
+                    Err(format!(
+                        "env() expects a string, found {}",
+                        other.expect("one isn't a string")
+                    ))
+                }
+                _ => Err(format!(
+                    "env() takes 1 or 2 arguments but {} {} given",
+                    args.len(),
+                    if args.len() == 1 { "was" } else { "were" }
+                )),
+            },
+            Builtin::Int | Builtin::Float => match self.arguments(args, 1)? {
+                [Type::Int | Type::Float | Type::Bool] if *self == Builtin::Int => {
+                    Ok(Some(Type::Int))
+                }
+                [Type::Int | Type::Float | Type::Bool] => Ok(Some(Type::Float)),
+                [Type::Str] => Err(format!(
+                    "{}() expects a number or a bool, found string; parse_num() reads a number from a string",
+                    self.name()
+                )),
+                [other] => Err(format!(
+                    "{}() expects a number or a bool, found {}",
+                    self.name(),
+                    other
+                )),
+                _ => unreachable!("argument count was checked"),
+            },
+            Builtin::Str | Builtin::TypeOf => {
+                self.arguments(args, 1)?;
+                Ok(Some(Type::Str))
+            }
+            Builtin::ParseNum => match self.arguments(args, 1)? {
+                [Type::Str] => Ok(Some(Type::Float)),
+                [other] => Err(self.expected_string(other)),
+                _ => unreachable!("argument count was checked"),
+            },
+            Builtin::Substring => match self.arguments(args, 3)? {
+                [Type::Str, Type::Int, Type::Int] => Ok(Some(Type::Str)),
+                [Type::Str, Type::Int, other] | [Type::Str, other, _] => {
+                    Err(self.expected_int(other))
+                }
+                [other, _, _] => Err(self.expected_string(other)),
+                _ => unreachable!("argument count was checked"),
+            },
+            Builtin::Find | Builtin::Split => match self.arguments(args, 2)? {
+                [Type::Str, Type::Str] if *self == Builtin::Find => Ok(Some(Type::Int)),
+                [Type::Str, Type::Str] => Ok(Some(Type::Array(Box::new(Type::Str)))),
+                [Type::Str, other] | [other, _] => Err(self.expected_string(other)),
+                _ => unreachable!("argument count was checked"),
+            },
+            Builtin::Upper | Builtin::Lower => match self.arguments(args, 1)? {
+                [Type::Str] => Ok(Some(Type::Str)),
+                [other] => Err(self.expected_string(other)),
+                _ => unreachable!("argument count was checked"),
+            },
+            Builtin::Ord => match self.arguments(args, 1)? {
+                [Type::Char] => Ok(Some(Type::Int)),
+                [other] => Err(format!("ord() expects a char, found {}", other)),
+                _ => unreachable!("argument count was checked"),
+            },
+            Builtin::Chr => match self.arguments(args, 1)? {
+                [Type::Int] => Ok(Some(Type::Char)),
+                [other] => Err(self.expected_int(other)),
+                _ => unreachable!("argument count was checked"),
+            },
+            Builtin::ReadFile => match self.arguments(args, 1)? {
+                [Type::Str] => Ok(Some(Type::Str)),
+                [other] => Err(self.expected_string(other)),
+                _ => unreachable!("argument count was checked"),
+            },
+            Builtin::WriteFile | Builtin::AppendFile => match self.arguments(args, 2)? {
+                [Type::Str, Type::Str] => Ok(None),
+                [Type::Str, other] | [other, _] => Err(self.expected_string(other)),
+                _ => unreachable!("argument count was checked"),
+            },
+            Builtin::Error => match self.arguments(args, 1)? {
+                [Type::Str] => Ok(None),
+                [other] => Err(format!("error() expects a string, found {}", other)),
+                _ => unreachable!("argument count was checked"),
+            },
+            Builtin::Apply => match args {
+                [Type::Function { params, result }, args @ ..] => {
+                    if params.len() != args.len() {
+                        return Err(format!(
+                            "apply() passes {} argument{} to a function that takes {}",
+                            args.len(),
+                            if args.len() == 1 { "" } else { "s" },
+                            params.len()
+                        ));
+                    }
+                    for (param, arg) in params.iter().zip(args) {
+                        if !param.accepts(arg) {
+                            return Err(format!(
+                                "apply() passes {} to a parameter of type {}",
+                                arg, param
+                            ));
+                        }
+                    }
+                    Ok(result.as_deref().cloned())
+                }
+                [other, ..] => Err(self.expected_function(other)),
+                [] => Err("apply() takes a function and its arguments".to_string()),
+            },
+            Builtin::Map => match self.arguments(args, 2)? {
+                [function @ Type::Function { params, result }, Type::Array(element)] => {
+                    match (&params[..], result) {
+                        ([param], Some(result)) if param.accepts(element) => {
+                            Ok(Some(Type::Array(result.clone())))
+                        }
+                        ([_], None) => Err(format!(
+                            "map() expects a function that returns a value, found {}",
+                            function
+                        )),
+                        _ => Err(format!(
+                            "map() expects a function taking {}, found {}",
+                            element, function
+                        )),
+                    }
+                }
+                [Type::Function { .. }, other] => Err(self.expected_array(other)),
+                [other, _] => Err(self.expected_function(other)),
+                _ => unreachable!("argument count was checked"),
+            },
+        }
+    }
+
+    /// Call the builtin on arguments of the types [`Builtin::check`]
+    /// accepted, returning its result if it has one
+    pub(crate) fn call(&self, args: &[Value], rng: &mut Rng) -> Result<Option<Value>, String> {
+        match (self, args) {
+            (Builtin::Exit | Builtin::Error, _) => {
+                unreachable!("the interpreters stop the program themselves")
+            }
+            (Builtin::Args | Builtin::Argc | Builtin::Argv, _) => {
+                unreachable!("the interpreters hold the program's arguments")
+            }
+            (Builtin::Apply | Builtin::Map, _) => {
+                unreachable!("the interpreters call functions themselves")
+            }
+            (Builtin::Len, [Value::Array(array)]) => {
+                Ok(Some(Value::Int(array.borrow().elements.len() as i64)))
+            }
+            (Builtin::Len, [Value::Str(text)]) => Ok(Some(Value::Int(text.chars().count() as i64))),
+            (Builtin::Push, [Value::Array(array), value]) => {
+                array.borrow_mut().push(value.clone());
+                Ok(None)
+            }
+            (Builtin::Pop, [Value::Array(array)]) => array.borrow_mut().pop().map(Some),
+            (Builtin::Keys, [Value::Map(map)]) => {
+                let keys = map.borrow().keys().into_iter().map(Value::Str).collect();
+                Ok(Some(interpreter::array(keys)))
+            }
+            (Builtin::Env, [Value::Str(name), rest @ ..]) => {
+                let value = std::env::var(name).ok().or_else(|| match rest {
+                    [Value::Str(default)] => Some(default.clone()),
+                    _ => None,
+                });
+                Ok(Some(Value::Str(value.unwrap_or_default())))
+            }
+            (Builtin::Int, [Value::Int(x)]) => Ok(Some(Value::Int(*x))),
+            (Builtin::Int, [Value::Float(x)]) => {
+                // The range of i64, which NaN isn't in either
+                if !(*x >= -9223372036854775808.0 && *x < 9223372036854775808.0) {
+                    return Err(format!("int() can't convert {} to an int", x));
+                }
+                Ok(Some(Value::Int(*x as i64)))
+            }
+            (Builtin::Int, [Value::Bool(b)]) => Ok(Some(Value::Int(*b as i64))),
+            (Builtin::Float, [Value::Int(x)]) => Ok(Some(Value::Float(*x as f64))),
+            (Builtin::Float, [Value::Float(x)]) => Ok(Some(Value::Float(*x))),
+            (Builtin::Float, [Value::Bool(b)]) => Ok(Some(Value::Float(*b as i64 as f64))),
+            (Builtin::Str, [value]) => Ok(Some(Value::Str(value.to_string()))),
+            // Only when the type wasn't checked: literals, which have the
+            // type of their value, and the REPL's statements, whose values
+            // only tell what kind they are
+            (Builtin::TypeOf, [value]) => Ok(Some(Value::Str(value.type_name().to_string()))),
+            (Builtin::ParseNum, [Value::Str(text)]) => text
+                .parse()
+                .map(|x| Some(Value::Float(x)))
+                .map_err(|_| format!("parse_num() can't parse '{}' as a number", text)),
+            (Builtin::Substring, [Value::Str(text), Value::Int(start), Value::Int(end)]) => {
+                let len = text.chars().count() as i64;
+                if *start < 0 || *start > *end || *end > len {
+                    return Err(format!(
+                        "substring({}, {}) is out of range for a string of length {}",
+                        start, end, len
+                    ));
+                }
+                let part = text
+                    .chars()
+                    .skip(*start as usize)
+                    .take((end - start) as usize)
+                    .collect();
+                Ok(Some(Value::Str(part)))
+            }
+            (Builtin::Find, [Value::Str(text), Value::Str(part)]) => {
+                let index = match text.find(part.as_str()) {
+                    Some(byte) => text[..byte].chars().count() as i64,
+                    None => -1,
+                };
+                Ok(Some(Value::Int(index)))
+            }
+            (Builtin::Split, [Value::Str(text), Value::Str(separator)]) => {
+                if separator.is_empty() {
+                    return Err("split() separator can't be empty".to_string());
+                }
+                let parts = text
+                    .split(separator.as_str())
+                    .map(|part| Value::Str(part.to_string()))
+                    .collect();
+                Ok(Some(interpreter::array(parts)))
+            }
+            (Builtin::Upper, [Value::Str(text)]) => Ok(Some(Value::Str(text.to_ascii_uppercase()))),
+            (Builtin::Lower, [Value::Str(text)]) => Ok(Some(Value::Str(text.to_ascii_lowercase()))),
+            (Builtin::Ord, [Value::Char(c)]) => Ok(Some(Value::Int(*c as i64))),
+            (Builtin::Chr, [Value::Int(n)]) => u32::try_from(*n)
+                .ok()
+                .and_then(char::from_u32)
+                .map(|c| Some(Value::Char(c)))
+                .ok_or_else(|| format!("chr({}) is not a valid character", n)),
+            (Builtin::ReadFile, [Value::Str(path)]) => fs::read_to_string(path)
+                .map(|text| Some(Value::Str(text)))
+                .map_err(|err| file_error("read", path, err)),
+            (Builtin::WriteFile, [Value::Str(path), Value::Str(text)]) => fs::write(path, text)
+                .map(|_| None)
+                .map_err(|err| file_error("write", path, err)),
+            (Builtin::AppendFile, [Value::Str(path), Value::Str(text)]) => OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(path)
+                .and_then(|mut file| file.write_all(text.as_bytes()))
+                .map(|_| None)
+                .map_err(|err| file_error("append to", path, err)),
+            (Builtin::Rand, []) => Ok(Some(Value::Float(rng.float()))),
+            (Builtin::RandRange, [Value::Int(low), Value::Int(high)]) => {
+                Ok(Some(Value::Int(rng.range(*low, *high)?)))
+            }
+            (Builtin::Seed, [Value::Int(seed)]) => {
+                rng.seed(*seed);
+                Ok(None)
+            }
+            (Builtin::Abs, [Value::Int(x)]) => Ok(Some(Value::Int(x.wrapping_abs()))),
+            (Builtin::Floor | Builtin::Ceil, [Value::Int(x)]) => Ok(Some(Value::Int(*x))),
+            (Builtin::Min, [Value::Int(x), Value::Int(y)]) => Ok(Some(Value::Int(*x.min(y)))),
+            (Builtin::Max, [Value::Int(x), Value::Int(y)]) => Ok(Some(Value::Int(*x.max(y)))),
+            (builtin, args) if builtin.is_math() => {
+                let args = args
+                    .iter()
+                    .map(|arg| match arg {
+                        Value::Int(x) => Ok(*x as f64),
+                        Value::Float(x) => Ok(*x),
+                        _ => Err(format!("Invalid arguments to {}()", self.name())),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let result = match (builtin, &args[..]) {
+                    (Builtin::Sqrt, [x]) => x.sqrt(),
+                    (Builtin::Abs, [x]) => x.abs(),
+                    (Builtin::Floor, [x]) => x.floor(),
+                    (Builtin::Ceil, [x]) => x.ceil(),
+                    (Builtin::Sin, [x]) => x.sin(),
+                    (Builtin::Cos, [x]) => x.cos(),
+                    (Builtin::Pow, [x, y]) => x.powf(*y),
+                    (Builtin::Min, [x, y]) => x.min(*y),
+                    (Builtin::Max, [x, y]) => x.max(*y),
+                    _ => return Err(format!("Invalid arguments to {}()", self.name())),
+                };
+                Ok(Some(Value::Float(result)))
+            }
+            _ => Err(format!("Invalid arguments to {}()", self.name())),
+        }
+    }
+
+    /// The arguments, if there are as many as the builtin takes
+    fn arguments<'a>(&self, args: &'a [Type], count: usize) -> Result<&'a [Type], String> {
+        if args.len() != count {
+            return Err(wrong_arity(self.name(), count, args.len()));
+        }
+        Ok(args)
+    }
+
+    /// The arguments, if there are `count` of them and all are numbers
+    fn numbers<'a>(&self, args: &'a [Type], count: usize) -> Result<&'a [Type], String> {
+        let args = self.arguments(args, count)?;
+        match args.iter().find(|ty| !ty.is_numeric()) {
+            Some(other) => Err(format!(
+                "{}() expects a number, found {}",
+                self.name(),
+                other
+            )),
+            None => Ok(args),
+        }
+    }
+
+    fn expected_int(&self, found: &Type) -> String {
+        format!("{}() expects an int, found {}", self.name(), found)
+    }
+
+    fn expected_string(&self, found: &Type) -> String {
+        format!("{}() expects a string, found {}", self.name(), found)
+    }
+
+    fn expected_array(&self, found: &Type) -> String {
+        format!("{}() expects an array, found {}", self.name(), found)
+    }
+
+    fn expected_function(&self, found: &Type) -> String {
+        format!("{}() expects a function, found {}", self.name(), found)
+    }
+}
+
+/// The code `exit(code)` stops the program with, which must fit in the 8
+/// bits an exit status has
+pub(crate) fn exit_code(args: &[Value]) -> Result<i32, String> {
+    match args {
+        [Value::Int(code)] if (0..=255).contains(code) => Ok(*code as i32),
+        [Value::Int(code)] => Err(format!("exit() code must be from 0 to 255, found {}", code)),
+        _ => Err("Invalid arguments to exit()".to_string()),
+    }
+}
+
+/// The message `error(message)` stops the program with
+pub(crate) fn error_message(args: &[Value]) -> String {
+    match args {
+        [Value::Str(message)] => message.clone(),
+        _ => "Invalid arguments to error()".to_string(),
+    }
+}
+
+/// The error for a file that couldn't be read or written. It leaves out
+/// the OS error number, so it reads like the compiled programs' `strerror`.
+fn file_error(action: &str, path: &str, err: io::Error) -> String {
+    let reason = err.to_string();
+    let reason = match reason.find(" (os error") {
+        Some(end) => &reason[..end],
+        None => &reason,
+    };
+    format!("Cannot {} '{}': {}", action, path, reason)
+}
+
+/// Call `args()`, `argc()` or `argv(index)` for a program run with
+/// `arguments`
+pub(crate) fn read_arguments(
+    builtin: Builtin,
+    arguments: &[String],
+    args: &[Value],
+) -> Result<Value, String> {
+    match (builtin, args) {
+        (Builtin::Args, []) => Ok(interpreter::array(
+            arguments.iter().cloned().map(Value::Str).collect(),
+        )),
+        (Builtin::Argc, []) => Ok(Value::Int(arguments.len() as i64)),
+        (Builtin::Argv, [Value::Int(index)]) => usize::try_from(*index)
+            .ok()
+            .and_then(|index| arguments.get(index))
+            .map(|argument| Value::Str(argument.clone()))
+            .ok_or_else(|| {
+                format!(
+                    "argv({}) is out of range for {} argument{}",
+                    index,
+                    arguments.len(),
+                    if arguments.len() == 1 { "" } else { "s" }
+                )
+            }),
+        _ => Err(format!("Invalid arguments to {}()", builtin.name())),
+    }
+}
+
+/// Error for a call to `name` with `given` arguments instead of `count`
+pub(crate) fn wrong_arity(name: &str, count: usize, given: usize) -> String {
+    format!(
+        "{}() takes {} argument{} but {} {} given",
+        name,
+        count,
+        if count == 1 { "" } else { "s" },
+        given,
+        if given == 1 { "was" } else { "were" }
+    )
+}
+
+impl fmt::Display for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -0,0 +1,1430 @@
+//! A stack-based virtual machine, an alternative to the tree-walking interpreter.
+//!
+//! [`Compiler`] flattens the AST into a [`Chunk`] of [`Instruction`]s, which
+//! [`Vm`] then executes. Variables live in numbered slots that are resolved
+//! at compile time, so running a program never looks anything up by name.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, Stdout, Write};
+use std::rc::Rc;
+
+use crate::builtins::{self, Builtin, Rng};
+use crate::checks::Checks;
+use crate::desugar;
+use crate::error::CompileError;
+use crate::format;
+use crate::interpreter::{
+    self, compare, read_from, Closure, RuntimeError, Value, DEFAULT_MAX_CALL_DEPTH,
+};
+use crate::lexer::Span;
+use crate::parser::{self, ASTNode, Ast, BinaryOperator, NodeId, Param, UnaryOperator};
+use crate::semantic_analyzer::{SemanticAnalyzer, Type};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    /// Push a constant
+    Push(Value),
+
+    /// Push the value of a variable
+    Load(usize),
+
+    /// Pop a value into a newly declared variable
+    Init(usize),
+
+    /// Pop a value into an existing variable, promoting integers stored into floats
+    Store(usize),
+
+    /// Pop two operands and push the result of an arithmetic or comparison operator
+    Binary(BinaryOperator),
+
+    /// Pop a number and push its negation
+    Negate,
+
+    /// Pop a number and push it as a float, for a value stored where a
+    /// float is annotated
+    ToFloat,
+
+    /// Pop a value and push whether it is falsy
+    Not,
+
+    /// Pop a value and push whether it is truthy
+    Truthy,
+
+    /// Continue at the given instruction
+    Jump(usize),
+
+    /// Pop a value and continue at the given instruction if it is falsy
+    JumpIfFalse(usize),
+
+    /// Pop one value per gap between the pieces of text, and print them
+    /// between the pieces, followed by a newline for `println`
+    Print { pieces: Vec<String>, newline: bool },
+
+    /// Pop one value per gap between the pieces of text, and push the
+    /// string of them between the pieces
+    Format(Vec<String>),
+
+    /// Read a number of the variable's type from input into it
+    Input(usize),
+
+    /// Pop the given number of values and push an array of them
+    Array(usize),
+
+    /// Pop the given number of keys and values, alternating, and push a map
+    /// of them
+    Map(usize),
+
+    /// Pop a value for each field, in order, and push a struct of them
+    Struct { name: String, fields: Vec<String> },
+
+    /// Pop a struct and push the value of its field
+    GetField(String),
+
+    /// Pop a value and a struct, and store the value in the struct's field
+    SetField(String),
+
+    /// Pop an index and an array, and push the element at the index
+    Index,
+
+    /// Pop the bounds that were given and an array, and push a copy of the
+    /// elements between them
+    Slice { start: bool, end: bool },
+
+    /// Pop a value, an index and an array, and store the value at the index
+    SetIndex,
+
+    /// Pop the given number of arguments and push the result of the
+    /// builtin, if it has one
+    Call(Builtin, usize),
+
+    /// Call the function with the given index in [`Chunk::functions`],
+    /// popping its arguments into the first slots of a new frame
+    CallFunction(usize),
+
+    /// Pop a value for each captured variable, in order, and push a closure
+    /// of the lambda holding them
+    Closure {
+        lambda: NodeId,
+        captures: Vec<String>,
+    },
+
+    /// Pop a closure or a function and then the given number of arguments,
+    /// and call it with the arguments in a new frame. A closure's captured
+    /// values go in the slots before them. The call always leaves a result,
+    /// so a placeholder is pushed for a function without one.
+    CallValue(usize),
+
+    /// Leave the current function. The flag says whether it leaves its
+    /// result on top of the stack for the caller.
+    Return(bool),
+
+    /// Pop a value and throw it away
+    Pop,
+
+    /// Stop the program with a runtime error
+    Fail(String),
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::Push(Value::Str(text)) => write!(f, "push {:?}", text),
+            Instruction::Push(Value::Char(c)) => write!(f, "push {:?}", c),
+            Instruction::Push(val) => write!(f, "push {}", val),
+            Instruction::Load(slot) => write!(f, "load {}", slot),
+            Instruction::Init(slot) => write!(f, "init {}", slot),
+            Instruction::Store(slot) => write!(f, "store {}", slot),
+            Instruction::Binary(op) => write!(f, "binary {}", op.symbol()),
+            Instruction::Negate => write!(f, "negate"),
+            Instruction::ToFloat => write!(f, "to_float"),
+            Instruction::Not => write!(f, "not"),
+            Instruction::Truthy => write!(f, "truthy"),
+            Instruction::Jump(target) => write!(f, "jump {}", target),
+            Instruction::JumpIfFalse(target) => write!(f, "jump_if_false {}", target),
+            Instruction::Format(pieces) => write!(f, "format {:?}", format::unparse(pieces)),
+            Instruction::Print { pieces, newline } => {
+                write!(f, "{}", if *newline { "println" } else { "print" })?;
+                let values = pieces.len() - 1;
+                if *pieces != format::separators(values) {
+                    write!(f, " {:?}", format::unparse(pieces))
+                } else if values != 1 {
+                    write!(f, " {}", values)
+                } else {
+                    Ok(())
+                }
+            }
+            Instruction::Input(slot) => write!(f, "input {}", slot),
+            Instruction::Array(len) => write!(f, "array {}", len),
+            Instruction::Map(len) => write!(f, "map {}", len),
+            Instruction::Struct { name, fields } => {
+                write!(f, "struct {} {}", name, fields.join(" "))
+            }
+            Instruction::GetField(field) => write!(f, "get_field {}", field),
+            Instruction::SetField(field) => write!(f, "set_field {}", field),
+            Instruction::Index => write!(f, "index"),
+            Instruction::Slice { start, end } => write!(
+                f,
+                "slice {}:{}",
+                if *start { "start" } else { "" },
+                if *end { "end" } else { "" }
+            ),
+            Instruction::SetIndex => write!(f, "set_index"),
+            Instruction::Call(builtin, args) => write!(f, "call {} {}", builtin, args),
+            Instruction::CallFunction(function) => write!(f, "call_function {}", function),
+            Instruction::Closure { captures, .. } => {
+                write!(f, "closure")?;
+                captures
+                    .iter()
+                    .try_for_each(|capture| write!(f, " {}", capture))
+            }
+            Instruction::CallValue(args) => write!(f, "call_value {}", args),
+            Instruction::Return(true) => write!(f, "return 1"),
+            Instruction::Return(false) => write!(f, "return 0"),
+            Instruction::Pop => write!(f, "pop"),
+            Instruction::Fail(message) => write!(f, "fail {:?}", message),
+        }
+    }
+}
+
+/// A compiled program
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Chunk {
+    pub code: Vec<Instruction>,
+
+    /// Source location of each instruction, for runtime errors
+    pub spans: Vec<Span>,
+
+    /// Number of variable slots the program needs
+    pub slots: usize,
+
+    /// The functions the code calls
+    pub functions: Vec<Function>,
+
+    /// Index in [`Chunk::functions`] of the code of each lambda
+    pub lambdas: HashMap<NodeId, usize>,
+}
+
+/// A compiled function, whose code is part of its chunk's
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+
+    /// Index of the function's first instruction
+    pub address: usize,
+
+    /// Number of arguments, which go in the first slots. A lambda's
+    /// captured values come before them.
+    pub params: usize,
+
+    /// Number of variable slots each call needs, including the arguments'
+    pub slots: usize,
+
+    /// Whether the function returns a value
+    pub returns: bool,
+}
+
+impl fmt::Display for Chunk {
+    /// One numbered instruction per line, with the name of each function
+    /// above its first instruction
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, instruction) in self.code.iter().enumerate() {
+            for function in self
+                .functions
+                .iter()
+                .filter(|function| function.address == i)
+            {
+                writeln!(f, "{}:", function.name)?;
+            }
+            writeln!(f, "{:04} {}", i, instruction)?;
+        }
+        Ok(())
+    }
+}
+
+/// Jumps out of a loop's body, patched once the loop is compiled
+#[derive(Debug, Default)]
+struct LoopJumps {
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+/// Compiles the AST into bytecode
+pub struct Compiler {
+    chunk: Chunk,
+
+    /// Slots of the variables in each enclosing scope, innermost last
+    scopes: Vec<HashMap<String, usize>>,
+
+    /// The fields of each declared struct, in order
+    structs: HashMap<String, Vec<String>>,
+
+    /// Jumps from `break` and `continue` in each enclosing loop, innermost
+    /// last
+    loops: Vec<LoopJumps>,
+
+    /// Index in [`Chunk::functions`] of each declared function, and whether
+    /// it returns a value
+    functions: HashMap<String, (usize, bool)>,
+
+    /// Whether the function being compiled is annotated to return a float,
+    /// so returned ints must be converted
+    float_result: bool,
+
+    /// The checked program, for the types `typeof()` gives, analyzed the
+    /// first time one is needed
+    checked: Option<Option<SemanticAnalyzer>>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Self {
+            chunk: Chunk::default(),
+            scopes: vec![HashMap::new()],
+            structs: HashMap::new(),
+            loops: Vec::new(),
+            functions: HashMap::new(),
+            float_result: false,
+            checked: None,
+        }
+    }
+
+    pub fn compile(mut self, ast: &Ast) -> Result<Chunk, CompileError> {
+        let ast = &desugar::desugared(ast);
+        self.statement(ast, ast.root())
+            .map_err(|message| CompileError::Codegen { message })?;
+        Ok(self.chunk)
+    }
+
+    fn statement(&mut self, ast: &Ast, node: NodeId) -> Result<(), String> {
+        match &ast[node] {
+            ASTNode::Program(statements) => {
+                // Functions may call ones declared after them
+                for stmt in statements {
+                    if let ASTNode::FunctionDeclaration { name, body, .. } = &ast[*stmt] {
+                        self.function_index(ast, name, *body);
+                    }
+                }
+                for stmt in statements {
+                    self.statement(ast, *stmt)?;
+                }
+            }
+            ASTNode::LetDeclaration {
+                name,
+                ty,
+                value,
+                span,
+                ..
+            } => {
+                self.expression(ast, *value)?;
+                if *ty == Some(Type::Float) {
+                    self.emit(Instruction::ToFloat, *span);
+                }
+                let slot = self.declare(name);
+                self.emit(Instruction::Init(slot), *span);
+            }
+            ASTNode::ConstDeclaration { name, value, span } => {
+                self.expression(ast, *value)?;
+                let slot = self.declare(name);
+                self.emit(Instruction::Init(slot), *span);
+            }
+            ASTNode::StructDeclaration { name, fields, .. } => {
+                self.structs.insert(name.clone(), fields.clone());
+            }
+            ASTNode::Assignment { name, value, span } => {
+                self.expression(ast, *value)?;
+                let slot = self.resolve(name)?;
+                self.emit(Instruction::Store(slot), *span);
+            }
+            ASTNode::Input { name, span } => {
+                let slot = self.resolve(name)?;
+                self.emit(Instruction::Input(slot), *span);
+            }
+            ASTNode::IndexAssignment {
+                array,
+                index,
+                value,
+                span,
+            } => {
+                self.expression(ast, *array)?;
+                self.expression(ast, *index)?;
+                self.expression(ast, *value)?;
+                self.emit(Instruction::SetIndex, *span);
+            }
+            ASTNode::FieldAssignment {
+                object,
+                field,
+                value,
+                span,
+            } => {
+                self.expression(ast, *object)?;
+                self.expression(ast, *value)?;
+                self.emit(Instruction::SetField(field.clone()), *span);
+            }
+            ASTNode::Call { name, .. } => {
+                self.expression(ast, node)?;
+                if self.has_result(name) {
+                    self.emit(Instruction::Pop, ast[node].span());
+                }
+            }
+            ASTNode::Print {
+                format,
+                args,
+                newline,
+                span,
+            } => {
+                for arg in args {
+                    self.expression(ast, *arg)?;
+                }
+                let pieces = format::print_pieces(format.as_deref(), args.len())?;
+                self.emit(
+                    Instruction::Print {
+                        pieces,
+                        newline: *newline,
+                    },
+                    *span,
+                );
+            }
+            ASTNode::Block(statements) => {
+                self.scopes.push(HashMap::new());
+                let result = statements
+                    .iter()
+                    .try_for_each(|stmt| self.statement(ast, *stmt));
+                self.scopes.pop();
+                result?;
+            }
+            ASTNode::If {
+                condition,
+                then_branch,
+                else_branch,
+                span,
+            } => {
+                self.expression(ast, *condition)?;
+                let to_else = self.emit(Instruction::JumpIfFalse(0), *span);
+                self.statement(ast, *then_branch)?;
+                match else_branch {
+                    Some(else_branch) => {
+                        let to_end = self.emit(Instruction::Jump(0), *span);
+                        self.patch(to_else);
+                        self.statement(ast, *else_branch)?;
+                        self.patch(to_end);
+                    }
+                    None => self.patch(to_else),
+                }
+            }
+            ASTNode::While {
+                condition,
+                body,
+                span,
+            } => {
+                let start = self.chunk.code.len();
+                self.expression(ast, *condition)?;
+                let to_end = self.emit(Instruction::JumpIfFalse(0), *span);
+                let jumps = self.loop_body(ast, *body)?;
+                for at in jumps.continues {
+                    self.jump_to(at, start);
+                }
+                self.emit(Instruction::Jump(start), *span);
+                self.patch(to_end);
+                for at in jumps.breaks {
+                    self.patch(at);
+                }
+            }
+            ASTNode::Assert {
+                condition,
+                message,
+                span,
+            } => {
+                self.expression(ast, *condition)?;
+                self.emit(Instruction::Not, *span);
+                let to_end = self.emit(Instruction::JumpIfFalse(0), *span);
+                let message = interpreter::assertion_failed(message.as_deref());
+                self.emit(Instruction::Fail(message), *span);
+                self.patch(to_end);
+            }
+            ASTNode::DoWhile {
+                body,
+                condition,
+                span,
+            } => {
+                let start = self.chunk.code.len();
+                let jumps = self.loop_body(ast, *body)?;
+                // `continue` goes on to check the condition
+                for at in jumps.continues {
+                    self.patch(at);
+                }
+                self.expression(ast, *condition)?;
+                let to_end = self.emit(Instruction::JumpIfFalse(0), *span);
+                self.emit(Instruction::Jump(start), *span);
+                self.patch(to_end);
+                for at in jumps.breaks {
+                    self.patch(at);
+                }
+            }
+            ASTNode::For {
+                init,
+                condition,
+                step,
+                body,
+                span,
+            } => {
+                // The initializer's variables belong to the loop's scope
+                self.scopes.push(HashMap::new());
+                let result = self.for_loop(ast, init, condition, step, *body, *span);
+                self.scopes.pop();
+                result?;
+            }
+            ASTNode::ForIn {
+                name,
+                range,
+                body,
+                span,
+            } => {
+                self.scopes.push(HashMap::new());
+                let result = self.for_in_loop(ast, name, *range, *body, *span);
+                self.scopes.pop();
+                result?;
+            }
+            ASTNode::Break { span } => {
+                let at = self.emit(Instruction::Jump(0), *span);
+                self.innermost_loop("break")?.breaks.push(at);
+            }
+            ASTNode::Continue { span } => {
+                let at = self.emit(Instruction::Jump(0), *span);
+                self.innermost_loop("continue")?.continues.push(at);
+            }
+            ASTNode::FunctionDeclaration {
+                name,
+                params,
+                result,
+                body,
+                span,
+            } => {
+                let over = self.emit(Instruction::Jump(0), *span);
+                let float_result =
+                    std::mem::replace(&mut self.float_result, *result == Some(Type::Float));
+                let compiled = self.function(ast, name, params, *body, *span);
+                self.float_result = float_result;
+                compiled?;
+                self.patch(over);
+            }
+            ASTNode::Return { value, span } => {
+                if let Some(value) = value {
+                    self.expression(ast, *value)?;
+                    if self.float_result {
+                        self.emit(Instruction::ToFloat, *span);
+                    }
+                }
+                self.emit(Instruction::Return(value.is_some()), *span);
+            }
+            _ => return Err("Unexpected AST node".to_string()),
+        }
+        Ok(())
+    }
+
+    /// Compile a function where it's declared. It has slots of its own,
+    /// starting with its parameters, and can't see the variables around it.
+    fn function(
+        &mut self,
+        ast: &Ast,
+        name: &str,
+        params: &[Param],
+        body: NodeId,
+        span: Span,
+    ) -> Result<(), String> {
+        let index = self.function_index(ast, name, body);
+        self.chunk.functions[index].address = self.chunk.code.len();
+        self.chunk.functions[index].params = params.len();
+
+        let slots = std::mem::take(&mut self.chunk.slots);
+        let scopes = std::mem::replace(&mut self.scopes, vec![HashMap::new()]);
+        let loops = std::mem::take(&mut self.loops);
+        for param in params {
+            let slot = self.declare(&param.name);
+            // Ints passed where a float is annotated become floats
+            if param.ty == Some(Type::Float) {
+                self.emit(Instruction::Load(slot), span);
+                self.emit(Instruction::ToFloat, span);
+                self.emit(Instruction::Init(slot), span);
+            }
+        }
+        let result = self.statement(ast, body);
+        // Reached when the body doesn't end with a return
+        self.emit(Instruction::Return(false), span);
+        self.chunk.functions[index].slots = self.chunk.slots;
+        self.chunk.slots = slots;
+        self.scopes = scopes;
+        self.loops = loops;
+        result
+    }
+
+    /// Compile a lambda where it's made, leaving a closure of it on the
+    /// stack. Its code is jumped over, and has slots for the values it
+    /// captures followed by its parameters.
+    fn lambda(
+        &mut self,
+        ast: &Ast,
+        lambda: NodeId,
+        params: &[Param],
+        body: NodeId,
+        span: Span,
+    ) -> Result<(), String> {
+        let captures: Vec<_> = parser::free_names(ast, params, body)
+            .into_iter()
+            .filter_map(|name| Some((self.resolve(&name).ok()?, name)))
+            .collect();
+        let over = self.emit(Instruction::Jump(0), span);
+        self.chunk.functions.push(Function {
+            name: "<lambda>".to_string(),
+            address: self.chunk.code.len(),
+            params: captures.len() + params.len(),
+            slots: 0,
+            returns: true,
+        });
+        let index = self.chunk.functions.len() - 1;
+        self.chunk.lambdas.insert(lambda, index);
+
+        let slots = std::mem::take(&mut self.chunk.slots);
+        let scopes = std::mem::replace(&mut self.scopes, vec![HashMap::new()]);
+        let loops = std::mem::take(&mut self.loops);
+        let float_result = std::mem::take(&mut self.float_result);
+        for (_, name) in &captures {
+            self.declare(name);
+        }
+        for param in params {
+            let slot = self.declare(&param.name);
+            if param.ty == Some(Type::Float) {
+                self.emit(Instruction::Load(slot), span);
+                self.emit(Instruction::ToFloat, span);
+                self.emit(Instruction::Init(slot), span);
+            }
+        }
+        let result = self.expression(ast, body);
+        // A call without a result still leaves something for the caller to pop
+        if matches!(&ast[body], ASTNode::Call { name, .. } if !self.has_result(name)) {
+            self.emit(Instruction::Push(Value::Int(0)), span);
+        }
+        self.emit(Instruction::Return(true), span);
+        self.chunk.functions[index].slots = self.chunk.slots;
+        self.chunk.slots = slots;
+        self.scopes = scopes;
+        self.loops = loops;
+        self.float_result = float_result;
+        result?;
+
+        self.patch(over);
+        for (slot, _) in &captures {
+            self.emit(Instruction::Load(*slot), span);
+        }
+        let captures = captures.into_iter().map(|(_, name)| name).collect();
+        self.emit(Instruction::Closure { lambda, captures }, span);
+        Ok(())
+    }
+
+    /// The index of a function in [`Chunk::functions`], adding it the first
+    /// time. Its code and slots are filled in when it's compiled.
+    fn function_index(&mut self, ast: &Ast, name: &str, body: NodeId) -> usize {
+        if let Some((index, _)) = self.functions.get(name) {
+            return *index;
+        }
+        let returns = ast[body].returns_value(ast);
+        self.chunk.functions.push(Function {
+            name: name.to_string(),
+            address: 0,
+            params: 0,
+            slots: 0,
+            returns,
+        });
+        let index = self.chunk.functions.len() - 1;
+        self.functions.insert(name.to_string(), (index, returns));
+        index
+    }
+
+    /// Whether a call of `name` leaves a value on the stack. Calling a
+    /// lambda always does.
+    fn has_result(&self, name: &str) -> bool {
+        if self.resolve(name).is_ok() {
+            return true;
+        }
+        match self.functions.get(name) {
+            Some((_, returns_value)) => *returns_value,
+            None => Builtin::from_name(name).is_some_and(|builtin| builtin.has_result()),
+        }
+    }
+
+    /// Compile the body of a loop, returning its `break` and `continue`
+    /// jumps for the loop to patch
+    fn loop_body(&mut self, ast: &Ast, body: NodeId) -> Result<LoopJumps, String> {
+        self.loops.push(LoopJumps::default());
+        let result = self.statement(ast, body);
+        let jumps = self.loops.pop().expect("pushed above");
+        result.map(|_| jumps)
+    }
+
+    fn innermost_loop(&mut self, keyword: &str) -> Result<&mut LoopJumps, String> {
+        self.loops
+            .last_mut()
+            .ok_or_else(|| format!("'{}' can only be used inside a loop", keyword))
+    }
+
+    fn for_loop(
+        &mut self,
+        ast: &Ast,
+        init: &Option<NodeId>,
+        condition: &Option<NodeId>,
+        step: &Option<NodeId>,
+        body: NodeId,
+        span: Span,
+    ) -> Result<(), String> {
+        if let Some(init) = init {
+            self.statement(ast, *init)?;
+        }
+        let start = self.chunk.code.len();
+        let to_end = match condition {
+            Some(condition) => {
+                self.expression(ast, *condition)?;
+                Some(self.emit(Instruction::JumpIfFalse(0), span))
+            }
+            None => None,
+        };
+        let jumps = self.loop_body(ast, body)?;
+        for at in jumps.continues {
+            self.patch(at);
+        }
+        if let Some(step) = step {
+            self.statement(ast, *step)?;
+        }
+        self.emit(Instruction::Jump(start), span);
+        if let Some(to_end) = to_end {
+            self.patch(to_end);
+        }
+        for at in jumps.breaks {
+            self.patch(at);
+        }
+        Ok(())
+    }
+
+    /// Compile `for name in range`, counting in the loop variable's slot up to
+    /// the end of the range, which is kept in a hidden slot
+    fn for_in_loop(
+        &mut self,
+        ast: &Ast,
+        name: &str,
+        range: NodeId,
+        body: NodeId,
+        span: Span,
+    ) -> Result<(), String> {
+        let ASTNode::Range {
+            start,
+            end,
+            inclusive,
+            ..
+        } = ast[range]
+        else {
+            unreachable!("the parser only puts ranges in for-in loops");
+        };
+        // Both bounds are evaluated before the loop variable exists
+        self.expression(ast, start)?;
+        self.expression(ast, end)?;
+        // Not an identifier, so no variable can refer to it
+        let end = self.declare("..end");
+        self.emit(Instruction::Init(end), span);
+        let counter = self.declare(name);
+        self.emit(Instruction::Init(counter), span);
+
+        let start = self.chunk.code.len();
+        self.emit(Instruction::Load(counter), span);
+        self.emit(Instruction::Load(end), span);
+        let compare = if inclusive {
+            BinaryOperator::LessEqual
+        } else {
+            BinaryOperator::Less
+        };
+        self.emit(Instruction::Binary(compare), span);
+        let to_end = self.emit(Instruction::JumpIfFalse(0), span);
+        let mut jumps = self.loop_body(ast, body)?;
+        for at in jumps.continues {
+            self.patch(at);
+        }
+        if inclusive {
+            // Stop at the end rather than count past it, which could overflow
+            self.emit(Instruction::Load(counter), span);
+            self.emit(Instruction::Load(end), span);
+            self.emit(Instruction::Binary(BinaryOperator::Equal), span);
+            let to_step = self.emit(Instruction::JumpIfFalse(0), span);
+            jumps.breaks.push(self.emit(Instruction::Jump(0), span));
+            self.patch(to_step);
+        }
+        self.emit(Instruction::Load(counter), span);
+        self.emit(Instruction::Push(Value::Int(1)), span);
+        self.emit(Instruction::Binary(BinaryOperator::Plus), span);
+        self.emit(Instruction::Store(counter), span);
+        self.emit(Instruction::Jump(start), span);
+        self.patch(to_end);
+        for at in jumps.breaks {
+            self.patch(at);
+        }
+        Ok(())
+    }
+
+    /// Compile `map(function, array)` into a loop that calls the function on
+    /// each element and pushes the results onto a new array. What the loop
+    /// keeps track of is held in hidden slots.
+    fn map(
+        &mut self,
+        ast: &Ast,
+        function: NodeId,
+        array: NodeId,
+        span: Span,
+    ) -> Result<(), String> {
+        self.expression(ast, function)?;
+        let function = self.declare("..function");
+        self.emit(Instruction::Init(function), span);
+        self.expression(ast, array)?;
+        let array = self.declare("..array");
+        self.emit(Instruction::Init(array), span);
+        self.emit(Instruction::Array(0), span);
+        let results = self.declare("..results");
+        self.emit(Instruction::Init(results), span);
+        self.emit(Instruction::Push(Value::Int(0)), span);
+        let counter = self.declare("..index");
+        self.emit(Instruction::Init(counter), span);
+
+        let start = self.chunk.code.len();
+        self.emit(Instruction::Load(counter), span);
+        self.emit(Instruction::Load(array), span);
+        self.emit(Instruction::Call(Builtin::Len, 1), span);
+        self.emit(Instruction::Binary(BinaryOperator::Less), span);
+        let to_end = self.emit(Instruction::JumpIfFalse(0), span);
+        self.emit(Instruction::Load(results), span);
+        self.emit(Instruction::Load(array), span);
+        self.emit(Instruction::Load(counter), span);
+        self.emit(Instruction::Index, span);
+        self.emit(Instruction::Load(function), span);
+        self.emit(Instruction::CallValue(1), span);
+        self.emit(Instruction::Call(Builtin::Push, 2), span);
+        self.emit(Instruction::Load(counter), span);
+        self.emit(Instruction::Push(Value::Int(1)), span);
+        self.emit(Instruction::Binary(BinaryOperator::Plus), span);
+        self.emit(Instruction::Store(counter), span);
+        self.emit(Instruction::Jump(start), span);
+        self.patch(to_end);
+        self.emit(Instruction::Load(results), span);
+        Ok(())
+    }
+
+    /// Emit code that leaves the expression's value on the stack
+    fn expression(&mut self, ast: &Ast, expr: NodeId) -> Result<(), String> {
+        let span = ast[expr].span();
+        match &ast[expr] {
+            ASTNode::Int(num) => {
+                self.emit(Instruction::Push(Value::Int(*num)), span);
+            }
+            ASTNode::Number(num) => {
+                self.emit(Instruction::Push(Value::Float(*num)), span);
+            }
+            ASTNode::Bool(b) => {
+                self.emit(Instruction::Push(Value::Bool(*b)), span);
+            }
+            ASTNode::Str(text) => {
+                self.emit(Instruction::Push(Value::Str(text.clone())), span);
+            }
+            ASTNode::Char(c) => {
+                self.emit(Instruction::Push(Value::Char(*c)), span);
+            }
+            ASTNode::Interpolation {
+                pieces,
+                values,
+                span,
+            } => {
+                for value in values {
+                    self.expression(ast, *value)?;
+                }
+                self.emit(Instruction::Format(pieces.clone()), *span);
+            }
+            // A declared function's name is a value when no variable hides it
+            ASTNode::Identifier { name, span }
+                if self.resolve(name).is_err() && self.functions.contains_key(name) =>
+            {
+                self.emit(Instruction::Push(Value::Function(name.clone())), *span);
+            }
+            ASTNode::Identifier { name, span } => {
+                let slot = self.resolve(name)?;
+                self.emit(Instruction::Load(slot), *span);
+            }
+            ASTNode::BinaryOp {
+                left,
+                op: BinaryOperator::And,
+                right,
+                span,
+            } => {
+                // Short-circuit: the right side only runs when the left is truthy
+                self.expression(ast, *left)?;
+                let to_false = self.emit(Instruction::JumpIfFalse(0), *span);
+                self.expression(ast, *right)?;
+                self.emit(Instruction::Truthy, *span);
+                let to_end = self.emit(Instruction::Jump(0), *span);
+                self.patch(to_false);
+                self.emit(Instruction::Push(Value::Bool(false)), *span);
+                self.patch(to_end);
+            }
+            ASTNode::BinaryOp {
+                left,
+                op: BinaryOperator::Or,
+                right,
+                span,
+            } => {
+                // Short-circuit: the right side only runs when the left is falsy
+                self.expression(ast, *left)?;
+                let to_right = self.emit(Instruction::JumpIfFalse(0), *span);
+                self.emit(Instruction::Push(Value::Bool(true)), *span);
+                let to_end = self.emit(Instruction::Jump(0), *span);
+                self.patch(to_right);
+                self.expression(ast, *right)?;
+                self.emit(Instruction::Truthy, *span);
+                self.patch(to_end);
+            }
+            ASTNode::BinaryOp {
+                left,
+                op,
+                right,
+                span,
+            } => {
+                self.expression(ast, *left)?;
+                self.expression(ast, *right)?;
+                self.emit(Instruction::Binary(*op), *span);
+            }
+            ASTNode::UnaryOp { op, operand, span } => {
+                self.expression(ast, *operand)?;
+                let instruction = match op {
+                    UnaryOperator::Not => Instruction::Not,
+                    UnaryOperator::Negate => Instruction::Negate,
+                };
+                self.emit(instruction, *span);
+            }
+            ASTNode::Conditional {
+                condition,
+                then_value,
+                else_value,
+                span,
+            } => {
+                self.expression(ast, *condition)?;
+                let to_else = self.emit(Instruction::JumpIfFalse(0), *span);
+                self.expression(ast, *then_value)?;
+                let to_end = self.emit(Instruction::Jump(0), *span);
+                self.patch(to_else);
+                self.expression(ast, *else_value)?;
+                self.patch(to_end);
+            }
+            ASTNode::ArrayLiteral { elements, span } => {
+                for element in elements {
+                    self.expression(ast, *element)?;
+                }
+                self.emit(Instruction::Array(elements.len()), *span);
+            }
+            ASTNode::MapLiteral { entries, span } => {
+                for (key, value) in entries {
+                    self.expression(ast, *key)?;
+                    self.expression(ast, *value)?;
+                }
+                self.emit(Instruction::Map(entries.len()), *span);
+            }
+            ASTNode::StructLiteral { name, fields, span } => {
+                let declared = self
+                    .structs
+                    .get(name)
+                    .ok_or_else(|| format!("Unknown struct '{}'", name))?
+                    .clone();
+                // Fields are evaluated in the order they're declared
+                for field in &declared {
+                    let (_, value) = fields
+                        .iter()
+                        .find(|(given, _)| given == field)
+                        .ok_or_else(|| format!("Missing field '{}' in {}", field, name))?;
+                    self.expression(ast, *value)?;
+                }
+                self.emit(
+                    Instruction::Struct {
+                        name: name.clone(),
+                        fields: declared,
+                    },
+                    *span,
+                );
+            }
+            ASTNode::FieldAccess {
+                object,
+                field,
+                span,
+            } => {
+                self.expression(ast, *object)?;
+                self.emit(Instruction::GetField(field.clone()), *span);
+            }
+            ASTNode::Index { array, index, span } => {
+                self.expression(ast, *array)?;
+                self.expression(ast, *index)?;
+                self.emit(Instruction::Index, *span);
+            }
+            ASTNode::Slice {
+                array,
+                start,
+                end,
+                span,
+            } => {
+                self.expression(ast, *array)?;
+                for bound in [start, end].into_iter().flatten() {
+                    self.expression(ast, *bound)?;
+                }
+                self.emit(
+                    Instruction::Slice {
+                        start: start.is_some(),
+                        end: end.is_some(),
+                    },
+                    *span,
+                );
+            }
+            // A variable holding a lambda hides functions of the same name
+            ASTNode::Call { name, args, span } if self.resolve(name).is_ok() => {
+                for arg in args {
+                    self.expression(ast, *arg)?;
+                }
+                let slot = self.resolve(name)?;
+                self.emit(Instruction::Load(slot), *span);
+                self.emit(Instruction::CallValue(args.len()), *span);
+            }
+            ASTNode::Lambda { params, body, span } => {
+                self.lambda(ast, expr, params, *body, *span)?;
+            }
+            ASTNode::Call { name, args, span } if self.functions.contains_key(name) => {
+                for arg in args {
+                    self.expression(ast, *arg)?;
+                }
+                let (index, _) = self.functions[name];
+                self.emit(Instruction::CallFunction(index), *span);
+            }
+            ASTNode::Call { name, args, span } if name == "apply" => {
+                let (function, args) = args.split_first().expect("apply() was checked");
+                // The function is evaluated first, like the interpreter does
+                self.expression(ast, *function)?;
+                let function = self.declare("..function");
+                self.emit(Instruction::Init(function), *span);
+                for arg in args {
+                    self.expression(ast, *arg)?;
+                }
+                self.emit(Instruction::Load(function), *span);
+                self.emit(Instruction::CallValue(args.len()), *span);
+            }
+            ASTNode::Call { name, args, span } if name == "map" => {
+                self.map(ast, args[0], args[1], *span)?;
+            }
+            ASTNode::Call { name, args, span } if name == "typeof" => {
+                let checked = self
+                    .checked
+                    .get_or_insert_with(|| SemanticAnalyzer::checked(ast));
+                let ty = checked
+                    .as_ref()
+                    .and_then(|checked| checked.type_of(&ast[args[0]]))
+                    .cloned();
+                match ty {
+                    // The type is known, so the value isn't computed
+                    Some(ty) => {
+                        self.emit(Instruction::Push(Value::Str(ty.to_string())), *span);
+                    }
+                    None => {
+                        self.expression(ast, args[0])?;
+                        self.emit(Instruction::Call(Builtin::TypeOf, 1), *span);
+                    }
+                }
+            }
+            ASTNode::Call { name, args, span } => {
+                let builtin = Builtin::from_name(name)
+                    .ok_or_else(|| format!("Unknown function '{}'", name))?;
+                for arg in args {
+                    self.expression(ast, *arg)?;
+                }
+                self.emit(Instruction::Call(builtin, args.len()), *span);
+            }
+            _ => return Err("Unexpected expression node".to_string()),
+        }
+        Ok(())
+    }
+
+    /// Append an instruction, returning its index
+    fn emit(&mut self, instruction: Instruction, span: Span) -> usize {
+        self.chunk.code.push(instruction);
+        self.chunk.spans.push(span);
+        self.chunk.code.len() - 1
+    }
+
+    /// Point the jump at `at` to the next instruction to be emitted
+    fn patch(&mut self, at: usize) {
+        self.jump_to(at, self.chunk.code.len());
+    }
+
+    /// Point the jump at `at` to the instruction at `to`
+    fn jump_to(&mut self, at: usize, to: usize) {
+        match &mut self.chunk.code[at] {
+            Instruction::Jump(target) | Instruction::JumpIfFalse(target) => *target = to,
+            other => unreachable!("tried to patch {:?}, which is not a jump", other),
+        }
+    }
+
+    /// Give a new variable the next free slot
+    fn declare(&mut self, name: &str) -> usize {
+        let slot = self.chunk.slots;
+        self.chunk.slots += 1;
+        self.scopes
+            .last_mut()
+            .expect("global scope is never popped")
+            .insert(name.to_string(), slot);
+        slot
+    }
+
+    /// Find a variable's slot, searching from the innermost scope outwards
+    fn resolve(&self, name: &str) -> Result<usize, String> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .copied()
+            .ok_or_else(|| format!("Variable '{}' is not declared", name))
+    }
+}
+
+/// A function call being run by the VM
+struct Frame {
+    /// Where to continue when the call returns
+    return_to: usize,
+
+    /// Index in [`Vm::slots`] of the function's first slot
+    base: usize,
+}
+
+/// Executes bytecode with a value stack
+pub struct Vm<W: Write = Stdout> {
+    stack: Vec<Value>,
+
+    /// Variable values of the program and then of each call, indexed by
+    /// slot from the frame's base
+    slots: Vec<Value>,
+
+    /// Calls being run, innermost last
+    frames: Vec<Frame>,
+
+    /// How many calls may be run at once
+    max_call_depth: usize,
+
+    /// Optional checks on what the program does
+    checks: Checks,
+
+    /// Where `rand()` gets its numbers
+    rng: Rng,
+
+    /// The arguments `args()` gives the program
+    arguments: Vec<String>,
+
+    /// Where `print` writes to
+    out: W,
+
+    /// Where `input` reads from, stdin when unset
+    input: Option<Box<dyn BufRead>>,
+
+    /// The code of the `exit()` the program just called, which stops it
+    exit: Option<i32>,
+}
+
+impl Vm {
+    /// Create a VM that prints to stdout
+    pub fn new() -> Self {
+        Self::with_output(io::stdout())
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Write> Vm<W> {
+    /// Create a VM that prints to the given writer
+    pub fn with_output(out: W) -> Self {
+        Self {
+            stack: Vec::new(),
+            slots: Vec::new(),
+            frames: Vec::new(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            checks: Checks::default(),
+            rng: Rng::default(),
+            arguments: Vec::new(),
+            out,
+            input: None,
+            exit: None,
+        }
+    }
+
+    /// Read `input` statements from the given reader instead of stdin
+    pub fn with_input(mut self, input: impl BufRead + 'static) -> Self {
+        self.input = Some(Box::new(input));
+        self
+    }
+
+    /// Fail calls nested deeper than `depth`, like the interpreter does
+    pub fn with_max_call_depth(mut self, depth: usize) -> Self {
+        self.max_call_depth = depth;
+        self
+    }
+
+    /// Make the given checks as the program runs, like the interpreter does
+    pub fn with_checks(mut self, checks: Checks) -> Self {
+        self.checks = checks;
+        self
+    }
+
+    /// Give the program `arguments`, like the interpreter does
+    pub fn with_args(mut self, arguments: Vec<String>) -> Self {
+        self.arguments = arguments;
+        self
+    }
+
+    /// Consume the VM, returning its output writer
+    pub fn into_output(self) -> W {
+        self.out
+    }
+
+    pub fn run(&mut self, chunk: &Chunk) -> Result<(), CompileError> {
+        self.stack.clear();
+        self.slots = vec![Value::Int(0); chunk.slots];
+        self.frames.clear();
+        let mut ip = 0;
+        while ip < chunk.code.len() {
+            let next = self.step(chunk, ip).map_err(|message| {
+                CompileError::Runtime(RuntimeError::new(message, chunk.spans[ip]))
+            })?;
+            if let Some(code) = self.exit.take() {
+                return Err(CompileError::Exit {
+                    code,
+                    span: chunk.spans[ip],
+                });
+            }
+            ip = next;
+        }
+        Ok(())
+    }
+
+    /// Execute the instruction at `ip`, returning the index of the next one
+    fn step(&mut self, chunk: &Chunk, ip: usize) -> Result<usize, String> {
+        let base = self.frames.last().map_or(0, |frame| frame.base);
+        match &chunk.code[ip] {
+            Instruction::Push(val) => self.stack.push(val.clone()),
+            Instruction::Load(slot) => self.stack.push(self.slots[base + slot].clone()),
+            Instruction::Init(slot) => self.slots[base + slot] = self.pop(),
+            Instruction::Store(slot) => {
+                let val = self.pop();
+                let current = &mut self.slots[base + slot];
+                *current = match (&*current, val) {
+                    // Integers are promoted when stored into a float variable
+                    (Value::Float(_), Value::Int(num)) => Value::Float(num as f64),
+                    (current, val) if current.type_name() != val.type_name() => {
+                        return Err(format!(
+                            "Cannot assign {} to {} variable",
+                            val.type_name(),
+                            current.type_name()
+                        ))
+                    }
+                    (_, val) => val,
+                };
+            }
+            Instruction::Input(slot) => {
+                self.slots[base + slot] = read_from(&mut self.input, &self.slots[base + slot])?;
+            }
+            Instruction::Binary(op) => {
+                let right = self.pop();
+                let left = self.pop();
+                let result = if op.is_comparison() {
+                    compare(op, &left, &right)?
+                } else {
+                    self.checks.arithmetic(op, &left, &right)?
+                };
+                self.stack.push(result);
+            }
+            Instruction::Negate => {
+                let val = self.pop();
+                self.stack.push(self.checks.negate(&val)?);
+            }
+            Instruction::ToFloat => {
+                let val = self.pop();
+                self.stack.push(interpreter::promote(true, val));
+            }
+            Instruction::Not => {
+                let val = self.pop();
+                self.stack.push(Value::Bool(!val.is_truthy()));
+            }
+            Instruction::Truthy => {
+                let val = self.pop();
+                self.stack.push(Value::Bool(val.is_truthy()));
+            }
+            Instruction::Jump(target) => return Ok(*target),
+            Instruction::JumpIfFalse(target) => {
+                if !self.pop().is_truthy() {
+                    return Ok(*target);
+                }
+            }
+            Instruction::Array(len) => {
+                let elements = self.stack.split_off(self.stack.len() - len);
+                self.stack.push(interpreter::array(elements));
+            }
+            Instruction::Map(len) => {
+                let mut values = self.stack.split_off(self.stack.len() - 2 * len).into_iter();
+                let mut entries = Vec::new();
+                while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                    entries.push((key, value));
+                }
+                self.stack.push(interpreter::map(entries)?);
+            }
+            Instruction::Struct { name, fields } => {
+                let values = self.stack.split_off(self.stack.len() - fields.len());
+                let fields = fields.iter().cloned().zip(values).collect();
+                self.stack.push(interpreter::structure(name, fields));
+            }
+            Instruction::GetField(field) => {
+                let object = self.pop();
+                self.stack.push(interpreter::field(&object, field)?);
+            }
+            Instruction::SetField(field) => {
+                let val = self.pop();
+                let object = self.pop();
+                interpreter::set_field(&object, field, val)?;
+            }
+            Instruction::Index => {
+                let index = self.pop();
+                let array = self.pop();
+                self.stack.push(interpreter::index(&array, &index)?);
+            }
+            Instruction::Slice { start, end } => {
+                let end = end.then(|| self.pop());
+                let start = start.then(|| self.pop());
+                let array = self.pop();
+                let slice = interpreter::slice(&array, start.as_ref(), end.as_ref())?;
+                self.stack.push(slice);
+            }
+            Instruction::SetIndex => {
+                let val = self.pop();
+                let index = self.pop();
+                let array = self.pop();
+                interpreter::set_index(&array, &index, val)?;
+            }
+            Instruction::Call(Builtin::Exit, args) => {
+                let args = self.stack.split_off(self.stack.len() - args);
+                self.exit = Some(builtins::exit_code(&args)?);
+            }
+            Instruction::Call(Builtin::Error, args) => {
+                let args = self.stack.split_off(self.stack.len() - args);
+                return Err(builtins::error_message(&args));
+            }
+            Instruction::Call(builtin, args) if builtin.reads_arguments() => {
+                let args = self.stack.split_off(self.stack.len() - args);
+                let result = builtins::read_arguments(*builtin, &self.arguments, &args)?;
+                self.stack.push(result);
+            }
+            Instruction::Call(builtin, args) => {
+                let args = self.stack.split_off(self.stack.len() - args);
+                if let Some(result) = builtin.call(&args, &mut self.rng)? {
+                    self.stack.push(result);
+                }
+            }
+            Instruction::CallFunction(function) => {
+                if self.frames.len() >= self.max_call_depth {
+                    return Err(interpreter::call_depth_exceeded(self.max_call_depth));
+                }
+                let function = &chunk.functions[*function];
+                let base = self.slots.len();
+                let args = self.stack.split_off(self.stack.len() - function.params);
+                self.slots.extend(args);
+                self.slots.resize(base + function.slots, Value::Int(0));
+                self.frames.push(Frame {
+                    return_to: ip + 1,
+                    base,
+                });
+                return Ok(function.address);
+            }
+            Instruction::Closure { lambda, captures } => {
+                let values = self.stack.split_off(self.stack.len() - captures.len());
+                let captures = captures.iter().cloned().zip(values).collect();
+                self.stack.push(Value::Closure(Rc::new(Closure {
+                    lambda: *lambda,
+                    captures,
+                })));
+            }
+            Instruction::CallValue(args) => {
+                if self.frames.len() >= self.max_call_depth {
+                    return Err(interpreter::call_depth_exceeded(self.max_call_depth));
+                }
+                let (function, captures) = match self.pop() {
+                    Value::Closure(closure) => {
+                        (chunk.lambdas[&closure.lambda], closure.captures.clone())
+                    }
+                    Value::Function(name) => {
+                        let function = chunk
+                            .functions
+                            .iter()
+                            .position(|function| function.name == name)
+                            .expect("function values name compiled functions");
+                        (function, Vec::new())
+                    }
+                    other => return Err(format!("{} is not a function", other.type_name())),
+                };
+                let function = &chunk.functions[function];
+                let args = self.stack.split_off(self.stack.len() - args);
+                if !function.returns {
+                    self.stack.push(Value::Int(0));
+                }
+                let base = self.slots.len();
+                let captures = captures.into_iter().map(|(_, val)| val);
+                self.slots.extend(captures.chain(args));
+                self.slots.resize(base + function.slots, Value::Int(0));
+                self.frames.push(Frame {
+                    return_to: ip + 1,
+                    base,
+                });
+                return Ok(function.address);
+            }
+            Instruction::Return(_) => {
+                let frame = self
+                    .frames
+                    .pop()
+                    .expect("return is only compiled into functions");
+                self.slots.truncate(frame.base);
+                return Ok(frame.return_to);
+            }
+            Instruction::Pop => {
+                self.pop();
+            }
+            Instruction::Fail(message) => return Err(message.clone()),
+            Instruction::Format(pieces) => {
+                let values = self.stack.split_off(self.stack.len() - (pieces.len() - 1));
+                self.stack.push(Value::Str(format::render(pieces, &values)));
+            }
+            Instruction::Print { pieces, newline } => {
+                let values = self.stack.split_off(self.stack.len() - (pieces.len() - 1));
+                let mut text = format::render(pieces, &values);
+                if *newline {
+                    text.push('\n');
+                }
+                write!(self.out, "{}", text).map_err(|err| err.to_string())?;
+            }
+        }
+        Ok(ip + 1)
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack
+            .pop()
+            .expect("bytecode never pops an empty stack")
+    }
+}
@@ -0,0 +1,114 @@
+//! The build cache in `.osho-cache/`, which lets a build that changed
+//! nothing reuse the C source and executable of the last one.
+//!
+//! Entries are named by a [`BuildKey`], a hash of the source files and of the
+//! settings that change the generated code or how it's compiled.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::driver::CCompiler;
+use crate::modules::SourceMap;
+
+/// Where builds are cached, relative to the current directory
+pub const CACHE_DIR: &str = ".osho-cache";
+
+/// A 64-bit FNV-1a hash of what a build depends on. Unlike the standard
+/// library's hasher, it gives the same key in every version of the compiler
+/// that osho is built with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildKey(u64);
+
+impl BuildKey {
+    /// A key for the version of osho doing the build
+    pub fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325).with(env!("CARGO_PKG_VERSION"))
+    }
+
+    /// Add a part to the key. Parts are ended by their length, so moving text
+    /// from one to the next gives a different key.
+    pub fn with(mut self, part: &str) -> Self {
+        let length = (part.len() as u64).to_le_bytes();
+        for byte in part.bytes().chain(length) {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        self
+    }
+
+    /// Add the name and text of every file of a program
+    pub fn with_sources(self, sources: &SourceMap) -> Self {
+        sources
+            .files()
+            .iter()
+            .fold(self, |key, file| key.with(&file.name).with(&file.source))
+    }
+
+    /// Add the compiler and the flags it's run with
+    pub fn with_compiler(self, cc: &CCompiler) -> Self {
+        let level = cc.opt_level.map(|level| level.to_string());
+        cc.flags
+            .iter()
+            .fold(self.with(&cc.program), |key, flag| key.with(flag))
+            .with(level.as_deref().unwrap_or(""))
+    }
+}
+
+impl Default for BuildKey {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for BuildKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// A directory of built programs, each a C file and an executable
+#[derive(Debug, Clone)]
+pub struct BuildCache {
+    dir: PathBuf,
+}
+
+impl BuildCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The cached C source of a build
+    pub fn c_file(&self, key: BuildKey) -> PathBuf {
+        self.dir.join(format!("{}.c", key))
+    }
+
+    /// The cached executable of a build
+    pub fn executable(&self, key: BuildKey) -> PathBuf {
+        self.dir.join(key.to_string())
+    }
+
+    /// Whether the build is cached, with both its files
+    pub fn contains(&self, key: BuildKey) -> bool {
+        self.c_file(key).is_file() && self.executable(key).is_file()
+    }
+
+    /// Copy the cached files of a build to where the build puts them
+    pub fn restore(&self, key: BuildKey, c_file: &Path, executable: &Path) -> io::Result<()> {
+        fs::copy(self.c_file(key), c_file)?;
+        fs::copy(self.executable(key), executable)?;
+        Ok(())
+    }
+
+    /// Keep copies of the files of a build. The executable is moved into
+    /// place last, so a build interrupted while being stored isn't found by
+    /// [`contains`](Self::contains).
+    pub fn store(&self, key: BuildKey, c_file: &Path, executable: &Path) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::copy(c_file, self.c_file(key))?;
+        let partial = self.dir.join(format!("{}.partial", key));
+        fs::copy(executable, &partial)?;
+        fs::rename(partial, self.executable(key))
+    }
+}
@@ -0,0 +1,78 @@
+//! Runtime checks a program can opt into, made the same way by the
+//! interpreters and the compiled program.
+
+use crate::interpreter::{arithmetic, negate, Value};
+use crate::parser::{BinaryOperator, UnaryOperator};
+
+/// Checks beyond the ones every program gets, like bounds checks on indexing
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Checks {
+    /// Fail on a division by zero or a NaN result, instead of carrying on
+    /// with inf or NaN, as `--checked-arith` asks for
+    pub arith: bool,
+
+    /// Fail when integer arithmetic overflows, instead of wrapping around,
+    /// as `--trap-overflow` asks for
+    pub overflow: bool,
+}
+
+impl Checks {
+    /// Apply an arithmetic operator like [`arithmetic`], failing where the
+    /// enabled checks say the result is wrong
+    pub(crate) fn arithmetic(
+        &self,
+        op: &BinaryOperator,
+        left: &Value,
+        right: &Value,
+    ) -> Result<Value, String> {
+        if self.arith && matches!(right.as_number(), Ok(num) if num == 0.0) {
+            match op {
+                BinaryOperator::Divide => return Err("Division by zero".to_string()),
+                BinaryOperator::Modulo => return Err("Modulo by zero".to_string()),
+                _ => {}
+            }
+        }
+        if let (true, Value::Int(left), Value::Int(right)) = (self.overflow, left, right) {
+            let result = match op {
+                BinaryOperator::Plus => left.checked_add(*right),
+                BinaryOperator::Minus => left.checked_sub(*right),
+                BinaryOperator::Multiply => left.checked_mul(*right),
+                // Only the most negative number divided by -1 overflows, and
+                // the remainder of that division counts too, as in Rust
+                BinaryOperator::Divide | BinaryOperator::Modulo if *right == -1 => {
+                    left.checked_neg()
+                }
+                _ => Some(0),
+            };
+            if result.is_none() {
+                return Err(overflow_message(op.symbol()));
+            }
+        }
+        let result = arithmetic(op, left, right)?;
+        match result {
+            Value::Float(num) if self.arith && num.is_nan() => Err(nan_message(op)),
+            result => Ok(result),
+        }
+    }
+
+    /// Negate a number like [`negate`], failing if the enabled checks say the
+    /// result is wrong
+    pub(crate) fn negate(&self, val: &Value) -> Result<Value, String> {
+        match val {
+            Value::Int(num) if self.overflow && num.checked_neg().is_none() => {
+                Err(overflow_message(UnaryOperator::Negate.symbol()))
+            }
+            val => negate(val),
+        }
+    }
+}
+
+/// What a checked program reports when the operator `symbol` overflows
+pub(crate) fn overflow_message(symbol: &str) -> String {
+    format!("The result of '{}' overflows", symbol)
+}
+
+/// What a checked program reports when `op` gives NaN
+pub(crate) fn nan_message(op: &BinaryOperator) -> String {
+    format!("The result of '{}' is NaN", op.symbol())
+}
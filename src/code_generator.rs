@@ -1,82 +1,2191 @@
-use crate::parser::{ASTNode, BinaryOperator};
+use std::collections::HashSet;
 
+use crate::backend::Backend;
+use crate::builtins::Builtin;
+use crate::checks::{self, Checks};
+use crate::error::{CompileError, RUNTIME_ERROR_EXIT_CODE};
+use crate::ir::{Function, Instr, Operand, Program, StructDef, VarId};
+use crate::parser::{Ast, BinaryOperator, UnaryOperator};
+use crate::semantic_analyzer::{SemanticAnalyzer, Type};
+
+/// Arrays and structs live on the heap and are shared by pointer, like in
+/// the interpreter. Each starts with a count of the references to it, and is
+/// freed by a generated `osho_release_*` function when the last one goes.
+const REFS_RUNTIME: &str = r#"
+static void osho_retain(void *object) {
+    if (object != NULL) ++*(long *)object;
+}
+"#;
+
+/// Arrays, which count their references like structs do
+const ARRAY_RUNTIME: &str = r#"typedef struct {
+    long refs;
+    long len;
+    long cap;
+    void *data;
+} osho_array;
+
+static osho_array *osho_array_new(long len, size_t size) {
+    osho_array *array = malloc(sizeof(osho_array));
+    array->refs = 1;
+    array->len = len;
+    array->cap = len;
+    array->data = malloc(len * size);
+    return array;
+}
+
+/* The index, if it is in bounds; otherwise the program stops */
+static long osho_index(const osho_array *array, long index) {
+    if (index < 0 || index >= array->len) {
+        fprintf(stderr, "Index %ld is out of bounds for an array of length %ld\n", index, array->len);
+        exit(OSHO_RUNTIME_ERROR);
+    }
+    return index;
+}
+"#;
+
+/// Functions for `push` and `pop`, only emitted when the program uses them
+const VECTOR_RUNTIME: &str = r#"
+/* Add room for one more element at the end, doubling the capacity when full */
+static void osho_array_grow(osho_array *array, size_t size) {
+    if (array->len == array->cap) {
+        array->cap = array->cap < 4 ? 4 : array->cap * 2;
+        array->data = realloc(array->data, array->cap * size);
+    }
+    array->len++;
+}
+
+/* Remove the last element, returning its index */
+static long osho_array_pop(osho_array *array) {
+    if (array->len == 0) {
+        fprintf(stderr, "Cannot pop from an empty array\n");
+        exit(OSHO_RUNTIME_ERROR);
+    }
+    return --array->len;
+}
+"#;
+
+/// Slicing arrays, which copies the elements into a new array
+const SLICE_RUNTIME: &str = r#"
+static osho_array *osho_slice(const osho_array *array, long start, long end, size_t size) {
+    if (start < 0 || start > end || end > array->len) {
+        fprintf(stderr, "Slice %ld:%ld is out of range for an array of length %ld\n", start, end, array->len);
+        exit(OSHO_RUNTIME_ERROR);
+    }
+    osho_array *slice = osho_array_new(end - start, size);
+    memcpy(slice->data, (char *)array->data + start * size, (end - start) * size);
+    return slice;
+}
+
+/* A slice of arrays or structs, which now has references to its elements too */
+static osho_array *osho_retain_elements(osho_array *array) {
+    for (long i = 0; i < array->len; i++) osho_retain(((void **)array->data)[i]);
+    return array;
+}
+"#;
+
+/// Prints floats like the interpreter does: the fewest digits that read back
+/// as the same number, never with an exponent
+const FLOAT_RUNTIME: &str = r#"
+/* Longest float: a sign, "0.", 323 zeros and 17 digits */
+#define OSHO_FLOAT_SIZE 400
+
+static const char *osho_float(char *buf, double value) {
+    if (isnan(value)) return "NaN";
+    if (isinf(value)) return value < 0 ? "-inf" : "inf";
+    char digits[32];
+    int precision = 0;
+    do {
+        snprintf(digits, sizeof digits, "%.*e", precision, value);
+    } while (strtod(digits, NULL) != value && ++precision <= 16);
+
+    /* Split "-d.ddde+xx" into its sign, digits and exponent */
+    char *p = digits, *out = buf;
+    if (*p == '-') *out++ = *p++;
+    char mantissa[20];
+    int len = 0;
+    for (; *p != 'e'; p++) {
+        if (*p != '.') mantissa[len++] = *p;
+    }
+    while (len > 1 && mantissa[len - 1] == '0') len--;
+    int point = atoi(p + 1) + 1;
+
+    if (point <= 0) {
+        *out++ = '0';
+        *out++ = '.';
+        for (int i = 0; i < -point; i++) *out++ = '0';
+        for (int i = 0; i < len; i++) *out++ = mantissa[i];
+    } else {
+        for (int i = 0; i < len || i < point; i++) {
+            if (i == point) *out++ = '.';
+            *out++ = i < len ? mantissa[i] : '0';
+        }
+    }
+    *out = '\0';
+    return buf;
+}
+"#;
+
+/// splitmix64 behind `rand()`, the same generator the interpreter runs so
+/// a seed gives the same numbers
+const RANDOM_RUNTIME: &str = r#"
+static uint64_t osho_random_state = 0;
+
+static uint64_t osho_random_next(void) {
+    uint64_t z = osho_random_state += 0x9E3779B97F4A7C15ULL;
+    z = (z ^ (z >> 30)) * 0xBF58476D1CE4E5B9ULL;
+    z = (z ^ (z >> 27)) * 0x94D049BB133111EBULL;
+    return z ^ (z >> 31);
+}
+
+static double osho_rand(void) {
+    return (double)(osho_random_next() >> 11) / 9007199254740992.0;
+}
+
+static long osho_rand_range(long low, long high) {
+    if (low >= high) {
+        fprintf(stderr, "rand_range(%ld, %ld) has no numbers to pick from\n", low, high);
+        exit(OSHO_RUNTIME_ERROR);
+    }
+    uint64_t span = (uint64_t)high - (uint64_t)low;
+    return (long)((uint64_t)low + osho_random_next() % span);
+}
+
+static void osho_seed(long seed) {
+    osho_random_state = (uint64_t)seed;
+}
+"#;
+
+/// `exit()`, which only takes codes an exit status can hold, like the
+/// interpreter
+const EXIT_RUNTIME: &str = r#"
+static void osho_exit(long code) {
+    if (code < 0 || code > 255) {
+        fprintf(stderr, "exit() code must be from 0 to 255, found %ld\n", code);
+        exit(OSHO_RUNTIME_ERROR);
+    }
+    exit((int)code);
+}
+"#;
+
+/// Strings count their references too, in a `long` just before their
+/// text, so they are still `const char *` to the rest of the runtime. String
+/// literals are never freed, which their count of -1 says.
+const TEXT_RUNTIME: &str = r#"
+#define OSHO_STR_REFS(text) ((long *)(text) - 1)
+
+/* A new string holding a copy of `len` bytes from `start` */
+static char *osho_str_copy(const char *start, size_t len) {
+    long *refs = malloc(sizeof(long) + len + 1);
+    *refs = 1;
+    char *copy = (char *)(refs + 1);
+    memcpy(copy, start, len);
+    copy[len] = '\0';
+    return copy;
+}
+
+static void osho_retain_str(const char *text) {
+    if (text != NULL && *OSHO_STR_REFS(text) >= 0) ++*OSHO_STR_REFS(text);
+}
+
+static void osho_release_str(const char *text) {
+    if (text == NULL || *OSHO_STR_REFS(text) < 0 || --*OSHO_STR_REFS(text) > 0) return;
+    free(OSHO_STR_REFS(text));
+}
+"#;
+
+/// `argc()` and `argv()`, which read the arguments `main` is given, less
+/// the name of the program. An argument is copied into a string of its own.
+const ARGS_RUNTIME: &str = r#"
+static long osho_arg_count;
+static char **osho_arg_values;
+
+static long osho_argc(void) {
+    return osho_arg_count;
+}
+
+static const char *osho_argv(long index) {
+    if (index < 0 || index >= osho_arg_count) {
+        fprintf(stderr, "argv(%ld) is out of range for %ld argument%s\n", index, osho_arg_count, osho_arg_count == 1 ? "" : "s");
+        exit(OSHO_RUNTIME_ERROR);
+    }
+    return osho_str_copy(osho_arg_values[index], strlen(osho_arg_values[index]));
+}
+"#;
+
+/// `args()`, which needs the array runtime as well
+const ARGS_ARRAY_RUNTIME: &str = r#"
+static osho_array *osho_args(void) {
+    osho_array *array = osho_array_new(osho_arg_count, sizeof(const char *));
+    for (long i = 0; i < osho_arg_count; i++) {
+        ((const char **)array->data)[i] = osho_str_copy(osho_arg_values[i], strlen(osho_arg_values[i]));
+    }
+    return array;
+}
+"#;
+
+/// `env()`, which gives the default for an unset variable like the
+/// interpreter. Either way the caller gets a reference of its own.
+const ENV_RUNTIME: &str = r#"
+static const char *osho_env(const char *name, const char *fallback) {
+    const char *value = getenv(name);
+    if (!value) {
+        osho_retain_str(fallback);
+        return fallback;
+    }
+    return osho_str_copy(value, strlen(value));
+}
+"#;
+
+/// The conversions that can fail or make a new string: `int()` of a float,
+/// `str()` of a number and `parse_num()`. They need the float runtime.
+const CONVERSION_RUNTIME: &str = r#"
+static long osho_float_to_int(double value) {
+    /* The range of long, which NaN isn't in either */
+    if (!(value >= -9223372036854775808.0 && value < 9223372036854775808.0)) {
+        char buf[OSHO_FLOAT_SIZE];
+        fprintf(stderr, "int() can't convert %s to an int\n", osho_float(buf, value));
+        exit(OSHO_RUNTIME_ERROR);
+    }
+    return (long)value;
+}
+
+static const char *osho_int_string(long value) {
+    char text[24];
+    snprintf(text, sizeof text, "%ld", value);
+    return osho_str_copy(text, strlen(text));
+}
+
+static const char *osho_float_string(double value) {
+    char buf[OSHO_FLOAT_SIZE];
+    const char *text = osho_float(buf, value);
+    return osho_str_copy(text, strlen(text));
+}
+
+static double osho_parse_num(const char *text) {
+    char *end;
+    double value = strtod(text, &end);
+    /* strtod also skips leading spaces and reads hex, which osho doesn't */
+    if (end == text || *end != '\0' || strchr(" \t\n\v\f\r", *text) || strpbrk(text, "xX(")) {
+        fprintf(stderr, "parse_num() can't parse '%s' as a number\n", text);
+        exit(OSHO_RUNTIME_ERROR);
+    }
+    return value;
+}
+"#;
+
+/// The string builtins. Strings are UTF-8 and indexed by character, like in
+/// the interpreter. They are never changed: the strings these functions give
+/// are new copies the caller holds the only reference to.
+const STRING_RUNTIME: &str = r#"
+/* Whether a byte starts a character rather than continuing one */
+#define OSHO_CHAR_START(byte) (((unsigned char)(byte) & 0xC0) != 0x80)
+
+static long osho_str_len(const char *text) {
+    long len = 0;
+    for (; *text; text++) len += OSHO_CHAR_START(*text);
+    return len;
+}
+
+/* Where character `index` starts, or the end of the string past its last one */
+static const char *osho_str_at(const char *text, long index) {
+    for (; *text; text++) {
+        if (OSHO_CHAR_START(*text) && index-- == 0) return text;
+    }
+    return text;
+}
+
+/* A copy of characters `start` up to `end`, which are in range */
+static const char *osho_str_range(const char *text, long start, long end) {
+    const char *from = osho_str_at(text, start);
+    const char *to = osho_str_at(from, end - start);
+    return osho_str_copy(from, to - from);
+}
+
+static const char *osho_substring(const char *text, long start, long end) {
+    long len = osho_str_len(text);
+    if (start < 0 || start > end || end > len) {
+        fprintf(stderr, "substring(%ld, %ld) is out of range for a string of length %ld\n", start, end, len);
+        exit(OSHO_RUNTIME_ERROR);
+    }
+    return osho_str_range(text, start, end);
+}
+
+static const char *osho_str_slice(const char *text, long start, long end) {
+    long len = osho_str_len(text);
+    if (start < 0 || start > end || end > len) {
+        fprintf(stderr, "Slice %ld:%ld is out of range for a string of length %ld\n", start, end, len);
+        exit(OSHO_RUNTIME_ERROR);
+    }
+    return osho_str_range(text, start, end);
+}
+
+static long osho_find(const char *text, const char *part) {
+    const char *found = strstr(text, part);
+    if (!found) return -1;
+    long index = 0;
+    for (; text < found; text++) index += OSHO_CHAR_START(*text);
+    return index;
+}
+
+static const char *osho_upper(const char *text) {
+    char *copy = osho_str_copy(text, strlen(text));
+    for (char *c = copy; *c; c++) {
+        if (*c >= 'a' && *c <= 'z') *c -= 'a' - 'A';
+    }
+    return copy;
+}
+
+static const char *osho_lower(const char *text) {
+    char *copy = osho_str_copy(text, strlen(text));
+    for (char *c = copy; *c; c++) {
+        if (*c >= 'A' && *c <= 'Z') *c += 'a' - 'A';
+    }
+    return copy;
+}
+"#;
+
+/// Characters, which are held as their code points and printed as UTF-8.
+/// Reading one out of a string needs the string runtime.
+const CHAR_RUNTIME: &str = r#"
+static const char *osho_char(char *buf, long c) {
+    if (c < 0x80) {
+        buf[0] = (char)c;
+        buf[1] = '\0';
+    } else if (c < 0x800) {
+        buf[0] = (char)(0xC0 | (c >> 6));
+        buf[1] = (char)(0x80 | (c & 0x3F));
+        buf[2] = '\0';
+    } else if (c < 0x10000) {
+        buf[0] = (char)(0xE0 | (c >> 12));
+        buf[1] = (char)(0x80 | ((c >> 6) & 0x3F));
+        buf[2] = (char)(0x80 | (c & 0x3F));
+        buf[3] = '\0';
+    } else {
+        buf[0] = (char)(0xF0 | (c >> 18));
+        buf[1] = (char)(0x80 | ((c >> 12) & 0x3F));
+        buf[2] = (char)(0x80 | ((c >> 6) & 0x3F));
+        buf[3] = (char)(0x80 | (c & 0x3F));
+        buf[4] = '\0';
+    }
+    return buf;
+}
+
+static long osho_char_at(const char *text, long index) {
+    long len = osho_str_len(text);
+    if (index < 0 || index >= len) {
+        fprintf(stderr, "Index %ld is out of bounds for a string of length %ld\n", index, len);
+        exit(OSHO_RUNTIME_ERROR);
+    }
+    const unsigned char *p = (const unsigned char *)osho_str_at(text, index);
+    if (p[0] < 0x80) return p[0];
+    if (p[0] < 0xE0) return ((long)(p[0] & 0x1F) << 6) | (p[1] & 0x3F);
+    if (p[0] < 0xF0) return ((long)(p[0] & 0x0F) << 12) | ((p[1] & 0x3F) << 6) | (p[2] & 0x3F);
+    return ((long)(p[0] & 0x07) << 18) | ((p[1] & 0x3F) << 12) | ((p[2] & 0x3F) << 6) | (p[3] & 0x3F);
+}
+
+static void osho_show_char(long c) {
+    char buf[5];
+    switch (c) {
+    case '\'': printf("'\\''"); return;
+    case '\\': printf("'\\\\'"); return;
+    case '\n': printf("'\\n'"); return;
+    case '\r': printf("'\\r'"); return;
+    case '\t': printf("'\\t'"); return;
+    case '\0': printf("'\\0'"); return;
+    }
+    if (c < 0x20 || c == 0x7F) {
+        printf("'\\u{%lx}'", c);
+    } else {
+        printf("'%s'", osho_char(buf, c));
+    }
+}
+
+static long osho_chr(long code) {
+    if (code < 0 || code > 0x10FFFF || (code >= 0xD800 && code <= 0xDFFF)) {
+        fprintf(stderr, "chr(%ld) is not a valid character\n", code);
+        exit(OSHO_RUNTIME_ERROR);
+    }
+    return code;
+}
+"#;
+
+/// `split()`, which needs the array runtime as well as the string runtime
+const SPLIT_RUNTIME: &str = r#"
+static osho_array *osho_split(const char *text, const char *separator) {
+    size_t width = strlen(separator);
+    if (width == 0) {
+        fprintf(stderr, "split() separator can't be empty\n");
+        exit(OSHO_RUNTIME_ERROR);
+    }
+    long count = 1;
+    for (const char *at = text; (at = strstr(at, separator)); at += width) count++;
+    osho_array *parts = osho_array_new(count, sizeof(const char *));
+    for (long i = 0; i < count - 1; i++) {
+        const char *end = strstr(text, separator);
+        ((const char **)parts->data)[i] = osho_str_copy(text, end - text);
+        text = end + width;
+    }
+    ((const char **)parts->data)[count - 1] = osho_str_copy(text, strlen(text));
+    return parts;
+}
+"#;
+
+/// `read_file()`, `write_file()` and `append_file()`, which stop the program
+/// with the reason when a file can't be read or written
+const FILE_RUNTIME: &str = r#"
+static void osho_file_error(const char *action, const char *path) {
+    fprintf(stderr, "Cannot %s '%s': %s\n", action, path, strerror(errno));
+    exit(OSHO_RUNTIME_ERROR);
+}
+
+static const char *osho_read_file(const char *path) {
+    FILE *file = fopen(path, "rb");
+    if (!file) osho_file_error("read", path);
+    size_t len = 0, cap = 4096, read;
+    char *text = malloc(cap);
+    while ((read = fread(text + len, 1, cap - len - 1, file)) > 0) {
+        len += read;
+        if (len == cap - 1) text = realloc(text, cap *= 2);
+    }
+    if (ferror(file)) osho_file_error("read", path);
+    fclose(file);
+    const char *copy = osho_str_copy(text, len);
+    free(text);
+    return copy;
+}
+
+static void osho_write_text(const char *path, const char *text, const char *mode, const char *action) {
+    FILE *file = fopen(path, mode);
+    if (!file) osho_file_error(action, path);
+    size_t len = strlen(text);
+    if (fwrite(text, 1, len, file) != len) osho_file_error(action, path);
+    if (fclose(file) != 0) osho_file_error(action, path);
+}
+
+static void osho_write_file(const char *path, const char *text) {
+    osho_write_text(path, text, "wb", "write");
+}
+
+static void osho_append_file(const char *path, const char *text) {
+    osho_write_text(path, text, "ab", "append to");
+}
+"#;
+
+/// Integer division and modulo that wrap around like the other operators,
+/// where C would trap on the most negative number divided by -1
+const DIVISION_RUNTIME: &str = r#"
+static long osho_div(long a, long b) {
+    return b == -1 ? (long)(0UL - (unsigned long)a) : a / b;
+}
+
+static long osho_mod(long a, long b) {
+    return b == -1 ? 0 : a % b;
+}
+"#;
+
+/// Stops a program when its arithmetic goes wrong: an integer division by
+/// zero, or with `--checked-arith` any division by zero or NaN result
+const ARITH_RUNTIME: &str = r#"
+static void osho_arith_error(const char *message) {
+    fprintf(stderr, "%s\n", message);
+    exit(OSHO_RUNTIME_ERROR);
+}
+"#;
+
+/// Stops a program built with `--trap-overflow` when integer arithmetic
+/// overflows
+const OVERFLOW_RUNTIME: &str = r#"
+static void osho_overflow(const char *op) {
+    fprintf(stderr, "The result of '%s' overflows\n", op);
+    exit(OSHO_RUNTIME_ERROR);
+}
+
+static long osho_checked_add(long a, long b) {
+    long result;
+    if (__builtin_add_overflow(a, b, &result)) osho_overflow("+");
+    return result;
+}
+
+static long osho_checked_sub(long a, long b) {
+    long result;
+    if (__builtin_sub_overflow(a, b, &result)) osho_overflow("-");
+    return result;
+}
+
+static long osho_checked_mul(long a, long b) {
+    long result;
+    if (__builtin_mul_overflow(a, b, &result)) osho_overflow("*");
+    return result;
+}
+
+static long osho_checked_div(long a, long b) {
+    long result;
+    /* Only the most negative number divided by -1 overflows */
+    if (b == -1 && __builtin_sub_overflow(0, a, &result)) osho_overflow("/");
+    return a / b;
+}
+
+static long osho_checked_mod(long a, long b) {
+    long result;
+    /* The same goes for the remainder, like Rust's checked_rem */
+    if (b == -1 && __builtin_sub_overflow(0, a, &result)) osho_overflow("%");
+    return a % b;
+}
+
+static long osho_checked_neg(long a) {
+    long result;
+    if (__builtin_sub_overflow(0, a, &result)) osho_overflow("-");
+    return result;
+}
+"#;
+
+/// A name that is valid in C. Items of modules are named `module.item`,
+/// which becomes `module__item`.
+fn c_name(name: &str) -> String {
+    name.replace('.', "__")
+}
+
+/// The C type an osho value is emitted as
+fn c_type(ty: &Type) -> String {
+    match ty {
+        Type::Int => "long".to_string(),
+        Type::Float => "double".to_string(),
+        Type::Bool => "bool".to_string(),
+        Type::Array(_) => "osho_array *".to_string(),
+        Type::Struct(name) => format!("struct {} *", c_name(name)),
+        // Strings are never changed
+        Type::Str => "const char *".to_string(),
+        // A code point
+        Type::Char => "long".to_string(),
+        Type::Map(_) => unreachable!("maps aren't lowered"),
+        Type::Function { .. } => format!("osho_{}", mangle(ty)),
+    }
+}
+
+/// Whether values of the type are pointers to the heap, which count their
+/// references
+fn is_reference(ty: &Type) -> bool {
+    matches!(ty, Type::Array(_) | Type::Struct(_) | Type::Str)
+}
+
+/// Whether values of the type are strings or arrays that hold them, so the
+/// program needs the string runtime to count their references
+fn has_strings(ty: &Type) -> bool {
+    match ty {
+        Type::Str => true,
+        Type::Array(element) => has_strings(element),
+        _ => false,
+    }
+}
+
+/// A statement taking a new reference to `value`, which is of type `ty`
+fn retain(ty: &Type, value: &str) -> String {
+    if *ty == Type::Str {
+        format!("osho_retain_str({});\n", value)
+    } else {
+        format!("osho_retain({});\n", value)
+    }
+}
+
+/// A pointer to a value of the C type of `ty`
+fn c_pointer(ty: &Type) -> String {
+    if is_reference(ty) {
+        format!("{}*", c_type(ty))
+    } else {
+        format!("{} *", c_type(ty))
+    }
+}
+
+/// A name for a type to use in generated function names, like `int_array`
+fn mangle(ty: &Type) -> String {
+    match ty {
+        Type::Array(element) => format!("{}_array", mangle(element)),
+        Type::Function { params, result } => {
+            let params: Vec<_> = params.iter().map(mangle).collect();
+            let result = result.as_deref().map_or("void".to_string(), mangle);
+            format!("func_{}_to_{}", params.join("_"), result)
+        }
+        ty => ty.to_string(),
+    }
+}
+
+/// Add the function types `ty` is made of to `found`, the ones they take or
+/// return before them, so each typedef only uses ones before it
+fn function_types(ty: &Type, found: &mut Vec<Type>) {
+    match ty {
+        Type::Array(element) => function_types(element, found),
+        Type::Function { params, result } => {
+            for param in params {
+                function_types(param, found);
+            }
+            if let Some(result) = result {
+                function_types(result, found);
+            }
+            if !found.contains(ty) {
+                found.push(ty.clone());
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The typedef naming a pointer to functions of a function type
+fn function_typedef(ty: &Type) -> String {
+    let Type::Function { params, result } = ty else {
+        unreachable!("only function types have typedefs");
+    };
+    let result = result.as_deref().map_or("void".to_string(), c_type);
+    let params = if params.is_empty() {
+        "void".to_string()
+    } else {
+        params.iter().map(c_type).collect::<Vec<_>>().join(", ")
+    };
+    format!(
+        "typedef {} (*{})({});
+",
+        result,
+        c_type(ty),
+        params
+    )
+}
+
+/// The C definition of a struct, with a function that allocates one. The
+/// struct starts with its reference count, and holds a reference to each
+/// array, struct or string it is given.
+fn struct_definition(def: &StructDef) -> String {
+    let mut fields = String::from("    long osho_refs;\n");
+    let mut params = Vec::new();
+    let mut inits = String::from("    value->osho_refs = 1;\n");
+    for (name, ty) in &def.fields {
+        fields.push_str(&format!("    {};\n", declare(ty, name)));
+        params.push(declare(ty, name));
+        inits.push_str(&format!("    value->{} = {};\n", name, name));
+        if is_reference(ty) {
+            inits.push_str(&format!("    {}", retain(ty, name)));
+        }
+    }
+    format!(
+        "struct {name} {{\n{fields}}};\n\nstatic struct {name} *osho_new_{name}({params}) {{\n    struct {name} *value = malloc(sizeof(struct {name}));\n{inits}    return value;\n}}\n",
+        name = c_name(&def.name),
+        fields = fields,
+        params = params.join(", "),
+        inits = inits
+    )
+}
+
+/// A C declaration of a name with the C type of `ty`
+fn declare(ty: &Type, name: &str) -> String {
+    if is_reference(ty) {
+        format!("{}{}", c_type(ty), name)
+    } else {
+        format!("{} {}", c_type(ty), name)
+    }
+}
+
+/// The C declaration of a function, without a body
+fn function_header(program: &Program, function: &Function) -> String {
+    let name = format!("osho_fn_{}", c_name(&function.name));
+    let header = match &function.result {
+        Some(ty) => declare(ty, &name),
+        None => format!("void {}", name),
+    };
+    let params: Vec<_> = function
+        .params
+        .iter()
+        .map(|id| declare(&program.vars[*id].ty, &program.vars[*id].name))
+        .collect();
+    let params = if params.is_empty() {
+        "void".to_string()
+    } else {
+        params.join(", ")
+    };
+    format!("static {}({})", header, params)
+}
+
+/// Escape text for a printf format string literal
+fn c_string(text: &str) -> String {
+    c_literal(&text.replace('%', "%%"))
+}
+
+/// Escape text for a C string literal
+fn c_literal(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_ascii_control() => out.push_str(&format!("\\{:03o}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Where `continue` goes in a loop being generated
+struct LoopLabels {
+    /// The label before the loop's step, so `continue` still runs the step.
+    /// Loops without a step use C's `continue`.
+    step: Option<String>,
+
+    /// Whether a `continue` jumps to the label, so it must be emitted
+    continued: bool,
+
+    /// Number of scopes outside the loop, so `break` releases the ones
+    /// inside it
+    outer_scopes: usize,
+
+    /// Number of scopes outside the loop's body, so `continue` releases the
+    /// ones inside it
+    body_scopes: usize,
+}
+
+/// Generates C from the typed IR
 pub struct CodeGenerator {
     code: String,
+
+    /// Variables declared so far. Each is declared where it's first assigned.
+    declared: HashSet<VarId>,
+
+    /// Whether the program uses a function from math.h
+    needs_math: bool,
+
+    /// Whether the program uses arrays, which need the array runtime
+    needs_arrays: bool,
+
+    /// Whether the program pushes onto or pops from arrays
+    needs_vectors: bool,
+
+    /// Whether the program uses random numbers
+    needs_random: bool,
+
+    /// Whether the program prints floats, which needs the float runtime
+    needs_floats: bool,
+
+    /// Whether the program constructs structs, which live on the heap
+    needs_structs: bool,
+
+    /// Whether the program stops with `exit()`, either when it calls it or
+    /// when reading input fails
+    needs_exit: bool,
+
+    /// Whether the program calls `exit()`, which needs its runtime
+    needs_exit_runtime: bool,
+
+    /// Whether the program reads its arguments, so `main` takes them
+    needs_args: bool,
+
+    /// Whether the program calls `args()`, which makes an array of them
+    needs_args_array: bool,
+
+    /// Whether the program reads environment variables
+    needs_env: bool,
+
+    /// Whether the program reads or writes files
+    needs_files: bool,
+
+    /// Whether the program makes a conversion that needs a runtime function
+    needs_conversions: bool,
+
+    /// Whether the program has strings, which count their references
+    needs_text: bool,
+
+    /// Whether the program calls a string builtin
+    needs_strings: bool,
+
+    /// Whether the program calls `split()`, which makes an array
+    needs_split: bool,
+
+    /// Whether the program uses characters
+    needs_chars: bool,
+
+    /// Whether the program slices arrays
+    needs_slices: bool,
+
+    /// Whether the program divides integers, which needs `osho_div` and
+    /// `osho_mod`
+    needs_division: bool,
+
+    /// Whether the program divides by a number it checks isn't zero, or
+    /// checks for NaN, which needs `osho_arith_error`
+    needs_arith_checks: bool,
+
+    /// Whether the program traps on integer overflow, which needs the
+    /// `osho_checked_*` functions
+    needs_overflow_checks: bool,
+
+    /// Optional checks the program makes as it runs
+    checks: Checks,
+
+    /// Functions the program needs, like array printers, in the order they
+    /// must be defined
+    helpers: Vec<String>,
+
+    /// Names of the functions in `helpers`
+    helper_names: HashSet<String>,
+
+    /// The string literals defined in `helpers`, each as `osho_text_<index>`
+    texts: Vec<String>,
+
+    /// Definitions of the program's functions, which come after the helpers
+    /// and a prototype of each, so they can call each other
+    functions: Vec<(String, String)>,
+
+    /// Labels of the enclosing loops, innermost last
+    loops: Vec<LoopLabels>,
+
+    /// The arrays, structs and strings declared in each enclosing body,
+    /// innermost last, which are released when it ends
+    scopes: Vec<Vec<VarId>>,
+
+    /// Number of loop labels made so far, to keep them unique
+    labels: usize,
+}
+
+impl Default for CodeGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for CodeGenerator {
+    fn generate(&mut self, ast: &Ast) -> Result<String, CompileError> {
+        let program = SemanticAnalyzer::new()
+            .lower(ast)
+            .map_err(|mut errors| errors.remove(0))?;
+        Ok(self.generate_ir(&program))
+    }
 }
 
 impl CodeGenerator {
     pub fn new() -> Self {
         Self {
             code: String::new(),
+            declared: HashSet::new(),
+            needs_math: false,
+            needs_arrays: false,
+            needs_vectors: false,
+            needs_random: false,
+            needs_floats: false,
+            needs_structs: false,
+            needs_exit: false,
+            needs_exit_runtime: false,
+            needs_args: false,
+            needs_args_array: false,
+            needs_env: false,
+            needs_files: false,
+            needs_conversions: false,
+            needs_text: false,
+            needs_strings: false,
+            needs_split: false,
+            needs_chars: false,
+            needs_slices: false,
+            needs_division: false,
+            needs_arith_checks: false,
+            needs_overflow_checks: false,
+            checks: Checks::default(),
+            helpers: Vec::new(),
+            helper_names: HashSet::new(),
+            texts: Vec::new(),
+            functions: Vec::new(),
+            loops: Vec::new(),
+            scopes: Vec::new(),
+            labels: 0,
         }
     }
 
-    pub fn generate(&mut self, node: &ASTNode) -> Result<String, String> {
+    /// Make the given checks in the generated program
+    pub fn with_checks(mut self, checks: Checks) -> Self {
+        self.checks = checks;
+        self
+    }
+
+    /// Generate a C program from lowered code
+    pub fn generate_ir(&mut self, program: &Program) -> String {
         self.code.clear();
-        self.visit(node)?;
-        let full_code = self.wrap_with_main(self.code.clone());
-        Ok(full_code)
-    }
-
-    fn visit(&mut self, node: &ASTNode) -> Result<(), String> {
-        match node {
-            ASTNode::Program(statements) => {
-                for stmt in statements {
-                    self.visit(stmt)?;
-                }
-            }
-            ASTNode::LetDeclaration { name, value } => {
-                self.code.push_str(&format!("double {} = ", name));
-                self.visit(value)?;
-                self.code.push_str(";\n");
-            }
-            ASTNode::Assignment { name, value } => {
-                self.code.push_str(&format!("{} = ", name));
-                self.visit(value)?;
-                self.code.push_str(";\n");
-            }
-            ASTNode::Increment(name) => {
-                self.code.push_str(&format!("{}++;\n", name));
-            }
-            ASTNode::Decrement(name) => {
-                self.code.push_str(&format!("{}--;\n", name));
-            }
-            ASTNode::Print(expr) => {
-                self.code.push_str("printf(\"%f\\n\", ");
-                self.visit(expr)?;
-                self.code.push_str(");\n");
-            }
-            ASTNode::BinaryOp { left, op, right } => {
-                self.code.push('(');
-                self.visit(left)?;
-                match op {
-                    BinaryOperator::Plus => self.code.push_str(" + "),
-                    BinaryOperator::Minus => self.code.push_str(" - "),
-                    BinaryOperator::Multiply => self.code.push_str(" * "),
-                    BinaryOperator::Divide => self.code.push_str(" / "),
-                }
-                self.visit(right)?;
-                self.code.push(')');
-            }
-            ASTNode::Number(num) => {
-                if num.fract() == 0.0 {
-                    // If num is an integer, append ".0"
-                    self.code.push_str(&format!("{:.1}", num));
+        self.declared.clear();
+        self.needs_math = false;
+        self.needs_arrays = false;
+        self.needs_vectors = false;
+        self.needs_random = false;
+        self.needs_floats = false;
+        self.needs_exit = false;
+        self.needs_exit_runtime = false;
+        self.needs_args = false;
+        self.needs_args_array = false;
+        self.needs_env = false;
+        self.needs_files = false;
+        self.needs_conversions = false;
+        self.needs_strings = false;
+        self.needs_split = false;
+        self.needs_chars = false;
+        self.needs_slices = false;
+        self.needs_division = false;
+        self.needs_arith_checks = false;
+        self.needs_overflow_checks = false;
+        self.helpers.clear();
+        self.helper_names.clear();
+        self.texts.clear();
+        self.functions.clear();
+        self.scopes.clear();
+        self.labels = 0;
+        self.needs_structs = !program.structs.is_empty();
+        self.needs_text = program.vars.iter().any(|var| has_strings(&var.ty))
+            || program
+                .structs
+                .iter()
+                .any(|def| def.fields.iter().any(|(_, ty)| has_strings(ty)));
+        // Function pointer types come first, since struct fields may be
+        // ones, and they may take structs, which are declared for them
+        let mut types = Vec::new();
+        for var in &program.vars {
+            function_types(&var.ty, &mut types);
+        }
+        for def in &program.structs {
+            for (_, ty) in &def.fields {
+                function_types(ty, &mut types);
+            }
+        }
+        for result in program
+            .functions
+            .iter()
+            .filter_map(|function| function.result.as_ref())
+        {
+            function_types(result, &mut types);
+        }
+        if !types.is_empty() {
+            let mut typedefs = String::new();
+            for def in &program.structs {
+                typedefs.push_str(&format!(
+                    "struct {};
+",
+                    c_name(&def.name)
+                ));
+            }
+            for ty in &types {
+                typedefs.push_str(&function_typedef(ty));
+            }
+            self.helpers.push(typedefs);
+        }
+        for def in &program.structs {
+            self.helpers.push(struct_definition(def));
+        }
+        for function in &program.functions {
+            self.function(program, function);
+        }
+        self.visit_scope(program, &program.body);
+        self.wrap_with_main(self.code.clone())
+    }
+
+    /// Generate a function into `functions`. Its parameters are declared
+    /// by its header, and borrow the caller's references since they can't
+    /// be assigned.
+    fn function(&mut self, program: &Program, function: &Function) {
+        self.declared.extend(&function.params);
+        let header = function_header(program, function);
+        self.visit_scope(program, &function.body);
+        let body = std::mem::take(&mut self.code);
+        self.functions
+            .push((header.clone(), format!("{} {{\n{}}}\n", header, body)));
+    }
+
+    fn visit_body(&mut self, program: &Program, body: &[Instr]) {
+        for instr in body {
+            self.visit(program, instr);
+        }
+    }
+
+    /// Generate a body that is a scope of its own, releasing the arrays,
+    /// structs and strings declared in it at its end unless it has already
+    /// left
+    fn visit_scope(&mut self, program: &Program, body: &[Instr]) {
+        self.scopes.push(Vec::new());
+        self.visit_body(program, body);
+        let declared = self.scopes.pop().expect("pushed above");
+        if !matches!(
+            body.last(),
+            Some(Instr::Return(_) | Instr::Break | Instr::Continue)
+        ) {
+            self.release(program, &declared);
+        }
+    }
+
+    /// Release the given variables, last declared first
+    fn release(&mut self, program: &Program, vars: &[VarId]) {
+        for var in vars.iter().rev() {
+            let var = &program.vars[*var];
+            let release = self.releaser(program, &var.ty);
+            self.code.push_str(&format!("{}({});\n", release, var.name));
+        }
+    }
+
+    /// Release the variables of the innermost scopes, leaving the first
+    /// `outer` scopes, before jumping out of the others
+    fn release_scopes(&mut self, program: &Program, outer: usize) {
+        let vars: Vec<_> = self.scopes[outer..].concat();
+        self.release(program, &vars);
+    }
+
+    fn visit(&mut self, program: &Program, instr: &Instr) {
+        match instr {
+            Instr::Copy { dest, value } => {
+                self.assign(program, *dest, program.operand(*value));
+            }
+            Instr::Text { dest, text } => {
+                let value = self.text(text);
+                self.assign(program, *dest, value);
+            }
+            Instr::Binary {
+                dest,
+                op,
+                left,
+                right,
+            } => {
+                let checked = self.checks.arith && !op.is_comparison() && !op.is_logical();
+                // Integers can't carry on with inf, so they are always checked
+                let integer = program.type_of(*left) == Type::Int;
+                if (checked || integer)
+                    && matches!(op, BinaryOperator::Divide | BinaryOperator::Modulo)
+                {
+                    let message = if *op == BinaryOperator::Divide {
+                        "Division by zero"
+                    } else {
+                        "Modulo by zero"
+                    };
+                    self.needs_arith_checks = true;
+                    self.code.push_str(&format!(
+                        "if ({} == 0) osho_arith_error(\"{}\");\n",
+                        program.operand(*right),
+                        message
+                    ));
+                }
+                self.binary(program, *dest, op, *left, *right);
+                if checked && program.vars[*dest].ty == Type::Float {
+                    self.needs_math = true;
+                    self.needs_arith_checks = true;
+                    self.code.push_str(&format!(
+                        "if (isnan({})) osho_arith_error(\"{}\");\n",
+                        program.vars[*dest].name,
+                        checks::nan_message(op)
+                    ));
+                }
+            }
+            Instr::Unary {
+                dest,
+                op: UnaryOperator::Negate,
+                operand,
+            } if self.checks.overflow && program.type_of(*operand) == Type::Int => {
+                self.needs_overflow_checks = true;
+                let value = format!("osho_checked_neg({})", program.operand(*operand));
+                self.assign(program, *dest, value);
+            }
+            Instr::Unary { dest, op, operand } => {
+                let op = match op {
+                    UnaryOperator::Not => "!",
+                    UnaryOperator::Negate => "-",
+                };
+                let value = format!("{}({})", op, program.operand(*operand));
+                self.assign(program, *dest, value);
+            }
+            Instr::IntToFloat { dest, value } => {
+                let value = format!("(double){}", program.operand(*value));
+                self.assign(program, *dest, value);
+            }
+            Instr::Truthy { dest, value } => {
+                let value = match program.type_of(*value) {
+                    Type::Bool => program.operand(*value),
+                    Type::Str => format!("*{} != '\\0'", program.operand(*value)),
+                    _ => format!("{} != 0", program.operand(*value)),
+                };
+                self.assign(program, *dest, value);
+            }
+            Instr::Select {
+                dest,
+                condition,
+                then_value,
+                else_value,
+            } => {
+                let value = format!(
+                    "{} ? {} : {}",
+                    program.operand(*condition),
+                    program.operand(*then_value),
+                    program.operand(*else_value)
+                );
+                self.assign(program, *dest, value);
+            }
+            Instr::Declare(id) => {
+                self.declared.insert(*id);
+                let var = &program.vars[*id];
+                if is_reference(&var.ty) {
+                    self.code
+                        .push_str(&format!("{} = NULL;\n", declare(&var.ty, &var.name)));
+                    self.scopes
+                        .last_mut()
+                        .expect("instructions are generated in a scope")
+                        .push(*id);
+                } else {
+                    self.code
+                        .push_str(&format!("{};\n", declare(&var.ty, &var.name)));
+                }
+            }
+            Instr::Print {
+                pieces,
+                values,
+                newline,
+            } => {
+                // printf can't print arrays, so each one is printed by a call
+                // of its own between printfs of the rest
+                let mut format = c_string(&pieces[0]);
+                let mut args = String::new();
+                for (value, piece) in values.iter().zip(&pieces[1..]) {
+                    let code = program.operand(*value);
+                    match program.type_of(*value) {
+                        ty @ (Type::Array(_) | Type::Struct(_)) => {
+                            self.printf(&mut format, &mut args);
+                            let printer = self.printer(program, &ty);
+                            self.code.push_str(&format!("{}({});\n", printer, code));
+                        }
+                        ty => {
+                            let (specifier, arg) = self.printf_arg(&ty, code);
+                            format.push_str(specifier);
+                            args.push_str(", ");
+                            args.push_str(&arg);
+                        }
+                    }
+                    format.push_str(&c_string(piece));
+                }
+                if *newline {
+                    format.push_str("\\n");
+                }
+                self.printf(&mut format, &mut args);
+            }
+            Instr::Input { dest } => {
+                let var = &program.vars[*dest];
+                let format = match var.ty {
+                    Type::Float => "%lf",
+                    _ => "%ld",
+                };
+                self.needs_exit = true;
+                self.code.push_str(&format!(
+                    "if (scanf(\"{}\", &{}) != 1) {{\nfprintf(stderr, \"Expected {} input\\n\");\nexit(OSHO_RUNTIME_ERROR);\n}}\n",
+                    format, var.name, var.ty
+                ));
+            }
+            Instr::Array { dest, elements } => {
+                self.needs_arrays = true;
+                let Type::Array(element) = &program.vars[*dest].ty else {
+                    unreachable!("arrays are created into array variables");
+                };
+                let value = format!(
+                    "osho_array_new({}, sizeof({}))",
+                    elements.len(),
+                    c_type(element)
+                );
+                self.assign_new(program, *dest, value);
+                let data = format!(
+                    "(({}){}->data)",
+                    c_pointer(element),
+                    program.vars[*dest].name
+                );
+                for (i, value) in elements.iter().enumerate() {
+                    let value = program.operand(*value);
+                    self.code
+                        .push_str(&format!("{}[{}] = {};\n", data, i, value));
+                    if is_reference(element) {
+                        self.code.push_str(&retain(element, &value));
+                    }
+                }
+            }
+            Instr::Index { dest, array, index } if program.type_of(*array) == Type::Str => {
+                self.use_chars();
+                let value = format!(
+                    "osho_char_at({}, {})",
+                    program.operand(*array),
+                    program.operand(*index)
+                );
+                self.assign(program, *dest, value);
+            }
+            Instr::Index { dest, array, index } => {
+                let element = self.element(program, *array, *index);
+                self.assign(program, *dest, element);
+            }
+            Instr::Slice {
+                dest,
+                array,
+                start,
+                end,
+            } => {
+                let value = match program.type_of(*array) {
+                    Type::Str => {
+                        self.use_strings();
+                        format!(
+                            "osho_str_slice({}, {}, {})",
+                            program.operand(*array),
+                            program.operand(*start),
+                            program.operand(*end)
+                        )
+                    }
+                    Type::Array(element) => {
+                        self.needs_arrays = true;
+                        self.needs_slices = true;
+                        let slice = format!(
+                            "osho_slice({}, {}, {}, sizeof({}))",
+                            program.operand(*array),
+                            program.operand(*start),
+                            program.operand(*end),
+                            c_type(&element)
+                        );
+                        // The copy holds its own references to the elements
+                        if matches!(*element, Type::Array(_) | Type::Struct(_)) {
+                            format!("osho_retain_elements({})", slice)
+                        } else {
+                            slice
+                        }
+                    }
+                    _ => unreachable!("only arrays and strings are sliced"),
+                };
+                self.assign_new(program, *dest, value);
+                // A copy of strings holds its own references to them too
+                if program.vars[*dest].ty == Type::Array(Box::new(Type::Str)) {
+                    let data = self.data(program, Operand::Var(*dest));
+                    self.code.push_str(&format!(
+                        "for (long i = 0; i < {}->len; i++) osho_retain_str({}[i]);\n",
+                        program.vars[*dest].name, data
+                    ));
+                }
+            }
+            Instr::SetIndex {
+                array,
+                index,
+                value,
+            } => {
+                let element = self.element(program, *array, *index);
+                self.store(program, element, *value);
+            }
+            Instr::Struct { dest, fields } => {
+                let Type::Struct(name) = &program.vars[*dest].ty else {
+                    unreachable!("structs are created into struct variables");
+                };
+                let value = format!("osho_new_{}({})", c_name(name), program.operands(fields));
+                self.assign_new(program, *dest, value);
+            }
+            Instr::GetField {
+                dest,
+                object,
+                field,
+            } => {
+                let value = format!("{}->{}", program.operand(*object), field);
+                self.assign(program, *dest, value);
+            }
+            Instr::SetField {
+                object,
+                field,
+                value,
+            } => {
+                let target = format!("{}->{}", program.operand(*object), field);
+                self.store(program, target, *value);
+            }
+            Instr::Call {
+                dest,
+                builtin: Builtin::Len,
+                args,
+            } if program.type_of(args[0]) == Type::Str => {
+                self.use_strings();
+                let value = format!("osho_str_len({})", program.operand(args[0]));
+                self.assign(program, dest.expect("len() returns a value"), value);
+            }
+            Instr::Call {
+                dest,
+                builtin:
+                    builtin @ (Builtin::Substring
+                    | Builtin::Find
+                    | Builtin::Split
+                    | Builtin::Upper
+                    | Builtin::Lower),
+                args,
+            } => {
+                self.use_strings();
+                if *builtin == Builtin::Split {
+                    self.needs_arrays = true;
+                    self.needs_split = true;
+                }
+                let call = format!("osho_{}({})", builtin.name(), program.operands(args));
+                self.assign_new(program, dest.expect("string builtins give a value"), call);
+            }
+            Instr::Call {
+                dest,
+                builtin: Builtin::Len,
+                args,
+            } => {
+                let value = format!("{}->len", program.operand(args[0]));
+                self.assign(program, dest.expect("len() returns a value"), value);
+            }
+            Instr::Call {
+                builtin: Builtin::Push,
+                args,
+                ..
+            } => {
+                self.needs_vectors = true;
+                let element = self.data(program, args[0]);
+                let array = program.operand(args[0]);
+                let value = program.operand(args[1]);
+                self.code.push_str(&format!(
+                    "osho_array_grow({}, sizeof(*{}));\n{}[{}->len - 1] = {};\n",
+                    array, element, element, array, value
+                ));
+                let ty = program.type_of(args[1]);
+                if is_reference(&ty) {
+                    self.code.push_str(&retain(&ty, &value));
+                }
+            }
+            Instr::Call {
+                dest,
+                builtin: Builtin::Pop,
+                args,
+            } => {
+                self.needs_vectors = true;
+                let element = self.data(program, args[0]);
+                let value = format!("{}[osho_array_pop({})]", element, program.operand(args[0]));
+                // The array's reference to the element passes to the variable
+                self.assign_new(program, dest.expect("pop() returns a value"), value);
+            }
+            Instr::Call {
+                builtin: Builtin::Keys,
+                ..
+            } => unreachable!("maps aren't lowered"),
+            Instr::Call {
+                dest,
+                builtin: builtin @ (Builtin::Rand | Builtin::RandRange | Builtin::Seed),
+                args,
+            } => {
+                self.needs_random = true;
+                let call = format!("osho_{}({})", builtin.name(), program.operands(args));
+                match dest {
+                    Some(dest) => self.assign(program, *dest, call),
+                    None => self.code.push_str(&format!("{};\n", call)),
+                }
+            }
+            Instr::Call {
+                builtin: Builtin::Exit,
+                args,
+                ..
+            } => {
+                self.needs_exit = true;
+                self.needs_exit_runtime = true;
+                self.code
+                    .push_str(&format!("osho_exit({});\n", program.operand(args[0])));
+            }
+            Instr::Call {
+                dest,
+                builtin: Builtin::Env,
+                args,
+            } => {
+                self.needs_env = true;
+                self.needs_text = true;
+                let call = format!("osho_env({})", program.operands(args));
+                self.assign_new(program, dest.expect("env() gives a value"), call);
+            }
+            Instr::Call {
+                dest,
+                builtin: builtin @ (Builtin::Int | Builtin::Float | Builtin::Str),
+                args,
+            } => {
+                let dest = dest.expect("conversions give a value");
+                let value = self.conversion(program, *builtin, args[0]);
+                // `str()` of a number makes a new string
+                if *builtin == Builtin::Str
+                    && matches!(program.type_of(args[0]), Type::Int | Type::Float)
+                {
+                    self.assign_new(program, dest, value);
+                } else {
+                    self.assign(program, dest, value);
+                }
+            }
+            Instr::Call {
+                dest,
+                builtin: Builtin::Ord,
+                args,
+            } => {
+                let value = program.operand(args[0]);
+                self.assign(program, dest.expect("ord() gives a value"), value);
+            }
+            Instr::Call {
+                dest,
+                builtin: Builtin::Chr,
+                args,
+            } => {
+                self.use_chars();
+                let value = format!("osho_chr({})", program.operand(args[0]));
+                self.assign(program, dest.expect("chr() gives a value"), value);
+            }
+            Instr::Call {
+                dest,
+                builtin: Builtin::ParseNum,
+                args,
+            } => {
+                self.use_conversions();
+                let value = format!("osho_parse_num({})", program.operand(args[0]));
+                self.assign(program, dest.expect("parse_num() gives a value"), value);
+            }
+            Instr::Call {
+                dest,
+                builtin,
+                args,
+            } if builtin.uses_files() => {
+                self.needs_files = true;
+                self.needs_text = true;
+                self.needs_exit = true;
+                let call = format!("osho_{}({})", builtin.name(), program.operands(args));
+                match dest {
+                    Some(dest) => self.assign_new(program, *dest, call),
+                    None => self.code.push_str(&format!("{};\n", call)),
+                }
+            }
+            Instr::Call {
+                dest,
+                builtin,
+                args,
+            } if builtin.reads_arguments() => {
+                self.needs_args = true;
+                self.needs_text = true;
+                self.needs_exit = true;
+                if *builtin == Builtin::Args {
+                    self.needs_arrays = true;
+                    self.needs_args_array = true;
+                }
+                let call = format!("osho_{}({})", builtin.name(), program.operands(args));
+                self.assign_new(program, dest.expect("arguments are read as values"), call);
+            }
+            Instr::Call {
+                dest,
+                builtin,
+                args,
+            } => {
+                let value = self.math(program, *builtin, args);
+                self.assign(program, dest.expect("math functions return a value"), value);
+            }
+            Instr::CallFunction {
+                dest,
+                function,
+                args,
+            } => {
+                let call = format!("osho_fn_{}({})", c_name(function), program.operands(args));
+                // A function gives back a reference of its own
+                match dest {
+                    Some(dest) => self.assign_new(program, *dest, call),
+                    None => self.code.push_str(&format!("{};\n", call)),
+                }
+            }
+            Instr::FunctionRef { dest, function } => {
+                let value = format!("osho_fn_{}", c_name(function));
+                self.assign(program, *dest, value);
+            }
+            Instr::CallValue { dest, callee, args } => {
+                let call = format!("{}({})", program.operand(*callee), program.operands(args));
+                match dest {
+                    Some(dest) => self.assign_new(program, *dest, call),
+                    None => self.code.push_str(&format!("{};\n", call)),
+                }
+            }
+            Instr::Return(Some(value)) => {
+                // The caller gets a reference of its own, which outlives the
+                // ones released here
+                let code = program.operand(*value);
+                let ty = program.type_of(*value);
+                if is_reference(&ty) {
+                    self.code.push_str(&retain(&ty, &code));
+                }
+                self.release_scopes(program, 0);
+                self.code.push_str(&format!("return {};\n", code));
+            }
+            Instr::Return(None) => {
+                self.release_scopes(program, 0);
+                self.code.push_str("return;\n");
+            }
+            Instr::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                self.code
+                    .push_str(&format!("if ({}) {{\n", program.operand(*condition)));
+                self.visit_scope(program, then_body);
+                if !else_body.is_empty() {
+                    self.code.push_str("} else {\n");
+                    self.visit_scope(program, else_body);
+                }
+                self.code.push_str("}\n");
+            }
+            Instr::Loop {
+                header,
+                condition,
+                body,
+                step,
+            } => {
+                let condition = program.operand(*condition);
+                // The header and step share a scope around the body's
+                let outer_scopes = self.scopes.len();
+                self.scopes.push(Vec::new());
+                if header.is_empty() && step.is_empty() {
+                    self.code.push_str(&format!("while ({}) {{\n", condition));
+                } else {
+                    // The condition is computed by instructions of its own,
+                    // so it's tested inside the loop
+                    self.code.push_str("while (true) {\n");
+                    self.visit_body(program, header);
+                    if self.scopes[outer_scopes].is_empty() {
+                        self.code.push_str(&format!("if (!{}) break;\n", condition));
+                    } else {
+                        self.code.push_str(&format!("if (!{}) {{\n", condition));
+                        self.release_scopes(program, outer_scopes);
+                        self.code.push_str("break;\n}\n");
+                    }
+                }
+                let step_label = (!step.is_empty()).then(|| {
+                    self.labels += 1;
+                    format!("osho_continue_{}", self.labels)
+                });
+                self.loops.push(LoopLabels {
+                    step: step_label,
+                    continued: false,
+                    outer_scopes,
+                    body_scopes: outer_scopes + 1,
+                });
+                self.visit_scope(program, body);
+                let labels = self.loops.last().expect("pushed above");
+                if let (Some(label), true) = (&labels.step, labels.continued) {
+                    self.code.push_str(&format!("{}:;\n", label));
+                }
+                // A do-while loop breaks out of its step
+                self.visit_body(program, step);
+                self.loops.pop();
+                let declared = self.scopes.pop().expect("pushed above");
+                self.release(program, &declared);
+                self.code.push_str("}\n");
+            }
+            Instr::ForRange {
+                var,
+                start,
+                end,
+                inclusive,
+                body,
+            } => {
+                self.declared.insert(*var);
+                let name = &program.vars[*var].name;
+                let end = program.operand(*end);
+                self.code.push_str(&format!(
+                    "for (long {0} = {1}; {0} {2} {3}; {0}++) {{\n",
+                    name,
+                    program.operand(*start),
+                    if *inclusive { "<=" } else { "<" },
+                    end
+                ));
+                // An inclusive range stops at its end rather than count past
+                // it, which would overflow when the end is the largest long.
+                // `continue` has to go through that check too.
+                let step_label = inclusive.then(|| {
+                    self.labels += 1;
+                    format!("osho_continue_{}", self.labels)
+                });
+                self.loops.push(LoopLabels {
+                    step: step_label,
+                    continued: false,
+                    outer_scopes: self.scopes.len(),
+                    body_scopes: self.scopes.len(),
+                });
+                self.visit_scope(program, body);
+                let labels = self.loops.pop().expect("pushed above");
+                if let (Some(label), true) = (labels.step, labels.continued) {
+                    self.code.push_str(&format!("{}:;\n", label));
+                }
+                if *inclusive {
+                    self.code
+                        .push_str(&format!("if ({} == {}) break;\n", name, end));
+                }
+                self.code.push_str("}\n");
+            }
+            Instr::Fail { pieces, values } => {
+                let mut format = c_string(&pieces[0]);
+                let mut args = String::new();
+                for (value, piece) in values.iter().zip(&pieces[1..]) {
+                    let ty = program.type_of(*value);
+                    let (specifier, arg) = self.printf_arg(&ty, program.operand(*value));
+                    format.push_str(specifier);
+                    args.push_str(", ");
+                    args.push_str(&arg);
+                    format.push_str(&c_string(piece));
+                }
+                self.needs_exit = true;
+                self.code.push_str(&format!(
+                    "fprintf(stderr, \"{}\\n\"{});\nexit(OSHO_RUNTIME_ERROR);\n",
+                    format, args
+                ));
+            }
+            Instr::Break => {
+                let labels = self.loops.last().expect("break is inside a loop");
+                self.release_scopes(program, labels.outer_scopes);
+                self.code.push_str("break;\n");
+            }
+            Instr::Continue => {
+                let labels = self.loops.last().expect("continue is inside a loop");
+                self.release_scopes(program, labels.body_scopes);
+                match self.loops.last_mut() {
+                    Some(LoopLabels {
+                        step: Some(label),
+                        continued,
+                        ..
+                    }) => {
+                        *continued = true;
+                        self.code.push_str(&format!("goto {};\n", label));
+                    }
+                    _ => self.code.push_str("continue;\n"),
+                }
+            }
+        }
+    }
+
+    /// Emit a printf of the format and arguments gathered so far, if they
+    /// print anything, and clear them
+    fn printf(&mut self, format: &mut String, args: &mut String) {
+        if !format.is_empty() {
+            self.code
+                .push_str(&format!("printf(\"{}\"{});\n", format, args));
+        }
+        format.clear();
+        args.clear();
+    }
+
+    /// The element of an array at an index, checked to be in bounds
+    fn element(&mut self, program: &Program, array: Operand, index: Operand) -> String {
+        format!(
+            "{}[osho_index({}, {})]",
+            self.data(program, array),
+            program.operand(array),
+            program.operand(index)
+        )
+    }
+
+    /// The name of a function printing arrays or structs of type `ty`,
+    /// defining it the first time it's needed
+    fn printer(&mut self, program: &Program, ty: &Type) -> String {
+        let name = format!("osho_print_{}", mangle(ty));
+        if self.helper_names.contains(&name) {
+            return name;
+        }
+        let helper = match ty {
+            Type::Array(element) => {
+                let data = format!("(({})array->data)[i]", c_pointer(element));
+                format!(
+                    "static void {}(const osho_array *array) {{\n    printf(\"[\");\n    for (long i = 0; i < array->len; i++) {{\n        if (i > 0) printf(\", \");\n        {}\n    }}\n    printf(\"]\");\n}}\n",
+                    name,
+                    self.print_value(program, element, data)
+                )
+            }
+            Type::Struct(struct_name) => {
+                let def = program
+                    .struct_def(struct_name)
+                    .expect("printed structs are constructed");
+                let mut body = String::new();
+                for (i, (field, field_type)) in def.fields.iter().enumerate() {
+                    let separator = if i == 0 { " " } else { ", " };
+                    body.push_str(&format!(
+                        "    printf(\"{}{}: \");\n    {}\n",
+                        separator,
+                        field,
+                        self.print_value(program, field_type, format!("value->{}", field))
+                    ));
+                }
+                format!(
+                    "static void {}(const struct {} *value) {{\n    printf(\"{} {{\");\n{}    printf(\" }}\");\n}}\n",
+                    name, struct_name, struct_name, body
+                )
+            }
+            _ => unreachable!("only arrays and structs have printers"),
+        };
+        self.helpers.push(helper);
+        self.helper_names.insert(name.clone());
+        name
+    }
+
+    /// The printf specifier and argument that print a number or bool
+    fn printf_arg(&mut self, ty: &Type, code: String) -> (&'static str, String) {
+        match ty {
+            // Int literals are C ints, so widen them for %ld
+            Type::Int => ("%ld", format!("(long){}", code)),
+            Type::Float => {
+                self.needs_floats = true;
+                (
+                    "%s",
+                    format!("osho_float((char[OSHO_FLOAT_SIZE]){{0}}, {})", code),
+                )
+            }
+            Type::Bool => ("%s", format!("{} ? \"true\" : \"false\"", code)),
+            Type::Str => ("%s", code),
+            Type::Char => {
+                self.use_chars();
+                ("%s", format!("osho_char((char[5]){{0}}, {})", code))
+            }
+            Type::Array(_) | Type::Struct(_) => {
+                unreachable!("arrays and structs are printed by a helper")
+            }
+            Type::Map(_) => unreachable!("maps aren't lowered"),
+            Type::Function { .. } => unreachable!("printing functions isn't lowered"),
+        }
+    }
+
+    /// A statement printing a value inside an array or struct
+    fn print_value(&mut self, program: &Program, ty: &Type, code: String) -> String {
+        if matches!(ty, Type::Array(_) | Type::Struct(_)) {
+            format!("{}({});", self.printer(program, ty), code)
+        } else if *ty == Type::Str {
+            // Quoted like the interpreter, though without escaping
+            format!("printf(\"\\\"%s\\\"\", {});", code)
+        } else if *ty == Type::Char {
+            // Quoted and escaped like the interpreter
+            self.use_chars();
+            format!("osho_show_char({});", code)
+        } else {
+            let (specifier, arg) = self.printf_arg(ty, code);
+            format!("printf(\"{}\", {});", specifier, arg)
+        }
+    }
+
+    /// The elements of an array, as a pointer to their C type
+    fn data(&mut self, program: &Program, array: Operand) -> String {
+        self.needs_arrays = true;
+        let Type::Array(element) = program.type_of(array) else {
+            unreachable!("only arrays have elements");
+        };
+        format!(
+            "(({}){}->data)",
+            c_pointer(&element),
+            program.operand(array)
+        )
+    }
+
+    /// Generate an operator applied to two operands
+    fn binary(
+        &mut self,
+        program: &Program,
+        dest: VarId,
+        op: &BinaryOperator,
+        left: Operand,
+        right: Operand,
+    ) {
+        match op {
+            BinaryOperator::Modulo | BinaryOperator::Power
+                if program.type_of(left) == Type::Float =>
+            {
+                // C has no power operator, and its % only works on integers
+                self.needs_math = true;
+                let function = if *op == BinaryOperator::Power {
+                    "pow"
+                } else {
+                    "fmod"
+                };
+                let value = format!(
+                    "{}({}, {})",
+                    function,
+                    program.operand(left),
+                    program.operand(right)
+                );
+                self.assign(program, dest, value);
+            }
+            BinaryOperator::Plus
+            | BinaryOperator::Minus
+            | BinaryOperator::Multiply
+            | BinaryOperator::Divide
+            | BinaryOperator::Modulo
+                if self.checks.overflow && program.type_of(left) == Type::Int =>
+            {
+                self.needs_overflow_checks = true;
+                let function = match op {
+                    BinaryOperator::Plus => "add",
+                    BinaryOperator::Minus => "sub",
+                    BinaryOperator::Multiply => "mul",
+                    BinaryOperator::Divide => "div",
+                    _ => "mod",
+                };
+                let value = format!(
+                    "osho_checked_{}({}, {})",
+                    function,
+                    program.operand(left),
+                    program.operand(right)
+                );
+                self.assign(program, dest, value);
+            }
+            // Only -1 can make C's division overflow, so other constants
+            // divide with the plain operators
+            BinaryOperator::Divide | BinaryOperator::Modulo
+                if program.type_of(left) == Type::Int
+                    && !matches!(right, Operand::Int(divisor) if divisor != -1 && divisor != 0) =>
+            {
+                self.needs_division = true;
+                let function = if *op == BinaryOperator::Divide {
+                    "osho_div"
                 } else {
-                    self.code.push_str(&num.to_string());
+                    "osho_mod"
+                };
+                let value = format!(
+                    "{}({}, {})",
+                    function,
+                    program.operand(left),
+                    program.operand(right)
+                );
+                self.assign(program, dest, value);
+            }
+            _ => {
+                let value = format!(
+                    "{} {} {}",
+                    program.operand(left),
+                    op.symbol(),
+                    program.operand(right)
+                );
+                self.assign(program, dest, value);
+            }
+        }
+    }
+
+    /// Assign to a variable, declaring it the first time. An array, struct
+    /// or string gets a reference of its own, since `value` is still held by
+    /// whatever it was read from.
+    fn assign(&mut self, program: &Program, dest: VarId, value: String) {
+        self.assign_value(program, dest, value, true);
+    }
+
+    /// Assign a newly made array, struct or string to a variable, which
+    /// takes the reference it was made with
+    fn assign_new(&mut self, program: &Program, dest: VarId, value: String) {
+        self.assign_value(program, dest, value, false);
+    }
+
+    fn assign_value(&mut self, program: &Program, dest: VarId, value: String, shared: bool) {
+        let var = &program.vars[dest];
+        let reference = is_reference(&var.ty);
+        let retained = if reference && shared {
+            retain(&var.ty, &var.name)
+        } else {
+            String::new()
+        };
+        if self.declared.insert(dest) {
+            // A const array or struct pointer still lets what it points to
+            // be assigned to
+            let declaration = match (&var.ty, var.mutable) {
+                (ty, true) => declare(ty, &var.name),
+                (ty, false) if reference => format!("{}const {}", c_type(ty), var.name),
+                (ty, false) => format!("const {} {}", c_type(ty), var.name),
+            };
+            self.code
+                .push_str(&format!("{} = {};\n{}", declaration, value, retained));
+            if reference {
+                self.scopes
+                    .last_mut()
+                    .expect("instructions are generated in a scope")
+                    .push(dest);
+            }
+        } else if reference {
+            // The old value is released after the new one is read, which
+            // may be made from it
+            let release = self.releaser(program, &var.ty);
+            self.code.push_str(&format!(
+                "{{\n{} = {};\n{} = {};\n{}{}(osho_old);\n}}\n",
+                declare(&var.ty, "osho_old"),
+                var.name,
+                var.name,
+                value,
+                retained,
+                release
+            ));
+        } else {
+            self.code.push_str(&format!("{} = {};\n", var.name, value));
+        }
+    }
+
+    /// Store a value into an element of an array or a field of a struct,
+    /// which holds a reference to it if it's an array, struct or string
+    fn store(&mut self, program: &Program, target: String, value: Operand) {
+        let ty = program.type_of(value);
+        let value = program.operand(value);
+        if is_reference(&ty) {
+            let release = self.releaser(program, &ty);
+            self.code.push_str(&format!(
+                "{{\n{} = {};\n{} = {};\n{}{}(osho_old);\n}}\n",
+                declare(&ty, "osho_old"),
+                target,
+                target,
+                value,
+                retain(&ty, &value),
+                release
+            ));
+        } else {
+            self.code.push_str(&format!("{} = {};\n", target, value));
+        }
+    }
+
+    /// The name of a function releasing a reference to an array, struct or
+    /// string of type `ty`, which frees it along with what only it refers to
+    /// when that was the last one. Defined the first time it's needed, after
+    /// a declaration so types that contain themselves can be released.
+    fn releaser(&mut self, program: &Program, ty: &Type) -> String {
+        // Strings hold nothing else, so the runtime releases them all
+        if *ty == Type::Str {
+            return "osho_release_str".to_string();
+        }
+        let name = format!("osho_release_{}", mangle(ty));
+        if !self.helper_names.insert(name.clone()) {
+            return name;
+        }
+        let header = format!("static void {}({})", name, declare(ty, "value"));
+        self.helpers.push(format!("{};\n", header));
+        let (refs, body) = match ty {
+            Type::Array(element) if is_reference(element) => {
+                let release = self.releaser(program, element);
+                let body = format!(
+                    "    for (long i = 0; i < value->len; i++) {}((({})value->data)[i]);\n    free(value->data);\n",
+                    release,
+                    c_pointer(element)
+                );
+                ("refs", body)
+            }
+            Type::Array(_) => ("refs", "    free(value->data);\n".to_string()),
+            Type::Struct(struct_name) => {
+                let def = program
+                    .struct_def(struct_name)
+                    .expect("released structs are constructed");
+                let mut body = String::new();
+                for (field, field_type) in &def.fields {
+                    if is_reference(field_type) {
+                        let release = self.releaser(program, field_type);
+                        body.push_str(&format!("    {}(value->{});\n", release, field));
+                    }
                 }
+                ("osho_refs", body)
+            }
+            _ => unreachable!("only arrays and structs are released"),
+        };
+        self.helpers.push(format!(
+            "{} {{\n    if (value == NULL || --value->{} > 0) return;\n{}    free(value);\n}}\n",
+            header, refs, body
+        ));
+        name
+    }
+
+    /// The C expression converting a value with `int()`, `float()` or
+    /// `str()`. Converting a value to its own type gives it back.
+    fn conversion(&mut self, program: &Program, builtin: Builtin, arg: Operand) -> String {
+        let value = program.operand(arg);
+        match (builtin, program.type_of(arg)) {
+            (Builtin::Int, Type::Float) => {
+                self.use_conversions();
+                format!("osho_float_to_int({})", value)
+            }
+            (Builtin::Int, Type::Bool) => format!("(long){}", value),
+            (Builtin::Float, Type::Int | Type::Bool) => format!("(double){}", value),
+            (Builtin::Str, Type::Int) => {
+                self.use_conversions();
+                format!("osho_int_string({})", value)
+            }
+            (Builtin::Str, Type::Float) => {
+                self.use_conversions();
+                format!("osho_float_string({})", value)
+            }
+            (Builtin::Str, Type::Bool) => {
+                format!(
+                    "({} ? {} : {})",
+                    value,
+                    self.text("true"),
+                    self.text("false")
+                )
+            }
+            _ => value,
+        }
+    }
+
+    /// A string literal, defined the first time it's used with a count of
+    /// references that says it's never freed
+    fn text(&mut self, text: &str) -> String {
+        let index = match self.texts.iter().position(|known| known == text) {
+            Some(index) => index,
+            None => {
+                self.helpers.push(format!(
+                    "static struct {{ long refs; char text[sizeof \"{literal}\"]; }} osho_text_{} = {{-1, \"{literal}\"}};\n",
+                    self.texts.len(),
+                    literal = c_literal(text)
+                ));
+                self.texts.push(text.to_string());
+                self.texts.len() - 1
             }
-            ASTNode::Identifier(name) => {
-                self.code.push_str(name);
+        };
+        format!("osho_text_{}.text", index)
+    }
+
+    /// Emit the string runtime, which can stop the program and makes
+    /// counted strings
+    fn use_strings(&mut self) {
+        self.needs_strings = true;
+        self.needs_text = true;
+        self.needs_exit = true;
+    }
+
+    /// Emit the character runtime, along with the string runtime it uses
+    fn use_chars(&mut self) {
+        self.needs_chars = true;
+        self.use_strings();
+    }
+
+    /// Emit the conversion runtime, along with the float and counted string
+    /// runtimes it uses
+    fn use_conversions(&mut self) {
+        self.needs_conversions = true;
+        self.needs_floats = true;
+        self.needs_text = true;
+        self.needs_exit = true;
+    }
+
+    /// The C expression for a call to a math builtin. The lowering already
+    /// made the arguments floats wherever the result is a float.
+    fn math(&mut self, program: &Program, builtin: Builtin, args: &[Operand]) -> String {
+        let operands: Vec<_> = args.iter().map(|arg| program.operand(*arg)).collect();
+        if program.type_of(args[0]) == Type::Int {
+            // Integer versions that don't need math.h
+            match (builtin, &operands[..]) {
+                (Builtin::Abs, [x]) => return format!("({0} < 0 ? -({0}) : {0})", x),
+                (Builtin::Floor | Builtin::Ceil, [x]) => return x.clone(),
+                (Builtin::Min, [x, y]) => return format!("({0} < {1} ? {0} : {1})", x, y),
+                (Builtin::Max, [x, y]) => return format!("({0} > {1} ? {0} : {1})", x, y),
+                _ => {}
             }
         }
-        Ok(())
+        self.needs_math = true;
+        let function = match builtin {
+            Builtin::Abs => "fabs",
+            Builtin::Min => "fmin",
+            Builtin::Max => "fmax",
+            other => other.name(),
+        };
+        format!("{}({})", function, operands.join(", "))
     }
 
     fn wrap_with_main(&self, code: String) -> String {
+        let mut includes = String::from("#include <stdio.h>\n#include <stdbool.h>\n");
+        if self.needs_math || self.needs_floats {
+            includes.push_str("#include <math.h>\n");
+        }
+        let mut runtime = String::new();
+        if self.needs_arrays
+            || self.needs_structs
+            || self.needs_random
+            || self.needs_floats
+            || self.needs_exit
+            || self.needs_text
+            || self.needs_arith_checks
+            || self.needs_overflow_checks
+        {
+            includes.push_str("#include <stdlib.h>\n");
+        }
+        if self.needs_random {
+            includes.push_str("#include <stdint.h>\n");
+        }
+        if self.needs_text || self.needs_slices {
+            includes.push_str("#include <string.h>\n");
+        }
+        if self.needs_files {
+            includes.push_str("#include <errno.h>\n");
+        }
+        if self.needs_arrays
+            || self.needs_random
+            || self.needs_exit
+            || self.needs_arith_checks
+            || self.needs_overflow_checks
+        {
+            runtime.push_str(&format!(
+                "\n#define OSHO_RUNTIME_ERROR {}\n",
+                RUNTIME_ERROR_EXIT_CODE
+            ));
+        }
+        if self.needs_arrays || self.needs_structs {
+            runtime.push_str(REFS_RUNTIME);
+        }
+        if self.needs_arrays {
+            runtime.push('\n');
+            runtime.push_str(ARRAY_RUNTIME);
+        }
+        if self.needs_vectors {
+            runtime.push_str(VECTOR_RUNTIME);
+        }
+        if self.needs_slices {
+            runtime.push_str(SLICE_RUNTIME);
+        }
+        if self.needs_random {
+            runtime.push_str(RANDOM_RUNTIME);
+        }
+        if self.needs_floats {
+            runtime.push_str(FLOAT_RUNTIME);
+        }
+        if self.needs_exit_runtime {
+            runtime.push_str(EXIT_RUNTIME);
+        }
+        if self.needs_text {
+            runtime.push_str(TEXT_RUNTIME);
+        }
+        if self.needs_args {
+            runtime.push_str(ARGS_RUNTIME);
+        }
+        if self.needs_args_array {
+            runtime.push_str(ARGS_ARRAY_RUNTIME);
+        }
+        if self.needs_env {
+            runtime.push_str(ENV_RUNTIME);
+        }
+        if self.needs_conversions {
+            runtime.push_str(CONVERSION_RUNTIME);
+        }
+        if self.needs_strings {
+            runtime.push_str(STRING_RUNTIME);
+        }
+        if self.needs_chars {
+            runtime.push_str(CHAR_RUNTIME);
+        }
+        if self.needs_split {
+            runtime.push_str(SPLIT_RUNTIME);
+        }
+        if self.needs_files {
+            runtime.push_str(FILE_RUNTIME);
+        }
+        if self.needs_division {
+            runtime.push_str(DIVISION_RUNTIME);
+        }
+        if self.needs_arith_checks {
+            runtime.push_str(ARITH_RUNTIME);
+        }
+        if self.needs_overflow_checks {
+            runtime.push_str(OVERFLOW_RUNTIME);
+        }
+        for helper in &self.helpers {
+            runtime.push('\n');
+            runtime.push_str(helper);
+        }
+        if !self.functions.is_empty() {
+            runtime.push('\n');
+            for (header, _) in &self.functions {
+                runtime.push_str(&format!("{};\n", header));
+            }
+        }
+        for (_, definition) in &self.functions {
+            runtime.push('\n');
+            runtime.push_str(definition);
+        }
+        if self.needs_args {
+            return format!(
+                "{}{}\nint main(int argc, char **argv) {{\nosho_arg_count = argc - 1;\nosho_arg_values = argv + 1;\n{}\nreturn 0;\n}}",
+                includes, runtime, code
+            );
+        }
         format!(
-            "#include <stdio.h>\n\nint main() {{\n{}\nreturn 0;\n}}",
-            code
+            "{}{}\nint main() {{\n{}\nreturn 0;\n}}",
+            includes, runtime, code
         )
     }
 }
@@ -0,0 +1,170 @@
+//! `osho debug`, a command line debugger for the tree-walking interpreter.
+//!
+//! The [`Debugger`] is a [`Hook`] that stops before statements, at
+//! breakpoints or while stepping, and reads commands until told to go on.
+
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+
+use crate::interpreter::{Hook, Pause};
+
+/// Error that stops the program when the user quits
+pub const STOPPED: &str = "Stopped by the debugger";
+
+const HELP: &str = "\
+Commands:
+    break <line>    Stop before the statements on <line> (b)
+    delete <line>   Remove the breakpoint on <line> (d)
+    step            Run to the next statement, even inside a call (s)
+    next            Run to the next statement, stepping over calls (n)
+    continue        Run to the next breakpoint (c)
+    print <name>    Show the value of a variable (p)
+    vars            Show every visible variable
+    where           Show the current line
+    quit            Stop the program (q)
+    help            Show this message";
+
+/// When to stop next
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    /// Before any statement
+    Step,
+
+    /// Before a statement at most this many calls deep
+    Next(usize),
+
+    /// Only at breakpoints
+    Continue,
+}
+
+/// Reads commands from `input` and writes to `out` whenever the program
+/// stops
+pub struct Debugger<R: BufRead, W: Write> {
+    /// Lines of the program's main file, for showing where it stopped
+    lines: Vec<String>,
+
+    input: R,
+    out: W,
+    breakpoints: BTreeSet<usize>,
+    mode: Mode,
+}
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+    /// A debugger for a program whose main file is `source`, which stops
+    /// before the first statement
+    pub fn new(source: &str, input: R, out: W) -> Self {
+        Self {
+            lines: source.lines().map(str::to_string).collect(),
+            input,
+            out,
+            breakpoints: BTreeSet::new(),
+            mode: Mode::Step,
+        }
+    }
+
+    fn should_stop(&self, pause: &Pause) -> bool {
+        // Statements of imported files aren't on the main file's lines
+        let in_main = pause.span.offset <= self.lines.iter().map(|l| l.len() + 1).sum();
+        match self.mode {
+            _ if in_main && self.breakpoints.contains(&pause.span.line) => true,
+            Mode::Step => true,
+            Mode::Next(depth) => pause.depth <= depth,
+            Mode::Continue => false,
+        }
+    }
+
+    /// Run a command, returning whether the program should go on
+    fn command(&mut self, line: &str, pause: &Pause) -> Result<bool, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let argument = words.next();
+        let line_argument = || {
+            argument
+                .and_then(|line| line.parse::<usize>().ok())
+                .ok_or_else(|| format!("'{}' needs a line number", command))
+        };
+        match command {
+            "break" | "b" => {
+                let line = line_argument()?;
+                self.breakpoints.insert(line);
+                self.say(&format!("Breakpoint at line {}", line));
+            }
+            "delete" | "d" => {
+                let line = line_argument()?;
+                if !self.breakpoints.remove(&line) {
+                    return Err(format!("No breakpoint at line {}", line));
+                }
+            }
+            "step" | "s" => {
+                self.mode = Mode::Step;
+                return Ok(true);
+            }
+            "next" | "n" => {
+                self.mode = Mode::Next(pause.depth);
+                return Ok(true);
+            }
+            "continue" | "c" => {
+                self.mode = Mode::Continue;
+                return Ok(true);
+            }
+            "print" | "p" => {
+                let name = argument.ok_or("'print' needs a variable name")?;
+                let variables = pause.variables();
+                let (_, value) = variables
+                    .iter()
+                    .find(|(variable, _)| *variable == name)
+                    .ok_or_else(|| format!("No variable '{}' here", name))?;
+                self.say(&format!("{} = {}", name, value));
+            }
+            "vars" => {
+                for (name, value) in pause.variables() {
+                    self.say(&format!("{} = {}", name, value));
+                }
+            }
+            "where" => self.show(pause),
+            "quit" | "q" => return Err(STOPPED.to_string()),
+            "help" => self.say(HELP),
+            "" => {}
+            _ => return Err(format!("Unknown command '{}', try 'help'", command)),
+        }
+        Ok(false)
+    }
+
+    /// Show the line the program stopped at
+    fn show(&mut self, pause: &Pause) {
+        let line = pause.span.line;
+        let text = self.lines.get(line - 1).map_or("", |text| text.trim());
+        self.say(&format!("line {}: {}", line, text));
+    }
+
+    fn say(&mut self, text: &str) {
+        // A debugger that can't write has nothing to show
+        let _ = writeln!(self.out, "{}", text);
+    }
+}
+
+impl<R: BufRead, W: Write> Hook for Debugger<R, W> {
+    fn before_statement(&mut self, pause: &Pause) -> Result<(), String> {
+        if !self.should_stop(pause) {
+            return Ok(());
+        }
+        self.show(pause);
+        loop {
+            let _ = write!(self.out, "(osho) ");
+            let _ = self.out.flush();
+            let mut line = String::new();
+            // At the end of the input, let the program finish
+            if self.input.read_line(&mut line).unwrap_or(0) == 0 {
+                self.mode = Mode::Continue;
+                self.breakpoints.clear();
+                return Ok(());
+            }
+            match self.command(&line, pause) {
+                Ok(true) => return Ok(()),
+                Ok(false) => {}
+                Err(err) if err == STOPPED => return Err(err),
+                Err(err) => self.say(&format!("error: {}", err)),
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Rewrites the surface constructs of a checked program into the smaller core
+//! language the compiled backends handle: `x op= v` becomes `x = x op v`,
+//! `x++` and `x--` become `x = x + 1` and `x = x - 1`, and a chain of
+//! assignments like `a = b = v` becomes `b = v` followed by `a = b`.
+//!
+//! Some constructs stay as they are, because they have no core form that
+//! keeps what they mean or what the backends make of them:
+//!
+//! - A for-in loop over a range stops at an inclusive end without counting
+//!   past it, which could overflow, and compiled targets turn it into their
+//!   own counted loops.
+//! - There's no string concatenation an interpolated string could become,
+//!   and compiled targets print one piece by piece.
+//!
+//! Programs are checked as written, so errors point at what was written,
+//! and the tree-walking interpreter runs them as written, so traces and the
+//! debugger follow the source. Each compiled backend desugars its own copy.
+
+use crate::lexer::Span;
+use crate::parser::{ASTNode, Ast, BinaryOperator, NodeId};
+use crate::visitor::MutVisitor;
+
+/// Rewrite the surface constructs of a whole program
+pub fn desugar(ast: &mut Ast) {
+    Desugarer.visit_mut(ast, ast.root());
+}
+
+/// A copy of the program with its surface constructs rewritten
+pub fn desugared(ast: &Ast) -> Ast {
+    let mut ast = ast.clone();
+    desugar(&mut ast);
+    ast
+}
+
+struct Desugarer;
+
+impl MutVisitor for Desugarer {
+    fn visit_mut(&mut self, ast: &mut Ast, node: NodeId) {
+        // Chains are split before walking, so the assignments split off are
+        // visited in turn
+        split_chain(ast, node);
+        self.walk_mut(ast, node);
+        let (name, op, value, span) = match &ast[node] {
+            ASTNode::CompoundAssignment {
+                name,
+                op,
+                value,
+                span,
+            } => (name.clone(), *op, Some(*value), *span),
+            ASTNode::Increment { name, span } => (name.clone(), BinaryOperator::Plus, None, *span),
+            ASTNode::Decrement { name, span } => (name.clone(), BinaryOperator::Minus, None, *span),
+            _ => return,
+        };
+        let value = value.unwrap_or_else(|| ast.add(ASTNode::Int(1)));
+        update(ast, node, name, op, value, span);
+    }
+}
+
+/// Replace an assignment whose value is the assignment of another variable
+/// with a block running that assignment first and then reading the variable
+/// back. Chains only assign variables, so nothing else is evaluated.
+fn split_chain(ast: &mut Ast, node: NodeId) {
+    let ASTNode::Assignment { name, value, span } = &ast[node] else {
+        return;
+    };
+    let (outer, inner, outer_span) = (name.clone(), *value, *span);
+    let ASTNode::Assignment { name, span, .. } = &ast[inner] else {
+        return;
+    };
+    let read = ast.add(ASTNode::Identifier {
+        name: name.clone(),
+        span: *span,
+    });
+    let outer = ast.add(ASTNode::Assignment {
+        name: outer,
+        value: read,
+        span: outer_span,
+    });
+    ast.replace(node, ASTNode::Block(vec![inner, outer]));
+}
+
+/// Replace the statement `node` with `name = name op value`
+fn update(
+    ast: &mut Ast,
+    node: NodeId,
+    name: String,
+    op: BinaryOperator,
+    value: NodeId,
+    span: Span,
+) {
+    let left = ast.add(ASTNode::Identifier {
+        name: name.clone(),
+        span,
+    });
+    let value = ast.add(ASTNode::BinaryOp {
+        left,
+        op,
+        right: value,
+        span,
+    });
+    ast.replace(node, ASTNode::Assignment { name, value, span });
+}
@@ -0,0 +1,332 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::error::CompileError;
+use crate::lexer::Span;
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    /// The program can't be compiled
+    Error,
+
+    /// The program compiles, but probably doesn't do what was intended
+    Warning,
+
+    /// Extra information about another diagnostic
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// A message about the program, rendered rustc-style against the source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+
+    /// Stable code, e.g. `E0002` for errors and `W0001` for warnings. Notes
+    /// have none.
+    pub code: &'static str,
+
+    /// The lint that produced a warning
+    pub lint: Option<Lint>,
+
+    pub message: String,
+
+    /// The offending source location, if known
+    pub span: Option<Span>,
+
+    /// Extra lines of explanation printed after the snippet
+    pub notes: Vec<String>,
+
+    /// Notes pointing at other places in the source, printed after this one
+    pub related: Vec<Diagnostic>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>, span: Option<Span>) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            lint: None,
+            message: message.into(),
+            span,
+            notes: Vec::new(),
+            related: Vec::new(),
+        }
+    }
+
+    pub fn warning(lint: Lint, message: impl Into<String>, span: Option<Span>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            lint: Some(lint),
+            ..Self::error(lint.code(), message, span)
+        }
+    }
+
+    pub fn note(message: impl Into<String>, span: Option<Span>) -> Self {
+        Self {
+            severity: Severity::Note,
+            ..Self::error("", message, span)
+        }
+    }
+
+    /// Attach a note about another place in the source
+    pub fn with_related(mut self, note: Diagnostic) -> Self {
+        self.related.push(note);
+        self
+    }
+
+    /// Attach a note to the diagnostic
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Render the diagnostic with the source line and a caret under the span
+    pub fn render(&self, file_name: &str, source: &str) -> String {
+        let mut out = if self.code.is_empty() {
+            format!("{}: {}\n", self.severity, self.message)
+        } else {
+            format!("{}[{}]: {}\n", self.severity, self.code, self.message)
+        };
+
+        let span = self
+            .span
+            .filter(|span| span.line >= 1 && span.line <= source.lines().count());
+        match span {
+            Some(span) => {
+                let line = source.lines().nth(span.line - 1).unwrap_or("");
+                let number = span.line.to_string();
+                let gutter = " ".repeat(number.len());
+                out.push_str(&format!(
+                    "{}--> {}:{}:{}\n",
+                    gutter, file_name, span.line, span.column
+                ));
+                out.push_str(&format!("{} |\n", gutter));
+                out.push_str(&format!("{} | {}\n", number, line));
+
+                // Keep tabs so the caret lines up with the source line
+                let padding: String = line
+                    .chars()
+                    .take(span.column.saturating_sub(1))
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                let available = line.chars().count().saturating_sub(padding.chars().count());
+                let carets = span.length.min(available).max(1);
+                out.push_str(&format!("{} | {}{}\n", gutter, padding, "^".repeat(carets)));
+                for note in &self.notes {
+                    out.push_str(&format!("{} = note: {}\n", gutter, note));
+                }
+            }
+            None => {
+                out.push_str(&format!(" --> {}\n", file_name));
+                for note in &self.notes {
+                    out.push_str(&format!("  = note: {}\n", note));
+                }
+            }
+        }
+        for related in &self.related {
+            out.push_str(&related.render(file_name, source));
+        }
+        out
+    }
+}
+
+/// A kind of warning, which can be turned on and off by name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// A variable is declared but its value is never read
+    UnusedVariables,
+
+    /// A `let` hides a variable of the same name from an enclosing scope
+    Shadowing,
+
+    /// Dividing or taking the remainder by a literal zero
+    DivisionByZero,
+
+    /// A function is declared but never called
+    UnusedFunctions,
+
+    /// The condition of an `if`, loop or `?:` is made only of literals
+    ConstantCondition,
+
+    /// A variable, element or field is assigned its own value
+    SelfAssignment,
+
+    /// A name doesn't follow the conventions: snake case for variables and
+    /// functions, upper case for constants and a capital letter for structs
+    IdentifierStyle,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 7] = [
+        Lint::UnusedVariables,
+        Lint::Shadowing,
+        Lint::DivisionByZero,
+        Lint::UnusedFunctions,
+        Lint::ConstantCondition,
+        Lint::SelfAssignment,
+        Lint::IdentifierStyle,
+    ];
+
+    /// Name used with `--warn` and `--allow`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedVariables => "unused-variables",
+            Lint::Shadowing => "shadowing",
+            Lint::DivisionByZero => "division-by-zero",
+            Lint::UnusedFunctions => "unused-functions",
+            Lint::ConstantCondition => "constant-condition",
+            Lint::SelfAssignment => "self-assignment",
+            Lint::IdentifierStyle => "identifier-style",
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Lint::UnusedVariables => "W0001",
+            Lint::Shadowing => "W0002",
+            Lint::DivisionByZero => "W0003",
+            Lint::UnusedFunctions => "W0004",
+            Lint::ConstantCondition => "W0005",
+            Lint::SelfAssignment => "W0006",
+            Lint::IdentifierStyle => "W0007",
+        }
+    }
+
+    /// Shadowing is often deliberate, so it has to be asked for
+    pub fn enabled_by_default(&self) -> bool {
+        !matches!(self, Lint::Shadowing)
+    }
+
+    pub fn from_name(name: &str) -> Result<Lint, String> {
+        Lint::ALL
+            .into_iter()
+            .find(|lint| lint.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<_> = Lint::ALL.iter().map(|lint| lint.name()).collect();
+                format!(
+                    "Unknown lint '{}', expected one of {}",
+                    name,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Which lints are reported, and whether warnings stop the build
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintLevels {
+    enabled: HashSet<Lint>,
+
+    /// Treat every reported warning as an error
+    pub deny_warnings: bool,
+}
+
+impl Default for LintLevels {
+    fn default() -> Self {
+        Self {
+            enabled: Lint::ALL
+                .into_iter()
+                .filter(Lint::enabled_by_default)
+                .collect(),
+            deny_warnings: false,
+        }
+    }
+}
+
+impl LintLevels {
+    pub fn warn(&mut self, lint: Lint) {
+        self.enabled.insert(lint);
+    }
+
+    pub fn allow(&mut self, lint: Lint) {
+        self.enabled.remove(&lint);
+    }
+
+    pub fn is_enabled(&self, lint: Lint) -> bool {
+        self.enabled.contains(&lint)
+    }
+
+    /// Apply a lint config file, with one setting per line: `warn <lint>`,
+    /// `allow <lint>` or `deny-warnings`. `#` starts a comment.
+    pub fn configure(&mut self, config: &str) -> Result<(), String> {
+        for (number, line) in config.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let setting = match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [] => Ok(()),
+                ["warn", name] => Lint::from_name(name).map(|lint| self.warn(lint)),
+                ["allow", name] => Lint::from_name(name).map(|lint| self.allow(lint)),
+                ["deny-warnings"] => {
+                    self.deny_warnings = true;
+                    Ok(())
+                }
+                _ => Err(format!(
+                    "Expected 'warn <lint>', 'allow <lint>' or 'deny-warnings', found '{}'",
+                    line
+                )),
+            };
+            setting.map_err(|err| format!("Line {} of the lint config: {}", number + 1, err))?;
+        }
+        Ok(())
+    }
+
+    /// Keep the warnings of enabled lints. With `deny_warnings` they become
+    /// errors.
+    pub fn apply(&self, warnings: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        warnings
+            .into_iter()
+            .filter(|warning| warning.lint.is_none_or(|lint| self.is_enabled(lint)))
+            .map(|warning| {
+                if self.deny_warnings {
+                    Diagnostic {
+                        severity: Severity::Error,
+                        ..warning
+                    }
+                    .with_note("warnings are errors because of --deny-warnings")
+                } else {
+                    warning
+                }
+            })
+            .collect()
+    }
+}
+
+/// How many of the calls a runtime error unwound through get a note
+const MAX_FRAMES: usize = 10;
+
+impl From<&CompileError> for Diagnostic {
+    fn from(err: &CompileError) -> Self {
+        let code = match err {
+            CompileError::Lex { .. } => "E0001",
+            CompileError::Parse { .. } => "E0002",
+            CompileError::Semantic { .. } => "E0003",
+            CompileError::Runtime(_) => "E0004",
+            CompileError::Codegen { .. } => "E0005",
+            CompileError::Exit { .. } => "E0006",
+        };
+        let mut diagnostic = Diagnostic::error(code, err.message(), err.span());
+        let CompileError::Runtime(err) = err else {
+            return diagnostic;
+        };
+        for frame in err.stack.iter().take(MAX_FRAMES) {
+            diagnostic = diagnostic.with_note(frame.to_string());
+        }
+        if err.stack.len() > MAX_FRAMES {
+            diagnostic = diagnostic.with_note(format!(
+                "... and {} more calls",
+                err.stack.len() - MAX_FRAMES
+            ));
+        }
+        diagnostic.with_note("the error happened while interpreting the program")
+    }
+}
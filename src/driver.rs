@@ -0,0 +1,131 @@
+//! Invoking the C compiler on generated code.
+
+use std::env;
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+
+use crate::error::RUNTIME_ERROR_EXIT_CODE;
+
+/// C compilers tried in order when none is chosen
+pub const C_COMPILERS: [&str; 3] = ["gcc", "clang", "cc"];
+
+/// A C compiler and the flags to build generated programs with
+#[derive(Debug, Clone, PartialEq)]
+pub struct CCompiler {
+    /// Program to run, e.g. `gcc`
+    pub program: String,
+
+    /// Optimization level 0 to 3, passed as `-O<n>`
+    pub opt_level: Option<u8>,
+
+    /// Extra flags passed through unchanged
+    pub flags: Vec<String>,
+}
+
+impl CCompiler {
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            opt_level: None,
+            flags: Vec::new(),
+        }
+    }
+
+    /// Pick the compiler: the one given, then `$OSHO_CC`, then the first
+    /// installed compiler from [`C_COMPILERS`]
+    pub fn detect(program: Option<&str>) -> Result<Self, String> {
+        if let Some(program) = program {
+            return Ok(Self::new(program));
+        }
+        if let Ok(program) = env::var("OSHO_CC") {
+            if !program.is_empty() {
+                return Ok(Self::new(program));
+            }
+        }
+        C_COMPILERS
+            .iter()
+            .find(|program| Self::is_installed(program))
+            .map(|program| Self::new(*program))
+            .ok_or_else(|| {
+                format!(
+                    "No C compiler found, tried {}. Install one or choose it with --cc or OSHO_CC",
+                    C_COMPILERS.join(", ")
+                )
+            })
+    }
+
+    fn is_installed(program: &str) -> bool {
+        Command::new(program)
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+
+    /// Set the optimization level, which must be between 0 and 3
+    pub fn with_opt_level(mut self, level: u8) -> Result<Self, String> {
+        if level > 3 {
+            return Err(format!(
+                "Invalid optimization level {}, expected 0 to 3",
+                level
+            ));
+        }
+        self.opt_level = Some(level);
+        Ok(self)
+    }
+
+    /// Add flags from a whitespace-separated string like `"-g -Wall"`
+    pub fn with_flags(mut self, flags: &str) -> Self {
+        self.flags
+            .extend(flags.split_whitespace().map(str::to_string));
+        self
+    }
+
+    /// The command that compiles `c_file` into `executable`
+    pub fn command(&self, c_file: &Path, executable: &Path) -> Command {
+        let mut command = Command::new(&self.program);
+        if let Some(level) = self.opt_level {
+            command.arg(format!("-O{}", level));
+        }
+        command
+            // Signed overflow wraps around like it does in the interpreter,
+            // rather than being undefined
+            .arg("-fwrapv")
+            .args(&self.flags)
+            .arg(c_file)
+            .arg("-o")
+            .arg(executable)
+            // math.h functions like pow() live in libm
+            .arg("-lm");
+        command
+    }
+
+    /// Compile `c_file` into `executable`
+    pub fn compile(&self, c_file: &Path, executable: &Path) -> Result<(), String> {
+        let status = self
+            .command(c_file, executable)
+            .status()
+            .map_err(|err| format!("Failed to run the C compiler '{}': {}", self.program, err))?;
+        if !status.success() {
+            return Err("Compilation failed".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The code a program exited with. One killed by a signal gets 128 plus the
+/// signal's number, the way shells report it.
+pub fn exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    RUNTIME_ERROR_EXIT_CODE
+}
@@ -0,0 +1,103 @@
+use std::fmt;
+
+use crate::interpreter::RuntimeError;
+use crate::lexer::Span;
+
+/// Exit code of `osho` when a program doesn't compile
+pub const COMPILE_ERROR_EXIT_CODE: i32 = 1;
+
+/// Exit code of a program stopped by a runtime error, whether it was
+/// interpreted or compiled. It differs from [`COMPILE_ERROR_EXIT_CODE`] so
+/// scripts can tell a program that failed from one that never ran.
+pub const RUNTIME_ERROR_EXIT_CODE: i32 = 70;
+
+/// An error from any phase of the compiler
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileError {
+    /// The source contains characters that don't form a token
+    Lex { message: String, span: Span },
+
+    /// The tokens could not be parsed
+    Parse { message: String, span: Span },
+
+    /// The program failed declaration or type checks
+    Semantic { message: String, span: Span },
+
+    /// The interpreter failed while running the program
+    Runtime(RuntimeError),
+
+    /// Code could not be generated for the program
+    Codegen { message: String },
+
+    /// The program called `exit(code)`, which stops it at once. This isn't
+    /// a failure unless the code is nonzero.
+    Exit { code: i32, span: Span },
+}
+
+impl CompileError {
+    /// Where in the source the error happened, if known
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            CompileError::Lex { span, .. }
+            | CompileError::Parse { span, .. }
+            | CompileError::Semantic { span, .. }
+            | CompileError::Exit { span, .. } => Some(*span),
+            CompileError::Runtime(err) => Some(err.span),
+            CompileError::Codegen { .. } => None,
+        }
+    }
+
+    /// The error message without position information
+    pub fn message(&self) -> &str {
+        match self {
+            CompileError::Lex { message, .. }
+            | CompileError::Parse { message, .. }
+            | CompileError::Semantic { message, .. }
+            | CompileError::Codegen { message } => message,
+            CompileError::Runtime(err) => &err.message,
+            CompileError::Exit { .. } => "The program called exit()",
+        }
+    }
+
+    /// Short name of the phase that failed
+    pub fn phase(&self) -> &'static str {
+        match self {
+            CompileError::Lex { .. } => "lex",
+            CompileError::Parse { .. } => "parse",
+            CompileError::Semantic { .. } => "semantic",
+            CompileError::Runtime(_) => "runtime",
+            CompileError::Codegen { .. } => "codegen",
+            CompileError::Exit { .. } => "exit",
+        }
+    }
+
+    /// The code `osho` exits with because of the error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CompileError::Exit { code, .. } => *code,
+            CompileError::Runtime(_) => RUNTIME_ERROR_EXIT_CODE,
+            _ => COMPILE_ERROR_EXIT_CODE,
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let CompileError::Exit { code, .. } = self {
+            return write!(f, "exited with code {}", code);
+        }
+        match self.span() {
+            Some(span) => write!(
+                f,
+                "{} error at {}:{}: {}",
+                self.phase(),
+                span.line,
+                span.column,
+                self.message()
+            ),
+            None => write!(f, "{} error: {}", self.phase(), self.message()),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
@@ -0,0 +1,79 @@
+//! Format strings of `print`, like `print "x = {}, y = {}", x, y`.
+//!
+//! Each `{}` is replaced by the next argument, and `{{` and `}}` print a
+//! literal brace. Without a format string the arguments are separated by
+//! spaces. Either way a print is a list of text pieces around the values,
+//! one more piece than there are values.
+
+use std::fmt::Display;
+
+/// The text around the placeholders of a format string
+pub fn pieces(format: &str) -> Result<Vec<String>, String> {
+    let mut pieces = vec![String::new()];
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        let piece = pieces.last_mut().expect("there is always a piece");
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                piece.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                pieces.push(String::new());
+            }
+            ('{', _) => return Err("Unmatched '{' in format string, use '{{' to print it".into()),
+            ('}', _) => return Err("Unmatched '}' in format string, use '}}' to print it".into()),
+            _ => piece.push(c),
+        }
+    }
+    Ok(pieces)
+}
+
+/// The pieces of a print without a format string: values separated by spaces
+pub fn separators(values: usize) -> Vec<String> {
+    let mut pieces = vec![String::new()];
+    for i in 0..values {
+        pieces.push(if i + 1 < values { " " } else { "" }.to_string());
+    }
+    pieces
+}
+
+/// The pieces of a print, checking the format string has a placeholder for
+/// every value
+pub fn print_pieces(format: Option<&str>, values: usize) -> Result<Vec<String>, String> {
+    let Some(format) = format else {
+        return Ok(separators(values));
+    };
+    let pieces = pieces(format)?;
+    let placeholders = pieces.len() - 1;
+    if placeholders != values {
+        return Err(format!(
+            "Format string has {} placeholder{} but {} value{} given",
+            placeholders,
+            if placeholders == 1 { "" } else { "s" },
+            values,
+            if values == 1 { " was" } else { "s were" }
+        ));
+    }
+    Ok(pieces)
+}
+
+/// Interleave pieces of text with the values between them
+pub fn render<T: Display>(pieces: &[String], values: &[T]) -> String {
+    let mut out = pieces[0].clone();
+    for (value, piece) in values.iter().zip(&pieces[1..]) {
+        out.push_str(&value.to_string());
+        out.push_str(piece);
+    }
+    out
+}
+
+/// The format string that produces pieces, for display
+pub fn unparse(pieces: &[String]) -> String {
+    pieces
+        .iter()
+        .map(|piece| piece.replace('{', "{{").replace('}', "}}"))
+        .collect::<Vec<_>>()
+        .join("{}")
+}
@@ -0,0 +1,775 @@
+//! `osho fmt`, which reprints a program in the canonical layout.
+//!
+//! The program is parsed, and the syntax tree decides the layout: one
+//! statement per line, four spaces of indentation per block and single spaces
+//! around operators. The text of each token, the comments and the
+//! parentheses, which the tree doesn't keep, come from the source: printing
+//! walks the tokens alongside the tree, so everything written appears in the
+//! output. Blank lines between statements are kept, but never more than one.
+
+use crate::error::CompileError;
+use crate::lexer::{Kind, Lexer, Token};
+use crate::parser::{ASTNode, Ast, ModuleItem, NodeId, Param, Parser};
+use crate::semantic_analyzer::Type;
+
+const INDENT: &str = "    ";
+
+/// Format a program, or give the syntax errors that stop it from being
+/// parsed
+pub fn format(source: &str) -> Result<String, Vec<CompileError>> {
+    let tokens = Lexer::new(source)
+        .get_tokens_with_trivia()
+        .map_err(|err| vec![err])?;
+    let code: Vec<Token> = tokens
+        .iter()
+        .filter(|token| !token.kind.is_trivia())
+        .cloned()
+        .collect();
+    let mut parser = Parser::new(&code);
+    let statements = parser.parse_statements()?;
+    let ast = parser.into_ast();
+    let mut printer = Printer::new(source, &ast, tokens);
+    printer.statements(&statements);
+    Ok(printer.finish())
+}
+
+/// Writes the formatted program, consuming the source tokens as it goes
+struct Printer<'a> {
+    source: &'a str,
+    ast: &'a Ast,
+    tokens: Vec<Token<'a>>,
+
+    /// The next source token
+    pos: usize,
+
+    out: String,
+    indent: usize,
+
+    /// Whether a `//` comment ended the current line, so the next text needs
+    /// a new one
+    pending_newline: bool,
+
+    /// Parentheses around expressions opened so far and not yet closed
+    groups: usize,
+}
+
+impl<'a> Printer<'a> {
+    fn new(source: &'a str, ast: &'a Ast, tokens: Vec<Token<'a>>) -> Self {
+        Self {
+            source,
+            ast,
+            tokens,
+            pos: 0,
+            out: String::new(),
+            indent: 0,
+            pending_newline: false,
+            groups: 0,
+        }
+    }
+
+    /// The output, with its comments after the last statement
+    fn finish(mut self) -> String {
+        self.flush();
+        self.newline();
+        let len = self.out.trim_end().len();
+        self.out.truncate(len);
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.out
+    }
+
+    fn statements(&mut self, statements: &[NodeId]) {
+        for &statement in statements {
+            let blank = self.flush() > 1;
+            self.newline();
+            if blank {
+                self.blank_line();
+            }
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, node: NodeId) {
+        let ast = self.ast;
+        match &ast[node] {
+            ASTNode::LetDeclaration {
+                mutable, ty, value, ..
+            } => {
+                self.keyword(Kind::Let);
+                if *mutable {
+                    self.keyword(Kind::Mut);
+                }
+                self.token(Kind::Identifier);
+                if let Some(ty) = ty {
+                    self.token(Kind::Colon);
+                    self.write(" ");
+                    self.type_name(ty);
+                }
+                self.operator(Kind::EqualsTo);
+                self.expression(*value);
+            }
+            ASTNode::ConstDeclaration { value, .. } => {
+                self.keyword(Kind::Const);
+                self.token(Kind::Identifier);
+                self.operator(Kind::EqualsTo);
+                self.expression(*value);
+            }
+            ASTNode::StructDeclaration { fields, .. } => {
+                self.keyword(Kind::Struct);
+                self.token(Kind::Identifier);
+                self.write(" ");
+                self.token(Kind::OpenBrace);
+                if !fields.is_empty() {
+                    self.write(" ");
+                }
+                for i in 0..fields.len() {
+                    if i > 0 {
+                        self.separator();
+                    }
+                    self.token(Kind::Identifier);
+                }
+                self.skip(Kind::Comma);
+                if !fields.is_empty() {
+                    self.write(" ");
+                }
+                self.token(Kind::CloseBrace);
+            }
+            ASTNode::FunctionDeclaration {
+                params,
+                result,
+                body,
+                ..
+            } => {
+                self.keyword(Kind::Fn);
+                self.token(Kind::Identifier);
+                self.token(Kind::OpenParen);
+                self.params(params);
+                self.token(Kind::CloseParen);
+                if let Some(result) = result {
+                    self.operator(Kind::Arrow);
+                    self.type_name(result);
+                }
+                self.write(" ");
+                self.block(*body);
+            }
+            ASTNode::Module { items, .. } => {
+                self.keyword(Kind::Module);
+                self.token(Kind::Identifier);
+                self.write(" ");
+                self.token(Kind::OpenBrace);
+                self.indent += 1;
+                for ModuleItem {
+                    public,
+                    declaration,
+                } in items
+                {
+                    let blank = self.flush() > 1;
+                    self.newline();
+                    if blank {
+                        self.blank_line();
+                    }
+                    if *public {
+                        self.keyword(Kind::Pub);
+                    }
+                    self.statement(*declaration);
+                }
+                self.close_block();
+            }
+            ASTNode::Return { value, .. } => {
+                self.token(Kind::Return);
+                if let Some(value) = value {
+                    self.write(" ");
+                    self.expression(*value);
+                }
+            }
+            ASTNode::Print {
+                format,
+                args,
+                newline,
+                ..
+            } => {
+                self.token(if *newline { Kind::Println } else { Kind::Print });
+                let parens = self.next_is(Kind::OpenParen);
+                if parens {
+                    self.token(Kind::OpenParen);
+                } else {
+                    self.write(" ");
+                }
+                if format.is_some() {
+                    self.token(Kind::String);
+                }
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 || format.is_some() {
+                        self.separator();
+                    }
+                    self.expression(*arg);
+                }
+                if parens {
+                    self.token(Kind::CloseParen);
+                }
+            }
+            ASTNode::Assert {
+                condition, message, ..
+            } => {
+                self.keyword(Kind::Assert);
+                self.expression(*condition);
+                if message.is_some() {
+                    self.separator();
+                    self.token(Kind::String);
+                }
+            }
+            ASTNode::Input { .. } => {
+                self.keyword(Kind::Input);
+                self.token(Kind::Identifier);
+            }
+            ASTNode::Import { .. } => {
+                self.keyword(Kind::Import);
+                if self.next_is(Kind::String) {
+                    self.token(Kind::String);
+                } else {
+                    self.token(Kind::Identifier);
+                    while self.next_is(Kind::Dot) {
+                        self.token(Kind::Dot);
+                        self.token(Kind::Identifier);
+                    }
+                }
+            }
+            ASTNode::Block(_) => self.block(node),
+            ASTNode::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.keyword(Kind::If);
+                self.expression(*condition);
+                self.write(" ");
+                self.block(*then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.write(" ");
+                    self.keyword(Kind::Else);
+                    self.statement(*else_branch);
+                }
+            }
+            ASTNode::While {
+                condition, body, ..
+            } => {
+                self.keyword(Kind::While);
+                self.expression(*condition);
+                self.write(" ");
+                self.block(*body);
+            }
+            ASTNode::DoWhile {
+                body, condition, ..
+            } => {
+                self.keyword(Kind::Do);
+                self.block(*body);
+                self.write(" ");
+                self.keyword(Kind::While);
+                self.expression(*condition);
+            }
+            ASTNode::For {
+                init,
+                condition,
+                step,
+                body,
+                ..
+            } => {
+                self.keyword(Kind::For);
+                self.token(Kind::OpenParen);
+                if let Some(init) = init {
+                    self.statement(*init);
+                }
+                self.token(Kind::Semicolon);
+                if let Some(condition) = condition {
+                    self.write(" ");
+                    self.expression(*condition);
+                }
+                self.token(Kind::Semicolon);
+                if let Some(step) = step {
+                    self.write(" ");
+                    self.statement(*step);
+                }
+                self.token(Kind::CloseParen);
+                self.write(" ");
+                self.block(*body);
+            }
+            ASTNode::ForIn { range, body, .. } => {
+                let ASTNode::Range {
+                    start,
+                    end,
+                    inclusive,
+                    ..
+                } = self.ast[*range]
+                else {
+                    unreachable!("the parser only puts ranges in for-in loops");
+                };
+                self.keyword(Kind::For);
+                self.token(Kind::Identifier);
+                self.write(" ");
+                self.keyword(Kind::In);
+                self.expression(start);
+                self.token(if inclusive {
+                    Kind::DotDotEquals
+                } else {
+                    Kind::DotDot
+                });
+                self.expression(end);
+                self.write(" ");
+                self.block(*body);
+            }
+            ASTNode::Break { .. } => self.token(Kind::Break),
+            ASTNode::Continue { .. } => self.token(Kind::Continue),
+            ASTNode::Assignment { value, .. } => {
+                let start = self.groups;
+                self.token(Kind::Identifier);
+                self.close_groups(start);
+                self.operator(Kind::EqualsTo);
+                self.expression(*value);
+            }
+            ASTNode::CompoundAssignment { value, .. } => {
+                let start = self.groups;
+                self.token(Kind::Identifier);
+                self.close_groups(start);
+                self.write(" ");
+                self.any_token();
+                self.write(" ");
+                self.expression(*value);
+            }
+            ASTNode::Increment { .. } => {
+                self.token(Kind::Identifier);
+                self.token(Kind::Increment);
+            }
+            ASTNode::Decrement { .. } => {
+                self.token(Kind::Identifier);
+                self.token(Kind::Decrement);
+            }
+            ASTNode::IndexAssignment {
+                array,
+                index,
+                value,
+                ..
+            } => {
+                let start = self.groups;
+                self.expression(*array);
+                self.token(Kind::OpenBracket);
+                self.expression(*index);
+                self.token(Kind::CloseBracket);
+                self.close_groups(start);
+                self.operator(Kind::EqualsTo);
+                self.expression(*value);
+            }
+            ASTNode::FieldAssignment { object, value, .. } => {
+                let start = self.groups;
+                self.expression(*object);
+                self.token(Kind::Dot);
+                self.token(Kind::Identifier);
+                self.close_groups(start);
+                self.operator(Kind::EqualsTo);
+                self.expression(*value);
+            }
+            _ => self.expression(node),
+        }
+    }
+
+    /// A `{ ... }` block, which is empty or has its statements on lines of
+    /// their own
+    fn block(&mut self, node: NodeId) {
+        let ast = self.ast;
+        let ASTNode::Block(statements) = &ast[node] else {
+            return self.statement(node);
+        };
+        self.token(Kind::OpenBrace);
+        if statements.is_empty() && self.next_is_code(Kind::CloseBrace) {
+            self.token(Kind::CloseBrace);
+            return;
+        }
+        self.indent += 1;
+        self.statements(statements);
+        self.close_block();
+    }
+
+    /// The `}` of a block, after the comments at its end
+    fn close_block(&mut self) {
+        self.flush();
+        self.indent -= 1;
+        self.newline();
+        self.token(Kind::CloseBrace);
+    }
+
+    fn expression(&mut self, node: NodeId) {
+        let start = self.groups;
+        let ast = self.ast;
+        match &ast[node] {
+            ASTNode::Int(_) | ASTNode::Number(_) => {
+                // A negative literal is a number folded with its `-`s
+                loop {
+                    self.open_groups();
+                    if !self.next_is(Kind::Minus) {
+                        break;
+                    }
+                    self.token(Kind::Minus);
+                }
+                self.any_token();
+            }
+            ASTNode::Bool(value) => self.token(if *value { Kind::True } else { Kind::False }),
+            ASTNode::Str(_) => self.token(Kind::String),
+            ASTNode::Char(_) => self.token(Kind::Char),
+            ASTNode::Interpolation { values, .. } => {
+                self.token(Kind::StringStart);
+                for (i, value) in values.iter().enumerate() {
+                    // Keep `${` and a map's `{` apart
+                    let map = self.next_is(Kind::OpenBrace);
+                    if map {
+                        self.write(" ");
+                    }
+                    self.expression(*value);
+                    if map {
+                        self.write(" ");
+                    }
+                    self.token(if i + 1 == values.len() {
+                        Kind::StringEnd
+                    } else {
+                        Kind::StringMiddle
+                    });
+                }
+            }
+            ASTNode::Identifier { .. } => self.token(Kind::Identifier),
+            ASTNode::BinaryOp {
+                left, op: _, right, ..
+            } => {
+                self.expression(*left);
+                self.write(" ");
+                self.any_token();
+                self.write(" ");
+                self.expression(*right);
+            }
+            ASTNode::UnaryOp { operand, .. } => {
+                self.open_groups();
+                self.any_token();
+                self.expression(*operand);
+            }
+            ASTNode::Conditional {
+                condition,
+                then_value,
+                else_value,
+                ..
+            } => {
+                self.expression(*condition);
+                self.operator(Kind::Question);
+                self.expression(*then_value);
+                self.operator(Kind::Colon);
+                self.expression(*else_value);
+            }
+            ASTNode::ArrayLiteral { elements, .. } => {
+                self.token(Kind::OpenBracket);
+                self.list(elements);
+                self.token(Kind::CloseBracket);
+            }
+            ASTNode::Index { array, index, .. } => {
+                self.expression(*array);
+                self.token(Kind::OpenBracket);
+                self.expression(*index);
+                self.token(Kind::CloseBracket);
+            }
+            ASTNode::Slice {
+                array, start, end, ..
+            } => {
+                self.expression(*array);
+                self.token(Kind::OpenBracket);
+                if let Some(start) = start {
+                    self.expression(*start);
+                }
+                self.token(Kind::Colon);
+                if let Some(end) = end {
+                    self.expression(*end);
+                }
+                self.token(Kind::CloseBracket);
+            }
+            ASTNode::Lambda { params, body, .. } => {
+                if self.next_is(Kind::Or) {
+                    self.token(Kind::Or);
+                } else {
+                    self.token(Kind::Pipe);
+                    self.params(params);
+                    self.token(Kind::Pipe);
+                }
+                self.write(" ");
+                self.expression(*body);
+            }
+            ASTNode::Call { name, args, .. } => {
+                self.qualified_name(name);
+                self.token(Kind::OpenParen);
+                self.list(args);
+                self.token(Kind::CloseParen);
+            }
+            ASTNode::MapLiteral { entries, .. } => {
+                self.token(Kind::OpenBrace);
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        self.separator();
+                    }
+                    self.expression(*key);
+                    self.token(Kind::Colon);
+                    self.write(" ");
+                    self.expression(*value);
+                }
+                self.token(Kind::CloseBrace);
+            }
+            ASTNode::StructLiteral { name, fields, .. } => {
+                self.qualified_name(name);
+                self.write(" ");
+                self.token(Kind::OpenBrace);
+                if !fields.is_empty() {
+                    self.write(" ");
+                }
+                for (i, (_, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.separator();
+                    }
+                    self.token(Kind::Identifier);
+                    self.token(Kind::Colon);
+                    self.write(" ");
+                    self.expression(*value);
+                }
+                self.skip(Kind::Comma);
+                if !fields.is_empty() {
+                    self.write(" ");
+                }
+                self.token(Kind::CloseBrace);
+            }
+            ASTNode::FieldAccess { object, .. } => {
+                self.expression(*object);
+                self.token(Kind::Dot);
+                self.token(Kind::Identifier);
+            }
+            // Assignments and `++` can be used as values
+            _ => self.statement(node),
+        }
+        self.close_groups(start);
+    }
+
+    /// Comma-separated parameters with their annotations, dropping a
+    /// trailing comma
+    fn params(&mut self, params: &[Param]) {
+        for (i, param) in params.iter().enumerate() {
+            if i > 0 {
+                self.separator();
+            }
+            self.token(Kind::Identifier);
+            if let Some(ty) = &param.ty {
+                self.token(Kind::Colon);
+                self.write(" ");
+                self.type_name(ty);
+            }
+        }
+        self.skip(Kind::Comma);
+    }
+
+    /// Comma-separated expressions
+    fn list(&mut self, nodes: &[NodeId]) {
+        for (i, &node) in nodes.iter().enumerate() {
+            if i > 0 {
+                self.separator();
+            }
+            self.expression(node);
+        }
+    }
+
+    /// A name that may be qualified by a module, `module.item`
+    fn qualified_name(&mut self, name: &str) {
+        self.token(Kind::Identifier);
+        for _ in name.matches('.') {
+            self.token(Kind::Dot);
+            self.token(Kind::Identifier);
+        }
+    }
+
+    fn type_name(&mut self, ty: &Type) {
+        match ty {
+            Type::Array(element) => {
+                self.token(Kind::OpenBracket);
+                self.type_name(element);
+                self.token(Kind::CloseBracket);
+            }
+            Type::Map(value) => {
+                self.token(Kind::OpenBrace);
+                self.token(Kind::Identifier);
+                self.token(Kind::Colon);
+                self.write(" ");
+                self.type_name(value);
+                self.token(Kind::CloseBrace);
+            }
+            Type::Struct(name) => self.qualified_name(name),
+            Type::Int | Type::Float | Type::Bool | Type::Str | Type::Char => {
+                self.token(Kind::Identifier)
+            }
+            Type::Function { params, result } => {
+                self.token(Kind::Fn);
+                self.token(Kind::OpenParen);
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        self.separator();
+                    }
+                    self.type_name(param);
+                }
+                self.skip(Kind::Comma);
+                self.token(Kind::CloseParen);
+                if let Some(result) = result {
+                    self.operator(Kind::Arrow);
+                    self.type_name(result);
+                }
+            }
+        }
+    }
+
+    /// A keyword followed by a space
+    fn keyword(&mut self, kind: Kind) {
+        self.token(kind);
+        self.write(" ");
+    }
+
+    /// A token with a space on each side
+    fn operator(&mut self, kind: Kind) {
+        self.write(" ");
+        self.token(kind);
+        self.write(" ");
+    }
+
+    /// `, ` between the items of a list
+    fn separator(&mut self) {
+        self.token(Kind::Comma);
+        self.write(" ");
+    }
+
+    /// Write the next source token, which the tree says is of kind `kind`,
+    /// after the comments and parentheses before it
+    fn token(&mut self, kind: Kind) {
+        if kind != Kind::OpenParen {
+            self.open_groups();
+        }
+        self.flush();
+        debug_assert_eq!(self.tokens[self.pos].kind, kind, "formatter out of step");
+        self.any_token();
+    }
+
+    /// Write the next source token as it is written
+    fn any_token(&mut self) {
+        self.flush();
+        let token = &self.tokens[self.pos];
+        let text = &self.source[token.start..token.end];
+        self.pos += 1;
+        self.write(text);
+    }
+
+    /// Drop an optional token, like a trailing comma, if it's there
+    fn skip(&mut self, kind: Kind) {
+        if self.next_is(kind) {
+            self.flush();
+            self.pos += 1;
+        }
+    }
+
+    /// Write the parentheses that open before an expression
+    fn open_groups(&mut self) {
+        while self.next_is(Kind::OpenParen) {
+            self.any_token();
+            self.groups += 1;
+        }
+    }
+
+    /// Close the parentheses opened in an expression that ends here
+    fn close_groups(&mut self, start: usize) {
+        while self.groups > start && self.next_is(Kind::CloseParen) {
+            self.any_token();
+            self.groups -= 1;
+        }
+    }
+
+    /// Whether the next token that isn't trivia is of kind `kind`
+    fn next_is(&self, kind: Kind) -> bool {
+        self.tokens[self.pos..]
+            .iter()
+            .find(|token| !token.kind.is_trivia())
+            .is_some_and(|token| token.kind == kind)
+    }
+
+    /// Whether the next token is of kind `kind`, with no comment before it
+    fn next_is_code(&self, kind: Kind) -> bool {
+        self.tokens[self.pos..]
+            .iter()
+            .find(|token| token.kind != Kind::WhiteSpace)
+            .is_some_and(|token| token.kind == kind)
+    }
+
+    /// Write the comments before the next token: on the current line if they
+    /// were on the line of the previous token, otherwise on lines of their
+    /// own. Returns how many line breaks come after the last of them.
+    fn flush(&mut self) -> usize {
+        let mut newlines = 0;
+        while self.pos < self.tokens.len() && self.tokens[self.pos].kind.is_trivia() {
+            let token = &self.tokens[self.pos];
+            let text = &self.source[token.start..token.end];
+            self.pos += 1;
+            if token.kind == Kind::WhiteSpace {
+                newlines += text.matches('\n').count();
+                continue;
+            }
+            let at_line_start = self.out.is_empty() || self.out.ends_with('\n');
+            if newlines == 0 && !at_line_start {
+                self.write(" ");
+                self.write(text);
+            } else {
+                self.newline();
+                if newlines > 1 {
+                    self.blank_line();
+                }
+                self.write(text);
+                self.newline();
+            }
+            if text.starts_with("//") {
+                self.pending_newline = true;
+            }
+            newlines = 0;
+        }
+        newlines
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.pending_newline {
+            self.newline();
+        }
+        if self.out.is_empty() || self.out.ends_with('\n') {
+            if text == " " {
+                return;
+            }
+            for _ in 0..self.indent {
+                self.out.push_str(INDENT);
+            }
+        }
+        // `- -x` isn't `--x`, a decrement
+        if self.out.ends_with('-') && text.starts_with('-') {
+            self.out.push(' ');
+        }
+        self.out.push_str(text);
+    }
+
+    /// End the current line, unless it's empty
+    fn newline(&mut self) {
+        self.pending_newline = false;
+        if self.out.is_empty() || self.out.ends_with('\n') {
+            return;
+        }
+        let len = self.out.trim_end_matches(' ').len();
+        self.out.truncate(len);
+        self.out.push('\n');
+    }
+
+    /// An empty line, except at the start of the program or of a block
+    fn blank_line(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("{\n") && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+}
@@ -0,0 +1,453 @@
+//! Random programs for testing the front end, built with the
+//! `program-generator` feature.
+//!
+//! [`program`] is a proptest strategy for programs that are syntactically
+//! valid, so printing one with [`to_source`](crate::ast_printer::to_source)
+//! and parsing the result gives back the same tree. They aren't meant to type
+//! check or run.
+//!
+//! A program is made from a list of choices, one number for each decision
+//! the generator takes, and a choice of 0 always takes the simplest option.
+//! proptest shrinks a failing list by dropping choices and lowering them
+//! towards 0, which shrinks the program with it; once the list runs out the
+//! rest of the program is made from zeros.
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use crate::lexer::Span;
+use crate::parser::{ASTNode, Ast, BinaryOperator, NodeId, Param, UnaryOperator};
+use crate::semantic_analyzer::Type;
+
+/// How deeply expressions and blocks nest
+const MAX_DEPTH: usize = 4;
+
+/// The most choices a program is made from
+const MAX_CHOICES: usize = 512;
+
+/// Names for variables and functions, none of which is a keyword
+const NAMES: &[&str] = &["a", "b", "count", "total", "xs", "point", "name", "i"];
+const FUNCTIONS: &[&str] = &["f", "helper", "sum", "len"];
+const STRUCTS: &[&str] = &["Point", "Pair"];
+const FIELDS: &[&str] = &["x", "y", "first", "rest"];
+
+/// Characters strings are made of, including the ones that need escaping
+const CHARS: &[char] = &['a', 'z', ' ', '$', '{', '}', '"', '\\', '\n', '\t', 'é'];
+
+const OPERATORS: &[BinaryOperator] = &[
+    BinaryOperator::Plus,
+    BinaryOperator::Minus,
+    BinaryOperator::Multiply,
+    BinaryOperator::Divide,
+    BinaryOperator::Modulo,
+    BinaryOperator::Power,
+    BinaryOperator::Equal,
+    BinaryOperator::NotEqual,
+    BinaryOperator::Less,
+    BinaryOperator::LessEqual,
+    BinaryOperator::Greater,
+    BinaryOperator::GreaterEqual,
+    BinaryOperator::And,
+    BinaryOperator::Or,
+];
+
+/// A strategy for programs of structs, functions and statements
+pub fn program() -> impl Strategy<Value = Ast> {
+    vec(any::<u32>(), 0..MAX_CHOICES).prop_map(|choices| Generator::new(choices).program())
+}
+
+/// Makes a program from a list of choices
+struct Generator {
+    choices: std::vec::IntoIter<u32>,
+    depth: usize,
+    in_loop: bool,
+    in_function: bool,
+
+    /// The program being generated
+    ast: Ast,
+}
+
+impl Generator {
+    fn new(choices: Vec<u32>) -> Self {
+        Generator {
+            choices: choices.into_iter(),
+            depth: 0,
+            in_loop: false,
+            in_function: false,
+            ast: Ast::new(),
+        }
+    }
+
+    fn program(mut self) -> Ast {
+        let mut statements = Vec::new();
+        for _ in 0..self.below(8) + 1 {
+            let statement = match self.below(6) {
+                4 => self.struct_declaration(),
+                5 => self.function_declaration(),
+                _ => self.statement(),
+            };
+            statements.push(statement);
+        }
+        let root = self.ast.root();
+        self.ast[root] = ASTNode::Program(statements);
+        self.ast
+    }
+
+    /// The next choice, from 0 up to but not including `n`
+    fn below(&mut self, n: usize) -> usize {
+        self.choices.next().unwrap_or(0) as usize % n
+    }
+
+    /// Whether to add an optional part, which a choice of 0 leaves out
+    fn chance(&mut self) -> bool {
+        self.below(2) == 1
+    }
+
+    fn pick<T: Clone>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())].clone()
+    }
+
+    fn name(&mut self) -> String {
+        self.pick(NAMES).to_string()
+    }
+
+    /// Text for a string literal
+    fn text(&mut self) -> String {
+        (0..self.below(5)).map(|_| self.pick(CHARS)).collect()
+    }
+
+    fn add(&mut self, node: ASTNode) -> NodeId {
+        self.ast.add(node)
+    }
+
+    fn struct_declaration(&mut self) -> NodeId {
+        let fields = FIELDS[..self.below(FIELDS.len()) + 1]
+            .iter()
+            .map(|field| field.to_string())
+            .collect();
+        let node = ASTNode::StructDeclaration {
+            name: self.pick(STRUCTS).to_string(),
+            fields,
+            span: Span::default(),
+        };
+        self.add(node)
+    }
+
+    fn function_declaration(&mut self) -> NodeId {
+        let params = (0..self.below(3))
+            .map(|_| Param {
+                name: self.name(),
+                ty: self.maybe_type(),
+            })
+            .collect();
+        let result = self.maybe_type();
+        self.in_function = true;
+        let body = self.block();
+        self.in_function = false;
+        let node = ASTNode::FunctionDeclaration {
+            name: self.pick(FUNCTIONS).to_string(),
+            params,
+            result,
+            body,
+            span: Span::default(),
+        };
+        self.add(node)
+    }
+
+    fn maybe_type(&mut self) -> Option<Type> {
+        self.chance().then(|| self.ty(2))
+    }
+
+    fn ty(&mut self, depth: usize) -> Type {
+        match self.below(if depth == 0 { 5 } else { 7 }) {
+            0 => Type::Int,
+            1 => Type::Float,
+            2 => Type::Bool,
+            3 => Type::Str,
+            4 => Type::Struct(self.pick(STRUCTS).to_string()),
+            5 => Type::Array(Box::new(self.ty(depth - 1))),
+            _ => Type::Map(Box::new(self.ty(depth - 1))),
+        }
+    }
+
+    fn block(&mut self) -> NodeId {
+        self.depth += 1;
+        let statements = (0..self.below(4)).map(|_| self.statement()).collect();
+        self.depth -= 1;
+        self.add(ASTNode::Block(statements))
+    }
+
+    fn statement(&mut self) -> NodeId {
+        let span = Span::default();
+        let choices = if self.depth >= MAX_DEPTH { 11 } else { 14 };
+        let node = match self.below(choices) {
+            0 => ASTNode::LetDeclaration {
+                name: self.name(),
+                mutable: self.chance(),
+                ty: self.maybe_type(),
+                value: self.expression(),
+                span,
+            },
+            1 => ASTNode::ConstDeclaration {
+                name: self.name(),
+                value: self.expression(),
+                span,
+            },
+            2 | 3 => return self.step(),
+            4 => {
+                let format = self.chance().then(|| self.text());
+                let args = (0..self.below(3)).map(|_| self.expression()).collect();
+                ASTNode::Print {
+                    format,
+                    args,
+                    newline: self.below(2) == 0,
+                    span,
+                }
+            }
+            5 => ASTNode::Input {
+                name: self.name(),
+                span,
+            },
+            6 => ASTNode::IndexAssignment {
+                array: self.place(),
+                index: self.expression(),
+                value: self.expression(),
+                span,
+            },
+            7 => ASTNode::FieldAssignment {
+                object: self.place(),
+                field: self.pick(FIELDS).to_string(),
+                value: self.expression(),
+                span,
+            },
+            8 => self.call(),
+            9 if self.in_loop => {
+                if self.below(2) == 0 {
+                    ASTNode::Break { span }
+                } else {
+                    ASTNode::Continue { span }
+                }
+            }
+            10 if self.in_function => ASTNode::Return {
+                value: self.chance().then(|| self.expression()),
+                span,
+            },
+            9 | 10 => self.call(),
+            11 => return self.if_statement(),
+            12 => {
+                let condition = self.expression();
+                ASTNode::While {
+                    condition,
+                    body: self.loop_body(),
+                    span,
+                }
+            }
+            _ => {
+                let init = self.chance().then(|| {
+                    let init = ASTNode::LetDeclaration {
+                        name: self.name(),
+                        mutable: true,
+                        ty: None,
+                        value: self.expression(),
+                        span,
+                    };
+                    self.add(init)
+                });
+                let condition = self.chance().then(|| self.expression());
+                let step = self.chance().then(|| self.step());
+                ASTNode::For {
+                    init,
+                    condition,
+                    step,
+                    body: self.loop_body(),
+                    span,
+                }
+            }
+        };
+        self.add(node)
+    }
+
+    /// A statement that changes a variable, which can also be a `for` step
+    fn step(&mut self) -> NodeId {
+        let name = self.name();
+        let span = Span::default();
+        let node = match self.below(4) {
+            0 => ASTNode::Assignment {
+                name,
+                value: self.expression(),
+                span,
+            },
+            1 => ASTNode::CompoundAssignment {
+                name,
+                op: self.pick(&OPERATORS[..4]),
+                value: self.expression(),
+                span,
+            },
+            2 => ASTNode::Increment { name, span },
+            _ => ASTNode::Decrement { name, span },
+        };
+        self.add(node)
+    }
+
+    fn if_statement(&mut self) -> NodeId {
+        let condition = self.expression();
+        let then_branch = self.block();
+        let else_branch = match self.below(3) {
+            0 => None,
+            1 => Some(self.block()),
+            // `else if` is parsed as an `if` in place of the else block
+            _ => {
+                self.depth += 1;
+                let else_if = self.if_statement();
+                self.depth -= 1;
+                Some(else_if)
+            }
+        };
+        self.add(ASTNode::If {
+            condition,
+            then_branch,
+            else_branch,
+            span: Span::default(),
+        })
+    }
+
+    fn loop_body(&mut self) -> NodeId {
+        let in_loop = std::mem::replace(&mut self.in_loop, true);
+        let body = self.block();
+        self.in_loop = in_loop;
+        body
+    }
+
+    /// What an element or field is assigned in
+    fn place(&mut self) -> NodeId {
+        let name = ASTNode::Identifier {
+            name: self.name(),
+            span: Span::default(),
+        };
+        let name = self.add(name);
+        let node = match self.below(3) {
+            0 => return name,
+            1 => ASTNode::Index {
+                array: name,
+                index: self.leaf(),
+                span: Span::default(),
+            },
+            _ => ASTNode::FieldAccess {
+                object: name,
+                field: self.pick(FIELDS).to_string(),
+                span: Span::default(),
+            },
+        };
+        self.add(node)
+    }
+
+    fn call(&mut self) -> ASTNode {
+        ASTNode::Call {
+            name: self.pick(FUNCTIONS).to_string(),
+            args: self.expressions(3),
+            span: Span::default(),
+        }
+    }
+
+    fn expressions(&mut self, most: usize) -> Vec<NodeId> {
+        (0..self.below(most + 1))
+            .map(|_| self.expression())
+            .collect()
+    }
+
+    fn expression(&mut self) -> NodeId {
+        if self.depth >= MAX_DEPTH || self.below(3) == 0 {
+            return self.leaf();
+        }
+        self.depth += 1;
+        let span = Span::default();
+        let node = match self.below(11) {
+            0 | 1 => ASTNode::BinaryOp {
+                left: self.expression(),
+                op: self.pick(OPERATORS),
+                right: self.expression(),
+                span,
+            },
+            2 => {
+                let op = if self.below(2) == 0 {
+                    UnaryOperator::Not
+                } else {
+                    UnaryOperator::Negate
+                };
+                let mut operand = self.expression();
+                // A negated literal is parsed as a negative literal
+                if op == UnaryOperator::Negate
+                    && matches!(self.ast[operand], ASTNode::Int(_) | ASTNode::Number(_))
+                {
+                    let name = ASTNode::Identifier {
+                        name: self.name(),
+                        span,
+                    };
+                    operand = self.add(name);
+                }
+                ASTNode::UnaryOp { op, operand, span }
+            }
+            3 => ASTNode::Conditional {
+                condition: self.expression(),
+                then_value: self.expression(),
+                else_value: self.expression(),
+                span,
+            },
+            4 => ASTNode::ArrayLiteral {
+                elements: self.expressions(3),
+                span,
+            },
+            5 => ASTNode::Index {
+                array: self.expression(),
+                index: self.expression(),
+                span,
+            },
+            6 => self.call(),
+            7 => ASTNode::MapLiteral {
+                entries: (0..self.below(3))
+                    .map(|_| {
+                        let key = ASTNode::Str(self.text());
+                        (self.add(key), self.expression())
+                    })
+                    .collect(),
+                span,
+            },
+            8 => ASTNode::StructLiteral {
+                name: self.pick(STRUCTS).to_string(),
+                fields: (0..self.below(3))
+                    .map(|_| (self.pick(FIELDS).to_string(), self.expression()))
+                    .collect(),
+                span,
+            },
+            9 => ASTNode::FieldAccess {
+                object: self.expression(),
+                field: self.pick(FIELDS).to_string(),
+                span,
+            },
+            _ => {
+                let values: Vec<_> = (0..self.below(2) + 1).map(|_| self.expression()).collect();
+                ASTNode::Interpolation {
+                    pieces: (0..=values.len()).map(|_| self.text()).collect(),
+                    values,
+                    span,
+                }
+            }
+        };
+        self.depth -= 1;
+        self.add(node)
+    }
+
+    fn leaf(&mut self) -> NodeId {
+        let node = match self.below(6) {
+            0 => ASTNode::Int(self.below(1000) as i64),
+            1 => ASTNode::Number(self.below(100) as f64 / 4.0),
+            2 => ASTNode::Bool(self.below(2) == 0),
+            3 => ASTNode::Str(self.text()),
+            _ => ASTNode::Identifier {
+                name: self.name(),
+                span: Span::default(),
+            },
+        };
+        self.add(node)
+    }
+}
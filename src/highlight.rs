@@ -0,0 +1,80 @@
+//! Token classes for syntax highlighting, so editor plugins don't need their
+//! own lexer.
+
+use serde::Serialize;
+
+use crate::lexer::{Kind, Lexer, Span, Token};
+
+/// How a token is highlighted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenClass {
+    /// A reserved word, including `true` and `false`
+    Keyword,
+    Number,
+    Identifier,
+    /// An operator or punctuation, like `+`, `=` or `{`
+    Operator,
+    Comment,
+    /// A string literal, or a piece of text of an interpolated one
+    String,
+}
+
+impl TokenClass {
+    /// The class of a token kind, or `None` for whitespace and the end of
+    /// the file
+    pub fn of(kind: Kind) -> Option<Self> {
+        Some(match kind {
+            Kind::Eof | Kind::WhiteSpace => return None,
+            Kind::Comment => TokenClass::Comment,
+            Kind::Identifier => TokenClass::Identifier,
+            Kind::Integer | Kind::Number => TokenClass::Number,
+            Kind::String
+            | Kind::Char
+            | Kind::StringStart
+            | Kind::StringMiddle
+            | Kind::StringEnd => TokenClass::String,
+            Kind::Print
+            | Kind::Println
+            | Kind::Input
+            | Kind::Let
+            | Kind::Mut
+            | Kind::Const
+            | Kind::Struct
+            | Kind::Fn
+            | Kind::Return
+            | Kind::If
+            | Kind::Else
+            | Kind::While
+            | Kind::Do
+            | Kind::Assert
+            | Kind::For
+            | Kind::In
+            | Kind::Break
+            | Kind::Continue
+            | Kind::Import
+            | Kind::Module
+            | Kind::Pub
+            | Kind::True
+            | Kind::False => TokenClass::Keyword,
+            _ => TokenClass::Operator,
+        })
+    }
+}
+
+/// The highlighted tokens of a source, comments included, in order. Text
+/// from a lexing error onwards, like an unterminated string, isn't
+/// highlighted.
+pub fn highlight(source: &str) -> Vec<(Span, TokenClass)> {
+    let tokens = Lexer::new(source)
+        .get_tokens_with_trivia()
+        .or_else(|err| {
+            let end = err.span().map_or(0, |span| span.offset).min(source.len());
+            Lexer::new(&source[..end]).get_tokens_with_trivia()
+        })
+        .unwrap_or_default();
+    tokens
+        .iter()
+        .filter_map(|token: &Token| Some((token.span, TokenClass::of(token.kind)?)))
+        .collect()
+}
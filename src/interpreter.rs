@@ -0,0 +1,1604 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, Stdout, Write};
+use std::rc::Rc;
+
+use crate::builtins::{self, Builtin, Rng};
+use crate::checks::Checks;
+use crate::error::CompileError;
+use crate::format;
+use crate::lexer::Span;
+use crate::parser::{self, ASTNode, Ast, BinaryOperator, NodeId, Param, UnaryOperator};
+use crate::semantic_analyzer::{SemanticAnalyzer, Type};
+
+/// Upper bound on loop iterations, to catch infinite loops
+const MAX_LOOP_ITERATIONS: usize = 1_000_000;
+
+/// How deeply calls may nest unless configured otherwise, to catch infinite
+/// recursion before it overflows the stack
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+/// Error for a call nested deeper than `max` calls
+pub(crate) fn call_depth_exceeded(max: usize) -> String {
+    format!(
+        "Call depth exceeded {} nested calls, possible infinite recursion",
+        max
+    )
+}
+
+/// Error for an `assert` whose condition is falsy
+pub(crate) fn assertion_failed(message: Option<&str>) -> String {
+    match message {
+        Some(message) => format!("Assertion failed: {}", message),
+        None => "Assertion failed".to_string(),
+    }
+}
+
+/// An error that stopped a program while it ran, and the calls it
+/// unwound through
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+
+    /// Where the error happened
+    pub span: Span,
+
+    /// The calls that were running, innermost first
+    pub stack: Vec<Frame>,
+}
+
+/// A call that was running when a runtime error happened
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// The function called, or `None` for a lambda
+    pub function: Option<String>,
+
+    /// Where it was called
+    pub span: Span,
+}
+
+impl RuntimeError {
+    pub fn new(message: String, span: Span) -> Self {
+        Self {
+            message,
+            span,
+            stack: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.function {
+            Some(function) => write!(f, "in {}()", function)?,
+            None => write!(f, "in a lambda")?,
+        }
+        write!(
+            f,
+            " called at line {}, column {}",
+            self.span.line, self.span.column
+        )
+    }
+}
+
+/// A runtime value produced by evaluating an expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    /// One character of a string
+    Char(char),
+    /// Arrays are shared: copying one gives another reference to the same
+    /// elements, so assigning to an element is seen through both
+    Array(Rc<RefCell<Array>>),
+    /// Maps are shared the same way as arrays
+    Map(Rc<RefCell<Map>>),
+    /// So are structs
+    Struct(Rc<RefCell<Struct>>),
+    /// A lambda, which can't change once it is made
+    Closure(Rc<Closure>),
+    /// A declared function used as a value, by name
+    Function(String),
+}
+
+/// A lambda along with the values of the variables it captured
+#[derive(Debug, Clone, PartialEq)]
+pub struct Closure {
+    pub(crate) lambda: NodeId,
+    pub(crate) captures: Vec<(String, Value)>,
+}
+
+/// The contents of an array value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Array {
+    pub elements: Vec<Value>,
+
+    /// Whether the elements are floats, so ints stored into the array are
+    /// promoted even once it has been emptied
+    floats: bool,
+}
+
+impl Array {
+    /// An array of the given elements. If any is a float, integers are
+    /// promoted so the elements share a type.
+    pub fn new(elements: Vec<Value>) -> Self {
+        let floats = elements
+            .iter()
+            .any(|element| matches!(element, Value::Float(_)));
+        let mut array = Self {
+            elements: Vec::new(),
+            floats,
+        };
+        array.elements = elements
+            .into_iter()
+            .map(|element| array.promote(element))
+            .collect();
+        array
+    }
+
+    /// Add an element to the end
+    pub fn push(&mut self, val: Value) {
+        let val = self.promote(val);
+        self.elements.push(val);
+    }
+
+    /// Remove the last element and return it
+    pub fn pop(&mut self) -> Result<Value, String> {
+        self.elements
+            .pop()
+            .ok_or_else(|| "Cannot pop from an empty array".to_string())
+    }
+
+    /// Convert an int to a float if this is an array of floats
+    fn promote(&self, val: Value) -> Value {
+        promote(self.floats, val)
+    }
+}
+
+/// The contents of a map value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Map {
+    pub entries: HashMap<String, Value>,
+
+    /// Whether the values are floats, so ints stored into the map are
+    /// promoted
+    floats: bool,
+}
+
+impl Map {
+    /// A map of the given entries, promoting integer values the same way
+    /// as [`Array::new`]. A later entry replaces an earlier one with the
+    /// same key.
+    pub fn new(entries: Vec<(String, Value)>) -> Self {
+        let floats = entries
+            .iter()
+            .any(|(_, value)| matches!(value, Value::Float(_)));
+        let mut map = Self {
+            entries: HashMap::new(),
+            floats,
+        };
+        for (key, value) in entries {
+            map.insert(key, value);
+        }
+        map
+    }
+
+    /// Store a value under a key, replacing any value already there
+    pub fn insert(&mut self, key: String, val: Value) {
+        let val = promote(self.floats, val);
+        self.entries.insert(key, val);
+    }
+
+    /// The keys in sorted order, so maps always print the same way
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<_> = self.entries.keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+}
+
+/// The contents of a struct value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Struct {
+    pub name: String,
+
+    /// The fields and their values, in the order they're declared
+    pub fields: Vec<(String, Value)>,
+}
+
+/// Convert an int to a float when it is stored among floats
+pub(crate) fn promote(floats: bool, val: Value) -> Value {
+    match val {
+        Value::Int(num) if floats => Value::Float(num as f64),
+        val => val,
+    }
+}
+
+impl Value {
+    /// Name of the value's type, used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "bool",
+            Value::Str(_) => "string",
+            Value::Char(_) => "char",
+            Value::Array(_) => "array",
+            Value::Map(_) => "map",
+            Value::Struct(_) => "struct",
+            Value::Closure(_) | Value::Function(_) => "function",
+        }
+    }
+
+    /// Booleans are used as is, numbers and characters are truthy when
+    /// non-zero, strings, arrays and maps when not empty, and structs and
+    /// functions always
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Int(num) => *num != 0,
+            Value::Float(num) => *num != 0.0,
+            Value::Bool(b) => *b,
+            Value::Str(text) => !text.is_empty(),
+            Value::Char(c) => *c != '\0',
+            Value::Array(array) => !array.borrow().elements.is_empty(),
+            Value::Map(map) => !map.borrow().entries.is_empty(),
+            Value::Struct(_) | Value::Closure(_) | Value::Function(_) => true,
+        }
+    }
+
+    /// Write the value as it appears inside an array, map or struct, where
+    /// strings and characters are quoted
+    fn fmt_nested(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Str(text) => write!(f, "{:?}", text),
+            Value::Char(c) => write!(f, "{:?}", c),
+            _ => write!(f, "{}", self),
+        }
+    }
+
+    /// Numeric value as a float, promoting integers
+    pub(crate) fn as_number(&self) -> Result<f64, String> {
+        match self {
+            Value::Int(num) => Ok(*num as f64),
+            Value::Float(num) => Ok(*num),
+            _ => Err(format!("Expected a number, found {}", self.type_name())),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(num) => write!(f, "{}", num),
+            Value::Float(num) => write!(f, "{}", num),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Str(text) => write!(f, "{}", text),
+            Value::Char(c) => write!(f, "{}", c),
+            Value::Array(array) => {
+                write!(f, "[")?;
+                for (i, element) in array.borrow().elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.fmt_nested(f)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(map) => {
+                let map = map.borrow();
+                write!(f, "{{")?;
+                for (i, key) in map.keys().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{:?}: ", key)?;
+                    map.entries[key].fmt_nested(f)?;
+                }
+                write!(f, "}}")
+            }
+            Value::Struct(value) => {
+                let value = value.borrow();
+                write!(f, "{} {{", value.name)?;
+                for (i, (field, val)) in value.fields.iter().enumerate() {
+                    let separator = if i > 0 { "," } else { "" };
+                    write!(f, "{} {}: ", separator, field)?;
+                    val.fmt_nested(f)?;
+                }
+                write!(f, " }}")
+            }
+            Value::Closure(_) => write!(f, "<closure>"),
+            Value::Function(name) => write!(f, "<fn {}>", name),
+        }
+    }
+}
+
+/// An array value holding the given elements
+pub(crate) fn array(elements: Vec<Value>) -> Value {
+    Value::Array(Rc::new(RefCell::new(Array::new(elements))))
+}
+
+/// A map value holding the given entries
+pub(crate) fn map(entries: Vec<(Value, Value)>) -> Result<Value, String> {
+    let entries = entries
+        .into_iter()
+        .map(|(key, value)| Ok((key_of(&key)?, value)))
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(Value::Map(Rc::new(RefCell::new(Map::new(entries)))))
+}
+
+/// The text of a value used as a map key
+fn key_of(key: &Value) -> Result<String, String> {
+    match key {
+        Value::Str(text) => Ok(text.clone()),
+        _ => Err(format!(
+            "Map key must be a string, found {}",
+            key.type_name()
+        )),
+    }
+}
+
+/// A struct value with the given fields, which are in declaration order
+pub(crate) fn structure(name: &str, fields: Vec<(String, Value)>) -> Value {
+    Value::Struct(Rc::new(RefCell::new(Struct {
+        name: name.to_string(),
+        fields,
+    })))
+}
+
+/// The value of a field of a struct
+pub(crate) fn field(object: &Value, field: &str) -> Result<Value, String> {
+    let Value::Struct(value) = object else {
+        return Err(format!(
+            "Cannot access field '{}' of {}",
+            field,
+            object.type_name()
+        ));
+    };
+    let value = value.borrow();
+    value
+        .fields
+        .iter()
+        .find(|(name, _)| name == field)
+        .map(|(_, val)| val.clone())
+        .ok_or_else(|| format!("Struct '{}' has no field '{}'", value.name, field))
+}
+
+/// Store `val` in a field of a struct
+pub(crate) fn set_field(object: &Value, field: &str, val: Value) -> Result<(), String> {
+    let Value::Struct(value) = object else {
+        return Err(format!(
+            "Cannot access field '{}' of {}",
+            field,
+            object.type_name()
+        ));
+    };
+    let mut value = value.borrow_mut();
+    let name = value.name.clone();
+    let slot = value
+        .fields
+        .iter_mut()
+        .find(|(name, _)| name == field)
+        .ok_or_else(|| format!("Struct '{}' has no field '{}'", name, field))?;
+    slot.1 = val;
+    Ok(())
+}
+
+/// The position of `index` in an array, or a string, of `len` elements, if
+/// it is in bounds
+fn position(index: &Value, len: usize, what: &str) -> Result<usize, String> {
+    let Value::Int(index) = index else {
+        return Err(format!(
+            "Array index must be an int, found {}",
+            index.type_name()
+        ));
+    };
+    usize::try_from(*index)
+        .ok()
+        .filter(|position| *position < len)
+        .ok_or_else(|| {
+            format!(
+                "Index {} is out of bounds for {} of length {}",
+                index, what, len
+            )
+        })
+}
+
+/// The element of `array` at `index`, the character of a string at
+/// `index`, or the value of a map under the key `index`
+pub(crate) fn index(array: &Value, index: &Value) -> Result<Value, String> {
+    match array {
+        Value::Array(array) => {
+            let elements = &array.borrow().elements;
+            Ok(elements[position(index, elements.len(), "an array")?].clone())
+        }
+        Value::Str(text) => {
+            let position = position(index, text.chars().count(), "a string")?;
+            Ok(Value::Char(text.chars().nth(position).expect("in bounds")))
+        }
+        Value::Map(map) => {
+            let key = key_of(index)?;
+            map.borrow()
+                .entries
+                .get(&key)
+                .cloned()
+                .ok_or_else(|| format!("Key {:?} is not in the map", key))
+        }
+        _ => Err(format!("Cannot index into {}", array.type_name())),
+    }
+}
+
+/// A copy of the elements of `array` from `start` up to `end`, or of the
+/// characters of a string. A missing bound is the start or the end.
+pub(crate) fn slice(
+    array: &Value,
+    start: Option<&Value>,
+    end: Option<&Value>,
+) -> Result<Value, String> {
+    let (len, what) = match array {
+        Value::Array(array) => (array.borrow().elements.len(), "an array"),
+        Value::Str(text) => (text.chars().count(), "a string"),
+        _ => return Err(format!("Cannot slice {}", array.type_name())),
+    };
+    let bound = |bound: Option<&Value>, default: usize| match bound {
+        None => Ok(default as i64),
+        Some(Value::Int(bound)) => Ok(*bound),
+        Some(other) => Err(format!(
+            "Slice bounds must be ints, found {}",
+            other.type_name()
+        )),
+    };
+    let (start, end) = (bound(start, 0)?, bound(end, len)?);
+    if start < 0 || start > end || end > len as i64 {
+        return Err(format!(
+            "Slice {}:{} is out of range for {} of length {}",
+            start, end, what, len
+        ));
+    }
+    let range = start as usize..end as usize;
+    Ok(match array {
+        Value::Array(array) => {
+            let array = array.borrow();
+            Value::Array(Rc::new(RefCell::new(Array {
+                elements: array.elements[range].to_vec(),
+                floats: array.floats,
+            })))
+        }
+        Value::Str(text) => Value::Str(text.chars().skip(range.start).take(range.len()).collect()),
+        _ => unreachable!("checked above"),
+    })
+}
+
+/// Store `val` as the element of `array` at `index`, or in a map under the
+/// key `index`, promoting an integer stored among floats
+pub(crate) fn set_index(array: &Value, index: &Value, val: Value) -> Result<(), String> {
+    match array {
+        Value::Array(array) => {
+            let mut array = array.borrow_mut();
+            let position = position(index, array.elements.len(), "an array")?;
+            array.elements[position] = array.promote(val);
+        }
+        Value::Map(map) => map.borrow_mut().insert(key_of(index)?, val),
+        _ => return Err(format!("Cannot index into {}", array.type_name())),
+    }
+    Ok(())
+}
+
+/// Apply an arithmetic operator, staying in integers when both sides are integers
+pub(crate) fn arithmetic(
+    op: &BinaryOperator,
+    left: &Value,
+    right: &Value,
+) -> Result<Value, String> {
+    // Powers are always floats, like C's pow()
+    if let (Value::Int(left), Value::Int(right), false) =
+        (left, right, *op == BinaryOperator::Power)
+    {
+        return match op {
+            BinaryOperator::Plus => Ok(Value::Int(left.wrapping_add(*right))),
+            BinaryOperator::Minus => Ok(Value::Int(left.wrapping_sub(*right))),
+            BinaryOperator::Multiply => Ok(Value::Int(left.wrapping_mul(*right))),
+            BinaryOperator::Divide if *right == 0 => Err("Division by zero".to_string()),
+            BinaryOperator::Divide => Ok(Value::Int(left.wrapping_div(*right))),
+            BinaryOperator::Modulo if *right == 0 => Err("Modulo by zero".to_string()),
+            BinaryOperator::Modulo => Ok(Value::Int(left.wrapping_rem(*right))),
+            _ => Err(format!("Unexpected arithmetic operator {:?}", op)),
+        };
+    }
+    let left = left.as_number()?;
+    let right = right.as_number()?;
+    match op {
+        BinaryOperator::Plus => Ok(Value::Float(left + right)),
+        BinaryOperator::Minus => Ok(Value::Float(left - right)),
+        BinaryOperator::Multiply => Ok(Value::Float(left * right)),
+        BinaryOperator::Divide => Ok(Value::Float(left / right)),
+        // Same as C's fmod: the result takes the sign of the dividend
+        BinaryOperator::Modulo => Ok(Value::Float(left % right)),
+        BinaryOperator::Power => Ok(Value::Float(left.powf(right))),
+        _ => Err(format!("Unexpected arithmetic operator {:?}", op)),
+    }
+}
+
+/// Apply a comparison operator; numbers of either kind compare by value
+pub(crate) fn compare(op: &BinaryOperator, left: &Value, right: &Value) -> Result<Value, String> {
+    let ordering = match (left, right) {
+        (Value::Int(left), Value::Int(right)) => left.partial_cmp(right),
+        (Value::Bool(left), Value::Bool(right)) => match op {
+            BinaryOperator::Equal | BinaryOperator::NotEqual => left.partial_cmp(right),
+            _ => return Err("Cannot order bool values".to_string()),
+        },
+        (Value::Str(left), Value::Str(right)) => match op {
+            BinaryOperator::Equal | BinaryOperator::NotEqual => left.partial_cmp(right),
+            _ => return Err("Cannot order string values".to_string()),
+        },
+        // Characters are ordered by their code points
+        (Value::Char(left), Value::Char(right)) => left.partial_cmp(right),
+        (Value::Bool(_) | Value::Str(_) | Value::Char(_), _)
+        | (_, Value::Bool(_) | Value::Str(_) | Value::Char(_)) => {
+            return Err(format!(
+                "Cannot compare {} and {}",
+                left.type_name(),
+                right.type_name()
+            ));
+        }
+        _ => left.as_number()?.partial_cmp(&right.as_number()?),
+    };
+    // NaN compares unequal to everything
+    let result = match ordering {
+        Some(ordering) => match op {
+            BinaryOperator::Equal => ordering.is_eq(),
+            BinaryOperator::NotEqual => ordering.is_ne(),
+            BinaryOperator::Less => ordering.is_lt(),
+            BinaryOperator::LessEqual => ordering.is_le(),
+            BinaryOperator::Greater => ordering.is_gt(),
+            BinaryOperator::GreaterEqual => ordering.is_ge(),
+            _ => return Err(format!("Unexpected comparison operator {:?}", op)),
+        },
+        None => *op == BinaryOperator::NotEqual,
+    };
+    Ok(Value::Bool(result))
+}
+
+/// Negate a number, wrapping on integer overflow
+pub(crate) fn negate(val: &Value) -> Result<Value, String> {
+    match val {
+        Value::Int(num) => Ok(Value::Int(num.wrapping_neg())),
+        Value::Float(num) => Ok(Value::Float(-num)),
+        _ => Err(format!("Cannot negate {}", val.type_name())),
+    }
+}
+
+/// Read a line from `input` and parse it as a number of the same type as
+/// `like`, the current value of the variable being read into
+pub(crate) fn read_value(input: &mut dyn BufRead, like: &Value) -> Result<Value, String> {
+    let mut line = String::new();
+    let read = input
+        .read_line(&mut line)
+        .map_err(|err| format!("Failed to read input: {}", err))?;
+    if read == 0 {
+        return Err("Unexpected end of input".to_string());
+    }
+    let text = line.trim();
+    let value = match like {
+        Value::Int(_) => text.parse().map(Value::Int).ok(),
+        Value::Float(_) => text.parse().map(Value::Float).ok(),
+        Value::Bool(_)
+        | Value::Str(_)
+        | Value::Char(_)
+        | Value::Array(_)
+        | Value::Map(_)
+        | Value::Struct(_)
+        | Value::Closure(_)
+        | Value::Function(_) => None,
+    };
+    value.ok_or_else(|| format!("Expected {} input, found '{}'", like.type_name(), text))
+}
+
+/// Where `input` reads from: the given reader, or stdin
+pub(crate) fn read_from(
+    input: &mut Option<Box<dyn BufRead>>,
+    like: &Value,
+) -> Result<Value, String> {
+    match input {
+        Some(input) => read_value(input.as_mut(), like),
+        // Stdin is locked per read so a REPL can share it
+        None => read_value(&mut io::stdin().lock(), like),
+    }
+}
+
+/// How a statement finished, which tells the loops and calls around it what
+/// to do next
+#[derive(Debug, Clone, PartialEq)]
+enum Flow {
+    /// Go on with the next statement
+    Normal,
+    /// Leave the innermost loop
+    Break,
+    /// Go on with the next iteration of the innermost loop
+    Continue,
+    /// Leave the function being called, with its result if it has one
+    Return(Option<Value>),
+}
+
+/// Where a program is about to run a statement, as seen by a [`Hook`]
+pub struct Pause<'a> {
+    /// The span of the statement
+    pub span: Span,
+
+    /// How many calls are being run, 0 outside of functions
+    pub depth: usize,
+
+    /// The statement itself
+    pub statement: &'a ASTNode,
+
+    /// The program the statement belongs to, which holds its children
+    pub ast: &'a Ast,
+
+    scopes: &'a [HashMap<String, Value>],
+}
+
+impl Pause<'_> {
+    /// The variables the statement can see and their values, sorted by name
+    pub fn variables(&self) -> Vec<(&str, &Value)> {
+        visible(self.scopes)
+    }
+}
+
+/// Called around each statement, for tools like the debugger and tracer
+pub trait Hook {
+    /// An error stops the program with that message
+    fn before_statement(&mut self, pause: &Pause) -> Result<(), String>;
+
+    /// Called once the statement has run without an error, which includes
+    /// leaving it by `break`, `continue` or `return`
+    fn after_statement(&mut self, _pause: &Pause) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// The variables in `scopes` by name, inner scopes hiding outer ones
+fn visible(scopes: &[HashMap<String, Value>]) -> Vec<(&str, &Value)> {
+    let mut visible: HashMap<&str, &Value> = HashMap::new();
+    // Inner scopes come later and shadow outer ones
+    for scope in scopes {
+        for (name, val) in scope {
+            visible.insert(name, val);
+        }
+    }
+    let mut variables: Vec<_> = visible.into_iter().collect();
+    variables.sort_by_key(|(name, _)| *name);
+    variables
+}
+
+/// A declared function
+struct Function {
+    params: Vec<Param>,
+
+    /// The annotated type of the result
+    result: Option<Type>,
+
+    body: NodeId,
+}
+
+/// Tree-walking interpreter that executes a program directly
+pub struct Interpreter<W: Write = Stdout> {
+    /// Runtime values of the variables in each enclosing scope, innermost last
+    scopes: Vec<HashMap<String, Value>>,
+
+    /// Where `print` writes to
+    out: W,
+
+    /// Where `input` reads from, stdin when unset
+    input: Option<Box<dyn BufRead>>,
+
+    /// The fields of each declared struct, in order
+    structs: HashMap<String, Vec<String>>,
+
+    /// Declared functions by name
+    functions: HashMap<String, Rc<Function>>,
+
+    /// Number of calls being run
+    call_depth: usize,
+
+    /// How many calls may be run at once
+    max_call_depth: usize,
+
+    /// Optional checks on what the program does
+    checks: Checks,
+
+    /// Where `rand()` gets its numbers
+    rng: Rng,
+
+    /// The arguments `args()` gives the program
+    arguments: Vec<String>,
+
+    /// Told about each statement before it runs
+    hook: Option<Box<dyn Hook>>,
+
+    /// The checked program, for the types `typeof()` gives, analyzed the
+    /// first time one is needed
+    checked: Option<Option<SemanticAnalyzer>>,
+}
+
+impl Interpreter {
+    /// Create an interpreter that prints to stdout
+    pub fn new() -> Self {
+        Self::with_output(io::stdout())
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Write> Interpreter<W> {
+    /// Create an interpreter that prints to the given writer
+    pub fn with_output(out: W) -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+            out,
+            input: None,
+            structs: HashMap::new(),
+            functions: HashMap::new(),
+            call_depth: 0,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            checks: Checks::default(),
+            rng: Rng::default(),
+            arguments: Vec::new(),
+            hook: None,
+            checked: None,
+        }
+    }
+
+    /// Fail calls nested deeper than `depth`. Every call uses some of the
+    /// Rust stack, so a deep limit needs a thread with a big one.
+    pub fn with_max_call_depth(mut self, depth: usize) -> Self {
+        self.max_call_depth = depth;
+        self
+    }
+
+    /// Make the given checks as the program runs
+    pub fn with_checks(mut self, checks: Checks) -> Self {
+        self.checks = checks;
+        self
+    }
+
+    /// Give the program `arguments`, like the ones after `--` on the
+    /// command line
+    pub fn with_args(mut self, arguments: Vec<String>) -> Self {
+        self.arguments = arguments;
+        self
+    }
+
+    /// Call `hook` before running each statement
+    pub fn with_hook(mut self, hook: impl Hook + 'static) -> Self {
+        self.hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Read `input` statements from the given reader instead of stdin
+    pub fn with_input(mut self, input: impl BufRead + 'static) -> Self {
+        self.input = Some(Box::new(input));
+        self
+    }
+
+    /// Consume the interpreter, returning its output writer
+    pub fn into_output(self) -> W {
+        self.out
+    }
+
+    /// The writer `print` writes to
+    pub fn output(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    /// All visible variables and their values, sorted by name
+    pub fn variables(&self) -> Vec<(&str, &Value)> {
+        visible(&self.scopes)
+    }
+
+    /// Run a statement of `ast`, or the whole program from its root
+    pub fn run(&mut self, ast: &Ast, node: NodeId) -> Result<(), CompileError> {
+        self.execute(ast, node).map(|_| ())
+    }
+
+    fn execute(&mut self, ast: &Ast, node: NodeId) -> Result<Flow, CompileError> {
+        // Blocks and programs are run statement by statement
+        let statement = &ast[node];
+        if self.hook.is_none() || statement.span() == Span::default() {
+            return self.execute_statement(ast, node);
+        }
+        self.call_hook(ast, statement, |hook, pause| hook.before_statement(pause))?;
+        let flow = self.execute_statement(ast, node)?;
+        self.call_hook(ast, statement, |hook, pause| hook.after_statement(pause))?;
+        Ok(flow)
+    }
+
+    /// Tell the hook about `statement`, turning its error into a runtime
+    /// error
+    fn call_hook(
+        &mut self,
+        ast: &Ast,
+        statement: &ASTNode,
+        call: impl FnOnce(&mut dyn Hook, &Pause) -> Result<(), String>,
+    ) -> Result<(), CompileError> {
+        let Some(mut hook) = self.hook.take() else {
+            return Ok(());
+        };
+        let span = statement.span();
+        let result = call(
+            hook.as_mut(),
+            &Pause {
+                span,
+                depth: self.call_depth,
+                statement,
+                ast,
+                scopes: &self.scopes,
+            },
+        );
+        self.hook = Some(hook);
+        result.map_err(|err| Self::error(err, span))
+    }
+
+    fn execute_statement(&mut self, ast: &Ast, node: NodeId) -> Result<Flow, CompileError> {
+        match &ast[node] {
+            ASTNode::Program(statements) => return self.execute_all(ast, statements),
+            ASTNode::LetDeclaration {
+                name,
+                ty,
+                value,
+                span,
+                ..
+            } => {
+                let val = self.evaluate_expression(ast, *value)?;
+                self.declare(name, promote(*ty == Some(Type::Float), val))
+                    .map_err(|err| Self::error(err, *span))?;
+            }
+            ASTNode::ConstDeclaration { name, value, span } => {
+                let val = self.evaluate_expression(ast, *value)?;
+                self.declare(name, val)
+                    .map_err(|err| Self::error(err, *span))?;
+            }
+            ASTNode::StructDeclaration { name, fields, .. } => {
+                self.structs.insert(name.clone(), fields.clone());
+            }
+            ASTNode::FunctionDeclaration {
+                name,
+                params,
+                result,
+                body,
+                ..
+            } => {
+                let function = Function {
+                    params: params.clone(),
+                    result: result.clone(),
+                    body: *body,
+                };
+                self.functions.insert(name.clone(), Rc::new(function));
+            }
+            ASTNode::Return { value, .. } => {
+                let val = match value {
+                    Some(value) => Some(self.evaluate_expression(ast, *value)?),
+                    None => None,
+                };
+                return Ok(Flow::Return(val));
+            }
+            ASTNode::Assignment { name, value, span } => {
+                let val = self.evaluate_expression(ast, *value)?;
+                self.assign(name, val)
+                    .map_err(|err| Self::error(err, *span))?;
+            }
+            ASTNode::CompoundAssignment {
+                name,
+                op,
+                value,
+                span,
+            } => {
+                let val = self.evaluate_expression(ast, *value)?;
+                let current = self.lookup(name).ok_or_else(|| {
+                    Self::error(format!("Variable '{}' is not declared", name), *span)
+                })?;
+                let result = self
+                    .checks
+                    .arithmetic(op, current, &val)
+                    .map_err(|err| Self::error(err, *span))?;
+                self.assign(name, result)
+                    .map_err(|err| Self::error(err, *span))?;
+            }
+            ASTNode::Increment { name, span } => {
+                self.update(name, BinaryOperator::Plus)
+                    .map_err(|err| Self::error(err, *span))?;
+            }
+            ASTNode::Decrement { name, span } => {
+                self.update(name, BinaryOperator::Minus)
+                    .map_err(|err| Self::error(err, *span))?;
+            }
+            ASTNode::Input { name, span } => {
+                let current = self.lookup(name).cloned().ok_or_else(|| {
+                    Self::error(format!("Variable '{}' is not declared", name), *span)
+                })?;
+                let val =
+                    read_from(&mut self.input, &current).map_err(|err| Self::error(err, *span))?;
+                self.assign(name, val)
+                    .map_err(|err| Self::error(err, *span))?;
+            }
+            ASTNode::IndexAssignment {
+                array,
+                index,
+                value,
+                span,
+            } => {
+                let array = self.evaluate_expression(ast, *array)?;
+                let index = self.evaluate_expression(ast, *index)?;
+                let val = self.evaluate_expression(ast, *value)?;
+                set_index(&array, &index, val).map_err(|err| Self::error(err, *span))?;
+            }
+            ASTNode::FieldAssignment {
+                object,
+                field,
+                value,
+                span,
+            } => {
+                let object = self.evaluate_expression(ast, *object)?;
+                let val = self.evaluate_expression(ast, *value)?;
+                set_field(&object, field, val).map_err(|err| Self::error(err, *span))?;
+            }
+            ASTNode::Call { name, args, span } => {
+                self.call(ast, name, args, *span)?;
+            }
+            ASTNode::Print {
+                format,
+                args,
+                newline,
+                span,
+            } => {
+                let values = args
+                    .iter()
+                    .map(|arg| self.evaluate_expression(ast, *arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let pieces = format::print_pieces(format.as_deref(), values.len())
+                    .map_err(|err| Self::error(err, *span))?;
+                let mut text = format::render(&pieces, &values);
+                if *newline {
+                    text.push('\n');
+                }
+                write!(self.out, "{}", text).map_err(|err| Self::error(err.to_string(), *span))?;
+            }
+            ASTNode::Assert {
+                condition,
+                message,
+                span,
+            } => {
+                if !self.evaluate_expression(ast, *condition)?.is_truthy() {
+                    return Err(Self::error(assertion_failed(message.as_deref()), *span));
+                }
+            }
+            ASTNode::Block(statements) => {
+                self.begin_scope();
+                let result = self.execute_all(ast, statements);
+                self.end_scope();
+                return result;
+            }
+            ASTNode::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                if self.evaluate_expression(ast, *condition)?.is_truthy() {
+                    return self.execute(ast, *then_branch);
+                } else if let Some(else_branch) = else_branch {
+                    return self.execute(ast, *else_branch);
+                }
+            }
+            ASTNode::While {
+                condition,
+                body,
+                span,
+            } => {
+                let mut iterations = 0;
+                while self.evaluate_expression(ast, *condition)?.is_truthy() {
+                    Self::guard_loop(&mut iterations).map_err(|err| Self::error(err, *span))?;
+                    match self.execute(ast, *body)? {
+                        Flow::Break => break,
+                        flow @ Flow::Return(_) => return Ok(flow),
+                        Flow::Normal | Flow::Continue => {}
+                    }
+                }
+            }
+            ASTNode::DoWhile {
+                body,
+                condition,
+                span,
+            } => {
+                let mut iterations = 0;
+                loop {
+                    Self::guard_loop(&mut iterations).map_err(|err| Self::error(err, *span))?;
+                    match self.execute(ast, *body)? {
+                        Flow::Break => break,
+                        flow @ Flow::Return(_) => return Ok(flow),
+                        Flow::Normal | Flow::Continue => {}
+                    }
+                    if !self.evaluate_expression(ast, *condition)?.is_truthy() {
+                        break;
+                    }
+                }
+            }
+            ASTNode::For {
+                init,
+                condition,
+                step,
+                body,
+                span,
+            } => {
+                // Variables declared by the initializer are scoped to the loop
+                self.begin_scope();
+                let result = self.run_for(ast, init, condition, step, *body, *span);
+                self.end_scope();
+                return result;
+            }
+            ASTNode::ForIn {
+                name,
+                range,
+                body,
+                span,
+            } => {
+                let ASTNode::Range {
+                    start,
+                    end,
+                    inclusive,
+                    ..
+                } = ast[*range]
+                else {
+                    unreachable!("the parser only puts ranges in for-in loops");
+                };
+                let start = self.evaluate_int(ast, start)?;
+                let end = self.evaluate_int(ast, end)?;
+                // `start..end` is `start..=end - 1`, and empty when that underflows
+                let last = if inclusive {
+                    Some(end)
+                } else {
+                    end.checked_sub(1)
+                };
+                let mut iterations = 0;
+                for i in last.into_iter().flat_map(|last| start..=last) {
+                    Self::guard_loop(&mut iterations).map_err(|err| Self::error(err, *span))?;
+                    self.begin_scope();
+                    let flow = self
+                        .declare(name, Value::Int(i))
+                        .map_err(|err| Self::error(err, *span))
+                        .and_then(|()| self.execute(ast, *body));
+                    self.end_scope();
+                    match flow? {
+                        Flow::Break => break,
+                        flow @ Flow::Return(_) => return Ok(flow),
+                        Flow::Normal | Flow::Continue => {}
+                    }
+                }
+            }
+            ASTNode::Break { .. } => return Ok(Flow::Break),
+            ASTNode::Continue { .. } => return Ok(Flow::Continue),
+
+            node => return Err(Self::error("Unexpected AST node".to_string(), node.span())),
+        }
+        Ok(Flow::Normal)
+    }
+
+    /// Run statements in order, stopping early at a `break`, `continue` or
+    /// `return`
+    fn execute_all(&mut self, ast: &Ast, statements: &[NodeId]) -> Result<Flow, CompileError> {
+        for stmt in statements {
+            let flow = self.execute(ast, *stmt)?;
+            if flow != Flow::Normal {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn run_for(
+        &mut self,
+        ast: &Ast,
+        init: &Option<NodeId>,
+        condition: &Option<NodeId>,
+        step: &Option<NodeId>,
+        body: NodeId,
+        span: Span,
+    ) -> Result<Flow, CompileError> {
+        if let Some(init) = init {
+            self.execute(ast, *init)?;
+        }
+        let mut iterations = 0;
+        loop {
+            if let Some(condition) = condition {
+                if !self.evaluate_expression(ast, *condition)?.is_truthy() {
+                    break;
+                }
+            }
+            Self::guard_loop(&mut iterations).map_err(|err| Self::error(err, span))?;
+            match self.execute(ast, body)? {
+                Flow::Break => break,
+                flow @ Flow::Return(_) => return Ok(flow),
+                Flow::Normal | Flow::Continue => {}
+            }
+            if let Some(step) = step {
+                self.execute(ast, *step)?;
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    /// Evaluate a range bound, which the analyzer has checked is an int
+    fn evaluate_int(&mut self, ast: &Ast, expr: NodeId) -> Result<i64, CompileError> {
+        match self.evaluate_expression(ast, expr)? {
+            Value::Int(num) => Ok(num),
+            other => Err(Self::error(
+                format!("Range bounds must be ints, found {}", other.type_name()),
+                ast[expr].span(),
+            )),
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Declare a variable in the innermost scope
+    fn declare(&mut self, name: &str, val: Value) -> Result<(), String> {
+        let scope = self
+            .scopes
+            .last_mut()
+            .expect("global scope is never popped");
+        if scope.contains_key(name) {
+            return Err(format!(
+                "Variable '{}' is already declared in this scope",
+                name
+            ));
+        }
+        scope.insert(name.to_string(), val);
+        Ok(())
+    }
+
+    /// Find a variable, searching from the innermost scope outwards
+    fn lookup(&self, name: &str) -> Option<&Value> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    /// Store a new value into an existing variable
+    fn assign(&mut self, name: &str, val: Value) -> Result<(), String> {
+        match self.lookup_mut(name) {
+            // Integers are promoted when stored into a float variable
+            Some(current @ Value::Float(_)) if matches!(val, Value::Int(_)) => {
+                *current = Value::Float(val.as_number()?);
+            }
+            Some(current) if current.type_name() != val.type_name() => {
+                return Err(format!(
+                    "Cannot assign {} to {} variable '{}'",
+                    val.type_name(),
+                    current.type_name(),
+                    name
+                ));
+            }
+            Some(current) => *current = val,
+            None => return Err(format!("Variable '{}' is not declared", name)),
+        }
+        Ok(())
+    }
+
+    /// Add or subtract one from a variable, for `++` and `--`
+    fn update(&mut self, name: &str, op: BinaryOperator) -> Result<(), String> {
+        let checks = self.checks;
+        match self.lookup_mut(name) {
+            Some(val) => {
+                *val = checks.arithmetic(&op, val, &Value::Int(1))?;
+                Ok(())
+            }
+            None => Err(format!("Variable '{}' is not declared", name)),
+        }
+    }
+
+    fn error(message: String, span: Span) -> CompileError {
+        CompileError::Runtime(RuntimeError::new(message, span))
+    }
+
+    /// Record that a runtime error unwound through a call
+    fn unwind(err: CompileError, function: Option<&str>, span: Span) -> CompileError {
+        match err {
+            CompileError::Runtime(mut err) => {
+                err.stack.push(Frame {
+                    function: function.map(str::to_string),
+                    span,
+                });
+                CompileError::Runtime(err)
+            }
+            other => other,
+        }
+    }
+
+    fn lookup_mut(&mut self, name: &str) -> Option<&mut Value> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+    }
+
+    /// Count a loop iteration and fail once the limit is exceeded
+    fn guard_loop(iterations: &mut usize) -> Result<(), String> {
+        *iterations += 1;
+        if *iterations > MAX_LOOP_ITERATIONS {
+            return Err(format!(
+                "Loop exceeded {} iterations, possible infinite loop",
+                MAX_LOOP_ITERATIONS
+            ));
+        }
+        Ok(())
+    }
+
+    /// Call a lambda held by a variable, a function or a builtin, in that
+    /// order, returning its result if it has one
+    pub(crate) fn call(
+        &mut self,
+        ast: &Ast,
+        name: &str,
+        args: &[NodeId],
+        span: Span,
+    ) -> Result<Option<Value>, CompileError> {
+        if let (Some(Builtin::TypeOf), [arg]) = (Builtin::from_name(name), args) {
+            if self.lookup(name).is_none() && !self.functions.contains_key(name) {
+                if let Some(ty) = self.checked_type(ast, *arg) {
+                    return Ok(Some(Value::Str(ty.to_string())));
+                }
+            }
+        }
+        let args = args
+            .iter()
+            .map(|arg| self.evaluate_expression(ast, *arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(callee) = self.lookup(name) {
+            let callee = callee.clone();
+            return self.call_value(ast, name, &callee, args, span);
+        }
+        if let Some(function) = self.functions.get(name).cloned() {
+            return self.call_function(ast, name, &function, args, span);
+        }
+        let builtin = Builtin::from_name(name)
+            .ok_or_else(|| Self::error(format!("Unknown function '{}'", name), span))?;
+        match (builtin, &args[..]) {
+            (Builtin::Exit, _) => {
+                // Unwinds like an error, through every call and loop being run
+                let code = builtins::exit_code(&args).map_err(|err| Self::error(err, span))?;
+                Err(CompileError::Exit { code, span })
+            }
+            (Builtin::Error, _) => Err(Self::error(builtins::error_message(&args), span)),
+            (builtin, _) if builtin.reads_arguments() => {
+                builtins::read_arguments(builtin, &self.arguments, &args)
+                    .map(Some)
+                    .map_err(|err| Self::error(err, span))
+            }
+            (Builtin::Apply, [callee, args @ ..]) => {
+                self.call_value(ast, name, callee, args.to_vec(), span)
+            }
+            (Builtin::Map, [callee, Value::Array(elements)]) => {
+                let elements = elements.borrow().elements.clone();
+                let mut results = Vec::with_capacity(elements.len());
+                for element in elements {
+                    let result = self.call_value(ast, name, callee, vec![element], span)?;
+                    results.push(result.expect("map() is only given functions with a result"));
+                }
+                Ok(Some(array(results)))
+            }
+            _ => builtin
+                .call(&args, &mut self.rng)
+                .map_err(|err| Self::error(err, span)),
+        }
+    }
+
+    /// The type the semantic analyzer found for an expression, if the
+    /// program being run could be checked as a whole
+    fn checked_type(&mut self, ast: &Ast, expr: NodeId) -> Option<Type> {
+        self.checked
+            .get_or_insert_with(|| SemanticAnalyzer::checked(ast))
+            .as_ref()?
+            .type_of(&ast[expr])
+            .cloned()
+    }
+
+    /// Call a closure or a function held by a value. `name` is what the
+    /// value was called through, for the error when it isn't a function.
+    fn call_value(
+        &mut self,
+        ast: &Ast,
+        name: &str,
+        callee: &Value,
+        args: Vec<Value>,
+        span: Span,
+    ) -> Result<Option<Value>, CompileError> {
+        match callee {
+            Value::Closure(closure) => self.call_closure(ast, closure, args, span),
+            Value::Function(function) => {
+                let name = function;
+                let function = self.functions[name].clone();
+                self.call_function(ast, name, &function, args, span)
+            }
+            other => Err(Self::error(
+                format!("'{}' is {}, not a function", name, other.type_name()),
+                span,
+            )),
+        }
+    }
+
+    /// Run the body of a function with its parameters bound to `args`. The
+    /// body only sees its own variables, so the caller's are set aside.
+    fn call_function(
+        &mut self,
+        ast: &Ast,
+        name: &str,
+        function: &Function,
+        args: Vec<Value>,
+        span: Span,
+    ) -> Result<Option<Value>, CompileError> {
+        if self.call_depth >= self.max_call_depth {
+            return Err(Self::error(call_depth_exceeded(self.max_call_depth), span));
+        }
+        // Ints passed or returned where a float is annotated become floats
+        let params = function
+            .params
+            .iter()
+            .zip(args)
+            .map(|(param, arg)| {
+                (
+                    param.name.clone(),
+                    promote(param.ty == Some(Type::Float), arg),
+                )
+            })
+            .collect();
+        let caller = std::mem::replace(&mut self.scopes, vec![params]);
+        self.call_depth += 1;
+        let flow = self.execute(ast, function.body);
+        self.call_depth -= 1;
+        self.scopes = caller;
+        match flow.map_err(|err| Self::unwind(err, Some(name), span))? {
+            Flow::Return(val) => {
+                let floats = function.result == Some(Type::Float);
+                Ok(val.map(|val| promote(floats, val)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Evaluate the body of a lambda with its parameters bound to `args`.
+    /// Like a function's, the body doesn't see the caller's variables, only
+    /// the ones the closure captured.
+    fn call_closure(
+        &mut self,
+        ast: &Ast,
+        closure: &Closure,
+        args: Vec<Value>,
+        span: Span,
+    ) -> Result<Option<Value>, CompileError> {
+        if self.call_depth >= self.max_call_depth {
+            return Err(Self::error(call_depth_exceeded(self.max_call_depth), span));
+        }
+        let ASTNode::Lambda { params, body, .. } = &ast[closure.lambda] else {
+            unreachable!("closures are made from lambdas");
+        };
+        let mut scope: HashMap<_, _> = closure.captures.iter().cloned().collect();
+        for (param, arg) in params.iter().zip(args) {
+            let arg = promote(param.ty == Some(Type::Float), arg);
+            scope.insert(param.name.clone(), arg);
+        }
+        let caller = std::mem::replace(&mut self.scopes, vec![scope]);
+        self.call_depth += 1;
+        // A body that is a call may not give anything back
+        let result = match &ast[*body] {
+            ASTNode::Call { name, args, span } => self.call(ast, name, args, *span),
+            _ => self.evaluate_expression(ast, *body).map(Some),
+        };
+        self.call_depth -= 1;
+        self.scopes = caller;
+        result.map_err(|err| Self::unwind(err, None, span))
+    }
+
+    /// Evaluate an expression to a value
+    pub fn evaluate_expression(&mut self, ast: &Ast, expr: NodeId) -> Result<Value, CompileError> {
+        match &ast[expr] {
+            ASTNode::Int(num) => Ok(Value::Int(*num)),
+            ASTNode::Number(num) => Ok(Value::Float(*num)),
+            ASTNode::Bool(b) => Ok(Value::Bool(*b)),
+            ASTNode::Str(text) => Ok(Value::Str(text.clone())),
+            ASTNode::Char(c) => Ok(Value::Char(*c)),
+            ASTNode::Interpolation { pieces, values, .. } => {
+                let values = values
+                    .iter()
+                    .map(|value| self.evaluate_expression(ast, *value))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Str(format::render(pieces, &values)))
+            }
+            ASTNode::Identifier { name, span } => {
+                if let Some(val) = self.lookup(name) {
+                    Ok(val.clone())
+                } else if self.functions.contains_key(name) {
+                    Ok(Value::Function(name.clone()))
+                } else {
+                    Err(Self::error(
+                        format!("Variable '{}' is not declared", name),
+                        *span,
+                    ))
+                }
+            }
+            ASTNode::BinaryOp {
+                left,
+                op: BinaryOperator::And,
+                right,
+                ..
+            } => {
+                // Short-circuit: the right side only runs when the left is truthy
+                if !self.evaluate_expression(ast, *left)?.is_truthy() {
+                    return Ok(Value::Bool(false));
+                }
+                Ok(Value::Bool(
+                    self.evaluate_expression(ast, *right)?.is_truthy(),
+                ))
+            }
+            ASTNode::BinaryOp {
+                left,
+                op: BinaryOperator::Or,
+                right,
+                ..
+            } => {
+                // Short-circuit: the right side only runs when the left is falsy
+                if self.evaluate_expression(ast, *left)?.is_truthy() {
+                    return Ok(Value::Bool(true));
+                }
+                Ok(Value::Bool(
+                    self.evaluate_expression(ast, *right)?.is_truthy(),
+                ))
+            }
+            ASTNode::BinaryOp {
+                left,
+                op,
+                right,
+                span,
+            } => {
+                let left_val = self.evaluate_expression(ast, *left)?;
+                let right_val = self.evaluate_expression(ast, *right)?;
+                if op.is_comparison() {
+                    compare(op, &left_val, &right_val)
+                } else {
+                    self.checks.arithmetic(op, &left_val, &right_val)
+                }
+                .map_err(|err| Self::error(err, *span))
+            }
+            ASTNode::UnaryOp { op, operand, span } => {
+                let val = self.evaluate_expression(ast, *operand)?;
+                match op {
+                    UnaryOperator::Not => Ok(Value::Bool(!val.is_truthy())),
+                    UnaryOperator::Negate => self
+                        .checks
+                        .negate(&val)
+                        .map_err(|err| Self::error(err, *span)),
+                }
+            }
+            ASTNode::Conditional {
+                condition,
+                then_value,
+                else_value,
+                ..
+            } => {
+                if self.evaluate_expression(ast, *condition)?.is_truthy() {
+                    self.evaluate_expression(ast, *then_value)
+                } else {
+                    self.evaluate_expression(ast, *else_value)
+                }
+            }
+            ASTNode::ArrayLiteral { elements, .. } => {
+                let elements = elements
+                    .iter()
+                    .map(|element| self.evaluate_expression(ast, *element))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(array(elements))
+            }
+            ASTNode::MapLiteral { entries, span } => {
+                let entries = entries
+                    .iter()
+                    .map(|(key, value)| {
+                        Ok((
+                            self.evaluate_expression(ast, *key)?,
+                            self.evaluate_expression(ast, *value)?,
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                map(entries).map_err(|err| Self::error(err, *span))
+            }
+            ASTNode::StructLiteral { name, fields, span } => {
+                let declared = self
+                    .structs
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| Self::error(format!("Unknown struct '{}'", name), *span))?;
+                // Fields are evaluated in the order they're declared
+                let mut values = Vec::new();
+                for field in &declared {
+                    let (_, value) =
+                        fields
+                            .iter()
+                            .find(|(given, _)| given == field)
+                            .ok_or_else(|| {
+                                Self::error(format!("Missing field '{}' in {}", field, name), *span)
+                            })?;
+                    values.push((field.clone(), self.evaluate_expression(ast, *value)?));
+                }
+                Ok(structure(name, values))
+            }
+            ASTNode::FieldAccess {
+                object,
+                field: name,
+                span,
+            } => {
+                let object = self.evaluate_expression(ast, *object)?;
+                field(&object, name).map_err(|err| Self::error(err, *span))
+            }
+            ASTNode::Index {
+                array,
+                index: position,
+                span,
+            } => {
+                let array = self.evaluate_expression(ast, *array)?;
+                let position = self.evaluate_expression(ast, *position)?;
+                index(&array, &position).map_err(|err| Self::error(err, *span))
+            }
+            ASTNode::Slice {
+                array,
+                start,
+                end,
+                span,
+            } => {
+                let array = self.evaluate_expression(ast, *array)?;
+                let start = start
+                    .map(|start| self.evaluate_expression(ast, start))
+                    .transpose()?;
+                let end = end
+                    .map(|end| self.evaluate_expression(ast, end))
+                    .transpose()?;
+                slice(&array, start.as_ref(), end.as_ref()).map_err(|err| Self::error(err, *span))
+            }
+            ASTNode::Call { name, args, span } => self
+                .call(ast, name, args, *span)?
+                .ok_or_else(|| Self::error(format!("{}() doesn't return a value", name), *span)),
+            // Captured variables are copied, so the closure keeps their
+            // values even once they go out of scope
+            ASTNode::Lambda { params, body, .. } => {
+                let captures = parser::free_names(ast, params, *body)
+                    .into_iter()
+                    .filter_map(|name| {
+                        let val = self.lookup(&name)?.clone();
+                        Some((name, val))
+                    })
+                    .collect();
+                Ok(Value::Closure(Rc::new(Closure {
+                    lambda: expr,
+                    captures,
+                })))
+            }
+            // The analyzer only allows this in a chain like `a = b = 0`
+            ASTNode::Assignment { name, span, .. } => {
+                self.execute_statement(ast, expr)?;
+                self.lookup(name).cloned().ok_or_else(|| {
+                    Self::error(format!("Variable '{}' is not declared", name), *span)
+                })
+            }
+            expr => Err(Self::error(
+                "Unexpected expression node".to_string(),
+                expr.span(),
+            )),
+        }
+    }
+}
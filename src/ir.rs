@@ -0,0 +1,1688 @@
+//! Typed intermediate representation between the checked AST and code
+//! generation.
+//!
+//! Expressions are flattened into instructions that each compute one value
+//! into a variable, so nested expressions get explicit temporaries. Every
+//! variable has a known type, and integer-to-float promotions are explicit
+//! [`Instr::IntToFloat`] instructions. Control flow stays structured, so
+//! backends can still emit `if` and loops. Variables have unique names within
+//! their function, so shadowing is already resolved. Constants are replaced
+//! by their values.
+//!
+//! Maps, closures that capture variables, and operators on strings are only
+//! supported by the interpreters for now, so programs using them can't be
+//! lowered. Plain strings can still be stored, passed, printed and given to
+//! builtins, and interpolated strings printed. The semantic analyzer notes
+//! each such construct while checking, so lowering fails with an error for
+//! every one of them rather than just the first.
+//!
+//! The IR is produced by [`SemanticAnalyzer::lower`].
+//!
+//! [`SemanticAnalyzer::lower`]: crate::SemanticAnalyzer::lower
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::builtins::Builtin;
+use crate::format;
+use crate::interpreter::{self, Value};
+use crate::lexer::Span;
+use crate::optimizer;
+use crate::parser::{self, ASTNode, Ast, BinaryOperator, NodeId, Param, UnaryOperator};
+use crate::semantic_analyzer::{Signature, Type};
+
+/// Index of a variable in [`Program::vars`]
+pub type VarId = usize;
+
+const INTERPRETER_ONLY: &str =
+    "Strings and maps are only supported by `osho run` for now, not by compiled targets";
+
+/// A program variable or a temporary
+#[derive(Debug, Clone, PartialEq)]
+pub struct Var {
+    /// Unique name, which starts with `_` for temporaries
+    pub name: String,
+    pub ty: Type,
+
+    /// Whether the variable is assigned after its first assignment.
+    /// Temporaries always are, since a branch may set them.
+    pub mutable: bool,
+}
+
+/// The input of an instruction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    /// A character, held as its code point
+    Char(char),
+    Var(VarId),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instr {
+    /// `dest = value`
+    Copy { dest: VarId, value: Operand },
+
+    /// `dest = "text"`, a string a builtin is called with
+    Text { dest: VarId, text: String },
+
+    /// `dest = left op right`. Both operands have the same type; arithmetic
+    /// results have it too, and comparisons give a bool.
+    Binary {
+        dest: VarId,
+        op: BinaryOperator,
+        left: Operand,
+        right: Operand,
+    },
+
+    /// `dest = op operand`
+    Unary {
+        dest: VarId,
+        op: UnaryOperator,
+        operand: Operand,
+    },
+
+    /// `dest = value` converted from int to float
+    IntToFloat { dest: VarId, value: Operand },
+
+    /// `dest = value != 0`, or a copy if the value is already a bool
+    Truthy { dest: VarId, value: Operand },
+
+    /// `dest = condition ? then_value : else_value`
+    Select {
+        dest: VarId,
+        condition: Operand,
+        then_value: Operand,
+        else_value: Operand,
+    },
+
+    /// Declare a variable that is first assigned in a nested body, so it
+    /// outlives that body
+    Declare(VarId),
+
+    /// Print the values between pieces of text, which have one more piece
+    /// than there are values, then a newline for `println`
+    Print {
+        pieces: Vec<String>,
+        values: Vec<Operand>,
+        newline: bool,
+    },
+
+    /// `dest =` a number read from input, of the variable's type
+    Input { dest: VarId },
+
+    /// `dest = [elements]`, a new array. The elements have the array's
+    /// element type.
+    Array { dest: VarId, elements: Vec<Operand> },
+
+    /// `dest = array[index]`, or the character of a string at `index`, failing
+    /// if the index is out of bounds
+    Index {
+        dest: VarId,
+        array: Operand,
+        index: Operand,
+    },
+
+    /// `dest = array[start:end]`, a new array holding a copy of the elements
+    /// from `start` up to `end`, or part of a string, failing if the bounds
+    /// are out of range
+    Slice {
+        dest: VarId,
+        array: Operand,
+        start: Operand,
+        end: Operand,
+    },
+
+    /// `array[index] = value`, failing if the index is out of bounds. The
+    /// value has the array's element type.
+    SetIndex {
+        array: Operand,
+        index: Operand,
+        value: Operand,
+    },
+
+    /// `dest = Name { fields }`, a new struct of the variable's type, with
+    /// the field values in declaration order
+    Struct { dest: VarId, fields: Vec<Operand> },
+
+    /// `dest = object.field`
+    GetField {
+        dest: VarId,
+        object: Operand,
+        field: String,
+    },
+
+    /// `object.field = value`. The value has the field's type.
+    SetField {
+        object: Operand,
+        field: String,
+        value: Operand,
+    },
+
+    /// `dest = builtin(args)`, without a `dest` for builtins that don't
+    /// return a value
+    Call {
+        dest: Option<VarId>,
+        builtin: Builtin,
+        args: Vec<Operand>,
+    },
+
+    /// `dest = function(args)`, a call to one of [`Program::functions`],
+    /// without a `dest` when the result isn't used or there is none
+    CallFunction {
+        dest: Option<VarId>,
+        function: String,
+        args: Vec<Operand>,
+    },
+
+    /// `dest = function`, a pointer to one of [`Program::functions`]
+    FunctionRef { dest: VarId, function: String },
+
+    /// `dest = callee(args)`, a call through a function value, without a
+    /// `dest` when the result isn't used or there is none
+    CallValue {
+        dest: Option<VarId>,
+        callee: Operand,
+        args: Vec<Operand>,
+    },
+
+    /// Leave the function, giving back the value if it returns one
+    Return(Option<Operand>),
+
+    If {
+        condition: Operand,
+        then_body: Vec<Instr>,
+        else_body: Vec<Instr>,
+    },
+
+    /// Run `header`, stop if `condition` is false, then run `body` and
+    /// `step`, and repeat
+    Loop {
+        header: Vec<Instr>,
+        condition: Operand,
+        body: Vec<Instr>,
+        step: Vec<Instr>,
+    },
+
+    /// Run `body` with `var` set to each int from `start` up to `end`, and
+    /// including `end` when `inclusive`. The bounds don't change while the
+    /// loop runs.
+    ForRange {
+        var: VarId,
+        start: Operand,
+        end: Operand,
+        inclusive: bool,
+        body: Vec<Instr>,
+    },
+
+    /// Stop the program with a runtime error, printing the message: the
+    /// pieces of text with the values between them, like a print
+    Fail {
+        pieces: Vec<String>,
+        values: Vec<Operand>,
+    },
+
+    /// Leave the innermost loop
+    Break,
+
+    /// Skip the rest of the innermost loop's body, going on with its `step`
+    Continue,
+}
+
+/// A struct type with the types of its fields, in declaration order
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructDef {
+    pub name: String,
+    pub fields: Vec<(String, Type)>,
+}
+
+/// A function that is called, with its parameters as variables
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<VarId>,
+    pub result: Option<Type>,
+    pub body: Vec<Instr>,
+}
+
+/// A lowered program: its structs and functions, its variables and the
+/// instructions of its body. Functions that are never called are left out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Program {
+    pub structs: Vec<StructDef>,
+    pub functions: Vec<Function>,
+    pub vars: Vec<Var>,
+    pub body: Vec<Instr>,
+}
+
+impl Program {
+    /// The definition of a struct the program constructs
+    pub fn struct_def(&self, name: &str) -> Option<&StructDef> {
+        self.structs.iter().find(|def| def.name == name)
+    }
+
+    pub fn type_of(&self, operand: Operand) -> Type {
+        match operand {
+            Operand::Int(_) => Type::Int,
+            Operand::Float(_) => Type::Float,
+            Operand::Bool(_) => Type::Bool,
+            Operand::Char(_) => Type::Char,
+            Operand::Var(id) => self.vars[id].ty.clone(),
+        }
+    }
+
+    /// Display an operand, using variable names
+    pub fn operand(&self, operand: Operand) -> String {
+        match operand {
+            // C reads 9223372036854775808 before negating it, which is too
+            // big for a long
+            Operand::Int(i64::MIN) => "(-9223372036854775807L - 1)".to_string(),
+            Operand::Int(num) => num.to_string(),
+            // Debug formatting keeps the '.0' so floats don't look like integers
+            Operand::Float(num) => format!("{:?}", num),
+            Operand::Bool(b) => b.to_string(),
+            // A C character constant where it reads as one
+            Operand::Char(c) if (c.is_ascii_graphic() || c == ' ') && c != '\'' && c != '\\' => {
+                format!("'{}'", c)
+            }
+            Operand::Char(c) => (c as u32).to_string(),
+            Operand::Var(id) => self.vars[id].name.clone(),
+        }
+    }
+
+    fn write_body(&self, f: &mut fmt::Formatter, body: &[Instr], depth: usize) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+        for instr in body {
+            write!(f, "{}", indent)?;
+            match instr {
+                Instr::Copy { dest, value } => {
+                    writeln!(f, "{} = {}", self.def(*dest), self.operand(*value))?
+                }
+                Instr::Text { dest, text } => writeln!(f, "{} = {:?}", self.def(*dest), text)?,
+                Instr::Binary {
+                    dest,
+                    op,
+                    left,
+                    right,
+                } => writeln!(
+                    f,
+                    "{} = {} {} {}",
+                    self.def(*dest),
+                    self.operand(*left),
+                    op.symbol(),
+                    self.operand(*right)
+                )?,
+                Instr::Unary { dest, op, operand } => writeln!(
+                    f,
+                    "{} = {}{}",
+                    self.def(*dest),
+                    op.symbol(),
+                    self.operand(*operand)
+                )?,
+                Instr::IntToFloat { dest, value } => {
+                    writeln!(f, "{} = float {}", self.def(*dest), self.operand(*value))?
+                }
+                Instr::Truthy { dest, value } => {
+                    writeln!(f, "{} = truthy {}", self.def(*dest), self.operand(*value))?
+                }
+                Instr::Select {
+                    dest,
+                    condition,
+                    then_value,
+                    else_value,
+                } => writeln!(
+                    f,
+                    "{} = {} ? {} : {}",
+                    self.def(*dest),
+                    self.operand(*condition),
+                    self.operand(*then_value),
+                    self.operand(*else_value)
+                )?,
+                Instr::Declare(id) => writeln!(f, "declare {}", self.def(*id))?,
+                Instr::Print {
+                    pieces,
+                    values,
+                    newline,
+                } => {
+                    let keyword = if *newline { "println" } else { "print" };
+                    let mut args: Vec<_> =
+                        values.iter().map(|value| self.operand(*value)).collect();
+                    if *pieces != format::separators(values.len()) {
+                        args.insert(0, format!("{:?}", format::unparse(pieces)));
+                    }
+                    if args.is_empty() {
+                        writeln!(f, "{}", keyword)?
+                    } else {
+                        writeln!(f, "{} {}", keyword, args.join(", "))?
+                    }
+                }
+                Instr::Input { dest } => writeln!(f, "{} = input", self.def(*dest))?,
+                Instr::Array { dest, elements } => {
+                    writeln!(f, "{} = [{}]", self.def(*dest), self.operands(elements))?
+                }
+                Instr::Index { dest, array, index } => writeln!(
+                    f,
+                    "{} = {}[{}]",
+                    self.def(*dest),
+                    self.operand(*array),
+                    self.operand(*index)
+                )?,
+                Instr::Slice {
+                    dest,
+                    array,
+                    start,
+                    end,
+                } => writeln!(
+                    f,
+                    "{} = {}[{}:{}]",
+                    self.def(*dest),
+                    self.operand(*array),
+                    self.operand(*start),
+                    self.operand(*end)
+                )?,
+                Instr::SetIndex {
+                    array,
+                    index,
+                    value,
+                } => writeln!(
+                    f,
+                    "{}[{}] = {}",
+                    self.operand(*array),
+                    self.operand(*index),
+                    self.operand(*value)
+                )?,
+                Instr::Struct { dest, fields } => {
+                    let ty = &self.vars[*dest].ty;
+                    let def = match ty {
+                        Type::Struct(name) => self.struct_def(name),
+                        _ => None,
+                    };
+                    let fields: Vec<_> = def
+                        .map(|def| &def.fields[..])
+                        .unwrap_or_default()
+                        .iter()
+                        .zip(fields)
+                        .map(|((name, _), value)| format!("{}: {}", name, self.operand(*value)))
+                        .collect();
+                    writeln!(
+                        f,
+                        "{} = {} {{ {} }}",
+                        self.def(*dest),
+                        ty,
+                        fields.join(", ")
+                    )?
+                }
+                Instr::GetField {
+                    dest,
+                    object,
+                    field,
+                } => writeln!(
+                    f,
+                    "{} = {}.{}",
+                    self.def(*dest),
+                    self.operand(*object),
+                    field
+                )?,
+                Instr::SetField {
+                    object,
+                    field,
+                    value,
+                } => writeln!(
+                    f,
+                    "{}.{} = {}",
+                    self.operand(*object),
+                    field,
+                    self.operand(*value)
+                )?,
+                Instr::Call {
+                    dest: Some(dest),
+                    builtin,
+                    args,
+                } => writeln!(
+                    f,
+                    "{} = {}({})",
+                    self.def(*dest),
+                    builtin,
+                    self.operands(args)
+                )?,
+                Instr::Call {
+                    dest: None,
+                    builtin,
+                    args,
+                } => writeln!(f, "{}({})", builtin, self.operands(args))?,
+                Instr::CallFunction {
+                    dest: Some(dest),
+                    function,
+                    args,
+                } => writeln!(
+                    f,
+                    "{} = {}({})",
+                    self.def(*dest),
+                    function,
+                    self.operands(args)
+                )?,
+                Instr::CallFunction {
+                    dest: None,
+                    function,
+                    args,
+                } => writeln!(f, "{}({})", function, self.operands(args))?,
+                Instr::FunctionRef { dest, function } => {
+                    writeln!(f, "{} = fn {}", self.def(*dest), function)?
+                }
+                Instr::CallValue {
+                    dest: Some(dest),
+                    callee,
+                    args,
+                } => writeln!(
+                    f,
+                    "{} = {}({})",
+                    self.def(*dest),
+                    self.operand(*callee),
+                    self.operands(args)
+                )?,
+                Instr::CallValue {
+                    dest: None,
+                    callee,
+                    args,
+                } => writeln!(f, "{}({})", self.operand(*callee), self.operands(args))?,
+                Instr::Return(Some(value)) => writeln!(f, "return {}", self.operand(*value))?,
+                Instr::Return(None) => writeln!(f, "return")?,
+                Instr::If {
+                    condition,
+                    then_body,
+                    else_body,
+                } => {
+                    writeln!(f, "if {} {{", self.operand(*condition))?;
+                    self.write_body(f, then_body, depth + 1)?;
+                    if !else_body.is_empty() {
+                        writeln!(f, "{}}} else {{", indent)?;
+                        self.write_body(f, else_body, depth + 1)?;
+                    }
+                    writeln!(f, "{}}}", indent)?;
+                }
+                Instr::Loop {
+                    header,
+                    condition,
+                    body,
+                    step,
+                } => {
+                    writeln!(f, "loop {{")?;
+                    self.write_body(f, header, depth + 1)?;
+                    writeln!(f, "{}  while {}", indent, self.operand(*condition))?;
+                    self.write_body(f, body, depth + 1)?;
+                    if !step.is_empty() {
+                        writeln!(f, "{}step:", indent)?;
+                        self.write_body(f, step, depth + 1)?;
+                    }
+                    writeln!(f, "{}}}", indent)?;
+                }
+                Instr::ForRange {
+                    var,
+                    start,
+                    end,
+                    inclusive,
+                    body,
+                } => {
+                    writeln!(
+                        f,
+                        "for {} in {}{}{} {{",
+                        self.def(*var),
+                        self.operand(*start),
+                        if *inclusive { "..=" } else { ".." },
+                        self.operand(*end)
+                    )?;
+                    self.write_body(f, body, depth + 1)?;
+                    writeln!(f, "{}}}", indent)?;
+                }
+                Instr::Fail { pieces, values } => {
+                    let mut args = vec![format!("{:?}", format::unparse(pieces))];
+                    args.extend(values.iter().map(|value| self.operand(*value)));
+                    writeln!(f, "fail {}", args.join(", "))?
+                }
+                Instr::Break => writeln!(f, "break")?,
+                Instr::Continue => writeln!(f, "continue")?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Display a list of operands separated by commas
+    pub fn operands(&self, operands: &[Operand]) -> String {
+        operands
+            .iter()
+            .map(|operand| self.operand(*operand))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// A variable being assigned, with its type
+    fn def(&self, id: VarId) -> String {
+        format!("{}: {}", self.vars[id].name, self.vars[id].ty)
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for def in &self.structs {
+            let fields: Vec<_> = def
+                .fields
+                .iter()
+                .map(|(name, ty)| format!("{}: {}", name, ty))
+                .collect();
+            writeln!(f, "struct {} {{ {} }}", def.name, fields.join(", "))?;
+        }
+        for function in &self.functions {
+            let params: Vec<_> = function.params.iter().map(|id| self.def(*id)).collect();
+            write!(f, "fn {}({})", function.name, params.join(", "))?;
+            if let Some(result) = &function.result {
+                write!(f, " -> {}", result)?;
+            }
+            writeln!(f, " {{")?;
+            self.write_body(f, &function.body, 1)?;
+            writeln!(f, "}}")?;
+        }
+        self.write_body(f, &self.body, 0)
+    }
+}
+
+/// Lowers a checked AST into a [`Program`]
+pub(crate) struct Lowerer<'a> {
+    ast: &'a Ast,
+
+    program: Program,
+
+    /// Instructions of the body being lowered, innermost last
+    bodies: Vec<Vec<Instr>>,
+
+    /// Variables visible in each enclosing scope, innermost last
+    scopes: Vec<HashMap<String, Operand>>,
+
+    /// Every name given to a variable so far in the function being lowered,
+    /// or in the program body
+    names: HashSet<String>,
+
+    /// Field names of each declared struct, in declaration order
+    struct_fields: HashMap<String, Vec<String>>,
+
+    /// Types of the fields of the structs that are constructed, from the
+    /// semantic analyzer. A function may read fields before the program
+    /// body constructs the struct, so they can't wait for its first literal.
+    struct_types: HashMap<String, Vec<(String, Type)>>,
+
+    /// Types of the functions that are called, from the semantic analyzer
+    signatures: HashMap<String, Signature>,
+
+    /// Types of the lambdas that capture nothing, which are lifted into
+    /// functions, from the semantic analyzer
+    lambdas: HashMap<NodeId, Signature>,
+
+    /// Types of the checked expressions by span, from the semantic analyzer
+    types: HashMap<Span, Type>,
+
+    /// The result type of the function being lowered
+    result: Option<Type>,
+
+    temps: usize,
+}
+
+impl<'a> Lowerer<'a> {
+    pub(crate) fn new(
+        ast: &'a Ast,
+        struct_types: HashMap<String, Vec<(String, Type)>>,
+        signatures: HashMap<String, Signature>,
+        lambdas: HashMap<NodeId, Signature>,
+        types: HashMap<Span, Type>,
+    ) -> Self {
+        Self {
+            ast,
+            program: Program::default(),
+            bodies: vec![Vec::new()],
+            scopes: vec![HashMap::new()],
+            names: HashSet::new(),
+            struct_fields: HashMap::new(),
+            struct_types,
+            signatures,
+            lambdas,
+            types,
+            result: None,
+            temps: 0,
+        }
+    }
+
+    /// Lower a program the semantic analyzer has accepted
+    pub(crate) fn lower(mut self) -> Result<Program, String> {
+        self.statement(self.ast.root())?;
+        self.program.body = self.bodies.pop().expect("program body is never popped");
+        Ok(self.program)
+    }
+
+    fn statement(&mut self, node: NodeId) -> Result<(), String> {
+        let ast = self.ast;
+        match &ast[node] {
+            ASTNode::Program(statements) => {
+                for stmt in statements {
+                    self.statement(*stmt)?;
+                }
+            }
+            ASTNode::Block(statements) => {
+                self.scopes.push(HashMap::new());
+                let result = statements.iter().try_for_each(|stmt| self.statement(*stmt));
+                self.scopes.pop();
+                result?;
+            }
+            ASTNode::LetDeclaration {
+                name,
+                mutable,
+                ty,
+                value,
+                ..
+            } => {
+                let value = self.expression(*value)?;
+                let ty = ty.clone().unwrap_or_else(|| self.program.type_of(value));
+                let value = self.convert(value, &ty);
+                let dest = self.declare(name, ty, *mutable);
+                self.emit(Instr::Copy { dest, value });
+            }
+            ASTNode::ConstDeclaration { name, value, .. } => {
+                let constant = |name: &str| match self.resolve(name) {
+                    Ok(Operand::Int(num)) => Some(Value::Int(num)),
+                    Ok(Operand::Float(num)) => Some(Value::Float(num)),
+                    Ok(Operand::Bool(b)) => Some(Value::Bool(b)),
+                    Ok(Operand::Char(c)) => Some(Value::Char(c)),
+                    _ => None,
+                };
+                let operand = match optimizer::evaluate_constant(ast, *value, &constant)? {
+                    Value::Int(num) => Operand::Int(num),
+                    Value::Float(num) => Operand::Float(num),
+                    Value::Bool(b) => Operand::Bool(b),
+                    Value::Char(c) => Operand::Char(c),
+                    Value::Array(_) => return Err(format!("Constant '{}' is an array", name)),
+                    Value::Str(_) | Value::Map(_) => return Err(INTERPRETER_ONLY.to_string()),
+                    Value::Struct(_) => return Err(format!("Constant '{}' is a struct", name)),
+                    Value::Closure(_) | Value::Function(_) => {
+                        unreachable!("functions aren't constant expressions")
+                    }
+                };
+                self.bind(name, operand);
+            }
+            ASTNode::Assignment { name, value, .. } => {
+                let dest = self.lookup(name)?;
+                let ty = self.program.vars[dest].ty.clone();
+                match &ast[*value] {
+                    // Computed straight into the variable, so `x += 1` needs
+                    // no temporary
+                    ASTNode::BinaryOp { op, .. }
+                        if !op.is_logical() && self.checked_type(*value)? == ty =>
+                    {
+                        self.binary(*value, Some(dest))?;
+                    }
+                    _ => {
+                        let value = self.expression(*value)?;
+                        let value = self.convert(value, &ty);
+                        self.emit(Instr::Copy { dest, value });
+                    }
+                }
+            }
+            ASTNode::Print {
+                format,
+                args,
+                newline,
+                ..
+            } => {
+                let pieces = format::print_pieces(format.as_deref(), args.len())?;
+                let (pieces, values) = self.print_parts(pieces, args)?;
+                self.emit(Instr::Print {
+                    pieces,
+                    values,
+                    newline: *newline,
+                });
+            }
+            ASTNode::Input { name, .. } => {
+                let dest = self.lookup(name)?;
+                self.emit(Instr::Input { dest });
+            }
+            ASTNode::IndexAssignment {
+                array,
+                index,
+                value,
+                ..
+            } => {
+                let array = self.expression(*array)?;
+                let index = self.expression(*index)?;
+                let value = self.expression(*value)?;
+                let element = Self::element_of(self.program.type_of(array))?;
+                let value = self.convert(value, &element);
+                self.emit(Instr::SetIndex {
+                    array,
+                    index,
+                    value,
+                });
+            }
+            ASTNode::StructDeclaration { name, fields, .. } => {
+                self.struct_fields.insert(name.clone(), fields.clone());
+            }
+            ASTNode::FieldAssignment {
+                object,
+                field,
+                value,
+                ..
+            } => {
+                let object = self.expression(*object)?;
+                let value = self.expression(*value)?;
+                self.emit(Instr::SetField {
+                    object,
+                    field: field.clone(),
+                    value,
+                });
+            }
+            ASTNode::Call { name, args, span } => {
+                self.call(name, args, *span)?;
+            }
+            ASTNode::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let condition = self.expression(*condition)?;
+                let then_body = self.body(|this| this.statement(*then_branch))?;
+                let else_body = match else_branch {
+                    Some(else_branch) => self.body(|this| this.statement(*else_branch))?,
+                    None => Vec::new(),
+                };
+                self.emit(Instr::If {
+                    condition,
+                    then_body,
+                    else_body,
+                });
+            }
+            ASTNode::While {
+                condition, body, ..
+            } => {
+                self.lower_loop(None, Some(*condition), None, *body)?;
+            }
+            ASTNode::Assert {
+                condition,
+                message,
+                span,
+            } => {
+                let condition = self.expression(*condition)?;
+                let message = format!(
+                    "{} (line {})",
+                    interpreter::assertion_failed(message.as_deref()),
+                    span.line
+                );
+                self.emit(Instr::If {
+                    condition,
+                    then_body: Vec::new(),
+                    else_body: vec![Instr::Fail {
+                        pieces: vec![message],
+                        values: Vec::new(),
+                    }],
+                });
+            }
+            ASTNode::DoWhile {
+                body, condition, ..
+            } => {
+                // The condition is checked as the loop's step, which is also
+                // where `continue` goes
+                let body = self.body(|this| this.statement(*body))?;
+                let step = self.body(|this| {
+                    let condition = this.expression(*condition)?;
+                    this.emit(Instr::If {
+                        condition,
+                        then_body: Vec::new(),
+                        else_body: vec![Instr::Break],
+                    });
+                    Ok(())
+                })?;
+                self.emit(Instr::Loop {
+                    header: Vec::new(),
+                    condition: Operand::Bool(true),
+                    body,
+                    step,
+                });
+            }
+            ASTNode::For {
+                init,
+                condition,
+                step,
+                body,
+                ..
+            } => {
+                // The initializer's variables belong to the loop's scope
+                self.scopes.push(HashMap::new());
+                let result = self.lower_loop(*init, *condition, *step, *body);
+                self.scopes.pop();
+                result?;
+            }
+            ASTNode::ForIn {
+                name, range, body, ..
+            } => {
+                let ASTNode::Range {
+                    start,
+                    end,
+                    inclusive,
+                    ..
+                } = ast[*range]
+                else {
+                    unreachable!("the parser only puts ranges in for-in loops");
+                };
+                let start = self.expression(start)?;
+                let mut end = self.expression(end)?;
+                // The body may assign the variable the end came from
+                if let Operand::Var(id) = end {
+                    if self.program.vars[id].mutable {
+                        let copy = self.temp(Type::Int);
+                        self.emit(Instr::Copy {
+                            dest: copy,
+                            value: end,
+                        });
+                        end = Operand::Var(copy);
+                    }
+                }
+                self.scopes.push(HashMap::new());
+                let var = self.declare(name, Type::Int, true);
+                let body = self.body(|this| this.statement(*body));
+                self.scopes.pop();
+                self.emit(Instr::ForRange {
+                    var,
+                    start,
+                    end,
+                    inclusive,
+                    body: body?,
+                });
+            }
+            ASTNode::Break { .. } => self.emit(Instr::Break),
+            ASTNode::Continue { .. } => self.emit(Instr::Continue),
+            ASTNode::FunctionDeclaration {
+                name, params, body, ..
+            } => self.function(name, params, *body)?,
+            ASTNode::Return { value, .. } => {
+                let value = match value {
+                    Some(value) => {
+                        let value = self.expression(*value)?;
+                        let result = self.result.clone().expect("the function returns a value");
+                        Some(self.convert(value, &result))
+                    }
+                    None => None,
+                };
+                self.emit(Instr::Return(value));
+            }
+            _ => return Err("Unexpected AST node".to_string()),
+        }
+        Ok(())
+    }
+
+    fn lower_loop(
+        &mut self,
+        init: Option<NodeId>,
+        condition: Option<NodeId>,
+        step: Option<NodeId>,
+        body: NodeId,
+    ) -> Result<(), String> {
+        if let Some(init) = init {
+            self.statement(init)?;
+        }
+        let mut condition_value = Operand::Bool(true);
+        let header = self.body(|this| {
+            if let Some(condition) = condition {
+                condition_value = this.expression(condition)?;
+            }
+            Ok(())
+        })?;
+        let body = self.body(|this| this.statement(body))?;
+        let step = match step {
+            Some(step) => self.body(|this| this.statement(step))?,
+            None => Vec::new(),
+        };
+        self.emit(Instr::Loop {
+            header,
+            condition: condition_value,
+            body,
+            step,
+        });
+        Ok(())
+    }
+
+    /// Lower an expression, returning the operand holding its value
+    fn expression(&mut self, expr: NodeId) -> Result<Operand, String> {
+        let ast = self.ast;
+        match &ast[expr] {
+            ASTNode::Int(num) => Ok(Operand::Int(*num)),
+            ASTNode::Number(num) => Ok(Operand::Float(*num)),
+            ASTNode::Bool(b) => Ok(Operand::Bool(*b)),
+            ASTNode::Char(c) => Ok(Operand::Char(*c)),
+            ASTNode::Str(text) => Ok(self.text(text)),
+            ASTNode::Interpolation { .. } | ASTNode::MapLiteral { .. } => {
+                Err(INTERPRETER_ONLY.to_string())
+            }
+            // A declared function's name is a value when no variable hides it
+            ASTNode::Identifier { name, .. } if self.resolve(name).is_err() => {
+                let signature = self
+                    .signatures
+                    .get(name)
+                    .ok_or_else(|| format!("Variable '{}' is not declared", name))?;
+                let dest = self.temp(signature.function_type());
+                self.emit(Instr::FunctionRef {
+                    dest,
+                    function: name.clone(),
+                });
+                Ok(Operand::Var(dest))
+            }
+            ASTNode::Identifier { name, .. } => self.resolve(name),
+            ASTNode::BinaryOp {
+                left,
+                op: op @ (BinaryOperator::And | BinaryOperator::Or),
+                right,
+                ..
+            } => {
+                // Short-circuit: the right side is only evaluated when needed
+                let left = self.expression(*left)?;
+                let dest = self.temp(Type::Bool);
+                self.emit(Instr::Truthy { dest, value: left });
+                let rest = self.body(|this| {
+                    let right = this.expression(*right)?;
+                    this.emit(Instr::Truthy { dest, value: right });
+                    Ok(())
+                })?;
+                let (then_body, else_body) = if *op == BinaryOperator::And {
+                    (rest, Vec::new())
+                } else {
+                    (Vec::new(), rest)
+                };
+                self.emit(Instr::If {
+                    condition: Operand::Var(dest),
+                    then_body,
+                    else_body,
+                });
+                Ok(Operand::Var(dest))
+            }
+            ASTNode::BinaryOp { .. } => self.binary(expr, None),
+            ASTNode::Conditional {
+                condition,
+                then_value,
+                else_value,
+                ..
+            } => {
+                let condition = self.expression(*condition)?;
+                let dest = self.temp(self.checked_type(expr)?);
+                let mut values = (Operand::Bool(false), Operand::Bool(false));
+                let mut then_body = self.body(|this| {
+                    values.0 = this.expression(*then_value)?;
+                    Ok(())
+                })?;
+                let mut else_body = self.body(|this| {
+                    values.1 = this.expression(*else_value)?;
+                    Ok(())
+                })?;
+                let (then_value, else_value) = values;
+                // Branches that are only operands become a C ternary, others
+                // need statements so only the chosen one is evaluated
+                if then_body.is_empty() && else_body.is_empty() {
+                    self.emit(Instr::Select {
+                        dest,
+                        condition,
+                        then_value,
+                        else_value,
+                    });
+                } else {
+                    then_body.push(Instr::Copy {
+                        dest,
+                        value: then_value,
+                    });
+                    else_body.push(Instr::Copy {
+                        dest,
+                        value: else_value,
+                    });
+                    self.emit(Instr::Declare(dest));
+                    self.emit(Instr::If {
+                        condition,
+                        then_body,
+                        else_body,
+                    });
+                }
+                Ok(Operand::Var(dest))
+            }
+            ASTNode::UnaryOp { op, operand, .. } => {
+                let operand = self.expression(*operand)?;
+                let dest = self.temp(self.checked_type(expr)?);
+                self.emit(Instr::Unary {
+                    dest,
+                    op: *op,
+                    operand,
+                });
+                Ok(Operand::Var(dest))
+            }
+            ASTNode::ArrayLiteral { elements, .. } => {
+                let elements = elements
+                    .iter()
+                    .map(|element| self.expression(*element))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let types: Vec<_> = elements
+                    .iter()
+                    .map(|element| self.program.type_of(*element))
+                    .collect();
+                let ty = if types.is_empty() {
+                    // Only an annotated declaration can have an empty array
+                    Self::element_of(self.checked_type(expr)?)?
+                } else {
+                    Type::element_type(&types)?
+                };
+                let elements = elements
+                    .into_iter()
+                    .map(|element| self.convert(element, &ty))
+                    .collect();
+                let dest = self.temp(Type::Array(Box::new(ty)));
+                self.emit(Instr::Array { dest, elements });
+                Ok(Operand::Var(dest))
+            }
+            ASTNode::Index { array, index, .. } => {
+                let array = self.expression(*array)?;
+                let index = self.expression(*index)?;
+                let dest = self.temp(Self::element_of(self.program.type_of(array))?);
+                self.emit(Instr::Index { dest, array, index });
+                Ok(Operand::Var(dest))
+            }
+            ASTNode::Slice {
+                array, start, end, ..
+            } => {
+                let array = self.expression(*array)?;
+                let start = match start {
+                    Some(start) => self.expression(*start)?,
+                    None => Operand::Int(0),
+                };
+                let end = match end {
+                    Some(end) => self.expression(*end)?,
+                    None => {
+                        let len = self.temp(Type::Int);
+                        self.emit(Instr::Call {
+                            dest: Some(len),
+                            builtin: Builtin::Len,
+                            args: vec![array],
+                        });
+                        Operand::Var(len)
+                    }
+                };
+                let dest = self.temp(self.program.type_of(array));
+                self.emit(Instr::Slice {
+                    dest,
+                    array,
+                    start,
+                    end,
+                });
+                Ok(Operand::Var(dest))
+            }
+            ASTNode::StructLiteral { name, fields, .. } => self.struct_literal(name, fields),
+            ASTNode::FieldAccess { object, field, .. } => {
+                let object = self.expression(*object)?;
+                let ty = self.field_type(&self.program.type_of(object), field)?;
+                let dest = self.temp(ty);
+                self.emit(Instr::GetField {
+                    dest,
+                    object,
+                    field: field.clone(),
+                });
+                Ok(Operand::Var(dest))
+            }
+            ASTNode::Call { name, args, span } => self
+                .call(name, args, *span)?
+                .ok_or_else(|| format!("{}() doesn't return a value", name)),
+            // C has no closures, and a lambda's environment can't be
+            // flattened into a function yet
+            ASTNode::Lambda { params, body, span } => {
+                let captures: Vec<_> = parser::free_names(ast, params, *body)
+                    .into_iter()
+                    .filter(|name| self.resolve(name).is_ok())
+                    .map(|name| format!("'{}'", name))
+                    .collect();
+                let at = format!("line {}, column {}", span.line, span.column);
+                if !captures.is_empty() {
+                    return Err(format!(
+                        "Closures are only supported by `osho run` for now, not by compiled targets: the closure at {} captures {}",
+                        at,
+                        captures.join(", ")
+                    ));
+                }
+                let signature = self.lambdas.get(&expr).cloned().ok_or_else(|| {
+                    format!(
+                        "The lambda at {} is never called, so the types of its parameters are unknown",
+                        at
+                    )
+                })?;
+                self.lambda(expr, params, *body, signature)
+            }
+            _ => Err("Unexpected expression node".to_string()),
+        }
+    }
+
+    /// Lower a struct literal, evaluating its fields in declaration order.
+    /// The first literal of a struct gives the types of its fields.
+    fn struct_literal(
+        &mut self,
+        name: &str,
+        fields: &[(String, NodeId)],
+    ) -> Result<Operand, String> {
+        let names = self
+            .struct_fields
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("Unknown struct '{}'", name))?;
+        let mut values = Vec::new();
+        for field in &names {
+            let (_, value) = fields
+                .iter()
+                .find(|(name, _)| name == field)
+                .ok_or_else(|| format!("Missing field '{}' in {}", field, name))?;
+            values.push(self.expression(*value)?);
+        }
+        if self.program.struct_def(name).is_none() {
+            let fields = names
+                .into_iter()
+                .zip(&values)
+                .map(|(field, value)| (field, self.program.type_of(*value)))
+                .collect();
+            self.program.structs.push(StructDef {
+                name: name.to_string(),
+                fields,
+            });
+        }
+        let dest = self.temp(Type::Struct(name.to_string()));
+        self.emit(Instr::Struct {
+            dest,
+            fields: values,
+        });
+        Ok(Operand::Var(dest))
+    }
+
+    /// Lower a function that is called. Its body only sees its parameters.
+    fn function(&mut self, name: &str, params: &[Param], body: NodeId) -> Result<(), String> {
+        let Some(signature) = self.signatures.get(name).cloned() else {
+            return Ok(());
+        };
+        // Each function is its own namespace in generated code
+        let outer = std::mem::replace(&mut self.scopes, vec![HashMap::new()]);
+        let names = std::mem::take(&mut self.names);
+        let params = params
+            .iter()
+            .zip(signature.params)
+            .map(|(param, ty)| self.declare(&param.name, ty, false))
+            .collect();
+        let outer_result = std::mem::replace(&mut self.result, signature.result.clone());
+        let body = self.body(|this| this.statement(body));
+        self.result = outer_result;
+        self.scopes = outer;
+        self.names = names;
+        self.program.functions.push(Function {
+            name: name.to_string(),
+            params,
+            result: signature.result,
+            body: body?,
+        });
+        Ok(())
+    }
+
+    /// Lift a lambda that captures nothing into a function named after
+    /// where it is, returning a pointer to it
+    fn lambda(
+        &mut self,
+        lambda: NodeId,
+        params: &[Param],
+        body: NodeId,
+        signature: Signature,
+    ) -> Result<Operand, String> {
+        let span = self.ast[lambda].span();
+        let name = format!("lambda.{}.{}", span.line, span.column);
+        let ty = signature.function_type();
+        let outer = std::mem::replace(&mut self.scopes, vec![HashMap::new()]);
+        let names = std::mem::take(&mut self.names);
+        let params = params
+            .iter()
+            .zip(signature.params)
+            .map(|(param, ty)| self.declare(&param.name, ty, false))
+            .collect();
+        let outer_result = std::mem::replace(&mut self.result, signature.result.clone());
+        let lowered = self.body(|this| {
+            // A body that is a call may not give anything back
+            let value = match &this.ast[body] {
+                ASTNode::Call { name, args, span } => this.call(name, args, *span)?,
+                _ => Some(this.expression(body)?),
+            };
+            let value = match (value, this.result.clone()) {
+                (Some(value), Some(result)) => Some(this.convert(value, &result)),
+                _ => None,
+            };
+            this.emit(Instr::Return(value));
+            Ok(())
+        });
+        self.result = outer_result;
+        self.scopes = outer;
+        self.names = names;
+        self.program.functions.push(Function {
+            name: name.clone(),
+            params,
+            result: signature.result,
+            body: lowered?,
+        });
+        let dest = self.temp(ty);
+        self.emit(Instr::FunctionRef {
+            dest,
+            function: name,
+        });
+        Ok(Operand::Var(dest))
+    }
+
+    /// Lower a call through a function value, converting the arguments to
+    /// the types of its parameters
+    fn call_value(&mut self, callee: Operand, args: Vec<Operand>) -> Option<Operand> {
+        let Type::Function { params, result } = self.program.type_of(callee) else {
+            unreachable!("only functions are called");
+        };
+        let args = args
+            .into_iter()
+            .zip(&params)
+            .map(|(arg, ty)| self.convert(arg, ty))
+            .collect();
+        let dest = result.map(|ty| self.temp(*ty));
+        self.emit(Instr::CallValue { dest, callee, args });
+        dest.map(Operand::Var)
+    }
+
+    /// Lower `map(function, array)` into a loop that calls the function on
+    /// each element and pushes the results onto a new array
+    fn map(&mut self, function: Operand, array: Operand) -> Result<Operand, String> {
+        let Type::Function {
+            result: Some(result),
+            ..
+        } = self.program.type_of(function)
+        else {
+            unreachable!("map() is only given functions with a result");
+        };
+        let element = Self::element_of(self.program.type_of(array))?;
+        let results = self.temp(Type::Array(result));
+        self.emit(Instr::Array {
+            dest: results,
+            elements: Vec::new(),
+        });
+        let len = self.temp(Type::Int);
+        self.emit(Instr::Call {
+            dest: Some(len),
+            builtin: Builtin::Len,
+            args: vec![array],
+        });
+        let var = self.temp(Type::Int);
+        let body = self.body(|this| {
+            let value = this.temp(element);
+            this.emit(Instr::Index {
+                dest: value,
+                array,
+                index: Operand::Var(var),
+            });
+            let result = this
+                .call_value(function, vec![Operand::Var(value)])
+                .expect("checked above");
+            this.emit(Instr::Call {
+                dest: None,
+                builtin: Builtin::Push,
+                args: vec![Operand::Var(results), result],
+            });
+            Ok(())
+        })?;
+        self.emit(Instr::ForRange {
+            var,
+            start: Operand::Int(0),
+            end: Operand::Var(len),
+            inclusive: false,
+            body,
+        });
+        Ok(Operand::Var(results))
+    }
+
+    /// Lower a call to a function value, a function or a builtin, returning
+    /// its result if it has one
+    fn call(&mut self, name: &str, args: &[NodeId], span: Span) -> Result<Option<Operand>, String> {
+        if let Ok(callee) = self.resolve(name) {
+            let args = args
+                .iter()
+                .map(|arg| self.expression(*arg))
+                .collect::<Result<Vec<_>, _>>()?;
+            return Ok(self.call_value(callee, args));
+        }
+        if let Some(signature) = self.signatures.get(name).cloned() {
+            let result = signature.result;
+            let args = args
+                .iter()
+                .zip(&signature.params)
+                .map(|(arg, ty)| {
+                    // Annotated float parameters take ints
+                    let arg = self.expression(*arg)?;
+                    Ok(self.convert(arg, ty))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            let dest = result.map(|ty| self.temp(ty));
+            self.emit(Instr::CallFunction {
+                dest,
+                function: name.to_string(),
+                args,
+            });
+            return Ok(dest.map(Operand::Var));
+        }
+        let builtin =
+            Builtin::from_name(name).ok_or_else(|| format!("Unknown function '{}'", name))?;
+        if builtin == Builtin::Error {
+            // The message is a string, which is only lowered as print text
+            return self.error(args, span).map(|_| None);
+        }
+        if builtin == Builtin::TypeOf {
+            // Known without computing the value, except for literals, which
+            // aren't given types but compute nothing
+            let ty = match self.checked_type(args[0]) {
+                Ok(ty) => ty,
+                Err(_) => {
+                    let value = self.expression(args[0])?;
+                    self.program.type_of(value)
+                }
+            };
+            return Ok(Some(self.text(&ty.to_string())));
+        }
+        let mut args = args
+            .iter()
+            .map(|arg| self.expression(*arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        if builtin == Builtin::Env && args.len() == 1 {
+            // Without a default, an unset variable is empty
+            args.push(self.text(""));
+        }
+        match builtin {
+            Builtin::Apply => return Ok(self.call_value(args[0], args[1..].to_vec())),
+            Builtin::Map => return self.map(args[0], args[1]).map(Some),
+            _ => {}
+        }
+        if builtin == Builtin::Push {
+            // The value is stored as an element, so it needs the element type
+            let element = Self::element_of(self.program.type_of(args[0]))?;
+            args[1] = self.convert(args[1], &element);
+        }
+        let types: Vec<_> = args.iter().map(|arg| self.program.type_of(*arg)).collect();
+        let result = builtin.check(&types)?;
+        if let (Builtin::Str, [ty]) = (builtin, &types[..]) {
+            if !matches!(ty, Type::Int | Type::Float | Type::Bool | Type::Str) {
+                return Err(format!(
+                    "Converting {} with str() is only supported by `osho run` for now, not by compiled targets: the call at line {}, column {}",
+                    ty, span.line, span.column
+                ));
+            }
+        }
+        if builtin.is_math() && result == Some(Type::Float) {
+            // Math on floats takes float arguments, like C's math.h
+            for arg in &mut args {
+                *arg = self.convert(*arg, &Type::Float);
+            }
+        }
+        let dest = result.map(|ty| self.temp(ty));
+        self.emit(Instr::Call {
+            dest,
+            builtin,
+            args,
+        });
+        Ok(dest.map(Operand::Var))
+    }
+
+    /// The type of a field of a struct type
+    fn field_type(&self, ty: &Type, field: &str) -> Result<Type, String> {
+        let Type::Struct(name) = ty else {
+            return Err(format!("Cannot access field '{}' of {}", field, ty));
+        };
+        self.struct_types
+            .get(name)
+            .and_then(|fields| fields.iter().find(|(name, _)| name == field))
+            .map(|(_, ty)| ty.clone())
+            .ok_or_else(|| format!("Struct '{}' has no field '{}'", name, field))
+    }
+
+    /// The element type of an array type, or char for a string
+    fn element_of(ty: Type) -> Result<Type, String> {
+        match ty {
+            Type::Array(element) => Ok(*element),
+            Type::Str => Ok(Type::Char),
+            other => Err(format!("Cannot index into {}", other)),
+        }
+    }
+
+    /// Lower a binary operation other than a short-circuit into `dest`, or
+    /// into a new temporary
+    fn binary(&mut self, expr: NodeId, dest: Option<VarId>) -> Result<Operand, String> {
+        let ASTNode::BinaryOp {
+            left, op, right, ..
+        } = &self.ast[expr]
+        else {
+            unreachable!("only binary operations are lowered as one");
+        };
+        let left = self.expression(*left)?;
+        let right = self.expression(*right)?;
+        if self.program.type_of(left) == Type::Str {
+            return Err(INTERPRETER_ONLY.to_string());
+        }
+        let (left, right) = self.promote(*op, left, right);
+        let dest = match dest {
+            Some(dest) => dest,
+            None => {
+                let result = self.checked_type(expr)?;
+                self.temp(result)
+            }
+        };
+        self.emit(Instr::Binary {
+            dest,
+            op: *op,
+            left,
+            right,
+        });
+        Ok(Operand::Var(dest))
+    }
+
+    /// Convert the operands of a binary operator to floats if either is a
+    /// float, or if the operator is a power
+    fn promote(&mut self, op: BinaryOperator, left: Operand, right: Operand) -> (Operand, Operand) {
+        let promote = op == BinaryOperator::Power
+            || self.program.type_of(left) == Type::Float
+            || self.program.type_of(right) == Type::Float;
+        if promote {
+            (
+                self.convert(left, &Type::Float),
+                self.convert(right, &Type::Float),
+            )
+        } else {
+            (left, right)
+        }
+    }
+
+    /// The type the semantic analyzer found for an expression
+    fn checked_type(&self, expr: NodeId) -> Result<Type, String> {
+        let span = self.ast[expr].span();
+        self.types.get(&span).cloned().ok_or_else(|| {
+            format!(
+                "The expression at {}:{} wasn't type checked",
+                span.line, span.column
+            )
+        })
+    }
+
+    /// Convert an int operand to a float when `ty` is float
+    fn convert(&mut self, value: Operand, ty: &Type) -> Operand {
+        match value {
+            Operand::Int(num) if *ty == Type::Float => Operand::Float(num as f64),
+            Operand::Var(id) if *ty == Type::Float && self.program.vars[id].ty == Type::Int => {
+                let dest = self.temp(Type::Float);
+                self.emit(Instr::IntToFloat { dest, value });
+                Operand::Var(dest)
+            }
+            _ => value,
+        }
+    }
+
+    /// The text and values a print writes. Printed strings and the text of
+    /// interpolated strings join the text around them, since C only prints
+    /// strings it's given as its format.
+    fn print_parts(
+        &mut self,
+        pieces: Vec<String>,
+        args: &[NodeId],
+    ) -> Result<(Vec<String>, Vec<Operand>), String> {
+        let mut parts = vec![pieces[0].clone()];
+        let mut values = Vec::new();
+        for (arg, piece) in args.iter().zip(&pieces[1..]) {
+            let (inner, inner_values) = match &self.ast[*arg] {
+                ASTNode::Str(text) => (std::slice::from_ref(text), &[][..]),
+                ASTNode::Interpolation { pieces, values, .. } => (&pieces[..], &values[..]),
+                _ => {
+                    let value = self.expression(*arg)?;
+                    if self.holds_function(&self.program.type_of(value)) {
+                        return Err(format!(
+                            "Printing functions is only supported by `osho run` for now, not by compiled targets: the value at line {}, column {}",
+                            self.ast[*arg].span().line,
+                            self.ast[*arg].span().column
+                        ));
+                    }
+                    values.push(value);
+                    parts.push(piece.clone());
+                    continue;
+                }
+            };
+            parts
+                .last_mut()
+                .expect("parts start with a piece")
+                .push_str(&inner[0]);
+            for (value, inner_piece) in inner_values.iter().zip(&inner[1..]) {
+                values.push(self.expression(*value)?);
+                parts.push(inner_piece.clone());
+            }
+            parts
+                .last_mut()
+                .expect("parts start with a piece")
+                .push_str(piece);
+        }
+        Ok((parts, values))
+    }
+
+    /// A string literal, in a temporary
+    fn text(&mut self, text: &str) -> Operand {
+        let dest = self.temp(Type::Str);
+        self.emit(Instr::Text {
+            dest,
+            text: text.to_string(),
+        });
+        Operand::Var(dest)
+    }
+
+    /// Lower `error(message)`, which prints the message and the line it
+    /// was called on and stops the program
+    fn error(&mut self, args: &[NodeId], span: Span) -> Result<(), String> {
+        let (mut pieces, values) = self.print_parts(vec![String::new(); 2], args)?;
+        for value in &values {
+            let ty = self.program.type_of(*value);
+            if matches!(ty, Type::Array(_) | Type::Struct(_)) {
+                return Err(format!(
+                    "Showing {} in an error() message is only supported by `osho run` for now, not by compiled targets: the call at line {}, column {}",
+                    ty, span.line, span.column
+                ));
+            }
+        }
+        pieces
+            .last_mut()
+            .expect("parts end with a piece")
+            .push_str(&format!(" (line {})", span.line));
+        self.emit(Instr::Fail { pieces, values });
+        Ok(())
+    }
+
+    /// Whether a value of the type is a function or holds one, which
+    /// compiled code can't print
+    fn holds_function(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Function { .. } => true,
+            Type::Array(element) => self.holds_function(element),
+            Type::Struct(name) => self
+                .struct_types
+                .get(name)
+                .is_some_and(|fields| fields.iter().any(|(_, ty)| self.holds_function(ty))),
+            _ => false,
+        }
+    }
+
+    /// Lower into a nested body, returning its instructions
+    fn body(
+        &mut self,
+        lower: impl FnOnce(&mut Self) -> Result<(), String>,
+    ) -> Result<Vec<Instr>, String> {
+        self.bodies.push(Vec::new());
+        self.scopes.push(HashMap::new());
+        let result = lower(self);
+        self.scopes.pop();
+        let body = self.bodies.pop().expect("pushed above");
+        result.map(|_| body)
+    }
+
+    fn emit(&mut self, instr: Instr) {
+        self.bodies
+            .last_mut()
+            .expect("program body is never popped")
+            .push(instr);
+    }
+
+    /// Add a variable with a name that no other variable has
+    fn add_var(&mut self, base: &str, ty: Type) -> VarId {
+        let mut name = base.to_string();
+        let mut suffix = 0;
+        while self.names.contains(&name) {
+            suffix += 1;
+            name = format!("{}_{}", base, suffix);
+        }
+        self.names.insert(name.clone());
+        self.program.vars.push(Var {
+            name,
+            ty,
+            mutable: true,
+        });
+        self.program.vars.len() - 1
+    }
+
+    fn declare(&mut self, name: &str, ty: Type, mutable: bool) -> VarId {
+        let id = self.add_var(name, ty);
+        self.program.vars[id].mutable = mutable;
+        self.bind(name, Operand::Var(id));
+        id
+    }
+
+    fn bind(&mut self, name: &str, operand: Operand) {
+        self.scopes
+            .last_mut()
+            .expect("global scope is never popped")
+            .insert(name.to_string(), operand);
+    }
+
+    fn temp(&mut self, ty: Type) -> VarId {
+        let base = format!("_t{}", self.temps);
+        self.temps += 1;
+        self.add_var(&base, ty)
+    }
+
+    /// The variable or constant value a name refers to
+    fn resolve(&self, name: &str) -> Result<Operand, String> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .copied()
+            .ok_or_else(|| format!("Variable '{}' is not declared", name))
+    }
+
+    /// The variable a name refers to, for assigning to it
+    fn lookup(&self, name: &str) -> Result<VarId, String> {
+        match self.resolve(name)? {
+            Operand::Var(id) => Ok(id),
+            _ => Err(format!("Cannot assign to constant '{}'", name)),
+        }
+    }
+}
@@ -0,0 +1,1249 @@
+//! JavaScript backend, emitting a script that runs under Node or in a browser.
+//!
+//! Variables become `let` declarations and `println` becomes `console.log`.
+//! `print` uses `process.stdout.write`, `exit()` uses `process.exit`,
+//! `args()` reads `process.argv`, `env()` reads `process.env` and the file
+//! builtins use Node's `fs` module, so they need Node.
+//! JavaScript only has doubles, so integer division is truncated explicitly
+//! and ints beyond 2^53 lose precision. Programs using `input` read all of
+//! stdin when they start, which needs Node. Arrays are JavaScript arrays,
+//! accessed through helper functions that check indexes are in bounds, maps
+//! are JavaScript `Map`s, and structs are objects tagged with their name.
+//! Characters are numbers holding their code point, like in C, and only
+//! become text when printed, so inside an array they print as numbers.
+//! Functions are generated at their first call, when the types of their
+//! parameters are known, and placed before the rest of the program.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::backend::Backend;
+use crate::builtins::Builtin;
+use crate::desugar;
+use crate::error::CompileError;
+use crate::format;
+use crate::interpreter;
+use crate::parser::{ASTNode, Ast, BinaryOperator, NodeId, Param, UnaryOperator};
+use crate::semantic_analyzer::Type;
+
+/// The osho type of an expression, which decides how operators are emitted
+#[derive(Debug, Clone, PartialEq)]
+enum JsType {
+    Int,
+    Float,
+    Bool,
+    Str,
+    Char,
+    Array(Box<JsType>),
+    Map(Box<JsType>),
+    Struct(String),
+}
+
+impl From<&Type> for JsType {
+    fn from(ty: &Type) -> Self {
+        match ty {
+            Type::Int => JsType::Int,
+            Type::Float => JsType::Float,
+            Type::Bool => JsType::Bool,
+            Type::Str => JsType::Str,
+            Type::Char => JsType::Char,
+            Type::Array(element) => JsType::Array(Box::new(JsType::from(&**element))),
+            Type::Map(value) => JsType::Map(Box::new(JsType::from(&**value))),
+            Type::Struct(name) => JsType::Struct(name.clone()),
+            Type::Function { .. } => unreachable!("lambdas are rejected before their types"),
+        }
+    }
+}
+
+/// Named like the osho types, for `typeof()`
+impl fmt::Display for JsType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsType::Int => write!(f, "int"),
+            JsType::Float => write!(f, "float"),
+            JsType::Bool => write!(f, "bool"),
+            JsType::Str => write!(f, "string"),
+            JsType::Char => write!(f, "char"),
+            JsType::Array(element) => write!(f, "[{}]", element),
+            JsType::Map(value) => write!(f, "{{string: {}}}", value),
+            JsType::Struct(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// A declared function
+struct JsFunction {
+    params: Vec<Param>,
+    body: NodeId,
+
+    /// The type of its result once it has been generated: `None` inside
+    /// for functions that don't return a value
+    result: Option<Option<JsType>>,
+
+    /// The annotated type of its result, which its `return`s don't change
+    declared: Option<JsType>,
+}
+
+/// A name that is valid in JavaScript. Items of modules are named
+/// `module.item`, which becomes `module$item`.
+fn js_name(name: &str) -> String {
+    name.replace('.', "$")
+}
+
+/// Escape text for a template literal
+fn js_template(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('`', "\\`")
+        .replace("${", "\\${")
+}
+
+pub struct JsGenerator {
+    code: String,
+
+    /// Nesting depth of the current statement, for indentation
+    depth: usize,
+
+    /// Types of the variables in each enclosing scope, innermost last
+    scopes: Vec<HashMap<String, JsType>>,
+
+    /// Whether the program uses `input`, which needs a helper function
+    uses_input: bool,
+
+    /// Whether the program uses arrays, maps or structs, which need helper
+    /// functions
+    uses_arrays: bool,
+
+    /// Whether the program uses random numbers
+    uses_random: bool,
+
+    /// Whether the program reads its arguments
+    uses_args: bool,
+
+    /// Whether the program converts floats to ints or parses numbers
+    uses_conversions: bool,
+
+    /// Whether the program calls a string builtin that needs a helper
+    uses_strings: bool,
+
+    /// Fields of each declared struct in declaration order, with their types
+    /// once a value of the struct has been constructed
+    structs: HashMap<String, Vec<(String, Option<JsType>)>>,
+
+    /// Declared functions by name
+    functions: HashMap<String, JsFunction>,
+
+    /// Functions being generated, innermost last
+    generating: Vec<String>,
+
+    /// Code of the functions generated so far
+    function_code: String,
+}
+
+/// Reads the lines of stdin one `input` at a time
+const INPUT_HELPER: &str = r#"const inputLines = require("fs").readFileSync(0, "utf8").split("\n");
+function readInput(type) {
+  if (inputLines.length === 0) throw new Error("Unexpected end of input");
+  const text = inputLines.shift().trim();
+  const value = Number(text);
+  if (text === "" || Number.isNaN(value) || (type === "int" && !Number.isInteger(value))) {
+    throw new Error(`Expected ${type} input, found '${text}'`);
+  }
+  return value;
+}
+"#;
+
+/// Bounds-checked array access and slices, map lookups, and printing arrays,
+/// maps and structs the way osho does
+const ARRAY_HELPER: &str = r#"const oshoStructName = Symbol("struct");
+function oshoCheckIndex(array, index) {
+  if (index < 0 || index >= array.length) {
+    throw new Error(`Index ${index} is out of bounds for an array of length ${array.length}`);
+  }
+  return index;
+}
+function oshoAt(array, index) {
+  return array[oshoCheckIndex(array, index)];
+}
+function oshoSetAt(array, index, value) {
+  array[oshoCheckIndex(array, index)] = value;
+}
+function oshoSlice(value, start, end) {
+  const text = typeof value === "string";
+  const items = text ? Array.from(value) : value;
+  if (end === undefined) end = items.length;
+  if (start < 0 || start > end || end > items.length) {
+    const what = text ? "a string" : "an array";
+    throw new Error(`Slice ${start}:${end} is out of range for ${what} of length ${items.length}`);
+  }
+  const slice = items.slice(start, end);
+  return text ? slice.join("") : slice;
+}
+function oshoPop(array) {
+  if (array.length === 0) throw new Error("Cannot pop from an empty array");
+  return array.pop();
+}
+function oshoGet(map, key) {
+  if (!map.has(key)) throw new Error(`Key ${JSON.stringify(key)} is not in the map`);
+  return map.get(key);
+}
+function oshoKeys(map) {
+  return [...map.keys()].sort();
+}
+function oshoShow(value, nested) {
+  if (Array.isArray(value)) {
+    return `[${value.map((element) => oshoShow(element, true)).join(", ")}]`;
+  }
+  if (value instanceof Map) {
+    const entries = oshoKeys(value).map((key) => `${JSON.stringify(key)}: ${oshoShow(value.get(key), true)}`);
+    return `{${entries.join(", ")}}`;
+  }
+  if (value !== null && typeof value === "object" && oshoStructName in value) {
+    const fields = Object.keys(value).map((field) => `${field}: ${oshoShow(value[field], true)}`);
+    return `${value[oshoStructName]} { ${fields.join(", ")} }`;
+  }
+  return nested && typeof value === "string" ? JSON.stringify(value) : String(value);
+}
+"#;
+
+/// splitmix64 behind `rand()`, in 64-bit BigInts so a seed gives the same
+/// numbers as the other backends
+const RANDOM_HELPER: &str = r#"let oshoRandomState = 0n;
+function oshoRandomNext() {
+  oshoRandomState = BigInt.asUintN(64, oshoRandomState + 0x9E3779B97F4A7C15n);
+  let z = oshoRandomState;
+  z = BigInt.asUintN(64, (z ^ (z >> 30n)) * 0xBF58476D1CE4E5B9n);
+  z = BigInt.asUintN(64, (z ^ (z >> 27n)) * 0x94D049BB133111EBn);
+  return z ^ (z >> 31n);
+}
+function oshoRand() {
+  return Number(oshoRandomNext() >> 11n) / 9007199254740992;
+}
+function oshoRandRange(low, high) {
+  if (low >= high) throw new Error(`rand_range(${low}, ${high}) has no numbers to pick from`);
+  const span = BigInt.asUintN(64, BigInt(high) - BigInt(low));
+  return Number(BigInt.asIntN(64, BigInt(low) + oshoRandomNext() % span));
+}
+function oshoSeed(seed) {
+  oshoRandomState = BigInt.asUintN(64, BigInt(seed));
+}
+"#;
+
+/// `int()` of a float and `parse_num()`, which fail like the other backends
+/// on values they can't convert
+const CONVERSION_HELPER: &str = r#"function oshoInt(value) {
+  if (!Number.isFinite(value)) throw new Error(`int() can't convert ${value} to an int`);
+  return Math.trunc(value);
+}
+function oshoParseNum(text) {
+  const value = Number(text);
+  if (text.trim() !== text || text === "" || /[xXbBoO]/.test(text) || Number.isNaN(value)) {
+    throw new Error(`parse_num() can't parse '${text}' as a number`);
+  }
+  return value;
+}
+"#;
+
+/// String builtins that index by character rather than by UTF-16 unit, and
+/// fail like the other backends
+const STRING_HELPER: &str = r#"function oshoSubstring(text, start, end) {
+  const chars = Array.from(text);
+  if (start < 0 || start > end || end > chars.length) {
+    throw new Error(`substring(${start}, ${end}) is out of range for a string of length ${chars.length}`);
+  }
+  return chars.slice(start, end).join("");
+}
+function oshoFind(text, part) {
+  const index = text.indexOf(part);
+  return index < 0 ? -1 : Array.from(text.slice(0, index)).length;
+}
+function oshoSplit(text, separator) {
+  if (separator === "") throw new Error("split() separator can't be empty");
+  return text.split(separator);
+}
+function oshoCharAt(text, index) {
+  const chars = Array.from(text);
+  if (index < 0 || index >= chars.length) {
+    throw new Error(`Index ${index} is out of bounds for a string of length ${chars.length}`);
+  }
+  return chars[index].codePointAt(0);
+}
+function oshoChr(code) {
+  if (code < 0 || code > 0x10ffff || (code >= 0xd800 && code <= 0xdfff)) {
+    throw new Error(`chr(${code}) is not a valid character`);
+  }
+  return code;
+}
+"#;
+
+/// The arguments after the script's name, which `argv()` checks indexes of
+const ARGS_HELPER: &str = r#"const oshoArgs = process.argv.slice(2);
+function oshoArgv(index) {
+  if (index < 0 || index >= oshoArgs.length) {
+    const count = oshoArgs.length;
+    throw new Error(`argv(${index}) is out of range for ${count} argument${count === 1 ? "" : "s"}`);
+  }
+  return oshoArgs[index];
+}
+"#;
+
+impl Default for JsGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for JsGenerator {
+    fn generate(&mut self, ast: &Ast) -> Result<String, CompileError> {
+        *self = Self::new();
+        let ast = &desugar::desugared(ast);
+        self.statement(ast, ast.root())
+            .map_err(|message| CompileError::Codegen { message })?;
+        let mut code = std::mem::take(&mut self.code);
+        code.insert_str(0, &self.function_code);
+        if self.uses_arrays {
+            code.insert_str(0, ARRAY_HELPER);
+        }
+        if self.uses_random {
+            code.insert_str(0, RANDOM_HELPER);
+        }
+        if self.uses_args {
+            code.insert_str(0, ARGS_HELPER);
+        }
+        if self.uses_conversions {
+            code.insert_str(0, CONVERSION_HELPER);
+        }
+        if self.uses_strings {
+            code.insert_str(0, STRING_HELPER);
+        }
+        if self.uses_input {
+            code.insert_str(0, INPUT_HELPER);
+        }
+        Ok(code)
+    }
+}
+
+impl JsGenerator {
+    pub fn new() -> Self {
+        Self {
+            code: String::new(),
+            depth: 0,
+            scopes: vec![HashMap::new()],
+            uses_input: false,
+            uses_arrays: false,
+            uses_random: false,
+            uses_args: false,
+            uses_conversions: false,
+            uses_strings: false,
+            structs: HashMap::new(),
+            functions: HashMap::new(),
+            generating: Vec::new(),
+            function_code: String::new(),
+        }
+    }
+
+    fn statement(&mut self, ast: &Ast, node: NodeId) -> Result<(), String> {
+        match &ast[node] {
+            ASTNode::Program(statements) => {
+                for stmt in statements {
+                    self.statement(ast, *stmt)?;
+                }
+            }
+            ASTNode::Block(statements) => {
+                self.line("{");
+                self.block(ast, statements)?;
+                self.line("}");
+            }
+            ASTNode::StructDeclaration { name, fields, .. } => {
+                let fields = fields.iter().map(|field| (field.clone(), None)).collect();
+                self.structs.insert(name.clone(), fields);
+            }
+            ASTNode::FunctionDeclaration {
+                name,
+                params,
+                result,
+                body,
+                ..
+            } => {
+                let function = JsFunction {
+                    params: params.clone(),
+                    body: *body,
+                    result: None,
+                    declared: result.as_ref().map(JsType::from),
+                };
+                self.functions.insert(name.clone(), function);
+            }
+            ASTNode::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let condition = self.top_level(ast, *condition)?.0;
+                self.line(&format!("if ({}) {{", condition));
+                self.body(ast, *then_branch)?;
+                let mut else_branch = *else_branch;
+                // Keep `else if` chains flat instead of nesting them
+                while let Some(ASTNode::If {
+                    condition,
+                    then_branch,
+                    else_branch: next,
+                    ..
+                }) = else_branch.map(|else_branch| &ast[else_branch])
+                {
+                    let condition = self.top_level(ast, *condition)?.0;
+                    self.line(&format!("}} else if ({}) {{", condition));
+                    self.body(ast, *then_branch)?;
+                    else_branch = *next;
+                }
+                if let Some(else_branch) = else_branch {
+                    self.line("} else {");
+                    self.body(ast, else_branch)?;
+                }
+                self.line("}");
+            }
+            ASTNode::While {
+                condition, body, ..
+            } => {
+                let condition = self.top_level(ast, *condition)?.0;
+                self.line(&format!("while ({}) {{", condition));
+                self.body(ast, *body)?;
+                self.line("}");
+            }
+            ASTNode::Assert {
+                condition,
+                message,
+                span,
+            } => {
+                let (condition, ty) = self.top_level(ast, *condition)?;
+                let message = format!(
+                    "{} (line {})",
+                    interpreter::assertion_failed(message.as_deref()),
+                    span.line
+                );
+                self.line(&format!(
+                    "if (!({})) throw new Error({:?});",
+                    Self::to_bool(condition, ty),
+                    message
+                ));
+            }
+            ASTNode::DoWhile {
+                body, condition, ..
+            } => {
+                self.line("do {");
+                self.body(ast, *body)?;
+                let condition = self.top_level(ast, *condition)?.0;
+                self.line(&format!("}} while ({});", condition));
+            }
+            ASTNode::For {
+                init,
+                condition,
+                step,
+                body,
+                ..
+            } => {
+                // The initializer's variables belong to the loop's scope
+                self.scopes.push(HashMap::new());
+                let result = self.for_loop(ast, init, condition, step, *body);
+                self.scopes.pop();
+                result?;
+            }
+            ASTNode::ForIn {
+                name, range, body, ..
+            } => {
+                let ASTNode::Range {
+                    start,
+                    end,
+                    inclusive,
+                    ..
+                } = ast[*range]
+                else {
+                    unreachable!("the parser only puts ranges in for-in loops");
+                };
+                let start = self.top_level(ast, start)?.0;
+                let end = self.top_level(ast, end)?.0;
+                // The end is evaluated once, like the interpreter does
+                self.line(&format!(
+                    "for (let {0} = {1}, {0}$end = {2}; {0} {3} {0}$end; {0}++) {{",
+                    name,
+                    start,
+                    end,
+                    if inclusive { "<=" } else { "<" }
+                ));
+                self.scopes
+                    .push(HashMap::from([(name.clone(), JsType::Int)]));
+                let result = self.body(ast, *body);
+                self.scopes.pop();
+                result?;
+                self.line("}");
+            }
+            _ => {
+                let stmt = self.simple_statement(ast, node)?;
+                self.line(&format!("{};", stmt));
+            }
+        }
+        Ok(())
+    }
+
+    fn for_loop(
+        &mut self,
+        ast: &Ast,
+        init: &Option<NodeId>,
+        condition: &Option<NodeId>,
+        step: &Option<NodeId>,
+        body: NodeId,
+    ) -> Result<(), String> {
+        let init = match init {
+            Some(init) => self.simple_statement(ast, *init)?,
+            None => String::new(),
+        };
+        let condition = match condition {
+            Some(condition) => self.top_level(ast, *condition)?.0,
+            None => String::new(),
+        };
+        let step = match step {
+            Some(step) => self.simple_statement(ast, *step)?,
+            None => String::new(),
+        };
+        self.line(&format!("for ({}; {}; {}) {{", init, condition, step));
+        self.body(ast, body)?;
+        self.line("}");
+        Ok(())
+    }
+
+    /// Generate a statement that fits on one line, without the trailing `;`
+    fn simple_statement(&mut self, ast: &Ast, node: NodeId) -> Result<String, String> {
+        match &ast[node] {
+            ASTNode::LetDeclaration {
+                name,
+                mutable,
+                ty: annotation,
+                value,
+                ..
+            } => {
+                let (value, ty) = match (annotation, &ast[*value]) {
+                    // Only an annotation can give an empty array or map a type
+                    (Some(annotation), ASTNode::ArrayLiteral { elements, .. })
+                        if elements.is_empty() =>
+                    {
+                        ("[]".to_string(), JsType::from(annotation))
+                    }
+                    (Some(annotation), ASTNode::MapLiteral { entries, .. })
+                        if entries.is_empty() =>
+                    {
+                        ("new Map()".to_string(), JsType::from(annotation))
+                    }
+                    (annotation, _) => {
+                        let (value, ty) = self.top_level(ast, *value)?;
+                        (value, annotation.as_ref().map_or(ty, JsType::from))
+                    }
+                };
+                self.scopes
+                    .last_mut()
+                    .expect("global scope is never popped")
+                    .insert(name.clone(), ty);
+                let keyword = if *mutable { "let" } else { "const" };
+                Ok(format!("{} {} = {}", keyword, name, value))
+            }
+            ASTNode::ConstDeclaration { name, value, .. } => {
+                let (value, ty) = self.top_level(ast, *value)?;
+                self.scopes
+                    .last_mut()
+                    .expect("global scope is never popped")
+                    .insert(name.clone(), ty);
+                Ok(format!("const {} = {}", js_name(name), value))
+            }
+            ASTNode::Assignment { name, value, .. } => {
+                let value = self.top_level(ast, *value)?.0;
+                Ok(format!("{} = {}", name, value))
+            }
+            ASTNode::Return { value: None, .. } => Ok("return".to_string()),
+            ASTNode::Return {
+                value: Some(value), ..
+            } => {
+                let (value, ty) = self.top_level(ast, *value)?;
+                if let Some(name) = self.generating.last() {
+                    let function = self.functions.get_mut(name).expect("function is declared");
+                    if function.declared.is_none() {
+                        function.result = Some(Some(ty));
+                    }
+                }
+                Ok(format!("return {}", value))
+            }
+            ASTNode::Break { .. } => Ok("break".to_string()),
+            ASTNode::Continue { .. } => Ok("continue".to_string()),
+            ASTNode::Print {
+                format: None,
+                args,
+                newline: true,
+                ..
+            } => {
+                // console.log separates its arguments with spaces too
+                let args = args
+                    .iter()
+                    .map(|arg| self.printed(ast, *arg))
+                    .collect::<Result<Vec<_>, String>>()?;
+                Ok(format!("console.log({})", args.join(", ")))
+            }
+            ASTNode::Print {
+                format,
+                args,
+                newline,
+                ..
+            } => {
+                let pieces = format::print_pieces(format.as_deref(), args.len())?;
+                let mut template = format!("`{}", js_template(&pieces[0]));
+                for (arg, piece) in args.iter().zip(&pieces[1..]) {
+                    let arg = self.printed(ast, *arg)?;
+                    template.push_str(&format!("${{{}}}{}", arg, js_template(piece)));
+                }
+                template.push('`');
+                if *newline {
+                    Ok(format!("console.log({})", template))
+                } else {
+                    Ok(format!("process.stdout.write({})", template))
+                }
+            }
+            ASTNode::Input { name, .. } => {
+                self.uses_input = true;
+                let ty = match self.lookup(name)? {
+                    JsType::Float => "float",
+                    _ => "int",
+                };
+                Ok(format!("{} = readInput(\"{}\")", name, ty))
+            }
+            ASTNode::IndexAssignment {
+                array,
+                index,
+                value,
+                ..
+            } => {
+                self.uses_arrays = true;
+                let (array, ty) = self.top_level(ast, *array)?;
+                let index = self.top_level(ast, *index)?.0;
+                let value = self.top_level(ast, *value)?.0;
+                match ty {
+                    JsType::Map(_) => Ok(format!("{}.set({}, {})", array, index, value)),
+                    _ => Ok(format!("oshoSetAt({}, {}, {})", array, index, value)),
+                }
+            }
+            ASTNode::FieldAssignment {
+                object,
+                field,
+                value,
+                ..
+            } => {
+                let object = self.expression(ast, *object)?.0;
+                let value = self.top_level(ast, *value)?.0;
+                Ok(format!("{}.{} = {}", object, field, value))
+            }
+            ASTNode::Call { name, args, .. } if name == Builtin::Push.name() => {
+                let [array, value] = &args[..] else {
+                    return Err("push() takes 2 arguments".to_string());
+                };
+                let array = self.top_level(ast, *array)?.0;
+                let value = self.top_level(ast, *value)?.0;
+                Ok(format!("{}.push({})", array, value))
+            }
+            ASTNode::Call { name, args, .. } if name == Builtin::Seed.name() => {
+                let [seed] = &args[..] else {
+                    return Err("seed() takes 1 argument".to_string());
+                };
+                self.uses_random = true;
+                let seed = self.top_level(ast, *seed)?.0;
+                Ok(format!("oshoSeed({})", seed))
+            }
+            ASTNode::Call { name, args, .. } if name == Builtin::Exit.name() => {
+                let [code] = &args[..] else {
+                    return Err("exit() takes 1 argument".to_string());
+                };
+                let code = self.top_level(ast, *code)?.0;
+                Ok(format!("process.exit({})", code))
+            }
+            ASTNode::Call { name, args, span } if name == Builtin::Error.name() => {
+                let [message] = &args[..] else {
+                    return Err("error() takes 1 argument".to_string());
+                };
+                let message = self.top_level(ast, *message)?.0;
+                Ok(format!(
+                    "throw new Error({} + {:?})",
+                    message,
+                    format!(" (line {})", span.line)
+                ))
+            }
+            ASTNode::Call { name, args, .. }
+                if name == Builtin::WriteFile.name() || name == Builtin::AppendFile.name() =>
+            {
+                let [path, text] = &args[..] else {
+                    return Err(format!("{}() takes 2 arguments", name));
+                };
+                let path = self.top_level(ast, *path)?.0;
+                let text = self.top_level(ast, *text)?.0;
+                let function = if name == Builtin::WriteFile.name() {
+                    "writeFileSync"
+                } else {
+                    "appendFileSync"
+                };
+                Ok(format!("require(\"fs\").{}({}, {})", function, path, text))
+            }
+            ASTNode::Call { name, args, .. } if self.functions.contains_key(name) => {
+                self.call_function(ast, name, args).map(|(code, _)| code)
+            }
+            ASTNode::Call { .. } => self.top_level(ast, node).map(|(code, _)| code),
+            // A chain of assignments the desugarer split up, which are
+            // expressions in JavaScript
+            ASTNode::Block(statements) => Ok(statements
+                .iter()
+                .map(|stmt| self.simple_statement(ast, *stmt))
+                .collect::<Result<Vec<_>, _>>()?
+                .join(", ")),
+            _ => Err("Unexpected AST node".to_string()),
+        }
+    }
+
+    /// Generate the statements of a braced body, unwrapping a block so it
+    /// doesn't get a second pair of braces
+    fn body(&mut self, ast: &Ast, node: NodeId) -> Result<(), String> {
+        match &ast[node] {
+            ASTNode::Block(statements) => self.block(ast, statements),
+            _ => {
+                self.depth += 1;
+                let result = self.statement(ast, node);
+                self.depth -= 1;
+                result
+            }
+        }
+    }
+
+    fn block(&mut self, ast: &Ast, statements: &[NodeId]) -> Result<(), String> {
+        self.depth += 1;
+        self.scopes.push(HashMap::new());
+        let result = statements
+            .iter()
+            .try_for_each(|stmt| self.statement(ast, *stmt));
+        self.scopes.pop();
+        self.depth -= 1;
+        result
+    }
+
+    /// Generate an expression, returning its code and type
+    fn expression(&mut self, ast: &Ast, expr: NodeId) -> Result<(String, JsType), String> {
+        match &ast[expr] {
+            // Negative literals are parenthesized because `-2 ** 2` is a
+            // syntax error in JavaScript
+            ASTNode::Int(num) if *num < 0 => Ok((format!("({})", num), JsType::Int)),
+            ASTNode::Int(num) => Ok((num.to_string(), JsType::Int)),
+            // Debug formatting keeps the '.0' so floats read as floats
+            ASTNode::Number(num) if *num < 0.0 => Ok((format!("({:?})", num), JsType::Float)),
+            ASTNode::Number(num) => Ok((format!("{:?}", num), JsType::Float)),
+            ASTNode::Bool(b) => Ok((b.to_string(), JsType::Bool)),
+            // Debug formatting escapes the string the way JavaScript does
+            ASTNode::Str(text) => Ok((format!("{:?}", text), JsType::Str)),
+            ASTNode::Char(c) => Ok(((*c as u32).to_string(), JsType::Char)),
+            ASTNode::Interpolation { pieces, values, .. } => {
+                let mut template = format!("`{}", js_template(&pieces[0]));
+                for (value, piece) in values.iter().zip(&pieces[1..]) {
+                    let value = self.printed(ast, *value)?;
+                    template.push_str(&format!("${{{}}}{}", value, js_template(piece)));
+                }
+                template.push('`');
+                Ok((template, JsType::Str))
+            }
+            ASTNode::Identifier { name, .. }
+                if self.lookup(name).is_err() && self.functions.contains_key(name) =>
+            {
+                Err("The JS target doesn't support function values yet".to_string())
+            }
+            ASTNode::Identifier { name, .. } => Ok((js_name(name), self.lookup(name)?)),
+            ASTNode::BinaryOp {
+                left, op, right, ..
+            } => {
+                let (left, left_ty) = self.expression(ast, *left)?;
+                let (right, right_ty) = self.expression(ast, *right)?;
+                if op.is_logical() {
+                    // && and || return an operand, so make sure the result is a bool
+                    let left = Self::to_bool(left, left_ty);
+                    let right = Self::to_bool(right, right_ty);
+                    return Ok((
+                        format!("({} {} {})", left, op.symbol(), right),
+                        JsType::Bool,
+                    ));
+                }
+                let symbol = match op {
+                    BinaryOperator::Equal => "===",
+                    BinaryOperator::NotEqual => "!==",
+                    _ => op.symbol(),
+                };
+                let code = format!("({} {} {})", left, symbol, right);
+                if op.is_comparison() {
+                    return Ok((code, JsType::Bool));
+                }
+                match (left_ty, right_ty) {
+                    (JsType::Int, JsType::Int) if *op == BinaryOperator::Divide => {
+                        Ok((format!("Math.trunc({} / {})", left, right), JsType::Int))
+                    }
+                    (JsType::Int, JsType::Int) if *op != BinaryOperator::Power => {
+                        Ok((code, JsType::Int))
+                    }
+                    _ => Ok((code, JsType::Float)),
+                }
+            }
+            ASTNode::UnaryOp { op, operand, .. } => {
+                let (operand, ty) = self.expression(ast, *operand)?;
+                match op {
+                    UnaryOperator::Not => Ok((format!("(!{})", operand), JsType::Bool)),
+                    UnaryOperator::Negate => Ok((format!("(-{})", operand), ty)),
+                }
+            }
+            ASTNode::Conditional {
+                condition,
+                then_value,
+                else_value,
+                ..
+            } => {
+                let (condition, condition_ty) = self.expression(ast, *condition)?;
+                let (then_value, ty) = self.expression(ast, *then_value)?;
+                let (else_value, _) = self.expression(ast, *else_value)?;
+                Ok((
+                    format!(
+                        "({} ? {} : {})",
+                        Self::to_bool(condition, condition_ty),
+                        then_value,
+                        else_value
+                    ),
+                    ty,
+                ))
+            }
+            ASTNode::ArrayLiteral { elements, .. } => {
+                let mut codes = Vec::new();
+                let mut element_ty = None;
+                for element in elements {
+                    let (code, ty) = self.top_level(ast, *element)?;
+                    codes.push(code);
+                    // Ints mixed with floats make an array of floats
+                    if element_ty.is_none() || ty == JsType::Float {
+                        element_ty = Some(ty);
+                    }
+                }
+                let element_ty = element_ty
+                    .ok_or_else(|| "Cannot infer the type of an empty array".to_string())?;
+                Ok((
+                    format!("[{}]", codes.join(", ")),
+                    JsType::Array(Box::new(element_ty)),
+                ))
+            }
+            ASTNode::MapLiteral { entries, .. } => {
+                let mut codes = Vec::new();
+                let mut value_ty = None;
+                for (key, value) in entries {
+                    let key = self.top_level(ast, *key)?.0;
+                    let (value, ty) = self.top_level(ast, *value)?;
+                    codes.push(format!("[{}, {}]", key, value));
+                    if value_ty.is_none() || ty == JsType::Float {
+                        value_ty = Some(ty);
+                    }
+                }
+                let value_ty =
+                    value_ty.ok_or_else(|| "Cannot infer the type of an empty map".to_string())?;
+                Ok((
+                    format!("new Map([{}])", codes.join(", ")),
+                    JsType::Map(Box::new(value_ty)),
+                ))
+            }
+            ASTNode::StructLiteral { name, fields, .. } => self.struct_literal(ast, name, fields),
+            ASTNode::FieldAccess { object, field, .. } => {
+                let (object, ty) = self.expression(ast, *object)?;
+                let JsType::Struct(name) = ty else {
+                    return Err(format!("Cannot access field '{}'", field));
+                };
+                let ty = self
+                    .structs
+                    .get(&name)
+                    .and_then(|fields| fields.iter().find(|(name, _)| name == field))
+                    .and_then(|(_, ty)| ty.clone())
+                    .ok_or_else(|| format!("Struct '{}' has no field '{}'", name, field))?;
+                Ok((format!("{}.{}", object, field), ty))
+            }
+            ASTNode::Index { array, index, .. } => {
+                let (array, ty) = self.top_level(ast, *array)?;
+                let index = self.top_level(ast, *index)?.0;
+                if ty == JsType::Str {
+                    self.uses_strings = true;
+                    return Ok((format!("oshoCharAt({}, {})", array, index), JsType::Char));
+                }
+                self.uses_arrays = true;
+                match ty {
+                    JsType::Array(element_ty) => {
+                        Ok((format!("oshoAt({}, {})", array, index), *element_ty))
+                    }
+                    JsType::Map(value_ty) => {
+                        Ok((format!("oshoGet({}, {})", array, index), *value_ty))
+                    }
+                    _ => Err("Only arrays, maps and strings can be indexed".to_string()),
+                }
+            }
+            ASTNode::Slice {
+                array, start, end, ..
+            } => {
+                self.uses_arrays = true;
+                let (array, ty) = self.top_level(ast, *array)?;
+                let mut args = vec![array];
+                args.push(match start {
+                    Some(start) => self.top_level(ast, *start)?.0,
+                    None => "0".to_string(),
+                });
+                if let Some(end) = end {
+                    args.push(self.top_level(ast, *end)?.0);
+                }
+                Ok((format!("oshoSlice({})", args.join(", ")), ty))
+            }
+            ASTNode::Call { name, args, .. } if self.functions.contains_key(name) => {
+                let (code, ty) = self.call_function(ast, name, args)?;
+                let ty = ty.ok_or_else(|| format!("{}() doesn't return a value", name))?;
+                Ok((code, ty))
+            }
+            ASTNode::Call { name, args, .. } => match (Builtin::from_name(name), &args[..]) {
+                (Some(Builtin::Len), [value]) => match self.expression(ast, *value)? {
+                    (text, JsType::Str) => {
+                        Ok((format!("Array.from({}).length", text), JsType::Int))
+                    }
+                    (array, _) => Ok((format!("{}.length", array), JsType::Int)),
+                },
+                (Some(Builtin::Substring), [text, start, end]) => {
+                    self.uses_strings = true;
+                    let text = self.top_level(ast, *text)?.0;
+                    let start = self.top_level(ast, *start)?.0;
+                    let end = self.top_level(ast, *end)?.0;
+                    Ok((
+                        format!("oshoSubstring({}, {}, {})", text, start, end),
+                        JsType::Str,
+                    ))
+                }
+                (Some(builtin @ (Builtin::Find | Builtin::Split)), [text, part]) => {
+                    self.uses_strings = true;
+                    let text = self.top_level(ast, *text)?.0;
+                    let part = self.top_level(ast, *part)?.0;
+                    if builtin == Builtin::Find {
+                        Ok((format!("oshoFind({}, {})", text, part), JsType::Int))
+                    } else {
+                        Ok((
+                            format!("oshoSplit({}, {})", text, part),
+                            JsType::Array(Box::new(JsType::Str)),
+                        ))
+                    }
+                }
+                // Only ASCII letters, like the other backends
+                (Some(builtin @ (Builtin::Upper | Builtin::Lower)), [text]) => {
+                    let text = self.expression(ast, *text)?.0;
+                    let (letters, case) = if builtin == Builtin::Upper {
+                        ("a-z", "toUpperCase")
+                    } else {
+                        ("A-Z", "toLowerCase")
+                    };
+                    Ok((
+                        format!(
+                            "{}.replace(/[{}]+/g, (letters) => letters.{}())",
+                            text, letters, case
+                        ),
+                        JsType::Str,
+                    ))
+                }
+                (Some(Builtin::Ord), [c]) => Ok((self.expression(ast, *c)?.0, JsType::Int)),
+                (Some(Builtin::Chr), [code]) => {
+                    self.uses_strings = true;
+                    let code = self.top_level(ast, *code)?.0;
+                    Ok((format!("oshoChr({})", code), JsType::Char))
+                }
+                (Some(Builtin::Pop), [array]) => {
+                    self.uses_arrays = true;
+                    let (array, ty) = self.top_level(ast, *array)?;
+                    let JsType::Array(element_ty) = ty else {
+                        return Err("pop() expects an array".to_string());
+                    };
+                    Ok((format!("oshoPop({})", array), *element_ty))
+                }
+                (Some(Builtin::Keys), [map]) => {
+                    self.uses_arrays = true;
+                    let map = self.top_level(ast, *map)?.0;
+                    Ok((
+                        format!("oshoKeys({})", map),
+                        JsType::Array(Box::new(JsType::Str)),
+                    ))
+                }
+                (Some(builtin), args) if builtin.is_math() => self.math(ast, builtin, args),
+                (Some(Builtin::Apply | Builtin::Map), _) => {
+                    Err("The JS target doesn't support function values yet".to_string())
+                }
+                (Some(Builtin::Rand), []) => {
+                    self.uses_random = true;
+                    Ok(("oshoRand()".to_string(), JsType::Float))
+                }
+                (Some(Builtin::RandRange), [low, high]) => {
+                    self.uses_random = true;
+                    let low = self.top_level(ast, *low)?.0;
+                    let high = self.top_level(ast, *high)?.0;
+                    Ok((format!("oshoRandRange({}, {})", low, high), JsType::Int))
+                }
+                // A copy, since the program may push onto it
+                (Some(Builtin::Args), []) => {
+                    self.uses_args = true;
+                    self.uses_arrays = true;
+                    Ok((
+                        "[...oshoArgs]".to_string(),
+                        JsType::Array(Box::new(JsType::Str)),
+                    ))
+                }
+                (Some(Builtin::Argc), []) => {
+                    self.uses_args = true;
+                    Ok(("oshoArgs.length".to_string(), JsType::Int))
+                }
+                (Some(Builtin::Argv), [index]) => {
+                    self.uses_args = true;
+                    let index = self.top_level(ast, *index)?.0;
+                    Ok((format!("oshoArgv({})", index), JsType::Str))
+                }
+                (Some(Builtin::Env), [name, rest @ ..]) => {
+                    let name = self.top_level(ast, *name)?.0;
+                    let default = match rest {
+                        [default] => self.top_level(ast, *default)?.0,
+                        _ => "\"\"".to_string(),
+                    };
+                    Ok((
+                        format!("(process.env[{}] ?? {})", name, default),
+                        JsType::Str,
+                    ))
+                }
+                (Some(Builtin::Int), [value]) => {
+                    let (value, ty) = self.top_level(ast, *value)?;
+                    match ty {
+                        JsType::Float => {
+                            self.uses_conversions = true;
+                            Ok((format!("oshoInt({})", value), JsType::Int))
+                        }
+                        JsType::Bool => Ok((format!("Number({})", value), JsType::Int)),
+                        _ => Ok((value, JsType::Int)),
+                    }
+                }
+                (Some(Builtin::Float), [value]) => {
+                    let (value, ty) = self.top_level(ast, *value)?;
+                    match ty {
+                        JsType::Bool => Ok((format!("Number({})", value), JsType::Float)),
+                        _ => Ok((value, JsType::Float)),
+                    }
+                }
+                (Some(Builtin::Str), [value]) => {
+                    let value = self.printed(ast, *value)?;
+                    Ok((format!("String({})", value), JsType::Str))
+                }
+                (Some(Builtin::TypeOf), [value]) => {
+                    // Only the type is needed, so the value's code is dropped
+                    let ty = self.expression(ast, *value)?.1;
+                    Ok((format!("{:?}", ty.to_string()), JsType::Str))
+                }
+                (Some(Builtin::ParseNum), [text]) => {
+                    self.uses_conversions = true;
+                    let text = self.top_level(ast, *text)?.0;
+                    Ok((format!("oshoParseNum({})", text), JsType::Float))
+                }
+                (Some(Builtin::ReadFile), [path]) => {
+                    let path = self.top_level(ast, *path)?.0;
+                    Ok((
+                        format!("require(\"fs\").readFileSync({}, \"utf8\")", path),
+                        JsType::Str,
+                    ))
+                }
+                _ => Err(format!("Unknown function '{}'", name)),
+            },
+            ASTNode::Lambda { .. } => Err("The JS target doesn't support lambdas yet".to_string()),
+            _ => Err("Unexpected expression node".to_string()),
+        }
+    }
+
+    /// Generate a call to a math builtin, which maps onto `Math`
+    fn math(
+        &mut self,
+        ast: &Ast,
+        builtin: Builtin,
+        args: &[NodeId],
+    ) -> Result<(String, JsType), String> {
+        let mut codes = Vec::new();
+        let mut all_ints = true;
+        for arg in args {
+            let (code, ty) = self.expression(ast, *arg)?;
+            all_ints &= ty == JsType::Int;
+            codes.push(code);
+        }
+        let ty = match builtin {
+            Builtin::Abs | Builtin::Floor | Builtin::Ceil | Builtin::Min | Builtin::Max
+                if all_ints =>
+            {
+                JsType::Int
+            }
+            _ => JsType::Float,
+        };
+        Ok((format!("Math.{}({})", builtin.name(), codes.join(", ")), ty))
+    }
+
+    /// Generate a call to a declared function, returning its code and the
+    /// type of its result if it has one
+    fn call_function(
+        &mut self,
+        ast: &Ast,
+        name: &str,
+        args: &[NodeId],
+    ) -> Result<(String, Option<JsType>), String> {
+        let mut codes = Vec::new();
+        let mut types = Vec::new();
+        for arg in args {
+            let (code, ty) = self.top_level(ast, *arg)?;
+            codes.push(code);
+            types.push(ty);
+        }
+        let result = match &self.functions[name].result {
+            Some(result) => result.clone(),
+            None => self.function(ast, name, types)?,
+        };
+        Ok((format!("{}({})", js_name(name), codes.join(", ")), result))
+    }
+
+    /// Generate a function into `function_code`, with parameters of the
+    /// types of its first call's arguments. It only sees its parameters.
+    fn function(
+        &mut self,
+        ast: &Ast,
+        name: &str,
+        types: Vec<JsType>,
+    ) -> Result<Option<JsType>, String> {
+        let function = self.functions.get_mut(name).expect("function is declared");
+        function.result = Some(function.declared.clone());
+        let params = function.params.clone();
+        let body = function.body;
+        // Annotated parameters have their own types
+        let scope = params
+            .iter()
+            .zip(types)
+            .map(|(param, ty)| {
+                let ty = param.ty.as_ref().map_or(ty, JsType::from);
+                (param.name.clone(), ty)
+            })
+            .collect();
+        let names: Vec<_> = params.iter().map(|param| param.name.as_str()).collect();
+
+        let code = std::mem::take(&mut self.code);
+        let depth = std::mem::take(&mut self.depth);
+        let scopes = std::mem::replace(&mut self.scopes, vec![scope]);
+        self.generating.push(name.to_string());
+        self.line(&format!(
+            "function {}({}) {{",
+            js_name(name),
+            names.join(", ")
+        ));
+        let result = self.body(ast, body);
+        self.line("}");
+        self.generating.pop();
+        self.scopes = scopes;
+        self.depth = depth;
+        let function_code = std::mem::replace(&mut self.code, code);
+        result?;
+        self.function_code.push_str(&function_code);
+        Ok(self.functions[name].result.clone().flatten())
+    }
+
+    /// Generate a struct literal as an object with its fields in declaration
+    /// order, so they are evaluated and printed in that order
+    fn struct_literal(
+        &mut self,
+        ast: &Ast,
+        name: &str,
+        fields: &[(String, NodeId)],
+    ) -> Result<(String, JsType), String> {
+        self.uses_arrays = true;
+        let declared = self
+            .structs
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("Unknown struct '{}'", name))?;
+        let mut codes = vec![format!("[oshoStructName]: {:?}", name)];
+        let mut types = Vec::new();
+        for (field, _) in declared {
+            let (_, value) = fields
+                .iter()
+                .find(|(name, _)| *name == field)
+                .ok_or_else(|| format!("Missing field '{}' in {}", field, name))?;
+            let (code, ty) = self.top_level(ast, *value)?;
+            codes.push(format!("{}: {}", field, code));
+            types.push((field, Some(ty)));
+        }
+        self.structs.insert(name.to_string(), types);
+        Ok((
+            format!("{{ {} }}", codes.join(", ")),
+            JsType::Struct(name.to_string()),
+        ))
+    }
+
+    /// Generate an argument of `print`, turning arrays, maps and structs into
+    /// text the way osho prints them
+    fn printed(&mut self, ast: &Ast, arg: NodeId) -> Result<String, String> {
+        match self.top_level(ast, arg)? {
+            (code, JsType::Array(_) | JsType::Map(_) | JsType::Struct(_)) => {
+                self.uses_arrays = true;
+                Ok(format!("oshoShow({})", code))
+            }
+            (code, JsType::Char) => Ok(format!("String.fromCodePoint({})", code)),
+            (code, _) => Ok(code),
+        }
+    }
+
+    /// Generate an expression that stands on its own, like a condition or the
+    /// value of a `let`, so its outer parentheses aren't needed
+    fn top_level(&mut self, ast: &Ast, expr: NodeId) -> Result<(String, JsType), String> {
+        let (code, ty) = self.expression(ast, expr)?;
+        Ok((Self::strip_parens(code), ty))
+    }
+
+    /// Remove parentheses around the whole of `code`, keeping ones like
+    /// `(a) + (b)` that only look like they do
+    fn strip_parens(code: String) -> String {
+        if !code.starts_with('(') {
+            return code;
+        }
+        let mut depth = 0;
+        for (i, c) in code.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                return if i == code.len() - 1 {
+                    code[1..i].to_string()
+                } else {
+                    code
+                };
+            }
+        }
+        code
+    }
+
+    fn to_bool(code: String, ty: JsType) -> String {
+        match ty {
+            JsType::Bool => code,
+            _ => format!("({} !== 0)", code),
+        }
+    }
+
+    /// Find a variable's type, searching from the innermost scope outwards
+    fn lookup(&self, name: &str) -> Result<JsType, String> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+            .ok_or_else(|| format!("Variable '{}' is not declared", name))
+    }
+
+    fn line(&mut self, line: &str) {
+        self.code.push_str(&"  ".repeat(self.depth));
+        self.code.push_str(line);
+        self.code.push('\n');
+    }
+}
@@ -1,8 +1,29 @@
+use std::borrow::Cow;
+use std::fmt;
 use std::str::Chars;
-use string_cache::DefaultAtom as Atom;
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct Token {
+use serde::Serialize;
+
+use crate::error::CompileError;
+
+/// A location in the source text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize)]
+pub struct Span {
+    /// Line number, starting at 1
+    pub line: usize,
+
+    /// Column number in characters, starting at 1
+    pub column: usize,
+
+    /// Length in characters
+    pub length: usize,
+
+    /// Byte offset of the start in the source
+    pub offset: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Token<'src> {
     /// Token Type
     pub kind: Kind,
 
@@ -12,34 +33,147 @@ pub struct Token {
     /// End offset in source
     pub end: usize,
 
-    pub value: TokenValue,
+    /// Line and column of the token
+    pub span: Span,
+
+    pub value: TokenValue<'src>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum TokenValue {
+impl fmt::Display for Token<'_> {
+    /// One line per token: position, kind and value, e.g. `1:5 Identifier "x"`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{} {:?}", self.span.line, self.span.column, self.kind)?;
+        match &self.value {
+            TokenValue::None => Ok(()),
+            TokenValue::Integer(num) => write!(f, " {}", num),
+            TokenValue::Number(num) => write!(f, " {}", num),
+            TokenValue::String(text) => write!(f, " {:?}", &**text),
+            TokenValue::Char(c) => write!(f, " {:?}", c),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum TokenValue<'src> {
     None,
+    Integer(i64),
     Number(f64),
-    String(Atom),
+
+    /// The text of an identifier or string, borrowed from the source unless
+    /// the string has escapes to replace
+    String(Cow<'src, str>),
+
+    /// The character of a character literal
+    Char(char),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
 pub enum Kind {
     Eof, // end of file
     WhiteSpace,
+    Comment, // for '// ...' and '/* ... */'
     Plus,
     Multiply,
+    Power, // for '**'
     Divide,
+    Modulo,
     Increment, // for '++'
     Decrement, // for '--'
     Minus,
+    Arrow,          // for '->'
+    PlusEquals,     // for '+='
+    MinusEquals,    // for '-='
+    MultiplyEquals, // for '*='
+    DivideEquals,   // for '/='
     EqualsTo,
+    EqualsEquals,      // for '=='
+    NotEquals,         // for '!='
+    LessThan,          // for '<'
+    LessThanEquals,    // for '<='
+    GreaterThan,       // for '>'
+    GreaterThanEquals, // for '>='
+    And,               // for '&&'
+    Or,                // for '||'
+    Pipe,              // for '|', around the parameters of a lambda
+    Bang,              // for '!'
     Identifier,
+    Integer,
     Number,
     String,
+    StringStart,  // for '"text${', the start of an interpolated string
+    StringMiddle, // for '}text${' between interpolated expressions
+    StringEnd,    // for '}text"'
+    Char,         // for a character literal like 'a'
     Print,
+    Println,
+    Input,
     OpenParen,
     CloseParen,
+    OpenBrace,
+    CloseBrace,
+    OpenBracket,
+    CloseBracket,
+    Semicolon,
+    Comma,
+    Colon,
+    Question,
+    Dot,
+    DotDot,       // for '..'
+    DotDotEquals, // for '..='
     Let,
+    Mut,
+    Const,
+    Struct,
+    Fn,
+    Return,
+    If,
+    Else,
+    While,
+    Do,
+    For,
+    In,
+    Break,
+    Continue,
+    Assert,
+    Import,
+    Module,
+    Pub,
+    True,
+    False,
+}
+
+impl Kind {
+    /// Whether the token is whitespace or a comment, which the parser never sees
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Kind::WhiteSpace | Kind::Comment)
+    }
+}
+
+/// Replace the escape sequences `\n`, `\t`, `\\`, `\"`, `\'` and `\$` in the
+/// contents of a string or character literal
+fn unescape(raw: &str) -> Result<Cow<'_, str>, String> {
+    if !raw.contains('\\') {
+        return Ok(Cow::Borrowed(raw));
+    }
+    let mut out = String::new();
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('\\') => out.push('\\'),
+            Some('"') => out.push('"'),
+            Some('\'') => out.push('\''),
+            Some('$') => out.push('$'),
+            Some(other) => return Err(format!("Unknown escape sequence '\\{}'", other)),
+            None => return Err("Unterminated escape sequence".to_string()),
+        }
+    }
+    Ok(Cow::Owned(out))
 }
 
 pub struct Lexer<'a> {
@@ -51,6 +185,29 @@ pub struct Lexer<'a> {
 
     /// Current position in the source
     current_pos: usize,
+
+    /// Current line, starting at 1
+    line: usize,
+
+    /// Current column, starting at 1
+    column: usize,
+
+    /// Kind of the last token that wasn't whitespace
+    last_kind: Option<Kind>,
+
+    /// Added to the offsets of spans, so files of one program don't overlap
+    base_offset: usize,
+
+    /// Braces opened inside each `${` of an interpolated string, innermost
+    /// last. The `}` that closes the expression resumes the string.
+    interpolations: Vec<usize>,
+
+    /// Whether iterating gives whitespace and comments too
+    keep_trivia: bool,
+
+    /// Whether the end of file or an error has been given, which ends the
+    /// iteration
+    finished: bool,
 }
 
 impl<'a> Lexer<'a> {
@@ -60,111 +217,312 @@ impl<'a> Lexer<'a> {
             source,
             chars: source.chars(),
             current_pos: 0,
+            line: 1,
+            column: 1,
+            last_kind: None,
+            interpolations: Vec::new(),
+            base_offset: 0,
+            keep_trivia: false,
+            finished: false,
         }
     }
 
-    /// Get all tokens from the source
-    pub fn get_tokens(&mut self) -> Vec<Token> {
-        let mut tokens = Vec::new();
-        loop {
-            let token = self.read_next_token();
-            if token.kind == Kind::Eof {
-                tokens.push(token);
-                break;
-            }
-            // Skip whitespace tokens
-            if token.kind != Kind::WhiteSpace {
-                tokens.push(token);
-            }
-        }
-        tokens
+    /// Start the offsets of spans at `base` instead of 0
+    pub fn with_base_offset(mut self, base: usize) -> Self {
+        self.base_offset = base;
+        self
+    }
+
+    /// Tokenize any text, giving an error for what isn't osho rather than
+    /// panicking, which makes this an entry point for fuzzing
+    pub fn try_tokenize(source: &'a str) -> Result<Vec<Token<'a>>, CompileError> {
+        Lexer::new(source).get_tokens()
+    }
+
+    /// Get all tokens from the source, skipping whitespace and comments
+    pub fn get_tokens(&mut self) -> Result<Vec<Token<'a>>, CompileError> {
+        self.collect_tokens(false)
+    }
+
+    /// Get all tokens from the source, keeping whitespace and comments so
+    /// that tools like a formatter can reproduce the original text
+    pub fn get_tokens_with_trivia(&mut self) -> Result<Vec<Token<'a>>, CompileError> {
+        self.collect_tokens(true)
+    }
+
+    fn collect_tokens(&mut self, keep_trivia: bool) -> Result<Vec<Token<'a>>, CompileError> {
+        self.keep_trivia = keep_trivia;
+        self.collect()
     }
 
     /// Read the next token
-    fn read_next_token(&mut self) -> Token {
+    fn read_next_token(&mut self) -> Result<Token<'a>, CompileError> {
         let start = self.offset();
+        let (line, column) = (self.line, self.column);
         let kind = self.read_next_kind();
         let end = self.offset();
-        let value = self.extract_value(&kind, start, end);
-        Token {
+        let span = Span {
+            line,
+            column,
+            length: self.source[start..end].chars().count(),
+            offset: self.base_offset + start,
+        };
+        let (kind, value) = kind
+            .and_then(|kind| Ok((kind, self.extract_value(&kind, start, end)?)))
+            .map_err(|message| CompileError::Lex { message, span })?;
+        if !kind.is_trivia() {
+            self.last_kind = Some(kind);
+        }
+        Ok(Token {
             kind,
             start,
             end,
+            span,
             value,
-        }
+        })
     }
 
     /// Read the next kind of token
-    fn read_next_kind(&mut self) -> Kind {
-        while let Some(c) = self.next_char() {
-            match c {
-                '+' => return self.handle_plus(),
-                '-' => return self.handle_minus(),
-                '*' => return Kind::Multiply,
-                '/' => return Kind::Divide,
-                '=' => return Kind::EqualsTo,
-                '(' => return Kind::OpenParen,
-                ')' => return Kind::CloseParen,
-                '"' => return self.read_string(),
-                _ if c.is_numeric() => return self.read_number(c),
-                _ if c.is_alphabetic() => return self.read_identifier_or_keyword(c),
-                _ if c.is_whitespace() => return Kind::WhiteSpace,
-                _ => {
-                    eprintln!("Unrecognized char: {}", c);
-                    continue;
+    fn read_next_kind(&mut self) -> Result<Kind, String> {
+        let Some(c) = self.next_char() else {
+            if !self.interpolations.is_empty() {
+                return Err("Unterminated string interpolation".to_string());
+            }
+            return Ok(Kind::Eof);
+        };
+        let kind = match c {
+            '+' => self.handle_plus(),
+            '-' => self.handle_minus(),
+            '*' if self.peek() == Some('*') => {
+                self.next_char(); // Consume the second '*'
+                Kind::Power
+            }
+            '*' if self.peek() == Some('=') => {
+                self.next_char(); // Consume the '='
+                Kind::MultiplyEquals
+            }
+            '*' => Kind::Multiply,
+            '/' if self.peek() == Some('/') => self.read_line_comment(),
+            '/' if self.peek() == Some('*') => return self.read_block_comment(),
+            '/' if self.peek() == Some('=') => {
+                self.next_char(); // Consume the '='
+                Kind::DivideEquals
+            }
+            '/' => Kind::Divide,
+            '%' => Kind::Modulo,
+            '=' => self.handle_equals(),
+            '!' => self.handle_bang(),
+            '&' if self.peek() == Some('&') => {
+                self.next_char(); // Consume the second '&'
+                Kind::And
+            }
+            '|' if self.peek() == Some('|') => {
+                self.next_char(); // Consume the second '|'
+                Kind::Or
+            }
+            '|' => Kind::Pipe,
+            '<' => self.handle_less_than(),
+            '>' => self.handle_greater_than(),
+            '(' => Kind::OpenParen,
+            ')' => Kind::CloseParen,
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
                 }
+                Kind::OpenBrace
             }
-        }
-        Kind::Eof
+            '}' if self.interpolations.last() == Some(&0) => {
+                self.interpolations.pop();
+                return self.read_string(true);
+            }
+            '}' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth -= 1;
+                }
+                Kind::CloseBrace
+            }
+            '[' => Kind::OpenBracket,
+            ']' => Kind::CloseBracket,
+            ';' => Kind::Semicolon,
+            ',' => Kind::Comma,
+            ':' => Kind::Colon,
+            '?' => Kind::Question,
+            '.' if self.peek() == Some('.') => {
+                self.next_char(); // Consume the second '.'
+                if self.peek() == Some('=') {
+                    self.next_char(); // Consume the '='
+                    Kind::DotDotEquals
+                } else {
+                    Kind::DotDot
+                }
+            }
+            '.' => Kind::Dot,
+            '"' => return self.read_string(false),
+            '\'' => return self.read_char(),
+            _ if c.is_numeric() => self.read_number(c),
+            _ if c.is_alphabetic() => self.read_identifier_or_keyword(c),
+            _ if c.is_whitespace() => Kind::WhiteSpace,
+            _ => return Err(format!("Unrecognized character '{}'", c)),
+        };
+        Ok(kind)
     }
 
-    /// Handle the '+' character and check for '++'
+    /// Handle the '+' character and check for '++' and '+='
     fn handle_plus(&mut self) -> Kind {
-        if let Some(next_char) = self.peek() {
-            if next_char == '+' {
+        match self.peek() {
+            Some('+') => {
                 self.next_char(); // Consume the second '+'
-                return Kind::Increment;
+                Kind::Increment
+            }
+            Some('=') => {
+                self.next_char(); // Consume the '='
+                Kind::PlusEquals
             }
+            _ => Kind::Plus,
         }
-        Kind::Plus
     }
 
-    /// Handle the '+' character and check for '++'
+    /// Handle the '-' character and check for '--', '-=' and '->'. It is only a
+    /// decrement after an identifier (`x--`); otherwise `--5` is two unary minuses.
     fn handle_minus(&mut self) -> Kind {
-        if let Some(next_char) = self.peek() {
-            if next_char == '-' {
-                self.next_char(); // Consume the second '+'
-                return Kind::Decrement;
+        match self.peek() {
+            Some('-') if self.last_kind == Some(Kind::Identifier) => {
+                self.next_char(); // Consume the second '-'
+                Kind::Decrement
+            }
+            Some('=') => {
+                self.next_char(); // Consume the '='
+                Kind::MinusEquals
             }
+            Some('>') => {
+                self.next_char(); // Consume the '>'
+                Kind::Arrow
+            }
+            _ => Kind::Minus,
+        }
+    }
+
+    /// Handle the '=' character and check for '=='
+    fn handle_equals(&mut self) -> Kind {
+        if self.peek() == Some('=') {
+            self.next_char(); // Consume the second '='
+            return Kind::EqualsEquals;
+        }
+        Kind::EqualsTo
+    }
+
+    /// Handle the '!' character and check for '!='
+    fn handle_bang(&mut self) -> Kind {
+        if self.peek() == Some('=') {
+            self.next_char(); // Consume the '='
+            return Kind::NotEquals;
         }
-        Kind::Minus
+        Kind::Bang
     }
 
-    /// Read a number token
+    /// Handle the '<' character and check for '<='
+    fn handle_less_than(&mut self) -> Kind {
+        if self.peek() == Some('=') {
+            self.next_char(); // Consume the '='
+            return Kind::LessThanEquals;
+        }
+        Kind::LessThan
+    }
+
+    /// Handle the '>' character and check for '>='
+    fn handle_greater_than(&mut self) -> Kind {
+        if self.peek() == Some('=') {
+            self.next_char(); // Consume the '='
+            return Kind::GreaterThanEquals;
+        }
+        Kind::GreaterThan
+    }
+
+    /// Skip the rest of a `//` comment, leaving the newline
+    fn read_line_comment(&mut self) -> Kind {
+        while self.peek().is_some_and(|c| c != '\n') {
+            self.next_char();
+        }
+        Kind::Comment
+    }
+
+    /// Read a `/* */` comment, which may span several lines
+    fn read_block_comment(&mut self) -> Result<Kind, String> {
+        self.next_char(); // Consume the '*'
+        while let Some(c) = self.next_char() {
+            if c == '*' && self.peek() == Some('/') {
+                self.next_char(); // Consume the '/'
+                return Ok(Kind::Comment);
+            }
+        }
+        Err("Unterminated block comment".to_string())
+    }
+
+    /// Read a number token, which is an integer unless it contains a '.'.
+    /// A '..' after it starts a range instead, as in `0..10`.
     fn read_number(&mut self, initial: char) -> Kind {
         let mut num_str = initial.to_string();
         while let Some(c) = self.peek() {
-            if c.is_digit(10) || c == '.' {
+            if c == '.' && self.peek_second() == Some('.') {
+                break;
+            }
+            if c.is_ascii_digit() || c == '.' {
                 num_str.push(c);
                 self.next_char();
             } else {
                 break;
             }
         }
-        Kind::Number
+        if num_str.contains('.') {
+            Kind::Number
+        } else {
+            Kind::Integer
+        }
     }
 
-    /// Read a string token
-    fn read_string(&mut self) -> Kind {
-        let mut str_content = String::new();
+    /// Read a string token, after its opening quote or, when `resumed`, after
+    /// the `}` of an interpolated expression. A `${` ends the token so the
+    /// expression is read as tokens of its own. Escape sequences are replaced
+    /// when its value is extracted.
+    fn read_string(&mut self, resumed: bool) -> Result<Kind, String> {
         while let Some(c) = self.next_char() {
-            if c == '"' {
-                break;
-            } else {
-                str_content.push(c);
+            match c {
+                '"' if resumed => return Ok(Kind::StringEnd),
+                '"' => return Ok(Kind::String),
+                '$' if self.peek() == Some('{') => {
+                    self.next_char(); // Consume the '{'
+                    self.interpolations.push(0);
+                    return Ok(if resumed {
+                        Kind::StringMiddle
+                    } else {
+                        Kind::StringStart
+                    });
+                }
+                // An escaped quote doesn't end the string
+                '\\' => {
+                    self.next_char();
+                }
+                _ => {}
             }
         }
-        Kind::String
+        Err("Unterminated string literal".to_string())
+    }
+
+    /// Read a character literal after its opening quote. Whether it holds
+    /// exactly one character is checked when its value is extracted.
+    fn read_char(&mut self) -> Result<Kind, String> {
+        while let Some(c) = self.next_char() {
+            match c {
+                '\'' => return Ok(Kind::Char),
+                '\n' => break,
+                // An escaped quote doesn't end the literal
+                '\\' => {
+                    self.next_char();
+                }
+                _ => {}
+            }
+        }
+        Err("Unterminated character literal".to_string())
     }
 
     /// Read an identifier or keyword
@@ -185,32 +543,77 @@ impl<'a> Lexer<'a> {
     fn match_keyword(&self, ident: &str) -> Kind {
         match ident {
             "print" => Kind::Print,
+            "println" => Kind::Println,
+            "input" => Kind::Input,
             "let" => Kind::Let,
+            "mut" => Kind::Mut,
+            "const" => Kind::Const,
+            "struct" => Kind::Struct,
+            "fn" => Kind::Fn,
+            "return" => Kind::Return,
+            "if" => Kind::If,
+            "else" => Kind::Else,
+            "while" => Kind::While,
+            "do" => Kind::Do,
+            "for" => Kind::For,
+            "in" => Kind::In,
+            "break" => Kind::Break,
+            "continue" => Kind::Continue,
+            "assert" => Kind::Assert,
+            "import" => Kind::Import,
+            "module" => Kind::Module,
+            "pub" => Kind::Pub,
+            "true" => Kind::True,
+            "false" => Kind::False,
             _ => Kind::Identifier,
         }
     }
 
     /// Extract the value of a token based on its kind
-    fn extract_value(&self, kind: &Kind, start: usize, end: usize) -> TokenValue {
+    fn extract_value(
+        &self,
+        kind: &Kind,
+        start: usize,
+        end: usize,
+    ) -> Result<TokenValue<'a>, String> {
         match kind {
+            Kind::Integer => {
+                let num_str = &self.source[start..end];
+                num_str
+                    .parse::<i64>()
+                    .map(TokenValue::Integer)
+                    .map_err(|_| format!("Invalid integer: {}", num_str))
+            }
             Kind::Number => {
                 let num_str = &self.source[start..end];
-                if let Ok(num) = num_str.parse::<f64>() {
-                    TokenValue::Number(num)
-                } else {
-                    eprintln!("Invalid number: {}", num_str);
-                    TokenValue::None
-                }
+                num_str
+                    .parse::<f64>()
+                    .map(TokenValue::Number)
+                    .map_err(|_| format!("Invalid number: {}", num_str))
+            }
+            Kind::Identifier => Ok(TokenValue::String(Cow::Borrowed(&self.source[start..end]))),
+            Kind::String | Kind::StringEnd => {
+                let str_content = unescape(&self.source[start + 1..end - 1])?; // exclude quotes
+                Ok(TokenValue::String(str_content))
             }
-            Kind::Identifier => {
-                let str_content = &self.source[start..end];
-                TokenValue::String(Atom::from(str_content))
+            Kind::Char => {
+                let text = unescape(&self.source[start + 1..end - 1])?; // exclude quotes
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Ok(TokenValue::Char(c)),
+                    (None, _) => Err("Empty character literal".to_string()),
+                    (Some(_), Some(_)) => Err(format!(
+                        "Character literal '{}' holds more than one character",
+                        text
+                    )),
+                }
             }
-            Kind::String => {
-                let str_content = &self.source[start + 1..end - 1]; // exclude quotes
-                TokenValue::String(Atom::from(str_content))
+            Kind::StringStart | Kind::StringMiddle => {
+                // Exclude the opening quote or brace and the '${'
+                let str_content = unescape(&self.source[start + 1..end - 2])?;
+                Ok(TokenValue::String(str_content))
             }
-            _ => TokenValue::None,
+            _ => Ok(TokenValue::None),
         }
     }
 
@@ -224,12 +627,141 @@ impl<'a> Lexer<'a> {
         self.chars.clone().next()
     }
 
+    /// The character after the next one, without consuming anything
+    fn peek_second(&self) -> Option<char> {
+        self.chars.clone().nth(1)
+    }
+
     /// Consume and return the next character
     fn next_char(&mut self) -> Option<char> {
         let next = self.chars.next();
         if let Some(c) = next {
             self.current_pos += c.len_utf8();
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
         }
         next
     }
 }
+
+/// The tokens one at a time, read from the source as they are asked for.
+/// Whitespace and comments are skipped, and the last token is the end of file
+/// or the first error.
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Token<'a>, CompileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        loop {
+            let token = self.read_next_token();
+            match &token {
+                Ok(token) if token.kind.is_trivia() && !self.keep_trivia => continue,
+                Ok(token) if token.kind != Kind::Eof => {}
+                _ => self.finished = true,
+            }
+            return Some(token);
+        }
+    }
+}
+
+/// Tokens taken from an iterator as they are needed, with the next one
+/// looked at before it's consumed. This is all the parser needs, so a program
+/// can be parsed without holding every token at once.
+pub struct PeekableTokens<'a> {
+    tokens: Box<dyn Iterator<Item = Result<Token<'a>, CompileError>> + 'a>,
+
+    /// The next token
+    next: Token<'a>,
+
+    /// The last token consumed
+    previous: Option<Token<'a>>,
+
+    /// How many tokens have been consumed
+    consumed: usize,
+
+    /// The error that ended the tokens early
+    error: Option<CompileError>,
+}
+
+impl<'a> PeekableTokens<'a> {
+    /// Read from `tokens`, which stop at the first end of file or error. If
+    /// they run out without either, an end of file is made up.
+    pub fn new(tokens: impl Iterator<Item = Result<Token<'a>, CompileError>> + 'a) -> Self {
+        let mut peekable = Self {
+            tokens: Box::new(tokens),
+            next: end_of_file(Span::default()),
+            previous: None,
+            consumed: 0,
+            error: None,
+        };
+        peekable.next = peekable.read(Span::default());
+        peekable
+    }
+
+    /// The next token, without consuming it
+    pub fn peek(&self) -> &Token<'a> {
+        &self.next
+    }
+
+    /// The last token consumed, or the next one before any are
+    pub fn previous(&self) -> &Token<'a> {
+        self.previous.as_ref().unwrap_or(&self.next)
+    }
+
+    /// Consume the next token, staying at the end of file once it's reached
+    pub fn advance(&mut self) {
+        if self.next.kind == Kind::Eof {
+            return;
+        }
+        let after = self.next.span;
+        let next = self.read(after);
+        self.previous = Some(std::mem::replace(&mut self.next, next));
+        self.consumed += 1;
+    }
+
+    /// How many tokens have been consumed
+    pub fn position(&self) -> usize {
+        self.consumed
+    }
+
+    /// The error that ended the tokens early, if there was one
+    pub fn error(&self) -> Option<&CompileError> {
+        self.error.as_ref()
+    }
+
+    /// The token after `last`, ending the tokens at an error or once they
+    /// run out
+    fn read(&mut self, last: Span) -> Token<'a> {
+        match self.tokens.next() {
+            Some(Ok(token)) => token,
+            Some(Err(err)) => {
+                let span = err.span().unwrap_or(last);
+                self.error = Some(err);
+                end_of_file(span)
+            }
+            None => end_of_file(last),
+        }
+    }
+}
+
+impl<'a> From<&'a [Token<'a>]> for PeekableTokens<'a> {
+    fn from(tokens: &'a [Token<'a>]) -> Self {
+        Self::new(tokens.iter().cloned().map(Ok))
+    }
+}
+
+fn end_of_file(span: Span) -> Token<'static> {
+    Token {
+        kind: Kind::Eof,
+        start: span.offset,
+        end: span.offset,
+        span,
+        value: TokenValue::None,
+    }
+}
@@ -0,0 +1,146 @@
+//! The osho language compiler.
+//!
+//! Programs can either be compiled to C with [`compile_to_c`] (or to
+//! WebAssembly or JavaScript with [`compile_to_wasm`] and [`compile_to_js`])
+//! or run directly, with the tree-walking interpreter through [`interpret`]
+//! or with the bytecode VM through [`run_vm`].
+//!
+//! These functions compile the program as written. The `osho` binary also
+//! runs the [`optimizer`] over the AST first.
+
+pub mod ast;
+pub mod ast_printer;
+pub mod backend;
+pub mod builtins;
+pub mod bytecode;
+pub mod cache;
+pub mod checks;
+pub mod code_generator;
+pub mod debugger;
+pub mod desugar;
+pub mod diagnostics;
+pub mod driver;
+pub mod error;
+pub mod format;
+pub mod formatter;
+#[cfg(feature = "program-generator")]
+pub mod generator;
+pub mod highlight;
+pub mod interpreter;
+pub mod ir;
+pub mod js_generator;
+pub mod lexer;
+pub mod lint;
+pub mod lsp;
+pub mod modules;
+pub mod optimizer;
+pub mod parser;
+pub mod repl;
+pub mod semantic_analyzer;
+pub mod stdlib;
+pub mod symbol_table;
+pub mod timings;
+pub mod trace;
+pub mod verify;
+pub mod visitor;
+pub mod wasm_generator;
+pub mod watch;
+
+pub use crate::backend::{Backend, Target};
+pub use crate::builtins::Builtin;
+pub use crate::bytecode::{Chunk, Vm};
+pub use crate::checks::Checks;
+pub use crate::code_generator::CodeGenerator;
+pub use crate::diagnostics::{Diagnostic, Lint, LintLevels, Severity};
+pub use crate::driver::CCompiler;
+pub use crate::error::CompileError;
+pub use crate::highlight::{highlight, TokenClass};
+pub use crate::interpreter::{Interpreter, Value};
+pub use crate::js_generator::JsGenerator;
+pub use crate::lexer::{Kind, Lexer, PeekableTokens, Token, TokenValue};
+pub use crate::modules::{Loader, SourceMap};
+pub use crate::parser::{ASTNode, Ast, NodeId, Parser};
+pub use crate::repl::Repl;
+pub use crate::semantic_analyzer::SemanticAnalyzer;
+pub use crate::symbol_table::{Symbol, SymbolTable};
+pub use crate::wasm_generator::WasmGenerator;
+
+/// What an interpreted program produced
+#[derive(Debug, Clone, PartialEq)]
+pub struct Output {
+    /// Everything the program printed
+    pub stdout: String,
+
+    /// The code the program exited with, 0 unless it called `exit()`
+    pub exit_code: i32,
+}
+
+/// Lex, parse and check a program, reporting every error found
+pub fn parse_and_check(source: &str) -> Result<Ast, Vec<CompileError>> {
+    let ast = Parser::from_lexer(Lexer::new(source)).parse()?;
+
+    let mut analyzer = SemanticAnalyzer::new();
+    analyzer.analyze_all(&ast)?;
+    Ok(ast)
+}
+
+/// The exit code of a program that ran to its end or called `exit()`,
+/// passing on any other error
+fn exit_code(result: Result<(), CompileError>) -> Result<i32, CompileError> {
+    match result {
+        Ok(()) => Ok(0),
+        Err(CompileError::Exit { code, .. }) => Ok(code),
+        Err(err) => Err(err),
+    }
+}
+
+/// The first of a non-empty list of errors
+fn first_error(mut errors: Vec<CompileError>) -> CompileError {
+    errors.remove(0)
+}
+
+/// Compile a program to source code for `target`
+pub fn compile(source: &str, target: &Target) -> Result<String, CompileError> {
+    let ast = parse_and_check(source).map_err(first_error)?;
+    target.backend(Checks::default()).generate(&ast)
+}
+
+/// Compile a program to C source code
+pub fn compile_to_c(source: &str) -> Result<String, CompileError> {
+    let ast = parse_and_check(source).map_err(first_error)?;
+    let mut generator = CodeGenerator::new();
+    generator.generate(&ast)
+}
+
+/// Compile a program to a WebAssembly text module
+pub fn compile_to_wasm(source: &str) -> Result<String, CompileError> {
+    let ast = parse_and_check(source).map_err(first_error)?;
+    let mut generator = WasmGenerator::new();
+    generator.generate(&ast)
+}
+
+/// Compile a program to JavaScript
+pub fn compile_to_js(source: &str) -> Result<String, CompileError> {
+    let ast = parse_and_check(source).map_err(first_error)?;
+    let mut generator = JsGenerator::new();
+    generator.generate(&ast)
+}
+
+/// Run a program with the interpreter, capturing what it prints
+pub fn interpret(source: &str) -> Result<Output, CompileError> {
+    let ast = parse_and_check(source).map_err(first_error)?;
+    let mut interpreter = Interpreter::with_output(Vec::new());
+    let exit_code = exit_code(interpreter.run(&ast, ast.root()))?;
+    let stdout = String::from_utf8_lossy(&interpreter.into_output()).into_owned();
+    Ok(Output { stdout, exit_code })
+}
+
+/// Compile a program to bytecode and run it on the VM, capturing what it prints
+pub fn run_vm(source: &str) -> Result<Output, CompileError> {
+    let ast = parse_and_check(source).map_err(first_error)?;
+    let chunk = bytecode::Compiler::new().compile(&ast)?;
+    let mut vm = Vm::with_output(Vec::new());
+    let exit_code = exit_code(vm.run(&chunk))?;
+    let stdout = String::from_utf8_lossy(&vm.into_output()).into_owned();
+    Ok(Output { stdout, exit_code })
+}
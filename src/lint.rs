@@ -0,0 +1,216 @@
+//! `osho lint`, style and correctness checks that don't stop a program from
+//! compiling.
+//!
+//! The semantic analyzer already warns about unused and shadowed variables
+//! while checking the program. This pass adds the rules that only need the
+//! syntax tree: conditions that never change, assignments of a value to
+//! itself, and names that don't follow the naming conventions.
+
+use crate::ast_printer;
+use crate::diagnostics::{Diagnostic, Lint};
+use crate::lexer::Span;
+use crate::optimizer;
+use crate::parser::{ASTNode, Ast, NodeId};
+use crate::visitor::Visitor;
+
+/// Lint a program, giving warnings in source order
+pub fn lint(ast: &Ast) -> Vec<Diagnostic> {
+    let mut linter = Linter::default();
+    linter.visit(ast, ast.root());
+    linter
+        .warnings
+        .sort_by_key(|warning| warning.span.map(|span| span.offset));
+    linter.warnings
+}
+
+#[derive(Default)]
+struct Linter {
+    warnings: Vec<Diagnostic>,
+}
+
+impl Visitor for Linter {
+    fn visit(&mut self, ast: &Ast, node: NodeId) {
+        match &ast[node] {
+            ASTNode::LetDeclaration { name, span, .. } => {
+                self.snake_case("Variable", name, *span);
+            }
+            ASTNode::ConstDeclaration { name, span, .. } => {
+                let item = unqualified(name);
+                if item.chars().any(char::is_lowercase) {
+                    self.style(
+                        format!(
+                            "Constant '{}' should be upper case, like '{}'",
+                            item,
+                            item.to_uppercase()
+                        ),
+                        *span,
+                    );
+                }
+            }
+            ASTNode::StructDeclaration { name, span, .. } => {
+                let item = unqualified(name);
+                if item.starts_with(|c: char| c.is_lowercase()) {
+                    let mut chars = item.chars();
+                    let capitalized: String = chars
+                        .next()
+                        .into_iter()
+                        .flat_map(char::to_uppercase)
+                        .chain(chars)
+                        .collect();
+                    self.style(
+                        format!(
+                            "Struct '{}' should start with a capital letter, like '{}'",
+                            item, capitalized
+                        ),
+                        *span,
+                    );
+                }
+            }
+            ASTNode::FunctionDeclaration {
+                name, params, span, ..
+            } => {
+                self.snake_case("Function", name, *span);
+                for param in params {
+                    self.snake_case("Parameter", &param.name, *span);
+                }
+            }
+            ASTNode::Assignment { name, value, span } => {
+                if matches!(&ast[*value], ASTNode::Identifier { name: read, .. } if read == name) {
+                    self.self_assignment(*span);
+                }
+            }
+            ASTNode::IndexAssignment {
+                array,
+                index,
+                value,
+                span,
+            } => {
+                if let ASTNode::Index {
+                    array: read_array,
+                    index: read_index,
+                    ..
+                } = &ast[*value]
+                {
+                    if same(ast, *array, *read_array) && same(ast, *index, *read_index) {
+                        self.self_assignment(*span);
+                    }
+                }
+            }
+            ASTNode::FieldAssignment {
+                object,
+                field,
+                value,
+                span,
+            } => {
+                if let ASTNode::FieldAccess {
+                    object: read_object,
+                    field: read_field,
+                    ..
+                } = &ast[*value]
+                {
+                    if field == read_field && same(ast, *object, *read_object) {
+                        self.self_assignment(*span);
+                    }
+                }
+            }
+            ASTNode::If {
+                condition, span, ..
+            }
+            | ASTNode::Conditional {
+                condition, span, ..
+            }
+            | ASTNode::For {
+                condition: Some(condition),
+                span,
+                ..
+            } => self.constant_condition(ast, *condition, *span),
+            // `while true` is how a loop that ends with `break` is written
+            ASTNode::While {
+                condition, span, ..
+            }
+            | ASTNode::DoWhile {
+                condition, span, ..
+            } if !matches!(ast[*condition], ASTNode::Bool(true)) => {
+                self.constant_condition(ast, *condition, *span);
+            }
+            // Including `assert`: `assert false` marks code that must not
+            // be reached, so constant conditions are fine
+            _ => {}
+        }
+        self.walk(ast, node);
+    }
+}
+
+impl Linter {
+    /// Warn when a condition is made only of literals
+    fn constant_condition(&mut self, ast: &Ast, condition: NodeId, span: Span) {
+        if let Ok(value) = optimizer::evaluate_constant(ast, condition, &|_| None) {
+            let span = Some(ast[condition].span())
+                .filter(|span| *span != Span::default())
+                .unwrap_or(span);
+            self.warnings.push(Diagnostic::warning(
+                Lint::ConstantCondition,
+                format!("Condition is always {}", value.is_truthy()),
+                Some(span),
+            ));
+        }
+    }
+
+    fn self_assignment(&mut self, span: Span) {
+        self.warnings.push(
+            Diagnostic::warning(
+                Lint::SelfAssignment,
+                "Value is assigned to itself",
+                Some(span),
+            )
+            .with_note("the assignment doesn't change anything"),
+        );
+    }
+
+    /// Warn when a name that should be snake case has capital letters
+    fn snake_case(&mut self, what: &str, name: &str, span: Span) {
+        let item = unqualified(name);
+        if !item.chars().any(char::is_uppercase) {
+            return;
+        }
+        let mut snake = String::new();
+        let chars: Vec<char> = item.chars().collect();
+        for (i, &c) in chars.iter().enumerate() {
+            // A word starts at a capital after a lower case letter, or at the
+            // last capital of an acronym, as in `HTTPCode`
+            let previous = i.checked_sub(1).map(|i| chars[i]);
+            let next = chars.get(i + 1);
+            let starts_word = previous.is_some_and(|previous| {
+                previous.is_lowercase()
+                    || previous.is_ascii_digit()
+                    || (previous.is_uppercase() && next.is_some_and(|next| next.is_lowercase()))
+            });
+            if c.is_uppercase() && starts_word {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        }
+        self.style(
+            format!("{} '{}' should be snake case, like '{}'", what, item, snake),
+            span,
+        );
+    }
+
+    fn style(&mut self, message: String, span: Span) {
+        self.warnings.push(Diagnostic::warning(
+            Lint::IdentifierStyle,
+            message,
+            Some(span),
+        ));
+    }
+}
+
+/// The name of a module item without its module
+fn unqualified(name: &str) -> &str {
+    name.rsplit('.').next().unwrap_or(name)
+}
+
+/// Whether two expressions are written the same, wherever they are
+fn same(ast: &Ast, a: NodeId, b: NodeId) -> bool {
+    ast_printer::to_sexpr(ast, a) == ast_printer::to_sexpr(ast, b)
+}
@@ -0,0 +1,493 @@
+//! `osho lsp`, a Language Server Protocol server over stdin and stdout.
+//!
+//! The server keeps the text of each open document, checks it after every
+//! change and publishes its errors and warnings. Go-to-definition and hover
+//! use the symbol table of the last check, and document symbols list the
+//! declarations at the top of the file.
+//!
+//! Columns are counted in characters, which are the protocol's UTF-16 code
+//! units except for characters outside the Basic Multilingual Plane.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value as Json};
+
+use crate::diagnostics::{Diagnostic, LintLevels, Severity};
+use crate::lexer::{Kind, Lexer, Span};
+use crate::modules::{Loader, SourceMap};
+use crate::parser::{ASTNode, Ast};
+use crate::semantic_analyzer::SemanticAnalyzer;
+use crate::symbol_table::{Symbol, SymbolTable};
+
+/// JSON-RPC error code for a request the server doesn't implement
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// `SymbolKind`s of the protocol
+const FUNCTION: u32 = 12;
+const VARIABLE: u32 = 13;
+const CONSTANT: u32 = 14;
+const STRUCT: u32 = 23;
+
+/// A language server that reads requests from a reader and writes responses
+/// and notifications to `out`
+pub struct Server<W: Write> {
+    out: W,
+
+    /// Open documents by URI
+    documents: HashMap<String, Document>,
+
+    /// Whether the client asked the server to shut down
+    shutdown: bool,
+}
+
+/// An open document and what the last check of it found
+struct Document {
+    text: String,
+    sources: SourceMap,
+
+    /// The program, unless it didn't parse
+    ast: Option<Ast>,
+
+    /// The variables of the program, unless it didn't parse
+    symbols: Option<SymbolTable>,
+
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Document {
+    fn new(uri: &str, text: String) -> Self {
+        let mut loader = Loader::new();
+        let loaded = loader.load(&uri_to_path(uri), &text);
+        let mut document = Self {
+            sources: loader.sources().clone(),
+            text,
+            ast: None,
+            symbols: None,
+            diagnostics: Vec::new(),
+        };
+        let ast = match loaded {
+            Ok(ast) => ast,
+            Err(errors) => {
+                document.diagnostics = errors.iter().map(Diagnostic::from).collect();
+                return document;
+            }
+        };
+        let mut analyzer = SemanticAnalyzer::new();
+        if let Err(errors) = analyzer.analyze_all(&ast) {
+            document.diagnostics = errors.iter().map(Diagnostic::from).collect();
+        }
+        let warnings = LintLevels::default().apply(analyzer.take_warnings());
+        document.diagnostics.extend(warnings);
+        document.symbols = Some(analyzer.symbols().clone());
+        document.ast = Some(ast);
+        document
+    }
+
+    /// Byte offset of an LSP position in the document
+    fn offset(&self, position: &Json) -> Option<usize> {
+        let line = position["line"].as_u64()? as usize;
+        let character = position["character"].as_u64()? as usize;
+        let start: usize = self
+            .text
+            .split_inclusive('\n')
+            .take(line)
+            .map(str::len)
+            .sum();
+        let rest = &self.text[start..];
+        let column = rest
+            .char_indices()
+            .nth(character)
+            .map_or(rest.len(), |(i, _)| i);
+        Some(start + column)
+    }
+
+    /// Whether a span is in the document itself rather than a file it
+    /// imports
+    fn contains(&self, span: Span) -> bool {
+        self.sources
+            .file(Some(span))
+            .is_some_and(|file| file.base_offset == 0)
+    }
+
+    /// Where the name at `offset` is declared
+    fn definition(&self, offset: usize) -> Option<Span> {
+        match self.symbol(offset) {
+            Some(symbol) => Some(symbol.span),
+            None => self.declaration(offset).map(ASTNode::span),
+        }
+    }
+
+    /// Markdown describing the name at `offset`
+    fn hover(&self, offset: usize) -> Option<String> {
+        let text = match self.symbol(offset) {
+            Some(symbol) => match &symbol.constant {
+                Some(value) => format!("const {}: {} = {}", symbol.name, symbol.ty, value),
+                None if symbol.mutable => format!("let mut {}: {}", symbol.name, symbol.ty),
+                None => format!("let {}: {}", symbol.name, symbol.ty),
+            },
+            None => signature(self.declaration(offset)?)?,
+        };
+        Some(format!("```osho\n{}\n```", text))
+    }
+
+    /// The variable declared or used at `offset`. Parameters are declared at
+    /// the name of their function, so the name has to match too.
+    fn symbol(&self, offset: usize) -> Option<&Symbol> {
+        let symbols = self.symbols.as_ref()?;
+        let symbol = symbols.get(symbols.at(offset)?);
+        (Some(&symbol.name) == self.name_at(offset).as_ref()).then_some(symbol)
+    }
+
+    /// The top-level function, struct or constant named at `offset`
+    fn declaration(&self, offset: usize) -> Option<&ASTNode> {
+        let name = self.name_at(offset)?;
+        let ast = self.ast.as_ref()?;
+        let statements = || ast.statements().iter().map(|&statement| &ast[statement]);
+        // Inside a module, its own items are used without the module's name
+        statements()
+            .find(|statement| declared(statement) == Some(name.as_str()))
+            .or_else(|| {
+                statements().find(|statement| {
+                    declared(statement)
+                        .is_some_and(|declared| declared.rsplit('.').next() == Some(&name))
+                })
+            })
+    }
+
+    /// The name at `offset`, with its module if it's written `module.item`
+    fn name_at(&self, offset: usize) -> Option<String> {
+        let tokens = Lexer::new(&self.text).get_tokens().ok()?;
+        let index = tokens.iter().position(|token| {
+            token.kind == Kind::Identifier && token.start <= offset && offset <= token.end
+        })?;
+        let text = |index: usize| &self.text[tokens[index].start..tokens[index].end];
+        if index >= 2
+            && tokens[index - 1].kind == Kind::Dot
+            && tokens[index - 2].kind == Kind::Identifier
+        {
+            return Some(format!("{}.{}", text(index - 2), text(index)));
+        }
+        Some(text(index).to_string())
+    }
+
+    /// The declarations at the top of the document
+    fn symbols(&self) -> Vec<Json> {
+        let Some(ast) = &self.ast else {
+            return Vec::new();
+        };
+        ast.statements()
+            .iter()
+            .map(|&statement| &ast[statement])
+            .filter(|statement| self.contains(statement.span()))
+            .filter_map(|statement| {
+                let (name, kind) = match statement {
+                    ASTNode::FunctionDeclaration { name, .. } => (name, FUNCTION),
+                    ASTNode::StructDeclaration { name, .. } => (name, STRUCT),
+                    ASTNode::ConstDeclaration { name, .. } => (name, CONSTANT),
+                    ASTNode::LetDeclaration { name, .. } => (name, VARIABLE),
+                    _ => return None,
+                };
+                let range = range(statement.span());
+                Some(json!({
+                    "name": name,
+                    "kind": kind,
+                    "range": range,
+                    "selectionRange": range,
+                }))
+            })
+            .collect()
+    }
+
+    /// The diagnostics as the protocol sends them. Those in imported files
+    /// are shown at the top of the document.
+    fn lsp_diagnostics(&self) -> Vec<Json> {
+        self.diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let mut message = diagnostic.message.clone();
+                let span = match diagnostic.span {
+                    Some(span) if self.contains(span) => span,
+                    Some(span) => {
+                        if let Some(file) = self.sources.file(Some(span)) {
+                            message = format!("{}: {}", file.name, message);
+                        }
+                        Span::default()
+                    }
+                    None => Span::default(),
+                };
+                for note in &diagnostic.notes {
+                    message.push_str(&format!("\nnote: {}", note));
+                }
+                let severity = match diagnostic.severity {
+                    Severity::Error => 1,
+                    Severity::Warning => 2,
+                    Severity::Note => 3,
+                };
+                json!({
+                    "range": range(span),
+                    "severity": severity,
+                    "code": diagnostic.code,
+                    "source": "osho",
+                    "message": message,
+                })
+            })
+            .collect()
+    }
+}
+
+impl<W: Write> Server<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            documents: HashMap::new(),
+            shutdown: false,
+        }
+    }
+
+    /// Serve requests until the client sends `exit` or closes the input
+    pub fn run(&mut self, mut input: impl BufRead) -> io::Result<()> {
+        while let Some(message) = read_message(&mut input)? {
+            let Ok(message) = serde_json::from_str::<Json>(&message) else {
+                continue;
+            };
+            if message["method"] == "exit" {
+                break;
+            }
+            self.handle(&message)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the client asked the server to shut down before exiting
+    pub fn is_shut_down(&self) -> bool {
+        self.shutdown
+    }
+
+    fn handle(&mut self, message: &Json) -> io::Result<()> {
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let result = match message["method"].as_str().unwrap_or_default() {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": { "name": "osho" },
+            }),
+            "shutdown" => {
+                self.shutdown = true;
+                Json::Null
+            }
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                return self.update(uri, text.to_string());
+            }
+            "textDocument/didChange" => {
+                // Every change holds the whole text, as asked for by
+                // `textDocumentSync`
+                let changes = params["contentChanges"].as_array();
+                let Some(text) = changes.and_then(|changes| changes.last()) else {
+                    return Ok(());
+                };
+                let text = text["text"].as_str().unwrap_or_default();
+                return self.update(uri, text.to_string());
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return self.notify(
+                    "textDocument/publishDiagnostics",
+                    json!({ "uri": uri, "diagnostics": [] }),
+                );
+            }
+            "textDocument/definition" => self
+                .at_position(uri, params, Document::definition)
+                .and_then(|(document, span)| location(uri, document, span))
+                .unwrap_or(Json::Null),
+            "textDocument/hover" => self
+                .at_position(uri, params, Document::hover)
+                .map(|(_, text)| json!({ "contents": { "kind": "markdown", "value": text } }))
+                .unwrap_or(Json::Null),
+            "textDocument/documentSymbol" => match self.documents.get(uri) {
+                Some(document) => Json::Array(document.symbols()),
+                None => Json::Null,
+            },
+            method => {
+                // Notifications the server doesn't use need no answer
+                if message.get("id").is_none() {
+                    return Ok(());
+                }
+                let error = json!({
+                    "code": METHOD_NOT_FOUND,
+                    "message": format!("Unknown method '{}'", method),
+                });
+                return self.send(json!({ "jsonrpc": "2.0", "id": message["id"], "error": error }));
+            }
+        };
+        self.send(json!({ "jsonrpc": "2.0", "id": message["id"], "result": result }))
+    }
+
+    /// Check a document's new text and publish what was found
+    fn update(&mut self, uri: &str, text: String) -> io::Result<()> {
+        let document = Document::new(uri, text);
+        let diagnostics = document.lsp_diagnostics();
+        self.documents.insert(uri.to_string(), document);
+        self.notify(
+            "textDocument/publishDiagnostics",
+            json!({ "uri": uri, "diagnostics": diagnostics }),
+        )
+    }
+
+    /// Answer a question about the position in a request's parameters
+    fn at_position<T>(
+        &self,
+        uri: &str,
+        params: &Json,
+        answer: impl Fn(&Document, usize) -> Option<T>,
+    ) -> Option<(&Document, T)> {
+        let document = self.documents.get(uri)?;
+        let offset = document.offset(&params["position"])?;
+        Some((document, answer(document, offset)?))
+    }
+
+    fn notify(&mut self, method: &str, params: Json) -> io::Result<()> {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    fn send(&mut self, message: Json) -> io::Result<()> {
+        let body = message.to_string();
+        write!(self.out, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+        self.out.flush()
+    }
+}
+
+/// Read the body of the next message, or `None` at the end of the input
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let Some(length) = length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Message without a Content-Length header",
+        ));
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// The LSP range of a span, which is on one line
+fn range(span: Span) -> Json {
+    let line = span.line.saturating_sub(1);
+    let start = span.column.saturating_sub(1);
+    json!({
+        "start": { "line": line, "character": start },
+        "end": { "line": line, "character": start + span.length },
+    })
+}
+
+/// The location of a span, in the document or a file it imports
+fn location(uri: &str, document: &Document, span: Span) -> Option<Json> {
+    let uri = if document.contains(span) {
+        uri.to_string()
+    } else {
+        path_to_uri(Path::new(&document.sources.file(Some(span))?.name))
+    };
+    Some(json!({ "uri": uri, "range": range(span) }))
+}
+
+/// The name a top-level function, struct or constant declares
+fn declared(statement: &ASTNode) -> Option<&str> {
+    match statement {
+        ASTNode::FunctionDeclaration { name, .. }
+        | ASTNode::StructDeclaration { name, .. }
+        | ASTNode::ConstDeclaration { name, .. } => Some(name),
+        _ => None,
+    }
+}
+
+/// How a function or struct is declared, without its body
+fn signature(declaration: &ASTNode) -> Option<String> {
+    match declaration {
+        ASTNode::FunctionDeclaration {
+            name,
+            params,
+            result,
+            ..
+        } => {
+            let params: Vec<_> = params
+                .iter()
+                .map(|param| match &param.ty {
+                    Some(ty) => format!("{}: {}", param.name, ty),
+                    None => param.name.clone(),
+                })
+                .collect();
+            let result = result
+                .as_ref()
+                .map(|ty| format!(" -> {}", ty))
+                .unwrap_or_default();
+            Some(format!("fn {}({}){}", name, params.join(", "), result))
+        }
+        ASTNode::StructDeclaration { name, fields, .. } => {
+            Some(format!("struct {} {{ {} }}", name, fields.join(", ")))
+        }
+        ASTNode::ConstDeclaration { name, .. } => Some(format!("const {}", name)),
+        _ => None,
+    }
+}
+
+/// The path of a `file://` URI
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| path.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+/// The `file://` URI of a path
+fn path_to_uri(path: &Path) -> String {
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
@@ -1,62 +1,1170 @@
-
-use std::fs::{read_to_string, File};
-use std::io::Write;
+use std::env;
+use std::fs::{self, read_to_string, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::thread;
 
-mod lexer;
-mod parser;
-mod semantic_analyzer;
-mod code_generator;
+use osho_lang::cache::{BuildCache, BuildKey, CACHE_DIR};
+use osho_lang::debugger::{self, Debugger};
+use osho_lang::driver;
+use osho_lang::interpreter::DEFAULT_MAX_CALL_DEPTH;
+use osho_lang::lsp::Server;
+use osho_lang::timings::Timings;
+use osho_lang::trace::Tracer;
+use osho_lang::verify;
+use osho_lang::watch::{self, Watcher};
+use osho_lang::{
+    ast_printer, backend, bytecode, formatter, highlight, ir, lint, optimizer, Ast, CCompiler,
+    Checks, CodeGenerator, CompileError, Diagnostic, Interpreter, Lexer, Lint, LintLevels, Loader,
+    Repl, SemanticAnalyzer, Severity, SourceMap, Target, Vm,
+};
 
-use crate::lexer::Lexer;
-use crate::parser::Parser;
-use crate::semantic_analyzer::SemanticAnalyzer;
-use code_generator::CodeGenerator;
+const USAGE: &str = "\
+Usage: osho <file.osho> [options] [-- <args>...]
+       osho - [options]      (read the program from stdin)
+       osho run <file.osho> [--backend interp|vm] [-- <args>...]
+       osho debug <file.osho>
+       osho verify <file.osho> [--cc <compiler>]
+       osho watch <file.osho> [--backend interp|vm]
+       osho repl
+       osho fmt [--check] <file.osho>...
+       osho lint <file.osho> [--warn <lint>] [--allow <lint>] [--config <path>]
+       osho lsp
 
-fn main() {
-    let file_path = "./test.osho";
+`osho run` executes the program without a C compiler, using the tree-walking
+interpreter (the default) or the bytecode VM.
+
+Arguments after `--` are passed to the program, which reads them with
+`args()`, `argc()` and `argv(i)`, whether it is interpreted or compiled and
+run with --run.
+
+`osho debug` runs the program in the interpreter, stopping before the first
+statement to read commands: `break <line>`, `delete <line>`, `step`, `next`,
+`continue`, `print <name>`, `vars`, `where`, `quit` and `help`.
+
+`osho verify` runs the program with the interpreter and as a compiled
+executable, giving both what it reads from stdin, and fails if they print
+different output or only one of them fails.
+
+A program stops with exit code 1 when it doesn't compile, 70 when it fails
+at runtime, for example by indexing past the end of an array, and n when it
+calls `exit(n)`. `--run` passes on the executable's exit code the same way.
+
+`osho watch` runs the program like `osho run`, then again each time it or a
+file it imports is saved, until it's stopped with Ctrl-C.
+
+`osho fmt` rewrites files in the canonical layout, or prints a program read
+from stdin (`-`) formatted. With --check it only lists the files that aren't
+formatted, and fails if there are any.
+
+`osho lint` checks a program with every lint, including constant-condition,
+self-assignment and identifier-style, which compiling doesn't run. Lints are
+configured by --config <path>, or a .osholint file in the current directory,
+with lines like `allow shadowing`, `warn shadowing` or `deny-warnings`.
+Flags override the file.
+
+Builds are cached in .osho-cache/, keyed by a hash of the source files and
+the options that change the executable. When none of them has changed, the C
+source and executable of the last build are reused instead of being generated
+and compiled again.
+
+`osho lsp` runs a Language Server Protocol server over stdin and stdout for
+editors: diagnostics, go to definition, hover and document symbols.
+
+Files included with `import \"file.osho\"` are found relative to the file that
+imports them. The standard library is built in: `import std.math` (gcd, lcm,
+factorial, clamp, sign, is_even) and `import std.list` (sum, product,
+index_of, contains, reversed, largest).
+
+Options:
+    -o <name>       Name of the output executable (default: input file stem, or a
+                    temporary file that is deleted afterwards when using --run)
+    --c-out <path>  Write the generated C source to <path>
+    --keep-c        Keep the generated C source as <name>.c
+    --check         Only check the program for errors, don't run or compile it
+    --no-opt        Don't optimize the program, e.g. by folding constants
+    --checked-arith Stop the program with an error when it divides by zero or
+                    gets NaN, in the interpreter, the VM and the executable,
+                    instead of carrying on with inf or NaN
+    --trap-overflow Stop the program with an error when integer arithmetic
+                    overflows, instead of wrapping around
+    --warn <lint>   Report warnings from a lint: unused-variables,
+                    division-by-zero, unused-functions, constant-condition,
+                    self-assignment, identifier-style (on by default) or
+                    shadowing
+    --allow <lint>  Don't report warnings from a lint
+    --deny-warnings Treat warnings as errors
+    --emit <stage>  Stop after a stage of the pipeline and output its result:
+                      tokens       print the token stream
+                      tokens-json  print the token stream as JSON
+                      highlights-json
+                                   print the class of each token, comments
+                                   included, as JSON for syntax highlighting
+                      ast          print the syntax tree
+                      ast-json     print the syntax tree as JSON, a list of
+                                   nodes that refer to their children by index
+                      sexpr        print the syntax tree as an S-expression
+                      ir           print the typed intermediate representation
+                      bytecode     print the VM instructions
+                      c            write the C source (<name>.c or --c-out), don't invoke gcc
+                      binary       interpret the program and build the executable (default)
+    --emit-c        Same as --emit c
+    --target <t>    Code to generate: c (default), wasm or js. wasm writes a
+                    WebAssembly text module to <name>.wat and js a script to
+                    <name>.js instead of building an executable
+    --run           Run the executable after compiling it
+    --cc <compiler> C compiler to use (default: $OSHO_CC, or the first of gcc,
+                    clang and cc that is installed)
+    --force         Build again even if the build cache has the program
+    --opt <level>   Optimization level 0 to 3 for the C compiler (-O<level>)
+    --cflags <...>  Extra flags for the C compiler, e.g. --cflags \"-g -Wall\"
+    --max-call-depth <n>
+                    Stop the interpreter and VM when calls nest deeper than
+                    <n> (default: 1000)
+    --trace         Log each statement the interpreter runs to stderr, with
+                    its line and the variables it reads and writes
+    --trace-file <path>
+                    Write the --trace log to <path> instead
+    --timings       Print how long each phase of the compiler took to stderr
+    -h, --help      Print this help message";
+
+/// Input path that means "read the program from stdin"
+const STDIN_PATH: &str = "-";
+
+/// Stack the interpreter's thread gets besides what its calls need
+const BASE_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Stack reserved for each nested call in the interpreter, enough for
+/// unoptimized builds, whose frames are much bigger
+const STACK_PER_CALL: usize = 128 * 1024;
+
+/// The stage of the pipeline to stop after
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Emit {
+    Tokens,
+    TokensJson,
+    HighlightsJson,
+    Ast,
+    AstJson,
+    Sexpr,
+    Ir,
+    Bytecode,
+    C,
+    Binary,
+}
+
+impl Emit {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "tokens" => Ok(Emit::Tokens),
+            "tokens-json" => Ok(Emit::TokensJson),
+            "highlights-json" => Ok(Emit::HighlightsJson),
+            "ast" => Ok(Emit::Ast),
+            "ast-json" => Ok(Emit::AstJson),
+            "sexpr" => Ok(Emit::Sexpr),
+            "ir" => Ok(Emit::Ir),
+            "bytecode" => Ok(Emit::Bytecode),
+            "c" => Ok(Emit::C),
+            "binary" => Ok(Emit::Binary),
+            _ => Err(format!(
+                "Unknown --emit stage '{}', expected tokens, tokens-json, highlights-json, ast, ast-json, sexpr, ir, bytecode, c or binary",
+                name
+            )),
+        }
+    }
+}
+
+/// The commands that share the compiler's pipeline
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Subcommand {
+    /// Build an executable, or output a stage of the pipeline
+    Compile,
+
+    /// `osho run`, which only executes the program
+    Run,
+
+    /// `osho debug`, which runs the program under the debugger
+    Debug,
+
+    /// `osho verify`, which checks the interpreter against the compiled
+    /// program
+    Verify,
+}
+
+/// How `osho run` executes the program
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RunBackend {
+    Interp,
+    Vm,
+}
+
+impl RunBackend {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "interp" => Ok(RunBackend::Interp),
+            "vm" => Ok(RunBackend::Vm),
+            _ => Err(format!("Unknown backend '{}', expected interp or vm", name)),
+        }
+    }
+}
+
+/// Command line options
+struct Options {
+    /// Path of the osho source file, or "-" for stdin
+    input: String,
+
+    /// Name of the output executable given with `-o`
+    output: Option<String>,
+
+    /// Where to write the generated C source, given with `--c-out`
+    c_out: Option<String>,
+
+    /// Keep the generated C source next to the executable
+    keep_c: bool,
+
+    /// Stop after semantic analysis
+    check: bool,
+
+    /// Skip the optimizer
+    no_opt: bool,
+
+    /// Optional runtime checks, like `--checked-arith`
+    checks: Checks,
+
+    /// Which warnings are reported, and whether they're fatal
+    lints: LintLevels,
+
+    /// What to output
+    emit: Emit,
+
+    /// Run the executable after compiling
+    run: bool,
+
+    /// What to generate code for
+    target: &'static Target,
+
+    /// Backend for `osho run`, which only executes the program when set
+    backend: Option<RunBackend>,
+
+    /// C compiler given with `--cc`
+    cc: Option<String>,
+
+    /// Optimization level given with `--opt`
+    opt_level: Option<u8>,
+
+    /// Extra C compiler flags given with `--cflags`
+    cflags: Vec<String>,
+
+    /// How deeply calls may nest when the program is interpreted
+    max_call_depth: usize,
+
+    /// Log each statement the interpreter runs
+    trace: bool,
+
+    /// File given with `--trace-file` for the log, instead of stderr
+    trace_file: Option<String>,
+
+    /// Print the time each phase took
+    timings: bool,
+
+    /// Build without looking in the build cache
+    force: bool,
+
+    /// Arguments after `--`, which are passed to the program
+    args: Vec<String>,
+}
+
+impl Options {
+    /// Name of the executable: the `-o` name or the input file stem
+    fn output_name(&self) -> String {
+        if let Some(output) = &self.output {
+            return output.clone();
+        }
+        if self.input == STDIN_PATH {
+            return "output".to_string();
+        }
+        Path::new(&self.input)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("output")
+            .to_string()
+    }
+
+    /// Name of the input shown in diagnostics
+    fn file_name(&self) -> &str {
+        if self.input == STDIN_PATH {
+            "<stdin>"
+        } else {
+            &self.input
+        }
+    }
+}
+
+/// Parse the command line arguments (without the program name)
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut input = None;
+    let mut output = None;
+    let mut c_out = None;
+    let mut keep_c = false;
+    let mut check = false;
+    let mut no_opt = false;
+    let mut checks = Checks::default();
+    let mut lints = LintLevels::default();
+    let mut emit = Emit::Binary;
+    let mut run = false;
+    let mut target = &backend::TARGETS[0];
+    let mut backend = None;
+    let mut cc = None;
+    let mut opt_level = None;
+    let mut cflags = Vec::new();
+    let mut max_call_depth = DEFAULT_MAX_CALL_DEPTH;
+    let mut trace = false;
+    let mut trace_file = None;
+    let mut timings = false;
+    let mut force = false;
+    let mut program_args = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-o" => {
+                let name = iter.next().ok_or("Expected a name after '-o'")?;
+                output = Some(name.clone());
+            }
+            "--c-out" => {
+                let path = iter.next().ok_or("Expected a path after '--c-out'")?;
+                c_out = Some(path.clone());
+            }
+            "--keep-c" => keep_c = true,
+            "--check" => check = true,
+            "--no-opt" => no_opt = true,
+            "--checked-arith" => checks.arith = true,
+            "--trap-overflow" => checks.overflow = true,
+            "--warn" => {
+                let name = iter.next().ok_or("Expected a lint after '--warn'")?;
+                lints.warn(Lint::from_name(name)?);
+            }
+            "--allow" => {
+                let name = iter.next().ok_or("Expected a lint after '--allow'")?;
+                lints.allow(Lint::from_name(name)?);
+            }
+            "--deny-warnings" => lints.deny_warnings = true,
+            "--emit" => {
+                let stage = iter.next().ok_or("Expected a stage after '--emit'")?;
+                emit = Emit::parse(stage)?;
+            }
+            "--emit-c" => emit = Emit::C,
+            "--run" => run = true,
+            "--target" => {
+                let name = iter.next().ok_or("Expected a target after '--target'")?;
+                target = backend::target(name)?;
+            }
+            "--backend" => {
+                let name = iter.next().ok_or("Expected a backend after '--backend'")?;
+                backend = Some(RunBackend::parse(name)?);
+            }
+            "--cc" => {
+                let compiler = iter.next().ok_or("Expected a compiler after '--cc'")?;
+                cc = Some(compiler.clone());
+            }
+            "--opt" => {
+                let level = iter.next().ok_or("Expected a level after '--opt'")?;
+                let level = level
+                    .parse()
+                    .ok()
+                    .filter(|level| *level <= 3)
+                    .ok_or_else(|| {
+                        format!("Invalid optimization level '{}', expected 0 to 3", level)
+                    })?;
+                opt_level = Some(level);
+            }
+            "--cflags" => {
+                let flags = iter.next().ok_or("Expected flags after '--cflags'")?;
+                cflags.push(flags.clone());
+            }
+            "--max-call-depth" => {
+                let depth = iter
+                    .next()
+                    .ok_or("Expected a number after '--max-call-depth'")?;
+                max_call_depth =
+                    depth
+                        .parse()
+                        .ok()
+                        .filter(|depth| *depth > 0)
+                        .ok_or_else(|| {
+                            format!("Invalid call depth '{}', expected a positive number", depth)
+                        })?;
+            }
+            "--trace" => trace = true,
+            "--trace-file" => {
+                let path = iter.next().ok_or("Expected a path after '--trace-file'")?;
+                trace = true;
+                trace_file = Some(path.clone());
+            }
+            "--timings" => timings = true,
+            "--force" => force = true,
+            "--" => program_args.extend(iter.by_ref().cloned()),
+            _ if arg.starts_with('-') && arg != STDIN_PATH => {
+                return Err(format!("Unknown option '{}'", arg))
+            }
+            _ => {
+                if input.is_some() {
+                    return Err(format!("Unexpected argument '{}'", arg));
+                }
+                input = Some(arg.clone());
+            }
+        }
+    }
+
+    let input = input.ok_or("No input file given")?;
+    if run && !target.native {
+        return Err("--run needs a native executable, use `--target c`".to_string());
+    }
+    if trace && backend == Some(RunBackend::Vm) {
+        return Err("--trace only works with the interpreter, use `--backend interp`".to_string());
+    }
+    Ok(Options {
+        input,
+        output,
+        c_out,
+        keep_c,
+        check,
+        no_opt,
+        checks,
+        lints,
+        emit,
+        run,
+        target,
+        backend,
+        cc,
+        opt_level,
+        cflags,
+        max_call_depth,
+        trace,
+        trace_file,
+        timings,
+        force,
+        args: program_args,
+    })
+}
+
+/// Where the build puts the generated C source and the executable
+struct BuildPaths {
+    c_file: PathBuf,
+    executable: PathBuf,
+
+    /// Scratch directory for intermediate files, removed after the build
+    temp_dir: PathBuf,
+}
+
+impl BuildPaths {
+    /// Intermediate files go into a temporary directory so that builds don't
+    /// clobber files in the working directory or each other. The executable
+    /// only goes there too when it is just being run.
+    fn new(options: &Options) -> Self {
+        let temp_dir = env::temp_dir().join(format!("osho-{}", std::process::id()));
+        let name = options.output_name();
+
+        let executable = if options.run && options.output.is_none() {
+            temp_dir.join("program")
+        } else {
+            PathBuf::from(&name)
+        };
+        let c_file = match &options.c_out {
+            Some(path) => PathBuf::from(path),
+            None if options.keep_c || options.emit == Emit::C => {
+                PathBuf::from(format!("{}.c", name))
+            }
+            None => temp_dir.join("program.c"),
+        };
+        Self {
+            c_file,
+            executable,
+            temp_dir,
+        }
+    }
+
+    /// Remove the temporary directory and anything left in it
+    fn clean_up(&self) {
+        let _ = fs::remove_dir_all(&self.temp_dir);
+    }
+}
+
+/// The C compiler configured by the command line options
+fn c_compiler(options: &Options) -> Result<CCompiler, String> {
+    let mut cc = CCompiler::detect(options.cc.as_deref())?;
+    if let Some(level) = options.opt_level {
+        cc = cc.with_opt_level(level)?;
+    }
+    for flags in &options.cflags {
+        cc = cc.with_flags(flags);
+    }
+    Ok(cc)
+}
+
+/// What the executable built from `sources` depends on
+fn build_key(options: &Options, sources: &SourceMap, cc: &CCompiler) -> BuildKey {
+    BuildKey::new()
+        .with_sources(sources)
+        .with_compiler(cc)
+        .with(if options.no_opt { "no-opt" } else { "opt" })
+        .with(if options.checks.arith {
+            "checked-arith"
+        } else {
+            "unchecked-arith"
+        })
+        .with(if options.checks.overflow {
+            "trap-overflow"
+        } else {
+            "wrap-overflow"
+        })
+}
+
+/// Read the whole program from the input file, or from stdin for "-"
+fn read_source(input: &str) -> io::Result<String> {
+    if input == STDIN_PATH {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+        Ok(source)
+    } else {
+        read_to_string(input)
+    }
+}
+
+/// `osho fmt`: format each file in place, or with `--check` list the ones
+/// that would change and fail if there are any
+fn format_files(args: &[String]) {
+    let check = args.iter().any(|arg| arg == "--check");
+    let files: Vec<_> = args.iter().filter(|arg| *arg != "--check").collect();
+    if files.is_empty() {
+        eprintln!("error: No input file given\n\n{}", USAGE);
+        std::process::exit(1);
+    }
+    let mut unformatted = 0;
+    for file in files {
+        let source = read_source(file).unwrap_or_else(|err| {
+            eprintln!("Failed to read '{}': {}", file, err);
+            std::process::exit(1);
+        });
+        let name = if file == STDIN_PATH { "<stdin>" } else { file };
+        let formatted = formatter::format(&source).unwrap_or_else(|errors| {
+            exit_with_errors(&SourceMap::single(name, source.as_str()), errors)
+        });
+        if check {
+            if formatted != source {
+                println!("{}", name);
+                unformatted += 1;
+            }
+        } else if file == STDIN_PATH {
+            print!("{}", formatted);
+        } else if formatted != source {
+            fs::write(file, formatted).unwrap_or_else(|err| {
+                eprintln!("Failed to write '{}': {}", file, err);
+                std::process::exit(1);
+            });
+        }
+    }
+    if unformatted > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// `osho watch`: run the program with `osho run` whenever one of its files
+/// changes
+fn watch_file(args: &[String]) -> ! {
+    let options = parse_args(args).unwrap_or_else(|err| exit_with_usage(&err));
+    if options.input == STDIN_PATH {
+        exit_with_usage("`osho watch` needs a file, not stdin");
+    }
+    let osho = env::current_exe().unwrap_or_else(|err| {
+        eprintln!("error: Can't find the osho executable: {}", err);
+        std::process::exit(1);
+    });
+    loop {
+        // Taken before the run, so saving during it runs the program again
+        let mut watcher = Watcher::new(watch::program_files(Path::new(&options.input)));
+        match Command::new(&osho).arg("run").args(args).status() {
+            Ok(status) if !status.success() => eprintln!("[watch] The program failed"),
+            Ok(_) => {}
+            Err(err) => eprintln!("[watch] Failed to run osho: {}", err),
+        }
+        let files: Vec<_> = watcher
+            .files()
+            .map(|file| file.display().to_string())
+            .collect();
+        eprintln!("[watch] Waiting for changes to {}", files.join(", "));
+        let changed = loop {
+            if let Some(file) = watcher.changed() {
+                break file.display().to_string();
+            }
+            thread::sleep(watch::POLL_INTERVAL);
+        };
+        eprintln!("[watch] {} changed\n", changed);
+    }
+}
 
-    let contents = read_to_string(file_path).expect("Should have been able to read the file");
+/// Config file `osho lint` reads when there's no `--config`
+const LINT_CONFIG: &str = ".osholint";
 
-    let mut lexer = Lexer::new(&contents);
-    let tokens = lexer.get_tokens();
-    let mut parser = Parser::new(&tokens);
-    let ast = parser.parse().expect("Failed to parse");
+/// `osho lint`: report the warnings of every enabled lint, failing if the
+/// program has errors or denied warnings
+fn lint_file(args: &[String]) {
+    let mut input = None;
+    let mut config = None;
+    let mut flags = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--warn" | "--allow" => match iter.next() {
+                Some(name) => flags.push((arg.as_str(), name.as_str())),
+                None => exit_with_usage(&format!("Expected a lint after '{}'", arg)),
+            },
+            "--deny-warnings" => flags.push((arg.as_str(), "")),
+            "--config" => match iter.next() {
+                Some(path) => config = Some(path.clone()),
+                None => exit_with_usage("Expected a path after '--config'"),
+            },
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => exit_with_usage(&format!("Unexpected argument '{}'", arg)),
+        }
+    }
+    let Some(input) = input else {
+        exit_with_usage("No input file given");
+    };
+
+    let mut lints = LintLevels::default();
+    let config = config.or_else(|| Path::new(LINT_CONFIG).exists().then(|| LINT_CONFIG.into()));
+    if let Some(config) = config {
+        let configured = read_to_string(&config)
+            .map_err(|err| format!("Failed to read '{}': {}", config, err))
+            .and_then(|text| lints.configure(&text));
+        if let Err(err) = configured {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    }
+    for (flag, name) in flags {
+        let lint = match flag {
+            "--deny-warnings" => {
+                lints.deny_warnings = true;
+                continue;
+            }
+            _ => Lint::from_name(name).unwrap_or_else(|err| exit_with_usage(&err)),
+        };
+        if flag == "--warn" {
+            lints.warn(lint);
+        } else {
+            lints.allow(lint);
+        }
+    }
 
+    let source = read_source(&input).unwrap_or_else(|err| {
+        eprintln!("Failed to read '{}': {}", input, err);
+        std::process::exit(1);
+    });
+    let name = if input == STDIN_PATH {
+        "<stdin>"
+    } else {
+        &input
+    };
+    let mut loader = Loader::new();
+    let loaded = loader.load(Path::new(name), &source);
+    let sources = loader.sources();
+    let ast = loaded.unwrap_or_else(|errors| exit_with_errors(sources, errors));
     let mut analyzer = SemanticAnalyzer::new();
-    print!("\nInterpreter output:\n",);
-    analyzer.analyze(&ast).expect("Semantic analysis failed");
+    analyzer
+        .analyze_all(&ast)
+        .unwrap_or_else(|errors| exit_with_errors(sources, errors));
+    let mut warnings = analyzer.take_warnings();
+    warnings.extend(lint::lint(&ast));
+    warnings.sort_by_key(|warning| warning.span.map(|span| span.offset));
 
-    let mut generator = CodeGenerator::new();
-    let code = generator.generate(&ast).unwrap();
+    report_warnings(&lints, sources, warnings);
+}
 
-    // Write the generated code to a C file
-    let mut file = File::create("output.c").unwrap();
-    file.write_all(code.as_bytes()).unwrap();
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    std::process::exit(1);
+}
 
-    // Compile the C file to create an executable
-    let status = Command::new("gcc")
-        .arg("output.c")
-        .arg("-o")
-        .arg("output")
-        .status()
-        .expect("Failed to compile");
+/// Render a compile error against the file it is in and exit
+fn exit_with_error(sources: &SourceMap, err: CompileError) -> ! {
+    exit_with_errors(sources, vec![err])
+}
 
-    if !status.success() {
-        eprintln!("Compilation failed");
+/// Render every compile error against the file it is in and exit
+fn exit_with_errors(sources: &SourceMap, errors: Vec<CompileError>) -> ! {
+    for err in &errors {
+        eprintln!("{}", sources.render(&Diagnostic::from(err)));
+    }
+    if errors.len() > 1 {
+        eprintln!("aborting due to {} previous errors", errors.len());
+    }
+    std::process::exit(errors.first().map_or(1, CompileError::exit_code));
+}
+
+/// Exit the way a run of the program ended, if it didn't finish: with the
+/// code it passed to `exit()`, or by reporting the error that stopped it
+fn exit_if_stopped(sources: &SourceMap, result: Result<(), CompileError>) {
+    match result {
+        Ok(()) => {}
+        Err(CompileError::Exit { code, .. }) => {
+            // Exiting skips the destructors that would flush what it printed
+            let _ = io::stdout().flush();
+            std::process::exit(code);
+        }
+        Err(err) => exit_with_error(sources, err),
+    }
+}
+
+/// Print the warnings of enabled lints, exiting if they're denied
+fn report_warnings(lints: &LintLevels, sources: &SourceMap, warnings: Vec<Diagnostic>) {
+    let warnings = lints.apply(warnings);
+    for warning in &warnings {
+        eprintln!("{}", sources.render(warning));
+    }
+    let denied = warnings
+        .iter()
+        .filter(|warning| warning.severity == Severity::Error)
+        .count();
+    if denied > 0 {
+        eprintln!("aborting due to {} denied warnings", denied);
+        std::process::exit(1);
+    }
+}
+
+/// A tool the interpreter runs the program under
+enum Tool<'a> {
+    /// The debugger, for a program whose main file has this source
+    Debugger(&'a str),
+
+    Tracer(Tracer<Box<dyn Write + Send>>),
+}
+
+impl<'a> Tool<'a> {
+    /// The tool `osho debug` or the options ask for, if any
+    fn new(debug: bool, options: &Options, sources: &SourceMap, main: &'a str) -> Option<Self> {
+        if debug {
+            return Some(Tool::Debugger(main));
+        }
+        if !options.trace {
+            return None;
+        }
+        let out: Box<dyn Write + Send> = match &options.trace_file {
+            Some(path) => Box::new(File::create(path).unwrap_or_else(|err| {
+                eprintln!("Failed to create '{}': {}", path, err);
+                std::process::exit(1);
+            })),
+            None => Box::new(io::stderr()),
+        };
+        Some(Tool::Tracer(Tracer::new(sources.clone(), out)))
+    }
+}
+
+/// Run the program with the tree-walking interpreter
+fn interpret(ast: &Ast, options: &Options, tool: Option<Tool>) -> Result<(), CompileError> {
+    let max_call_depth = options.max_call_depth;
+    with_interpreter_stack(max_call_depth, move || {
+        let interpreter = Interpreter::new()
+            .with_max_call_depth(max_call_depth)
+            .with_checks(options.checks)
+            .with_args(options.args.clone());
+        let mut interpreter = match tool {
+            Some(Tool::Debugger(source)) => {
+                interpreter.with_hook(Debugger::new(source, io::stdin().lock(), io::stdout()))
+            }
+            Some(Tool::Tracer(tracer)) => interpreter.with_hook(tracer),
+            None => interpreter,
+        };
+        interpreter.run(ast, ast.root())
+    })
+}
+
+/// Run `f` on a thread with enough stack for the interpreter to make
+/// `max_call_depth` nested calls
+fn with_interpreter_stack<T: Send>(max_call_depth: usize, f: impl FnOnce() -> T + Send) -> T {
+    let stack_size = max_call_depth
+        .saturating_mul(STACK_PER_CALL)
+        .saturating_add(BASE_STACK_SIZE);
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(stack_size)
+            .spawn_scoped(scope, f)
+            .unwrap_or_else(|err| {
+                eprintln!("Failed to start the interpreter: {}", err);
+                std::process::exit(1);
+            })
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+/// Run the program with the interpreter and compiled, on the same input,
+/// and exit with an error if they disagree
+fn verify_program(ast: &Ast, options: &Options) {
+    let mut input = Vec::new();
+    if let Err(err) = io::stdin().read_to_end(&mut input) {
+        eprintln!("Failed to read stdin: {}", err);
+        std::process::exit(1);
+    }
+    let interpreted = with_interpreter_stack(options.max_call_depth, || {
+        verify::interpret(ast, &input, options.max_call_depth, options.checks)
+    });
+    let paths = BuildPaths::new(options);
+    let compiled = c_compiler(options)
+        .and_then(|cc| verify::run_compiled(ast, &cc, options.checks, &paths.temp_dir, &input));
+    paths.clean_up();
+    let compiled = compiled.unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    });
+    match verify::compare(&interpreted, &compiled) {
+        Ok(()) => println!("The interpreter and the compiled program agree"),
+        Err(difference) => {
+            eprintln!("error: {}", difference);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Print a compiler data structure as pretty JSON for external tools
+fn print_json<T: serde::Serialize>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(err) => {
+            eprintln!("Failed to serialize to JSON: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    if args.first().map(String::as_str) == Some("repl") {
+        let stdin = std::io::stdin();
+        if let Err(err) = Repl::new(std::io::stdout()).run(stdin.lock()) {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.first().map(String::as_str) == Some("lsp") {
+        let stdin = std::io::stdin();
+        let mut server = Server::new(std::io::stdout());
+        if let Err(err) = server.run(stdin.lock()) {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+        // The protocol's exit code says whether `shutdown` came first
+        std::process::exit(if server.is_shut_down() { 0 } else { 1 });
+    }
+    if let Some(("lint", args)) = args
+        .split_first()
+        .map(|(first, rest)| (first.as_str(), rest))
+    {
+        lint_file(args);
+        return;
+    }
+    if let Some(("watch", args)) = args
+        .split_first()
+        .map(|(first, rest)| (first.as_str(), rest))
+    {
+        watch_file(args);
+    }
+    if let Some(("fmt", files)) = args
+        .split_first()
+        .map(|(first, rest)| (first.as_str(), rest))
+    {
+        format_files(files);
+        return;
+    }
+    let (args, command) = match args.split_first() {
+        Some((first, rest)) if first == "run" => (rest, Subcommand::Run),
+        Some((first, rest)) if first == "debug" => (rest, Subcommand::Debug),
+        Some((first, rest)) if first == "verify" => (rest, Subcommand::Verify),
+        _ => (&args[..], Subcommand::Compile),
+    };
+    let debug = command == Subcommand::Debug;
+    let options = match parse_args(args) {
+        Ok(options) if debug && options.trace => {
+            eprintln!(
+                "error: --trace can't be used with `osho debug`\n\n{}",
+                USAGE
+            );
+            std::process::exit(1);
+        }
+        Ok(options) if debug => Options {
+            backend: Some(RunBackend::Interp),
+            // Folded constants and removed code wouldn't match the source
+            no_opt: true,
+            ..options
+        },
+        Ok(options) if command == Subcommand::Run => Options {
+            backend: options.backend.or(Some(RunBackend::Interp)),
+            ..options
+        },
+        Ok(options) if command == Subcommand::Verify && !options.args.is_empty() => {
+            eprintln!(
+                "error: `osho verify` doesn't pass arguments to the program\n\n{}",
+                USAGE
+            );
+            std::process::exit(1);
+        }
+        Ok(options) if options.backend.is_some() => {
+            eprintln!("error: --backend is only used by `osho run`\n\n{}", USAGE);
+            std::process::exit(1);
+        }
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    let contents = read_source(&options.input).unwrap_or_else(|err| {
+        eprintln!("Failed to read '{}': {}", options.file_name(), err);
         std::process::exit(1);
+    });
+
+    let mut timings = Timings::new();
+    build(&options, command, &contents, &mut timings);
+    if options.timings {
+        eprint!("{}", timings);
+    }
+}
+
+/// Lower the program for the C target, exiting with an error for each
+/// construct it can't compile yet
+fn lower(ast: &Ast, sources: &SourceMap, timings: &mut Timings) -> ir::Program {
+    timings
+        .time("lower", || SemanticAnalyzer::new().lower(ast))
+        .unwrap_or_else(|errors| exit_with_errors(sources, errors))
+}
+
+/// Take the program through the pipeline as far as the options say, timing
+/// each phase
+fn build(options: &Options, command: Subcommand, contents: &str, timings: &mut Timings) {
+    let debug = command == Subcommand::Debug;
+
+    // Tokens are only shown for the main file, the AST includes its imports
+    if options.emit == Emit::HighlightsJson {
+        let highlights: Vec<_> = highlight(contents)
+            .into_iter()
+            .map(|(span, class)| serde_json::json!({ "span": span, "class": class }))
+            .collect();
+        print_json(&highlights);
+        return;
+    }
+    if matches!(options.emit, Emit::Tokens | Emit::TokensJson) {
+        let sources = SourceMap::single(options.file_name(), contents);
+        let tokens = Lexer::new(contents)
+            .get_tokens()
+            .unwrap_or_else(|err| exit_with_error(&sources, err));
+        if options.emit == Emit::Tokens {
+            for token in &tokens {
+                println!("{}", token);
+            }
+        } else {
+            print_json(&tokens);
+        }
+        return;
+    }
+
+    let mut loader = Loader::new();
+    let loaded = loader.load(Path::new(options.file_name()), contents);
+    timings.extend(loader.timings());
+    let sources = loader.sources();
+    let ast = loaded.unwrap_or_else(|errors| exit_with_errors(sources, errors));
+    if options.emit == Emit::Ast {
+        print!("{}", ast_printer::to_tree(&ast, ast.root()));
+        return;
+    }
+    if options.emit == Emit::AstJson {
+        print_json(&ast);
+        return;
+    }
+    if options.emit == Emit::Sexpr {
+        println!("{}", ast_printer::to_sexpr(&ast, ast.root()));
+        return;
+    }
+
+    let mut analyzer = SemanticAnalyzer::new();
+    timings
+        .time("analyze", || analyzer.analyze_all(&ast))
+        .unwrap_or_else(|errors| exit_with_errors(sources, errors));
+    report_warnings(&options.lints, sources, analyzer.take_warnings());
+
+    if options.check {
+        return;
+    }
+
+    let mut ast = ast;
+    if !options.no_opt {
+        timings.time("optimize", || optimizer::optimize(&mut ast));
     }
 
-    // Run the executable and capture its output
-    let output = Command::new("./output")
-        .output()
-        .expect("Failed to run the executable");
+    if command == Subcommand::Verify {
+        lower(&ast, sources, timings);
+        timings.time("verify", || verify_program(&ast, options));
+        return;
+    }
+
+    if options.emit == Emit::Ir {
+        print!("{}", lower(&ast, sources, timings));
+        return;
+    }
+
+    if options.emit == Emit::Bytecode {
+        let chunk = timings
+            .time("bytecode", || bytecode::Compiler::new().compile(&ast))
+            .unwrap_or_else(|err| exit_with_error(sources, err));
+        print!("{}", chunk);
+        return;
+    }
+
+    match options.backend {
+        Some(RunBackend::Interp) => {
+            let tool = Tool::new(debug, options, sources, contents);
+            match timings.time("interpret", || interpret(&ast, options, tool)) {
+                Err(err) if err.message() == debugger::STOPPED => {}
+                result => exit_if_stopped(sources, result),
+            }
+            return;
+        }
+        Some(RunBackend::Vm) => {
+            let chunk = timings
+                .time("bytecode", || bytecode::Compiler::new().compile(&ast))
+                .unwrap_or_else(|err| exit_with_error(sources, err));
+            let result = timings.time("run", || {
+                Vm::new()
+                    .with_max_call_depth(options.max_call_depth)
+                    .with_checks(options.checks)
+                    .with_args(options.args.clone())
+                    .run(&chunk)
+            });
+            exit_if_stopped(sources, result);
+            return;
+        }
+        None => {}
+    }
+
+    // Other targets only write out the generated code
+    if !options.target.native {
+        let mut generator = options.target.backend(options.checks);
+        let code = timings
+            .time("generate", || generator.generate(&ast))
+            .unwrap_or_else(|err| exit_with_error(sources, err));
+        // `-o out.js` names the file itself
+        let name = options.output_name();
+        let extension = options.target.extension;
+        let path = if Path::new(&name).extension().and_then(|ext| ext.to_str()) == Some(extension) {
+            name
+        } else {
+            format!("{}.{}", name, extension)
+        };
+        if let Err(err) = fs::write(&path, code) {
+            eprintln!("Failed to write '{}': {}", path, err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let program = lower(&ast, sources, timings);
 
-    if !output.status.success() {
-        eprintln!("Execution failed");
+    // The interpreter would use up the input meant for the executable
+    if options.emit == Emit::Binary && !analyzer.reads_input() {
+        print!("\nInterpreter output:\n",);
+        let tool = Tool::new(false, options, sources, contents);
+        match timings.time("interpret", || interpret(&ast, options, tool)) {
+            // The executable still gets built for a program that exits early
+            Ok(()) | Err(CompileError::Exit { .. }) => {}
+            Err(err) => exit_with_error(sources, err),
+        }
+    }
+
+    let paths = BuildPaths::new(options);
+    if let Err(err) = fs::create_dir_all(&paths.temp_dir) {
+        eprintln!("Failed to create '{}': {}", paths.temp_dir.display(), err);
         std::process::exit(1);
     }
+    let cache = BuildCache::new(CACHE_DIR);
+    // `--emit c` doesn't run the C compiler, so there's nothing to save
+    let key = match c_compiler(options) {
+        Ok(cc) if options.emit == Emit::Binary => Some(build_key(options, sources, &cc)),
+        _ => None,
+    };
+    let cached = key.is_some_and(|key| {
+        !options.force
+            && cache.contains(key)
+            && cache.restore(key, &paths.c_file, &paths.executable).is_ok()
+    });
+
+    if !cached {
+        let code = timings.time("generate", || {
+            CodeGenerator::new()
+                .with_checks(options.checks)
+                .generate_ir(&program)
+        });
 
-    let stdout = std::str::from_utf8(&output.stdout).expect("Invalid UTF-8 output");
-    println!("\nExecutable output:\n{}", stdout);
+        // Write the generated code to a C file
+        if let Err(err) = fs::write(&paths.c_file, code) {
+            eprintln!("Failed to write '{}': {}", paths.c_file.display(), err);
+            paths.clean_up();
+            std::process::exit(1);
+        }
+
+        if options.emit == Emit::C {
+            paths.clean_up();
+            return;
+        }
+
+        // Compile the C file to create an executable
+        let compiled = timings.time("cc", || {
+            c_compiler(options).and_then(|cc| cc.compile(&paths.c_file, &paths.executable))
+        });
+        if let Err(err) = compiled {
+            eprintln!("error: {}", err);
+            paths.clean_up();
+            std::process::exit(1);
+        }
+
+        if let Some(key) = key {
+            if let Err(err) = cache.store(key, &paths.c_file, &paths.executable) {
+                eprintln!(
+                    "warning: Failed to cache the build in '{}': {}",
+                    CACHE_DIR, err
+                );
+            }
+        }
+    }
+
+    if !options.run {
+        paths.clean_up();
+        return;
+    }
+
+    // Run the executable, passing its output through as it's printed. A bare
+    // name is looked up in PATH, so run it from the current directory
+    // explicitly.
+    println!("\nExecutable output:");
+    let _ = io::stdout().flush();
+    let executable = Path::new(".").join(&paths.executable);
+    let status = timings.time("run", || {
+        Command::new(executable).args(&options.args).status()
+    });
+    paths.clean_up();
+
+    let status = status.unwrap_or_else(|err| {
+        eprintln!("Failed to run the executable: {}", err);
+        std::process::exit(1);
+    });
+    if !status.success() {
+        let code = driver::exit_code(status);
+        eprintln!("Execution failed with exit code {}", code);
+        std::process::exit(code);
+    }
 }
@@ -0,0 +1,670 @@
+//! Programs split over several files with `import "file.osho"`, and
+//! namespaces declared with `module name { ... }`.
+//!
+//! The [`Loader`] reads a file and every file it imports, relative to the
+//! importing file, and merges them into one program. `import std.math`
+//! includes a file of the [standard library](crate::stdlib) instead. Each
+//! file is included once, before the files that import it, so declarations
+//! come before their uses in generated code. Import cycles are errors.
+//!
+//! Spans of each file start at a different offset, so the [`SourceMap`] can
+//! tell which file an error is in.
+//!
+//! [`resolve_modules`] replaces each module with its declarations, renamed
+//! to `name.item`, so later phases only see qualified names. Backends that
+//! can't use a `.` in names mangle them.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::diagnostics::Diagnostic;
+use crate::error::CompileError;
+use crate::lexer::{Lexer, Span};
+use crate::parser::{ASTNode, Ast, NodeId, Parser};
+use crate::semantic_analyzer::Type;
+use crate::stdlib;
+use crate::timings::Timings;
+
+/// A file of a program
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFile {
+    /// Name shown in diagnostics
+    pub name: String,
+
+    pub source: String,
+
+    /// Offset of the file's spans
+    pub base_offset: usize,
+}
+
+/// The files of a program, for rendering diagnostics against the file they
+/// are in
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A program of one file
+    pub fn single(name: impl Into<String>, source: impl Into<String>) -> Self {
+        let mut map = Self::new();
+        map.add(name, source);
+        map
+    }
+
+    /// Add a file, returning the offset its spans start at
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<String>) -> usize {
+        // Even an empty file gets an offset of its own, for its end of file
+        let base_offset = self
+            .files
+            .last()
+            .map_or(0, |file| file.base_offset + file.source.len() + 1);
+        self.files.push(SourceFile {
+            name: name.into(),
+            source: source.into(),
+            base_offset,
+        });
+        base_offset
+    }
+
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    /// The file a span is in, or the first file when there's no span
+    pub fn file(&self, span: Option<Span>) -> Option<&SourceFile> {
+        match span {
+            Some(span) => self
+                .files
+                .iter()
+                .rev()
+                .find(|file| file.base_offset <= span.offset),
+            None => self.files.first(),
+        }
+    }
+
+    /// Render a diagnostic against the file it is in
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        match self.file(diagnostic.span) {
+            Some(file) => diagnostic.render(&file.name, &file.source),
+            None => diagnostic.render("<unknown>", ""),
+        }
+    }
+}
+
+/// Loads a program and the files it imports
+#[derive(Debug, Default)]
+pub struct Loader {
+    sources: SourceMap,
+
+    /// Files already included in the program
+    loaded: HashSet<PathBuf>,
+
+    /// Files whose imports are being loaded, importers first, for finding
+    /// cycles
+    loading: Vec<(PathBuf, String)>,
+
+    /// Where the files' nodes go
+    ast: Ast,
+
+    /// Statements of the program so far
+    statements: Vec<NodeId>,
+
+    /// Time spent lexing and parsing the files
+    timings: Timings,
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The files loaded so far, including one that failed to parse
+    pub fn sources(&self) -> &SourceMap {
+        &self.sources
+    }
+
+    /// How long lexing and parsing the files has taken
+    pub fn timings(&self) -> &Timings {
+        &self.timings
+    }
+
+    /// Load a program from the source of its main file, which is at `path`.
+    /// Its imports are resolved relative to the directory of `path`.
+    pub fn load(&mut self, path: &Path, source: &str) -> Result<Ast, Vec<CompileError>> {
+        self.load_file(path, source)?;
+        let mut statements = std::mem::take(&mut self.statements);
+        let mut ast = std::mem::take(&mut self.ast);
+        self.timings
+            .time("parse", || resolve_modules(&mut ast, &mut statements))?;
+        let root = ast.root();
+        ast[root] = ASTNode::Program(statements);
+        Ok(ast)
+    }
+
+    /// Read a program's main file and load it
+    pub fn load_path(&mut self, path: &Path) -> Result<Ast, Vec<CompileError>> {
+        let source = fs::read_to_string(path).map_err(|err| {
+            vec![CompileError::Parse {
+                message: format!("Cannot read '{}': {}", path.display(), err),
+                span: Span::default(),
+            }]
+        })?;
+        self.load(path, &source)
+    }
+
+    fn load_file(&mut self, path: &Path, source: &str) -> Result<(), Vec<CompileError>> {
+        let base_offset = self.sources.add(path.display().to_string(), source);
+        let tokens = self
+            .timings
+            .time("lex", || {
+                Lexer::new(source)
+                    .with_base_offset(base_offset)
+                    .get_tokens()
+            })
+            .map_err(|err| vec![err])?;
+        let ast = std::mem::take(&mut self.ast);
+        let mut parser = Parser::new(&tokens).with_ast(ast);
+        let statements = self.timings.time("parse", || parser.parse_statements());
+        self.ast = parser.into_ast();
+        let statements = statements?;
+
+        self.loaded.insert(Self::key(path));
+        self.loading
+            .push((Self::key(path), path.display().to_string()));
+        // Imported files come first, wherever the imports are
+        let (imports, statements): (Vec<_>, Vec<_>) = statements
+            .into_iter()
+            .partition(|stmt| matches!(self.ast[*stmt], ASTNode::Import { .. }));
+        for import in imports {
+            if let ASTNode::Import { path: file, span } = self.ast[import].clone() {
+                let resolved = if stdlib::source(&file).is_some() {
+                    PathBuf::from(file)
+                } else {
+                    path.parent().unwrap_or(Path::new("")).join(&file)
+                };
+                self.import(&resolved, span)?;
+            }
+        }
+        self.loading.pop();
+        self.statements.extend(statements);
+        Ok(())
+    }
+
+    /// Load an imported file unless it's already part of the program
+    fn import(&mut self, path: &Path, span: Span) -> Result<(), Vec<CompileError>> {
+        let key = Self::key(path);
+        if let Some(start) = self.loading.iter().position(|(loading, _)| *loading == key) {
+            let mut cycle: Vec<_> = self.loading[start..]
+                .iter()
+                .map(|(_, name)| name.as_str())
+                .collect();
+            let name = path.display().to_string();
+            cycle.push(&name);
+            return Err(vec![CompileError::Parse {
+                message: format!("Import cycle: {}", cycle.join(" -> ")),
+                span,
+            }]);
+        }
+        if self.loaded.contains(&key) {
+            return Ok(());
+        }
+        let source = match Self::embedded(path) {
+            Some(source) => source.to_string(),
+            None => fs::read_to_string(path).map_err(|err| {
+                vec![CompileError::Parse {
+                    message: format!("Cannot read '{}': {}", path.display(), err),
+                    span,
+                }]
+            })?,
+        };
+        self.load_file(path, &source)
+    }
+
+    /// What identifies a file, however the path to it is written
+    fn key(path: &Path) -> PathBuf {
+        if Self::embedded(path).is_some() {
+            return path.to_path_buf();
+        }
+        fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    /// The source of a standard library file, which is preferred over a file
+    /// with the same path
+    fn embedded(path: &Path) -> Option<&'static str> {
+        path.to_str().and_then(stdlib::source)
+    }
+}
+
+/// What kind of declaration a module member is
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MemberKind {
+    Function,
+    Constant,
+    Struct,
+}
+
+impl MemberKind {
+    fn name(&self) -> &'static str {
+        match self {
+            MemberKind::Function => "function",
+            MemberKind::Constant => "constant",
+            MemberKind::Struct => "struct",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Member {
+    kind: MemberKind,
+    public: bool,
+}
+
+/// The name and kind of what a module item declares
+fn declared(declaration: &ASTNode) -> Option<(&str, MemberKind)> {
+    match declaration {
+        ASTNode::FunctionDeclaration { name, .. } => Some((name, MemberKind::Function)),
+        ASTNode::ConstDeclaration { name, .. } => Some((name, MemberKind::Constant)),
+        ASTNode::StructDeclaration { name, .. } => Some((name, MemberKind::Struct)),
+        _ => None,
+    }
+}
+
+/// Replace the modules declared at the top level of a program with their
+/// declarations, named `module.item`. Uses of a module's members inside it
+/// are qualified the same way, unless a variable hides them, and uses from
+/// outside it are checked to be `pub`.
+pub fn resolve_modules(
+    ast: &mut Ast,
+    statements: &mut Vec<NodeId>,
+) -> Result<(), Vec<CompileError>> {
+    let mut modules: HashMap<String, HashMap<String, Member>> = HashMap::new();
+    let mut errors = Vec::new();
+    for stmt in statements.iter() {
+        let ASTNode::Module { name, items, span } = &ast[*stmt] else {
+            continue;
+        };
+        if modules.contains_key(name) {
+            errors.push(CompileError::Semantic {
+                message: format!("Module '{}' is already declared", name),
+                span: *span,
+            });
+            continue;
+        }
+        let members = items
+            .iter()
+            .filter_map(|item| {
+                let (name, kind) = declared(&ast[item.declaration])?;
+                let public = item.public;
+                Some((name.to_string(), Member { kind, public }))
+            })
+            .collect();
+        modules.insert(name.clone(), members);
+    }
+    if modules.is_empty() {
+        return Ok(());
+    }
+
+    let mut resolver = Resolver {
+        modules: &modules,
+        current: None,
+        scopes: vec![HashSet::new()],
+        errors,
+    };
+    let mut resolved = Vec::new();
+    for stmt in std::mem::take(statements) {
+        match &ast[stmt] {
+            ASTNode::Module { name, items, .. } => {
+                let (name, items) = (name.clone(), items.clone());
+                resolver.current = Some(name.clone());
+                // Module items don't see the program's variables
+                let outer = std::mem::replace(&mut resolver.scopes, vec![HashSet::new()]);
+                for item in items {
+                    if let ASTNode::FunctionDeclaration { name: declared, .. }
+                    | ASTNode::ConstDeclaration { name: declared, .. }
+                    | ASTNode::StructDeclaration { name: declared, .. } =
+                        &mut ast[item.declaration]
+                    {
+                        *declared = format!("{}.{}", name, declared);
+                        if item.public {
+                            let declared = declared.clone();
+                            ast.mark_public(declared);
+                        }
+                    }
+                    resolver.node(ast, item.declaration);
+                    resolved.push(item.declaration);
+                }
+                resolver.scopes = outer;
+                resolver.current = None;
+            }
+            _ => {
+                resolver.node(ast, stmt);
+                resolved.push(stmt);
+            }
+        }
+    }
+    *statements = resolved;
+    if resolver.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(resolver.errors)
+    }
+}
+
+/// Qualifies the names used in a program's statements
+struct Resolver<'a> {
+    /// The members of each module
+    modules: &'a HashMap<String, HashMap<String, Member>>,
+
+    /// The module whose items are being resolved
+    current: Option<String>,
+
+    /// Variables declared in each enclosing scope, which hide module members
+    scopes: Vec<HashSet<String>>,
+
+    errors: Vec<CompileError>,
+}
+
+impl Resolver<'_> {
+    fn node(&mut self, ast: &mut Ast, id: NodeId) {
+        ast.update(id, |ast, node| self.resolve(ast, node));
+    }
+
+    fn resolve(&mut self, ast: &mut Ast, node: &mut ASTNode) {
+        match node {
+            ASTNode::Program(statements) | ASTNode::Block(statements) => {
+                self.scopes.push(HashSet::new());
+                statements.iter().for_each(|stmt| self.node(ast, *stmt));
+                self.scopes.pop();
+            }
+            ASTNode::LetDeclaration {
+                name,
+                ty,
+                value,
+                span,
+                ..
+            } => {
+                if let Some(ty) = ty {
+                    self.qualify_type(ty, *span);
+                }
+                self.node(ast, *value);
+                self.declare(name);
+            }
+            ASTNode::ConstDeclaration { name, value, .. } => {
+                self.node(ast, *value);
+                self.declare(name);
+            }
+            ASTNode::FunctionDeclaration {
+                params,
+                result,
+                body,
+                span,
+                ..
+            } => {
+                for ty in params
+                    .iter_mut()
+                    .filter_map(|param| param.ty.as_mut())
+                    .chain(result.as_mut())
+                {
+                    self.qualify_type(ty, *span);
+                }
+                // Functions only see their parameters
+                let scope = params.iter().map(|param| param.name.clone()).collect();
+                let outer = std::mem::replace(&mut self.scopes, vec![scope]);
+                self.node(ast, *body);
+                self.scopes = outer;
+            }
+            ASTNode::For {
+                init,
+                condition,
+                step,
+                body,
+                ..
+            } => {
+                self.scopes.push(HashSet::new());
+                for clause in [init, condition, step].into_iter().flatten() {
+                    self.node(ast, *clause);
+                }
+                self.node(ast, *body);
+                self.scopes.pop();
+            }
+            ASTNode::Lambda {
+                params, body, span, ..
+            } => {
+                for ty in params.iter_mut().filter_map(|param| param.ty.as_mut()) {
+                    self.qualify_type(ty, *span);
+                }
+                // Unlike functions, lambdas see the variables around them
+                self.scopes
+                    .push(params.iter().map(|param| param.name.clone()).collect());
+                self.node(ast, *body);
+                self.scopes.pop();
+            }
+            ASTNode::ForIn {
+                name, range, body, ..
+            } => {
+                self.node(ast, *range);
+                self.scopes.push(HashSet::new());
+                self.declare(name);
+                self.node(ast, *body);
+                self.scopes.pop();
+            }
+            ASTNode::Call { name, args, span } => {
+                // A variable holding a lambda hides functions of the same name
+                if !self.is_variable(name) {
+                    self.qualify(name, MemberKind::Function, *span);
+                }
+                args.iter().for_each(|arg| self.node(ast, *arg));
+            }
+            ASTNode::StructLiteral { name, fields, span } => {
+                self.qualify(name, MemberKind::Struct, *span);
+                fields.iter().for_each(|(_, value)| self.node(ast, *value));
+            }
+            ASTNode::Identifier { name, span } => {
+                self.qualify(name, MemberKind::Constant, *span);
+            }
+            // `module.CONSTANT` reads a constant of a module
+            ASTNode::FieldAccess {
+                object,
+                field,
+                span,
+            } => match &ast[*object] {
+                ASTNode::Identifier { name: module, .. }
+                    if !self.is_variable(module) && self.modules.contains_key(module) =>
+                {
+                    let mut name = format!("{}.{}", module, field);
+                    self.qualify(&mut name, MemberKind::Constant, *span);
+                    *node = ASTNode::Identifier { name, span: *span };
+                }
+                _ => self.node(ast, *object),
+            },
+            ASTNode::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.node(ast, *value);
+                }
+            }
+            ASTNode::Print { args, .. }
+            | ASTNode::ArrayLiteral { elements: args, .. }
+            | ASTNode::Interpolation { values: args, .. } => {
+                args.iter().for_each(|arg| self.node(ast, *arg));
+            }
+            ASTNode::Assignment { value, .. }
+            | ASTNode::CompoundAssignment { value, .. }
+            | ASTNode::Assert {
+                condition: value, ..
+            }
+            | ASTNode::UnaryOp { operand: value, .. } => self.node(ast, *value),
+            ASTNode::FieldAssignment { object, value, .. } => {
+                self.node(ast, *object);
+                self.node(ast, *value);
+            }
+            ASTNode::IndexAssignment {
+                array,
+                index,
+                value,
+                ..
+            } => {
+                self.node(ast, *array);
+                self.node(ast, *index);
+                self.node(ast, *value);
+            }
+            ASTNode::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.node(ast, *condition);
+                self.node(ast, *then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.node(ast, *else_branch);
+                }
+            }
+            ASTNode::While {
+                condition, body, ..
+            } => {
+                self.node(ast, *condition);
+                self.node(ast, *body);
+            }
+            ASTNode::DoWhile {
+                body, condition, ..
+            } => {
+                self.node(ast, *body);
+                self.node(ast, *condition);
+            }
+            ASTNode::BinaryOp { left, right, .. }
+            | ASTNode::Index {
+                array: left,
+                index: right,
+                ..
+            }
+            | ASTNode::Range {
+                start: left,
+                end: right,
+                ..
+            } => {
+                self.node(ast, *left);
+                self.node(ast, *right);
+            }
+            ASTNode::Slice {
+                array, start, end, ..
+            } => {
+                self.node(ast, *array);
+                for bound in [start, end].into_iter().flatten() {
+                    self.node(ast, *bound);
+                }
+            }
+            ASTNode::MapLiteral { entries, .. } => {
+                for (key, value) in entries {
+                    self.node(ast, *key);
+                    self.node(ast, *value);
+                }
+            }
+            ASTNode::Conditional {
+                condition,
+                then_value,
+                else_value,
+                ..
+            } => {
+                self.node(ast, *condition);
+                self.node(ast, *then_value);
+                self.node(ast, *else_value);
+            }
+            // Nested modules and imports are reported by the analyzer
+            ASTNode::StructDeclaration { .. }
+            | ASTNode::Increment { .. }
+            | ASTNode::Decrement { .. }
+            | ASTNode::Input { .. }
+            | ASTNode::Break { .. }
+            | ASTNode::Continue { .. }
+            | ASTNode::Import { .. }
+            | ASTNode::Module { .. }
+            | ASTNode::Int(_)
+            | ASTNode::Number(_)
+            | ASTNode::Bool(_)
+            | ASTNode::Str(_)
+            | ASTNode::Char(_) => {}
+        }
+    }
+
+    fn declare(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string());
+        }
+    }
+
+    fn is_variable(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    /// Check a use of `module.item` is of a `pub` member, or qualify a name
+    /// used inside a module that declares it
+    fn qualify(&mut self, name: &mut String, kind: MemberKind, span: Span) {
+        if let Some((module, item)) = name.split_once('.') {
+            let Some(members) = self.modules.get(module) else {
+                // Calling a method on a variable isn't resolved here
+                if !self.is_variable(module) {
+                    self.error(format!("Unknown module '{}'", module), span);
+                }
+                return;
+            };
+            match members.get(item) {
+                Some(member) if member.kind != kind => self.error(
+                    format!(
+                        "'{}' is a {}, not a {}",
+                        name,
+                        member.kind.name(),
+                        kind.name()
+                    ),
+                    span,
+                ),
+                Some(member) if !member.public && self.current.as_deref() != Some(module) => self
+                    .error(
+                        format!("'{}' is private to module '{}'", item, module),
+                        span,
+                    ),
+                Some(_) => {}
+                None => self.error(
+                    format!("Module '{}' has no {} '{}'", module, kind.name(), item),
+                    span,
+                ),
+            }
+            return;
+        }
+        if kind == MemberKind::Constant && self.is_variable(name) {
+            return;
+        }
+        let Some(module) = &self.current else {
+            return;
+        };
+        let declares = self.modules[module]
+            .get(name.as_str())
+            .is_some_and(|member| member.kind == kind);
+        if declares {
+            *name = format!("{}.{}", module, name);
+        }
+    }
+
+    /// Qualify the struct names in a type annotation
+    fn qualify_type(&mut self, ty: &mut Type, span: Span) {
+        match ty {
+            Type::Array(element) | Type::Map(element) => self.qualify_type(element, span),
+            Type::Struct(name) => self.qualify(name, MemberKind::Struct, span),
+            Type::Function { params, result } => {
+                for ty in params.iter_mut().chain(result.as_deref_mut()) {
+                    self.qualify_type(ty, span);
+                }
+            }
+            Type::Int | Type::Float | Type::Bool | Type::Str | Type::Char => {}
+        }
+    }
+
+    fn error(&mut self, message: String, span: Span) {
+        self.errors.push(CompileError::Semantic { message, span });
+    }
+}
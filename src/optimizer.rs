@@ -0,0 +1,466 @@
+//! Optimization passes over the checked AST, run before code generation.
+
+use std::collections::HashMap;
+
+use crate::builtins::Builtin;
+use crate::checks::Checks;
+use crate::interpreter::{self, Value};
+use crate::parser::{ASTNode, Ast, BinaryOperator, NodeId, UnaryOperator};
+use crate::visitor::{MutVisitor, Visitor};
+
+/// Run every optimization pass over a program
+pub fn optimize(ast: &mut Ast) {
+    fold_constants(ast);
+    remove_unused_variables(ast);
+}
+
+/// Replace operations on literals with their result, so `2 + 3 * 4` becomes
+/// `14`. Operations that fail, like dividing by zero, are left in place for
+/// the program to report when it runs.
+pub fn fold_constants(ast: &mut Ast) {
+    Folder.visit_mut(ast, ast.root());
+}
+
+/// Folds each node once the nodes it holds are folded
+struct Folder;
+
+impl MutVisitor for Folder {
+    fn visit_mut(&mut self, ast: &mut Ast, node: NodeId) {
+        self.walk_mut(ast, node);
+        ast.update(node, fold_node);
+    }
+}
+
+fn fold_node(ast: &mut Ast, node: &mut ASTNode) {
+    match node {
+        ASTNode::BinaryOp {
+            left, op, right, ..
+        } => {
+            let folded = match (literal(&ast[*left]), literal(&ast[*right])) {
+                (Some(l), Some(r)) => evaluate_binary(op, &l, &r).ok(),
+                _ => None,
+            };
+            if let Some(folded) = folded.and_then(to_literal) {
+                *node = folded;
+            }
+        }
+        ASTNode::UnaryOp { op, operand, .. } => {
+            let folded = literal(&ast[*operand]).and_then(|value| evaluate_unary(op, &value).ok());
+            if let Some(folded) = folded.and_then(to_literal) {
+                *node = folded;
+            }
+        }
+        ASTNode::Conditional {
+            condition,
+            then_value,
+            else_value,
+            ..
+        } => {
+            if let Some(value) = literal(&ast[*condition]) {
+                let chosen = if value.is_truthy() {
+                    then_value
+                } else {
+                    else_value
+                };
+                *node = ast.replace(*chosen, ASTNode::Bool(false));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The value of a literal node
+fn literal(node: &ASTNode) -> Option<Value> {
+    match node {
+        ASTNode::Int(num) => Some(Value::Int(*num)),
+        ASTNode::Number(num) => Some(Value::Float(*num)),
+        ASTNode::Bool(b) => Some(Value::Bool(*b)),
+        ASTNode::Str(text) => Some(Value::Str(text.clone())),
+        ASTNode::Char(c) => Some(Value::Char(*c)),
+        _ => None,
+    }
+}
+
+/// How operators are evaluated at compile time. Integer overflow fails, so
+/// it is left for the program to wrap around or trap on as it was built to.
+const COMPILE_TIME: Checks = Checks {
+    arith: false,
+    overflow: true,
+};
+
+/// Evaluate a binary operator the same way the interpreter does
+fn evaluate_binary(op: &BinaryOperator, left: &Value, right: &Value) -> Result<Value, String> {
+    match op {
+        BinaryOperator::And => Ok(Value::Bool(left.is_truthy() && right.is_truthy())),
+        BinaryOperator::Or => Ok(Value::Bool(left.is_truthy() || right.is_truthy())),
+        _ if op.is_comparison() => interpreter::compare(op, left, right),
+        _ => COMPILE_TIME.arithmetic(op, left, right),
+    }
+}
+
+fn evaluate_unary(op: &UnaryOperator, value: &Value) -> Result<Value, String> {
+    match op {
+        UnaryOperator::Not => Ok(Value::Bool(!value.is_truthy())),
+        UnaryOperator::Negate => COMPILE_TIME.negate(value),
+    }
+}
+
+/// Evaluate an expression at compile time. It may only use literals and the
+/// constants `constant` knows the value of.
+pub(crate) fn evaluate_constant(
+    ast: &Ast,
+    expr: NodeId,
+    constant: &dyn Fn(&str) -> Option<Value>,
+) -> Result<Value, String> {
+    match &ast[expr] {
+        ASTNode::BinaryOp {
+            left, op, right, ..
+        } => {
+            let left = evaluate_constant(ast, *left, constant)?;
+            let right = evaluate_constant(ast, *right, constant)?;
+            evaluate_binary(op, &left, &right)
+        }
+        ASTNode::UnaryOp { op, operand, .. } => {
+            evaluate_unary(op, &evaluate_constant(ast, *operand, constant)?)
+        }
+        ASTNode::Identifier { name, .. } => {
+            constant(name).ok_or_else(|| format!("'{}' is not a constant", name))
+        }
+        ASTNode::Conditional {
+            condition,
+            then_value,
+            else_value,
+            ..
+        } => {
+            if evaluate_constant(ast, *condition, constant)?.is_truthy() {
+                evaluate_constant(ast, *then_value, constant)
+            } else {
+                evaluate_constant(ast, *else_value, constant)
+            }
+        }
+        expr => literal(expr).ok_or_else(|| "Expected a constant expression".to_string()),
+    }
+}
+
+/// A literal node holding a value. Infinities and NaN have no literal
+/// syntax in the generated code, so they aren't folded.
+fn to_literal(value: Value) -> Option<ASTNode> {
+    match value {
+        Value::Int(num) => Some(ASTNode::Int(num)),
+        Value::Float(num) if num.is_finite() => Some(ASTNode::Number(num)),
+        Value::Float(_) => None,
+        Value::Bool(b) => Some(ASTNode::Bool(b)),
+        Value::Str(text) => Some(ASTNode::Str(text)),
+        Value::Char(c) => Some(ASTNode::Char(c)),
+        Value::Array(_)
+        | Value::Map(_)
+        | Value::Struct(_)
+        | Value::Closure(_)
+        | Value::Function(_) => None,
+    }
+}
+
+/// Remove `let` declarations of variables that are never read, along with
+/// every assignment to them. The semantic analyzer warns about these.
+/// Unused variables whose values could fail at runtime, like `let x = 1 / y`,
+/// are kept so the program still reports the error.
+pub fn remove_unused_variables(ast: &mut Ast) {
+    let mut uses = UseDef::default();
+    uses.visit(ast, ast.root());
+
+    let dead = uses
+        .bindings
+        .iter()
+        .map(|binding| binding.reads == 0 && binding.removable)
+        .collect();
+    Sweeper {
+        dead,
+        next: 0,
+        scopes: vec![HashMap::new()],
+    }
+    .sweep(ast, ast.root());
+}
+
+/// A variable introduced by a `let`
+struct Binding {
+    /// How many times the value is read; `x += 1` and `x++` don't count
+    reads: usize,
+
+    /// Whether the declaration and every assignment can be deleted without
+    /// losing a runtime error
+    removable: bool,
+}
+
+/// Stands in for a binding in [`UseDef::scopes`] for a lambda's parameter
+const PARAMETER: usize = usize::MAX;
+
+/// Use-def analysis: finds the `let` each variable refers to and counts
+/// how often every binding is read. Bindings are numbered in source order.
+#[derive(Default)]
+struct UseDef {
+    bindings: Vec<Binding>,
+
+    /// Binding of each name in each enclosing scope, innermost last
+    scopes: Vec<HashMap<String, usize>>,
+}
+
+impl Visitor for UseDef {
+    fn visit(&mut self, ast: &Ast, node: NodeId) {
+        match &ast[node] {
+            ASTNode::Program(_) | ASTNode::Block(_) => {
+                self.scopes.push(HashMap::new());
+                self.walk(ast, node);
+                self.scopes.pop();
+            }
+            ASTNode::LetDeclaration { name, value, .. }
+            | ASTNode::ConstDeclaration { name, value, .. } => {
+                // The value is evaluated before the new variable exists
+                self.visit(ast, *value);
+                self.bindings.push(Binding {
+                    reads: 0,
+                    removable: !can_fail(ast, *value),
+                });
+                let id = self.bindings.len() - 1;
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(name.clone(), id);
+                }
+            }
+            ASTNode::Assignment { name, value, .. } => {
+                self.visit(ast, *value);
+                self.read_chained(ast, *value);
+                if can_fail(ast, *value) {
+                    self.keep(name);
+                }
+            }
+            ASTNode::CompoundAssignment {
+                name, op, value, ..
+            } => {
+                self.visit(ast, *value);
+                let divides = matches!(op, BinaryOperator::Divide | BinaryOperator::Modulo);
+                if can_fail(ast, *value) || (divides && !is_nonzero_literal(&ast[*value])) {
+                    self.keep(name);
+                }
+            }
+            ASTNode::Identifier { name, .. } => {
+                if let Some(id) = self.resolve(name) {
+                    self.bindings[id].reads += 1;
+                }
+            }
+            // Calling a lambda reads the variable holding it
+            ASTNode::Call { name, .. } => {
+                if let Some(id) = self.resolve(name) {
+                    self.bindings[id].reads += 1;
+                }
+                self.walk(ast, node);
+            }
+            // Reading consumes input and can fail, so it always happens
+            ASTNode::Input { name, .. } => self.keep(name),
+            // The initializer's variables belong to the loop's scope
+            ASTNode::For { .. } => {
+                self.scopes.push(HashMap::new());
+                self.walk(ast, node);
+                self.scopes.pop();
+            }
+            ASTNode::ForIn {
+                name, range, body, ..
+            } => {
+                self.visit(ast, *range);
+                // The loop variable is a binding that is never removed
+                self.bindings.push(Binding {
+                    reads: 0,
+                    removable: false,
+                });
+                let id = self.bindings.len() - 1;
+                self.scopes.push(HashMap::from([(name.clone(), id)]));
+                self.visit(ast, *body);
+                self.scopes.pop();
+            }
+            // Functions can't see the variables around them
+            ASTNode::FunctionDeclaration { .. } => {
+                let outer = std::mem::replace(&mut self.scopes, vec![HashMap::new()]);
+                self.walk(ast, node);
+                self.scopes = outer;
+            }
+            // A lambda's body is an expression, so the parameters hide
+            // outer variables without being bindings the sweeper would see
+            ASTNode::Lambda { params, .. } => {
+                let params = params.iter().map(|param| (param.name.clone(), PARAMETER));
+                self.scopes.push(params.collect());
+                self.walk(ast, node);
+                self.scopes.pop();
+            }
+            // Storing into an element or a field reads the array or struct,
+            // so it stays alive
+            _ => self.walk(ast, node),
+        }
+    }
+}
+
+impl UseDef {
+    fn resolve(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .copied()
+            .filter(|id| *id != PARAMETER)
+    }
+
+    /// In a chain like `a = b = 0`, what's stored in `a` is read from `b`
+    fn read_chained(&mut self, ast: &Ast, value: NodeId) {
+        if let ASTNode::Assignment { name, .. } = &ast[value] {
+            if let Some(id) = self.resolve(name) {
+                self.bindings[id].reads += 1;
+            }
+        }
+    }
+
+    fn keep(&mut self, name: &str) {
+        if let Some(id) = self.resolve(name) {
+            self.bindings[id].removable = false;
+        }
+    }
+}
+
+/// Deletes the statements of dead bindings. It visits `let`s in the same
+/// order as [`UseDef`], so bindings get the same numbers.
+struct Sweeper {
+    /// Whether each binding is dead, by number
+    dead: Vec<bool>,
+
+    /// Number of the next `let`
+    next: usize,
+
+    scopes: Vec<HashMap<String, usize>>,
+}
+
+impl Sweeper {
+    /// Sweep a statement, returning whether it should be deleted
+    fn sweep(&mut self, ast: &mut Ast, node: NodeId) -> bool {
+        ast.update(node, |ast, node| self.sweep_node(ast, node))
+    }
+
+    fn sweep_node(&mut self, ast: &mut Ast, node: &mut ASTNode) -> bool {
+        match node {
+            ASTNode::Program(statements) => {
+                statements.retain(|stmt| !self.sweep(ast, *stmt));
+                false
+            }
+            ASTNode::Block(statements) => {
+                self.scopes.push(HashMap::new());
+                statements.retain(|stmt| !self.sweep(ast, *stmt));
+                self.scopes.pop();
+                false
+            }
+            ASTNode::LetDeclaration { name, .. } | ASTNode::ConstDeclaration { name, .. } => {
+                let id = self.next;
+                self.next += 1;
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(name.clone(), id);
+                }
+                self.dead[id]
+            }
+            ASTNode::Assignment { name, .. }
+            | ASTNode::CompoundAssignment { name, .. }
+            | ASTNode::Increment { name, .. }
+            | ASTNode::Decrement { name, .. } => self
+                .scopes
+                .iter()
+                .rev()
+                .find_map(|scope| scope.get(name))
+                .is_some_and(|id| self.dead[*id]),
+            ASTNode::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.sweep(ast, *then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.sweep(ast, *else_branch);
+                }
+                false
+            }
+            ASTNode::While { body, .. } | ASTNode::DoWhile { body, .. } => {
+                self.sweep(ast, *body);
+                false
+            }
+            ASTNode::For {
+                init, step, body, ..
+            } => {
+                self.scopes.push(HashMap::new());
+                if init.is_some_and(|init| self.sweep(ast, init)) {
+                    *init = None;
+                }
+                if step.is_some_and(|step| self.sweep(ast, step)) {
+                    *step = None;
+                }
+                self.sweep(ast, *body);
+                self.scopes.pop();
+                false
+            }
+            ASTNode::ForIn { name, body, .. } => {
+                let id = self.next;
+                self.next += 1;
+                self.scopes.push(HashMap::from([(name.clone(), id)]));
+                self.sweep(ast, *body);
+                self.scopes.pop();
+                false
+            }
+            ASTNode::FunctionDeclaration { body, .. } => {
+                let outer = std::mem::replace(&mut self.scopes, vec![HashMap::new()]);
+                self.sweep(ast, *body);
+                self.scopes = outer;
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Whether evaluating an expression could fail at runtime, or do more than
+/// compute its value. Division and modulo can fail when the divisor isn't
+/// known to be non-zero, and so can indexing or slicing an array, and
+/// builtins like `pop` change their arguments.
+fn can_fail(ast: &Ast, expr: NodeId) -> bool {
+    match &ast[expr] {
+        // The assignment in the middle of a chain like `a = b = 0`
+        ASTNode::Index { .. } | ASTNode::Slice { .. } | ASTNode::Assignment { .. } => true,
+        ASTNode::Call { name, args, .. } => {
+            !Builtin::from_name(name).is_some_and(|builtin| builtin.is_pure())
+                || args.iter().any(|arg| can_fail(ast, *arg))
+        }
+        ASTNode::ArrayLiteral { elements: args, .. }
+        | ASTNode::Interpolation { values: args, .. } => args.iter().any(|arg| can_fail(ast, *arg)),
+        ASTNode::MapLiteral { entries, .. } => entries
+            .iter()
+            .any(|(key, value)| can_fail(ast, *key) || can_fail(ast, *value)),
+        ASTNode::StructLiteral { fields, .. } => {
+            fields.iter().any(|(_, value)| can_fail(ast, *value))
+        }
+        ASTNode::FieldAccess { object, .. } => can_fail(ast, *object),
+        ASTNode::BinaryOp {
+            left, op, right, ..
+        } => {
+            let divides = matches!(op, BinaryOperator::Divide | BinaryOperator::Modulo);
+            (divides && !is_nonzero_literal(&ast[*right]))
+                || can_fail(ast, *left)
+                || can_fail(ast, *right)
+        }
+        ASTNode::UnaryOp { operand, .. } => can_fail(ast, *operand),
+        ASTNode::Conditional {
+            condition,
+            then_value,
+            else_value,
+            ..
+        } => can_fail(ast, *condition) || can_fail(ast, *then_value) || can_fail(ast, *else_value),
+        _ => false,
+    }
+}
+
+fn is_nonzero_literal(expr: &ASTNode) -> bool {
+    match expr {
+        ASTNode::Int(num) => *num != 0,
+        ASTNode::Number(num) => *num != 0.0,
+        _ => false,
+    }
+}
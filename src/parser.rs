@@ -1,180 +1,1201 @@
+pub use crate::ast::*;
+use crate::error::CompileError;
 use crate::lexer::*;
+use crate::modules;
+use crate::semantic_analyzer::Type;
+use crate::visitor::Visitor;
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum ASTNode {
-    Program(Vec<ASTNode>),
-    LetDeclaration {
-        name: String,
-        value: Box<ASTNode>,
-    },
-    Assignment {
-        name: String,
-        value: Box<ASTNode>,
-    },
-    Increment(String),
-    Decrement(String),
-    Print(Box<ASTNode>),
-    BinaryOp {
-        left: Box<ASTNode>,
-        op: BinaryOperator,
-        right: Box<ASTNode>,
-    },
-    Number(f64),
-    Identifier(String),
+/// The names a lambda's body uses that aren't its parameters, in the order
+/// they first appear. Those naming variables where the lambda is made are
+/// the ones it captures; the rest name functions.
+pub(crate) fn free_names(ast: &Ast, params: &[Param], body: NodeId) -> Vec<String> {
+    let mut free = FreeNames {
+        bound: params.iter().map(|param| param.name.clone()).collect(),
+        names: Vec::new(),
+    };
+    free.visit(ast, body);
+    free.names
+}
+
+struct FreeNames {
+    bound: Vec<String>,
+    names: Vec<String>,
+}
+
+impl FreeNames {
+    fn name(&mut self, name: &String) {
+        if !self.bound.contains(name) && !self.names.contains(name) {
+            self.names.push(name.clone());
+        }
+    }
+}
+
+impl Visitor for FreeNames {
+    fn visit(&mut self, ast: &Ast, node: NodeId) {
+        match &ast[node] {
+            ASTNode::Identifier { name, .. }
+            | ASTNode::Increment { name, .. }
+            | ASTNode::Decrement { name, .. }
+            | ASTNode::Assignment { name, .. }
+            | ASTNode::CompoundAssignment { name, .. }
+            | ASTNode::Call { name, .. } => self.name(name),
+            // A nested lambda's parameters are only bound in its own body
+            ASTNode::Lambda { params, body, .. } => {
+                let outer = self.bound.len();
+                self.bound
+                    .extend(params.iter().map(|param| param.name.clone()));
+                self.visit(ast, *body);
+                self.bound.truncate(outer);
+                return;
+            }
+            _ => {}
+        }
+        self.walk(ast, node);
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum BinaryOperator {
-    Plus,
-    Minus,
-    Multiply,
-    Divide, // Add other operators as needed
+/// How deeply expressions, blocks and types may nest, so that the parser's
+/// recursion stops with an error well before it overflows the stack
+pub const MAX_NESTING: usize = 128;
+
+/// How tightly an operator binds, from loosest to tightest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    /// `a = b`, `a += b` and the other compound assignments
+    Assignment,
+    /// `a ? b : c`
+    Conditional,
+    Or,
+    And,
+    Comparison,
+    /// Addition and subtraction
+    Term,
+    /// Multiplication, division and remainder
+    Factor,
+    /// `!a` and `-a`
+    Unary,
+    Power,
+}
+
+impl Precedence {
+    /// The level binding just tighter than this one
+    fn next(self) -> Self {
+        match self {
+            Precedence::Assignment => Precedence::Conditional,
+            Precedence::Conditional => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary | Precedence::Power => Precedence::Power,
+        }
+    }
 }
 
+/// The binary operators, with the tokens they're written as and how tightly
+/// they bind. Indexing, field access and calls bind tighter than all of them.
+const BINARY_OPERATORS: [(Kind, BinaryOperator, Precedence); 14] = [
+    (Kind::Or, BinaryOperator::Or, Precedence::Or),
+    (Kind::And, BinaryOperator::And, Precedence::And),
+    (
+        Kind::EqualsEquals,
+        BinaryOperator::Equal,
+        Precedence::Comparison,
+    ),
+    (
+        Kind::NotEquals,
+        BinaryOperator::NotEqual,
+        Precedence::Comparison,
+    ),
+    (Kind::LessThan, BinaryOperator::Less, Precedence::Comparison),
+    (
+        Kind::LessThanEquals,
+        BinaryOperator::LessEqual,
+        Precedence::Comparison,
+    ),
+    (
+        Kind::GreaterThan,
+        BinaryOperator::Greater,
+        Precedence::Comparison,
+    ),
+    (
+        Kind::GreaterThanEquals,
+        BinaryOperator::GreaterEqual,
+        Precedence::Comparison,
+    ),
+    (Kind::Plus, BinaryOperator::Plus, Precedence::Term),
+    (Kind::Minus, BinaryOperator::Minus, Precedence::Term),
+    (Kind::Multiply, BinaryOperator::Multiply, Precedence::Factor),
+    (Kind::Divide, BinaryOperator::Divide, Precedence::Factor),
+    (Kind::Modulo, BinaryOperator::Modulo, Precedence::Factor),
+    (Kind::Power, BinaryOperator::Power, Precedence::Power),
+];
+
 pub struct Parser<'a> {
-    tokens: &'a [Token],
-    current: usize,
+    tokens: PeekableTokens<'a>,
+
+    /// Where the parsed nodes go
+    ast: Ast,
+
+    /// How many nested constructs are being parsed
+    depth: usize,
+
+    /// Errors recovered from so far
+    errors: Vec<CompileError>,
+
+    /// Whether `Name {` starts a struct literal. It doesn't in the condition
+    /// of an `if` or `while`, where the brace starts the body.
+    struct_literals: bool,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(tokens: &'a [Token]) -> Self {
-        Self { tokens, current: 0 }
+    pub fn new(tokens: &'a [Token<'a>]) -> Self {
+        Self::from_tokens(tokens.into())
+    }
+
+    /// Parse the tokens as `lexer` reads them, without lexing the whole
+    /// source first
+    pub fn from_lexer(lexer: Lexer<'a>) -> Self {
+        Self::from_tokens(PeekableTokens::new(lexer))
+    }
+
+    fn from_tokens(tokens: PeekableTokens<'a>) -> Self {
+        Self {
+            tokens,
+            ast: Ast::new(),
+            depth: 0,
+            errors: Vec::new(),
+            struct_literals: true,
+        }
+    }
+
+    /// Parse any tokens, giving errors rather than panicking, which makes
+    /// this an entry point for fuzzing. Tokens from [`Lexer`] always end with
+    /// an end of file; other token lists must too.
+    ///
+    /// [`Lexer`]: crate::lexer::Lexer
+    pub fn try_parse(tokens: &[Token<'_>]) -> Result<Ast, Vec<CompileError>> {
+        match tokens.last() {
+            Some(last) if last.kind == Kind::Eof => Parser::new(tokens).parse(),
+            last => Err(vec![CompileError::Parse {
+                message: "Expected the tokens to end with an end of file".to_string(),
+                span: last.map(|token| token.span).unwrap_or_default(),
+            }]),
+        }
+    }
+
+    /// Add the nodes to `ast` rather than a new AST, so they can refer to
+    /// nodes parsed before, like the REPL's earlier lines
+    pub fn with_ast(mut self, ast: Ast) -> Self {
+        self.ast = ast;
+        self
     }
 
-    pub fn parse(&mut self) -> Result<ASTNode, String> {
+    /// The AST the parsed nodes were added to
+    pub fn into_ast(self) -> Ast {
+        self.ast
+    }
+
+    /// Parse the whole program, reporting every syntax error found
+    pub fn parse(&mut self) -> Result<Ast, Vec<CompileError>> {
+        let mut statements = self.parse_statements()?;
+        modules::resolve_modules(&mut self.ast, &mut statements)?;
+        let root = self.ast.root();
+        self.ast[root] = ASTNode::Program(statements);
+        Ok(std::mem::take(&mut self.ast))
+    }
+
+    /// Parse the tokens as a list of statements without making them the
+    /// program, for incremental use such as the REPL
+    pub fn parse_statements(&mut self) -> Result<Vec<NodeId>, Vec<CompileError>> {
         let mut statements = Vec::new();
         while !self.is_at_end() {
-            statements.push(self.declaration()?);
+            if let Some(stmt) = self.declaration_or_recover() {
+                statements.push(self.ast.add(stmt));
+            }
+        }
+        // The source stopped being lexed at the error, so what was parsed
+        // before it may be cut short
+        if let Some(err) = self.tokens.error() {
+            return Err(vec![err.clone()]);
+        }
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    /// Parse a declaration; on error, record it and skip to the next statement
+    fn declaration_or_recover(&mut self) -> Option<ASTNode> {
+        let start = self.tokens.position();
+        match self.declaration() {
+            Ok(stmt) => Some(stmt),
+            Err(err) => {
+                self.errors.push(err);
+                self.synchronize(start);
+                None
+            }
+        }
+    }
+
+    /// Skip tokens until something that can start a statement
+    fn synchronize(&mut self, start: usize) {
+        // Always make progress, or a stray token would be reparsed forever
+        if self.tokens.position() == start {
+            self.advance();
+        }
+        while !self.is_at_end() {
+            match self.peek().kind {
+                Kind::Let
+                | Kind::Const
+                | Kind::Struct
+                | Kind::Fn
+                | Kind::Return
+                | Kind::Print
+                | Kind::Println
+                | Kind::Input
+                | Kind::If
+                | Kind::While
+                | Kind::Do
+                | Kind::For
+                | Kind::Break
+                | Kind::Continue
+                | Kind::Assert
+                | Kind::Import
+                | Kind::Module
+                | Kind::CloseBrace => return,
+                _ => {
+                    self.advance();
+                }
+            }
         }
-        Ok(ASTNode::Program(statements))
     }
 
-    fn declaration(&mut self) -> Result<ASTNode, String> {
+    fn declaration(&mut self) -> Result<ASTNode, CompileError> {
         if self.match_token(Kind::Let) {
             self.let_declaration()
-        } else if self.match_token(Kind::Identifier) {
-            self.statement()
+        } else if self.match_token(Kind::Const) {
+            self.const_declaration()
+        } else if self.match_token(Kind::Struct) {
+            self.struct_declaration()
+        } else if self.match_token(Kind::Fn) {
+            self.function_declaration()
+        } else if self.match_token(Kind::Module) {
+            self.module_declaration()
+        } else if self.match_token(Kind::Pub) {
+            Err(self.error_at_previous("Only declarations in a module can be 'pub'"))
+        } else if self.match_token(Kind::Import) {
+            let span = self.previous().span;
+            Ok(ASTNode::Import {
+                path: self.import_path()?,
+                span,
+            })
         } else {
             self.statement()
         }
     }
 
-    fn let_declaration(&mut self) -> Result<ASTNode, String> {
+    /// A file name, or a dotted name like `std.math` for `std/math.osho`
+    fn import_path(&mut self) -> Result<String, CompileError> {
+        if self.match_token(Kind::String) {
+            let path = self.previous().clone();
+            return self.token_to_string(&path);
+        }
+        let message = "Expected a file or module name after 'import'";
+        let mut parts = Vec::new();
+        loop {
+            let part = self.consume(Kind::Identifier, message)?.clone();
+            parts.push(self.token_to_string(&part)?);
+            if !self.match_token(Kind::Dot) {
+                break;
+            }
+        }
+        Ok(format!("{}.osho", parts.join("/")))
+    }
+
+    fn let_declaration(&mut self) -> Result<ASTNode, CompileError> {
+        let mutable = self.match_token(Kind::Mut);
         let name = self
             .consume(Kind::Identifier, "Expected identifier after 'let'")?
             .clone();
+        let ty = self.annotation(Kind::Colon)?;
         self.consume(Kind::EqualsTo, "Expected '=' after let declaration")?;
         let value = self.expression()?;
         Ok(ASTNode::LetDeclaration {
             name: self.token_to_string(&name)?,
-            value: Box::new(value),
+            mutable,
+            ty,
+            value: self.ast.add(value),
+            span: name.span,
+        })
+    }
+
+    fn const_declaration(&mut self) -> Result<ASTNode, CompileError> {
+        let name = self
+            .consume(Kind::Identifier, "Expected identifier after 'const'")?
+            .clone();
+        self.consume(Kind::EqualsTo, "Expected '=' after const declaration")?;
+        let value = self.expression()?;
+        Ok(ASTNode::ConstDeclaration {
+            name: self.token_to_string(&name)?,
+            value: self.ast.add(value),
+            span: name.span,
+        })
+    }
+
+    fn function_declaration(&mut self) -> Result<ASTNode, CompileError> {
+        let name = self
+            .consume(Kind::Identifier, "Expected function name after 'fn'")?
+            .clone();
+        self.consume(Kind::OpenParen, "Expected '(' after function name")?;
+        let mut params = Vec::new();
+        while !self.check(Kind::CloseParen) {
+            let param = self.consume(Kind::Identifier, "Expected parameter name")?;
+            params.push(Param {
+                name: self.token_to_string(&param)?,
+                ty: self.annotation(Kind::Colon)?,
+            });
+            if !self.match_token(Kind::Comma) {
+                break;
+            }
+        }
+        self.consume(Kind::CloseParen, "Expected ')' after parameters")?;
+        let result = self.annotation(Kind::Arrow)?;
+        let body = self.block()?;
+        Ok(ASTNode::FunctionDeclaration {
+            name: self.token_to_string(&name)?,
+            params,
+            result,
+            body: self.ast.add(body),
+            span: name.span,
+        })
+    }
+
+    /// A type after `separator`, like the `: int` of a declaration, if the
+    /// separator is there
+    fn annotation(&mut self, separator: Kind) -> Result<Option<Type>, CompileError> {
+        if !self.match_token(separator) {
+            return Ok(None);
+        }
+        self.type_name().map(Some)
+    }
+
+    /// A type: `int`, `float`, `bool`, `string`, `[element]`,
+    /// `{string: value}`, `fn(params) -> result` or the name of a struct
+    fn type_name(&mut self) -> Result<Type, CompileError> {
+        if self.match_token(Kind::Fn) {
+            self.consume(Kind::OpenParen, "Expected '(' after 'fn' in a type")?;
+            let mut params = Vec::new();
+            while !self.check(Kind::CloseParen) {
+                params.push(self.nested(Self::type_name)?);
+                if !self.match_token(Kind::Comma) {
+                    break;
+                }
+            }
+            self.consume(Kind::CloseParen, "Expected ')' after the parameter types")?;
+            let result = if self.match_token(Kind::Arrow) {
+                Some(Box::new(self.nested(Self::type_name)?))
+            } else {
+                None
+            };
+            return Ok(Type::Function { params, result });
+        }
+        if self.match_token(Kind::OpenBracket) {
+            let element = self.nested(Self::type_name)?;
+            self.consume(Kind::CloseBracket, "Expected ']' after the element type")?;
+            return Ok(Type::Array(Box::new(element)));
+        }
+        if self.match_token(Kind::OpenBrace) {
+            if self.nested(Self::type_name)? != Type::Str {
+                return Err(self.error_at_previous("Map keys must be strings"));
+            }
+            self.consume(Kind::Colon, "Expected ':' after the key type")?;
+            let value = self.nested(Self::type_name)?;
+            self.consume(Kind::CloseBrace, "Expected '}' after the value type")?;
+            return Ok(Type::Map(Box::new(value)));
+        }
+        let name = self.consume(Kind::Identifier, "Expected a type")?.clone();
+        let mut name = self.token_to_string(&name)?;
+        // A struct declared in a module, `module.Item`
+        if self.match_token(Kind::Dot) {
+            let item = self.consume(Kind::Identifier, "Expected a type after '.'")?;
+            name = format!("{}.{}", name, self.token_to_string(&item)?);
+        }
+        Ok(match name.as_str() {
+            "int" => Type::Int,
+            "float" => Type::Float,
+            "bool" => Type::Bool,
+            "string" => Type::Str,
+            "char" => Type::Char,
+            _ => Type::Struct(name),
+        })
+    }
+
+    fn module_declaration(&mut self) -> Result<ASTNode, CompileError> {
+        let name = self
+            .consume(Kind::Identifier, "Expected module name after 'module'")?
+            .clone();
+        self.consume(Kind::OpenBrace, "Expected '{' after module name")?;
+        let mut items = Vec::new();
+        while !self.check(Kind::CloseBrace) && !self.is_at_end() {
+            let public = self.match_token(Kind::Pub);
+            let declaration = if self.match_token(Kind::Fn) {
+                self.child(Self::function_declaration)?
+            } else if self.match_token(Kind::Const) {
+                self.child(Self::const_declaration)?
+            } else if self.match_token(Kind::Struct) {
+                self.child(Self::struct_declaration)?
+            } else {
+                return Err(self.error_at_current(
+                    "Modules can only declare functions, constants and structs",
+                ));
+            };
+            items.push(ModuleItem {
+                public,
+                declaration,
+            });
+        }
+        self.consume(Kind::CloseBrace, "Expected '}' after module items")?;
+        Ok(ASTNode::Module {
+            name: self.token_to_string(&name)?,
+            items,
+            span: name.span,
+        })
+    }
+
+    fn struct_declaration(&mut self) -> Result<ASTNode, CompileError> {
+        let name = self
+            .consume(Kind::Identifier, "Expected struct name after 'struct'")?
+            .clone();
+        self.consume(Kind::OpenBrace, "Expected '{' after struct name")?;
+        let mut fields = Vec::new();
+        while !self.check(Kind::CloseBrace) {
+            let field = self.consume(Kind::Identifier, "Expected field name")?;
+            fields.push(self.token_to_string(&field)?);
+            if !self.match_token(Kind::Comma) {
+                break;
+            }
+        }
+        self.consume(Kind::CloseBrace, "Expected '}' after struct fields")?;
+        Ok(ASTNode::StructDeclaration {
+            name: self.token_to_string(&name)?,
+            fields,
+            span: name.span,
         })
     }
 
-    fn statement(&mut self) -> Result<ASTNode, String> {
+    fn statement(&mut self) -> Result<ASTNode, CompileError> {
         if self.match_token(Kind::Print) {
-            self.print_statement()
+            self.print_statement(false)
+        } else if self.match_token(Kind::Println) {
+            self.print_statement(true)
+        } else if self.match_token(Kind::Input) {
+            self.input_statement()
+        } else if self.match_token(Kind::If) {
+            self.if_statement()
+        } else if self.match_token(Kind::While) {
+            self.while_statement()
+        } else if self.match_token(Kind::Do) {
+            self.do_while_statement()
+        } else if self.match_token(Kind::For) {
+            self.for_statement()
+        } else if self.match_token(Kind::Return) {
+            self.return_statement()
+        } else if self.match_token(Kind::Break) {
+            Ok(ASTNode::Break {
+                span: self.previous().span,
+            })
+        } else if self.match_token(Kind::Continue) {
+            Ok(ASTNode::Continue {
+                span: self.previous().span,
+            })
+        } else if self.match_token(Kind::Assert) {
+            self.assert_statement()
+        } else if self.check(Kind::OpenBrace) {
+            self.block()
         } else {
             self.expression_statement()
         }
     }
 
-    fn print_statement(&mut self) -> Result<ASTNode, String> {
-        let expr = self.expression()?;
-        Ok(ASTNode::Print(Box::new(expr)))
+    fn print_statement(&mut self, newline: bool) -> Result<ASTNode, CompileError> {
+        let span = self.previous().span;
+        // `print(a, b)` is written like a call, and `print()` prints an empty line
+        let parens = self.match_token(Kind::OpenParen);
+        let format = if self.check(Kind::String) {
+            let token = self.advance().clone();
+            Some(self.token_to_string(&token)?)
+        } else {
+            None
+        };
+        let mut args = Vec::new();
+        if format.is_none() && !(parens && self.check(Kind::CloseParen)) {
+            args.push(self.child(Self::expression)?);
+        }
+        while self.match_token(Kind::Comma) {
+            args.push(self.child(Self::expression)?);
+        }
+        if parens {
+            self.consume(Kind::CloseParen, "Expected ')' after print arguments")?;
+        }
+        Ok(ASTNode::Print {
+            format,
+            args,
+            newline,
+            span,
+        })
+    }
+
+    fn assert_statement(&mut self) -> Result<ASTNode, CompileError> {
+        let span = self.previous().span;
+        let condition = self.child(Self::expression)?;
+        let message = if self.match_token(Kind::Comma) {
+            let token = self
+                .consume(Kind::String, "Expected a message after ',' in an assert")?
+                .clone();
+            Some(self.token_to_string(&token)?)
+        } else {
+            None
+        };
+        Ok(ASTNode::Assert {
+            condition,
+            message,
+            span,
+        })
+    }
+
+    /// `return`, with a value if one follows on the same line
+    fn return_statement(&mut self) -> Result<ASTNode, CompileError> {
+        let span = self.previous().span;
+        let value = if self.check(Kind::CloseBrace)
+            || self.is_at_end()
+            || self.peek().span.line != span.line
+        {
+            None
+        } else {
+            Some(self.child(Self::expression)?)
+        };
+        Ok(ASTNode::Return { value, span })
+    }
+
+    fn input_statement(&mut self) -> Result<ASTNode, CompileError> {
+        let name = self
+            .consume(Kind::Identifier, "Expected a variable after 'input'")?
+            .clone();
+        Ok(ASTNode::Input {
+            name: self.token_to_string(&name)?,
+            span: name.span,
+        })
+    }
+
+    /// Parse the condition of an `if` or `while`, which is followed by a block
+    fn condition(&mut self) -> Result<ASTNode, CompileError> {
+        let struct_literals = std::mem::replace(&mut self.struct_literals, false);
+        let condition = self.expression();
+        self.struct_literals = struct_literals;
+        condition
     }
 
-    fn expression_statement(&mut self) -> Result<ASTNode, String> {
+    fn if_statement(&mut self) -> Result<ASTNode, CompileError> {
+        let span = self.previous().span;
+        let condition = self.condition()?;
+        let then_branch = self.block()?;
+        let else_branch = if self.match_token(Kind::Else) {
+            if self.match_token(Kind::If) {
+                Some(self.child(|parser| parser.nested(Self::if_statement))?)
+            } else {
+                Some(self.child(Self::block)?)
+            }
+        } else {
+            None
+        };
+        Ok(ASTNode::If {
+            condition: self.ast.add(condition),
+            then_branch: self.ast.add(then_branch),
+            else_branch,
+            span,
+        })
+    }
+
+    fn while_statement(&mut self) -> Result<ASTNode, CompileError> {
+        let span = self.previous().span;
+        let condition = self.condition()?;
+        let body = self.block()?;
+        Ok(ASTNode::While {
+            condition: self.ast.add(condition),
+            body: self.ast.add(body),
+            span,
+        })
+    }
+
+    fn do_while_statement(&mut self) -> Result<ASTNode, CompileError> {
+        let span = self.previous().span;
+        let body = self.block()?;
+        self.consume(Kind::While, "Expected 'while' after the body of 'do'")?;
+        let condition = self.condition()?;
+        Ok(ASTNode::DoWhile {
+            body: self.ast.add(body),
+            condition: self.ast.add(condition),
+            span,
+        })
+    }
+
+    fn for_statement(&mut self) -> Result<ASTNode, CompileError> {
+        let span = self.previous().span;
+        if self.check(Kind::Identifier) {
+            return self.for_in_statement(span);
+        }
+        self.consume(Kind::OpenParen, "Expected '(' after 'for'")?;
+        let init = if self.check(Kind::Semicolon) {
+            None
+        } else {
+            Some(self.child(Self::declaration)?)
+        };
+        self.consume(Kind::Semicolon, "Expected ';' after loop initializer")?;
+        let condition = if self.check(Kind::Semicolon) {
+            None
+        } else {
+            Some(self.child(Self::expression)?)
+        };
+        self.consume(Kind::Semicolon, "Expected ';' after loop condition")?;
+        let step = if self.check(Kind::CloseParen) {
+            None
+        } else {
+            Some(self.child(Self::declaration)?)
+        };
+        self.consume(Kind::CloseParen, "Expected ')' after for clauses")?;
+        let body = self.block()?;
+        Ok(ASTNode::For {
+            init,
+            condition,
+            step,
+            body: self.ast.add(body),
+            span,
+        })
+    }
+
+    /// The rest of `for name in start..end { ... }`, after the `for`
+    fn for_in_statement(&mut self, span: Span) -> Result<ASTNode, CompileError> {
+        let name = self.consume(Kind::Identifier, "Expected a variable name after 'for'")?;
+        let name = self.token_to_string(&name)?;
+        self.consume(
+            Kind::In,
+            "Expected 'in' after the variable of a for-in loop",
+        )?;
+        let range = self.child(Self::range)?;
+        let body = self.block()?;
+        Ok(ASTNode::ForIn {
+            name,
+            range,
+            body: self.ast.add(body),
+            span,
+        })
+    }
+
+    fn range(&mut self) -> Result<ASTNode, CompileError> {
+        let start = self.condition()?;
+        let inclusive = if self.match_token(Kind::DotDotEquals) {
+            true
+        } else {
+            self.consume(
+                Kind::DotDot,
+                "Expected '..' or '..=' in the range of a for-in loop",
+            )?;
+            false
+        };
+        let span = self.previous().span;
+        let end = self.condition()?;
+        Ok(ASTNode::Range {
+            start: self.ast.add(start),
+            end: self.ast.add(end),
+            inclusive,
+            span,
+        })
+    }
+
+    fn block(&mut self) -> Result<ASTNode, CompileError> {
+        self.consume(Kind::OpenBrace, "Expected '{' before block")?;
+        let statements = self.nested(|parser| {
+            let mut statements = Vec::new();
+            while !parser.check(Kind::CloseBrace) && !parser.is_at_end() {
+                if let Some(stmt) = parser.declaration_or_recover() {
+                    statements.push(parser.ast.add(stmt));
+                }
+            }
+            Ok(statements)
+        })?;
+        self.consume(Kind::CloseBrace, "Expected '}' after block")?;
+        Ok(ASTNode::Block(statements))
+    }
+
+    fn expression_statement(&mut self) -> Result<ASTNode, CompileError> {
         let expr = self.expression()?;
         if self.match_token(Kind::Increment) {
-            if let ASTNode::Identifier(name) = expr {
-                return Ok(ASTNode::Increment(name));
+            if let ASTNode::Identifier { name, span } = expr {
+                return Ok(ASTNode::Increment { name, span });
             }
-            return Err("Expected identifier before '++'".to_string());
-        } else if self.match_token(Kind::Minus) && self.match_token(Kind::Minus) {
-            if let ASTNode::Identifier(name) = expr {
-                return Ok(ASTNode::Decrement(name));
+            return Err(self.error_at_previous("Expected identifier before '++'"));
+        } else if self.match_token(Kind::Decrement) {
+            if let ASTNode::Identifier { name, span } = expr {
+                return Ok(ASTNode::Decrement { name, span });
             }
-            return Err("Expected identifier before '--'".to_string());
+            return Err(self.error_at_previous("Expected identifier before '--'"));
         }
         Ok(expr)
     }
 
-    fn expression(&mut self) -> Result<ASTNode, String> {
-        self.arithmetic()
+    fn expression(&mut self) -> Result<ASTNode, CompileError> {
+        self.nested(|parser| parser.binary(Precedence::Assignment))
     }
 
-    fn arithmetic(&mut self) -> Result<ASTNode, String> {
-        let mut node = self.primary()?;
-        while let Some(operator) = {
-            if self.match_token(Kind::Plus) {
-                Some(BinaryOperator::Plus)
-            } else if self.match_token(Kind::Minus) {
-                Some(BinaryOperator::Minus)
-            } else if self.match_token(Kind::Multiply) {
-                Some(BinaryOperator::Multiply)
-            } else if self.match_token(Kind::Divide) {
-                Some(BinaryOperator::Divide)
-            } else {
-                None
+    /// An expression whose operators bind at least as tightly as `min`.
+    /// Operators that bind tighter are parsed further in, so they're grouped
+    /// first: `a + b * c` is `a + (b * c)`.
+    fn binary(&mut self, min: Precedence) -> Result<ASTNode, CompileError> {
+        let mut node = self.unary()?;
+        loop {
+            if min == Precedence::Assignment && self.match_token(Kind::EqualsTo) {
+                return self.assignment(node);
+            }
+            if min == Precedence::Assignment {
+                if let Some(op) = self.compound_operator() {
+                    return self.compound_assignment(node, op);
+                }
+            }
+            if min <= Precedence::Conditional && self.match_token(Kind::Question) {
+                node = self.conditional(node)?;
+                continue;
             }
-        } {
-            let right = self.primary()?;
+            let Some(&(_, op, precedence)) =
+                BINARY_OPERATORS.iter().find(|(kind, ..)| self.check(*kind))
+            else {
+                return Ok(node);
+            };
+            if precedence < min {
+                return Ok(node);
+            }
+            self.advance();
+            let span = self.previous().span;
+            // `**` groups to the right, and every other operator to the left
+            let right_min = if op == BinaryOperator::Power {
+                precedence
+            } else {
+                precedence.next()
+            };
+            let right = self.nested(|parser| parser.binary(right_min))?;
             node = ASTNode::BinaryOp {
-                left: Box::new(node),
-                op: operator,
-                right: Box::new(right),
+                left: self.ast.add(node),
+                op,
+                right: self.ast.add(right),
+                span,
             };
         }
-        Ok(node)
     }
-    
-    fn primary(&mut self) -> Result<ASTNode, String> {
-        let prev_token: Token = self.previous().clone();
+
+    /// The rest of an assignment after its `=`, storing into `target`.
+    /// Assignments group to the right, so `a = b = 0` stores 0 in `b` and
+    /// then in `a`.
+    fn assignment(&mut self, target: ASTNode) -> Result<ASTNode, CompileError> {
+        if !matches!(
+            target,
+            ASTNode::Identifier { .. } | ASTNode::Index { .. } | ASTNode::FieldAccess { .. }
+        ) {
+            return Err(self.error_at_previous("Expected a variable, element or field before '='"));
+        }
+        let value = self.child(Self::expression)?;
+        match target {
+            ASTNode::Identifier { name, span } => Ok(ASTNode::Assignment { name, value, span }),
+            ASTNode::Index { array, index, span } => Ok(ASTNode::IndexAssignment {
+                array,
+                index,
+                value,
+                span,
+            }),
+            ASTNode::FieldAccess {
+                object,
+                field,
+                span,
+            } => Ok(ASTNode::FieldAssignment {
+                object,
+                field,
+                value,
+                span,
+            }),
+            _ => unreachable!("checked above"),
+        }
+    }
+
+    /// The rest of `a op= b` after its operator
+    fn compound_assignment(
+        &mut self,
+        target: ASTNode,
+        op: BinaryOperator,
+    ) -> Result<ASTNode, CompileError> {
+        let ASTNode::Identifier { name, span } = target else {
+            return Err(
+                self.error_at_previous(&format!("Expected a variable before '{}='", op.symbol()))
+            );
+        };
+        let value = self.child(Self::expression)?;
+        Ok(ASTNode::CompoundAssignment {
+            name,
+            op,
+            value,
+            span,
+        })
+    }
+
+    /// The rest of `a ? b : c` after its `?`, grouping to the right
+    fn conditional(&mut self, condition: ASTNode) -> Result<ASTNode, CompileError> {
+        let span = self.previous().span;
+        let then_value = self.expression()?;
+        self.consume(Kind::Colon, "Expected ':' in conditional expression")?;
+        let else_value = self.nested(|parser| parser.binary(Precedence::Conditional))?;
+        Ok(ASTNode::Conditional {
+            condition: self.ast.add(condition),
+            then_value: self.ast.add(then_value),
+            else_value: self.ast.add(else_value),
+            span,
+        })
+    }
+
+    /// `!a` and `-a`, whose operand can be a power, so `-2 ** 2` is
+    /// `-(2 ** 2)`
+    fn unary(&mut self) -> Result<ASTNode, CompileError> {
+        if self.match_token(Kind::Bang) {
+            let span = self.previous().span;
+            let operand = self.nested(|parser| parser.binary(Precedence::Unary))?;
+            return Ok(ASTNode::UnaryOp {
+                op: UnaryOperator::Not,
+                operand: self.ast.add(operand),
+                span,
+            });
+        }
+        if self.match_token(Kind::Minus) {
+            let span = self.previous().span;
+            let operand = self.nested(|parser| parser.binary(Precedence::Unary))?;
+            // Fold negative literals so `-5` is a number rather than an operation
+            return Ok(match operand {
+                ASTNode::Int(num) => ASTNode::Int(num.wrapping_neg()),
+                ASTNode::Number(num) => ASTNode::Number(-num),
+                operand => ASTNode::UnaryOp {
+                    op: UnaryOperator::Negate,
+                    operand: self.ast.add(operand),
+                    span,
+                },
+            });
+        }
+        self.postfix()
+    }
+
+    /// Indexing, slicing and field access, like `a[0]`, `a[1:3]` and `p.x`,
+    /// which bind tighter than any operator
+    fn postfix(&mut self) -> Result<ASTNode, CompileError> {
+        let mut node = self.primary()?;
+        loop {
+            if self.match_token(Kind::OpenBracket) {
+                let span = self.previous().span;
+                let start = if self.check(Kind::Colon) {
+                    None
+                } else {
+                    Some(self.expression()?)
+                };
+                if self.match_token(Kind::Colon) {
+                    let end = if self.check(Kind::CloseBracket) {
+                        None
+                    } else {
+                        Some(self.expression()?)
+                    };
+                    self.consume(Kind::CloseBracket, "Expected ']' after slice")?;
+                    node = ASTNode::Slice {
+                        array: self.ast.add(node),
+                        start: start.map(|start| self.ast.add(start)),
+                        end: end.map(|end| self.ast.add(end)),
+                        span,
+                    };
+                    continue;
+                }
+                self.consume(Kind::CloseBracket, "Expected ']' after index")?;
+                let index = start.expect("an index without a ':' isn't empty");
+                node = ASTNode::Index {
+                    array: self.ast.add(node),
+                    index: self.ast.add(index),
+                    span,
+                };
+            } else if self.match_token(Kind::Dot) {
+                let field = self
+                    .consume(Kind::Identifier, "Expected field name after '.'")?
+                    .clone();
+                let field_name = self.token_to_string(&field)?;
+                // `module.item(...)` and `module.Item { ... }` use what a
+                // module declares
+                if let ASTNode::Identifier { name, span } = &node {
+                    let qualified = format!("{}.{}", name, field_name);
+                    if self.match_token(Kind::OpenParen) {
+                        let args = self.arguments(Kind::CloseParen)?;
+                        self.consume(Kind::CloseParen, "Expected ')' after arguments")?;
+                        node = ASTNode::Call {
+                            name: qualified,
+                            args,
+                            span: *span,
+                        };
+                        continue;
+                    }
+                    if self.struct_literals && self.match_token(Kind::OpenBrace) {
+                        node = self.struct_literal(qualified, *span)?;
+                        continue;
+                    }
+                }
+                node = ASTNode::FieldAccess {
+                    object: self.ast.add(node),
+                    field: field_name,
+                    span: field.span,
+                };
+            } else {
+                return Ok(node);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<ASTNode, CompileError> {
+        if self.match_token(Kind::Integer) {
+            let value = self.previous().clone();
+            if let TokenValue::Integer(num) = value.value {
+                return Ok(ASTNode::Int(num));
+            }
+            return Err(self.error_at_previous("Expected integer"));
+        }
+
         if self.match_token(Kind::Number) {
             let value = self.previous().clone();
             if let TokenValue::Number(num) = value.value {
                 return Ok(ASTNode::Number(num));
             }
-            return Err("Expected number".to_string());
+            return Err(self.error_at_previous("Expected number"));
         }
 
-        if self.match_token(Kind::Identifier) {
-            let name = self.previous().clone();
-            return Ok(ASTNode::Identifier(self.token_to_string(&name)?));
+        if self.match_token(Kind::True) {
+            return Ok(ASTNode::Bool(true));
+        }
+
+        if self.match_token(Kind::False) {
+            return Ok(ASTNode::Bool(false));
+        }
+
+        if self.match_token(Kind::String) {
+            let token = self.previous().clone();
+            return Ok(ASTNode::Str(self.token_to_string(&token)?));
+        }
+
+        if self.match_token(Kind::Char) {
+            if let TokenValue::Char(c) = self.previous().value {
+                return Ok(ASTNode::Char(c));
+            }
+            return Err(self.error_at_previous("Expected a character"));
+        }
+
+        if self.match_token(Kind::StringStart) {
+            return self.interpolation();
         }
 
-        if self.match_token(Kind::EqualsTo) {
-            let expr = self.expression()?;
-            return Ok(ASTNode::Assignment {
-                name: self.token_to_string(&prev_token)?,
-                value: Box::new(expr),
+        if self.match_token(Kind::Identifier) {
+            let name = self.previous().clone();
+            if self.match_token(Kind::Increment) {
+                return Ok(ASTNode::Increment {
+                    name: self.token_to_string(&name)?,
+                    span: name.span,
+                });
+            }
+            if self.match_token(Kind::Decrement) {
+                return Ok(ASTNode::Decrement {
+                    name: self.token_to_string(&name)?,
+                    span: name.span,
+                });
+            }
+            if self.match_token(Kind::OpenParen) {
+                let args = self.arguments(Kind::CloseParen)?;
+                self.consume(Kind::CloseParen, "Expected ')' after arguments")?;
+                return Ok(ASTNode::Call {
+                    name: self.token_to_string(&name)?,
+                    args,
+                    span: name.span,
+                });
+            }
+            if self.struct_literals && self.match_token(Kind::OpenBrace) {
+                return self.struct_literal(self.token_to_string(&name)?, name.span);
+            }
+            return Ok(ASTNode::Identifier {
+                name: self.token_to_string(&name)?,
+                span: name.span,
             });
         }
 
         if self.match_token(Kind::OpenParen) {
-            let expr = self.expression()?;
-            dbg!(self.tokens[self.current + 1].clone());
+            // Parentheses end the ambiguity with a block
+            let struct_literals = std::mem::replace(&mut self.struct_literals, true);
+            let expr = self.expression();
+            self.struct_literals = struct_literals;
+            let expr = expr?;
             self.consume(Kind::CloseParen, "Expected ')' after expression")?;
             return Ok(expr);
         }
 
-        if self.match_token(Kind::Increment) {
-            return Ok(ASTNode::Increment(self.token_to_string(&prev_token)?));
+        if self.match_token(Kind::Pipe) {
+            return self.lambda();
+        }
+
+        // `||` starts a lambda without parameters
+        if self.match_token(Kind::Or) {
+            let span = self.previous().span;
+            let body = self.child(|parser| parser.nested(Self::expression))?;
+            return Ok(ASTNode::Lambda {
+                params: Vec::new(),
+                body,
+                span,
+            });
+        }
+
+        if self.match_token(Kind::OpenBracket) {
+            let span = self.previous().span;
+            let elements = self.arguments(Kind::CloseBracket)?;
+            self.consume(Kind::CloseBracket, "Expected ']' after array elements")?;
+            return Ok(ASTNode::ArrayLiteral { elements, span });
         }
 
-        if self.match_token(Kind::Decrement) {
-            return Ok(ASTNode::Decrement(self.token_to_string(&prev_token)?));
+        if self.match_token(Kind::OpenBrace) {
+            let span = self.previous().span;
+            let mut entries = Vec::new();
+            if !self.check(Kind::CloseBrace) {
+                loop {
+                    let key = self.expression()?;
+                    self.consume(Kind::Colon, "Expected ':' after map key")?;
+                    let key = self.ast.add(key);
+                    entries.push((key, self.child(Self::expression)?));
+                    if !self.match_token(Kind::Comma) {
+                        break;
+                    }
+                }
+            }
+            self.consume(Kind::CloseBrace, "Expected '}' after map entries")?;
+            return Ok(ASTNode::MapLiteral { entries, span });
         }
 
-        Err("Expected expression".to_string())
+        Err(self.error_at_current("Expected expression"))
     }
 
-    fn consume(&mut self, kind: Kind, message: &str) -> Result<Token, String> {
+    /// Parse a lambda after its opening `|`. Its body is an expression that
+    /// reaches as far to the right as it can.
+    fn lambda(&mut self) -> Result<ASTNode, CompileError> {
+        let span = self.previous().span;
+        let mut params = Vec::new();
+        while !self.check(Kind::Pipe) {
+            let param = self.consume(Kind::Identifier, "Expected parameter name")?;
+            params.push(Param {
+                name: self.token_to_string(&param)?,
+                ty: self.annotation(Kind::Colon)?,
+            });
+            if !self.match_token(Kind::Comma) {
+                break;
+            }
+        }
+        self.consume(Kind::Pipe, "Expected '|' after the parameters of a lambda")?;
+        let body = self.child(|parser| parser.nested(Self::expression))?;
+        Ok(ASTNode::Lambda { params, body, span })
+    }
+
+    /// Parse the fields of a struct literal, after the `{`
+    fn struct_literal(&mut self, name: String, span: Span) -> Result<ASTNode, CompileError> {
+        let mut fields = Vec::new();
+        while !self.check(Kind::CloseBrace) {
+            let field = self.consume(Kind::Identifier, "Expected field name")?;
+            self.consume(Kind::Colon, "Expected ':' after field name")?;
+            fields.push((self.token_to_string(&field)?, self.child(Self::expression)?));
+            if !self.match_token(Kind::Comma) {
+                break;
+            }
+        }
+        self.consume(Kind::CloseBrace, "Expected '}' after struct fields")?;
+        Ok(ASTNode::StructLiteral { name, fields, span })
+    }
+
+    /// Parse comma-separated expressions up to, but not including, `close`
+    fn arguments(&mut self, close: Kind) -> Result<Vec<NodeId>, CompileError> {
+        let mut args = Vec::new();
+        if self.check(close) {
+            return Ok(args);
+        }
+        args.push(self.child(Self::expression)?);
+        while self.match_token(Kind::Comma) {
+            args.push(self.child(Self::expression)?);
+        }
+        Ok(args)
+    }
+
+    /// Consume a compound assignment token such as `+=`, returning its operator
+    fn compound_operator(&mut self) -> Option<BinaryOperator> {
+        if self.match_token(Kind::PlusEquals) {
+            Some(BinaryOperator::Plus)
+        } else if self.match_token(Kind::MinusEquals) {
+            Some(BinaryOperator::Minus)
+        } else if self.match_token(Kind::MultiplyEquals) {
+            Some(BinaryOperator::Multiply)
+        } else if self.match_token(Kind::DivideEquals) {
+            Some(BinaryOperator::Divide)
+        } else {
+            None
+        }
+    }
+
+    fn consume(&mut self, kind: Kind, message: &str) -> Result<Token<'a>, CompileError> {
         if self.check(kind) {
             return Ok(self.advance().clone());
         }
-        Err(message.to_string())
+        Err(self.error_at_current(message))
+    }
+
+    /// Build a parse error pointing at the token about to be consumed
+    fn error_at_current(&self, message: &str) -> CompileError {
+        CompileError::Parse {
+            message: message.to_string(),
+            span: self.peek().span,
+        }
+    }
+
+    /// Build a parse error pointing at the token just consumed
+    fn error_at_previous(&self, message: &str) -> CompileError {
+        CompileError::Parse {
+            message: message.to_string(),
+            span: self.previous().span,
+        }
+    }
+
+    /// Parse a node and add it to the AST, as a child of the node being built
+    fn child(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<ASTNode, CompileError>,
+    ) -> Result<NodeId, CompileError> {
+        let node = parse(self)?;
+        Ok(self.ast.add(node))
+    }
+
+    /// Parse something nested inside what is being parsed, failing when the
+    /// nesting is deeper than [`MAX_NESTING`]
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, CompileError>,
+    ) -> Result<T, CompileError> {
+        if self.depth >= MAX_NESTING {
+            return Err(self.error_at_current(&format!(
+                "Nested too deeply, the limit is {} levels",
+                MAX_NESTING
+            )));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
     }
 
     fn match_token(&mut self, kind: Kind) -> bool {
@@ -190,10 +1211,8 @@ impl<'a> Parser<'a> {
         !self.is_at_end() && self.peek().kind == kind
     }
 
-    fn advance(&mut self) -> &Token {
-        if !self.is_at_end() {
-            self.current += 1;
-        }
+    fn advance(&mut self) -> &Token<'a> {
+        self.tokens.advance();
         self.previous()
     }
 
@@ -201,19 +1220,45 @@ impl<'a> Parser<'a> {
         self.peek().kind == Kind::Eof
     }
 
-    fn peek(&self) -> &Token {
-        &self.tokens[self.current]
+    fn peek(&self) -> &Token<'a> {
+        self.tokens.peek()
+    }
+
+    /// The last token consumed, or the first token before any are
+    fn previous(&self) -> &Token<'a> {
+        self.tokens.previous()
     }
 
-    fn previous(&self) -> &Token {
-        &self.tokens[self.current - 1]
+    /// The rest of an interpolated string after its first piece of text
+    fn interpolation(&mut self) -> Result<ASTNode, CompileError> {
+        let start = self.previous().clone();
+        let mut pieces = vec![self.token_to_string(&start)?];
+        let mut values = Vec::new();
+        loop {
+            values.push(self.child(Self::expression)?);
+            if self.match_token(Kind::StringMiddle) {
+                let token = self.previous().clone();
+                pieces.push(self.token_to_string(&token)?);
+                continue;
+            }
+            let end = self.consume(Kind::StringEnd, "Expected '}' after interpolated value")?;
+            pieces.push(self.token_to_string(&end)?);
+            return Ok(ASTNode::Interpolation {
+                pieces,
+                values,
+                span: start.span,
+            });
+        }
     }
 
-    fn token_to_string(&self, token: &Token) -> Result<String, String> {
-        if let TokenValue::String(atom) = &token.value {
-            Ok(atom.to_string())
+    fn token_to_string(&self, token: &Token) -> Result<String, CompileError> {
+        if let TokenValue::String(text) = &token.value {
+            Ok(text.to_string())
         } else {
-            Err("Expected string".to_string())
+            Err(CompileError::Parse {
+                message: "Expected string".to_string(),
+                span: token.span,
+            })
         }
     }
 }
@@ -0,0 +1,189 @@
+use std::io::{self, BufRead, Write};
+
+use crate::ast_printer;
+use crate::diagnostics::Diagnostic;
+use crate::error::CompileError;
+use crate::interpreter::{Interpreter, Value};
+use crate::lexer::{Kind, Lexer};
+use crate::parser::{ASTNode, Ast, Parser};
+use crate::semantic_analyzer::SemanticAnalyzer;
+
+const HELP: &str = "\
+Enter osho statements or expressions. Commands:
+    :vars         List the variables and their values
+    :ast <code>   Show the syntax tree of <code>
+    :help         Show this message
+    :quit         Exit the REPL";
+
+/// Interactive session that keeps variables alive between inputs
+pub struct Repl<W: Write> {
+    /// Every statement entered so far, which the functions declared keep
+    /// their bodies in
+    ast: Ast,
+    analyzer: SemanticAnalyzer,
+    interpreter: Interpreter<W>,
+}
+
+impl<W: Write> Repl<W> {
+    /// Create a session that prints to the given writer
+    pub fn new(out: W) -> Self {
+        Self {
+            ast: Ast::new(),
+            analyzer: SemanticAnalyzer::new(),
+            interpreter: Interpreter::with_output(out),
+        }
+    }
+
+    /// Check and run a piece of source against the session state, returning
+    /// the value of the last statement if it is a bare expression
+    pub fn eval(&mut self, source: &str) -> Result<Option<Value>, Vec<CompileError>> {
+        let mut parser =
+            Parser::from_lexer(Lexer::new(source)).with_ast(std::mem::take(&mut self.ast));
+        let parsed = parser.parse_statements();
+        self.ast = parser.into_ast();
+        let statements = parsed?;
+
+        let ast = &self.ast;
+        let mut last = None;
+        for &stmt in &statements {
+            last = None;
+            if let ASTNode::Call { name, args, span } = &ast[stmt] {
+                // Calls are run for what they do, and show their result if
+                // they have one
+                self.analyzer
+                    .check_call(ast, name, args, *span)
+                    .map_err(|err| vec![err])?;
+                last = self
+                    .interpreter
+                    .call(ast, name, args, *span)
+                    .map_err(|err| vec![err])?;
+            } else if ast[stmt].is_expression() {
+                self.analyzer
+                    .check_expression(ast, stmt)
+                    .map_err(|err| vec![err])?;
+                let val = self
+                    .interpreter
+                    .evaluate_expression(ast, stmt)
+                    .map_err(|err| vec![err])?;
+                last = Some(val);
+            } else {
+                self.analyzer.analyze(ast, stmt).map_err(|err| vec![err])?;
+                self.interpreter.run(ast, stmt).map_err(|err| vec![err])?;
+            }
+        }
+        Ok(last)
+    }
+
+    /// Read lines from `input` until EOF or `:quit`
+    pub fn run<R: BufRead>(&mut self, mut input: R) -> io::Result<()> {
+        writeln!(self.out(), "osho repl, type :help for help")?;
+        let mut buffer = String::new();
+        loop {
+            let prompt = if buffer.is_empty() { ">> " } else { ".. " };
+            write!(self.out(), "{}", prompt)?;
+            self.out().flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(self.out())?;
+                return Ok(());
+            }
+
+            if buffer.is_empty() {
+                match line.trim() {
+                    "" => continue,
+                    ":quit" | ":q" => return Ok(()),
+                    ":help" => {
+                        writeln!(self.out(), "{}", HELP)?;
+                        continue;
+                    }
+                    ":vars" => {
+                        self.print_variables()?;
+                        continue;
+                    }
+                    command if command.starts_with(":ast") => {
+                        self.print_ast(command.trim_start_matches(":ast"))?;
+                        continue;
+                    }
+                    command if command.starts_with(':') => {
+                        writeln!(self.out(), "Unknown command '{}'", command)?;
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+
+            // Keep reading while a block is still open
+            buffer.push_str(&line);
+            if Self::open_braces(&buffer) > 0 {
+                continue;
+            }
+
+            let source = std::mem::take(&mut buffer);
+            match self.eval(&source) {
+                Ok(Some(val)) => writeln!(self.out(), "{}", val)?,
+                Ok(None) => {}
+                Err(errors) => self.print_errors(&source, &errors)?,
+            }
+        }
+    }
+
+    fn out(&mut self) -> &mut W {
+        self.interpreter.output()
+    }
+
+    fn print_variables(&mut self) -> io::Result<()> {
+        let lines: Vec<String> = self
+            .interpreter
+            .variables()
+            .into_iter()
+            .map(|(name, val)| format!("{}: {} = {}", name, val.type_name(), val))
+            .collect();
+        for line in lines {
+            writeln!(self.out(), "{}", line)?;
+        }
+        Ok(())
+    }
+
+    fn print_ast(&mut self, source: &str) -> io::Result<()> {
+        let mut lexer = Lexer::new(source);
+        let parsed = lexer
+            .get_tokens()
+            .map_err(|err| vec![err])
+            .and_then(|tokens| {
+                let mut parser = Parser::new(&tokens);
+                let statements = parser.parse_statements()?;
+                Ok((parser.into_ast(), statements))
+            });
+        match parsed {
+            Ok((ast, statements)) => {
+                for stmt in statements {
+                    write!(self.out(), "{}", ast_printer::to_tree(&ast, stmt))?;
+                }
+                Ok(())
+            }
+            Err(errors) => self.print_errors(source, &errors),
+        }
+    }
+
+    fn print_errors(&mut self, source: &str, errors: &[CompileError]) -> io::Result<()> {
+        for err in errors {
+            let rendered = Diagnostic::from(err).render("<repl>", source);
+            write!(self.out(), "{}", rendered)?;
+        }
+        Ok(())
+    }
+
+    /// How many more '{' than '}' the input has, ignoring comments. Input
+    /// that doesn't lex is treated as complete so the error gets reported.
+    fn open_braces(source: &str) -> i64 {
+        let Ok(tokens) = Lexer::new(source).get_tokens() else {
+            return 0;
+        };
+        tokens.iter().fold(0, |depth, token| match token.kind {
+            Kind::OpenBrace => depth + 1,
+            Kind::CloseBrace => depth - 1,
+            _ => depth,
+        })
+    }
+}
@@ -1,86 +1,1980 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
-use std::collections::HashMap;
+use serde::Serialize;
 
-use crate::parser::{ASTNode, BinaryOperator};
+use crate::builtins::{self, Builtin};
+use crate::desugar;
+use crate::diagnostics::{Diagnostic, Lint};
+use crate::error::CompileError;
+use crate::format;
+use crate::interpreter::Value;
+use crate::ir::{self, Lowerer};
+use crate::lexer::Span;
+use crate::optimizer;
+use crate::parser::{self, ASTNode, Ast, BinaryOperator, NodeId, Param, UnaryOperator};
+use crate::symbol_table::{Symbol, SymbolId, SymbolTable};
 
+/// The static type of an expression
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Type {
+    Int,
+    Float,
+    Bool,
+    /// Text, which for now is only used as the keys of maps
+    Str,
+    /// One character of a string, written `'a'`
+    Char,
+    /// An array with elements of the given type, written `[int]`
+    Array(Box<Type>),
+    /// A map from strings to values of the given type, written
+    /// `{string: int}`
+    Map(Box<Type>),
+    /// A value of the struct type with the given name
+    Struct(String),
+    /// A lambda taking arguments of the given types, written
+    /// `fn(int) -> int`, or `fn(int)` when it gives nothing back
+    Function {
+        params: Vec<Type>,
+        result: Option<Box<Type>>,
+    },
+}
+
+impl Type {
+    pub(crate) fn is_numeric(&self) -> bool {
+        matches!(self, Type::Int | Type::Float)
+    }
+
+    /// Whether a value of type `ty` can be stored where this type is
+    /// expected. Integers may be stored into floats.
+    pub(crate) fn accepts(&self, ty: &Type) -> bool {
+        self == ty || (*self == Type::Float && *ty == Type::Int)
+    }
+
+    /// The type of an array holding all of `elements`: ints mixed with
+    /// floats become floats
+    pub(crate) fn element_type(elements: &[Type]) -> Result<Type, String> {
+        let Some(first) = elements.first() else {
+            return Err("Cannot infer the type of an empty array".to_string());
+        };
+        elements.iter().try_fold(first.clone(), |common, ty| {
+            common.unify(ty).ok_or_else(|| {
+                format!(
+                    "Array elements must have the same type, found {} and {}",
+                    common, ty
+                )
+            })
+        })
+    }
+
+    /// The type of the values of a map holding all of `values`, promoted
+    /// the same way as array elements
+    pub(crate) fn value_type(values: &[Type]) -> Result<Type, String> {
+        let Some(first) = values.first() else {
+            return Err("Cannot infer the type of an empty map".to_string());
+        };
+        values.iter().try_fold(first.clone(), |common, ty| {
+            common.unify(ty).ok_or_else(|| {
+                format!(
+                    "Map values must have the same type, found {} and {}",
+                    common, ty
+                )
+            })
+        })
+    }
+
+    /// The type that values of both types can be stored as, if any
+    fn unify(&self, ty: &Type) -> Option<Type> {
+        if self.accepts(ty) {
+            Some(self.clone())
+        } else if ty.accepts(self) {
+            Some(ty.clone())
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::Bool => write!(f, "bool"),
+            Type::Str => write!(f, "string"),
+            Type::Char => write!(f, "char"),
+            Type::Array(element) => write!(f, "[{}]", element),
+            Type::Map(value) => write!(f, "{{string: {}}}", value),
+            Type::Struct(name) => write!(f, "{}", name),
+            Type::Function { params, result } => {
+                let params: Vec<_> = params.iter().map(Type::to_string).collect();
+                write!(f, "fn({})", params.join(", "))?;
+                match result {
+                    Some(result) => write!(f, " -> {}", result),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// The fields of a declared struct, in order. Their types are unknown until
+/// the first value of the struct is built, which decides them.
+type StructFields = Vec<(String, Option<Type>)>;
+
+/// The types of a function's parameters and of its result, if it returns
+/// a value
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Signature {
+    pub params: Vec<Type>,
+    pub result: Option<Type>,
+}
+
+/// A declared function. Like the fields of a struct, the types of its
+/// parameters are decided by its first call, which is when its body is
+/// checked, unless they are annotated.
+struct Function {
+    params: Vec<Param>,
+
+    /// The annotated type of the result
+    result: Option<Type>,
+
+    body: NodeId,
+    span: Span,
+    signature: Option<Signature>,
+}
+
+impl Signature {
+    /// The type of a lambda with this signature
+    pub(crate) fn function_type(&self) -> Type {
+        Type::Function {
+            params: self.params.clone(),
+            result: self.result.clone().map(Box::new),
+        }
+    }
+}
+
+/// A lambda and the variables it captures. Like a function's, the types
+/// of its parameters may be decided by its first call.
+struct Closure {
+    /// The captured variables, as they were when the lambda was made
+    captures: Vec<(String, SymbolId)>,
+
+    signature: Option<Signature>,
+}
+
+/// A function whose body is being checked
+struct FunctionScope {
+    name: String,
+
+    /// What the `return`s seen so far give back, `None` before the first.
+    /// An annotated result type is known from the start.
+    returns: Option<Option<Type>>,
+
+    /// Whether the result type is annotated, so ints may be returned as
+    /// floats
+    annotated: bool,
+}
+
+/// Checks declarations and types without executing the program
 pub struct SemanticAnalyzer {
-    variables: HashMap<String, f64>,
+    /// Declared variables. A `let` may shadow a variable from an outer
+    /// scope, but not one declared earlier in the same scope.
+    symbols: SymbolTable,
+
+    /// Declared structs by name. They are visible everywhere after their
+    /// declaration.
+    structs: HashMap<String, StructFields>,
+
+    /// Declared functions by name, which can only be called after their
+    /// declaration
+    functions: HashMap<String, Function>,
+
+    /// Functions whose bodies are being checked, innermost last
+    checking: Vec<FunctionScope>,
+
+    /// Every lambda seen so far, by node
+    closures: HashMap<NodeId, Closure>,
+
+    /// Variables holding a lambda without annotated parameters, which are
+    /// only checked once the variable is called
+    pending: HashMap<SymbolId, NodeId>,
+
+    /// Keep checking after an error, recording it in `errors`
+    accumulate: bool,
+
+    errors: Vec<CompileError>,
+
+    /// Variables whose `let` failed to check, so their type is unknown
+    unknown: HashSet<SymbolId>,
+
+    /// Whether checking the current statement used a variable in `unknown`,
+    /// so its error only follows from the one already reported
+    used_unknown: bool,
+
+    /// Warnings for every lint, whether enabled or not
+    warnings: Vec<Diagnostic>,
+
+    /// Whether a checked statement reads input
+    reads_input: bool,
+
+    /// Number of loops around the statement being checked, since `break`
+    /// and `continue` only work inside one
+    loop_depth: usize,
+
+    /// The type of each checked expression, by its span. Literals have no
+    /// span, and their type is plain anyway.
+    types: HashMap<Span, Type>,
+
+    /// Constructs only `osho run` supports for now, each as an error at
+    /// its location, so compiled targets can list them all at once
+    interpreter_only: Vec<CompileError>,
+}
+
+impl Default for SemanticAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SemanticAnalyzer {
     pub fn new() -> Self {
         Self {
-            variables: HashMap::new(),
+            symbols: SymbolTable::new(),
+            structs: HashMap::new(),
+            functions: HashMap::new(),
+            checking: Vec::new(),
+            closures: HashMap::new(),
+            pending: HashMap::new(),
+            accumulate: false,
+            errors: Vec::new(),
+            unknown: HashSet::new(),
+            used_unknown: false,
+            warnings: Vec::new(),
+            reads_input: false,
+            types: HashMap::new(),
+            loop_depth: 0,
+            interpreter_only: Vec::new(),
+        }
+    }
+
+    /// Whether the programs checked so far use `input`
+    pub fn reads_input(&self) -> bool {
+        self.reads_input
+    }
+
+    /// The variables declared by the programs checked so far
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    /// The warnings found so far, in source order
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        let mut warnings = std::mem::take(&mut self.warnings);
+        warnings.sort_by_key(|warning| warning.span.map(|span| span.offset));
+        warnings
+    }
+
+    /// The constructs in the programs checked so far that compiled targets
+    /// can't handle yet, in source order
+    pub fn interpreter_only(&self) -> Vec<CompileError> {
+        let mut errors = self.interpreter_only.clone();
+        errors.sort_by_key(|err| err.span().map(|span| span.offset));
+        errors
+    }
+
+    /// Check the whole program, collecting every error instead of stopping at the first
+    pub fn analyze_all(&mut self, ast: &Ast) -> Result<(), Vec<CompileError>> {
+        self.accumulate = true;
+        let result = self.analyze(ast, ast.root());
+        self.accumulate = false;
+
+        let mut errors = std::mem::take(&mut self.errors);
+        if let Err(err) = result {
+            errors.push(err);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
         }
     }
 
-    pub fn analyze(&mut self, node: &ASTNode) -> Result<(), String> {
-        match node {
+    /// Check the whole program and lower it to the typed IR. A program
+    /// using constructs only `osho run` supports fails with an error for
+    /// each of them.
+    pub fn lower(&mut self, ast: &Ast) -> Result<ir::Program, Vec<CompileError>> {
+        let ast = &desugar::desugared(ast);
+        self.analyze_all(ast)?;
+        let interpreter_only = self.interpreter_only();
+        if !interpreter_only.is_empty() {
+            return Err(interpreter_only);
+        }
+        // Only structs that are constructed have field types
+        let struct_types = self
+            .structs
+            .iter()
+            .filter_map(|(name, fields)| {
+                let fields = fields
+                    .iter()
+                    .map(|(field, ty)| Some((field.clone(), ty.clone()?)))
+                    .collect::<Option<_>>()?;
+                Some((name.clone(), fields))
+            })
+            .collect();
+        let signatures = self
+            .functions
+            .iter()
+            .filter_map(|(name, function)| Some((name.clone(), function.signature.clone()?)))
+            .collect();
+        let lambdas = self
+            .closures
+            .iter()
+            .filter(|(_, closure)| closure.captures.is_empty())
+            .filter_map(|(lambda, closure)| Some((*lambda, closure.signature.clone()?)))
+            .collect();
+        let types = std::mem::take(&mut self.types);
+        Lowerer::new(ast, struct_types, signatures, lambdas, types)
+            .lower()
+            .map_err(|message| vec![CompileError::Codegen { message }])
+    }
+
+    pub fn analyze(&mut self, ast: &Ast, node: NodeId) -> Result<(), CompileError> {
+        match &ast[node] {
             ASTNode::Program(statements) => {
-                for stmt in statements {
-                    self.analyze(stmt)?;
+                self.analyze_statements(ast, statements)?;
+                self.check_uncalled(ast)?;
+                self.warn_unused(self.symbols.current_scope());
+                self.warn_uncalled(ast);
+            }
+            ASTNode::LetDeclaration {
+                name,
+                mutable,
+                ty: annotation,
+                value,
+                span,
+            } => {
+                // The first call decides the types of unannotated parameters
+                if let (None, ASTNode::Lambda { params, .. }) = (annotation, &ast[*value]) {
+                    if params.iter().any(|param| param.ty.is_none()) {
+                        self.make_closure(ast, *value)?;
+                        let placeholder = Type::Function {
+                            params: Vec::new(),
+                            result: None,
+                        };
+                        let id = self.declare(name, placeholder, *mutable, *span)?;
+                        self.pending.insert(id, *value);
+                        return Ok(());
+                    }
+                }
+                let ty = match annotation {
+                    Some(annotation) => self.check_annotated(ast, name, annotation, *value, *span),
+                    None => self.check_expression(ast, *value),
+                };
+                let ty = match ty {
+                    Ok(ty) => ty,
+                    Err(err) => {
+                        // Uses of the variable would only repeat the error
+                        if self.accumulate {
+                            if let Ok(id) = self.declare(name, Type::Int, *mutable, *span) {
+                                self.unknown.insert(id);
+                            }
+                        }
+                        return Err(err);
+                    }
+                };
+                self.declare(name, ty, *mutable, *span)?;
+            }
+            ASTNode::ConstDeclaration { name, value, span } => {
+                let ty = self.check_expression(ast, *value)?;
+                let constant = self.evaluate_constant(ast, name, *value, *span)?;
+                if ty == Type::Str {
+                    self.note_interpreter_only("String constants are", *span);
+                }
+                let id = self.declare(name, ty, false, *span)?;
+                self.symbols.get_mut(id).constant = Some(constant);
+            }
+            ASTNode::StructDeclaration { name, fields, span } => {
+                if self.structs.contains_key(name) {
+                    return Err(Self::error(
+                        format!("Struct '{}' is already declared", name),
+                        *span,
+                    ));
+                }
+                for (i, field) in fields.iter().enumerate() {
+                    if fields[..i].contains(field) {
+                        return Err(Self::error(
+                            format!("Field '{}' is declared twice in struct '{}'", field, name),
+                            *span,
+                        ));
+                    }
                 }
+                let fields = fields.iter().map(|field| (field.clone(), None)).collect();
+                self.structs.insert(name.clone(), fields);
             }
-            ASTNode::LetDeclaration { name, value } => {
-                if self.variables.contains_key(name) {
-                    return Err(format!("Variable '{}' is already declared", name));
+            ASTNode::FunctionDeclaration {
+                name,
+                params,
+                result,
+                body,
+                span,
+            } => {
+                if !self.symbols.is_global() || !self.checking.is_empty() {
+                    return Err(Self::error(
+                        "Functions can only be declared at the top level".to_string(),
+                        *span,
+                    ));
+                }
+                if Builtin::from_name(name).is_some() {
+                    return Err(Self::error(
+                        format!("Function '{}' is a builtin", name),
+                        *span,
+                    ));
+                }
+                if self.functions.contains_key(name) {
+                    return Err(Self::error(
+                        format!("Function '{}' is already declared", name),
+                        *span,
+                    ));
+                }
+                for (i, param) in params.iter().enumerate() {
+                    if params[..i].iter().any(|other| other.name == param.name) {
+                        return Err(Self::error(
+                            format!(
+                                "Parameter '{}' is declared twice in function '{}'",
+                                param.name, name
+                            ),
+                            *span,
+                        ));
+                    }
+                }
+                for ty in params
+                    .iter()
+                    .filter_map(|param| param.ty.as_ref())
+                    .chain(result)
+                {
+                    self.check_annotation(ty, *span)?;
+                }
+                let function = Function {
+                    params: params.clone(),
+                    result: result.clone(),
+                    body: *body,
+                    span: *span,
+                    signature: None,
+                };
+                self.functions.insert(name.clone(), function);
+            }
+            ASTNode::Return { value, span } => {
+                let ty = match value {
+                    Some(value) => Some(self.check_expression(ast, *value)?),
+                    None => None,
+                };
+                let Some(function) = self.checking.last_mut() else {
+                    return Err(Self::error(
+                        "'return' can only be used inside a function".to_string(),
+                        *span,
+                    ));
+                };
+                // An annotated float result also takes ints
+                let annotated = function.annotated;
+                let fits = |returns: &Option<Type>| match (returns, &ty) {
+                    (Some(returns), Some(ty)) if annotated => returns.accepts(ty),
+                    (returns, ty) => returns == ty,
+                };
+                match &function.returns {
+                    Some(returns) if !fits(returns) => {
+                        let describe = |ty: &Option<Type>| match ty {
+                            Some(ty) => ty.to_string(),
+                            None => "nothing".to_string(),
+                        };
+                        return Err(Self::error(
+                            format!(
+                                "Function '{}' returns {}, but this returns {}",
+                                function.name,
+                                describe(returns),
+                                describe(&ty)
+                            ),
+                            *span,
+                        ));
+                    }
+                    Some(_) => {}
+                    None => function.returns = Some(ty),
+                }
+            }
+            ASTNode::Assignment { name, value, span } => {
+                let ty = self.check_assigned(ast, *value)?;
+                let declared = self.lookup_for_write(name, *span)?;
+                if !declared.accepts(&ty) {
+                    return Err(Self::error(
+                        format!("Cannot assign {} to {} variable '{}'", ty, declared, name),
+                        *span,
+                    ));
+                }
+            }
+            ASTNode::CompoundAssignment {
+                name,
+                op,
+                value,
+                span,
+            } => {
+                let ty = self.check_expression(ast, *value)?;
+                let declared = self.lookup_for_write(name, *span)?;
+                if matches!(op, BinaryOperator::Divide | BinaryOperator::Modulo) {
+                    self.warn_zero_divisor(&ast[*value], *span);
+                }
+                let result = Self::arithmetic_type(op, &declared, &ty, *span)?;
+                if declared != result {
+                    return Err(Self::error(
+                        format!(
+                            "Cannot assign {} to {} variable '{}'",
+                            result, declared, name
+                        ),
+                        *span,
+                    ));
+                }
+            }
+            ASTNode::Increment { name, span } | ASTNode::Decrement { name, span } => {
+                let ty = self.lookup_for_write(name, *span)?;
+                if !ty.is_numeric() {
+                    return Err(Self::error(
+                        format!("Cannot increment or decrement {} variable '{}'", ty, name),
+                        *span,
+                    ));
+                }
+            }
+            ASTNode::Input { name, span } => {
+                self.reads_input = true;
+                let ty = self.lookup_for_write(name, *span)?;
+                if !ty.is_numeric() {
+                    return Err(Self::error(
+                        format!("Cannot read {} variable '{}' from input", ty, name),
+                        *span,
+                    ));
                 }
-                let val = self.evaluate_expression(value)?;
-                self.variables.insert(name.clone(), val);
             }
-            ASTNode::Assignment { name, value } => {
-                if !self.variables.contains_key(name) {
-                    return Err(format!("Variable '{}' is not declared", name));
+            ASTNode::IndexAssignment {
+                array,
+                index,
+                value,
+                span,
+            } => {
+                let name = Self::assigned_variable(ast, *array).ok_or_else(|| {
+                    Self::error("Expected a variable to assign to".to_string(), *span)
+                })?;
+                self.lookup_for_write(name, *span)?;
+                let (container, element) = self.check_index(ast, *array, *index, *span)?;
+                if container == Type::Str {
+                    return Err(Self::error(
+                        "Cannot assign to a character of a string; strings can't be changed"
+                            .to_string(),
+                        *span,
+                    ));
+                }
+                if matches!(ast[*value], ASTNode::Assignment { .. }) {
+                    return Err(Self::chained_element(*span));
+                }
+                let ty = self.check_expression(ast, *value)?;
+                if !element.accepts(&ty) {
+                    let what = match container {
+                        Type::Map(_) => "a value",
+                        _ => "an element",
+                    };
+                    return Err(Self::error(
+                        format!("Cannot assign {} to {} of {}", ty, what, container),
+                        *span,
+                    ));
                 }
-                let val = self.evaluate_expression(value)?;
-                self.variables.insert(name.clone(), val);
             }
-            ASTNode::Increment(name) => {
-                if let Some(val) = self.variables.get_mut(name) {
-                    *val += 1.0;
-                } else {
-                    return Err(format!("Variable '{}' is not declared", name));
+            ASTNode::FieldAssignment {
+                object,
+                field,
+                value,
+                span,
+            } => {
+                let name = Self::assigned_variable(ast, *object).ok_or_else(|| {
+                    Self::error("Expected a variable to assign to".to_string(), *span)
+                })?;
+                self.lookup_for_write(name, *span)?;
+                let (struct_name, declared) = self.check_field(ast, *object, field, *span)?;
+                if matches!(ast[*value], ASTNode::Assignment { .. }) {
+                    return Err(Self::chained_element(*span));
+                }
+                let ty = self.check_expression(ast, *value)?;
+                if ty != declared {
+                    return Err(Self::error(
+                        format!(
+                            "Cannot assign {} to field '{}' of {}, which is {}",
+                            ty, field, struct_name, declared
+                        ),
+                        *span,
+                    ));
                 }
             }
-            ASTNode::Decrement(name) => {
-                if let Some(val) = self.variables.get_mut(name) {
-                    *val -= 1.0;
-                } else {
-                    return Err(format!("Variable '{}' is not declared", name));
+            ASTNode::Call { name, args, span } => {
+                self.check_call(ast, name, args, *span)?;
+            }
+            ASTNode::Print {
+                format, args, span, ..
+            } => {
+                for arg in args {
+                    self.check_shown(ast, *arg, false)?;
                 }
+                format::print_pieces(format.as_deref(), args.len())
+                    .map_err(|err| Self::error(err, *span))?;
             }
-            ASTNode::Print(expr) => {
-                let val = self.evaluate_expression(expr)?;
-                println!("{}", val);
+            ASTNode::Block(statements) => {
+                self.begin_scope();
+                let result = self.analyze_statements(ast, statements);
+                self.end_scope();
+                result?;
+            }
+            ASTNode::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.check_condition(ast, *condition)?;
+                self.analyze(ast, *then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.analyze(ast, *else_branch)?;
+                }
+            }
+            ASTNode::While {
+                condition, body, ..
+            } => {
+                self.check_condition(ast, *condition)?;
+                self.analyze_loop_body(ast, *body)?;
+            }
+            ASTNode::DoWhile {
+                body, condition, ..
+            } => {
+                self.analyze_loop_body(ast, *body)?;
+                self.check_condition(ast, *condition)?;
+            }
+            ASTNode::Assert { condition, .. } => self.check_condition(ast, *condition)?,
+            ASTNode::For {
+                init,
+                condition,
+                step,
+                body,
+                ..
+            } => {
+                // Variables declared by the initializer are scoped to the loop
+                self.begin_scope();
+                let result = self.analyze_for(ast, init, condition, step, *body);
+                self.end_scope();
+                result?;
+            }
+            ASTNode::ForIn {
+                name,
+                range,
+                body,
+                span,
+            } => {
+                self.check_range(ast, *range)?;
+                self.begin_scope();
+                let result = self
+                    .declare(name, Type::Int, false, *span)
+                    .and_then(|_| self.analyze_loop_body(ast, *body));
+                self.end_scope();
+                result?;
+            }
+            ASTNode::Break { span } | ASTNode::Continue { span } => {
+                if self.loop_depth == 0 {
+                    let keyword = if matches!(ast[node], ASTNode::Break { .. }) {
+                        "break"
+                    } else {
+                        "continue"
+                    };
+                    return Err(Self::error(
+                        format!("'{}' can only be used inside a loop", keyword),
+                        *span,
+                    ));
+                }
+            }
+            // The loader replaces the imports at the top of a file
+            ASTNode::Import { span, .. } => {
+                return Err(Self::error(
+                    "Imports must be at the top level of a file".to_string(),
+                    *span,
+                ));
+            }
+            // Parsing a program replaces the modules at its top level
+            ASTNode::Module { span, .. } => {
+                return Err(Self::error(
+                    "Modules must be declared at the top level of a file".to_string(),
+                    *span,
+                ));
             }
 
-            _ => return Err("Unexpected AST node".to_string()),
+            _ => {
+                return Err(Self::error(
+                    "Unexpected AST node".to_string(),
+                    ast[node].span(),
+                ))
+            }
         }
         Ok(())
     }
 
-    fn evaluate_expression(&self, expr: &ASTNode) -> Result<f64, String> {
-        match expr {
-            ASTNode::Number(num) => Ok(*num),
-            ASTNode::Identifier(name) => {
-                if let Some(val) = self.variables.get(name) {
-                    Ok(*val)
-                } else {
-                    Err(format!("Variable '{}' is not declared", name))
+    fn analyze_statements(&mut self, ast: &Ast, statements: &[NodeId]) -> Result<(), CompileError> {
+        for stmt in statements {
+            self.used_unknown = false;
+            if let Err(err) = self.analyze(ast, *stmt) {
+                if !self.accumulate {
+                    return Err(err);
                 }
+                self.record(err);
             }
-            ASTNode::BinaryOp { left, op, right } => {
-                let left_val = self.evaluate_expression(left)?;
-                let right_val = self.evaluate_expression(right)?;
+        }
+        Ok(())
+    }
+
+    /// Keep an error to report once the whole program is checked, unless it
+    /// follows from a `let` that already failed
+    fn record(&mut self, err: CompileError) {
+        if !std::mem::take(&mut self.used_unknown) {
+            self.errors.push(err);
+        }
+    }
+
+    fn analyze_for(
+        &mut self,
+        ast: &Ast,
+        init: &Option<NodeId>,
+        condition: &Option<NodeId>,
+        step: &Option<NodeId>,
+        body: NodeId,
+    ) -> Result<(), CompileError> {
+        if let Some(init) = init {
+            self.analyze(ast, *init)?;
+        }
+        if let Some(condition) = condition {
+            self.check_condition(ast, *condition)?;
+        }
+        if let Some(step) = step {
+            self.analyze(ast, *step)?;
+        }
+        self.analyze_loop_body(ast, body)
+    }
+
+    /// Check that both bounds of the range a for-in loop iterates are ints
+    fn check_range(&mut self, ast: &Ast, range: NodeId) -> Result<(), CompileError> {
+        let ASTNode::Range {
+            start, end, span, ..
+        } = ast[range]
+        else {
+            unreachable!("the parser only puts ranges in for-in loops");
+        };
+        for bound in [start, end] {
+            let ty = self.check_expression(ast, bound)?;
+            if ty != Type::Int {
+                return Err(Self::error(
+                    format!("Range bounds must be ints, found {}", ty),
+                    span,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the body of a loop, where `break` and `continue` are allowed
+    fn analyze_loop_body(&mut self, ast: &Ast, body: NodeId) -> Result<(), CompileError> {
+        self.loop_depth += 1;
+        let result = self.analyze(ast, body);
+        self.loop_depth -= 1;
+        result
+    }
+
+    fn begin_scope(&mut self) {
+        self.symbols.begin_scope();
+    }
+
+    fn end_scope(&mut self) {
+        let ids = self.symbols.end_scope();
+        self.warn_unused(ids);
+    }
+
+    /// Warn about the variables that were never read
+    fn warn_unused(&mut self, ids: Vec<SymbolId>) {
+        for id in ids {
+            let symbol = self.symbols.get(id);
+            if symbol.reads == 0 {
+                self.warnings.push(Diagnostic::warning(
+                    Lint::UnusedVariables,
+                    format!("Unused variable '{}'", symbol.name),
+                    Some(symbol.span),
+                ));
+            }
+        }
+    }
+
+    /// Check the bodies of the functions that were never called but have
+    /// every parameter annotated, since their types don't wait on a call.
+    /// Nothing runs them, so they and whatever only they call are still
+    /// left out of generated code.
+    fn check_uncalled(&mut self, ast: &Ast) -> Result<(), CompileError> {
+        let mut annotated: Vec<_> = self
+            .functions
+            .iter()
+            .filter(|(_, function)| function.signature.is_none())
+            .filter_map(|(name, function)| {
+                let params = function.params.iter().map(|param| param.ty.clone());
+                Some((
+                    name.clone(),
+                    function.span,
+                    params.collect::<Option<Vec<_>>>()?,
+                ))
+            })
+            .collect();
+        annotated.sort_by_key(|(_, span, _)| span.offset);
+        let signatures: Vec<_> = self
+            .functions
+            .iter()
+            .map(|(name, function)| (name.clone(), function.signature.clone()))
+            .collect();
+        let interpreter_only = self.interpreter_only.len();
+        for (name, _, params) in annotated {
+            // It may have been checked by a call from one checked before it
+            if self.functions[&name].signature.is_some() {
+                continue;
+            }
+            self.used_unknown = false;
+            if let Err(err) = self.check_body(ast, &name, params) {
+                if !self.accumulate {
+                    return Err(err);
+                }
+                self.record(err);
+            }
+        }
+        for (name, signature) in signatures {
+            self.functions
+                .get_mut(&name)
+                .expect("function is declared")
+                .signature = signature;
+        }
+        self.interpreter_only.truncate(interpreter_only);
+        Ok(())
+    }
+
+    /// Warn about the functions that were never called. A module's `pub`
+    /// functions, like those of the standard library, are there for
+    /// importers that may not need them all.
+    fn warn_uncalled(&mut self, ast: &Ast) {
+        let mut uncalled: Vec<_> = self
+            .functions
+            .iter()
+            .filter(|(name, function)| function.signature.is_none() && !ast.is_public(name))
+            .collect();
+        uncalled.sort_by_key(|(_, function)| function.span.offset);
+        for (name, function) in uncalled {
+            let warning = Diagnostic::warning(
+                Lint::UnusedFunctions,
+                format!("Function '{}' is never called", name),
+                Some(function.span),
+            );
+            // Without annotations on every parameter the body isn't checked
+            let warning = if function.params.iter().all(|param| param.ty.is_some()) {
+                warning
+            } else {
+                warning.with_note("its body is only checked once it is called")
+            };
+            self.warnings.push(warning);
+        }
+    }
+
+    /// Work out the value of a constant's initializer
+    fn evaluate_constant(
+        &self,
+        ast: &Ast,
+        name: &str,
+        value: NodeId,
+        span: Span,
+    ) -> Result<Value, CompileError> {
+        let constant = |name: &str| {
+            let id = self.symbols.resolve(name)?;
+            self.symbols.get(id).constant.clone()
+        };
+        match optimizer::evaluate_constant(ast, value, &constant) {
+            // Generated code has no literal for infinity or NaN
+            Ok(Value::Float(num)) if !num.is_finite() => Err(Self::error(
+                format!("Constant '{}' is not a finite number", name),
+                span,
+            )),
+            Ok(constant) => Ok(constant),
+            Err(err) => Err(Self::error(
+                format!(
+                    "Constant '{}' must be computable at compile time: {}",
+                    name, err
+                ),
+                span,
+            )),
+        }
+    }
+
+    /// Warn when the divisor of the operation at `span` is a literal zero
+    fn warn_zero_divisor(&mut self, divisor: &ASTNode, span: Span) {
+        if matches!(divisor, ASTNode::Int(0)) || matches!(divisor, ASTNode::Number(n) if *n == 0.0)
+        {
+            self.warnings.push(Diagnostic::warning(
+                Lint::DivisionByZero,
+                "Division by zero",
+                Some(span),
+            ));
+        }
+    }
+
+    /// Declare a variable in the innermost scope
+    fn declare(
+        &mut self,
+        name: &str,
+        ty: Type,
+        mutable: bool,
+        span: Span,
+    ) -> Result<SymbolId, CompileError> {
+        if self.symbols.resolve_local(name).is_some() {
+            return Err(Self::error(
+                format!("Variable '{}' is already declared in this scope", name),
+                span,
+            ));
+        }
+        if let Some(shadowed) = self.symbols.resolve(name) {
+            let shadowed = self.symbols.get(shadowed);
+            self.warnings.push(
+                Diagnostic::warning(
+                    Lint::Shadowing,
+                    format!("Variable '{}' shadows an outer variable", name),
+                    Some(span),
+                )
+                .with_related(Diagnostic::note(
+                    format!("'{}' was declared here", name),
+                    Some(shadowed.span),
+                )),
+            );
+        }
+        Ok(self.symbols.declare(Symbol {
+            mutable,
+            ..Symbol::new(name, ty, span)
+        }))
+    }
+
+    /// Find a variable's type to read it, searching from the innermost scope
+    /// outwards
+    fn lookup(&mut self, name: &str, span: Span) -> Result<Type, CompileError> {
+        let symbol = self.find(name, span)?;
+        symbol.reads += 1;
+        Ok(symbol.ty.clone())
+    }
+
+    /// Find a variable's type to assign to it, which needs it to be mutable
+    fn lookup_for_write(&mut self, name: &str, span: Span) -> Result<Type, CompileError> {
+        let symbol = self.find(name, span)?;
+        if symbol.constant.is_some() {
+            return Err(Self::error(
+                format!("Cannot assign to constant '{}'", name),
+                span,
+            ));
+        }
+        if !symbol.mutable {
+            return Err(Self::error(
+                format!(
+                    "Cannot assign to immutable variable '{}', declare it with `let mut {}`",
+                    name, name
+                ),
+                span,
+            ));
+        }
+        symbol.writes += 1;
+        Ok(symbol.ty.clone())
+    }
+
+    /// The variable an element or field assignment like `a[i].x = v` stores
+    /// into
+    fn assigned_variable(ast: &Ast, target: NodeId) -> Option<&str> {
+        match &ast[target] {
+            ASTNode::Identifier { name, .. } => Some(name),
+            ASTNode::Index { array, .. } => Self::assigned_variable(ast, *array),
+            ASTNode::FieldAccess { object, .. } => Self::assigned_variable(ast, *object),
+            _ => None,
+        }
+    }
+
+    fn find(&mut self, name: &str, span: Span) -> Result<&mut Symbol, CompileError> {
+        let id = self
+            .symbols
+            .resolve(name)
+            .ok_or_else(|| Self::error(format!("Variable '{}' is not declared", name), span))?;
+        self.symbols.add_reference(id, span);
+        if self.unknown.contains(&id) {
+            self.used_unknown = true;
+            return Err(Self::error(
+                format!("The type of '{}' is unknown", name),
+                span,
+            ));
+        }
+        Ok(self.symbols.get_mut(id))
+    }
+
+    /// Type of an arithmetic operation: integers stay integral, except in a power
+    pub(crate) fn arithmetic_type(
+        op: &BinaryOperator,
+        left: &Type,
+        right: &Type,
+        span: Span,
+    ) -> Result<Type, CompileError> {
+        match (left, right) {
+            (Type::Int, Type::Int) if *op != BinaryOperator::Power => Ok(Type::Int),
+            _ if left.is_numeric() && right.is_numeric() => Ok(Type::Float),
+            _ => {
+                let message = match op {
+                    BinaryOperator::Plus => format!("Cannot add {} and {}", left, right),
+                    BinaryOperator::Minus => format!("Cannot subtract {} from {}", right, left),
+                    BinaryOperator::Multiply => format!("Cannot multiply {} and {}", left, right),
+                    BinaryOperator::Divide => format!("Cannot divide {} by {}", left, right),
+                    BinaryOperator::Modulo => {
+                        format!("Cannot take the remainder of {} by {}", left, right)
+                    }
+                    BinaryOperator::Power => {
+                        format!("Cannot raise {} to the power of {}", left, right)
+                    }
+                    _ => format!("Cannot use {} on {} and {}", op.symbol(), left, right),
+                };
+                Err(Self::error(message, span))
+            }
+        }
+    }
+
+    fn error(message: String, span: Span) -> CompileError {
+        CompileError::Semantic { message, span }
+    }
+
+    /// Note a construct compiled targets can't handle yet. `what` names it
+    /// along with its verb, like "Maps are".
+    fn note_interpreter_only(&mut self, what: &str, span: Span) {
+        let err = Self::error(
+            format!(
+                "{} only supported by `osho run` for now, not by compiled targets",
+                what
+            ),
+            span,
+        );
+        // Function bodies may be checked more than once
+        if !self.interpreter_only.contains(&err) {
+            self.interpreter_only.push(err);
+        }
+    }
+
+    /// Whether a value of this type is or contains a function
+    fn holds_function(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Function { .. } => true,
+            Type::Array(element) => self.holds_function(element),
+            Type::Struct(name) => self.structs.get(name).is_some_and(|fields| {
+                fields
+                    .iter()
+                    .any(|(_, ty)| ty.as_ref().is_some_and(|ty| self.holds_function(ty)))
+            }),
+            _ => false,
+        }
+    }
+
+    /// Check a value print or error() shows. Compiled targets print an
+    /// interpolated string there piece by piece, but can't print functions,
+    /// nor arrays and structs in an error() message.
+    fn check_shown(&mut self, ast: &Ast, arg: NodeId, error: bool) -> Result<Type, CompileError> {
+        let ASTNode::Interpolation { values, span, .. } = &ast[arg] else {
+            let ty = self.check_expression(ast, arg)?;
+            self.note_unshown(&ty, ast[arg].span(), error);
+            return Ok(ty);
+        };
+        for value in values {
+            let ty = self.check_expression(ast, *value)?;
+            self.note_unshown(&ty, ast[*value].span(), error);
+        }
+        self.types.insert(*span, Type::Str);
+        Ok(Type::Str)
+    }
+
+    /// Note a shown value of a type compiled targets can't show
+    fn note_unshown(&mut self, ty: &Type, span: Span, error: bool) {
+        if self.holds_function(ty) {
+            self.note_interpreter_only("Printing functions is", span);
+        } else if error && matches!(ty, Type::Array(_) | Type::Struct(_)) {
+            self.note_interpreter_only(&format!("Showing {} in an error() message is", ty), span);
+        }
+    }
+
+    /// The type of the value stored in a variable. In a chain like
+    /// `a = b = 0` that's another assignment, and what it stored in its
+    /// variable is read back.
+    fn check_assigned(&mut self, ast: &Ast, value: NodeId) -> Result<Type, CompileError> {
+        match &ast[value] {
+            ASTNode::Assignment { name, span, .. } => {
+                self.analyze(ast, value)?;
+                let ty = self.lookup(name, *span)?;
+                self.types.insert(*span, ty.clone());
+                Ok(ty)
+            }
+            ASTNode::IndexAssignment { span, .. } | ASTNode::FieldAssignment { span, .. } => {
+                Err(Self::chained_element(*span))
+            }
+            _ => self.check_expression(ast, value),
+        }
+    }
+
+    /// The error for an element or field in a chain of assignments. The
+    /// compiled targets run a chain from its end, which would evaluate an
+    /// element's array and index, or a field's object, out of order.
+    fn chained_element(span: Span) -> CompileError {
+        Self::error(
+            "Only variables can be assigned in a chain of assignments".to_string(),
+            span,
+        )
+    }
+
+    /// Check an expression used as a condition. Only bools and numbers have
+    /// a truth value.
+    fn check_condition(&mut self, ast: &Ast, expr: NodeId) -> Result<(), CompileError> {
+        let ty = self.check_expression(ast, expr)?;
+        if !matches!(ty, Type::Int | Type::Float | Type::Bool) {
+            return Err(Self::error(
+                format!("Cannot use {} as a condition", ty),
+                ast[expr].span(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check `array[index]`, `text[index]` or `map[key]`, returning the type
+    /// of the array, string or map and the type of its element
+    fn check_index(
+        &mut self,
+        ast: &Ast,
+        array: NodeId,
+        index: NodeId,
+        span: Span,
+    ) -> Result<(Type, Type), CompileError> {
+        let container = self.check_expression(ast, array)?;
+        let index = self.check_expression(ast, index)?;
+        let (element, expected, what) = match &container {
+            Type::Array(element) => (&**element, Type::Int, "Array index must be an int"),
+            Type::Str => (&Type::Char, Type::Int, "String index must be an int"),
+            Type::Map(value) => (&**value, Type::Str, "Map key must be a string"),
+            _ => {
+                return Err(Self::error(
+                    format!("Cannot index into {}", container),
+                    span,
+                ))
+            }
+        };
+        if index != expected {
+            return Err(Self::error(format!("{}, found {}", what, index), span));
+        }
+        let element = element.clone();
+        Ok((container, element))
+    }
+
+    /// Check `Name { a: 1, b: 2 }`. Every field must be given once, and the
+    /// first value of a struct decides the types of its fields.
+    fn check_struct_literal(
+        &mut self,
+        ast: &Ast,
+        name: &str,
+        fields: &[(String, NodeId)],
+        span: Span,
+    ) -> Result<Type, CompileError> {
+        let mut types = Vec::new();
+        for (_, value) in fields {
+            types.push(self.check_expression(ast, *value)?);
+        }
+        let declared = self
+            .structs
+            .get_mut(name)
+            .ok_or_else(|| Self::error(format!("Unknown struct '{}'", name), span))?;
+        for (i, (field, _)) in fields.iter().enumerate() {
+            if fields[..i].iter().any(|(given, _)| given == field) {
+                return Err(Self::error(
+                    format!("Field '{}' is given twice", field),
+                    span,
+                ));
+            }
+            if !declared.iter().any(|(declared, _)| declared == field) {
+                return Err(Self::error(
+                    format!("Struct '{}' has no field '{}'", name, field),
+                    span,
+                ));
+            }
+        }
+        for (field, declared_ty) in declared.iter_mut() {
+            let Some(i) = fields.iter().position(|(given, _)| given == field) else {
+                return Err(Self::error(
+                    format!("Missing field '{}' in {}", field, name),
+                    span,
+                ));
+            };
+            match declared_ty {
+                Some(declared_ty) if *declared_ty != types[i] => {
+                    return Err(Self::error(
+                        format!(
+                            "Field '{}' of {} is {}, found {}",
+                            field, name, declared_ty, types[i]
+                        ),
+                        span,
+                    ));
+                }
+                Some(_) => {}
+                None => *declared_ty = Some(types[i].clone()),
+            }
+        }
+        Ok(Type::Struct(name.to_string()))
+    }
+
+    /// Check `object.field`, returning the name of the struct and the type
+    /// of the field
+    fn check_field(
+        &mut self,
+        ast: &Ast,
+        object: NodeId,
+        field: &str,
+        span: Span,
+    ) -> Result<(String, Type), CompileError> {
+        let ty = self.check_expression(ast, object)?;
+        let Type::Struct(name) = ty else {
+            return Err(Self::error(
+                format!("Cannot access field '{}' of {}", field, ty),
+                span,
+            ));
+        };
+        let declared = self.structs[&name]
+            .iter()
+            .find(|(declared, _)| declared == field)
+            .ok_or_else(|| {
+                Self::error(format!("Struct '{}' has no field '{}'", name, field), span)
+            })?;
+        let ty = declared
+            .1
+            .clone()
+            .expect("a struct value exists, so its field types are known");
+        Ok((name, ty))
+    }
+
+    /// Check a call, returning the type of its result if it has one. A
+    /// variable holding a lambda hides a function of the same name.
+    pub(crate) fn check_call(
+        &mut self,
+        ast: &Ast,
+        name: &str,
+        args: &[NodeId],
+        span: Span,
+    ) -> Result<Option<Type>, CompileError> {
+        if let Some(id) = self.symbols.resolve(name) {
+            return self.check_value_call(ast, id, name, args, span);
+        }
+        if self.functions.contains_key(name) {
+            return self.check_function_call(ast, name, args, span);
+        }
+        let builtin = Builtin::from_name(name)
+            .ok_or_else(|| Self::error(format!("Unknown function '{}'", name), span))?;
+        let types = if builtin.calls_function() {
+            self.check_function_args(ast, builtin, args)?
+        } else if builtin == Builtin::Error {
+            args.iter()
+                .map(|arg| self.check_shown(ast, *arg, true))
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            args.iter()
+                .map(|arg| self.check_expression(ast, *arg))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let result = builtin
+            .check(&types)
+            .map_err(|err| Self::error(err, span))?;
+        if let (Builtin::Str, [ty]) = (builtin, &types[..]) {
+            if !matches!(ty, Type::Int | Type::Float | Type::Bool | Type::Str) {
+                self.note_interpreter_only(&format!("Converting {} with str() is", ty), span);
+            }
+        }
+        if builtin.mutates() {
+            let variable = Self::assigned_variable(ast, args[0]).ok_or_else(|| {
+                Self::error(format!("{}() needs a variable to change", name), span)
+            })?;
+            self.lookup_for_write(variable, span)?;
+        }
+        Ok(result)
+    }
+
+    /// Check the arguments of `apply` or `map`. A lambda passed to them
+    /// takes parameters of the types it will be called with, so they
+    /// needn't be annotated.
+    fn check_function_args(
+        &mut self,
+        ast: &Ast,
+        builtin: Builtin,
+        args: &[NodeId],
+    ) -> Result<Vec<Type>, CompileError> {
+        let Some((&function, rest)) = args.split_first() else {
+            return Ok(Vec::new());
+        };
+        let rest = rest
+            .iter()
+            .map(|arg| self.check_expression(ast, *arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        let called_with = match (builtin, &rest[..]) {
+            (Builtin::Map, [Type::Array(element)]) => vec![(**element).clone()],
+            (Builtin::Map, _) => Vec::new(),
+            _ => rest.clone(),
+        };
+        let ty = match &ast[function] {
+            ASTNode::Lambda { params, span, .. }
+                if params.iter().any(|param| param.ty.is_none())
+                    && params.len() == called_with.len() =>
+            {
+                let params: Vec<_> = params
+                    .iter()
+                    .zip(called_with)
+                    .map(|(param, ty)| param.ty.clone().unwrap_or(ty))
+                    .collect();
+                self.make_closure(ast, function)?;
+                let result = self.check_lambda_body(ast, function, params.clone())?;
+                let ty = Signature { params, result }.function_type();
+                self.types.insert(*span, ty.clone());
+                ty
+            }
+            _ => self.check_expression(ast, function)?,
+        };
+        Ok(std::iter::once(ty).chain(rest).collect())
+    }
+
+    /// The type of a declared function used as a value. Its body must be
+    /// checked by then, so the types of its parameters must be known from
+    /// their annotations or an earlier call.
+    fn function_value(&mut self, ast: &Ast, name: &str, span: Span) -> Result<Type, CompileError> {
+        let function = &self.functions[name];
+        // Inside its own body, it gives what the returns seen so far do
+        if let Some(checking) = self.checking.iter().find(|function| function.name == name) {
+            let result = checking.returns.clone().ok_or_else(|| {
+                Self::error(
+                    format!(
+                        "Cannot infer what {}() returns before its first 'return'",
+                        name
+                    ),
+                    span,
+                )
+            })?;
+            let params = function
+                .signature
+                .as_ref()
+                .expect("a function being checked has a signature")
+                .params
+                .clone();
+            return Ok(Signature { params, result }.function_type());
+        }
+        if let Some(signature) = &function.signature {
+            return Ok(signature.function_type());
+        }
+        let params = function
+            .params
+            .iter()
+            .map(|param| param.ty.clone())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| {
+                Self::error(
+                    format!(
+                        "Cannot use {}() as a value before a call decides the types of its parameters, annotate them like `fn {}(x: int)`",
+                        name, name
+                    ),
+                    span,
+                )
+            })?;
+        let result = self.check_body(ast, name, params.clone())?;
+        Ok(Signature { params, result }.function_type())
+    }
+
+    /// Check a call to a declared function. The first call decides the
+    /// types of the parameters and checks the body; later calls must pass
+    /// arguments of exactly those types.
+    fn check_function_call(
+        &mut self,
+        ast: &Ast,
+        name: &str,
+        args: &[NodeId],
+        span: Span,
+    ) -> Result<Option<Type>, CompileError> {
+        let types = args
+            .iter()
+            .map(|arg| self.check_expression(ast, *arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        let function = &self.functions[name];
+        if types.len() != function.params.len() {
+            return Err(Self::error(
+                builtins::wrong_arity(name, function.params.len(), types.len()),
+                span,
+            ));
+        }
+        let wrong_type = |param: &Param, expected: &Type, ty: &Type| {
+            Self::error(
+                format!(
+                    "Parameter '{}' of {}() is {}, found {}",
+                    param.name, name, expected, ty
+                ),
+                span,
+            )
+        };
+        let Some(signature) = &function.signature else {
+            let mut params = Vec::new();
+            for (param, ty) in function.params.iter().zip(types) {
+                match &param.ty {
+                    Some(expected) if !expected.accepts(&ty) => {
+                        return Err(wrong_type(param, expected, &ty))
+                    }
+                    Some(expected) => params.push(expected.clone()),
+                    None => params.push(ty),
+                }
+            }
+            return self.check_body(ast, name, params);
+        };
+        for ((param, expected), ty) in function.params.iter().zip(&signature.params).zip(&types) {
+            // Parameters without an annotation take exactly the types of the
+            // first call
+            let fits = match param.ty {
+                Some(_) => expected.accepts(ty),
+                None => expected == ty,
+            };
+            if !fits {
+                return Err(wrong_type(param, expected, ty));
+            }
+        }
+        // A recursive call gives what the returns seen so far do
+        match self.checking.iter().find(|function| function.name == name) {
+            Some(function) => function.returns.clone().ok_or_else(|| {
+                Self::error(
+                    format!(
+                        "Cannot infer what {}() returns before its first 'return'",
+                        name
+                    ),
+                    span,
+                )
+            }),
+            None => Ok(signature.result.clone()),
+        }
+    }
+
+    /// Check the body of a function with its parameters of the given types,
+    /// returning the type of its result
+    fn check_body(
+        &mut self,
+        ast: &Ast,
+        name: &str,
+        params: Vec<Type>,
+    ) -> Result<Option<Type>, CompileError> {
+        let function = self.functions.get_mut(name).expect("function is declared");
+        function.signature = Some(Signature {
+            params: params.clone(),
+            result: None,
+        });
+        let names = function.params.clone();
+        let annotated = function.result.clone();
+        let body = function.body;
+        let span = function.span;
+
+        let hidden = self.symbols.enter_function();
+        for (param, ty) in names.iter().zip(params) {
+            self.symbols.declare(Symbol::new(&param.name, ty, span));
+        }
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        self.checking.push(FunctionScope {
+            name: name.to_string(),
+            annotated: annotated.is_some(),
+            returns: annotated.map(Some),
+        });
+        let result = self.analyze(ast, body);
+        let returns = self.checking.pop().and_then(|function| function.returns);
+        self.loop_depth = loop_depth;
+        let ids = self.symbols.exit_function(hidden);
+        self.warn_unused(ids);
+        result?;
+
+        let result = returns.flatten();
+        if result.is_some() && !Self::always_returns(ast, body) {
+            return Err(Self::error(
+                format!("Function '{}' doesn't return a value on every path", name),
+                span,
+            ));
+        }
+        if let Some(signature) = &mut self
+            .functions
+            .get_mut(name)
+            .expect("function is declared")
+            .signature
+        {
+            signature.result = result.clone();
+        }
+        Ok(result)
+    }
+
+    /// Check a call to the lambda held by a variable. The arguments must
+    /// fit its parameters, whose types the first call decides when they
+    /// aren't annotated.
+    fn check_value_call(
+        &mut self,
+        ast: &Ast,
+        id: SymbolId,
+        name: &str,
+        args: &[NodeId],
+        span: Span,
+    ) -> Result<Option<Type>, CompileError> {
+        let types = args
+            .iter()
+            .map(|arg| self.check_expression(ast, *arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ty = self.lookup(name, span)?;
+        let signature = match self.pending.get(&id) {
+            Some(lambda) => match &self.closures[lambda].signature {
+                Some(signature) => signature.clone(),
+                None => {
+                    let ASTNode::Lambda { params, .. } = &ast[*lambda] else {
+                        unreachable!("only lambdas are pending");
+                    };
+                    if types.len() != params.len() {
+                        return Err(Self::error(
+                            builtins::wrong_arity(name, params.len(), types.len()),
+                            span,
+                        ));
+                    }
+                    return self.check_lambda_body(ast, *lambda, types);
+                }
+            },
+            None => match ty {
+                Type::Function { params, result } => Signature {
+                    params,
+                    result: result.map(|result| *result),
+                },
+                ty => {
+                    return Err(Self::error(
+                        format!("'{}' is {}, not a function", name, ty),
+                        span,
+                    ))
+                }
+            },
+        };
+        if types.len() != signature.params.len() {
+            return Err(Self::error(
+                builtins::wrong_arity(name, signature.params.len(), types.len()),
+                span,
+            ));
+        }
+        for (i, (expected, ty)) in signature.params.iter().zip(&types).enumerate() {
+            if !expected.accepts(ty) {
+                return Err(Self::error(
+                    format!(
+                        "Argument {} of {}() must be {}, found {}",
+                        i + 1,
+                        name,
+                        expected,
+                        ty
+                    ),
+                    span,
+                ));
+            }
+        }
+        Ok(signature.result)
+    }
+
+    /// Record a lambda along with the variables it captures. Closures copy
+    /// what they capture when they are made, so a variable that could change
+    /// afterwards can't be captured.
+    fn make_closure(&mut self, ast: &Ast, lambda: NodeId) -> Result<(), CompileError> {
+        let ASTNode::Lambda { params, body, span } = &ast[lambda] else {
+            unreachable!("closures are made from lambdas");
+        };
+        for (i, param) in params.iter().enumerate() {
+            if params[..i].iter().any(|other| other.name == param.name) {
+                return Err(Self::error(
+                    format!("Parameter '{}' is declared twice in a lambda", param.name),
+                    *span,
+                ));
+            }
+            if let Some(ty) = &param.ty {
+                self.check_annotation(ty, *span)?;
+            }
+        }
+        let mut captures = Vec::new();
+        for name in parser::free_names(ast, params, *body) {
+            // Other names are functions or builtins
+            let Some(id) = self.symbols.resolve(&name) else {
+                continue;
+            };
+            let symbol = self.find(&name, *span)?;
+            if symbol.mutable {
+                return Err(Self::error(
+                    format!(
+                        "Closures can only capture immutable variables, but '{}' is declared with `let mut`",
+                        name
+                    ),
+                    *span,
+                ));
+            }
+            symbol.reads += 1;
+            captures.push((name, id));
+        }
+        if !captures.is_empty() {
+            let names: Vec<_> = captures
+                .iter()
+                .map(|(name, _)| format!("'{}'", name))
+                .collect();
+            self.note_interpreter_only(
+                &format!("Closures capturing {} are", names.join(", ")),
+                *span,
+            );
+        }
+        let closure = Closure {
+            captures,
+            signature: None,
+        };
+        self.closures.insert(lambda, closure);
+        Ok(())
+    }
+
+    /// Check the body of a lambda with its parameters of the given types,
+    /// returning the type of its result. The body sees only the captured
+    /// variables and the parameters.
+    fn check_lambda_body(
+        &mut self,
+        ast: &Ast,
+        lambda: NodeId,
+        params: Vec<Type>,
+    ) -> Result<Option<Type>, CompileError> {
+        let ASTNode::Lambda {
+            params: names,
+            body,
+            span,
+        } = &ast[lambda]
+        else {
+            unreachable!("closures are made from lambdas");
+        };
+        let captures = self.closures[&lambda].captures.clone();
+
+        let hidden = self.symbols.enter_function();
+        let mut copies = Vec::new();
+        for (name, id) in captures {
+            let ty = self.symbols.get(id).ty.clone();
+            let copy = self.symbols.declare(Symbol::new(&name, ty, *span));
+            if let Some(lambda) = self.pending.get(&id).copied() {
+                self.pending.insert(copy, lambda);
+            }
+            copies.push(copy);
+        }
+        for (param, ty) in names.iter().zip(&params) {
+            self.symbols
+                .declare(Symbol::new(&param.name, ty.clone(), *span));
+        }
+        // A lambda whose body is a call gives back whatever the call does
+        let result = match &ast[*body] {
+            ASTNode::Call { name, args, span } => self.check_call(ast, name, args, *span),
+            _ => self.check_expression(ast, *body).map(Some),
+        };
+        let mut ids = self.symbols.exit_function(hidden);
+        ids.retain(|id| !copies.contains(id));
+        self.warn_unused(ids);
+        let result = result?;
+
+        let signature = Signature { params, result };
+        // Variables holding the lambda now have its type
+        for (id, _) in self.pending.iter().filter(|(_, held)| **held == lambda) {
+            self.symbols.get_mut(*id).ty = signature.function_type();
+        }
+        let result = signature.result.clone();
+        self.closures
+            .get_mut(&lambda)
+            .expect("closure is made")
+            .signature = Some(signature);
+        Ok(result)
+    }
+
+    /// Check the value of a declaration annotated with a type, which it must
+    /// fit. An empty array or map gets its type from the annotation.
+    fn check_annotated(
+        &mut self,
+        ast: &Ast,
+        name: &str,
+        annotation: &Type,
+        value: NodeId,
+        span: Span,
+    ) -> Result<Type, CompileError> {
+        self.check_annotation(annotation, span)?;
+        let empty = match (annotation, &ast[value]) {
+            (Type::Array(_), ASTNode::ArrayLiteral { elements, .. }) => elements.is_empty(),
+            (Type::Map(_), ASTNode::MapLiteral { entries, .. }) => entries.is_empty(),
+            _ => false,
+        };
+        if empty {
+            self.types.insert(ast[value].span(), annotation.clone());
+            return Ok(annotation.clone());
+        }
+        let ty = self.check_expression(ast, value)?;
+        if !annotation.accepts(&ty) {
+            return Err(Self::error(
+                format!("Variable '{}' is {}, found {}", name, annotation, ty),
+                span,
+            ));
+        }
+        Ok(annotation.clone())
+    }
+
+    /// Check that the structs a type annotation names are declared
+    fn check_annotation(&self, ty: &Type, span: Span) -> Result<(), CompileError> {
+        match ty {
+            Type::Array(element) | Type::Map(element) => self.check_annotation(element, span),
+            Type::Struct(name) if !self.structs.contains_key(name) => {
+                Err(Self::error(format!("Unknown type '{}'", name), span))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether running a statement always ends in a `return`
+    fn always_returns(ast: &Ast, stmt: NodeId) -> bool {
+        match &ast[stmt] {
+            ASTNode::Return { .. } => true,
+            ASTNode::Block(statements) => statements
+                .iter()
+                .any(|stmt| Self::always_returns(ast, *stmt)),
+            ASTNode::If {
+                then_branch,
+                else_branch: Some(else_branch),
+                ..
+            } => Self::always_returns(ast, *then_branch) && Self::always_returns(ast, *else_branch),
+            _ => false,
+        }
+    }
+
+    /// The type an expression was found to have, if it has been checked
+    pub fn type_of(&self, expr: &ASTNode) -> Option<&Type> {
+        self.types.get(&expr.span())
+    }
+
+    /// An analyzer that has checked the whole program `ast` holds, for
+    /// [`SemanticAnalyzer::type_of`]. None if it isn't a whole program, like
+    /// the REPL's statements, or isn't accepted.
+    pub(crate) fn checked(ast: &Ast) -> Option<Self> {
+        if !matches!(ast[ast.root()], ASTNode::Program(_)) {
+            return None;
+        }
+        let mut analyzer = Self::new();
+        analyzer.analyze_all(ast).ok()?;
+        Some(analyzer)
+    }
+
+    /// Check an expression and work out its type, recording it for
+    /// [`SemanticAnalyzer::type_of`]
+    pub fn check_expression(&mut self, ast: &Ast, expr: NodeId) -> Result<Type, CompileError> {
+        let ty = self.infer_expression(ast, expr)?;
+        let span = ast[expr].span();
+        if span != Span::default() {
+            self.types.insert(span, ty.clone());
+        }
+        Ok(ty)
+    }
+
+    fn infer_expression(&mut self, ast: &Ast, expr: NodeId) -> Result<Type, CompileError> {
+        match &ast[expr] {
+            ASTNode::Int(_) => Ok(Type::Int),
+            ASTNode::Number(_) => Ok(Type::Float),
+            ASTNode::Bool(_) => Ok(Type::Bool),
+            ASTNode::Str(_) => Ok(Type::Str),
+            ASTNode::Char(_) => Ok(Type::Char),
+            ASTNode::Interpolation { values, span, .. } => {
+                for value in values {
+                    self.check_expression(ast, *value)?;
+                }
+                self.note_interpreter_only("Interpolated strings outside print are", *span);
+                Ok(Type::Str)
+            }
+            // A declared function's name is a value when no variable hides it
+            ASTNode::Identifier { name, span }
+                if self.symbols.resolve(name).is_none() && self.functions.contains_key(name) =>
+            {
+                self.function_value(ast, name, *span)
+            }
+            ASTNode::Identifier { name, span } => {
+                let ty = self.lookup(name, *span)?;
+                let pending = self
+                    .symbols
+                    .resolve(name)
+                    .and_then(|id| self.pending.get(&id));
+                match pending {
+                    Some(lambda) if self.closures[lambda].signature.is_none() => {
+                        Err(Self::error(
+                            format!(
+                                "Cannot use '{}' before a call decides the types of its parameters, annotate them like `|x: int|`",
+                                name
+                            ),
+                            *span,
+                        ))
+                    }
+                    _ => Ok(ty),
+                }
+            }
+            ASTNode::Lambda { params, span, .. } => {
+                if params.iter().any(|param| param.ty.is_none()) {
+                    return Err(Self::error(
+                        "Cannot infer the types of this lambda's parameters, annotate them like `|x: int|`".to_string(),
+                        *span,
+                    ));
+                }
+                let params: Vec<_> = params.iter().filter_map(|param| param.ty.clone()).collect();
+                self.make_closure(ast, expr)?;
+                let result = self.check_lambda_body(ast, expr, params.clone())?;
+                Ok(Signature { params, result }.function_type())
+            }
+            ASTNode::BinaryOp {
+                left,
+                op,
+                right,
+                span,
+            } => {
+                if matches!(op, BinaryOperator::Divide | BinaryOperator::Modulo) {
+                    self.warn_zero_divisor(&ast[*right], *span);
+                }
+                if op.is_logical() {
+                    self.check_condition(ast, *left)?;
+                    self.check_condition(ast, *right)?;
+                    return Ok(Type::Bool);
+                }
+                let left = self.check_expression(ast, *left)?;
+                let right = self.check_expression(ast, *right)?;
+                if left == Type::Str {
+                    self.note_interpreter_only("Operators on strings are", *span);
+                }
+                if op.is_comparison() {
+                    let equality = matches!(op, BinaryOperator::Equal | BinaryOperator::NotEqual);
+                    return match (&left, &right) {
+                        (Type::Bool, Type::Bool) | (Type::Str, Type::Str) if equality => {
+                            Ok(Type::Bool)
+                        }
+                        (Type::Bool, Type::Bool) | (Type::Str, Type::Str) => {
+                            Err(Self::error(format!("Cannot order {} values", left), *span))
+                        }
+                        (Type::Char, Type::Char) => Ok(Type::Bool),
+                        _ if left.is_numeric() && right.is_numeric() => Ok(Type::Bool),
+                        _ => Err(Self::error(
+                            format!("Cannot compare {} and {}", left, right),
+                            *span,
+                        )),
+                    };
+                }
+                Self::arithmetic_type(op, &left, &right, *span)
+            }
+            ASTNode::UnaryOp {
+                op: UnaryOperator::Not,
+                operand,
+                ..
+            } => {
+                self.check_condition(ast, *operand)?;
+                Ok(Type::Bool)
+            }
+            ASTNode::UnaryOp { op, operand, span } => {
+                let ty = self.check_expression(ast, *operand)?;
                 match op {
-                    BinaryOperator::Plus => Ok(left_val + right_val),
-                    BinaryOperator::Minus => Ok(left_val - right_val),
-                    BinaryOperator::Divide => Ok(left_val / right_val),
-                    BinaryOperator::Multiply => Ok(left_val * right_val),
+                    UnaryOperator::Not => Ok(Type::Bool),
+                    UnaryOperator::Negate if ty.is_numeric() => Ok(ty),
+                    UnaryOperator::Negate => {
+                        Err(Self::error(format!("Cannot negate {}", ty), *span))
+                    }
                 }
             }
-            _ => Err("Unexpected expression node".to_string()),
+            ASTNode::ArrayLiteral { elements, span } => {
+                let types = elements
+                    .iter()
+                    .map(|element| self.check_expression(ast, *element))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let element = Type::element_type(&types).map_err(|err| Self::error(err, *span))?;
+                Ok(Type::Array(Box::new(element)))
+            }
+            ASTNode::MapLiteral { entries, span } => {
+                let mut values = Vec::new();
+                for (key, value) in entries {
+                    let key = self.check_expression(ast, *key)?;
+                    if key != Type::Str {
+                        return Err(Self::error(
+                            format!("Map keys must be strings, found {}", key),
+                            *span,
+                        ));
+                    }
+                    values.push(self.check_expression(ast, *value)?);
+                }
+                let value = Type::value_type(&values).map_err(|err| Self::error(err, *span))?;
+                self.note_interpreter_only("Maps are", *span);
+                Ok(Type::Map(Box::new(value)))
+            }
+            ASTNode::Index { array, index, span } => {
+                Ok(self.check_index(ast, *array, *index, *span)?.1)
+            }
+            ASTNode::Slice {
+                array,
+                start,
+                end,
+                span,
+            } => {
+                let container = self.check_expression(ast, *array)?;
+                if !matches!(container, Type::Array(_) | Type::Str) {
+                    return Err(Self::error(format!("Cannot slice {}", container), *span));
+                }
+                for bound in [start, end].into_iter().flatten() {
+                    let ty = self.check_expression(ast, *bound)?;
+                    if ty != Type::Int {
+                        return Err(Self::error(
+                            format!("Slice bounds must be ints, found {}", ty),
+                            *span,
+                        ));
+                    }
+                }
+                Ok(container)
+            }
+            ASTNode::StructLiteral { name, fields, span } => {
+                self.check_struct_literal(ast, name, fields, *span)
+            }
+            ASTNode::FieldAccess {
+                object,
+                field,
+                span,
+            } => Ok(self.check_field(ast, *object, field, *span)?.1),
+            ASTNode::Conditional {
+                condition,
+                then_value,
+                else_value,
+                span,
+            } => {
+                self.check_condition(ast, *condition)?;
+                let then_type = self.check_expression(ast, *then_value)?;
+                let else_type = self.check_expression(ast, *else_value)?;
+                // Either branch may be the result, so they can't differ
+                if then_type != else_type {
+                    return Err(Self::error(
+                        format!(
+                            "Branches of '?:' must have the same type, found {} and {}",
+                            then_type, else_type
+                        ),
+                        *span,
+                    ));
+                }
+                Ok(then_type)
+            }
+            ASTNode::Call { name, args, span } => self
+                .check_call(ast, name, args, *span)?
+                .ok_or_else(|| Self::error(format!("{}() doesn't return a value", name), *span)),
+            ASTNode::Assignment { span, .. }
+            | ASTNode::CompoundAssignment { span, .. }
+            | ASTNode::IndexAssignment { span, .. }
+            | ASTNode::FieldAssignment { span, .. } => Err(Self::error(
+                "An assignment isn't a value, except in a chain like 'a = b = 0'".to_string(),
+                *span,
+            )),
+            _ => Err(Self::error(
+                "Unexpected expression node".to_string(),
+                ast[expr].span(),
+            )),
         }
     }
 }
-
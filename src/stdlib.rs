@@ -0,0 +1,25 @@
+//! The standard library, osho files built into the compiler.
+//!
+//! `import std.math` includes `std/math.osho`, which the [`Loader`] takes
+//! from here rather than from the filesystem.
+//!
+//! [`Loader`]: crate::modules::Loader
+
+/// The path each file is imported by, and its source
+const FILES: &[(&str, &str)] = &[
+    ("std/math.osho", include_str!("std/math.osho")),
+    ("std/list.osho", include_str!("std/list.osho")),
+];
+
+/// The source of a standard library file, if `path` is one
+pub fn source(path: &str) -> Option<&'static str> {
+    FILES
+        .iter()
+        .find(|(name, _)| *name == path)
+        .map(|(_, source)| *source)
+}
+
+/// The paths of all the standard library files
+pub fn files() -> impl Iterator<Item = &'static str> {
+    FILES.iter().map(|(name, _)| *name)
+}
@@ -0,0 +1,182 @@
+//! Declared variables and what is known about them, kept by the semantic
+//! analyzer and available to tools once a program has been checked.
+
+use std::collections::HashMap;
+
+use crate::interpreter::Value;
+use crate::lexer::Span;
+use crate::semantic_analyzer::Type;
+
+/// Index of a symbol in its [`SymbolTable`]
+pub type SymbolId = usize;
+
+/// A declared variable
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub ty: Type,
+
+    /// Where the variable was declared
+    pub span: Span,
+
+    /// Whether the variable may be assigned to after its declaration
+    pub mutable: bool,
+
+    /// Whether the variable has been given a value
+    pub initialized: bool,
+
+    /// The value of a `const`, worked out during analysis
+    pub constant: Option<Value>,
+
+    /// How many times the value is read
+    pub reads: usize,
+
+    /// How many times the variable is assigned to, not counting its declaration
+    pub writes: usize,
+}
+
+impl Symbol {
+    /// An initialized, immutable symbol
+    pub fn new(name: &str, ty: Type, span: Span) -> Self {
+        Self {
+            name: name.to_string(),
+            ty,
+            span,
+            mutable: false,
+            initialized: true,
+            constant: None,
+            reads: 0,
+            writes: 0,
+        }
+    }
+}
+
+/// Every symbol declared in a program, and the scopes that are currently open
+#[derive(Debug, Clone)]
+pub struct SymbolTable {
+    /// All symbols in declaration order, including those of closed scopes
+    symbols: Vec<Symbol>,
+
+    /// Symbols visible in each open scope by name, innermost last
+    scopes: Vec<HashMap<String, SymbolId>>,
+
+    /// Where symbols are used, for tools that go from a use to a declaration
+    references: Vec<(Span, SymbolId)>,
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SymbolTable {
+    /// A table with just the global scope open
+    pub fn new() -> Self {
+        Self {
+            symbols: Vec::new(),
+            scopes: vec![HashMap::new()],
+            references: Vec::new(),
+        }
+    }
+
+    pub fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    /// Close the innermost scope, returning the symbols declared in it
+    pub fn end_scope(&mut self) -> Vec<SymbolId> {
+        let scope = self.scopes.pop().expect("global scope is never popped");
+        let mut ids: Vec<_> = scope.into_values().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Hide every open scope behind a fresh one for the body of a function,
+    /// which can't see the variables around it. Returns the hidden scopes.
+    pub fn enter_function(&mut self) -> Vec<HashMap<String, SymbolId>> {
+        std::mem::replace(&mut self.scopes, vec![HashMap::new()])
+    }
+
+    /// Restore the scopes hidden by [`SymbolTable::enter_function`],
+    /// returning the symbols of the function's scope
+    pub fn exit_function(&mut self, hidden: Vec<HashMap<String, SymbolId>>) -> Vec<SymbolId> {
+        let ids = self.end_scope();
+        self.scopes = hidden;
+        ids
+    }
+
+    /// Whether only the outermost scope is open
+    pub fn is_global(&self) -> bool {
+        self.scopes.len() == 1
+    }
+
+    /// The symbols of the innermost scope, in declaration order
+    pub fn current_scope(&self) -> Vec<SymbolId> {
+        let mut ids: Vec<_> = self.innermost().values().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Add a symbol to the innermost scope, hiding any other of the same name
+    pub fn declare(&mut self, symbol: Symbol) -> SymbolId {
+        let id = self.symbols.len();
+        let name = symbol.name.clone();
+        self.symbols.push(symbol);
+        self.scopes
+            .last_mut()
+            .expect("global scope is never popped")
+            .insert(name, id);
+        id
+    }
+
+    /// Find a visible symbol, searching from the innermost scope outwards
+    pub fn resolve(&self, name: &str) -> Option<SymbolId> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .copied()
+    }
+
+    /// Find a symbol declared in the innermost scope
+    pub fn resolve_local(&self, name: &str) -> Option<SymbolId> {
+        self.innermost().get(name).copied()
+    }
+
+    pub fn get(&self, id: SymbolId) -> &Symbol {
+        &self.symbols[id]
+    }
+
+    pub fn get_mut(&mut self, id: SymbolId) -> &mut Symbol {
+        &mut self.symbols[id]
+    }
+
+    /// Every symbol declared so far, in declaration order
+    pub fn iter(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.iter()
+    }
+
+    /// Record that the symbol is used at `span`
+    pub fn add_reference(&mut self, id: SymbolId, span: Span) {
+        self.references.push((span, id));
+    }
+
+    /// The symbol declared or used at a byte offset of the source
+    pub fn at(&self, offset: usize) -> Option<SymbolId> {
+        let covers = |span: &Span| span.offset <= offset && offset <= span.offset + span.length;
+        self.symbols
+            .iter()
+            .position(|symbol| covers(&symbol.span))
+            .or_else(|| {
+                self.references
+                    .iter()
+                    .find(|(span, _)| covers(span))
+                    .map(|(_, id)| *id)
+            })
+    }
+
+    fn innermost(&self) -> &HashMap<String, SymbolId> {
+        self.scopes.last().expect("global scope is never popped")
+    }
+}
@@ -0,0 +1,64 @@
+//! Wall-clock times of the phases of a compilation, shown by `--timings`.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// How long each phase took, in the order they first ran. A phase that runs
+/// more than once, like lexing each imported file, adds up.
+#[derive(Debug, Clone, Default)]
+pub struct Timings {
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `f` and add the time it takes to `phase`
+    pub fn time<T>(&mut self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.add(phase, start.elapsed());
+        result
+    }
+
+    /// Add `elapsed` to the time of `phase`
+    pub fn add(&mut self, phase: &'static str, elapsed: Duration) {
+        match self.phases.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += elapsed,
+            None => self.phases.push((phase, elapsed)),
+        }
+    }
+
+    /// Add every phase of `other`
+    pub fn extend(&mut self, other: &Timings) {
+        for (phase, elapsed) in &other.phases {
+            self.add(phase, *elapsed);
+        }
+    }
+
+    pub fn phases(&self) -> &[(&'static str, Duration)] {
+        &self.phases
+    }
+
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|(_, elapsed)| *elapsed).sum()
+    }
+}
+
+impl fmt::Display for Timings {
+    /// A table of the phases in milliseconds, ending with the total
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = ("total", self.total());
+        for (phase, elapsed) in self.phases.iter().chain([&total]) {
+            writeln!(
+                f,
+                "{:<12}{:>10.3} ms",
+                phase,
+                elapsed.as_secs_f64() * 1000.0
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+//! `--trace`, a log of every statement the interpreter runs.
+//!
+//! Each statement is logged with its file and line before it runs, along
+//! with the variables it reads, and the variables it writes are logged once
+//! it has run. Statements in calls are indented by the depth of the call.
+
+use std::io::Write;
+
+use crate::interpreter::{Hook, Pause};
+use crate::modules::SourceMap;
+use crate::parser::{ASTNode, Ast, NodeId};
+
+/// A [`Hook`] that writes a line to `out` around each statement
+pub struct Tracer<W: Write> {
+    sources: SourceMap,
+    out: W,
+}
+
+impl<W: Write> Tracer<W> {
+    /// A tracer showing statements from the files in `sources`
+    pub fn new(sources: SourceMap, out: W) -> Self {
+        Self { sources, out }
+    }
+
+    /// Log a line for `pause` with the values of `names` after `label`
+    fn log(
+        &mut self,
+        pause: &Pause,
+        text: &str,
+        label: &str,
+        names: &[&str],
+    ) -> Result<(), String> {
+        let location = match self.sources.file(Some(pause.span)) {
+            Some(file) => format!("{}:{}", file.name, pause.span.line),
+            None => format!("line {}", pause.span.line),
+        };
+        let variables = pause.variables();
+        let values: Vec<String> = names
+            .iter()
+            .filter_map(|name| {
+                let (_, value) = variables.iter().find(|(variable, _)| variable == name)?;
+                Some(format!("{} = {}", name, value))
+            })
+            .collect();
+        let mut line = format!("[{}] {}{}", location, "  ".repeat(pause.depth), text);
+        if !values.is_empty() {
+            if !text.is_empty() {
+                line.push_str("    ");
+            }
+            line.push_str(&format!("{} {}", label, values.join(", ")));
+        }
+        writeln!(self.out, "{}", line).map_err(|err| format!("Cannot write the trace: {}", err))
+    }
+
+    /// The source line the statement starts on
+    fn text(&self, pause: &Pause) -> String {
+        self.sources
+            .file(Some(pause.span))
+            .and_then(|file| file.source.lines().nth(pause.span.line.saturating_sub(1)))
+            .unwrap_or("")
+            .trim()
+            .to_string()
+    }
+}
+
+impl<W: Write> Hook for Tracer<W> {
+    fn before_statement(&mut self, pause: &Pause) -> Result<(), String> {
+        let text = self.text(pause);
+        self.log(pause, &text, "read", &reads(pause.ast, pause.statement))
+    }
+
+    fn after_statement(&mut self, pause: &Pause) -> Result<(), String> {
+        match writes(pause.ast, pause.statement) {
+            Some(name) => self.log(pause, "", "wrote", &[name]),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The variables a statement reads itself, leaving out those read by the
+/// statements in its body, in the order they are written
+fn reads<'a>(ast: &'a Ast, statement: &'a ASTNode) -> Vec<&'a str> {
+    let mut names = Vec::new();
+    match statement {
+        ASTNode::LetDeclaration { value, .. }
+        | ASTNode::ConstDeclaration { value, .. }
+        | ASTNode::Assignment { value, .. }
+        | ASTNode::Return {
+            value: Some(value), ..
+        }
+        | ASTNode::If {
+            condition: value, ..
+        }
+        | ASTNode::While {
+            condition: value, ..
+        }
+        | ASTNode::DoWhile {
+            condition: value, ..
+        }
+        | ASTNode::Assert {
+            condition: value, ..
+        }
+        | ASTNode::For {
+            condition: Some(value),
+            ..
+        }
+        | ASTNode::ForIn { range: value, .. } => identifiers(ast, &ast[*value], &mut names),
+        ASTNode::CompoundAssignment { name, value, .. } => {
+            names.push(name.as_str());
+            identifiers(ast, &ast[*value], &mut names);
+        }
+        ASTNode::Increment { name, .. } | ASTNode::Decrement { name, .. } => {
+            names.push(name.as_str())
+        }
+        ASTNode::Print { args, .. } => args
+            .iter()
+            .for_each(|arg| identifiers(ast, &ast[*arg], &mut names)),
+        ASTNode::IndexAssignment {
+            array,
+            index,
+            value,
+            ..
+        } => {
+            identifiers(ast, &ast[*array], &mut names);
+            identifiers(ast, &ast[*index], &mut names);
+            identifiers(ast, &ast[*value], &mut names);
+        }
+        ASTNode::FieldAssignment { object, value, .. } => {
+            identifiers(ast, &ast[*object], &mut names);
+            identifiers(ast, &ast[*value], &mut names);
+        }
+        expression if expression.is_expression() => identifiers(ast, expression, &mut names),
+        _ => {}
+    }
+    let mut seen = Vec::new();
+    names.retain(|name| {
+        let new = !seen.contains(name);
+        seen.push(name);
+        new
+    });
+    names
+}
+
+/// The variable a statement writes, once it has run
+fn writes<'a>(ast: &'a Ast, statement: &'a ASTNode) -> Option<&'a str> {
+    match statement {
+        ASTNode::LetDeclaration { name, .. }
+        | ASTNode::ConstDeclaration { name, .. }
+        | ASTNode::Assignment { name, .. }
+        | ASTNode::CompoundAssignment { name, .. }
+        | ASTNode::Increment { name, .. }
+        | ASTNode::Decrement { name, .. }
+        | ASTNode::Input { name, .. } => Some(name),
+        ASTNode::IndexAssignment { array: target, .. }
+        | ASTNode::FieldAssignment { object: target, .. } => root(ast, *target),
+        _ => None,
+    }
+}
+
+/// The variable an element or field belongs to, as `xs` in `xs[i].x`
+fn root(ast: &Ast, target: NodeId) -> Option<&str> {
+    match &ast[target] {
+        ASTNode::Identifier { name, .. } => Some(name),
+        ASTNode::Index { array: target, .. } | ASTNode::FieldAccess { object: target, .. } => {
+            root(ast, *target)
+        }
+        _ => None,
+    }
+}
+
+/// The variables an expression reads
+fn identifiers<'a>(ast: &'a Ast, expression: &'a ASTNode, names: &mut Vec<&'a str>) {
+    match expression {
+        ASTNode::Identifier { name, .. } => names.push(name),
+        ASTNode::BinaryOp { left, right, .. }
+        | ASTNode::Index {
+            array: left,
+            index: right,
+            ..
+        }
+        | ASTNode::Range {
+            start: left,
+            end: right,
+            ..
+        } => {
+            identifiers(ast, &ast[*left], names);
+            identifiers(ast, &ast[*right], names);
+        }
+        ASTNode::Slice {
+            array, start, end, ..
+        } => {
+            identifiers(ast, &ast[*array], names);
+            for bound in [start, end].into_iter().flatten() {
+                identifiers(ast, &ast[*bound], names);
+            }
+        }
+        ASTNode::UnaryOp { operand: value, .. } | ASTNode::FieldAccess { object: value, .. } => {
+            identifiers(ast, &ast[*value], names)
+        }
+        ASTNode::Conditional {
+            condition,
+            then_value,
+            else_value,
+            ..
+        } => {
+            identifiers(ast, &ast[*condition], names);
+            identifiers(ast, &ast[*then_value], names);
+            identifiers(ast, &ast[*else_value], names);
+        }
+        ASTNode::ArrayLiteral {
+            elements: values, ..
+        }
+        | ASTNode::Call { args: values, .. }
+        | ASTNode::Interpolation { values, .. } => values
+            .iter()
+            .for_each(|value| identifiers(ast, &ast[*value], names)),
+        ASTNode::MapLiteral { entries, .. } => {
+            for (key, value) in entries {
+                identifiers(ast, &ast[*key], names);
+                identifiers(ast, &ast[*value], names);
+            }
+        }
+        ASTNode::StructLiteral { fields, .. } => fields
+            .iter()
+            .for_each(|(_, value)| identifiers(ast, &ast[*value], names)),
+        // Only the variables a lambda captures are read where it's made
+        ASTNode::Lambda { params, body, .. } => {
+            let mut used = Vec::new();
+            identifiers(ast, &ast[*body], &mut used);
+            used.retain(|name| params.iter().all(|param| param.name != *name));
+            names.extend(used);
+        }
+        _ => {}
+    }
+}
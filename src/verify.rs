@@ -0,0 +1,137 @@
+//! `osho verify`, differential testing of the interpreter against the
+//! compiled program.
+//!
+//! Both run the same program on the same input. Any difference in what they
+//! print, or in whether they fail, means one of the two execution paths has
+//! drifted from the other.
+
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::backend::Backend;
+use crate::checks::Checks;
+use crate::code_generator::CodeGenerator;
+use crate::driver::{self, CCompiler};
+use crate::error::CompileError;
+use crate::interpreter::Interpreter;
+use crate::parser::Ast;
+
+/// How a run of the program ended
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    /// Everything the program printed
+    pub stdout: String,
+
+    /// The error that stopped it, if any
+    pub error: Option<String>,
+
+    /// The code it exited with
+    pub exit_code: i32,
+}
+
+/// Run the program with the interpreter, giving it `input` to read
+pub fn interpret(ast: &Ast, input: &[u8], max_call_depth: usize, checks: Checks) -> Run {
+    let mut interpreter = Interpreter::with_output(Vec::new())
+        .with_input(Cursor::new(input.to_vec()))
+        .with_max_call_depth(max_call_depth)
+        .with_checks(checks);
+    let (error, exit_code) = match interpreter.run(ast, ast.root()) {
+        Ok(()) => (None, 0),
+        Err(CompileError::Exit { code, .. }) => (None, code),
+        Err(err) => (Some(err.to_string()), err.exit_code()),
+    };
+    Run {
+        stdout: String::from_utf8_lossy(&interpreter.into_output()).into_owned(),
+        error,
+        exit_code,
+    }
+}
+
+/// Compile the program to C with `checks`, build it in `dir` and run it on
+/// `input`
+pub fn run_compiled(
+    ast: &Ast,
+    cc: &CCompiler,
+    checks: Checks,
+    dir: &Path,
+    input: &[u8],
+) -> Result<Run, String> {
+    let code = CodeGenerator::new()
+        .with_checks(checks)
+        .generate(ast)
+        .map_err(|err| err.to_string())?;
+    fs::create_dir_all(dir)
+        .map_err(|err| format!("Failed to create '{}': {}", dir.display(), err))?;
+    let c_file = dir.join("program.c");
+    let executable = dir.join("program");
+    fs::write(&c_file, code)
+        .map_err(|err| format!("Failed to write '{}': {}", c_file.display(), err))?;
+    cc.compile(&c_file, &executable)?;
+
+    let mut child = Command::new(&executable)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Failed to run '{}': {}", executable.display(), err))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A program that stops reading early closes the pipe, which is fine
+        let _ = stdin.write_all(input);
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("Failed to run '{}': {}", executable.display(), err))?;
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    Ok(Run {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        // A program can exit with a nonzero code without printing why
+        error: (!stderr.is_empty()).then_some(stderr),
+        exit_code: driver::exit_code(output.status),
+    })
+}
+
+/// Check the two runs agree, describing the first difference if they don't.
+/// Errors only have to agree on the exit code, as their messages are worded
+/// differently.
+pub fn compare(interpreted: &Run, compiled: &Run) -> Result<(), String> {
+    let mut interpreted_lines = interpreted.stdout.lines();
+    let mut compiled_lines = compiled.stdout.lines();
+    for line in 1.. {
+        match (interpreted_lines.next(), compiled_lines.next()) {
+            (Some(a), Some(b)) if a == b => {}
+            (None, None) => break,
+            (a, b) => {
+                return Err(format!(
+                    "Outputs differ at line {}:\n  interpreter: {}\n  compiled:    {}",
+                    line,
+                    a.unwrap_or("<no more output>"),
+                    b.unwrap_or("<no more output>")
+                ))
+            }
+        }
+    }
+    if interpreted.stdout != compiled.stdout {
+        return Err("Outputs differ in their line endings".to_string());
+    }
+    let failure = |run: &Run| match &run.error {
+        Some(error) => error.clone(),
+        None => format!("exited with code {}", run.exit_code),
+    };
+    match (interpreted.exit_code, compiled.exit_code) {
+        (a, b) if a == b => Ok(()),
+        (_, 0) => Err(format!(
+            "The interpreter failed but the compiled program didn't: {}",
+            failure(interpreted)
+        )),
+        (0, _) => Err(format!(
+            "The compiled program failed but the interpreter didn't: {}",
+            failure(compiled)
+        )),
+        (a, b) => Err(format!(
+            "The interpreter exited with code {} but the compiled program with code {}",
+            a, b
+        )),
+    }
+}
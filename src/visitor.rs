@@ -0,0 +1,52 @@
+//! Walking the AST. A pass implements [`Visitor`], or [`MutVisitor`] when it
+//! rewrites nodes, and overrides `visit` for the kinds of node it cares
+//! about. Every other node is walked by default, visiting the nodes it holds
+//! in source order.
+//!
+//! What each kind of node holds is written down once, in
+//! [`ASTNode::children`], so a new kind of node only needs adding there for
+//! every pass to walk into it.
+//!
+//! Two passes don't use these traits. The semantic analyzer computes a
+//! `Result<Type, CompileError>` for each expression and stops at the first
+//! error, which a visit returning nothing can't carry, and each kind of node
+//! it checks needs its own code anyway; its one generic walk, finding the
+//! variables a lambda captures, is a [`Visitor`]. The C code generator doesn't see the
+//! AST at all: it generates from the IR that
+//! [`SemanticAnalyzer::lower`](crate::semantic_analyzer::SemanticAnalyzer::lower)
+//! builds.
+//!
+//! [`ASTNode::children`]: crate::ast::ASTNode::children
+
+use crate::ast::{Ast, NodeId};
+
+/// A pass reading the AST
+pub trait Visitor {
+    /// Visit a node. Unless overridden, this walks it.
+    fn visit(&mut self, ast: &Ast, node: NodeId) {
+        self.walk(ast, node);
+    }
+
+    /// Visit each node `node` holds
+    fn walk(&mut self, ast: &Ast, node: NodeId) {
+        for child in ast[node].children() {
+            self.visit(ast, child);
+        }
+    }
+}
+
+/// A pass rewriting the AST. Nodes are replaced in place, so the nodes
+/// holding them needn't change.
+pub trait MutVisitor {
+    /// Visit a node. Unless overridden, this walks it.
+    fn visit_mut(&mut self, ast: &mut Ast, node: NodeId) {
+        self.walk_mut(ast, node);
+    }
+
+    /// Visit each node `node` holds
+    fn walk_mut(&mut self, ast: &mut Ast, node: NodeId) {
+        for child in ast[node].children() {
+            self.visit_mut(ast, child);
+        }
+    }
+}
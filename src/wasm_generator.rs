@@ -0,0 +1,699 @@
+//! WebAssembly backend, emitting a module in the text format (WAT).
+//!
+//! The program becomes an exported `main` function. Variables are locals,
+//! ints are `i64`, floats are `f64` and bools are `i32`. Printing goes
+//! through functions the host provides in the `env` module:
+//!
+//! - `print_i64 (param i64)`, `print_f64 (param f64)` and `print_bool (param i32)`,
+//!   which print a value and a newline, so only `println` of a single value
+//!   is supported
+//! - `pow` and `fmod`, both `(param f64 f64) (result f64)`, only imported when used
+//! - `read_i64 (result i64)` and `read_f64 (result f64)` for `input`, only
+//!   imported when used
+//!
+//! Arrays, strings, maps, structs, lambdas and builtin functions are not
+//! supported.
+
+use std::collections::HashMap;
+
+use crate::backend::Backend;
+use crate::builtins::Builtin;
+use crate::desugar;
+use crate::error::CompileError;
+use crate::parser::{ASTNode, Ast, BinaryOperator, NodeId, UnaryOperator};
+use crate::semantic_analyzer::Type;
+
+/// The WebAssembly type an osho expression is emitted as
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WasmType {
+    I64,
+    F64,
+    /// Bools, as 0 or 1
+    I32,
+}
+
+impl WasmType {
+    fn name(&self) -> &'static str {
+        match self {
+            WasmType::I64 => "i64",
+            WasmType::F64 => "f64",
+            WasmType::I32 => "i32",
+        }
+    }
+}
+
+/// A variable: its local's name and type
+#[derive(Debug, Clone)]
+struct Local {
+    name: String,
+    ty: WasmType,
+}
+
+pub struct WasmGenerator {
+    /// Instructions of `main`, one per line
+    body: Vec<String>,
+
+    /// Nesting depth of the current instruction, for indentation
+    depth: usize,
+
+    /// Every local of `main`, in declaration order
+    locals: Vec<Local>,
+
+    /// Variables visible in each enclosing scope, innermost last
+    scopes: Vec<HashMap<String, Local>>,
+
+    /// Counter for unique loop labels
+    labels: usize,
+
+    /// The labels `break` and `continue` branch to in each enclosing loop,
+    /// innermost last
+    loops: Vec<(String, String)>,
+
+    uses_pow: bool,
+    uses_fmod: bool,
+    uses_read_i64: bool,
+    uses_read_f64: bool,
+}
+
+impl Default for WasmGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for WasmGenerator {
+    fn generate(&mut self, ast: &Ast) -> Result<String, CompileError> {
+        *self = Self::new();
+        let ast = &desugar::desugared(ast);
+        self.statement(ast, ast.root())
+            .map_err(|message| CompileError::Codegen { message })?;
+        Ok(self.module())
+    }
+}
+
+impl WasmGenerator {
+    pub fn new() -> Self {
+        Self {
+            body: Vec::new(),
+            depth: 2,
+            locals: Vec::new(),
+            scopes: vec![HashMap::new()],
+            labels: 0,
+            loops: Vec::new(),
+            uses_pow: false,
+            uses_fmod: false,
+            uses_read_i64: false,
+            uses_read_f64: false,
+        }
+    }
+
+    fn statement(&mut self, ast: &Ast, node: NodeId) -> Result<(), String> {
+        match &ast[node] {
+            ASTNode::Program(statements) => {
+                for stmt in statements {
+                    self.statement(ast, *stmt)?;
+                }
+            }
+            ASTNode::LetDeclaration {
+                name,
+                ty: Some(Type::Float),
+                value,
+                ..
+            } => {
+                let value = self.expression_as(ast, *value, WasmType::F64)?;
+                let local = self.declare(name, WasmType::F64);
+                self.emit(format!("(local.set ${} {})", local, value));
+            }
+            ASTNode::LetDeclaration { name, value, .. }
+            | ASTNode::ConstDeclaration { name, value, .. } => {
+                let (value, ty) = self.expression(ast, *value)?;
+                let local = self.declare(name, ty);
+                self.emit(format!("(local.set ${} {})", local, value));
+            }
+            ASTNode::Assignment { name, value, .. } => {
+                let local = self.lookup(name)?;
+                let value = self.expression_as(ast, *value, local.ty)?;
+                self.emit(format!("(local.set ${} {})", local.name, value));
+            }
+            ASTNode::Input { name, .. } => {
+                let local = self.lookup(name)?;
+                let function = match local.ty {
+                    WasmType::F64 => {
+                        self.uses_read_f64 = true;
+                        "read_f64"
+                    }
+                    _ => {
+                        self.uses_read_i64 = true;
+                        "read_i64"
+                    }
+                };
+                self.emit(format!("(local.set ${} (call ${}))", local.name, function));
+            }
+            ASTNode::Print {
+                format,
+                args,
+                newline,
+                ..
+            } => {
+                // The host functions print one value per line
+                let [expr] = args.as_slice() else {
+                    return Err("The wasm target can only print one value at a time".to_string());
+                };
+                if format.is_some() {
+                    return Err("The wasm target doesn't support format strings".to_string());
+                }
+                if !newline {
+                    return Err(
+                        "The wasm target can only print whole lines, use println".to_string()
+                    );
+                }
+                let (value, ty) = self.expression(ast, *expr)?;
+                let function = match ty {
+                    WasmType::I64 => "print_i64",
+                    WasmType::F64 => "print_f64",
+                    WasmType::I32 => "print_bool",
+                };
+                self.emit(format!("(call ${} {})", function, value));
+            }
+            ASTNode::Block(statements) => {
+                self.scopes.push(HashMap::new());
+                let result = statements
+                    .iter()
+                    .try_for_each(|stmt| self.statement(ast, *stmt));
+                self.scopes.pop();
+                result?;
+            }
+            ASTNode::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let condition = self.condition(ast, *condition)?;
+                self.open(format!("(if {}", condition));
+                self.open("(then".to_string());
+                self.statement(ast, *then_branch)?;
+                self.close();
+                if let Some(else_branch) = else_branch {
+                    self.open("(else".to_string());
+                    self.statement(ast, *else_branch)?;
+                    self.close();
+                }
+                self.close();
+            }
+            ASTNode::While {
+                condition, body, ..
+            } => {
+                let label = self.next_label();
+                self.open(format!("(block $break_{}", label));
+                self.open(format!("(loop $continue_{}", label));
+                let condition = self.condition(ast, *condition)?;
+                self.emit(format!("(br_if $break_{} (i32.eqz {}))", label, condition));
+                let targets = (format!("$break_{}", label), format!("$continue_{}", label));
+                self.loop_body(ast, targets, *body)?;
+                self.emit(format!("(br $continue_{})", label));
+                self.close();
+                self.close();
+            }
+            // There is no host function to report the message, so a failed
+            // assert just traps
+            ASTNode::Assert { condition, .. } => {
+                let condition = self.condition(ast, *condition)?;
+                self.emit(format!("(if (i32.eqz {}) (then unreachable))", condition));
+            }
+            ASTNode::DoWhile {
+                body, condition, ..
+            } => {
+                let label = self.next_label();
+                self.open(format!("(block $break_{}", label));
+                self.open(format!("(loop $continue_{}", label));
+                // `continue` leaves this block, so the condition still runs
+                self.open(format!("(block $next_{}", label));
+                let targets = (format!("$break_{}", label), format!("$next_{}", label));
+                self.loop_body(ast, targets, *body)?;
+                self.close();
+                let condition = self.condition(ast, *condition)?;
+                self.emit(format!("(br_if $continue_{} {})", label, condition));
+                self.close();
+                self.close();
+            }
+            ASTNode::For {
+                init,
+                condition,
+                step,
+                body,
+                ..
+            } => {
+                // The initializer's variables belong to the loop's scope
+                self.scopes.push(HashMap::new());
+                let result = self.for_loop(ast, init, condition, step, *body);
+                self.scopes.pop();
+                result?;
+            }
+            ASTNode::ForIn {
+                name, range, body, ..
+            } => {
+                self.scopes.push(HashMap::new());
+                let result = self.for_in_loop(ast, name, *range, *body);
+                self.scopes.pop();
+                result?;
+            }
+            ASTNode::IndexAssignment { .. } => {
+                return Err("The wasm target doesn't support arrays".to_string())
+            }
+            // Declaring a struct is fine as long as none is constructed
+            ASTNode::StructDeclaration { .. } => {}
+            ASTNode::FieldAssignment { .. } => {
+                return Err("The wasm target doesn't support structs".to_string())
+            }
+            // Calls to a function are only possible after its declaration
+            ASTNode::FunctionDeclaration { .. } | ASTNode::Return { .. } => {
+                return Err("The wasm target doesn't support functions".to_string())
+            }
+            // Like a failed assert, it traps without a message
+            ASTNode::Call { name, .. } if name == Builtin::Error.name() => {
+                self.emit("unreachable".to_string());
+            }
+            ASTNode::Call { .. } => {
+                // Reports that builtins aren't supported
+                self.expression(ast, node)?;
+            }
+            ASTNode::Break { .. } | ASTNode::Continue { .. } => {
+                let (break_label, continue_label) = self
+                    .loops
+                    .last()
+                    .ok_or("'break' and 'continue' can only be used inside a loop")?;
+                let label = if matches!(ast[node], ASTNode::Break { .. }) {
+                    break_label
+                } else {
+                    continue_label
+                };
+                self.emit(format!("(br {})", label));
+            }
+            _ => return Err("Unexpected AST node".to_string()),
+        }
+        Ok(())
+    }
+
+    fn for_loop(
+        &mut self,
+        ast: &Ast,
+        init: &Option<NodeId>,
+        condition: &Option<NodeId>,
+        step: &Option<NodeId>,
+        body: NodeId,
+    ) -> Result<(), String> {
+        if let Some(init) = init {
+            self.statement(ast, *init)?;
+        }
+        let label = self.next_label();
+        self.open(format!("(block $break_{}", label));
+        self.open(format!("(loop $continue_{}", label));
+        if let Some(condition) = condition {
+            let condition = self.condition(ast, *condition)?;
+            self.emit(format!("(br_if $break_{} (i32.eqz {}))", label, condition));
+        }
+        let continue_target = if step.is_some() {
+            // `continue` leaves this block, so the step still runs
+            self.open(format!("(block $next_{}", label));
+            format!("$next_{}", label)
+        } else {
+            format!("$continue_{}", label)
+        };
+        self.loop_body(ast, (format!("$break_{}", label), continue_target), body)?;
+        if let Some(step) = step {
+            self.close();
+            self.statement(ast, *step)?;
+        }
+        self.emit(format!("(br $continue_{})", label));
+        self.close();
+        self.close();
+        Ok(())
+    }
+
+    /// Generate `for name in range`, counting in the loop variable's local up
+    /// to the end of the range, which is kept in a local of its own
+    fn for_in_loop(
+        &mut self,
+        ast: &Ast,
+        name: &str,
+        range: NodeId,
+        body: NodeId,
+    ) -> Result<(), String> {
+        let ASTNode::Range {
+            start,
+            end,
+            inclusive,
+            ..
+        } = ast[range]
+        else {
+            unreachable!("the parser only puts ranges in for-in loops");
+        };
+        // Both bounds are evaluated before the loop variable exists
+        let start = self.expression_as(ast, start, WasmType::I64)?;
+        let end = self.expression_as(ast, end, WasmType::I64)?;
+        // Not an identifier, so no variable can refer to it
+        let end_local = self.declare(&format!("{}..end", name), WasmType::I64);
+        self.emit(format!("(local.set ${} {})", end_local, end));
+        let counter = self.declare(name, WasmType::I64);
+        self.emit(format!("(local.set ${} {})", counter, start));
+
+        let label = self.next_label();
+        self.open(format!("(block $break_{}", label));
+        self.open(format!("(loop $continue_{}", label));
+        let past_end = if inclusive { "i64.gt_s" } else { "i64.ge_s" };
+        self.emit(format!(
+            "(br_if $break_{} ({} (local.get ${}) (local.get ${})))",
+            label, past_end, counter, end_local
+        ));
+        // `continue` leaves this block, so the counter is still incremented
+        self.open(format!("(block $next_{}", label));
+        let targets = (format!("$break_{}", label), format!("$next_{}", label));
+        self.loop_body(ast, targets, body)?;
+        self.close();
+        if inclusive {
+            // Stop at the end rather than count past it, which could overflow
+            self.emit(format!(
+                "(br_if $break_{} (i64.eq (local.get ${}) (local.get ${})))",
+                label, counter, end_local
+            ));
+        }
+        self.emit(format!(
+            "(local.set ${0} (i64.add (local.get ${0}) (i64.const 1)))",
+            counter
+        ));
+        self.emit(format!("(br $continue_{})", label));
+        self.close();
+        self.close();
+        Ok(())
+    }
+
+    /// Generate the body of a loop, where `break` and `continue` branch to
+    /// the given labels
+    fn loop_body(
+        &mut self,
+        ast: &Ast,
+        targets: (String, String),
+        body: NodeId,
+    ) -> Result<(), String> {
+        self.loops.push(targets);
+        let result = self.statement(ast, body);
+        self.loops.pop();
+        result
+    }
+
+    /// Generate an expression, returning its code and type
+    fn expression(&mut self, ast: &Ast, expr: NodeId) -> Result<(String, WasmType), String> {
+        match &ast[expr] {
+            ASTNode::Int(num) => Ok((format!("(i64.const {})", num), WasmType::I64)),
+            // Debug formatting keeps the '.0' and round-trips exactly
+            ASTNode::Number(num) => Ok((format!("(f64.const {:?})", num), WasmType::F64)),
+            ASTNode::Bool(b) => Ok((format!("(i32.const {})", *b as i32), WasmType::I32)),
+            ASTNode::Identifier { name, .. } => {
+                let local = self.lookup(name)?;
+                Ok((format!("(local.get ${})", local.name), local.ty))
+            }
+            ASTNode::BinaryOp {
+                left,
+                op: BinaryOperator::And,
+                right,
+                ..
+            } => {
+                // Short-circuit: the right side only runs when the left is truthy
+                let left = self.condition(ast, *left)?;
+                let right = self.condition(ast, *right)?;
+                Ok((
+                    format!(
+                        "(if (result i32) {} (then {}) (else (i32.const 0)))",
+                        left, right
+                    ),
+                    WasmType::I32,
+                ))
+            }
+            ASTNode::BinaryOp {
+                left,
+                op: BinaryOperator::Or,
+                right,
+                ..
+            } => {
+                // Short-circuit: the right side only runs when the left is falsy
+                let left = self.condition(ast, *left)?;
+                let right = self.condition(ast, *right)?;
+                Ok((
+                    format!(
+                        "(if (result i32) {} (then (i32.const 1)) (else {}))",
+                        left, right
+                    ),
+                    WasmType::I32,
+                ))
+            }
+            ASTNode::BinaryOp {
+                left, op, right, ..
+            } => {
+                let left = self.expression(ast, *left)?;
+                let right = self.expression(ast, *right)?;
+                if op.is_comparison() {
+                    self.comparison(op, left, right)
+                } else {
+                    self.arithmetic(op, left, right)
+                }
+            }
+            ASTNode::UnaryOp { op, operand, .. } => match op {
+                UnaryOperator::Not => {
+                    let operand = self.condition(ast, *operand)?;
+                    Ok((format!("(i32.eqz {})", operand), WasmType::I32))
+                }
+                UnaryOperator::Negate => match self.expression(ast, *operand)? {
+                    (operand, WasmType::F64) => {
+                        Ok((format!("(f64.neg {})", operand), WasmType::F64))
+                    }
+                    (operand, ty) => Ok((format!("(i64.sub (i64.const 0) {})", operand), ty)),
+                },
+            },
+            ASTNode::Conditional {
+                condition,
+                then_value,
+                else_value,
+                ..
+            } => {
+                let condition = self.condition(ast, *condition)?;
+                let (then_value, ty) = self.expression(ast, *then_value)?;
+                let (else_value, _) = self.expression(ast, *else_value)?;
+                Ok((
+                    format!(
+                        "(if (result {}) {} (then {}) (else {}))",
+                        ty.name(),
+                        condition,
+                        then_value,
+                        else_value
+                    ),
+                    ty,
+                ))
+            }
+            ASTNode::ArrayLiteral { .. } | ASTNode::Index { .. } | ASTNode::Slice { .. } => {
+                Err("The wasm target doesn't support arrays".to_string())
+            }
+            ASTNode::Str(_) | ASTNode::Interpolation { .. } | ASTNode::MapLiteral { .. } => {
+                Err("The wasm target doesn't support strings or maps".to_string())
+            }
+            ASTNode::Char(_) => Err("The wasm target doesn't support characters".to_string()),
+            ASTNode::StructLiteral { .. } | ASTNode::FieldAccess { .. } => {
+                Err("The wasm target doesn't support structs".to_string())
+            }
+            ASTNode::Call { name, .. } => Err(format!(
+                "The wasm target doesn't support builtin functions like '{}'",
+                name
+            )),
+            ASTNode::Lambda { .. } => Err("The wasm target doesn't support lambdas".to_string()),
+            _ => Err("Unexpected expression node".to_string()),
+        }
+    }
+
+    /// Generate an expression converted to the given type, for storing into a variable
+    fn expression_as(&mut self, ast: &Ast, expr: NodeId, ty: WasmType) -> Result<String, String> {
+        match self.expression(ast, expr)? {
+            (value, WasmType::I64) if ty == WasmType::F64 => {
+                Ok(format!("(f64.convert_i64_s {})", value))
+            }
+            (value, _) => Ok(value),
+        }
+    }
+
+    /// Generate an expression as an `i32` that is 1 when it is truthy
+    fn condition(&mut self, ast: &Ast, expr: NodeId) -> Result<String, String> {
+        Ok(match self.expression(ast, expr)? {
+            (value, WasmType::I32) => value,
+            (value, WasmType::I64) => format!("(i64.ne {} (i64.const 0))", value),
+            (value, WasmType::F64) => format!("(f64.ne {} (f64.const 0))", value),
+        })
+    }
+
+    /// Integer arithmetic stays integral, anything else is promoted to f64
+    fn arithmetic(
+        &mut self,
+        op: &BinaryOperator,
+        left: (String, WasmType),
+        right: (String, WasmType),
+    ) -> Result<(String, WasmType), String> {
+        if left.1 == WasmType::I64 && right.1 == WasmType::I64 && *op != BinaryOperator::Power {
+            let instruction = match op {
+                BinaryOperator::Plus => "i64.add",
+                BinaryOperator::Minus => "i64.sub",
+                BinaryOperator::Multiply => "i64.mul",
+                BinaryOperator::Divide => "i64.div_s",
+                BinaryOperator::Modulo => "i64.rem_s",
+                _ => return Err(format!("Unexpected arithmetic operator {:?}", op)),
+            };
+            return Ok((
+                format!("({} {} {})", instruction, left.0, right.0),
+                WasmType::I64,
+            ));
+        }
+        let left = Self::to_f64(left);
+        let right = Self::to_f64(right);
+        let code = match op {
+            BinaryOperator::Plus => format!("(f64.add {} {})", left, right),
+            BinaryOperator::Minus => format!("(f64.sub {} {})", left, right),
+            BinaryOperator::Multiply => format!("(f64.mul {} {})", left, right),
+            BinaryOperator::Divide => format!("(f64.div {} {})", left, right),
+            // WebAssembly has neither a float remainder nor a power instruction
+            BinaryOperator::Modulo => {
+                self.uses_fmod = true;
+                format!("(call $fmod {} {})", left, right)
+            }
+            BinaryOperator::Power => {
+                self.uses_pow = true;
+                format!("(call $pow {} {})", left, right)
+            }
+            _ => return Err(format!("Unexpected arithmetic operator {:?}", op)),
+        };
+        Ok((code, WasmType::F64))
+    }
+
+    /// Compare in the operands' own type, promoting to f64 when they differ
+    fn comparison(
+        &mut self,
+        op: &BinaryOperator,
+        left: (String, WasmType),
+        right: (String, WasmType),
+    ) -> Result<(String, WasmType), String> {
+        let (left, right, ty) = if left.1 == right.1 {
+            (left.0, right.0, left.1)
+        } else {
+            (Self::to_f64(left), Self::to_f64(right), WasmType::F64)
+        };
+        let float = ty == WasmType::F64;
+        let instruction = match op {
+            BinaryOperator::Equal => "eq",
+            BinaryOperator::NotEqual => "ne",
+            BinaryOperator::Less if float => "lt",
+            BinaryOperator::LessEqual if float => "le",
+            BinaryOperator::Greater if float => "gt",
+            BinaryOperator::GreaterEqual if float => "ge",
+            BinaryOperator::Less => "lt_s",
+            BinaryOperator::LessEqual => "le_s",
+            BinaryOperator::Greater => "gt_s",
+            BinaryOperator::GreaterEqual => "ge_s",
+            _ => return Err(format!("Unexpected comparison operator {:?}", op)),
+        };
+        Ok((
+            format!("({}.{} {} {})", ty.name(), instruction, left, right),
+            WasmType::I32,
+        ))
+    }
+
+    fn to_f64((value, ty): (String, WasmType)) -> String {
+        match ty {
+            WasmType::F64 => value,
+            _ => format!("(f64.convert_i64_s {})", value),
+        }
+    }
+
+    /// Declare a variable in the innermost scope, giving it a fresh local so
+    /// shadowed variables don't clash
+    fn declare(&mut self, name: &str, ty: WasmType) -> String {
+        let taken = |candidate: &str| self.locals.iter().any(|local| local.name == candidate);
+        let mut local_name = name.to_string();
+        let mut suffix = 0;
+        while taken(&local_name) {
+            suffix += 1;
+            local_name = format!("{}_{}", name, suffix);
+        }
+        let local = Local {
+            name: local_name.clone(),
+            ty,
+        };
+        self.locals.push(local.clone());
+        self.scopes
+            .last_mut()
+            .expect("global scope is never popped")
+            .insert(name.to_string(), local);
+        local_name
+    }
+
+    /// Find a variable, searching from the innermost scope outwards
+    fn lookup(&self, name: &str) -> Result<Local, String> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+            .ok_or_else(|| format!("Variable '{}' is not declared", name))
+    }
+
+    fn next_label(&mut self) -> usize {
+        self.labels += 1;
+        self.labels
+    }
+
+    fn emit(&mut self, line: String) {
+        self.body
+            .push(format!("{}{}", "  ".repeat(self.depth), line));
+    }
+
+    /// Emit the start of a block whose contents are indented
+    fn open(&mut self, line: String) {
+        self.emit(line);
+        self.depth += 1;
+    }
+
+    fn close(&mut self) {
+        self.depth -= 1;
+        if let Some(last) = self.body.last_mut() {
+            last.push(')');
+        }
+    }
+
+    fn module(&self) -> String {
+        let mut out = String::from("(module\n");
+        out.push_str("  (import \"env\" \"print_i64\" (func $print_i64 (param i64)))\n");
+        out.push_str("  (import \"env\" \"print_f64\" (func $print_f64 (param f64)))\n");
+        out.push_str("  (import \"env\" \"print_bool\" (func $print_bool (param i32)))\n");
+        if self.uses_pow {
+            out.push_str("  (import \"env\" \"pow\" (func $pow (param f64 f64) (result f64)))\n");
+        }
+        if self.uses_fmod {
+            out.push_str("  (import \"env\" \"fmod\" (func $fmod (param f64 f64) (result f64)))\n");
+        }
+        if self.uses_read_i64 {
+            out.push_str("  (import \"env\" \"read_i64\" (func $read_i64 (result i64)))\n");
+        }
+        if self.uses_read_f64 {
+            out.push_str("  (import \"env\" \"read_f64\" (func $read_f64 (result f64)))\n");
+        }
+        out.push_str("  (func $main (export \"main\")\n");
+        for local in &self.locals {
+            out.push_str(&format!(
+                "    (local ${} {})\n",
+                local.name,
+                local.ty.name()
+            ));
+        }
+        for line in &self.body {
+            out.push_str(line);
+            out.push('\n');
+        }
+        out.push_str("  )\n)\n");
+        out
+    }
+}
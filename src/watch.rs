@@ -0,0 +1,70 @@
+//! `osho watch`, which runs a program again whenever it's saved.
+//!
+//! Files are polled for their modification times rather than watched through
+//! the operating system, which is plenty for the handful of files a program
+//! is made of.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::modules::Loader;
+
+/// How often the files are checked for changes
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The files of the program at `path`: its main file and the files it
+/// imports, as far as they load. The standard library is built in, so it's
+/// never watched.
+pub fn program_files(path: &Path) -> Vec<PathBuf> {
+    let mut loader = Loader::new();
+    let _ = loader.load_path(path);
+    let mut files = vec![path.to_path_buf()];
+    for file in loader.sources().files() {
+        let file = PathBuf::from(&file.name);
+        if file.is_file() && !files.contains(&file) {
+            files.push(file);
+        }
+    }
+    files
+}
+
+/// Files and when they were last changed, for noticing when they change
+/// again
+#[derive(Debug, Clone)]
+pub struct Watcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl Watcher {
+    /// Start watching `files` as they are now
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        let files = files
+            .into_iter()
+            .map(|file| {
+                let modified = modified(&file);
+                (file, modified)
+            })
+            .collect();
+        Self { files }
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|(file, _)| file.as_path())
+    }
+
+    /// The first file that was changed, created or deleted since the watcher
+    /// started or last saw a change
+    pub fn changed(&mut self) -> Option<&Path> {
+        let (file, seen) = self
+            .files
+            .iter_mut()
+            .find(|(file, seen)| modified(file) != *seen)?;
+        *seen = modified(file);
+        Some(file)
+    }
+}
+
+fn modified(file: &Path) -> Option<SystemTime> {
+    fs::metadata(file).and_then(|meta| meta.modified()).ok()
+}
@@ -0,0 +1,94 @@
+use osho_lang::ast_printer;
+
+const PROGRAM: &str = "\
+struct P { x, y }
+fn half(a: float) -> float {
+    return a / 2
+}
+fn down(n: int) -> int {
+    if n > 0 {
+        return down(n - 1)
+    }
+    return n
+}
+fn make(x: int, y: float) -> P {
+    return P { x: x, y: y }
+}
+let x: float = 5
+let mut xs: [int] = []
+push(xs, 4)
+let p: P = make(1, 2)
+println(x / 2, half(3), down(3), len(xs), p.y / 4, 7 / 2)";
+
+const OUTPUT: &str = "2.5 1.5 0 1 0.5 3\n";
+
+#[test]
+fn annotated_types_decide_how_values_are_stored() {
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, OUTPUT);
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, OUTPUT);
+    let tokens = osho_lang::Lexer::new("fn f(a: [float], b) -> {string: int} {}")
+        .get_tokens()
+        .unwrap();
+    let ast = osho_lang::Parser::new(&tokens).parse().unwrap();
+    assert_eq!(
+        ast_printer::to_sexpr(&ast, ast.root()),
+        "(program (fn f(a: [float], b) -> {string: int} (block)))"
+    );
+}
+
+#[test]
+fn values_must_fit_their_annotations() {
+    let source = "\
+let x: int = 1.5
+let e: [int] = [1.5]
+fn f(a: float) -> int {
+    return a
+}
+println(f(1))
+fn g(a: float) {}
+g(true)
+fn h() -> int {}
+println(h())
+let q: Q = 1";
+    let errors = osho_lang::parse_and_check(source).unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|err| err.message()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Variable 'x' is int, found float",
+            "Variable 'e' is [int], found [float]",
+            "Function 'f' returns int, but this returns float",
+            "Parameter 'a' of g() is float, found bool",
+            "Function 'h' doesn't return a value on every path",
+            "Unknown type 'Q'"
+        ]
+    );
+    let errors = osho_lang::parse_and_check("let m: {int: int} = {}").unwrap_err();
+    assert_eq!(errors[0].message(), "Map keys must be strings");
+}
+
+#[test]
+fn uncalled_functions_are_checked_when_their_parameters_are_annotated() {
+    let source = "\
+fn f(a: int) -> int {
+    return a + true
+}
+fn g(a) {
+    println(a + true)
+}";
+    let errors = osho_lang::parse_and_check(source).unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|err| err.message()).collect();
+    assert_eq!(messages, ["Cannot add int and bool"]);
+
+    let c = osho_lang::compile_to_c("fn twice(a: int) -> int {\n    return a * 2\n}\nprintln(1)")
+        .unwrap();
+    assert!(!c.contains("osho_fn_twice"), "{}", c);
+}
+
+#[test]
+fn annotations_pick_c_types() {
+    let c = osho_lang::compile_to_c(PROGRAM).unwrap();
+    assert!(c.contains("const double x = 5.0;"), "{}", c);
+    assert!(c.contains("static double osho_fn_half(double a)"), "{}", c);
+    assert!(c.contains("osho_fn_half(3.0)"), "{}", c);
+}
@@ -0,0 +1,104 @@
+use std::fs;
+use std::process::Command;
+
+use osho_lang::{bytecode, Interpreter, Vm};
+
+mod common;
+
+const PROGRAM: &str = "\
+fn greet(name: string) {
+    println(\"hello ${name}\")
+}
+let all = args()
+println(all, len(all), argc())
+for i in 0..argc() {
+    greet(argv(i))
+}";
+
+const ARGS: [&str; 2] = ["ann", "b c"];
+
+const OUTPUT: &str = "[\"ann\", \"b c\"] 2 2\nhello ann\nhello b c\n";
+
+fn args() -> Vec<String> {
+    ARGS.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn programs_read_the_arguments_they_are_given() {
+    let ast = osho_lang::parse_and_check(PROGRAM).unwrap();
+    let mut interpreter = Interpreter::with_output(Vec::new()).with_args(args());
+    interpreter.run(&ast, ast.root()).unwrap();
+    assert_eq!(
+        String::from_utf8(interpreter.into_output()).unwrap(),
+        OUTPUT
+    );
+
+    let chunk = bytecode::Compiler::new().compile(&ast).unwrap();
+    let mut vm = Vm::with_output(Vec::new()).with_args(args());
+    vm.run(&chunk).unwrap();
+    assert_eq!(String::from_utf8(vm.into_output()).unwrap(), OUTPUT);
+
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, "[] 0 0\n");
+    let err = osho_lang::run_vm("println(argv(1))").unwrap_err();
+    assert_eq!(err.message(), "argv(1) is out of range for 0 arguments");
+    assert_eq!(err.exit_code(), 70);
+}
+
+#[test]
+fn compiled_main_takes_the_arguments() {
+    let c = osho_lang::compile_to_c(PROGRAM).unwrap();
+    assert!(c.contains("int main(int argc, char **argv) {"), "{}", c);
+    assert!(
+        c.contains("static void osho_fn_greet(const char *name)"),
+        "{}",
+        c
+    );
+    let plain = osho_lang::compile_to_c("println(1)").unwrap();
+    assert!(plain.contains("int main() {"), "{}", plain);
+
+    let Some(cc) = common::c_compiler() else {
+        return;
+    };
+    let dir = std::env::temp_dir().join(format!("osho-args-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let c_file = dir.join("program.c");
+    let executable = dir.join("program");
+    fs::write(&c_file, &c).unwrap();
+    cc.compile(&c_file, &executable).unwrap();
+    let output = Command::new(&executable).args(ARGS).output().unwrap();
+    let without_args = Command::new(&executable).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), OUTPUT);
+    assert_eq!(String::from_utf8_lossy(&without_args.stdout), "[] 0 0\n");
+
+    let js = osho_lang::compile_to_js("println(argc(), argv(0))").unwrap();
+    assert!(
+        js.contains("console.log(oshoArgs.length, oshoArgv(0));"),
+        "{}",
+        js
+    );
+}
+
+#[test]
+fn arguments_are_checked() {
+    let cases = [
+        (
+            "println(argv(\"x\"))",
+            "argv() expects an int, found string",
+        ),
+        (
+            "println(argc(1))",
+            "argc() takes 0 arguments but 1 was given",
+        ),
+        ("let n: int = argv(0)", "Variable 'n' is int, found string"),
+    ];
+    for (source, message) in cases {
+        let errors = osho_lang::parse_and_check(source).unwrap_err();
+        assert_eq!(errors[0].message(), message, "{}", source);
+    }
+    let c = osho_lang::compile_to_c("println(argv(0) == argv(1))").unwrap_err();
+    assert_eq!(
+        c.message(),
+        "Operators on strings are only supported by `osho run` for now, not by compiled targets"
+    );
+}
@@ -0,0 +1,99 @@
+use osho_lang::Checks;
+
+mod common;
+
+const PROGRAM: &str = "\
+let mut a = [1, 2, 3]
+let b = a
+a[0] = 10
+let mut m = [[1.5], [2.0, 3.5]]
+m[1][0] = 4
+println(b, len(a), b[0] / 3)
+println \"{} {}\", m, len(m[1])";
+
+const OUTPUT: &str = "[10, 2, 3] 3 3\n[[1.5], [4, 3.5]] 2\n";
+
+#[test]
+fn arrays_are_shared_indexed_and_measured() {
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, OUTPUT);
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, OUTPUT);
+}
+
+#[test]
+fn indexing_is_bounds_checked() {
+    let source = "let a = [1, 2]\nprintln(a[0])\nprintln(a[-1])";
+    for err in [
+        osho_lang::interpret(source).unwrap_err(),
+        osho_lang::run_vm(source).unwrap_err(),
+    ] {
+        assert_eq!(
+            err.message(),
+            "Index -1 is out of bounds for an array of length 2"
+        );
+        assert_eq!(err.span().unwrap().line, 3);
+    }
+
+    let c = osho_lang::compile_to_c("let mut a = [1, 2]\na[1] = a[0]").unwrap();
+    assert!(
+        c.contains("((long *)a->data)[osho_index(a, 1)] = _t1;"),
+        "{}",
+        c
+    );
+}
+
+#[test]
+fn arrays_are_type_checked() {
+    let source = "\
+let a = []
+let b = [1, true]
+let c = [1]
+c[0] = 2
+let mut d = [1]
+d[0] = 2.5
+println(d[true], len(1), size(d))
+if d {}";
+    let errors = osho_lang::parse_and_check(source).unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|err| err.message()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Cannot infer the type of an empty array",
+            "Array elements must have the same type, found int and bool",
+            "Cannot assign to immutable variable 'c', declare it with `let mut c`",
+            "Cannot assign float to an element of [int]",
+            "Array index must be an int, found bool",
+            "Cannot use [int] as a condition"
+        ]
+    );
+}
+
+const GRID: &str = "\
+let mut grid: [[int]] = []
+for i in 0..3 {
+    let mut row: [int] = []
+    for j in 0..3 {
+        push(row, i * j)
+    }
+    push(grid, row)
+}
+grid[2][2] = grid[2][2] + 10
+let mut cube = [[[1, 2], [3]], [[4]]]
+cube[0][1][0] = 30
+let mut row = grid[1]
+row[0] = 99
+println(grid, cube, cube[1][0][0])
+println(grid[1][5])";
+
+#[test]
+fn compiled_nested_arrays_match_the_interpreter() {
+    if let Some(compiled) = common::run_both("grid", GRID, Checks::default()) {
+        assert_eq!(
+            compiled.stdout,
+            "[[0, 0, 0], [99, 1, 2], [0, 2, 14]] [[[1, 2], [30]], [[4]]] 4\n"
+        );
+        assert_eq!(
+            compiled.error.as_deref(),
+            Some("Index 5 is out of bounds for an array of length 3")
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use osho_lang::{ast_printer, formatter, Checks, CompileError, Lexer, Parser};
+
+mod common;
+
+const PROGRAM: &str = "\
+fn halve(n: int) -> int {
+    assert n % 2 == 0, \"n must be even\"
+    return n / 2
+}
+let x = 3
+assert x > 0
+println(halve(4))
+println(halve(x))
+println(\"unreachable\")";
+
+#[test]
+fn failed_asserts_stop_the_program_with_their_message() {
+    for result in [osho_lang::interpret(PROGRAM), osho_lang::run_vm(PROGRAM)] {
+        let err = result.unwrap_err();
+        let CompileError::Runtime(runtime) = &err else {
+            panic!("expected a runtime error, found {:?}", err);
+        };
+        assert_eq!(runtime.message, "Assertion failed: n must be even");
+        assert_eq!((runtime.span.line, runtime.span.column), (2, 5));
+        assert_eq!(err.exit_code(), 70);
+    }
+    let err = osho_lang::interpret("assert 1 > 2").unwrap_err();
+    assert_eq!(err.message(), "Assertion failed");
+    assert_eq!(
+        osho_lang::run_vm("assert 1 < 2\nprintln(1)")
+            .unwrap()
+            .stdout,
+        "1\n"
+    );
+}
+
+#[test]
+fn compiled_asserts_print_the_message_and_exit() {
+    if let Some(compiled) = common::run_both("assert", PROGRAM, Checks::default()) {
+        assert_eq!(compiled.stdout, "2\n");
+        assert_eq!(
+            compiled.error.as_deref(),
+            Some("Assertion failed: n must be even (line 2)")
+        );
+    }
+
+    let js = osho_lang::compile_to_js(PROGRAM).unwrap();
+    assert!(
+        js.contains(
+            "if (!((n % 2) === 0)) throw new Error(\"Assertion failed: n must be even (line 2)\");"
+        ),
+        "{}",
+        js
+    );
+    let wat = osho_lang::compile_to_wasm("let x = 1\nassert x").unwrap();
+    assert!(
+        wat.contains("(if (i32.eqz (i64.ne (local.get $x) (i64.const 0))) (then unreachable))"),
+        "{}",
+        wat
+    );
+}
+
+#[test]
+fn asserts_parse_and_format() {
+    let source = "assert  x>0 ,\"x is positive\"\nassert(done)";
+    let tokens = Lexer::new(source).get_tokens().unwrap();
+    let mut parser = Parser::new(&tokens);
+    let statements = parser.parse_statements().unwrap();
+    let ast = parser.into_ast();
+    assert_eq!(
+        ast_printer::to_sexpr(&ast, statements[0]),
+        "(assert \"x is positive\" (> x 0))"
+    );
+    assert_eq!(ast_printer::to_sexpr(&ast, statements[1]), "(assert done)");
+    assert_eq!(
+        formatter::format(source).unwrap(),
+        "assert x > 0, \"x is positive\"\nassert (done)\n"
+    );
+}
+
+#[test]
+fn asserts_are_checked() {
+    let cases = [
+        ("assert \"yes\"", "Cannot use string as a condition"),
+        (
+            "let x = 1\nassert x,",
+            "Expected a message after ',' in an assert",
+        ),
+        ("assert missing", "Variable 'missing' is not declared"),
+    ];
+    for (source, message) in cases {
+        let errors = osho_lang::parse_and_check(source).unwrap_err();
+        assert_eq!(errors[0].message(), message, "{}", source);
+    }
+}
@@ -0,0 +1,117 @@
+use osho_lang::ast_printer::to_sexpr;
+use osho_lang::formatter;
+use osho_lang::{Checks, Lexer, Parser};
+
+mod common;
+
+const PROGRAM: &str = "\
+fn val(n: int) -> int {
+    println(\"val {}\", n)
+    return n
+}
+fn idx(n: int) -> int {
+    println(\"idx {}\", n)
+    return n
+}
+let mut a = 1.5
+let mut b = 2
+let mut c = [0, 0]
+c[idx(1)] = val(7)
+a = b = val(3)
+for (let mut i = 0; i < 2; i = b = i + 1) {
+    print(i, \"\")
+}
+println(a, b, c)";
+
+const OUTPUT: &str = "idx 1\nval 7\nval 3\n0 1 3 2 [0, 7]\n";
+
+fn sexpr(source: &str) -> String {
+    let ast = Parser::from_lexer(Lexer::new(source)).parse().unwrap();
+    to_sexpr(&ast, ast.root())
+}
+
+#[test]
+fn assignments_group_to_the_right_below_every_operator() {
+    assert_eq!(
+        sexpr("a = b = c ? 1 : 2\nx[i] = p.y = n += 1 + 2"),
+        "(program (= a (= b (? c 1 2))) ([]= x i (.y= p (+= n (+ 1 2)))))"
+    );
+    for (source, message) in [
+        ("1 = 2", "Expected a variable, element or field before '='"),
+        (
+            "a + b = 2",
+            "Expected a variable, element or field before '='",
+        ),
+        (
+            "f() = 2",
+            "Expected a variable, element or field before '='",
+        ),
+        ("a[0] += 1", "Expected a variable before '+='"),
+    ] {
+        let err = Parser::from_lexer(Lexer::new(source)).parse().unwrap_err();
+        assert_eq!(err[0].message(), message, "{}", source);
+    }
+    assert_eq!(
+        formatter::format("a   =  b=3\n(a) += 4\n").unwrap(),
+        "a = b = 3\n(a) += 4\n"
+    );
+}
+
+#[test]
+fn chained_assignments_store_the_same_value() {
+    for result in [osho_lang::interpret(PROGRAM), osho_lang::run_vm(PROGRAM)] {
+        assert_eq!(result.unwrap().stdout, OUTPUT);
+    }
+}
+
+#[test]
+fn compiled_chains_match_the_interpreter() {
+    if let Some(compiled) = common::run_both("assignment", PROGRAM, Checks::default()) {
+        assert_eq!(compiled.stdout, OUTPUT);
+    }
+
+    let js = osho_lang::compile_to_js(PROGRAM).unwrap();
+    assert!(
+        js.contains("for (let i = 0; i < 2; b = i + 1, i = b) {"),
+        "{}",
+        js
+    );
+}
+
+#[test]
+fn only_chains_use_an_assignment_as_a_value() {
+    let cases = [
+        (
+            "let mut a = 1\nprintln(a = 2)",
+            "An assignment isn't a value, except in a chain like 'a = b = 0'",
+        ),
+        (
+            "let mut a = 1\nlet b = a += 1",
+            "An assignment isn't a value, except in a chain like 'a = b = 0'",
+        ),
+        (
+            "let mut a = 1\nlet mut c = [1]\na = c[0] = 2",
+            "Only variables can be assigned in a chain of assignments",
+        ),
+        (
+            "let mut b = 1\nlet mut c = [1]\nc[0] = b = 2",
+            "Only variables can be assigned in a chain of assignments",
+        ),
+        (
+            "struct P { x }\nlet mut b = 1\nlet mut p = P { x: 1 }\np.x = b = 2",
+            "Only variables can be assigned in a chain of assignments",
+        ),
+        (
+            "let mut a = 1\nlet b = 2\na = b = 3",
+            "Cannot assign to immutable variable 'b', declare it with `let mut b`",
+        ),
+        (
+            "let mut a = 1\nlet mut b = 2.5\na = b = 3",
+            "Cannot assign float to int variable 'a'",
+        ),
+    ];
+    for (source, message) in cases {
+        let errors = osho_lang::parse_and_check(source).unwrap_err();
+        assert_eq!(errors[0].message(), message, "{}", source);
+    }
+}
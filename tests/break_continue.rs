@@ -0,0 +1,48 @@
+const PROGRAM: &str = "\
+let mut total = 0
+for (let mut i = 0; i < 10; i++) {
+    if i % 2 == 0 { continue }
+    if i > 7 { break }
+    let mut j = 0
+    while true {
+        j++
+        if j < 3 { continue }
+        break
+    }
+    total += i * j
+}
+println(total)";
+
+#[test]
+fn break_and_continue_affect_the_innermost_loop() {
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, "48\n");
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, "48\n");
+}
+
+#[test]
+fn break_and_continue_need_a_loop() {
+    let source = "\
+break
+if true { continue }
+while false {
+    if true { break }
+}";
+    let errors = osho_lang::parse_and_check(source).unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|err| err.message()).collect();
+    assert_eq!(
+        messages,
+        [
+            "'break' can only be used inside a loop",
+            "'continue' can only be used inside a loop"
+        ]
+    );
+}
+
+#[test]
+fn continue_runs_the_step_of_a_for_loop_in_c() {
+    let c = osho_lang::compile_to_c(PROGRAM).unwrap();
+    assert!(c.contains("goto osho_continue_1;"), "{}", c);
+    assert!(c.contains("osho_continue_1:;\ni = i + 1;"), "{}", c);
+    // The inner loop has no step, so C's own continue works
+    assert!(c.contains("continue;"), "{}", c);
+}
@@ -0,0 +1,67 @@
+use std::fs;
+
+use osho_lang::cache::{BuildCache, BuildKey};
+use osho_lang::{CCompiler, SourceMap};
+
+fn key(source: &str, cc: &CCompiler) -> BuildKey {
+    BuildKey::new()
+        .with_sources(&SourceMap::single("main.osho", source))
+        .with_compiler(cc)
+}
+
+#[test]
+fn keys_change_with_the_source() {
+    let cc = CCompiler::new("gcc");
+    assert_eq!(key("println(1)", &cc), key("println(1)", &cc));
+    assert_ne!(key("println(1)", &cc), key("println(2)", &cc));
+
+    // Parts are kept apart, so moving text between them changes the key
+    assert_ne!(
+        BuildKey::new().with("ab").with("c"),
+        BuildKey::new().with("a").with("bc")
+    );
+    assert_eq!(key("", &cc).to_string().len(), 16);
+}
+
+#[test]
+fn keys_change_with_the_compiler_and_its_flags() {
+    let gcc = CCompiler::new("gcc");
+    let optimized = gcc.clone().with_opt_level(2).unwrap();
+    let debug = gcc.clone().with_flags("-g");
+    let keys = [
+        key("println(1)", &gcc),
+        key("println(1)", &CCompiler::new("clang")),
+        key("println(1)", &optimized),
+        key("println(1)", &debug),
+    ];
+    for (i, a) in keys.iter().enumerate() {
+        for b in &keys[i + 1..] {
+            assert_ne!(a, b);
+        }
+    }
+}
+
+#[test]
+fn stored_builds_are_restored() {
+    let dir = std::env::temp_dir().join(format!("osho-cache-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let (c_file, executable) = (dir.join("program.c"), dir.join("program"));
+    fs::write(&c_file, "int main(void) { return 0; }").unwrap();
+    fs::write(&executable, "binary").unwrap();
+
+    let cache = BuildCache::new(dir.join("cache"));
+    let key = key("println(1)", &CCompiler::new("gcc"));
+    assert!(!cache.contains(key));
+    cache.store(key, &c_file, &executable).unwrap();
+    assert!(cache.contains(key));
+
+    let (restored_c, restored) = (dir.join("restored.c"), dir.join("restored"));
+    cache.restore(key, &restored_c, &restored).unwrap();
+    assert_eq!(
+        fs::read_to_string(restored_c).unwrap(),
+        "int main(void) { return 0; }"
+    );
+    assert_eq!(fs::read_to_string(restored).unwrap(), "binary");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,117 @@
+use osho_lang::lexer::{Kind, Lexer, TokenValue};
+use osho_lang::Checks;
+
+mod common;
+
+const PROGRAM: &str = "\
+let word = \"héllo\"
+let first = word[0]
+println(first, word[1], ord(first), chr(233) == word[1], \"${word[4]}!\")
+let letters = [word[0], '\\n', '\\'', chr(128512)]
+println(letters)
+let mut count = 0
+for i in 0..len(word) {
+    if word[i] >= 'a' && word[i] <= 'z' {
+        count = count + 1
+    }
+}
+println(count, ord('a') - ord('A'), chr(ord('a') + 1))
+println(word[5])";
+
+const OUTPUT: &str = "h é 104 true o!\n['h', '\\n', '\\'', '😀']\n4 32 b\n";
+
+#[test]
+fn character_literals_are_lexed() {
+    let tokens = Lexer::new("'a' '\\n' 'é'").get_tokens().unwrap();
+    let chars: Vec<_> = tokens
+        .iter()
+        .filter(|token| token.kind == Kind::Char)
+        .map(|token| token.value.clone())
+        .collect();
+    assert_eq!(
+        chars,
+        [
+            TokenValue::Char('a'),
+            TokenValue::Char('\n'),
+            TokenValue::Char('é')
+        ]
+    );
+    for (source, message) in [
+        ("let c = ''", "Empty character literal"),
+        (
+            "let c = 'ab'",
+            "Character literal 'ab' holds more than one character",
+        ),
+        ("let c = 'a", "Unterminated character literal"),
+    ] {
+        let errors = osho_lang::parse_and_check(source).unwrap_err();
+        assert_eq!(errors[0].message(), message, "{}", source);
+    }
+}
+
+#[test]
+fn strings_are_indexed_by_character() {
+    for result in [osho_lang::interpret(PROGRAM), osho_lang::run_vm(PROGRAM)] {
+        let err = result.unwrap_err();
+        assert_eq!(
+            err.message(),
+            "Index 5 is out of bounds for a string of length 5"
+        );
+        assert_eq!(err.exit_code(), 70);
+    }
+    let err = osho_lang::run_vm("println(chr(55296))").unwrap_err();
+    assert_eq!(err.message(), "chr(55296) is not a valid character");
+    assert_eq!(
+        osho_lang::interpret("let c: char = 'x'\nprintln(c, str(c), 'a' != 'b')")
+            .unwrap()
+            .stdout,
+        "x x true\n"
+    );
+}
+
+#[test]
+fn compiled_chars_match_the_interpreter() {
+    if let Some(compiled) = common::run_both("chars", PROGRAM, Checks::default()) {
+        assert_eq!(compiled.stdout, OUTPUT);
+        assert_eq!(
+            compiled.error.as_deref(),
+            Some("Index 5 is out of bounds for a string of length 5")
+        );
+    }
+
+    let c = osho_lang::compile_to_c("let s = \"ab\"\nprintln(ord(s[1]))").unwrap();
+    assert!(c.contains("osho_char_at(s, 1)"), "{}", c);
+    // A char is already its code point
+    assert!(c.contains("long _t2 = _t1;"), "{}", c);
+
+    let js = osho_lang::compile_to_js("let s = \"ab\"\nlet c = s[0]\nprintln(c, ord(c), chr(98))")
+        .unwrap();
+    assert!(js.contains("const c = oshoCharAt(s, 0);"), "{}", js);
+    assert!(
+        js.contains("console.log(String.fromCodePoint(c), c, String.fromCodePoint(oshoChr(98)));"),
+        "{}",
+        js
+    );
+}
+
+#[test]
+fn chars_are_checked() {
+    let cases = [
+        ("println(ord(1))", "ord() expects a char, found int"),
+        ("println(chr(\"a\"))", "chr() expects an int, found string"),
+        (
+            "let mut s = \"ab\"\ns[0] = 'c'",
+            "Cannot assign to a character of a string; strings can't be changed",
+        ),
+        (
+            "let s = \"ab\"\nprintln(s[true])",
+            "String index must be an int, found bool",
+        ),
+        ("println('a' == \"a\")", "Cannot compare char and string"),
+        ("let c: char = \"a\"", "Variable 'c' is char, found string"),
+    ];
+    for (source, message) in cases {
+        let errors = osho_lang::parse_and_check(source).unwrap_err();
+        assert_eq!(errors[0].message(), message, "{}", source);
+    }
+}
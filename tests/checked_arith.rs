@@ -0,0 +1,86 @@
+use osho_lang::{Backend, Checks};
+
+mod common;
+
+const CHECKED: Checks = Checks {
+    arith: true,
+    overflow: false,
+};
+
+#[test]
+fn unchecked_arithmetic_carries_on_with_inf_and_nan() {
+    let source = "let zero = 0.0\nlet inf = 1.0 / zero\nprintln(inf, inf - inf)";
+    assert_eq!(common::run(source, Checks::default()).unwrap(), "inf NaN\n");
+}
+
+#[test]
+fn checked_arithmetic_fails_at_the_operator() {
+    let err = common::run("let zero = 0.0\nprintln(1.5 / zero)", CHECKED).unwrap_err();
+    assert_eq!(err.message(), "Division by zero");
+    let span = err.span().unwrap();
+    assert_eq!((span.line, span.column), (2, 13));
+
+    let err = common::run("let mut x = 2.5\nx /= 0", CHECKED).unwrap_err();
+    assert_eq!(err.message(), "Division by zero");
+    let err = common::run("println(2.5 % 0.0)", CHECKED).unwrap_err();
+    assert_eq!(err.message(), "Modulo by zero");
+
+    let err = common::run(
+        "let inf = 10.0 ** 400.0\nprintln(inf)\nprintln(inf - inf)",
+        CHECKED,
+    )
+    .unwrap_err();
+    assert_eq!(err.message(), "The result of '-' is NaN");
+    assert_eq!(err.span().unwrap().line, 3);
+}
+
+#[test]
+fn compiled_programs_check_arithmetic_like_the_interpreter() {
+    let cases = [
+        ("float", "let zero = 0.0\nprintln(1)\nprintln(1.5 / zero)"),
+        ("int", "let zero = 0\nprintln(1)\nprintln(7 % zero)"),
+        (
+            "nan",
+            "let inf = 10.0 ** 400.0\nprintln(1)\nprintln(inf - inf)",
+        ),
+    ];
+    for (name, source) in cases {
+        if let Some(compiled) = common::run_both(name, source, CHECKED) {
+            assert_eq!((compiled.stdout.as_str(), compiled.exit_code), ("1\n", 70));
+        }
+    }
+}
+
+#[test]
+fn integer_division_by_zero_fails_without_the_checks() {
+    let source = "let mut zero = 0\nprintln(1)\nprintln(7 / zero)";
+    let err = common::run(source, Checks::default()).unwrap_err();
+    assert_eq!(err.message(), "Division by zero");
+    if let Some(compiled) = common::run_both("unchecked-int", source, Checks::default()) {
+        assert_eq!((compiled.stdout.as_str(), compiled.exit_code), ("1\n", 70));
+        assert_eq!(compiled.error.as_deref(), Some("Division by zero"));
+    }
+}
+
+#[test]
+fn checks_are_only_generated_when_asked_for() {
+    let source = "let x = 2.0\nprintln(1.0 / x)";
+    let c = osho_lang::compile_to_c(source).unwrap();
+    assert!(!c.contains("osho_arith_error"), "{}", c);
+
+    let ast = osho_lang::parse_and_check(source).unwrap();
+    let c = osho_lang::CodeGenerator::new()
+        .with_checks(CHECKED)
+        .generate(&ast)
+        .unwrap();
+    assert!(
+        c.contains("if (x == 0) osho_arith_error(\"Division by zero\");\n"),
+        "{}",
+        c
+    );
+    assert!(
+        c.contains("osho_arith_error(\"The result of '/' is NaN\");\n"),
+        "{}",
+        c
+    );
+}
@@ -0,0 +1,128 @@
+use osho_lang::{ast_printer, formatter, Lexer, Parser};
+
+const PROGRAM: &str = "\
+fn double(x) {
+    return x * 2
+}
+fn println_twice(s) {
+    println(s)
+    println(s)
+}
+let n = 10
+let add = |x| x + n
+println(add(5))
+let scale = |x: float| x * 1.5
+println(scale(2))
+let twice = |x: int| add(add(x))
+println(twice(1))
+let adder = |k: int| |x: int| x + k
+let add3 = adder(3)
+println(add3(4))
+let double = |x| x * 3
+println(double(2))
+let shout = |s| println_twice(s)
+shout(\"hi\")";
+
+const OUTPUT: &str = "15\n3\n21\n7\n6\nhi\nhi\n";
+
+#[test]
+fn lambdas_capture_the_variables_around_them() {
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, OUTPUT);
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, OUTPUT);
+}
+
+#[test]
+fn closures_keep_captured_values_after_their_scope_ends() {
+    let source = "\
+let make = |start: int| |step: int| start + step
+let mut total = 0
+{
+    let base = 100
+    let from_base = make(base)
+    total = from_base(1)
+}
+println(total)
+println(make)";
+    let output = "101\n<closure>\n";
+    assert_eq!(osho_lang::interpret(source).unwrap().stdout, output);
+    assert_eq!(osho_lang::run_vm(source).unwrap().stdout, output);
+}
+
+#[test]
+fn lambdas_parse_and_format() {
+    let source = "let f = |x,y:int|x+y*2";
+    let tokens = Lexer::new(source).get_tokens().unwrap();
+    let mut parser = Parser::new(&tokens);
+    let statements = parser.parse_statements().unwrap();
+    let ast = parser.into_ast();
+    assert_eq!(
+        ast_printer::to_sexpr(&ast, statements[0]),
+        "(let f (|x, y: int| (+ x (* y 2))))"
+    );
+    assert_eq!(
+        formatter::format(source).unwrap(),
+        "let f = |x, y: int| x + y * 2\n"
+    );
+    assert_eq!(formatter::format("let g=||  1").unwrap(), "let g = || 1\n");
+}
+
+#[test]
+fn lambdas_are_checked() {
+    let cases = [
+        (
+            "let mut n = 1\nlet f = |x| x + n",
+            "Closures can only capture immutable variables, but 'n' is declared with `let mut`",
+        ),
+        (
+            "let fs = [|x| x]",
+            "Cannot infer the types of this lambda's parameters, annotate them like `|x: int|`",
+        ),
+        (
+            "let f = |x| x\nlet g = f",
+            "Cannot use 'f' before a call decides the types of its parameters, annotate them like `|x: int|`",
+        ),
+        ("let f = 1\nf(2)", "'f' is int, not a function"),
+        (
+            "let f = |x| x + 1\nprintln(f(1))\nprintln(f(1.5))",
+            "Argument 1 of f() must be int, found float",
+        ),
+        (
+            "let f = |x: int, y: int| x + y\nprintln(f(1))",
+            "f() takes 2 arguments but 1 was given",
+        ),
+        (
+            "let f = |x, x| x",
+            "Parameter 'x' is declared twice in a lambda",
+        ),
+        (
+            "let f = |x: bool| x + 1",
+            "Cannot add bool and int",
+        ),
+    ];
+    for (source, message) in cases {
+        let errors = osho_lang::parse_and_check(source).unwrap_err();
+        assert_eq!(errors[0].message(), message, "{}", source);
+    }
+}
+
+#[test]
+fn compiled_targets_point_at_the_closure_they_cannot_express() {
+    let c = osho_lang::compile_to_c("let n = 2\nlet f = |x| x * n\nprintln(f(1))").unwrap_err();
+    assert_eq!(
+        c.message(),
+        "Closures capturing 'n' are only supported by `osho run` for now, not by compiled targets"
+    );
+    let span = c.span().unwrap();
+    assert_eq!((span.line, span.column), (2, 9));
+    // One that captures nothing is a plain function
+    let c = osho_lang::compile_to_c("let f = |x: int| x\nprintln(f(1))").unwrap();
+    assert!(
+        c.contains("static long osho_fn_lambda__1__9(long x)"),
+        "{}",
+        c
+    );
+    let js = osho_lang::compile_to_js("let f = |x: int| x\nprintln(f(1))").unwrap_err();
+    assert_eq!(js.message(), "The JS target doesn't support lambdas yet");
+    let wasm = osho_lang::compile_to_wasm("let f = |x: int| x\nprintln(f(1))").unwrap_err();
+    assert_eq!(wasm.message(), "The wasm target doesn't support lambdas");
+}
@@ -0,0 +1,49 @@
+//! Helpers shared by the integration tests
+
+// Each test crate uses only some of them
+#![allow(dead_code)]
+
+use std::fs;
+
+use osho_lang::interpreter::DEFAULT_MAX_CALL_DEPTH;
+use osho_lang::verify::{self, Run};
+use osho_lang::{bytecode, CCompiler, Checks, CompileError, Interpreter, Vm};
+
+/// The C compiler to check compiled programs with, or `None` after noting
+/// why those checks are skipped
+pub fn c_compiler() -> Option<CCompiler> {
+    CCompiler::detect(None)
+        .map_err(|err| eprintln!("note: not checking executables: {}", err))
+        .ok()
+}
+
+/// Run a program with the interpreter and then the VM, returning the first
+/// one's output or error, after checking the VM agrees
+pub fn run(source: &str, checks: Checks) -> Result<String, CompileError> {
+    let ast = osho_lang::parse_and_check(source).expect("program should compile");
+    let mut interpreter = Interpreter::with_output(Vec::new()).with_checks(checks);
+    let interpreted = interpreter
+        .run(&ast, ast.root())
+        .map(|()| String::from_utf8(interpreter.into_output()).unwrap());
+    let chunk = bytecode::Compiler::new().compile(&ast).unwrap();
+    let mut vm = Vm::with_output(Vec::new()).with_checks(checks);
+    let run = vm
+        .run(&chunk)
+        .map(|()| String::from_utf8(vm.into_output()).unwrap());
+    assert_eq!(run, interpreted);
+    interpreted
+}
+
+/// Run a program interpreted and compiled with `checks`, checking they agree
+/// and returning the compiled run, or `None` without a C compiler
+pub fn run_both(name: &str, source: &str, checks: Checks) -> Option<Run> {
+    let cc = c_compiler()?;
+    let ast = osho_lang::parse_and_check(source).expect("program should compile");
+    let interpreted = verify::interpret(&ast, b"", DEFAULT_MAX_CALL_DEPTH, checks);
+    let dir = std::env::temp_dir().join(format!("osho-{}-{}", name, std::process::id()));
+    let compiled = verify::run_compiled(&ast, &cc, checks, &dir, b"");
+    fs::remove_dir_all(&dir).unwrap();
+    let compiled = compiled.unwrap_or_else(|err| panic!("{}: {}", name, err));
+    verify::compare(&interpreted, &compiled).unwrap_or_else(|err| panic!("{}: {}", name, err));
+    Some(compiled)
+}
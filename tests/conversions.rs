@@ -0,0 +1,99 @@
+use osho_lang::Checks;
+
+mod common;
+
+const PROGRAM: &str = "\
+let n = 7
+let x = 2.9
+println(int(x), int(-x), int(true), float(n) / 2, float(false))
+println(str(n), str(x), str(true), str(0.1 + 0.2), str(\"text\"))
+let total = parse_num(\"2.5\") + parse_num(\"-3\")
+println(total, parse_num(\"1e3\"), int(parse_num(\"12\")) * 2)
+println(parse_num(\"12abc\"))";
+
+const OUTPUT: &str = "2 -2 1 3.5 0\n7 2.9 true 0.30000000000000004 text\n-0.5 1000 24\n";
+
+#[test]
+fn values_are_converted() {
+    for result in [osho_lang::interpret(PROGRAM), osho_lang::run_vm(PROGRAM)] {
+        let err = result.unwrap_err();
+        assert_eq!(err.message(), "parse_num() can't parse '12abc' as a number");
+        assert_eq!(err.exit_code(), 70);
+    }
+    let source = "let a = [1, 2]\nprintln(\"${str(a)}!\", str(pow(2, 3)))";
+    assert_eq!(osho_lang::interpret(source).unwrap().stdout, "[1, 2]! 8\n");
+    for (source, message) in [
+        (
+            "println(int(pow(10, 19)))",
+            "int() can't convert 10000000000000000000 to an int",
+        ),
+        (
+            "println(int(sqrt(-1)))",
+            "int() can't convert NaN to an int",
+        ),
+        (
+            "println(parse_num(\" 1\"))",
+            "parse_num() can't parse ' 1' as a number",
+        ),
+    ] {
+        let err = osho_lang::run_vm(source).unwrap_err();
+        assert_eq!(err.message(), message, "{}", source);
+    }
+}
+
+#[test]
+fn compiled_conversions_match_the_interpreter() {
+    if let Some(compiled) = common::run_both("conversions", PROGRAM, Checks::default()) {
+        assert_eq!(compiled.stdout, OUTPUT);
+        assert_eq!(
+            compiled.error.as_deref(),
+            Some("parse_num() can't parse '12abc' as a number")
+        );
+    }
+
+    let c = osho_lang::compile_to_c("let b = true\nprintln(int(b), str(b))").unwrap();
+    assert!(c.contains("(long)b"), "{}", c);
+    assert!(!c.contains("osho_parse_num"), "{}", c);
+    let err = osho_lang::compile_to_c("println(str([1]))").unwrap_err();
+    assert_eq!(
+        err.message(),
+        "Converting [int] with str() is only supported by `osho run` for now, not by compiled targets"
+    );
+    let span = err.span().unwrap();
+    assert_eq!((span.line, span.column), (1, 9));
+
+    let js = osho_lang::compile_to_js("let x = 1.5\nprintln(int(x), str([x]), parse_num(\"2\"))")
+        .unwrap();
+    assert!(
+        js.contains("console.log(oshoInt(x), String(oshoShow([x])), oshoParseNum(\"2\"));"),
+        "{}",
+        js
+    );
+}
+
+#[test]
+fn conversions_are_checked() {
+    let cases = [
+        (
+            "println(int(\"3\"))",
+            "int() expects a number or a bool, found string; parse_num() reads a number from a string",
+        ),
+        (
+            "println(float([1]))",
+            "float() expects a number or a bool, found [int]",
+        ),
+        (
+            "println(parse_num(3))",
+            "parse_num() expects a string, found int",
+        ),
+        ("println(str())", "str() takes 1 argument but 0 were given"),
+        (
+            "let n: int = parse_num(\"3\")",
+            "Variable 'n' is int, found float",
+        ),
+    ];
+    for (source, message) in cases {
+        let errors = osho_lang::parse_and_check(source).unwrap_err();
+        assert_eq!(errors[0].message(), message, "{}", source);
+    }
+}
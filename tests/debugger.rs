@@ -0,0 +1,75 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use osho_lang::debugger::{Debugger, STOPPED};
+use osho_lang::{Interpreter, Lexer, Parser};
+
+const PROGRAM: &str = "\
+fn square(n: int) -> int {
+    let r = n * n
+    return r
+}
+let x = 3
+let y = square(x)
+print(x + y)";
+
+/// Output shared with the test after the interpreter takes the debugger
+#[derive(Clone, Default)]
+struct Shared(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Run the program under the debugger with scripted commands, returning the
+/// error message if it stopped early and what the debugger wrote
+fn debug(commands: &'static str) -> (Option<String>, String) {
+    let tokens = Lexer::new(PROGRAM).get_tokens().unwrap();
+    let ast = Parser::new(&tokens).parse().unwrap();
+    let out = Shared::default();
+    let debugger = Debugger::new(PROGRAM, commands.as_bytes(), out.clone());
+    let result = Interpreter::new().with_hook(debugger).run(&ast, ast.root());
+    let written = String::from_utf8(out.0.borrow().clone()).unwrap();
+    (result.err().map(|err| err.message().to_string()), written)
+}
+
+#[test]
+fn breakpoints_stop_by_line_and_show_variables() {
+    let (error, out) = debug("break 6\ncontinue\nvars\ncontinue\n");
+    assert_eq!(error, None);
+    assert_eq!(
+        out,
+        "line 1: fn square(n: int) -> int {\n\
+         (osho) Breakpoint at line 6\n\
+         (osho) line 6: let y = square(x)\n\
+         (osho) x = 3\n\
+         (osho) "
+    );
+}
+
+#[test]
+fn step_enters_calls_and_next_steps_over_them() {
+    let (_, out) = debug("n\nn\ns\np n\nq\n");
+    assert!(out.contains("line 6: let y = square(x)\n(osho) line 2: let r = n * n"));
+    assert!(out.contains("n = 3"));
+
+    let (_, out) = debug("n\nn\nn\nq\n");
+    assert!(out.contains("line 6: let y = square(x)\n(osho) line 7: print(x + y)"));
+    assert!(!out.contains("line 2"));
+}
+
+#[test]
+fn quitting_stops_the_program_and_bad_commands_are_reported() {
+    let (error, out) = debug("print z\nbreak\nfly\nquit\n");
+    assert_eq!(error.as_deref(), Some(STOPPED));
+    assert!(out.contains("error: No variable 'z' here"));
+    assert!(out.contains("error: 'break' needs a line number"));
+    assert!(out.contains("error: Unknown command 'fly', try 'help'"));
+}
@@ -0,0 +1,49 @@
+use osho_lang::ast_printer::to_sexpr;
+use osho_lang::desugar;
+
+const PROGRAM: &str = "\
+fn halve(n: int) -> int {
+    let mut m = n
+    m /= 2
+    return m
+}
+let mut total = 0.5
+for (let mut i = 0; i < 3; i++) {
+    total += i
+}
+let mut count = 10
+for j in 0..=2 {
+    count--
+    if j == 1 {
+        continue
+    }
+    count -= halve(j + 4)
+}
+println(total, count, \"${count}!\")";
+
+#[test]
+fn compound_assignments_and_increments_become_assignments() {
+    let mut ast = osho_lang::parse_and_check(PROGRAM).unwrap();
+    desugar::desugar(&mut ast);
+    assert_eq!(
+        to_sexpr(&ast, ast.root()),
+        "(program (fn halve(n: int) -> int (block (let mut m n) (= m (/ m 2)) (return m))) (let mut total 0.5) (for (let mut i 0) (< i 3) (= i (+ i 1)) (block (= total (+ total i)))) (let mut count 10) (for j in (..= 0 2) (block (= count (- count 1)) (if (== j 1) (block (continue))) (= count (- count (call halve (+ j 4)))))) (println total count (interpolate \"{}!\" count)))"
+    );
+    // The copy the backends take leaves the program as written
+    let ast = osho_lang::parse_and_check(PROGRAM).unwrap();
+    let copy = desugar::desugared(&ast);
+    assert_ne!(to_sexpr(&ast, ast.root()), to_sexpr(&copy, copy.root()));
+}
+
+#[test]
+fn desugared_programs_run_the_same_on_every_backend() {
+    let expected = "3.5 2 2!\n";
+    for result in [osho_lang::interpret(PROGRAM), osho_lang::run_vm(PROGRAM)] {
+        assert_eq!(result.unwrap().stdout, expected);
+    }
+    let c = osho_lang::compile_to_c("let mut x = 1\nx += 2\nx++\nprintln(x)").unwrap();
+    // Still computed straight into the variable
+    assert!(c.contains("x = x + 2;\nx = x + 1;\n"), "{}", c);
+    let js = osho_lang::compile_to_js("let mut x = 7\nx /= 2\nprintln(x)").unwrap();
+    assert!(js.contains("x = Math.trunc(x / 2);"), "{}", js);
+}
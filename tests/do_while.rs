@@ -0,0 +1,60 @@
+use osho_lang::{ast_printer, formatter, Checks, Lexer, Parser};
+
+mod common;
+
+const PROGRAM: &str = "\
+let mut n = 0
+do {
+    n++
+} while n > 100
+println(n)
+let mut digits = 0
+let mut x = 40321
+do {
+    x /= 10
+    digits++
+    if digits == 2 { continue }
+    if digits > 7 { break }
+} while x != 0
+println(digits)";
+
+#[test]
+fn the_body_runs_before_the_condition_is_checked() {
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, "1\n5\n");
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, "1\n5\n");
+}
+
+#[test]
+fn compiled_do_while_loops_agree_with_the_interpreter() {
+    common::run_both("do-while", PROGRAM, Checks::default());
+
+    let js = osho_lang::compile_to_js(PROGRAM).unwrap();
+    assert!(js.contains("} while (n > 100);"), "{}", js);
+    let wat = osho_lang::compile_to_wasm("let mut n = 0\ndo { n++ } while n < 3").unwrap();
+    assert!(wat.contains("(br_if $continue_1"), "{}", wat);
+}
+
+#[test]
+fn do_while_parses_and_formats() {
+    let source = "do{n++}while n<3";
+    let tokens = Lexer::new(source).get_tokens().unwrap();
+    let mut parser = Parser::new(&tokens);
+    let statements = parser.parse_statements().unwrap();
+    let ast = parser.into_ast();
+    assert_eq!(
+        ast_printer::to_sexpr(&ast, statements[0]),
+        "(do-while (block (++ n)) (< n 3))"
+    );
+    assert_eq!(
+        formatter::format(source).unwrap(),
+        "do {\n    n++\n} while n < 3\n"
+    );
+
+    let errors = osho_lang::parse_and_check("let mut n = 0\ndo { n++ } n < 3").unwrap_err();
+    assert_eq!(
+        errors[0].message(),
+        "Expected 'while' after the body of 'do'"
+    );
+    let errors = osho_lang::parse_and_check("do { let y = 1 } while y < 3").unwrap_err();
+    assert_eq!(errors[0].message(), "Variable 'y' is not declared");
+}
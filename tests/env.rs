@@ -0,0 +1,62 @@
+use osho_lang::Checks;
+
+mod common;
+
+const PROGRAM: &str = "\
+let greeting = env(\"OSHO_TEST_GREETING\")
+println(greeting, \"[${env(\"OSHO_TEST_UNSET\")}]\")
+println(env(\"OSHO_TEST_UNSET\", \"fallback\"), env(\"OSHO_TEST_GREETING\", \"fallback\"))";
+
+const OUTPUT: &str = "hi 100%  []\nfallback hi 100% \n";
+
+fn set_variables() {
+    std::env::set_var("OSHO_TEST_GREETING", "hi 100% ");
+    std::env::remove_var("OSHO_TEST_UNSET");
+}
+
+#[test]
+fn env_reads_environment_variables() {
+    set_variables();
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, OUTPUT);
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, OUTPUT);
+}
+
+#[test]
+fn compiled_env_calls_getenv() {
+    set_variables();
+    if let Some(compiled) = common::run_both("env", PROGRAM, Checks::default()) {
+        assert_eq!(compiled.stdout, OUTPUT);
+    }
+
+    let c = osho_lang::compile_to_c(PROGRAM).unwrap();
+    assert!(c.contains("const char *_t2 = osho_env(_t0, _t1);"), "{}", c);
+    let js = osho_lang::compile_to_js("println(env(\"HOME\"))").unwrap();
+    assert!(
+        js.contains("console.log(process.env[\"HOME\"] ?? \"\");"),
+        "{}",
+        js
+    );
+}
+
+#[test]
+fn env_is_checked() {
+    let cases = [
+        ("println(env(1))", "env() expects a string, found int"),
+        (
+            "println(env(\"A\", 2))",
+            "env() expects a string, found int",
+        ),
+        (
+            "println(env())",
+            "env() takes 1 or 2 arguments but 0 were given",
+        ),
+        (
+            "let n: int = env(\"HOME\")",
+            "Variable 'n' is int, found string",
+        ),
+    ];
+    for (source, message) in cases {
+        let errors = osho_lang::parse_and_check(source).unwrap_err();
+        assert_eq!(errors[0].message(), message, "{}", source);
+    }
+}
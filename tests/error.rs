@@ -0,0 +1,103 @@
+use osho_lang::{Checks, CompileError, Diagnostic};
+
+mod common;
+
+const PROGRAM: &str = "\
+fn check(n: int) -> int {
+    if n < 0 {
+        error(\"${n} is negative\")
+    }
+    return n
+}
+fn total(a: int, b: int) -> int {
+    return check(a) + check(b)
+}
+println(total(1, 2))
+println(total(3, -4))
+println(\"unreachable\")";
+
+#[test]
+fn errors_stop_the_program_with_their_message() {
+    let err = osho_lang::interpret(PROGRAM).unwrap_err();
+    assert_eq!(err.message(), "-4 is negative");
+    assert_eq!(err.exit_code(), 70);
+    let err = osho_lang::run_vm(PROGRAM).unwrap_err();
+    assert_eq!(err.message(), "-4 is negative");
+    assert_eq!(err.span().map(|span| span.line), Some(3));
+
+    let err = osho_lang::interpret("let x = 1\nerror(\"bad\")\nprintln(x)").unwrap_err();
+    assert_eq!(err.message(), "bad");
+}
+
+#[test]
+fn runtime_errors_carry_the_calls_they_unwound_through() {
+    let err = osho_lang::interpret(PROGRAM).unwrap_err();
+    let CompileError::Runtime(runtime) = &err else {
+        panic!("expected a runtime error, found {:?}", err);
+    };
+    assert_eq!((runtime.span.line, runtime.span.column), (3, 9));
+    let frames: Vec<_> = runtime.stack.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        frames,
+        [
+            "in check() called at line 8, column 23",
+            "in total() called at line 11, column 9",
+        ]
+    );
+    assert_eq!(
+        Diagnostic::from(&err).notes,
+        [
+            "in check() called at line 8, column 23",
+            "in total() called at line 11, column 9",
+            "the error happened while interpreting the program",
+        ]
+    );
+
+    let err = osho_lang::interpret("let f = |x: int| x / 0\nprintln(f(1))").unwrap_err();
+    let CompileError::Runtime(runtime) = &err else {
+        panic!("expected a runtime error, found {:?}", err);
+    };
+    assert_eq!(
+        runtime.stack[0].to_string(),
+        "in a lambda called at line 2, column 9"
+    );
+}
+
+#[test]
+fn compiled_errors_print_to_stderr_and_exit() {
+    if let Some(compiled) = common::run_both("error", PROGRAM, Checks::default()) {
+        assert_eq!(compiled.stdout, "3\n");
+        assert_eq!(compiled.exit_code, 70);
+        assert_eq!(compiled.error.as_deref(), Some("-4 is negative (line 3)"));
+    }
+
+    let js = osho_lang::compile_to_js(PROGRAM).unwrap();
+    assert!(
+        js.contains("throw new Error(`${n} is negative` + \" (line 3)\");"),
+        "{}",
+        js
+    );
+    let wat = osho_lang::compile_to_wasm("let x = 1\nif x > 0 {\n    error(\"no\")\n}").unwrap();
+    assert!(wat.contains("(then\n        unreachable))"), "{}", wat);
+
+    let c = osho_lang::compile_to_c("let a = [1]\nerror(\"bad ${a}\")").unwrap_err();
+    assert_eq!(
+        c.message(),
+        "Showing [int] in an error() message is only supported by `osho run` for now, not by compiled targets"
+    );
+    let span = c.span().unwrap();
+    assert_eq!((span.line, span.column), (2, 14));
+}
+
+#[test]
+fn errors_are_checked() {
+    let cases = [
+        ("error(1)", "error() expects a string, found int"),
+        ("error()", "error() takes 1 argument but 0 were given"),
+        ("let x = error(\"no\")", "error() doesn't return a value"),
+    ];
+    for (source, message) in cases {
+        let errors = osho_lang::parse_and_check(source).unwrap_err();
+        assert_eq!(errors[0].message(), message, "{}", source);
+    }
+}
@@ -0,0 +1,68 @@
+use osho_lang::{Checks, CompileError};
+
+mod common;
+
+const PROGRAM: &str = "\
+fn check(n: int) {
+    for (let mut i = 0; i < n; i++) {
+        if (i == 3) {
+            exit(i + 4)
+        }
+        println(i)
+    }
+}
+check(10)
+println(\"unreachable\")";
+
+#[test]
+fn exit_stops_both_interpreters_with_its_code() {
+    let interpreted = osho_lang::interpret(PROGRAM).unwrap();
+    assert_eq!(interpreted.stdout, "0\n1\n2\n");
+    assert_eq!(interpreted.exit_code, 7);
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap(), interpreted);
+    assert_eq!(osho_lang::interpret("println(1)").unwrap().exit_code, 0);
+}
+
+#[test]
+fn exit_codes_are_checked() {
+    let errors = osho_lang::parse_and_check("exit(1.5)\nlet x = exit(0)").unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|err| err.message()).collect();
+    assert_eq!(
+        messages,
+        [
+            "exit() expects an int, found float",
+            "exit() doesn't return a value"
+        ]
+    );
+    let err = osho_lang::interpret("exit(256)").unwrap_err();
+    assert_eq!(
+        err.message(),
+        "exit() code must be from 0 to 255, found 256"
+    );
+    assert_eq!(err.exit_code(), 70);
+    let err = osho_lang::run_vm("exit(-1)").unwrap_err();
+    assert!(matches!(err, CompileError::Runtime(_)), "{:?}", err);
+}
+
+#[test]
+fn compiled_programs_exit_with_the_same_codes() {
+    let cases = [
+        ("exit", PROGRAM, 7),
+        ("bounds", "let a = [1, 2]\nprintln(a[2])", 70),
+        ("range", "println(1)\nexit(300)", 70),
+    ];
+    for (name, source, code) in cases {
+        if let Some(compiled) = common::run_both(name, source, Checks::default()) {
+            assert_eq!(compiled.exit_code, code, "{}", name);
+        }
+    }
+}
+
+#[test]
+fn exit_is_a_c_helper_and_process_exit_in_js() {
+    let c = osho_lang::compile_to_c(PROGRAM).unwrap();
+    assert!(c.contains("static void osho_exit(long code)"), "{}", c);
+    assert!(c.contains("#define OSHO_RUNTIME_ERROR 70\n"), "{}", c);
+    let js = osho_lang::compile_to_js(PROGRAM).unwrap();
+    assert!(js.contains("process.exit("), "{}", js);
+}
@@ -0,0 +1,112 @@
+use std::fs;
+use std::path::Path;
+
+use osho_lang::Checks;
+
+mod common;
+
+/// A program that writes, appends to and reads back `path`, then reads a
+/// file that doesn't exist
+fn program(dir: &Path) -> String {
+    let path = dir.join("notes.txt");
+    let missing = dir.join("missing.txt");
+    format!(
+        "\
+let path = \"{}\"
+write_file(path, \"first\\n\")
+append_file(path, \"second\\n\")
+print(read_file(path))
+write_file(path, \"replaced\")
+println(read_file(path))
+println(read_file(\"{}\"))",
+        path.display(),
+        missing.display()
+    )
+}
+
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("osho-files-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn files_are_written_appended_to_and_read() {
+    let dir = temp_dir("interpret");
+    let source = program(&dir);
+    let missing = format!(
+        "Cannot read '{}': No such file or directory",
+        dir.join("missing.txt").display()
+    );
+    for result in [osho_lang::interpret(&source), osho_lang::run_vm(&source)] {
+        let err = result.unwrap_err();
+        assert_eq!(err.message(), missing);
+        assert_eq!(err.exit_code(), 70);
+    }
+    let contents = fs::read_to_string(dir.join("notes.txt")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(contents, "replaced");
+
+    let err = osho_lang::interpret("write_file(\"/\", \"x\")").unwrap_err();
+    assert!(
+        err.message().starts_with("Cannot write '/': "),
+        "{}",
+        err.message()
+    );
+}
+
+#[test]
+fn compiled_programs_use_the_file_runtime() {
+    let dir = temp_dir("compiled");
+    let source = program(&dir);
+    let compiled = common::run_both("files", &source, Checks::default());
+    let missing = dir.join("missing.txt");
+    fs::remove_dir_all(&dir).unwrap();
+    if let Some(compiled) = compiled {
+        assert_eq!(compiled.stdout, "first\nsecond\nreplaced\n");
+        assert_eq!(
+            compiled.error,
+            Some(format!(
+                "Cannot read '{}': No such file or directory",
+                missing.display()
+            ))
+        );
+    }
+
+    let c = osho_lang::compile_to_c("append_file(\"log.txt\", \"line\\n\")").unwrap();
+    assert!(c.contains("osho_append_file(_t0, _t1);"), "{}", c);
+    let js = osho_lang::compile_to_js("write_file(\"out.txt\", read_file(\"in.txt\"))").unwrap();
+    assert!(
+        js.contains(
+            "require(\"fs\").writeFileSync(\"out.txt\", require(\"fs\").readFileSync(\"in.txt\", \"utf8\"));"
+        ),
+        "{}",
+        js
+    );
+}
+
+#[test]
+fn file_builtins_are_checked() {
+    let cases = [
+        (
+            "println(read_file(1))",
+            "read_file() expects a string, found int",
+        ),
+        (
+            "write_file(\"a.txt\", 2)",
+            "write_file() expects a string, found int",
+        ),
+        (
+            "append_file(\"a.txt\")",
+            "append_file() takes 2 arguments but 1 was given",
+        ),
+        (
+            "let n = write_file(\"a.txt\", \"x\")",
+            "write_file() doesn't return a value",
+        ),
+    ];
+    for (source, message) in cases {
+        let errors = osho_lang::parse_and_check(source).unwrap_err();
+        assert_eq!(errors[0].message(), message, "{}", source);
+    }
+}
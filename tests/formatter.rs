@@ -0,0 +1,66 @@
+use std::fs;
+
+use osho_lang::{ast_printer, formatter, Lexer, Parser};
+
+fn sexpr(source: &str) -> String {
+    let tokens = Lexer::new(source).get_tokens().unwrap();
+    let mut parser = Parser::new(&tokens);
+    let statements = parser.parse_statements().unwrap();
+    let ast = parser.into_ast();
+    statements
+        .iter()
+        .map(|&statement| ast_printer::to_sexpr(&ast, statement))
+        .collect()
+}
+
+#[test]
+fn messy_code_gets_the_canonical_layout() {
+    let source = "// header\n\n\n\
+        let   mut x=1+ 2*(3 -4)   // trailing\n\
+        fn add(a:int,b:int)->int{return a+b}\n\
+        if x>1{\n\
+        println(\"big\")   /* why */\n\
+        } else if x<0 { }\n\
+        else{\n\
+        // only a comment\n\
+        }\n\
+        struct P{x,y,}\n\
+        let p=P{x:1,y:- -2}\n\
+        for(let mut i=0;i<3;i++){x+=i}\n";
+    let expected = "// header\n\n\
+        let mut x = 1 + 2 * (3 - 4) // trailing\n\
+        fn add(a: int, b: int) -> int {\n    return a + b\n}\n\
+        if x > 1 {\n    println(\"big\") /* why */\n} else if x < 0 {} else {\n    // only a comment\n}\n\
+        struct P { x, y }\n\
+        let p = P { x: 1, y: - -2 }\n\
+        for (let mut i = 0; i < 3; i++) {\n    x += i\n}\n";
+    assert_eq!(formatter::format(source).unwrap(), expected);
+}
+
+#[test]
+fn formatting_keeps_the_program_and_is_stable() {
+    let mut paths: Vec<_> = fs::read_dir("examples")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "osho"))
+        .collect();
+    paths.push("src/std/math.osho".into());
+    paths.push("src/std/list.osho".into());
+    for path in paths {
+        let source = fs::read_to_string(&path).unwrap();
+        let formatted = formatter::format(&source).unwrap();
+        assert_eq!(sexpr(&formatted), sexpr(&source), "{}", path.display());
+        assert_eq!(
+            formatter::format(&formatted).unwrap(),
+            formatted,
+            "{}",
+            path.display()
+        );
+    }
+}
+
+#[test]
+fn syntax_errors_stop_formatting() {
+    let errors = formatter::format("let x = \nlet y = 2").unwrap_err();
+    assert_eq!(errors[0].message(), "Expected expression");
+}
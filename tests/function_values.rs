@@ -0,0 +1,147 @@
+use osho_lang::{ast_printer, formatter, Checks, Lexer, Parser};
+
+mod common;
+
+const PROGRAM: &str = "\
+fn double(x: int) -> int {
+    return x * 2
+}
+fn twice(f: fn(int) -> int, x: int) -> int {
+    return f(f(x))
+}
+fn pick(big: bool) -> fn(int) -> int {
+    if big {
+        return double
+    }
+    return |x: int| x + 1
+}
+let g = double
+println(g(4))
+println(apply(double, 5))
+println(twice(double, 3))
+println(map(double, [1, 2, 3]))
+println(map(|x| x + 1, [1, 2]))
+println(map(|x: float| x / 2, [1, 3]))
+println(apply(|a, b| a * b, 6, 7))
+let inc = pick(false)
+println(inc(1), apply(pick(true), 4))
+let fs = [double, |x: int| x * 10]
+let tenfold = fs[1]
+println(tenfold(2), map(fs[0], [7]))";
+
+const OUTPUT: &str = "8\n10\n12\n[2, 4, 6]\n[2, 3]\n[0.5, 1.5]\n42\n2 8\n20 [14]\n";
+
+#[test]
+fn functions_are_passed_as_values() {
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, OUTPUT);
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, OUTPUT);
+
+    let source = "\
+fn shout(n: int) {
+    println(n)
+}
+apply(shout, 1)
+let s = shout
+s(2)
+println(s)";
+    let output = "1\n2\n<fn shout>\n";
+    assert_eq!(osho_lang::interpret(source).unwrap().stdout, output);
+    assert_eq!(osho_lang::run_vm(source).unwrap().stdout, output);
+}
+
+#[test]
+fn compiled_function_values_are_function_pointers() {
+    common::run_both("function-values", PROGRAM, Checks::default());
+
+    let c = osho_lang::compile_to_c(PROGRAM).unwrap();
+    assert!(
+        c.contains("typedef long (*osho_func_int_to_int)(long);\n"),
+        "{}",
+        c
+    );
+    assert!(
+        c.contains("static long osho_fn_twice(osho_func_int_to_int f, long x)"),
+        "{}",
+        c
+    );
+    assert!(
+        c.contains("static long osho_fn_lambda__11__12(long x)"),
+        "{}",
+        c
+    );
+
+    let printed =
+        osho_lang::compile_to_c("fn one() -> int {\n    return 1\n}\nprintln(one)").unwrap_err();
+    assert_eq!(
+        printed.message(),
+        "Printing functions is only supported by `osho run` for now, not by compiled targets"
+    );
+    let span = printed.span().unwrap();
+    assert_eq!((span.line, span.column), (4, 9));
+    let js =
+        osho_lang::compile_to_js("fn one() -> int {\n    return 1\n}\nlet f = one").unwrap_err();
+    assert_eq!(
+        js.message(),
+        "The JS target doesn't support function values yet"
+    );
+}
+
+#[test]
+fn function_types_parse_and_format() {
+    let source = "fn twice(f:fn(int)->int,x:int)->int{return f(f(x))}\nlet g:fn()->bool=||true";
+    let tokens = Lexer::new(source).get_tokens().unwrap();
+    let mut parser = Parser::new(&tokens);
+    let statements = parser.parse_statements().unwrap();
+    let ast = parser.into_ast();
+    assert_eq!(
+        ast_printer::to_sexpr(&ast, statements[1]),
+        "(let g: fn() -> bool (|| true))"
+    );
+    assert_eq!(
+        formatter::format(source).unwrap(),
+        "fn twice(f: fn(int) -> int, x: int) -> int {\n    return f(f(x))\n}\nlet g: fn() -> bool = || true\n"
+    );
+}
+
+#[test]
+fn function_values_are_checked() {
+    let double = "fn double(x: int) -> int {\n    return x * 2\n}\n";
+    let cases = [
+        (
+            "fn f(x) {\n    return x\n}\nlet g = f".to_string(),
+            "Cannot use f() as a value before a call decides the types of its parameters, annotate them like `fn f(x: int)`",
+        ),
+        (
+            format!("{}println(apply(double, 1.5))", double),
+            "apply() passes float to a parameter of type int",
+        ),
+        (
+            format!("{}println(apply(double))", double),
+            "apply() passes 0 arguments to a function that takes 1",
+        ),
+        (
+            "println(apply(1, 2))".to_string(),
+            "apply() expects a function, found int",
+        ),
+        (
+            format!("{}println(map(double, 3))", double),
+            "map() expects an array, found int",
+        ),
+        (
+            "println(map(|x: bool| x, [1]))".to_string(),
+            "map() expects a function taking int, found fn(bool) -> bool",
+        ),
+        (
+            "fn show(n: int) {\n    println(n)\n}\nprintln(map(show, [1]))".to_string(),
+            "map() expects a function that returns a value, found fn(int)",
+        ),
+        (
+            format!("{}let f: fn(float) -> int = double", double),
+            "Variable 'f' is fn(float) -> int, found fn(int) -> int",
+        ),
+    ];
+    for (source, message) in cases {
+        let errors = osho_lang::parse_and_check(&source).unwrap_err();
+        assert_eq!(errors[0].message(), message, "{}", source);
+    }
+}
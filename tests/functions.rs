@@ -0,0 +1,92 @@
+const PROGRAM: &str = "\
+fn fib(n) {
+    if n < 2 {
+        return n
+    }
+    return fib(n - 1) + fib(n - 2)
+}
+fn first_over(xs, limit) {
+    for (let mut i = 0; i < len(xs); i++) {
+        if xs[i] > limit {
+            return xs[i]
+        }
+    }
+    return -1
+}
+fn show(x) {
+    if x < 0 {
+        return
+    }
+    println(x)
+}
+println(fib(10), first_over([1, 5, 9], 4), first_over([1], 4))
+show(-1)
+show(2)";
+
+const OUTPUT: &str = "55 5 -1\n2\n";
+
+#[test]
+fn functions_return_from_anywhere_in_their_body() {
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, OUTPUT);
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, OUTPUT);
+}
+
+#[test]
+fn functions_are_type_checked() {
+    let source = "\
+return 1
+fn f(a, b) {
+    if a > b {
+        return a
+    }
+}
+println(f(1, 2))
+fn g(x) {
+    if x {
+        return 1
+    }
+    return 1.5
+}
+println(g(true))
+fn h(a) {
+    return a
+}
+println(h(1) + h(1.5))
+h(1, 2)
+fn h(z) {}
+fn len(a) {}
+fn v() {}
+let x = v()";
+    let errors = osho_lang::parse_and_check(source).unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|err| err.message()).collect();
+    assert_eq!(
+        messages,
+        [
+            "'return' can only be used inside a function",
+            "Function 'f' doesn't return a value on every path",
+            "Function 'g' returns int, but this returns float",
+            "Parameter 'a' of h() is int, found float",
+            "h() takes 1 argument but 2 were given",
+            "Function 'h' is already declared",
+            "Function 'len' is a builtin",
+            "v() doesn't return a value"
+        ]
+    );
+}
+
+#[test]
+fn functions_become_c_functions() {
+    let c = osho_lang::compile_to_c(PROGRAM).unwrap();
+    assert!(c.contains("static long osho_fn_fib(long n);\n"), "{}", c);
+    assert!(
+        c.contains("static long osho_fn_first_over(osho_array *xs, long limit) {\n"),
+        "{}",
+        c
+    );
+    assert!(c.contains("static void osho_fn_show(long x) {\n"), "{}", c);
+    assert!(c.contains("return n;\n"), "{}", c);
+    assert!(c.contains("return;\n"), "{}", c);
+
+    let wasm = osho_lang::compile_to_wasm(PROGRAM).unwrap_err();
+    assert_eq!(wasm.message(), "The wasm target doesn't support functions");
+}
@@ -0,0 +1,66 @@
+use std::thread;
+
+use osho_lang::parser::MAX_NESTING;
+use osho_lang::{Lexer, Parser};
+
+/// Lex and parse, returning the first error message
+fn first_error(source: &str) -> Option<String> {
+    let tokens = match Lexer::try_tokenize(source) {
+        Ok(tokens) => tokens,
+        Err(err) => return Some(err.message().to_string()),
+    };
+    Parser::try_parse(&tokens)
+        .err()
+        .map(|errors| errors[0].message().to_string())
+}
+
+#[test]
+fn truncated_and_garbled_programs_are_errors() {
+    for source in [
+        "(1",
+        "(",
+        ")",
+        "-(x",
+        "else",
+        "\"${",
+        "fn f(",
+        "let x = [1, (2",
+    ] {
+        assert!(first_error(source).is_some(), "{:?} should fail", source);
+    }
+    assert_eq!(first_error("let x = (1 + 2) * 3"), None);
+}
+
+#[test]
+fn nesting_is_limited_instead_of_overflowing_the_stack() {
+    // The limit is meant for a main thread's stack, which is bigger than a
+    // test thread's
+    let parse = thread::Builder::new().stack_size(8 << 20).spawn(|| {
+        let deepest = format!(
+            "let x = {}1{}",
+            "(".repeat(MAX_NESTING - 1),
+            ")".repeat(MAX_NESTING - 1)
+        );
+        assert_eq!(first_error(&deepest), None);
+
+        let too_deep = format!("let x = {}1", "(".repeat(100_000));
+        let message = format!("Nested too deeply, the limit is {} levels", MAX_NESTING);
+        assert_eq!(first_error(&too_deep), Some(message.clone()));
+        assert_eq!(first_error(&"-".repeat(100_000)), Some(message.clone()));
+        assert_eq!(first_error(&"if true {".repeat(10_000)), Some(message));
+    });
+    parse.unwrap().join().unwrap();
+}
+
+#[test]
+fn token_lists_must_end_with_an_end_of_file() {
+    let tokens = Lexer::try_tokenize("let x = 1").unwrap();
+    assert!(Parser::try_parse(&tokens).is_ok());
+    for tokens in [&tokens[..0], &tokens[..2]] {
+        let errors = Parser::try_parse(tokens).unwrap_err();
+        assert_eq!(
+            errors[0].message(),
+            "Expected the tokens to end with an end of file"
+        );
+    }
+}
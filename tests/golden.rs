@@ -0,0 +1,144 @@
+//! Runs every program in `examples/` through the whole pipeline and compares
+//! the interpreter's output, the generated C and the compiled executable's
+//! output with the files checked in under `tests/golden/<example>/`.
+//!
+//! `cargo test --test golden -- --bless` writes the golden files from the
+//! current output instead. Other arguments filter the examples by name.
+
+use std::fs;
+use std::path::Path;
+use std::process::{self, Command};
+
+use osho_lang::CCompiler;
+
+const EXAMPLES: &str = "examples";
+const GOLDEN: &str = "tests/golden";
+
+/// What the pipeline produced for one example
+struct Outputs {
+    interpreter: String,
+    c: String,
+
+    /// Missing when there's no C compiler to build the executable with
+    binary: Option<String>,
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let bless = args.iter().any(|arg| arg == "--bless");
+    let filters: Vec<&String> = args.iter().filter(|arg| !arg.starts_with('-')).collect();
+
+    let cc = CCompiler::detect(None)
+        .map_err(|err| eprintln!("note: not checking executables: {}", err))
+        .ok();
+    let mut examples: Vec<_> = fs::read_dir(EXAMPLES)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "osho"))
+        .collect();
+    examples.sort();
+
+    let mut failures = 0;
+    let mut checked = 0;
+    for path in examples {
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        if !filters.is_empty() && !filters.iter().any(|filter| name.contains(filter.as_str())) {
+            continue;
+        }
+        checked += 1;
+        let outputs = run(&name, &path, cc.as_ref());
+        let dir = Path::new(GOLDEN).join(&name);
+        let files = [
+            ("interpreter.txt", Some(&outputs.interpreter)),
+            ("program.c", Some(&outputs.c)),
+            ("binary.txt", outputs.binary.as_ref()),
+        ];
+        for (file, actual) in files {
+            let Some(actual) = actual else { continue };
+            let golden = dir.join(file);
+            if bless {
+                fs::create_dir_all(&dir).unwrap();
+                fs::write(&golden, actual).unwrap();
+                continue;
+            }
+            match fs::read_to_string(&golden) {
+                Ok(expected) if expected == *actual => {}
+                Ok(expected) => {
+                    failures += 1;
+                    eprintln!("{}: differs from {}", name, golden.display());
+                    eprintln!("{}", first_difference(&expected, actual));
+                }
+                Err(_) => {
+                    failures += 1;
+                    eprintln!("{}: {} is missing", name, golden.display());
+                }
+            }
+        }
+    }
+
+    if bless {
+        println!("blessed {} examples", checked);
+    } else if failures > 0 {
+        eprintln!(
+            "{} golden files don't match, run `cargo test --test golden -- --bless` to update them",
+            failures
+        );
+        process::exit(1);
+    } else {
+        println!("{} examples match their golden files", checked);
+    }
+}
+
+/// Run an example through the interpreter and the C backend
+fn run(name: &str, path: &Path, cc: Option<&CCompiler>) -> Outputs {
+    let source = fs::read_to_string(path).unwrap();
+    let fail = |stage: &str, err: String| -> ! {
+        eprintln!("{}: {} failed: {}", name, stage, err);
+        process::exit(1)
+    };
+    let interpreter = osho_lang::interpret(&source)
+        .unwrap_or_else(|err| fail("interpreting", err.to_string()))
+        .stdout;
+    let c = osho_lang::compile_to_c(&source)
+        .unwrap_or_else(|err| fail("generating C", err.to_string()));
+
+    let binary = cc.map(|cc| {
+        let dir = std::env::temp_dir().join(format!("osho-golden-{}-{}", process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let c_file = dir.join(format!("{}.c", name));
+        let executable = dir.join(name);
+        fs::write(&c_file, &c).unwrap();
+        cc.compile(&c_file, &executable)
+            .unwrap_or_else(|err| fail("compiling C", err));
+        let output = Command::new(&executable).output().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    });
+
+    Outputs {
+        interpreter,
+        c,
+        binary,
+    }
+}
+
+/// The first line where two outputs differ, for the failure message
+fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    for line in 1.. {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(expected), Some(actual)) if expected == actual => {}
+            (None, None) => break,
+            (expected, actual) => {
+                return format!(
+                    "  line {}:\n    expected: {}\n    actual:   {}",
+                    line,
+                    expected.unwrap_or("<end of file>"),
+                    actual.unwrap_or("<end of file>")
+                )
+            }
+        }
+    }
+    "  only the line endings differ".to_string()
+}
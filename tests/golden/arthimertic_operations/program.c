@@ -0,0 +1,64 @@
+#include <stdio.h>
+#include <stdbool.h>
+
+int main() {
+long a = 1;
+long b = 2;
+long c = 3;
+long d = 4;
+a = b;
+b = a;
+c = c + 1;
+d = d - 1;
+printf("%ld\n", (long)a);
+printf("%ld\n", (long)b);
+printf("%ld\n", (long)c);
+printf("%ld\n", (long)d);
+long _t0 = a + b;
+printf("%ld\n", (long)_t0);
+long _t1 = a - b;
+printf("%ld\n", (long)_t1);
+long _t2 = a / b;
+printf("%ld\n", (long)_t2);
+long _t3 = a * b;
+printf("%ld\n", (long)_t3);
+long _t4 = a + b;
+long _t5 = _t4 + c;
+printf("%ld\n", (long)_t5);
+long _t6 = a + b;
+long _t7 = _t6 + c;
+long _t8 = _t7 + d;
+printf("%ld\n", (long)_t8);
+long _t9 = a - b;
+long _t10 = _t9 - c;
+printf("%ld\n", (long)_t10);
+long _t11 = a - b;
+long _t12 = _t11 - c;
+long _t13 = _t12 - d;
+printf("%ld\n", (long)_t13);
+long _t14 = a * b;
+long _t15 = _t14 * c;
+printf("%ld\n", (long)_t15);
+long _t16 = a * b;
+long _t17 = _t16 * c;
+long _t18 = _t17 * d;
+printf("%ld\n", (long)_t18);
+long _t19 = a / b;
+long _t20 = _t19 / c;
+printf("%ld\n", (long)_t20);
+long _t21 = a / b;
+long _t22 = _t21 / c;
+long _t23 = _t22 / d;
+printf("%ld\n", (long)_t23);
+long _t24 = d * a;
+long _t25 = _t24 + b;
+printf("%ld\n", (long)_t25);
+long _t26 = d / a;
+long _t27 = _t26 + b;
+printf("%ld\n", (long)_t27);
+long _t28 = d / a;
+long _t29 = _t28 - b;
+printf("%ld\n", (long)_t29);
+
+return 0;
+}
\ No newline at end of file
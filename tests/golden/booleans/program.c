@@ -0,0 +1,25 @@
+#include <stdio.h>
+#include <stdbool.h>
+
+int main() {
+bool done = false;
+long count = 0;
+while (true) {
+bool _t0 = !(done);
+if (!_t0) break;
+count = count + 1;
+done = count >= 3;
+}
+printf("%s\n", done ? "true" : "false");
+printf("%ld\n", (long)count);
+bool _t1 = true;
+if (_t1) {
+bool _t2 = !(false);
+_t1 = _t2;
+}
+printf("%s\n", _t1 ? "true" : "false");
+bool _t3 = done == true;
+printf("%s\n", _t3 ? "true" : "false");
+
+return 0;
+}
\ No newline at end of file
@@ -0,0 +1,36 @@
+#include <stdio.h>
+#include <stdbool.h>
+
+int main() {
+long total = 0;
+long i = 0;
+while (true) {
+bool _t0 = i < 10;
+if (!_t0) break;
+long _t1 = i % 2;
+bool _t2 = _t1 == 0;
+if (_t2) {
+goto osho_continue_1;
+}
+bool _t3 = i > 7;
+if (_t3) {
+break;
+}
+total = total + i;
+osho_continue_1:;
+i = i + 1;
+}
+printf("%ld\n", (long)total);
+long n = 0;
+while (true) {
+n = n + 7;
+long _t4 = n % 5;
+bool _t5 = _t4 == 0;
+if (_t5) {
+break;
+}
+}
+printf("%ld\n", (long)n);
+
+return 0;
+}
\ No newline at end of file
@@ -0,0 +1,19 @@
+#include <stdio.h>
+#include <stdbool.h>
+
+int main() {
+long total = 0;
+long i = 1;
+while (true) {
+bool _t0 = i <= 4;
+if (!_t0) break;
+long _t1 = i * i;
+total = total + _t1;
+i = i + 1;
+}
+printf("%ld\n", (long)total);
+long _t2 = 10 / 2;
+printf("%ld\n", (long)_t2);
+
+return 0;
+}
\ No newline at end of file
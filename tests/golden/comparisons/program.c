@@ -0,0 +1,28 @@
+#include <stdio.h>
+#include <stdbool.h>
+
+int main() {
+const long a = 2;
+const long b = 3;
+bool _t0 = a == b;
+printf("%s\n", _t0 ? "true" : "false");
+bool _t1 = a != b;
+printf("%s\n", _t1 ? "true" : "false");
+bool _t2 = a < b;
+printf("%s\n", _t2 ? "true" : "false");
+bool _t3 = a <= b;
+printf("%s\n", _t3 ? "true" : "false");
+bool _t4 = a > b;
+printf("%s\n", _t4 ? "true" : "false");
+bool _t5 = a >= b;
+printf("%s\n", _t5 ? "true" : "false");
+long _t6 = a + 1;
+bool _t7 = _t6 == b;
+printf("%s\n", _t7 ? "true" : "false");
+bool _t8 = a < b;
+if (_t8) {
+printf("%ld\n", (long)a);
+}
+
+return 0;
+}
\ No newline at end of file
@@ -0,0 +1,67 @@
+#include <stdio.h>
+#include <stdbool.h>
+#include <math.h>
+#include <stdlib.h>
+
+/* Longest float: a sign, "0.", 323 zeros and 17 digits */
+#define OSHO_FLOAT_SIZE 400
+
+static const char *osho_float(char *buf, double value) {
+    if (isnan(value)) return "NaN";
+    if (isinf(value)) return value < 0 ? "-inf" : "inf";
+    char digits[32];
+    int precision = 0;
+    do {
+        snprintf(digits, sizeof digits, "%.*e", precision, value);
+    } while (strtod(digits, NULL) != value && ++precision <= 16);
+
+    /* Split "-d.ddde+xx" into its sign, digits and exponent */
+    char *p = digits, *out = buf;
+    if (*p == '-') *out++ = *p++;
+    char mantissa[20];
+    int len = 0;
+    for (; *p != 'e'; p++) {
+        if (*p != '.') mantissa[len++] = *p;
+    }
+    while (len > 1 && mantissa[len - 1] == '0') len--;
+    int point = atoi(p + 1) + 1;
+
+    if (point <= 0) {
+        *out++ = '0';
+        *out++ = '.';
+        for (int i = 0; i < -point; i++) *out++ = '0';
+        for (int i = 0; i < len; i++) *out++ = mantissa[i];
+    } else {
+        for (int i = 0; i < len || i < point; i++) {
+            if (i == point) *out++ = '.';
+            *out++ = i < len ? mantissa[i] : '0';
+        }
+    }
+    *out = '\0';
+    return buf;
+}
+
+int main() {
+long total = 0;
+double scale = 1.5;
+long i = 1;
+while (true) {
+bool _t0 = i <= 5;
+if (!_t0) break;
+total = total + i;
+i = i + 1;
+}
+printf("%ld\n", (long)total);
+total = total - 5;
+printf("%ld\n", (long)total);
+total = total * 3;
+printf("%ld\n", (long)total);
+total = total / 2;
+printf("%ld\n", (long)total);
+scale = scale * 2.0;
+double _t1 = (double)total;
+scale = scale + _t1;
+printf("%s\n", osho_float((char[OSHO_FLOAT_SIZE]){0}, scale));
+
+return 0;
+}
\ No newline at end of file
@@ -0,0 +1,24 @@
+#include <stdio.h>
+#include <stdbool.h>
+
+int main() {
+const long a = 1;
+const long b = 0;
+if (a) {
+printf("%ld\n", (long)1);
+} else {
+printf("%ld\n", (long)2);
+}
+if (b) {
+printf("%ld\n", (long)3);
+} else {
+long _t0 = a - 1;
+if (_t0) {
+printf("%ld\n", (long)4);
+} else {
+printf("%ld\n", (long)5);
+}
+}
+
+return 0;
+}
\ No newline at end of file
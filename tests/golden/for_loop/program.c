@@ -0,0 +1,17 @@
+#include <stdio.h>
+#include <stdbool.h>
+
+int main() {
+const long n = 5;
+long total = 0;
+long i = 0;
+while (true) {
+bool _t0 = i < n;
+if (!_t0) break;
+total = total + i;
+i = i + 1;
+}
+printf("%ld\n", (long)total);
+
+return 0;
+}
\ No newline at end of file
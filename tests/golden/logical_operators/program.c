@@ -0,0 +1,51 @@
+#include <stdio.h>
+#include <stdbool.h>
+
+int main() {
+const long a = 1;
+const long b = 0;
+bool _t0 = a != 0;
+if (_t0) {
+_t0 = b != 0;
+}
+printf("%s\n", _t0 ? "true" : "false");
+bool _t1 = a != 0;
+if (_t1) {
+} else {
+_t1 = b != 0;
+}
+printf("%s\n", _t1 ? "true" : "false");
+bool _t2 = !(a);
+printf("%s\n", _t2 ? "true" : "false");
+bool _t3 = !(b);
+printf("%s\n", _t3 ? "true" : "false");
+bool _t4 = !(a);
+bool _t5 = _t4;
+if (_t5) {
+} else {
+_t5 = b != 0;
+}
+printf("%s\n", _t5 ? "true" : "false");
+bool _t6 = a < 2;
+bool _t7 = _t6;
+if (_t7) {
+bool _t8 = b < 2;
+_t7 = _t8;
+}
+printf("%s\n", _t7 ? "true" : "false");
+bool _t9 = a == 0;
+bool _t10 = _t9;
+if (_t10) {
+} else {
+bool _t11 = b == 0;
+bool _t12 = _t11;
+if (_t12) {
+bool _t13 = a == 1;
+_t12 = _t13;
+}
+_t10 = _t12;
+}
+printf("%s\n", _t10 ? "true" : "false");
+
+return 0;
+}
\ No newline at end of file
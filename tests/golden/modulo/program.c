@@ -0,0 +1,74 @@
+#include <stdio.h>
+#include <stdbool.h>
+#include <math.h>
+#include <stdlib.h>
+
+/* Longest float: a sign, "0.", 323 zeros and 17 digits */
+#define OSHO_FLOAT_SIZE 400
+
+static const char *osho_float(char *buf, double value) {
+    if (isnan(value)) return "NaN";
+    if (isinf(value)) return value < 0 ? "-inf" : "inf";
+    char digits[32];
+    int precision = 0;
+    do {
+        snprintf(digits, sizeof digits, "%.*e", precision, value);
+    } while (strtod(digits, NULL) != value && ++precision <= 16);
+
+    /* Split "-d.ddde+xx" into its sign, digits and exponent */
+    char *p = digits, *out = buf;
+    if (*p == '-') *out++ = *p++;
+    char mantissa[20];
+    int len = 0;
+    for (; *p != 'e'; p++) {
+        if (*p != '.') mantissa[len++] = *p;
+    }
+    while (len > 1 && mantissa[len - 1] == '0') len--;
+    int point = atoi(p + 1) + 1;
+
+    if (point <= 0) {
+        *out++ = '0';
+        *out++ = '.';
+        for (int i = 0; i < -point; i++) *out++ = '0';
+        for (int i = 0; i < len; i++) *out++ = mantissa[i];
+    } else {
+        for (int i = 0; i < len || i < point; i++) {
+            if (i == point) *out++ = '.';
+            *out++ = i < len ? mantissa[i] : '0';
+        }
+    }
+    *out = '\0';
+    return buf;
+}
+
+int main() {
+const long a = 17;
+const long b = 5;
+const double x = 7.5;
+long _t0 = a % b;
+printf("%ld\n", (long)_t0);
+long _t1 = -(a);
+long _t2 = _t1 % b;
+printf("%ld\n", (long)_t2);
+long _t3 = a % b;
+long _t4 = _t3 * 2;
+printf("%ld\n", (long)_t4);
+double _t5 = fmod(x, 2.0);
+printf("%s\n", osho_float((char[OSHO_FLOAT_SIZE]){0}, _t5));
+double _t6 = fmod(x, 2.5);
+bool _t7 = _t6 == 0.0;
+printf("%s\n", _t7 ? "true" : "false");
+long i = 0;
+while (true) {
+bool _t8 = i < 10;
+if (!_t8) break;
+long _t9 = i % 3;
+bool _t10 = _t9 == 0;
+if (_t10) {
+printf("%ld\n", (long)i);
+}
+i = i + 1;
+}
+
+return 0;
+}
\ No newline at end of file
@@ -0,0 +1,70 @@
+#include <stdio.h>
+#include <stdbool.h>
+#include <math.h>
+#include <stdlib.h>
+
+/* Longest float: a sign, "0.", 323 zeros and 17 digits */
+#define OSHO_FLOAT_SIZE 400
+
+static const char *osho_float(char *buf, double value) {
+    if (isnan(value)) return "NaN";
+    if (isinf(value)) return value < 0 ? "-inf" : "inf";
+    char digits[32];
+    int precision = 0;
+    do {
+        snprintf(digits, sizeof digits, "%.*e", precision, value);
+    } while (strtod(digits, NULL) != value && ++precision <= 16);
+
+    /* Split "-d.ddde+xx" into its sign, digits and exponent */
+    char *p = digits, *out = buf;
+    if (*p == '-') *out++ = *p++;
+    char mantissa[20];
+    int len = 0;
+    for (; *p != 'e'; p++) {
+        if (*p != '.') mantissa[len++] = *p;
+    }
+    while (len > 1 && mantissa[len - 1] == '0') len--;
+    int point = atoi(p + 1) + 1;
+
+    if (point <= 0) {
+        *out++ = '0';
+        *out++ = '.';
+        for (int i = 0; i < -point; i++) *out++ = '0';
+        for (int i = 0; i < len; i++) *out++ = mantissa[i];
+    } else {
+        for (int i = 0; i < len || i < point; i++) {
+            if (i == point) *out++ = '.';
+            *out++ = i < len ? mantissa[i] : '0';
+        }
+    }
+    *out = '\0';
+    return buf;
+}
+
+int main() {
+const long x = -5;
+const double y = -2.5;
+long z = 10;
+printf("%ld\n", (long)x);
+printf("%s\n", osho_float((char[OSHO_FLOAT_SIZE]){0}, y));
+long _t0 = -(x);
+printf("%ld\n", (long)_t0);
+long _t1 = 3 - -2;
+printf("%ld\n", (long)_t1);
+long _t2 = -(x);
+long _t3 = -(_t2);
+printf("%ld\n", (long)_t3);
+long _t4 = x + 1;
+long _t5 = -(_t4);
+printf("%ld\n", (long)_t5);
+double _t6 = -(y);
+double _t7 = _t6 * 2.0;
+printf("%s\n", osho_float((char[OSHO_FLOAT_SIZE]){0}, _t7));
+z = z - 1;
+printf("%ld\n", (long)z);
+long _t8 = -(z);
+z = _t8;
+printf("%ld\n", (long)z);
+
+return 0;
+}
\ No newline at end of file
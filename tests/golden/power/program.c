@@ -0,0 +1,66 @@
+#include <stdio.h>
+#include <stdbool.h>
+#include <math.h>
+#include <stdlib.h>
+
+/* Longest float: a sign, "0.", 323 zeros and 17 digits */
+#define OSHO_FLOAT_SIZE 400
+
+static const char *osho_float(char *buf, double value) {
+    if (isnan(value)) return "NaN";
+    if (isinf(value)) return value < 0 ? "-inf" : "inf";
+    char digits[32];
+    int precision = 0;
+    do {
+        snprintf(digits, sizeof digits, "%.*e", precision, value);
+    } while (strtod(digits, NULL) != value && ++precision <= 16);
+
+    /* Split "-d.ddde+xx" into its sign, digits and exponent */
+    char *p = digits, *out = buf;
+    if (*p == '-') *out++ = *p++;
+    char mantissa[20];
+    int len = 0;
+    for (; *p != 'e'; p++) {
+        if (*p != '.') mantissa[len++] = *p;
+    }
+    while (len > 1 && mantissa[len - 1] == '0') len--;
+    int point = atoi(p + 1) + 1;
+
+    if (point <= 0) {
+        *out++ = '0';
+        *out++ = '.';
+        for (int i = 0; i < -point; i++) *out++ = '0';
+        for (int i = 0; i < len; i++) *out++ = mantissa[i];
+    } else {
+        for (int i = 0; i < len || i < point; i++) {
+            if (i == point) *out++ = '.';
+            *out++ = i < len ? mantissa[i] : '0';
+        }
+    }
+    *out = '\0';
+    return buf;
+}
+
+int main() {
+const long base = 2;
+const double x = 1.5;
+double _t0 = (double)base;
+double _t1 = pow(_t0, 10.0);
+printf("%s\n", osho_float((char[OSHO_FLOAT_SIZE]){0}, _t1));
+double _t2 = pow(3.0, 2.0);
+double _t3 = pow(2.0, _t2);
+printf("%s\n", osho_float((char[OSHO_FLOAT_SIZE]){0}, _t3));
+double _t4 = pow(2.0, 2.0);
+double _t5 = -(_t4);
+printf("%s\n", osho_float((char[OSHO_FLOAT_SIZE]){0}, _t5));
+double _t6 = pow(2.0, -1.0);
+printf("%s\n", osho_float((char[OSHO_FLOAT_SIZE]){0}, _t6));
+double _t7 = pow(x, 2.0);
+printf("%s\n", osho_float((char[OSHO_FLOAT_SIZE]){0}, _t7));
+double _t8 = pow(2.0, 3.0);
+double _t9 = _t8 * 2.0;
+double _t10 = 1.0 + _t9;
+printf("%s\n", osho_float((char[OSHO_FLOAT_SIZE]){0}, _t10));
+
+return 0;
+}
\ No newline at end of file
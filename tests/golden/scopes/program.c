@@ -0,0 +1,30 @@
+#include <stdio.h>
+#include <stdbool.h>
+
+int main() {
+const long x = 1;
+long y = 10;
+const bool x_1 = true;
+printf("%s\n", x_1 ? "true" : "false");
+y = y + 1;
+printf("%ld\n", (long)x);
+printf("%ld\n", (long)y);
+long i = 0;
+while (true) {
+bool _t0 = i < 2;
+if (!_t0) break;
+long _t1 = i * 100;
+const long x_2 = _t1;
+printf("%ld\n", (long)x_2);
+i = i + 1;
+}
+long i_1 = 5;
+while (true) {
+bool _t2 = i_1 < 6;
+if (!_t2) break;
+printf("%ld\n", (long)i_1);
+i_1 = i_1 + 1;
+}
+
+return 0;
+}
\ No newline at end of file
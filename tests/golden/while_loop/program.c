@@ -0,0 +1,14 @@
+#include <stdio.h>
+#include <stdbool.h>
+
+int main() {
+long n = 5;
+long total = 0;
+while (n) {
+total = total + n;
+n = n - 1;
+}
+printf("%ld\n", (long)total);
+
+return 0;
+}
\ No newline at end of file
@@ -0,0 +1,54 @@
+use osho_lang::{highlight, TokenClass};
+
+/// The text and class of each highlighted token
+fn classes(source: &str) -> Vec<(&str, TokenClass)> {
+    highlight(source)
+        .into_iter()
+        .map(|(span, class)| (&source[span.offset..span.offset + span.length], class))
+        .collect()
+}
+
+#[test]
+fn tokens_are_classified() {
+    use TokenClass::*;
+    assert_eq!(
+        classes("let x = 2.5 // half\nprintln(x >= 1, true)"),
+        [
+            ("let", Keyword),
+            ("x", Identifier),
+            ("=", Operator),
+            ("2.5", Number),
+            ("// half", Comment),
+            ("println", Keyword),
+            ("(", Operator),
+            ("x", Identifier),
+            (">=", Operator),
+            ("1", Number),
+            (",", Operator),
+            ("true", Keyword),
+            (")", Operator)
+        ]
+    );
+}
+
+#[test]
+fn interpolated_strings_are_split_around_their_values() {
+    use TokenClass::*;
+    assert_eq!(
+        classes(r#""a${n}b""#),
+        [("\"a${", String), ("n", Identifier), ("}b\"", String)]
+    );
+}
+
+#[test]
+fn text_after_a_lexing_error_is_not_highlighted() {
+    assert_eq!(
+        classes("let s = \"open\nlet t = 1"),
+        [
+            ("let", TokenClass::Keyword),
+            ("s", TokenClass::Identifier),
+            ("=", TokenClass::Operator)
+        ]
+    );
+    assert!(highlight("").is_empty());
+}
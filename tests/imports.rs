@@ -0,0 +1,77 @@
+use std::path::Path;
+
+use osho_lang::{ast_printer, Interpreter, Loader, SemanticAnalyzer};
+
+const FIXTURES: &str = "tests/fixtures/imports";
+
+#[test]
+fn imported_files_come_first_and_only_once() {
+    let mut loader = Loader::new();
+    let ast = loader
+        .load_path(&Path::new(FIXTURES).join("main.osho"))
+        .unwrap();
+    // lib/shapes.osho imports lib/math.osho too, which is only included once
+    let names: Vec<_> = loader
+        .sources()
+        .files()
+        .iter()
+        .map(|file| file.name.replace('\\', "/"))
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "tests/fixtures/imports/main.osho",
+            "tests/fixtures/imports/lib/shapes.osho",
+            "tests/fixtures/imports/lib/math.osho"
+        ]
+    );
+    assert!(
+        ast_printer::to_sexpr(&ast, ast.root()).starts_with("(program (const PI_ISH 3) (fn square")
+    );
+
+    SemanticAnalyzer::new().analyze_all(&ast).unwrap();
+    let mut interpreter = Interpreter::with_output(Vec::new());
+    interpreter.run(&ast, ast.root()).unwrap();
+    assert_eq!(interpreter.into_output(), b"start\n25 3\n");
+}
+
+#[test]
+fn errors_are_reported_in_the_file_they_are_in() {
+    let mut loader = Loader::new();
+    let errors = loader
+        .load_path(&Path::new(FIXTURES).join("cycle_a.osho"))
+        .unwrap_err();
+    assert_eq!(
+        errors[0].message().replace('\\', "/"),
+        "Import cycle: tests/fixtures/imports/cycle_a.osho -> \
+         tests/fixtures/imports/cycle_b.osho -> tests/fixtures/imports/cycle_a.osho"
+    );
+    let file = loader.sources().file(errors[0].span()).unwrap();
+    assert!(file.name.ends_with("cycle_b.osho"), "{}", file.name);
+
+    let mut loader = Loader::new();
+    let ast = loader
+        .load_path(&Path::new(FIXTURES).join("bad_call.osho"))
+        .unwrap();
+    let errors = SemanticAnalyzer::new().analyze_all(&ast).unwrap_err();
+    assert_eq!(
+        errors[0].message(),
+        "Parameter 'n' of square() is int, found bool"
+    );
+    let file = loader.sources().file(errors[0].span()).unwrap();
+    assert!(file.name.ends_with("bad_call.osho"), "{}", file.name);
+}
+
+#[test]
+fn imports_must_be_at_the_top_level() {
+    let errors = osho_lang::parse_and_check("if true {\n    import \"a.osho\"\n}").unwrap_err();
+    assert_eq!(
+        errors[0].message(),
+        "Imports must be at the top level of a file"
+    );
+    let errors = osho_lang::parse_and_check("import std.").unwrap_err();
+    assert_eq!(
+        errors[0].message(),
+        "Expected a file or module name after 'import'"
+    );
+}
@@ -0,0 +1,49 @@
+use std::io::Cursor;
+
+use osho_lang::{bytecode, Interpreter, Vm};
+
+const PROGRAM: &str =
+    "let mut n = 0\nlet mut x = 0.0\ninput n\ninput x\nprintln(n * 2)\nprintln(x + n)";
+
+#[test]
+fn interpreter_reads_numbers_of_the_variables_type() {
+    let ast = osho_lang::parse_and_check(PROGRAM).unwrap();
+    let mut interpreter =
+        Interpreter::with_output(Vec::new()).with_input(Cursor::new("21\n 1.5 \n"));
+    interpreter.run(&ast, ast.root()).unwrap();
+    assert_eq!(
+        String::from_utf8(interpreter.into_output()).unwrap(),
+        "42\n22.5\n"
+    );
+
+    let mut interpreter = Interpreter::with_output(Vec::new()).with_input(Cursor::new("2.5\n"));
+    let err = interpreter.run(&ast, ast.root()).unwrap_err();
+    assert_eq!(err.message(), "Expected int input, found '2.5'");
+}
+
+#[test]
+fn vm_reads_numbers_of_the_variables_type() {
+    let ast = osho_lang::parse_and_check(PROGRAM).unwrap();
+    let chunk = bytecode::Compiler::new().compile(&ast).unwrap();
+    let mut vm = Vm::with_output(Vec::new()).with_input(Cursor::new("21\n1.5\n"));
+    vm.run(&chunk).unwrap();
+    assert_eq!(String::from_utf8(vm.into_output()).unwrap(), "42\n22.5\n");
+
+    let mut vm = Vm::with_output(Vec::new()).with_input(Cursor::new("21\n"));
+    let err = vm.run(&chunk).unwrap_err();
+    assert_eq!(err.message(), "Unexpected end of input");
+}
+
+#[test]
+fn input_needs_a_mutable_number_variable() {
+    let errors = osho_lang::parse_and_check("let mut b = true\ninput b").unwrap_err();
+    assert_eq!(
+        errors[0].message(),
+        "Cannot read bool variable 'b' from input"
+    );
+    let errors = osho_lang::parse_and_check("let n = 1\ninput n").unwrap_err();
+    assert_eq!(
+        errors[0].message(),
+        "Cannot assign to immutable variable 'n', declare it with `let mut n`"
+    );
+}
@@ -0,0 +1,55 @@
+use osho_lang::ast_printer;
+use osho_lang::{Kind, Lexer};
+
+const PROGRAM: &str = r#"
+let x = 4
+let xs = [1, 2]
+let m = {"k": 3}
+let s = "m[k] = ${m["k"]}, ${xs} \${x}"
+println("x is ${x + 1}, ${xs[1] > 1 ? "big ${xs[1]}" : "small"}")
+println(s)"#;
+
+const OUTPUT: &str = "x is 5, big 2\nm[k] = 3, [1, 2] ${x}\n";
+
+#[test]
+fn interpolated_values_are_formatted_into_strings() {
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, OUTPUT);
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, OUTPUT);
+    let tokens = Lexer::new(r#"let s = "a${x}b${ {"k": 1}["k"] }c""#)
+        .get_tokens()
+        .unwrap();
+    let ast = osho_lang::Parser::new(&tokens).parse().unwrap();
+    assert_eq!(
+        ast_printer::to_sexpr(&ast, ast.root()),
+        r#"(program (let s (interpolate "a{}b{}c" x ([] (map "k" 1) "k"))))"#
+    );
+}
+
+#[test]
+fn the_lexer_splits_strings_around_expressions() {
+    let tokens = Lexer::new(r#""a${x}b${y}c""#).get_tokens().unwrap();
+    let kinds: Vec<_> = tokens.iter().map(|token| token.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            Kind::StringStart,
+            Kind::Identifier,
+            Kind::StringMiddle,
+            Kind::Identifier,
+            Kind::StringEnd,
+            Kind::Eof
+        ]
+    );
+    let err = Lexer::new(r#"println("a${x)"#).get_tokens().unwrap_err();
+    assert_eq!(err.message(), "Unterminated string interpolation");
+}
+
+#[test]
+fn printed_interpolations_become_one_printf() {
+    let c = osho_lang::compile_to_c("let x = 4\nprintln(\"x is ${x + 1}!\", x)").unwrap();
+    assert!(
+        c.contains(r#"printf("x is %ld! %ld\n", (long)_t0, (long)x);"#),
+        "{}",
+        c
+    );
+}
@@ -0,0 +1,68 @@
+use osho_lang::SemanticAnalyzer;
+
+const PROGRAM: &str = "\
+let n = 2
+let f = |x: int| x * n
+let m = {\"a\": 1}
+let s = \"${n}!\"
+println(f(1), m[\"a\"], s == \"2!\", \"${n}\")";
+
+#[test]
+fn compiled_targets_list_every_construct_they_cannot_compile() {
+    let ast = osho_lang::parse_and_check(PROGRAM).unwrap();
+    let errors = SemanticAnalyzer::new().lower(&ast).unwrap_err();
+    let found: Vec<_> = errors
+        .iter()
+        .map(|err| {
+            let span = err.span().unwrap();
+            (err.message(), span.line, span.column)
+        })
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (
+                "Closures capturing 'n' are only supported by `osho run` for now, not by compiled targets",
+                2,
+                9
+            ),
+            (
+                "Maps are only supported by `osho run` for now, not by compiled targets",
+                3,
+                9
+            ),
+            (
+                "Interpolated strings outside print are only supported by `osho run` for now, not by compiled targets",
+                4,
+                9
+            ),
+            (
+                "Operators on strings are only supported by `osho run` for now, not by compiled targets",
+                5,
+                25
+            ),
+        ]
+    );
+    // The interpreters still run the program
+    assert_eq!(
+        osho_lang::interpret(PROGRAM).unwrap().stdout,
+        "2 1 true 2\n"
+    );
+}
+
+#[test]
+fn checking_alone_notes_the_constructs_without_failing() {
+    let ast = osho_lang::parse_and_check("const NAME = \"osho\"\nprintln(NAME)").unwrap();
+    let mut analyzer = SemanticAnalyzer::new();
+    analyzer.analyze_all(&ast).unwrap();
+    let notes = analyzer.interpreter_only();
+    assert_eq!(notes.len(), 1);
+    assert_eq!(
+        notes[0].message(),
+        "String constants are only supported by `osho run` for now, not by compiled targets"
+    );
+
+    // Programs without them compile as before
+    let c = osho_lang::compile_to_c("let s = \"a\"\nprintln(s, \"${len(s)}\")").unwrap();
+    assert!(c.contains("int main("), "{}", c);
+}
@@ -0,0 +1,52 @@
+use osho_lang::SemanticAnalyzer;
+
+/// The printed IR of a program
+fn lowered(source: &str) -> String {
+    let ast = osho_lang::parse_and_check(source).unwrap();
+    SemanticAnalyzer::new().lower(&ast).unwrap().to_string()
+}
+
+#[test]
+fn flattens_expressions_into_typed_temporaries() {
+    assert_eq!(
+        lowered("let x = 2\nlet y = x * 1.5 + 1\nprintln(y)"),
+        "\
+x: int = 2
+_t0: float = float x
+_t1: float = _t0 * 1.5
+_t2: float = _t1 + 1.0
+y: float = _t2
+println y
+"
+    );
+}
+
+#[test]
+fn gives_shadowed_variables_unique_names() {
+    assert_eq!(
+        lowered("let x = 1\n{\nlet x = true\nprintln(x)\n}\nprintln(x)"),
+        "x: int = 1\nx_1: bool = true\nprintln x_1\nprintln x\n"
+    );
+}
+
+#[test]
+fn lowers_short_circuits_and_loops_to_structured_control_flow() {
+    assert_eq!(
+        lowered("for (let mut i = 0; i < 3 || false; i++) {\nprintln(i)\n}"),
+        "\
+i: int = 0
+loop {
+  _t0: bool = i < 3
+  _t1: bool = truthy _t0
+  if _t1 {
+  } else {
+    _t1: bool = truthy false
+  }
+  while _t1
+  println i
+step:
+  i: int = i + 1
+}
+"
+    );
+}
@@ -0,0 +1,71 @@
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Compile a program to JavaScript and run it with node, returning what it prints
+fn run_js(source: &str) -> String {
+    let js = osho_lang::compile_to_js(source).unwrap();
+    let mut node = Command::new("node")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("node should be installed");
+    node.stdin.take().unwrap().write_all(js.as_bytes()).unwrap();
+    let output = node.wait_with_output().unwrap();
+    assert!(output.status.success(), "node failed on:\n{}", js);
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// Every example should print the same under node as with the interpreter
+#[test]
+fn js_matches_interpreter_on_examples() {
+    let mut checked = 0;
+    for entry in fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("osho") {
+            continue;
+        }
+        let source = fs::read_to_string(&path).unwrap();
+        let interpreted = osho_lang::interpret(&source).unwrap().stdout;
+        assert_eq!(run_js(&source), interpreted, "{}", path.display());
+        checked += 1;
+    }
+    assert!(checked > 0, "no examples found");
+}
+
+#[test]
+fn js_truncates_integer_division() {
+    let output = run_js(
+        "let mut x = 7\nprintln(x / 2)\nprintln(-7 / 2)\nx /= 2\nprintln(x)\nprintln(7.0 / 2)",
+    );
+    assert_eq!(output, "3\n-3\n3\n3.5\n");
+}
+
+#[test]
+fn js_logical_operators_produce_bools() {
+    let output = run_js("println(1 && 2)\nprintln(0 || 0.5)\nprintln(-2 ** 2)");
+    assert_eq!(output, "true\ntrue\n-4\n");
+}
+
+#[test]
+fn js_is_readable() {
+    let js = osho_lang::compile_to_js("let mut x = 1\nif x > 0 {\nprintln(x)\n} else {\nx = 2\n}")
+        .unwrap();
+    assert_eq!(
+        js,
+        "let x = 1;\nif (x > 0) {\n  console.log(x);\n} else {\n  x = 2;\n}\n"
+    );
+}
+
+#[test]
+fn js_prints_several_values_and_format_strings() {
+    let output = run_js("let x = 3\nprintln(x, x > 2)\nprintln \"`\\${{}}` = {}\", x * 2");
+    assert_eq!(output, "3 true\n`${}` = 6\n");
+}
+
+#[test]
+fn js_prints_arrays_like_the_interpreter() {
+    let source =
+        "let mut a = [[1, 2], [3]]\na[1][0] = 7 / 2\nprintln(a, len(a[0]))\nprint \"{}\", a[0]";
+    assert_eq!(run_js(source), "[[1, 2], [3]] 2\n[1, 2]");
+}
@@ -0,0 +1,67 @@
+use std::borrow::Cow;
+
+use osho_lang::ast_printer::to_sexpr;
+use osho_lang::{CompileError, Kind, Lexer, Parser, TokenValue};
+
+const PROGRAM: &str = "// sum\nfn add(a, b) { return a + b }\nprintln(\"${add(1, 2)}\") /* done */";
+
+#[test]
+fn iterating_gives_the_same_tokens_as_collecting() {
+    let collected = Lexer::new(PROGRAM).get_tokens().unwrap();
+    let iterated: Vec<_> = Lexer::new(PROGRAM).map(Result::unwrap).collect();
+    assert_eq!(iterated, collected);
+    assert_eq!(iterated.last().map(|token| token.kind), Some(Kind::Eof));
+}
+
+#[test]
+fn iteration_stops_at_the_first_error() {
+    let mut lexer = Lexer::new("let x = 1 @ 2 #");
+    let kinds: Vec<_> = lexer
+        .by_ref()
+        .take_while(Result::is_ok)
+        .map(|token| token.unwrap().kind)
+        .collect();
+    assert_eq!(
+        kinds,
+        [Kind::Let, Kind::Identifier, Kind::EqualsTo, Kind::Integer]
+    );
+    assert!(lexer.next().is_none());
+}
+
+#[test]
+fn the_parser_reads_tokens_as_they_are_lexed() {
+    let tokens = Lexer::new(PROGRAM).get_tokens().unwrap();
+    let buffered = Parser::new(&tokens).parse().unwrap();
+    let streamed = Parser::from_lexer(Lexer::new(PROGRAM)).parse().unwrap();
+    assert_eq!(
+        to_sexpr(&streamed, streamed.root()),
+        to_sexpr(&buffered, buffered.root())
+    );
+
+    // A lexing error is reported on its own, not the syntax errors it causes
+    let errors = Parser::from_lexer(Lexer::new("let x = (1 @"))
+        .parse()
+        .unwrap_err();
+    assert!(
+        matches!(errors[..], [CompileError::Lex { .. }]),
+        "{:?}",
+        errors
+    );
+}
+
+#[test]
+fn token_text_is_borrowed_unless_it_has_escapes() {
+    let tokens = Lexer::new(r#"name "plain" "tab\t""#).get_tokens().unwrap();
+    let texts: Vec<_> = tokens
+        .iter()
+        .filter_map(|token| match &token.value {
+            TokenValue::String(text) => Some(text),
+            _ => None,
+        })
+        .collect();
+    assert!(matches!(
+        texts[..],
+        [Cow::Borrowed("name"), Cow::Borrowed("plain"), Cow::Owned(_)]
+    ));
+    assert_eq!(texts[2], "tab\t");
+}
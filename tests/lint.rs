@@ -0,0 +1,60 @@
+use osho_lang::{lint, Lint, LintLevels};
+
+/// Messages of the lint pass's warnings for a program
+fn lints(source: &str) -> Vec<String> {
+    let ast = osho_lang::parse_and_check(source).unwrap();
+    lint::lint(&ast)
+        .into_iter()
+        .map(|warning| warning.message)
+        .collect()
+}
+
+#[test]
+fn warns_about_constant_conditions_and_self_assignments() {
+    let source = "let mut x = 1\nx = x\nlet mut a = [1]\na[0] = a[0]\n\
+        if 1 < 2 {\nprintln(x)\n}\nwhile true {\nbreak\n}\nprintln(false ? x : a[0])";
+    assert_eq!(
+        lints(source),
+        [
+            "Value is assigned to itself",
+            "Value is assigned to itself",
+            "Condition is always true",
+            "Condition is always false"
+        ]
+    );
+}
+
+#[test]
+fn names_follow_the_conventions() {
+    let source = "const limit = 3\nstruct point { x }\n\
+        fn getX(thePoint) {\nreturn thePoint.x\n}\nlet HTTPCode = 1\nlet ok_name = 2";
+    assert_eq!(
+        lints(source),
+        [
+            "Constant 'limit' should be upper case, like 'LIMIT'",
+            "Struct 'point' should start with a capital letter, like 'Point'",
+            "Function 'getX' should be snake case, like 'get_x'",
+            "Parameter 'thePoint' should be snake case, like 'the_point'",
+            "Variable 'HTTPCode' should be snake case, like 'http_code'"
+        ]
+    );
+}
+
+#[test]
+fn config_files_enable_and_disable_lints() {
+    let mut levels = LintLevels::default();
+    levels
+        .configure("# strict\nwarn shadowing\nallow identifier-style  # noisy\n\ndeny-warnings\n")
+        .unwrap();
+    assert!(levels.is_enabled(Lint::Shadowing));
+    assert!(!levels.is_enabled(Lint::IdentifierStyle));
+    assert!(levels.is_enabled(Lint::SelfAssignment));
+    assert!(levels.deny_warnings);
+
+    let err = LintLevels::default().configure("warn nope").unwrap_err();
+    assert!(
+        err.starts_with("Line 1 of the lint config: Unknown lint 'nope'"),
+        "{}",
+        err
+    );
+}
@@ -0,0 +1,128 @@
+use osho_lang::lsp::Server;
+use serde_json::{json, Value};
+
+const URI: &str = "file:///tmp/main.osho";
+
+/// Send messages to a server, returning what it wrote back
+fn exchange(messages: &[Value]) -> Vec<Value> {
+    let mut input = String::new();
+    for message in messages {
+        let body = message.to_string();
+        input.push_str(&format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+    }
+    let mut output = Vec::new();
+    Server::new(&mut output).run(input.as_bytes()).unwrap();
+
+    let output = String::from_utf8(output).unwrap();
+    let mut replies = Vec::new();
+    let mut rest = output.as_str();
+    while let Some((header, body)) = rest.split_once("\r\n\r\n") {
+        let length: usize = header["Content-Length: ".len()..].parse().unwrap();
+        replies.push(serde_json::from_str(&body[..length]).unwrap());
+        rest = &body[length..];
+    }
+    replies
+}
+
+fn open(text: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": { "textDocument": { "uri": URI, "languageId": "osho", "version": 1, "text": text } },
+    })
+}
+
+fn request(id: u64, method: &str, line: u64, character: u64) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": method,
+        "params": {
+            "textDocument": { "uri": URI },
+            "position": { "line": line, "character": character },
+        },
+    })
+}
+
+#[test]
+fn diagnostics_are_published_on_open_and_change() {
+    let change = json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didChange",
+        "params": {
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{ "text": "let x = 1\nprintln(x)\n" }],
+        },
+    });
+    let replies = exchange(&[open("let x = 1\nprintln(x + true)\n"), change]);
+    assert_eq!(replies[0]["method"], "textDocument/publishDiagnostics");
+    let diagnostics = &replies[0]["params"]["diagnostics"];
+    assert_eq!(diagnostics[0]["message"], "Cannot add int and bool");
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(
+        diagnostics[0]["range"]["start"],
+        json!({ "line": 1, "character": 10 })
+    );
+    assert_eq!(replies[1]["params"]["diagnostics"], json!([]));
+}
+
+#[test]
+fn definition_and_hover_use_the_symbol_table() {
+    let text = "fn square(n: int) -> int {\n    return n * n\n}\nlet mut total = square(3)\nprintln(total)\n";
+    let replies = exchange(&[
+        open(text),
+        request(1, "textDocument/definition", 4, 10),
+        request(2, "textDocument/hover", 4, 10),
+        request(3, "textDocument/definition", 3, 18),
+        request(4, "textDocument/hover", 3, 18),
+        request(5, "textDocument/hover", 0, 5),
+    ]);
+    assert_eq!(replies[1]["id"], 1);
+    assert_eq!(
+        replies[1]["result"],
+        json!({
+            "uri": URI,
+            "range": { "start": { "line": 3, "character": 8 }, "end": { "line": 3, "character": 13 } },
+        })
+    );
+    assert_eq!(
+        replies[2]["result"]["contents"]["value"],
+        "```osho\nlet mut total: int\n```"
+    );
+    assert_eq!(
+        replies[3]["result"]["range"]["start"],
+        json!({ "line": 0, "character": 3 })
+    );
+    assert_eq!(
+        replies[4]["result"]["contents"]["value"],
+        "```osho\nfn square(n: int) -> int\n```"
+    );
+    assert_eq!(replies[5]["result"], replies[4]["result"]);
+}
+
+#[test]
+fn document_symbols_list_the_declarations() {
+    let text = "const LIMIT = 3\nstruct Point { x, y }\nfn origin() {\n    return Point { x: 0, y: 0 }\n}\nlet p = origin()\nprintln(p.x, LIMIT)\n";
+    let symbols = json!({
+        "jsonrpc": "2.0",
+        "id": 7,
+        "method": "textDocument/documentSymbol",
+        "params": { "textDocument": { "uri": URI } },
+    });
+    let replies = exchange(&[open(text), symbols]);
+    let names: Vec<_> = replies[1]["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|symbol| {
+            (
+                symbol["name"].as_str().unwrap(),
+                symbol["kind"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        names,
+        [("LIMIT", 14), ("Point", 23), ("origin", 12), ("p", 13)]
+    );
+}
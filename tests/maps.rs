@@ -0,0 +1,62 @@
+const PROGRAM: &str = "\
+let mut m = {\"b\": 2, \"a\": 1.5}
+m[\"c\"] = 3
+let k = keys(m)
+println(m, m[\"a\"] + m[\"c\"])
+println(k, k[0] == \"a\")";
+
+const OUTPUT: &str = "{\"a\": 1.5, \"b\": 2, \"c\": 3} 4.5\n[\"a\", \"b\", \"c\"] true\n";
+
+#[test]
+fn maps_are_indexed_by_key_and_grow() {
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, OUTPUT);
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, OUTPUT);
+
+    let source = "let m = {\"a\": 1}\nprintln(m[\"a\"])\nprintln(m[\"b\"])";
+    for err in [
+        osho_lang::interpret(source).unwrap_err(),
+        osho_lang::run_vm(source).unwrap_err(),
+    ] {
+        assert_eq!(err.message(), "Key \"b\" is not in the map");
+        assert_eq!(err.span().unwrap().line, 3);
+    }
+}
+
+#[test]
+fn maps_are_type_checked() {
+    let source = "\
+let a = {}
+let b = {\"x\": 1, \"y\": true}
+let c = {1: 2}
+let mut d = {\"x\": 1}
+d[\"y\"] = 2.5
+println(d[0], keys([1]))
+if \"x\" < \"y\" {}";
+    let errors = osho_lang::parse_and_check(source).unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|err| err.message()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Cannot infer the type of an empty map",
+            "Map values must have the same type, found int and bool",
+            "Map keys must be strings, found int",
+            "Cannot assign float to a value of {string: int}",
+            "Map key must be a string, found int",
+            "Cannot order string values"
+        ]
+    );
+}
+
+#[test]
+fn maps_are_interpreter_only_for_compiled_targets() {
+    let c = osho_lang::compile_to_c(PROGRAM).unwrap_err();
+    assert_eq!(
+        c.message(),
+        "Maps are only supported by `osho run` for now, not by compiled targets"
+    );
+    let wasm = osho_lang::compile_to_wasm(PROGRAM).unwrap_err();
+    assert_eq!(
+        wasm.message(),
+        "The wasm target doesn't support strings or maps"
+    );
+}
@@ -0,0 +1,50 @@
+const PROGRAM: &str = "\
+let x = -3
+println(sqrt(16), abs(x), abs(-2.5), floor(3.7), ceil(3.2), floor(x))
+println(pow(2, 10), min(3, 7), max(3, 7.5), min(x, 1.5), sin(0), cos(0))
+let mut biggest = 0
+for (let mut i = 0; i < 5; i++) {
+    biggest = max(biggest, i * 2)
+}
+println(biggest)";
+
+const OUTPUT: &str = "4 3 2.5 3 4 -3\n1024 3 7.5 -3 0 1\n8\n";
+
+#[test]
+fn math_builtins_compute_the_same_on_every_backend() {
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, OUTPUT);
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, OUTPUT);
+}
+
+#[test]
+fn math_builtins_take_numbers() {
+    let source = "\
+println(sqrt(true))
+println(pow(2))
+let m = max(1, 2)
+let mut i = 0
+i = m
+i = min(1, 2.5)
+fn abs(x) {}";
+    let errors = osho_lang::parse_and_check(source).unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|err| err.message()).collect();
+    assert_eq!(
+        messages,
+        [
+            "sqrt() expects a number, found bool",
+            "pow() takes 2 arguments but 1 was given",
+            "Cannot assign float to int variable 'i'",
+            "Function 'abs' is a builtin"
+        ]
+    );
+}
+
+#[test]
+fn math_builtins_map_to_math_h() {
+    let c = osho_lang::compile_to_c(PROGRAM).unwrap();
+    assert!(c.contains("#include <math.h>\n"), "{}", c);
+    assert!(c.contains("sqrt(16.0)"), "{}", c);
+    assert!(c.contains("fmax(3.0, 7.5)"), "{}", c);
+    // Integer versions are plain C
+    assert!(!c.contains("fmax(biggest"), "{}", c);
+}
@@ -0,0 +1,66 @@
+const PROGRAM: &str = "\
+module geo {
+    pub struct Point { x, y }
+    pub const ORIGIN = 0
+    fn square(n: int) -> int {
+        return n * n
+    }
+    pub fn dist2(p: Point) -> int {
+        return square(p.x) + square(p.y)
+    }
+}
+fn square(n: int) -> int {
+    return n + 1000
+}
+let p: geo.Point = geo.Point { x: 3, y: 4 }
+println(geo.dist2(p), square(2), geo.ORIGIN)";
+
+const OUTPUT: &str = "25 1002 0\n";
+
+#[test]
+fn module_items_are_used_through_the_module_name() {
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, OUTPUT);
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, OUTPUT);
+}
+
+#[test]
+fn only_pub_items_are_visible_outside_a_module() {
+    let source = "\
+module m {
+    fn hidden() -> int {
+        return 1
+    }
+    pub const K = 2
+}
+module m {}
+println(m.hidden(), m.K(), m.nope, z.f())";
+    let errors = osho_lang::parse_and_check(source).unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|err| err.message()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Module 'm' is already declared",
+            "'hidden' is private to module 'm'",
+            "'m.K' is a constant, not a function",
+            "Module 'm' has no constant 'nope'",
+            "Unknown module 'z'"
+        ]
+    );
+    let errors = osho_lang::parse_and_check("pub fn f() {}").unwrap_err();
+    assert_eq!(
+        errors[0].message(),
+        "Only declarations in a module can be 'pub'"
+    );
+}
+
+#[test]
+fn module_items_get_mangled_c_names() {
+    let c = osho_lang::compile_to_c(PROGRAM).unwrap();
+    assert!(
+        c.contains("static long osho_fn_geo__dist2(struct geo__Point *p)"),
+        "{}",
+        c
+    );
+    assert!(c.contains("osho_fn_geo__square(_t"), "{}", c);
+    assert!(c.contains("osho_fn_square(2)"), "{}", c);
+}
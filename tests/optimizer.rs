@@ -0,0 +1,100 @@
+use std::fs;
+
+use osho_lang::{ast_printer, optimizer, Interpreter};
+
+/// The S-expression of a program after constant folding
+fn folded(source: &str) -> String {
+    let mut ast = osho_lang::parse_and_check(source).unwrap();
+    optimizer::fold_constants(&mut ast);
+    ast_printer::to_sexpr(&ast, ast.root())
+}
+
+#[test]
+fn folds_arithmetic() {
+    assert_eq!(folded("println(2 + 3 * 4)"), "(program (println 14))");
+    assert_eq!(folded("println(7 / 2 + 0.5)"), "(program (println 3.5))");
+    assert_eq!(folded("println(2 ** 3)"), "(program (println 8.0))");
+    assert_eq!(folded("println(-(1 + 2))"), "(program (println -3))");
+}
+
+#[test]
+fn folds_comparisons_and_logic() {
+    assert_eq!(
+        folded("println(1 < 2 && !false)"),
+        "(program (println true))"
+    );
+    assert_eq!(
+        folded("println(0 || 2.5 == 2.5)"),
+        "(program (println true))"
+    );
+}
+
+#[test]
+fn folds_inside_statements_but_not_around_variables() {
+    assert_eq!(
+        folded("let mut x = 1 + 1\nwhile x < 2 * 5 {\nx += 3 - 1\n}"),
+        "(program (let mut x 2) (while (< x 10) (block (+= x 2))))"
+    );
+    assert_eq!(
+        folded("let x = 1\nprintln(x + 2 * 3)"),
+        "(program (let x 1) (println (+ x 6)))"
+    );
+}
+
+#[test]
+fn leaves_failing_operations_for_runtime() {
+    assert_eq!(folded("println(1 / 0)"), "(program (println (/ 1 0)))");
+    assert_eq!(folded("println(1.0 / 0)"), "(program (println (/ 1.0 0)))");
+}
+
+/// Folding must not change what any example prints
+#[test]
+fn folded_examples_print_the_same() {
+    for entry in fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("osho") {
+            continue;
+        }
+        let source = fs::read_to_string(&path).unwrap();
+        let mut ast = osho_lang::parse_and_check(&source).unwrap();
+        optimizer::optimize(&mut ast);
+        let mut interpreter = Interpreter::with_output(Vec::new());
+        interpreter.run(&ast, ast.root()).unwrap();
+        let optimized = String::from_utf8(interpreter.into_output()).unwrap();
+        assert_eq!(
+            optimized,
+            osho_lang::interpret(&source).unwrap().stdout,
+            "{}",
+            path.display()
+        );
+    }
+}
+
+/// The S-expression of a program after removing unused variables
+fn without_unused(source: &str) -> String {
+    let mut ast = osho_lang::parse_and_check(source).unwrap();
+    optimizer::remove_unused_variables(&mut ast);
+    ast_printer::to_sexpr(&ast, ast.root())
+}
+
+#[test]
+fn removes_unused_variables_and_their_assignments() {
+    let ast =
+        without_unused("let x = 1\nlet mut y = 2\ny += 1\ny++\n{\nlet x = 3\ny = x\n}\nprintln(x)");
+    assert_eq!(ast, "(program (let x 1) (block (let x 3)) (println x))");
+}
+
+#[test]
+fn removes_unused_loop_variables() {
+    let ast = without_unused("for (let mut i = 0; ; i++) {\nprintln(1)\n}");
+    assert_eq!(ast, "(program (for _ _ _ (block (println 1))))");
+}
+
+#[test]
+fn keeps_unused_variables_that_could_fail() {
+    let ast = without_unused("let y = 0\nlet x = 1 / y\nlet mut z = 1.5\nz /= y");
+    assert_eq!(
+        ast,
+        "(program (let y 0) (let x (/ 1 y)) (let mut z 1.5) (/= z y))"
+    );
+}
@@ -0,0 +1,136 @@
+use osho_lang::{ast_printer, optimizer, Backend, Checks, CodeGenerator};
+
+mod common;
+
+const TRAPPING: Checks = Checks {
+    arith: false,
+    overflow: true,
+};
+
+const WRAPS: &str = "\
+fn quotient(a: int, b: int) -> int {
+    return a / b
+}
+fn remainder(a: int, b: int) -> int {
+    return a % b
+}
+let max = 9223372036854775807
+let mut x = max
+x++
+println(max + 1, max * 2, -(max + 1), x)
+println(quotient(x, -1), remainder(x, -1), quotient(7, -2), remainder(-7, 2))";
+
+#[test]
+fn integers_wrap_around_by_default() {
+    let wrapped = "-9223372036854775808 -2 -9223372036854775808 -9223372036854775808\n\
+                   -9223372036854775808 0 -3 -1\n";
+    assert_eq!(common::run(WRAPS, Checks::default()).unwrap(), wrapped);
+    if let Some(compiled) = common::run_both("wraps", WRAPS, Checks::default()) {
+        assert_eq!(compiled.stdout, wrapped);
+    }
+
+    // Only a divisor of -1 can overflow, so other constants use C's operators
+    let c = osho_lang::compile_to_c("let mut n = 7\nn = n / 2\nn = n % -1").unwrap();
+    assert!(c.contains("n = n / 2;"), "{}", c);
+    assert!(c.contains("osho_mod(n, -1)"), "{}", c);
+
+    // The most negative int is a constant C reads as a long
+    let mut ast = osho_lang::parse_and_check("println(-9223372036854775807 - 1)").unwrap();
+    optimizer::fold_constants(&mut ast);
+    let c = CodeGenerator::new().generate(&ast).unwrap();
+    assert!(
+        c.contains("printf(\"%ld\\n\", (long)(-9223372036854775807L - 1));"),
+        "{}",
+        c
+    );
+}
+
+#[test]
+fn trapping_overflow_fails_at_the_operator() {
+    let err = common::run(
+        "let max = 9223372036854775807\nprintln(1)\nprintln(max + 1)",
+        TRAPPING,
+    )
+    .unwrap_err();
+    assert_eq!(err.message(), "The result of '+' overflows");
+    let span = err.span().unwrap();
+    assert_eq!((span.line, span.column), (3, 13));
+
+    let cases = [
+        ("let mut x = 9223372036854775807\nx++", "+"),
+        ("let x = -9223372036854775807 - 1\nprintln(x - 1)", "-"),
+        ("let x = 4611686018427387904\nprintln(x * 2)", "*"),
+        ("let x = -9223372036854775807 - 1\nprintln(x / -1)", "/"),
+        ("let x = -9223372036854775807 - 1\nprintln(x % -1)", "%"),
+        ("let x = -9223372036854775807 - 1\nprintln(-x)", "-"),
+    ];
+    for (source, symbol) in cases {
+        let err = common::run(source, TRAPPING).unwrap_err();
+        assert_eq!(
+            err.message(),
+            format!("The result of '{}' overflows", symbol),
+            "{}",
+            source
+        );
+    }
+    assert_eq!(
+        common::run("println(3 * 4 - 20 / -2)", TRAPPING).unwrap(),
+        "22\n"
+    );
+}
+
+#[test]
+fn compiled_programs_trap_overflow_like_the_interpreter() {
+    let cases = [
+        (
+            "add",
+            "let max = 9223372036854775807\nprintln(1)\nprintln(max + 1)",
+        ),
+        (
+            "mul",
+            "let x = 4611686018427387904\nprintln(1)\nprintln(x * 2)",
+        ),
+        (
+            "neg",
+            "let x = -9223372036854775807 - 1\nprintln(1)\nprintln(-x)",
+        ),
+        (
+            "div",
+            "let x = -9223372036854775807 - 1\nprintln(1)\nprintln(x / -1)",
+        ),
+        (
+            "mod",
+            "let x = -9223372036854775807 - 1\nprintln(1)\nprintln(x % -1)",
+        ),
+    ];
+    for (name, source) in cases {
+        if let Some(compiled) = common::run_both(name, source, TRAPPING) {
+            assert_eq!((compiled.stdout.as_str(), compiled.exit_code), ("1\n", 70));
+            assert_eq!(
+                compiled.error.as_deref(),
+                Some(common::run(source, TRAPPING).unwrap_err().message()),
+                "{}",
+                name
+            );
+        }
+    }
+    if let Some(compiled) = common::run_both("fits", "println(3 * 4 - 20 / -2)", TRAPPING) {
+        assert_eq!(compiled.stdout, "22\n");
+    }
+}
+
+#[test]
+fn overflowing_constants_are_left_for_the_program() {
+    let mut ast = osho_lang::parse_and_check("println(9223372036854775807 + 1, 2 + 3)").unwrap();
+    optimizer::fold_constants(&mut ast);
+    assert_eq!(
+        ast_printer::to_sexpr(&ast, ast.root()),
+        "(program (println (+ 9223372036854775807 1) 5))"
+    );
+
+    let errors = osho_lang::parse_and_check("const BIG = 9223372036854775807 * 2").unwrap_err();
+    assert_eq!(
+        errors[0].message(),
+        "Constant 'BIG' must be computable at compile time: The result of '*' overflows"
+    );
+}
@@ -0,0 +1,144 @@
+use osho_lang::ast_printer::{to_sexpr, to_tree};
+use osho_lang::{Lexer, Parser};
+
+/// Parse a program and render it as an S-expression
+fn sexpr(source: &str) -> String {
+    let tokens = Lexer::new(source).get_tokens().expect("program should lex");
+    let ast = Parser::new(&tokens).parse().expect("program should parse");
+    to_sexpr(&ast, ast.root())
+}
+
+#[test]
+fn arrays_indexing_and_calls() {
+    assert_eq!(
+        sexpr("let a = [1, 2]\na[len(a) - 1] = a[0][1]"),
+        "(program (let a (array 1 2)) ([]= a (- (call len a) 1) ([] ([] a 0) 1)))"
+    );
+}
+
+#[test]
+fn map_literals_and_strings() {
+    assert_eq!(
+        sexpr("let m = {\"a\": 1, \"b\": [2]}\nm[\"c\"] = len(keys(m))"),
+        "(program (let m (map \"a\" 1 \"b\" (array 2))) ([]= m \"c\" (call len (call keys m))))"
+    );
+}
+
+#[test]
+fn structs_and_field_access() {
+    assert_eq!(
+        sexpr("struct P { x }\nlet p = P { x: [1] }\np.x[0] = 2\nif p.x[0] > 1 {}"),
+        "(program (struct P {x}) (let p (P {x} (array 1))) ([]= (.x p) 0 2) (if (> ([] (.x p) 0) 1) (block)))"
+    );
+}
+
+#[test]
+fn functions_and_return() {
+    assert_eq!(
+        sexpr("fn f(a, b) {\n    return\n}\nfn g() {\n    return f(1, 2)\n}"),
+        "(program (fn f(a, b) (block (return))) (fn g() (block (return (call f 1 2)))))"
+    );
+}
+
+#[test]
+fn arithmetic_precedence() {
+    assert_eq!(
+        sexpr("println(1 + 2 * 3 - 4 / 2)"),
+        "(program (println (- (+ 1 (* 2 3)) (/ 4 2))))"
+    );
+}
+
+#[test]
+fn power_is_right_associative_and_binds_tighter_than_negation() {
+    assert_eq!(
+        sexpr("println(-2 ** 3 ** 2)"),
+        "(program (println (- (** 2 (** 3 2)))))"
+    );
+}
+
+#[test]
+fn negative_literals_are_folded() {
+    assert_eq!(
+        sexpr("let x = -5\nprintln(--x)"),
+        "(program (let x -5) (println (- (- x))))"
+    );
+}
+
+#[test]
+fn logical_operators_bind_looser_than_comparisons() {
+    assert_eq!(
+        sexpr("println(!a || b && 1 < 2)"),
+        "(program (println (|| (! a) (&& b (< 1 2)))))"
+    );
+}
+
+#[test]
+fn every_level_of_precedence_groups_in_order() {
+    assert_eq!(
+        sexpr("println(a || b && c == d + e * -f ** g % h ? x : y ? z : w)"),
+        "(program (println (? (|| a (&& b (== c (+ d (% (* e (- (** f g))) h))))) x (? y z w))))"
+    );
+    assert_eq!(
+        sexpr("println(a - b - c < d, a * b ** c ** -d)"),
+        "(program (println (< (- (- a b) c) d) (* a (** b (** c (- d))))))"
+    );
+}
+
+#[test]
+fn else_if_chains_nest() {
+    assert_eq!(
+        sexpr("if a { println(1) } else if b { println(2) } else { println(3) }"),
+        "(program (if a (block (println 1)) (if b (block (println 2)) (block (println 3)))))"
+    );
+}
+
+#[test]
+fn assignments_and_updates() {
+    assert_eq!(
+        sexpr("x = 1\nx += 2.0\nx++\nx--"),
+        "(program (= x 1) (+= x 2.0) (++ x) (-- x))"
+    );
+}
+
+#[test]
+fn missing_for_clauses_are_placeholders() {
+    assert_eq!(
+        sexpr("for (;;) { println(1) }"),
+        "(program (for _ _ _ (block (println 1))))"
+    );
+}
+
+#[test]
+fn tree_labels_the_parts_of_a_loop() {
+    let tokens = Lexer::new("while i < 3 { i++ }").get_tokens().unwrap();
+    let ast = Parser::new(&tokens).parse().unwrap();
+    assert_eq!(
+        to_tree(&ast, ast.root()),
+        "\
+program
+└── while
+    ├── condition: <
+    │   ├── i
+    │   └── 3
+    └── body: block
+        └── ++ i
+"
+    );
+}
+
+#[test]
+fn parsing_more_code_adds_to_an_existing_ast() {
+    let parse = |source: &str, ast| {
+        let tokens = Lexer::new(source).get_tokens().unwrap();
+        let mut parser = Parser::new(&tokens).with_ast(ast);
+        let statements = parser.parse_statements().unwrap();
+        (parser.into_ast(), statements)
+    };
+    let (ast, first) = parse("fn double(x) { return x * 2 }", Default::default());
+    let (ast, second) = parse("let y = double(3)", ast);
+    assert_eq!(
+        to_sexpr(&ast, first[0]),
+        "(fn double(x) (block (return (* x 2))))"
+    );
+    assert_eq!(to_sexpr(&ast, second[0]), "(let y (call double 3))");
+}
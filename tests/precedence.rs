@@ -0,0 +1,64 @@
+use osho_lang::Checks;
+
+mod common;
+
+/// Check the interpreter prints `expected`, and that the compiled program agrees
+fn assert_output(name: &str, source: &str, expected: &str) {
+    let interpreted = osho_lang::interpret(source).expect("program should run");
+    assert_eq!(
+        interpreted.stdout, expected,
+        "interpreter output of {}",
+        name
+    );
+    common::run_both(name, source, Checks::default());
+}
+
+#[test]
+fn multiplication_binds_tighter_than_addition() {
+    assert_output(
+        "mul_add",
+        "println(1 + 2 * 3)\nprintln(2 * 3 + 1)",
+        "7\n7\n",
+    );
+}
+
+#[test]
+fn division_binds_tighter_than_subtraction() {
+    assert_output(
+        "div_sub",
+        "println(10 - 6 / 2)\nprintln(6 / 2 - 10)",
+        "7\n-7\n",
+    );
+}
+
+#[test]
+fn same_level_operators_are_left_associative() {
+    assert_output(
+        "left_assoc",
+        "println(10 - 4 - 3)\nprintln(24 / 4 / 2)\nprintln(8 / 2 * 4)",
+        "3\n3\n16\n",
+    );
+}
+
+#[test]
+fn parentheses_override_precedence() {
+    assert_output(
+        "parens",
+        "println((1 + 2) * 3)\nprintln(2 * (3 - 5))",
+        "9\n-4\n",
+    );
+}
+
+#[test]
+fn arithmetic_binds_tighter_than_comparison() {
+    assert_output(
+        "comparison",
+        "println(1 + 2 * 3 == 7)\nprintln(2 * 3 < 1 + 4)",
+        "true\nfalse\n",
+    );
+}
+
+#[test]
+fn modulo_binds_like_multiplication() {
+    assert_output("modulo", "println(1 + 7 % 4)\nprintln(7 % 4 * 2)", "4\n6\n");
+}
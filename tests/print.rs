@@ -0,0 +1,64 @@
+const PROGRAM: &str = "\
+let x = 3
+let y = 1.5
+println(x, y, x > 2)
+println \"x = {}, y = {} {{braces}} 100%\", x, y
+println(\"sum: {}\", x + y)
+println()";
+
+const OUTPUT: &str = "3 1.5 true\nx = 3, y = 1.5 {braces} 100%\nsum: 4.5\n\n";
+
+#[test]
+fn prints_several_values_and_format_strings() {
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, OUTPUT);
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, OUTPUT);
+}
+
+#[test]
+fn c_builds_a_printf_format_from_the_argument_types() {
+    let c = osho_lang::compile_to_c(PROGRAM).unwrap();
+    assert!(
+        c.contains("printf(\"%ld %s %s\\n\", (long)x, osho_float((char[OSHO_FLOAT_SIZE]){0}, y), _t0 ? \"true\" : \"false\");"),
+        "{}",
+        c
+    );
+    assert!(
+        c.contains("printf(\"x = %ld, y = %s {braces} 100%%\\n\", (long)x, osho_float((char[OSHO_FLOAT_SIZE]){0}, y));"),
+        "{}",
+        c
+    );
+}
+
+#[test]
+fn format_strings_need_a_value_per_placeholder() {
+    let errors = osho_lang::parse_and_check("print \"{} {}\", 1\nprint(\"{\")").unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|err| err.message()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Format string has 2 placeholders but 1 value was given",
+            "Unmatched '{' in format string, use '{{' to print it"
+        ]
+    );
+}
+
+#[test]
+fn print_leaves_the_line_open_and_strings_understand_escapes() {
+    let source = r#"print "a\tb"
+print(1, 2)
+println " \"quoted\" \\ {}\n", 3"#;
+    let output = "a\tb1 2 \"quoted\" \\ 3\n\n";
+    assert_eq!(osho_lang::interpret(source).unwrap().stdout, output);
+    assert_eq!(osho_lang::run_vm(source).unwrap().stdout, output);
+
+    let c = osho_lang::compile_to_c(source).unwrap();
+    assert!(c.contains(r#"printf("a\tb");"#), "{}", c);
+    assert!(
+        c.contains(r#"printf("%ld %ld", (long)1, (long)2);"#),
+        "{}",
+        c
+    );
+
+    let errors = osho_lang::parse_and_check(r#"print "\q""#).unwrap_err();
+    assert_eq!(errors[0].message(), "Unknown escape sequence '\\q'");
+}
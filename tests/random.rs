@@ -0,0 +1,60 @@
+const PROGRAM: &str = "\
+seed(42)
+let first = rand_range(0, 100)
+let x = rand()
+seed(42)
+println(rand_range(0, 100) == first, rand() == x, x >= 0.0, x < 1.0)
+let mut counts = [0, 0, 0]
+for (let mut i = 0; i < 300; i++) {
+    let roll = rand_range(-1, 2)
+    counts[roll + 1] = counts[roll + 1] + 1
+}
+println(counts)";
+
+#[test]
+fn seeds_repeat_the_same_numbers_in_both_interpreters() {
+    let interpreted = osho_lang::interpret(PROGRAM).unwrap().stdout;
+    assert!(
+        interpreted.starts_with("true true true true\n"),
+        "{}",
+        interpreted
+    );
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, interpreted);
+}
+
+#[test]
+fn random_builtins_are_checked() {
+    let source = "\
+seed(1.5)
+let r = rand(1)
+let n = rand_range(0, 2.5)
+let s = seed(1)";
+    let errors = osho_lang::parse_and_check(source).unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|err| err.message()).collect();
+    assert_eq!(
+        messages,
+        [
+            "seed() expects an int, found float",
+            "rand() takes 0 arguments but 1 was given",
+            "rand_range() expects an int, found float",
+            "seed() doesn't return a value"
+        ]
+    );
+    let err = osho_lang::interpret("println(rand_range(3, 3))").unwrap_err();
+    assert_eq!(
+        err.message(),
+        "rand_range(3, 3) has no numbers to pick from"
+    );
+}
+
+#[test]
+fn random_numbers_come_from_a_c_helper() {
+    let c = osho_lang::compile_to_c(PROGRAM).unwrap();
+    assert!(c.contains("#include <stdint.h>\n"), "{}", c);
+    assert!(
+        c.contains("static long osho_rand_range(long low, long high)"),
+        "{}",
+        c
+    );
+    assert!(c.contains("osho_seed(42);\n"), "{}", c);
+}
@@ -0,0 +1,104 @@
+use osho_lang::{ast_printer, formatter, Checks, Lexer, Parser};
+
+mod common;
+
+const PROGRAM: &str = "\
+let mut sum = 0
+for i in 0..5 {
+    sum += i
+}
+println(sum)
+let mut n = 3
+for i in 1..=n {
+    n = 10
+    if i == 2 { continue }
+    print(i)
+}
+println()
+for i in 10..3 {
+    println(\"never\")
+}
+for i in 0..100 {
+    if i * i > 20 { break }
+    print(i)
+}
+println()
+let max = 9223372036854775807
+for i in max - 1..=max {
+    println(i)
+}";
+
+const OUTPUT: &str = "10\n13\n01234\n9223372036854775806\n9223372036854775807\n";
+
+#[test]
+fn for_in_loops_count_through_the_range() {
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, OUTPUT);
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, OUTPUT);
+}
+
+#[test]
+fn compiled_for_in_loops_agree_with_the_interpreter() {
+    let trapping = Checks {
+        arith: false,
+        overflow: true,
+    };
+    common::run_both("ranges", PROGRAM, trapping);
+
+    let c = osho_lang::compile_to_c("for i in 0..3 { println(i) }").unwrap();
+    assert!(c.contains("for (long i = 0; i < 3; i++) {\n"), "{}", c);
+    let js = osho_lang::compile_to_js("for i in 0..=3 { println(i) }").unwrap();
+    assert!(
+        js.contains("for (let i = 0, i$end = 3; i <= i$end; i++) {"),
+        "{}",
+        js
+    );
+    let wat = osho_lang::compile_to_wasm("let mut n = 0\nfor i in 0..3 { n += i }").unwrap();
+    assert!(wat.contains("(br_if $break_1 (i64.ge_s"), "{}", wat);
+}
+
+#[test]
+fn for_in_loops_parse_and_format() {
+    let source = "for i in 0..=n+1{println(i)}";
+    let tokens = Lexer::new(source).get_tokens().unwrap();
+    let mut parser = Parser::new(&tokens);
+    let statements = parser.parse_statements().unwrap();
+    let ast = parser.into_ast();
+    assert_eq!(
+        ast_printer::to_sexpr(&ast, statements[0]),
+        "(for i in (..= 0 (+ n 1)) (block (println i)))"
+    );
+    assert_eq!(
+        formatter::format(source).unwrap(),
+        "for i in 0..=n + 1 {\n    println(i)\n}\n"
+    );
+}
+
+#[test]
+fn for_in_loops_are_checked() {
+    let cases = [
+        (
+            "for i in 0..1.5 {}",
+            "Range bounds must be ints, found float",
+        ),
+        (
+            "for i 0..3 {}",
+            "Expected 'in' after the variable of a for-in loop",
+        ),
+        (
+            "for i in 3 {}",
+            "Expected '..' or '..=' in the range of a for-in loop",
+        ),
+        (
+            "for i in 0..3 { i = 1 }",
+            "Cannot assign to immutable variable 'i', declare it with `let mut i`",
+        ),
+        (
+            "for i in 0..3 {}\nprintln(i)",
+            "Variable 'i' is not declared",
+        ),
+    ];
+    for (source, message) in cases {
+        let errors = osho_lang::parse_and_check(source).unwrap_err();
+        assert_eq!(errors[0].message(), message, "{}", source);
+    }
+}
@@ -0,0 +1,70 @@
+use osho_lang::{bytecode, Interpreter, Vm};
+
+const PROGRAM: &str = "\
+fn is_even(n) {
+    if n == 0 {
+        return true
+    }
+    return is_odd(n - 1)
+}
+fn is_odd(n) {
+    if n == 0 {
+        return false
+    }
+    return is_even(n - 1)
+}
+fn fact(n) {
+    if n <= 1 {
+        return 1
+    }
+    return n * fact(n - 1)
+}
+println(is_even(10), is_odd(7), fact(10))";
+
+#[test]
+fn functions_call_themselves_and_each_other() {
+    let output = "true true 3628800\n";
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, output);
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, output);
+}
+
+#[test]
+fn deep_recursion_stops_at_the_call_depth_limit() {
+    let source = "\
+fn down(n) {
+    if n == 0 {
+        return 0
+    }
+    return down(n - 1)
+}
+println(down(-1))";
+    let ast = osho_lang::parse_and_check(source).unwrap();
+    let message = "Call depth exceeded 20 nested calls, possible infinite recursion";
+
+    let err = Interpreter::with_output(Vec::new())
+        .with_max_call_depth(20)
+        .run(&ast, ast.root())
+        .unwrap_err();
+    assert_eq!(err.message(), message);
+
+    let chunk = bytecode::Compiler::new().compile(&ast).unwrap();
+    let err = Vm::with_output(Vec::new())
+        .with_max_call_depth(20)
+        .run(&chunk)
+        .unwrap_err();
+    assert_eq!(err.message(), message);
+}
+
+#[test]
+fn mutually_recursive_c_functions_are_declared_first() {
+    let c = osho_lang::compile_to_c(PROGRAM).unwrap();
+    let prototypes = "static bool osho_fn_is_even(long n);\nstatic bool osho_fn_is_odd(long n);\nstatic long osho_fn_fact(long n);\n";
+    let prototype = c.find(prototypes).unwrap_or_else(|| panic!("{}", c));
+    let definition = c.find("static bool osho_fn_is_even(long n) {").unwrap();
+    assert!(prototype < definition, "{}", c);
+    assert!(
+        c.contains("return osho_fn_is_odd") || c.contains("= osho_fn_is_odd(_t"),
+        "{}",
+        c
+    );
+}
@@ -0,0 +1,156 @@
+use std::fs;
+
+use osho_lang::verify;
+use osho_lang::Checks;
+
+mod common;
+
+const PROGRAM: &str = "\
+struct Node { val, children }
+fn leaf(v: int) -> Node {
+    let none: [Node] = []
+    return Node { val: v, children: none }
+}
+fn total(n: Node) -> int {
+    let mut sum = n.val
+    for i in 0..len(n.children) {
+        sum = sum + total(n.children[i])
+    }
+    return sum
+}
+fn squares(n: int) -> [int] {
+    let mut out: [int] = []
+    for i in 0..n {
+        push(out, i * i)
+    }
+    return out
+}
+let tree = Node { val: 1, children: [leaf(2), Node { val: 3, children: [leaf(4)] }] }
+println(total(tree))
+squares(3)
+let mut a = squares(5)
+a = a[1:]
+let mut rows: [[int]] = []
+let mut i = 0
+while i < 10 {
+    i = i + 1
+    let row = squares(i)
+    if i % 2 == 0 {
+        continue
+    }
+    if i > 7 {
+        break
+    }
+    push(rows, row)
+}
+let last = pop(rows)
+rows[0] = last
+rows[1] = rows[0]
+println(a, rows, last)";
+
+const OUTPUT: &str = "\
+10
+[1, 4, 9, 16] [[0, 1, 4, 9, 16, 25, 36], [0, 1, 4, 9, 16, 25, 36], [0, 1, 4, 9, 16]] [0, 1, 4, 9, 16, 25, 36]
+";
+
+#[test]
+fn compiled_references_match_the_interpreter() {
+    if let Some(compiled) = common::run_both("refcount", PROGRAM, Checks::default()) {
+        assert_eq!(compiled.stdout, OUTPUT);
+    }
+}
+
+#[test]
+fn arrays_and_structs_are_released_when_their_last_reference_goes() {
+    let c = osho_lang::compile_to_c(PROGRAM).unwrap();
+    // Elements and fields are released along with what holds them
+    assert!(
+        c.contains("for (long i = 0; i < value->len; i++) osho_release_Node(((struct Node **)value->data)[i]);"),
+        "{}",
+        c
+    );
+    assert!(
+        c.contains("    osho_release_Node_array(value->children);\n"),
+        "{}",
+        c
+    );
+    // A function's result is already held by the variable it's put in
+    assert!(
+        c.contains("osho_array *_t18 = osho_fn_squares(3);\nosho_array *_t19"),
+        "{}",
+        c
+    );
+    // The old value goes once the new one is held
+    assert!(c.contains("{\nosho_array *osho_old = a;\na = _t"), "{}", c);
+    assert!(
+        c.contains("osho_retain(a);\nosho_release_int_array(osho_old);\n}"),
+        "{}",
+        c
+    );
+    // A returned array outlives the function's own references to it
+    assert!(
+        c.contains("osho_retain(out);\nosho_release_int_array(out);\n"),
+        "{}",
+        c
+    );
+
+    // Programs without arrays or structs are unchanged
+    let c = osho_lang::compile_to_c("let mut n = 0\nwhile n < 3 {\n    n = n + 1\n}").unwrap();
+    assert!(!c.contains("osho_retain"), "{}", c);
+    assert!(!c.contains("osho_release"), "{}", c);
+}
+
+const STRINGS: &str = "\
+struct Person { name, tags }
+fn shout(s: string) -> string {
+    let loud = upper(s)
+    return loud
+}
+let mut words = split(\"ant,bee,cat\", \",\")
+let mut people: [Person] = []
+for i in 0..3 {
+    let name = shout(words[i])
+    push(people, Person { name: name, tags: words[i:] })
+    words[i] = lower(name)
+}
+let mut label = str(len(people))
+label = str(2.5)
+label = substring(\"hello\", 1, 3)
+label = label[1:]
+let last = pop(people)
+people[0].name = str(true)
+println(words, people, last, label, env(\"OSHO_UNSET\", \"none\"))";
+
+#[test]
+fn compiled_strings_are_freed_with_their_last_reference() {
+    let Some(cc) = common::c_compiler() else {
+        return;
+    };
+    // AddressSanitizer reports whatever is never freed when the program ends
+    let cc = cc.with_flags("-fsanitize=address");
+    let dir = std::env::temp_dir().join(format!("osho-strings-{}", std::process::id()));
+    let run = |source: &str| {
+        let ast = osho_lang::parse_and_check(source).unwrap();
+        verify::run_compiled(&ast, &cc, Checks::default(), &dir, b"")
+    };
+    if let Err(err) = run("println(1)") {
+        eprintln!(
+            "note: not checking for leaks without AddressSanitizer: {}",
+            err
+        );
+        fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+    let compiled = run(STRINGS);
+    fs::remove_dir_all(&dir).unwrap();
+    let compiled = compiled.unwrap();
+    assert_eq!(compiled.error, None);
+    assert_eq!(
+        compiled.stdout,
+        "[\"ant\", \"bee\", \"cat\"] [Person { name: \"true\", tags: [\"ant\", \"bee\", \"cat\"] }, Person { name: \"BEE\", tags: [\"bee\", \"cat\"] }] Person { name: \"CAT\", tags: [\"cat\"] } l none\n"
+    );
+
+    // Literals are never freed
+    let c = osho_lang::compile_to_c(STRINGS).unwrap();
+    assert!(c.contains("osho_text_2 = {-1, \"hello\"};"), "{}", c);
+}
@@ -0,0 +1,70 @@
+use std::fs;
+
+use osho_lang::ast_printer::{to_sexpr, to_source};
+use osho_lang::{Ast, Lexer, Parser};
+
+fn parse(source: &str) -> Ast {
+    Parser::from_lexer(Lexer::new(source))
+        .parse()
+        .unwrap_or_else(|errors| panic!("{}\n{}", errors[0], source))
+}
+
+fn sexpr(ast: &Ast) -> String {
+    to_sexpr(ast, ast.root())
+}
+
+/// Print the program as source and check it parses back to the same tree
+fn assert_round_trips(source: &str) {
+    let ast = parse(source);
+    let printed = to_source(&ast, ast.root());
+    assert_eq!(sexpr(&parse(&printed)), sexpr(&ast), "\n{}", printed);
+}
+
+#[test]
+fn examples_round_trip() {
+    let mut checked = 0;
+    for entry in fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        assert_round_trips(&fs::read_to_string(&path).unwrap());
+        checked += 1;
+    }
+    assert!(checked > 0, "no examples found");
+}
+
+#[test]
+fn operands_keep_their_grouping() {
+    assert_round_trips("let x = (1 - 2) - (3 - 4) * -(5 ** 2) ** 2");
+    assert_round_trips("let y = -2 ** 2 + (-2) ** 2 + (-1.5) * 2.0");
+    assert_round_trips("let z = !(true && false) ? (1 > 2 ? 3 : 4) : [1, 2][0]");
+    assert_round_trips("let s = (\"a\" + \"b\").len");
+}
+
+#[test]
+fn statements_and_literals_round_trip() {
+    assert_round_trips(
+        "struct P { x, y }\n\
+         fn f(p: P, xs: [int]) -> {string: float} {\n\
+             if (P { x: 1, y: 2 }).x == p.x { return {\"a\\\"\": 1.0} }\n\
+             return {}\n\
+         }\n\
+         let mut i = 0\n\
+         for (let mut j = 0; j < 3; j++) { i += j continue }\n\
+         while i > 0 { i-- if i == 1 { break } else if i == 2 { } else { i = 0 } }\n\
+         print(\"x = {}\\n\", i)\n\
+         println(\"tab\\t\", \"pct ${i + 1} \\${not} end\")\n\
+         input i\n\
+         let mut a = [[1], [2]]\n\
+         a[0][0] = 3\n",
+    );
+}
+
+#[cfg(feature = "program-generator")]
+proptest::proptest! {
+    #![proptest_config(proptest::test_runner::Config::with_cases(10_000))]
+
+    #[test]
+    fn generated_programs_round_trip(ast in osho_lang::generator::program()) {
+        let printed = to_source(&ast, ast.root());
+        proptest::prop_assert_eq!(sexpr(&parse(&printed)), sexpr(&ast), "\n{}", printed);
+    }
+}
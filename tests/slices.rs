@@ -0,0 +1,108 @@
+use osho_lang::ast_printer::{to_sexpr, to_source};
+use osho_lang::{Checks, Lexer, Parser};
+
+mod common;
+
+const PROGRAM: &str = "\
+let a = [1, 2, 3, 4, 5]
+let mut b = a[1:4]
+b[0] = 20
+let f = [1.5, 2.5][:1]
+println(a, b, a[:2], a[3:], a[:], a[2:2], f)
+let s = \"héllo\"
+println(s[1:3], s[:1], s[4:], len(s[0:0]))
+let grid = [[1, 2], [3, 4]]
+println(grid[1][0:1], grid[:1][0], a[len(a) - 2:])
+println(a[3:9])";
+
+const OUTPUT: &str = "\
+[1, 2, 3, 4, 5] [20, 3, 4] [1, 2] [4, 5] [1, 2, 3, 4, 5] [] [1.5]
+él h o 0
+[3] [1, 2] [4, 5]
+";
+
+#[test]
+fn slices_are_parsed_and_printed() {
+    let source = "let a = [1, 2]\nprintln(a[1:], a[:1][0], a[:], a[0:c ? 1 : 2])";
+    let ast = Parser::from_lexer(Lexer::new(source)).parse().unwrap();
+    assert_eq!(
+        to_sexpr(&ast, ast.root()),
+        "(program (let a (array 1 2)) (println ([:] a 1 _) ([] ([:] a _ 1) 0) ([:] a _ _) ([:] a 0 (? c 1 2))))"
+    );
+    assert_eq!(to_source(&ast, ast.root()), format!("{}\n", source));
+
+    let err = Parser::from_lexer(Lexer::new("println(a[1:2:3])"))
+        .parse()
+        .unwrap_err();
+    assert_eq!(err[0].message(), "Expected ']' after slice");
+}
+
+#[test]
+fn slices_copy_the_elements_between_their_bounds() {
+    for result in [osho_lang::interpret(PROGRAM), osho_lang::run_vm(PROGRAM)] {
+        let err = result.unwrap_err();
+        assert_eq!(
+            err.message(),
+            "Slice 3:9 is out of range for an array of length 5"
+        );
+        assert_eq!(err.exit_code(), 70);
+    }
+    for (source, message) in [
+        (
+            "let a = [1, 2]\nprintln(a[2:1])",
+            "Slice 2:1 is out of range for an array of length 2",
+        ),
+        (
+            "let s = \"ab\"\nprintln(s[-1:])",
+            "Slice -1:2 is out of range for a string of length 2",
+        ),
+    ] {
+        let err = osho_lang::run_vm(source).unwrap_err();
+        assert_eq!(err.message(), message, "{}", source);
+    }
+}
+
+#[test]
+fn compiled_slices_match_the_interpreter() {
+    if let Some(compiled) = common::run_both("slices", PROGRAM, Checks::default()) {
+        assert_eq!(compiled.stdout, OUTPUT);
+        assert_eq!(
+            compiled.error.as_deref(),
+            Some("Slice 3:9 is out of range for an array of length 5")
+        );
+    }
+
+    let c = osho_lang::compile_to_c("let a = [1.5]\nprintln(a[1:])").unwrap();
+    assert!(c.contains("osho_slice(a, 1, _t1, sizeof(double))"), "{}", c);
+    assert!(!c.contains("osho_str_slice("), "{}", c);
+
+    let js = osho_lang::compile_to_js("let s = \"ab\"\nprintln(s[:1], [1, 2][1:])").unwrap();
+    assert!(
+        js.contains("console.log(oshoSlice(s, 0, 1), oshoShow(oshoSlice([1, 2], 1)));"),
+        "{}",
+        js
+    );
+}
+
+#[test]
+fn slices_are_checked() {
+    let cases = [
+        ("let n = 1\nprintln(n[0:1])", "Cannot slice int"),
+        (
+            "let m = {\"a\": 1}\nprintln(m[:1])",
+            "Cannot slice {string: int}",
+        ),
+        (
+            "let a = [1]\nprintln(a[0:true])",
+            "Slice bounds must be ints, found bool",
+        ),
+        (
+            "let a = [1]\nlet b: int = a[:]",
+            "Variable 'b' is int, found [int]",
+        ),
+    ];
+    for (source, message) in cases {
+        let errors = osho_lang::parse_and_check(source).unwrap_err();
+        assert_eq!(errors[0].message(), message, "{}", source);
+    }
+}
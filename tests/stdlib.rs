@@ -0,0 +1,59 @@
+use std::path::Path;
+
+use osho_lang::{stdlib, Interpreter, LintLevels, Loader, SemanticAnalyzer};
+
+fn run(source: &str) -> String {
+    let ast = Loader::new().load(Path::new("main.osho"), source).unwrap();
+    SemanticAnalyzer::new().analyze_all(&ast).unwrap();
+    let mut interpreter = Interpreter::with_output(Vec::new());
+    interpreter.run(&ast, ast.root()).unwrap();
+    String::from_utf8(interpreter.into_output()).unwrap()
+}
+
+#[test]
+fn math_helpers() {
+    let output = run("import std.math\n\
+         println(math.gcd(12, -18), math.lcm(4, 6), math.factorial(5))\n\
+         println(math.clamp(15, 0, 10), math.sign(-3), math.is_even(4))");
+    assert_eq!(output, "6 12 120\n10 -1 true\n");
+}
+
+#[test]
+fn list_helpers() {
+    let output = run("import std.list\n\
+         let xs = [3, 1, 4, 1, 5]\n\
+         println(list.sum(xs), list.product(xs), list.largest(xs))\n\
+         println(list.index_of(xs, 4), list.contains(xs, 9), list.reversed(xs)[0])");
+    assert_eq!(output, "14 60 5\n2 false 5\n");
+}
+
+#[test]
+fn every_file_checks_and_is_imported_once() {
+    for file in stdlib::files() {
+        let name = file.trim_end_matches(".osho").replace('/', ".");
+        let mut loader = Loader::new();
+        let source = format!("import {}\nimport \"{}\"", name, file);
+        let ast = loader.load(Path::new("main.osho"), &source).unwrap();
+        SemanticAnalyzer::new().analyze_all(&ast).unwrap();
+        assert_eq!(loader.sources().files().len(), 2, "{}", file);
+    }
+}
+
+#[test]
+fn unused_pub_functions_of_imported_modules_are_not_reported() {
+    let source = "import std.math\n\
+         module shapes {\n\
+             pub fn area(w: int, h: int) -> int {\n return w * h\n }\n\
+             fn unused(w: int) -> int {\n return w\n }\n\
+         }\n\
+         println(math.gcd(4, 6))";
+    let ast = Loader::new().load(Path::new("main.osho"), source).unwrap();
+    let mut analyzer = SemanticAnalyzer::new();
+    analyzer.analyze_all(&ast).unwrap();
+    let warnings: Vec<_> = LintLevels::default()
+        .apply(analyzer.take_warnings())
+        .into_iter()
+        .map(|warning| warning.message)
+        .collect();
+    assert_eq!(warnings, ["Function 'shapes.unused' is never called"]);
+}
@@ -0,0 +1,113 @@
+use osho_lang::Checks;
+
+mod common;
+
+const PROGRAM: &str = "\
+let text = \"Hello, wörld\"
+println(len(text), substring(text, 7, 12), find(text, \"wö\"), find(text, \"xyz\"))
+let words = split(\"a,b,,c\", \",\")
+println(words, len(words), upper(text), lower(\"ABC def\"))
+for i in 0..len(words) {
+    println(i, words[i], len(words[i]))
+}
+println(substring(text, 3, 20))";
+
+const OUTPUT: &str = "\
+12 wörld 7 -1
+[\"a\", \"b\", \"\", \"c\"] 4 HELLO, WöRLD abc def
+0 a 1
+1 b 1
+2  0
+3 c 1
+";
+
+#[test]
+fn strings_are_measured_cut_searched_and_split() {
+    for result in [osho_lang::interpret(PROGRAM), osho_lang::run_vm(PROGRAM)] {
+        let err = result.unwrap_err();
+        assert_eq!(
+            err.message(),
+            "substring(3, 20) is out of range for a string of length 12"
+        );
+        assert_eq!(err.exit_code(), 70);
+    }
+    let cases = [
+        (
+            "println(split(\"abc\", \"\"))",
+            "split() separator can't be empty",
+        ),
+        (
+            "println(substring(\"abc\", 2, 1))",
+            "substring(2, 1) is out of range for a string of length 3",
+        ),
+    ];
+    for (source, message) in cases {
+        let err = osho_lang::interpret(source).unwrap_err();
+        assert_eq!(err.message(), message, "{}", source);
+    }
+    assert_eq!(
+        osho_lang::interpret("println(split(\"\", \",\"), find(\"ab\", \"\"))")
+            .unwrap()
+            .stdout,
+        "[\"\"] 0\n"
+    );
+}
+
+#[test]
+fn compiled_strings_match_the_interpreter() {
+    if let Some(compiled) = common::run_both("strings", PROGRAM, Checks::default()) {
+        assert_eq!(compiled.stdout, OUTPUT);
+        assert_eq!(
+            compiled.error.as_deref(),
+            Some("substring(3, 20) is out of range for a string of length 12")
+        );
+    }
+
+    let c = osho_lang::compile_to_c("println(upper(\"a\"))").unwrap();
+    assert!(c.contains("osho_upper(_t0)"), "{}", c);
+    assert!(!c.contains("osho_split"), "{}", c);
+
+    let js = osho_lang::compile_to_js("let s = \"ab\"\nprintln(len(s), upper(s), find(s, \"b\"))")
+        .unwrap();
+    assert!(
+        js.contains(
+            "console.log(Array.from(s).length, s.replace(/[a-z]+/g, (letters) => letters.toUpperCase()), oshoFind(s, \"b\"));"
+        ),
+        "{}",
+        js
+    );
+}
+
+#[test]
+fn string_builtins_are_checked() {
+    let cases = [
+        (
+            "println(len(1))",
+            "len() expects an array or a string, found int",
+        ),
+        (
+            "println(substring(\"abc\", \"1\", 2))",
+            "substring() expects an int, found string",
+        ),
+        (
+            "println(find(1, \"a\"))",
+            "find() expects a string, found int",
+        ),
+        (
+            "println(split(\"a b\"))",
+            "split() takes 2 arguments but 1 was given",
+        ),
+        (
+            "let parts: [int] = split(\"a b\", \" \")",
+            "Variable 'parts' is [int], found [string]",
+        ),
+        (
+            "println(upper(true))",
+            "upper() expects a string, found bool",
+        ),
+    ];
+    for (source, message) in cases {
+        let errors = osho_lang::parse_and_check(source).unwrap_err();
+        assert_eq!(errors[0].message(), message, "{}", source);
+    }
+}
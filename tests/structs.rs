@@ -0,0 +1,66 @@
+const PROGRAM: &str = "\
+struct Point { x, y }
+struct Line { from, to }
+let mut p = Point { y: 2.5, x: 1 }
+let l = Line { from: p, to: Point { x: 3, y: 4.0 } }
+p.x = 7
+println(p, l.from.x + l.to.x)
+println(l)";
+
+const OUTPUT: &str =
+    "Point { x: 7, y: 2.5 } 10\nLine { from: Point { x: 7, y: 2.5 }, to: Point { x: 3, y: 4 } }\n";
+
+#[test]
+fn structs_are_shared_and_printed_in_declaration_order() {
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, OUTPUT);
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, OUTPUT);
+}
+
+#[test]
+fn structs_are_type_checked() {
+    let source = "\
+struct P { a, b }
+struct P { c }
+struct Q { a, a }
+let p = P { a: 1 }
+let p2 = P { a: 1, b: true, c: 3 }
+let p3 = P { a: 1, b: true }
+let p4 = P { a: 1.5, b: true }
+println(p3.c)
+p3.a = 2
+let mut p5 = p3
+p5.b = 1
+if p3 {}";
+    let errors = osho_lang::parse_and_check(source).unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|err| err.message()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Struct 'P' is already declared",
+            "Field 'a' is declared twice in struct 'Q'",
+            "Missing field 'b' in P",
+            "Struct 'P' has no field 'c'",
+            "Field 'a' of P is int, found float",
+            "Struct 'P' has no field 'c'",
+            "Cannot assign to immutable variable 'p3', declare it with `let mut p3`",
+            "Cannot assign int to field 'b' of P, which is bool",
+            "Cannot use P as a condition"
+        ]
+    );
+}
+
+#[test]
+fn structs_become_c_structs() {
+    let c = osho_lang::compile_to_c(PROGRAM).unwrap();
+    assert!(
+        c.contains("struct Point {\n    long osho_refs;\n    long x;\n    double y;\n};"),
+        "{}",
+        c
+    );
+    assert!(c.contains("osho_new_Point(1, 2.5)"), "{}", c);
+    assert!(c.contains("p->x = 7;"), "{}", c);
+    assert!(c.contains("osho_print_Line(l);"), "{}", c);
+
+    let wasm = osho_lang::compile_to_wasm(PROGRAM).unwrap_err();
+    assert_eq!(wasm.message(), "The wasm target doesn't support structs");
+}
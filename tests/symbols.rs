@@ -0,0 +1,69 @@
+use osho_lang::{SemanticAnalyzer, Value};
+
+#[test]
+fn symbol_table_counts_reads_and_writes() {
+    let ast =
+        osho_lang::parse_and_check("let mut x = 1\nx += 2\nx++\n{\nlet y = x * x\n}").unwrap();
+    let mut analyzer = SemanticAnalyzer::new();
+    analyzer.analyze_all(&ast).unwrap();
+
+    let symbols: Vec<_> = analyzer
+        .symbols()
+        .iter()
+        .map(|symbol| {
+            (
+                symbol.name.as_str(),
+                symbol.ty.to_string(),
+                symbol.reads,
+                symbol.writes,
+            )
+        })
+        .collect();
+    assert_eq!(
+        symbols,
+        [
+            ("x", "int".to_string(), 2, 2),
+            ("y", "int".to_string(), 0, 0)
+        ]
+    );
+    assert_eq!(analyzer.symbols().iter().next().unwrap().span.line, 1);
+}
+
+#[test]
+fn only_mutable_variables_can_be_assigned() {
+    let errors = osho_lang::parse_and_check("let x = 1\nx = 2").unwrap_err();
+    assert_eq!(
+        errors[0].message(),
+        "Cannot assign to immutable variable 'x', declare it with `let mut x`"
+    );
+    assert_eq!(errors[0].span().unwrap().line, 2);
+
+    let c = osho_lang::compile_to_c("let x = 1\nlet mut y = x\ny++\nprintln(y)").unwrap();
+    assert!(c.contains("const long x = 1;"), "{}", c);
+    assert!(c.contains("\nlong y = x;"), "{}", c);
+}
+
+#[test]
+fn constants_are_evaluated_during_analysis() {
+    let source = "const N = 2 * 3\nconst HALF = N / 4.0\nprintln(N * HALF)";
+    let ast = osho_lang::parse_and_check(source).unwrap();
+    let mut analyzer = SemanticAnalyzer::new();
+    analyzer.analyze_all(&ast).unwrap();
+    let values: Vec<_> = analyzer
+        .symbols()
+        .iter()
+        .map(|symbol| symbol.constant.clone())
+        .collect();
+    assert_eq!(values, [Some(Value::Int(6)), Some(Value::Float(1.5))]);
+
+    // The C code uses the values instead of variables
+    let c = osho_lang::compile_to_c(source).unwrap();
+    assert!(c.contains("double _t0 = 6.0 * 1.5;"), "{}", c);
+    assert!(!c.contains("HALF"), "{}", c);
+
+    let errors = osho_lang::parse_and_check("let x = 1\nconst y = x + 1").unwrap_err();
+    assert_eq!(
+        errors[0].message(),
+        "Constant 'y' must be computable at compile time: 'x' is not a constant"
+    );
+}
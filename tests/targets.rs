@@ -0,0 +1,16 @@
+use osho_lang::backend::{self, TARGETS};
+
+#[test]
+fn every_target_generates_code() {
+    for target in &TARGETS {
+        let code = osho_lang::compile("let x = 2\nprintln(x * 3)", target).unwrap();
+        assert!(!code.is_empty(), "{}", target.name);
+    }
+}
+
+#[test]
+fn targets_are_looked_up_by_name() {
+    assert_eq!(backend::target("wasm").unwrap().extension, "wat");
+    let err = backend::target("llvm").err().unwrap();
+    assert_eq!(err, "Unknown target 'llvm', expected one of c, wasm, js");
+}
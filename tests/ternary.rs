@@ -0,0 +1,50 @@
+use osho_lang::ast_printer;
+
+const PROGRAM: &str = "\
+let xs = [1, 2, 3]
+let mut i = 0
+while i < 5 {
+    print(i < len(xs) ? xs[i] : 0)
+    i++
+}
+let a = 3
+let f = a > 10 ? 1.5 : a > 2 ? 2.5 : 3.5
+println()
+println(f, a ? a * 2 : -1)";
+
+const OUTPUT: &str = "12300\n2.5 6\n";
+
+#[test]
+fn conditionals_evaluate_only_the_chosen_branch() {
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, OUTPUT);
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, OUTPUT);
+    let tokens = osho_lang::Lexer::new("let x = a || b ? 1 : c ? 2 : 3")
+        .get_tokens()
+        .unwrap();
+    let ast = osho_lang::Parser::new(&tokens).parse().unwrap();
+    assert_eq!(
+        ast_printer::to_sexpr(&ast, ast.root()),
+        "(program (let x (? (|| a b) 1 (? c 2 3))))"
+    );
+}
+
+#[test]
+fn branches_must_have_the_same_type() {
+    let errors =
+        osho_lang::parse_and_check("let x = true ? 1 : 2.5\nlet y = \"s\" ? 1 : 2").unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|err| err.message()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Branches of '?:' must have the same type, found int and float",
+            "Cannot use string as a condition"
+        ]
+    );
+}
+
+#[test]
+fn conditionals_become_c_ternaries() {
+    let c = osho_lang::compile_to_c(PROGRAM).unwrap();
+    assert!(c.contains("? 2.5 : 3.5;"), "{}", c);
+    assert!(c.contains("osho_index(xs, i)"), "{}", c);
+}
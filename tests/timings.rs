@@ -0,0 +1,56 @@
+use std::path::Path;
+use std::time::Duration;
+
+use osho_lang::timings::Timings;
+use osho_lang::Loader;
+
+#[test]
+fn phases_keep_their_order_and_add_up() {
+    let mut timings = Timings::new();
+    timings.add("lex", Duration::from_millis(2));
+    timings.add("parse", Duration::from_millis(3));
+    timings.add("lex", Duration::from_millis(1));
+    assert_eq!(
+        timings.phases(),
+        [
+            ("lex", Duration::from_millis(3)),
+            ("parse", Duration::from_millis(3))
+        ]
+    );
+    assert_eq!(timings.total(), Duration::from_millis(6));
+
+    let value = timings.time("analyze", || 42);
+    assert_eq!(value, 42);
+    assert_eq!(timings.phases()[2].0, "analyze");
+}
+
+#[test]
+fn the_table_ends_with_the_total() {
+    let mut timings = Timings::new();
+    timings.add("lex", Duration::from_micros(1500));
+    timings.add("generate", Duration::from_micros(250));
+    assert_eq!(
+        timings.to_string(),
+        "lex              1.500 ms\n\
+         generate         0.250 ms\n\
+         total            1.750 ms\n"
+    );
+}
+
+#[test]
+fn loading_times_lexing_and_parsing() {
+    let mut loader = Loader::new();
+    loader
+        .load(
+            Path::new("main.osho"),
+            "import std.math\nprintln(gcd(4, 6))\n",
+        )
+        .unwrap();
+    let phases: Vec<_> = loader
+        .timings()
+        .phases()
+        .iter()
+        .map(|(phase, _)| *phase)
+        .collect();
+    assert_eq!(phases, ["lex", "parse"]);
+}
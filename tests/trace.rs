@@ -0,0 +1,67 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use osho_lang::trace::Tracer;
+use osho_lang::{Interpreter, Loader};
+
+/// Output shared with the test after the interpreter takes the tracer
+#[derive(Clone, Default)]
+struct Shared(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Run a program with the tracer, returning its log
+fn trace(source: &str) -> String {
+    let mut loader = Loader::new();
+    let ast = loader.load(Path::new("main.osho"), source).unwrap();
+    let log = Shared::default();
+    let tracer = Tracer::new(loader.sources().clone(), log.clone());
+    Interpreter::new()
+        .with_hook(tracer)
+        .run(&ast, ast.root())
+        .unwrap();
+    let log = log.0.borrow();
+    String::from_utf8(log.clone()).unwrap()
+}
+
+#[test]
+fn statements_are_logged_with_what_they_read_and_write() {
+    let log = trace("let mut x = 1\nlet y = x + x * 2\nx += y\n");
+    assert_eq!(
+        log,
+        "[main.osho:1] let mut x = 1\n\
+         [main.osho:1] wrote x = 1\n\
+         [main.osho:2] let y = x + x * 2    read x = 1\n\
+         [main.osho:2] wrote y = 3\n\
+         [main.osho:3] x += y    read x = 1, y = 3\n\
+         [main.osho:3] wrote x = 4\n"
+    );
+}
+
+#[test]
+fn statements_in_calls_are_indented() {
+    let log = trace("fn double(n: int) -> int {\n    return n * 2\n}\nlet a = double(4)\n");
+    assert!(log.contains(
+        "[main.osho:4] let a = double(4)\n\
+         [main.osho:2]   return n * 2    read n = 4\n\
+         [main.osho:4] wrote a = 8\n"
+    ));
+}
+
+#[test]
+fn element_assignments_write_the_whole_variable() {
+    let log = trace("let mut xs = [1, 2]\nlet i = 1\nxs[i] = 5\nif xs[1] > 2 {\n}\n");
+    assert!(log.contains("[main.osho:3] xs[i] = 5    read xs = [1, 2], i = 1\n"));
+    assert!(log.contains("[main.osho:3] wrote xs = [1, 5]\n"));
+    assert!(log.contains("[main.osho:4] if xs[1] > 2 {    read xs = [1, 5]\n"));
+}
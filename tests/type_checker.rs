@@ -0,0 +1,91 @@
+use osho_lang::parser::ASTNode;
+use osho_lang::semantic_analyzer::Type;
+use osho_lang::{Lexer, Parser, SemanticAnalyzer};
+
+#[test]
+fn mismatched_operands_are_reported_with_their_spans() {
+    let source = "\
+let s = \"a\" + true
+let d = 1.5 - \"b\"
+let mut x = 1
+x *= false
+println(2 ** true)";
+    let errors = osho_lang::parse_and_check(source).unwrap_err();
+    let found: Vec<_> = errors
+        .iter()
+        .map(|err| {
+            let span = err.span().unwrap();
+            (err.message(), span.line, span.column)
+        })
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("Cannot add string and bool", 1, 13),
+            ("Cannot subtract string from float", 2, 13),
+            ("Cannot multiply int and bool", 4, 1),
+            ("Cannot raise int to the power of bool", 5, 11),
+        ]
+    );
+}
+
+#[test]
+fn a_failed_let_is_only_reported_once() {
+    let source = "\
+let x = 1 + true
+println(x + 1)
+let y = x * 2
+x = 3
+println(y, z)";
+    let errors = osho_lang::parse_and_check(source).unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|err| err.message()).collect();
+    assert_eq!(messages, ["Cannot add int and bool"]);
+
+    // Other errors are still reported
+    let errors =
+        osho_lang::parse_and_check("let x = 1 + true\nprintln(x)\nprintln(z)").unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|err| err.message()).collect();
+    assert_eq!(
+        messages,
+        ["Cannot add int and bool", "Variable 'z' is not declared"]
+    );
+}
+
+#[test]
+fn checked_expressions_are_annotated_with_their_types() {
+    let source = "\
+let mut n = 2
+let half = n / 2
+let ratio = n * 0.5
+let big = ratio > 1 || n == 2";
+    let tokens = Lexer::new(source).get_tokens().unwrap();
+    let ast = Parser::new(&tokens).parse().unwrap();
+    let mut analyzer = SemanticAnalyzer::new();
+    analyzer.analyze(&ast, ast.root()).unwrap();
+    let types: Vec<_> = ast.statements()[1..]
+        .iter()
+        .map(|&statement| match &ast[statement] {
+            ASTNode::LetDeclaration { value, .. } => analyzer.type_of(&ast[*value]).cloned(),
+            other => panic!("expected a let, found {:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        types,
+        [Some(Type::Int), Some(Type::Float), Some(Type::Bool)]
+    );
+}
+
+#[test]
+fn c_types_and_formats_follow_the_annotations() {
+    let c = osho_lang::compile_to_c(
+        "\
+let mut n = 3
+n++
+println(n * 0.5, n / 2, n > 2)",
+    )
+    .unwrap();
+    assert!(c.contains("double _t1 = _t0 * 0.5;"), "{}", c);
+    assert!(c.contains("long _t2 = n / 2;"), "{}", c);
+    assert!(c.contains("bool _t3 = n > 2;"), "{}", c);
+    assert!(c.contains("printf(\"%s %ld %s\\n\", osho_float("), "{}", c);
+}
@@ -0,0 +1,86 @@
+use osho_lang::interpreter::Value;
+use osho_lang::{Checks, Repl};
+
+mod common;
+
+const PROGRAM: &str = "\
+struct P { x, y }
+fn half(n: int) -> float {
+    return n / 2.0
+}
+fn noisy() -> int {
+    println(\"evaluated\")
+    return 1
+}
+let empty: [[string]] = []
+let word = \"abc\"
+println(typeof(1), typeof(2.5), typeof(true), typeof(word), typeof(word[0]))
+println(typeof([1, 2]), typeof(empty), typeof(empty[0:0]), typeof(P { x: 1, y: [2.5] }))
+println(typeof(noisy()), typeof(half(3)), typeof(len(word)), len(typeof(empty)))";
+
+const OUTPUT: &str = "\
+int float bool string char
+[int] [[string]] [[string]] P
+int float int 10
+";
+
+#[test]
+fn typeof_names_the_checked_type_without_evaluating() {
+    for result in [osho_lang::interpret(PROGRAM), osho_lang::run_vm(PROGRAM)] {
+        assert_eq!(result.unwrap().stdout, OUTPUT);
+    }
+    let output = osho_lang::interpret(
+        "fn half(n: int) -> float {\n    return n / 2.0\n}\nlet m = {\"a\": 1.5}\nlet f = half\nprintln(typeof(m), typeof(f))",
+    )
+    .unwrap();
+    assert_eq!(output.stdout, "{string: float} fn(int) -> float\n");
+}
+
+#[test]
+fn compiled_typeof_matches_the_interpreter() {
+    if let Some(compiled) = common::run_both("typeof", PROGRAM, Checks::default()) {
+        assert_eq!(compiled.stdout, OUTPUT);
+    }
+
+    let c = osho_lang::compile_to_c("let a = [1.5]\nprintln(typeof(a))").unwrap();
+    assert!(c.contains("\"[float]\""), "{}", c);
+    let js =
+        osho_lang::compile_to_js("let a = [[1]]\nprintln(typeof(a), typeof(a[0][0]))").unwrap();
+    assert!(js.contains("console.log(\"[[int]]\", \"int\");"), "{}", js);
+}
+
+#[test]
+fn the_repl_names_values_by_their_kind() {
+    let mut repl = Repl::new(Vec::new());
+    repl.eval("let a = [1, 2]").unwrap();
+    assert_eq!(
+        repl.eval("typeof(a)").unwrap(),
+        Some(Value::Str("array".to_string()))
+    );
+    assert_eq!(
+        repl.eval("typeof(len(a))").unwrap(),
+        Some(Value::Str("int".to_string()))
+    );
+}
+
+#[test]
+fn typeof_is_checked() {
+    let cases = [
+        (
+            "println(typeof())",
+            "typeof() takes 1 argument but 0 were given",
+        ),
+        (
+            "println(typeof(1, 2))",
+            "typeof() takes 1 argument but 2 were given",
+        ),
+        (
+            "let n: int = typeof(1)",
+            "Variable 'n' is int, found string",
+        ),
+    ];
+    for (source, message) in cases {
+        let errors = osho_lang::parse_and_check(source).unwrap_err();
+        assert_eq!(errors[0].message(), message, "{}", source);
+    }
+}
@@ -0,0 +1,71 @@
+const PROGRAM: &str = "\
+let mut a = [1.5]
+pop(a)
+push(a, 2)
+let mut i = 0
+while (i < 3) {
+    push(a, i)
+    i += 1
+}
+let last = pop(a)
+println(a, len(a), last)";
+
+const OUTPUT: &str = "[2, 0, 1] 3 2\n";
+
+#[test]
+fn arrays_grow_and_shrink() {
+    assert_eq!(osho_lang::interpret(PROGRAM).unwrap().stdout, OUTPUT);
+    assert_eq!(osho_lang::run_vm(PROGRAM).unwrap().stdout, OUTPUT);
+
+    let source = "let mut a = [1]\npop(a)\npop(a)";
+    for err in [
+        osho_lang::interpret(source).unwrap_err(),
+        osho_lang::run_vm(source).unwrap_err(),
+    ] {
+        assert_eq!(err.message(), "Cannot pop from an empty array");
+        assert_eq!(err.span().unwrap().line, 3);
+    }
+}
+
+#[test]
+fn vector_runtime_is_only_emitted_when_used() {
+    let c = osho_lang::compile_to_c("let a = [1, 2]\nprintln(a[0])").unwrap();
+    assert!(!c.contains("osho_array_grow"), "{}", c);
+
+    let c = osho_lang::compile_to_c(PROGRAM).unwrap();
+    assert!(c.contains("realloc(array->data"), "{}", c);
+    assert!(
+        c.contains("osho_array_grow(a, sizeof(*((double *)a->data)));"),
+        "{}",
+        c
+    );
+    assert!(
+        c.contains("((double *)a->data)[osho_array_pop(a)]"),
+        "{}",
+        c
+    );
+}
+
+#[test]
+fn push_and_pop_are_type_checked() {
+    let source = "\
+let mut a = [1]
+push(a, true)
+let b = [1]
+push(b, 2)
+push([1], 2)
+let c = push(a, 2)
+pop(a, 1)";
+    let errors = osho_lang::parse_and_check(source).unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|err| err.message()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Cannot push bool onto [int]",
+            "Cannot assign to immutable variable 'b', declare it with `let mut b`",
+            "push() needs a variable to change",
+            "push() doesn't return a value",
+            "pop() takes 1 argument but 2 were given"
+        ]
+    );
+}
@@ -0,0 +1,96 @@
+use std::fs;
+
+use osho_lang::interpreter::DEFAULT_MAX_CALL_DEPTH;
+use osho_lang::verify::{self, Run};
+use osho_lang::Checks;
+
+mod common;
+
+/// Run a program both ways and compare them
+fn check(name: &str, source: &str, input: &str) -> Result<(), String> {
+    let Some(cc) = common::c_compiler() else {
+        return Ok(());
+    };
+    let ast = osho_lang::parse_and_check(source).expect("program should compile");
+    let interpreted = verify::interpret(
+        &ast,
+        input.as_bytes(),
+        DEFAULT_MAX_CALL_DEPTH,
+        Checks::default(),
+    );
+    let dir = std::env::temp_dir().join(format!("osho-verify-{}-{}", std::process::id(), name));
+    let compiled = verify::run_compiled(&ast, &cc, Checks::default(), &dir, input.as_bytes());
+    fs::remove_dir_all(&dir).unwrap();
+    verify::compare(&interpreted, &compiled.unwrap())
+}
+
+#[test]
+fn examples_print_the_same_interpreted_and_compiled() {
+    let mut checked = 0;
+    for entry in fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let source = fs::read_to_string(&path).unwrap();
+        check(&name, &source, "").unwrap_or_else(|err| panic!("{}: {}", name, err));
+        checked += 1;
+    }
+    assert!(checked > 0, "no examples found");
+}
+
+#[test]
+fn floats_and_input_agree() {
+    let source = "\
+let mut x: float = 0.0
+input x
+let values = [x, x / 3.0, 0.1 + 0.2, 1024.0, -0.0, 0.0000001 / 7.0, 1000000000000000000000.0 * 10.0]
+println(values)
+println(\"${x * 2.0} ${x > 1.0}\")
+";
+    check("floats", source, "2.5\n").unwrap();
+}
+
+#[test]
+fn differences_in_output_or_failure_are_reported() {
+    let run = |stdout: &str, error: Option<&str>| Run {
+        stdout: stdout.to_string(),
+        error: error.map(str::to_string),
+        exit_code: if error.is_some() { 70 } else { 0 },
+    };
+    assert_eq!(
+        verify::compare(&run("1\n2.5\n", None), &run("1\n2.500000\n", None)),
+        Err("Outputs differ at line 2:\n  interpreter: 2.5\n  compiled:    2.500000".to_string())
+    );
+    assert_eq!(
+        verify::compare(&run("1\n", None), &run("1\n", Some("Segmentation fault"))),
+        Err(
+            "The compiled program failed but the interpreter didn't: Segmentation fault"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        verify::compare(
+            &run("1\n", Some("Index 3 is out of bounds")),
+            &run(
+                "1\n",
+                Some("Index 3 is out of bounds for an array of length 1")
+            )
+        ),
+        Ok(())
+    );
+    let exited = |code| Run {
+        exit_code: code,
+        ..run("1\n", None)
+    };
+    assert_eq!(verify::compare(&exited(3), &exited(3)), Ok(()));
+    assert_eq!(
+        verify::compare(&exited(3), &exited(4)),
+        Err("The interpreter exited with code 3 but the compiled program with code 4".to_string())
+    );
+    assert_eq!(
+        verify::compare(&exited(0), &exited(4)),
+        Err(
+            "The compiled program failed but the interpreter didn't: exited with code 4"
+                .to_string()
+        )
+    );
+}
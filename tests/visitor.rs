@@ -0,0 +1,60 @@
+use osho_lang::ast_printer::to_source;
+use osho_lang::parser::{ASTNode, Ast, NodeId};
+use osho_lang::visitor::{MutVisitor, Visitor};
+use osho_lang::{Lexer, Parser};
+
+const PROGRAM: &str = "\
+fn f(n: int) -> int {
+    return n * 2
+}
+let mut x = 1
+for i in 0..3 {
+    x = x + f(i)
+}
+do {
+    x -= 1
+} while x > a ? b : c
+println(\"${x}\", [y, z][0])";
+
+struct Names(Vec<String>);
+
+impl Visitor for Names {
+    fn visit(&mut self, ast: &Ast, node: NodeId) {
+        if let ASTNode::Identifier { name, .. } = &ast[node] {
+            self.0.push(name.clone());
+        }
+        self.walk(ast, node);
+    }
+}
+
+struct Rename;
+
+impl MutVisitor for Rename {
+    fn visit_mut(&mut self, ast: &mut Ast, node: NodeId) {
+        if let ASTNode::Identifier { name, .. } = &mut ast[node] {
+            name.make_ascii_uppercase();
+        }
+        self.walk_mut(ast, node);
+    }
+}
+
+#[test]
+fn visitors_walk_every_node_in_source_order() {
+    let ast = Parser::from_lexer(Lexer::new(PROGRAM)).parse().unwrap();
+    let mut names = Names(Vec::new());
+    names.visit(&ast, ast.root());
+    assert_eq!(names.0, ["n", "x", "i", "x", "a", "b", "c", "x", "y", "z"]);
+}
+
+#[test]
+fn mut_visitors_rewrite_nodes_in_place() {
+    let mut ast = Parser::from_lexer(Lexer::new("let a = [b, c ? d : e]\nprintln(a[0])"))
+        .parse()
+        .unwrap();
+    let root = ast.root();
+    Rename.visit_mut(&mut ast, root);
+    assert_eq!(
+        to_source(&ast, ast.root()),
+        "let a = [B, C ? D : E]\nprintln(A[0])\n"
+    );
+}
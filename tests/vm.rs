@@ -0,0 +1,36 @@
+use std::fs;
+
+/// Every example should print the same with the VM as with the interpreter
+#[test]
+fn vm_matches_interpreter_on_examples() {
+    let mut checked = 0;
+    for entry in fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("osho") {
+            continue;
+        }
+        let source = fs::read_to_string(&path).unwrap();
+        let interpreted = osho_lang::interpret(&source).map(|output| output.stdout);
+        let vm = osho_lang::run_vm(&source).map(|output| output.stdout);
+        assert_eq!(vm, interpreted, "{}", path.display());
+        checked += 1;
+    }
+    assert!(checked > 0, "no examples found");
+}
+
+#[test]
+fn vm_reports_runtime_errors_at_the_operator() {
+    let err = osho_lang::run_vm("let x = 0\nprintln(10 % x)").unwrap_err();
+    assert_eq!(err.message(), "Modulo by zero");
+    let span = err.span().unwrap();
+    assert_eq!((span.line, span.column), (2, 12));
+}
+
+#[test]
+fn vm_short_circuits_logical_operators() {
+    let output = osho_lang::run_vm(
+        "let x = 0\nprintln(x != 0 && 10 / x > 1)\nprintln(x == 0 || 10 / x > 1)",
+    )
+    .unwrap();
+    assert_eq!(output.stdout, "false\ntrue\n");
+}
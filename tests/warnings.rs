@@ -0,0 +1,54 @@
+use osho_lang::{Lint, LintLevels, SemanticAnalyzer, Severity};
+
+/// Messages of the warnings reported for a program with the given lint levels
+fn warnings(source: &str, levels: &LintLevels) -> Vec<String> {
+    let ast = osho_lang::parse_and_check(source).unwrap();
+    let mut analyzer = SemanticAnalyzer::new();
+    analyzer.analyze_all(&ast).unwrap();
+    levels
+        .apply(analyzer.take_warnings())
+        .into_iter()
+        .map(|warning| warning.message)
+        .collect()
+}
+
+#[test]
+fn warns_about_unused_variables_and_zero_divisors() {
+    let source = "let mut x = 1\nlet mut y = 2\ny += 1\nprintln(x / 0)\nx /= 0\n{\nlet z = 1\n}";
+    assert_eq!(
+        warnings(source, &LintLevels::default()),
+        [
+            "Unused variable 'y'",
+            "Division by zero",
+            "Division by zero",
+            "Unused variable 'z'"
+        ]
+    );
+}
+
+#[test]
+fn shadowing_is_only_reported_when_enabled() {
+    let source = "let x = 1\n{\nlet x = 2\nprintln(x)\n}\nprintln(x)";
+    let mut levels = LintLevels::default();
+    assert!(warnings(source, &levels).is_empty());
+    levels.warn(Lint::Shadowing);
+    assert_eq!(
+        warnings(source, &levels),
+        ["Variable 'x' shadows an outer variable"]
+    );
+}
+
+#[test]
+fn allowed_lints_are_dropped_and_denied_ones_are_errors() {
+    let mut levels = LintLevels::default();
+    levels.allow(Lint::UnusedVariables);
+    levels.deny_warnings = true;
+
+    let ast = osho_lang::parse_and_check("let x = 1\nprintln(2.5 / 0.0)").unwrap();
+    let mut analyzer = SemanticAnalyzer::new();
+    analyzer.analyze_all(&ast).unwrap();
+    let reported = levels.apply(analyzer.take_warnings());
+    assert_eq!(reported.len(), 1);
+    assert_eq!(reported[0].code, "W0003");
+    assert_eq!(reported[0].severity, Severity::Error);
+}
@@ -0,0 +1,90 @@
+use std::fs;
+
+use wasmi::{Caller, Engine, Linker, Module, Store};
+
+/// Compile a program to WebAssembly and run its `main` with wasmi, capturing
+/// what it prints
+fn run_wasm(source: &str) -> String {
+    let wat = osho_lang::compile_to_wasm(source).unwrap();
+    let engine = Engine::default();
+    let module = Module::new(&engine, &wat).unwrap_or_else(|err| panic!("{}\n{}", err, wat));
+    let mut store = Store::new(&engine, String::new());
+    let mut linker = <Linker<String>>::new(&engine);
+    linker
+        .func_wrap(
+            "env",
+            "print_i64",
+            |mut caller: Caller<'_, String>, n: i64| {
+                caller.data_mut().push_str(&format!("{}\n", n));
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "env",
+            "print_f64",
+            |mut caller: Caller<'_, String>, n: f64| {
+                caller.data_mut().push_str(&format!("{}\n", n));
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "env",
+            "print_bool",
+            |mut caller: Caller<'_, String>, b: i32| {
+                caller.data_mut().push_str(&format!("{}\n", b != 0));
+            },
+        )
+        .unwrap()
+        .func_wrap("env", "pow", |_: Caller<'_, String>, x: f64, y: f64| {
+            x.powf(y)
+        })
+        .unwrap()
+        .func_wrap("env", "fmod", |_: Caller<'_, String>, x: f64, y: f64| x % y)
+        .unwrap();
+    let instance = linker.instantiate_and_start(&mut store, &module).unwrap();
+    instance
+        .get_typed_func::<(), ()>(&store, "main")
+        .unwrap()
+        .call(&mut store, ())
+        .unwrap();
+    store.into_data()
+}
+
+/// Every example should print the same under WebAssembly as with the interpreter
+#[test]
+fn wasm_matches_interpreter_on_examples() {
+    let mut checked = 0;
+    for entry in fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("osho") {
+            continue;
+        }
+        let source = fs::read_to_string(&path).unwrap();
+        let interpreted = osho_lang::interpret(&source).unwrap().stdout;
+        assert_eq!(run_wasm(&source), interpreted, "{}", path.display());
+        checked += 1;
+    }
+    assert!(checked > 0, "no examples found");
+}
+
+#[test]
+fn wasm_gives_shadowed_variables_their_own_locals() {
+    let output = run_wasm("let x = 1\n{\n let x = 2.5\n println(x)\n}\nprintln(x)");
+    assert_eq!(output, "2.5\n1\n");
+}
+
+#[test]
+fn wasm_runs_chained_assignments() {
+    let output = run_wasm(
+        "let mut a = 0.5\nlet mut b = 1\nfor (let mut i = 0; i < 3; i = b = i + 1) {}\na = b = 4\nprintln(a)\nprintln(b)",
+    );
+    assert_eq!(output, "4\n4\n");
+}
+
+#[test]
+fn wasm_only_imports_math_functions_when_used() {
+    let wat = osho_lang::compile_to_wasm("println(7 % 2)").unwrap();
+    assert!(!wat.contains("fmod") && !wat.contains("pow"));
+    let wat = osho_lang::compile_to_wasm("println(2 ** 3)").unwrap();
+    assert!(wat.contains("(import \"env\" \"pow\""));
+}
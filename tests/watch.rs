@@ -0,0 +1,61 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use osho_lang::watch::{program_files, Watcher};
+
+fn temp_file(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("osho-watch-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    dir.join("main.osho")
+}
+
+/// Mark `file` as changed at a time of its own, however coarse the file
+/// system's clock is
+fn touch(file: &Path, seconds: u64) {
+    File::options()
+        .write(true)
+        .open(file)
+        .unwrap()
+        .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+        .unwrap();
+}
+
+#[test]
+fn imported_files_are_watched_too() {
+    let main = Path::new("tests/fixtures/imports/main.osho");
+    let files = program_files(main);
+    assert_eq!(files[0], main);
+    assert!(files.contains(&PathBuf::from("tests/fixtures/imports/lib/math.osho")));
+    assert!(files.contains(&PathBuf::from("tests/fixtures/imports/lib/shapes.osho")));
+    assert_eq!(files.len(), 3, "{:?}", files);
+}
+
+#[test]
+fn a_change_is_seen_once() {
+    let file = temp_file("change");
+    fs::write(&file, "println(1)").unwrap();
+    touch(&file, 1_000);
+    let mut watcher = Watcher::new(vec![file.clone()]);
+    assert_eq!(watcher.changed(), None);
+
+    touch(&file, 2_000);
+    assert_eq!(watcher.changed(), Some(file.as_path()));
+    assert_eq!(watcher.changed(), None);
+    fs::remove_dir_all(file.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn deleting_and_creating_a_file_are_changes() {
+    let file = temp_file("delete");
+    fs::write(&file, "println(1)").unwrap();
+    let mut watcher = Watcher::new(vec![file.clone()]);
+
+    fs::remove_file(&file).unwrap();
+    assert_eq!(watcher.changed(), Some(file.as_path()));
+    assert_eq!(watcher.changed(), None);
+
+    fs::write(&file, "println(2)").unwrap();
+    assert_eq!(watcher.changed(), Some(file.as_path()));
+    fs::remove_dir_all(file.parent().unwrap()).unwrap();
+}